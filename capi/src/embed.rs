@@ -0,0 +1,331 @@
+use std::fmt;
+
+use front::ParseError;
+use vm::{error::InterpretError, Bytecode, IntoYex, Status, Value, VirtualMachine, VmOptions};
+
+/// An error raised by the code that the host ran
+#[derive(Debug, Clone)]
+pub enum Error {
+    /// The source couldn't be parsed
+    Syntax(ParseError),
+    /// The code raised an error while it ran
+    Runtime(InterpretError),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Syntax(e) => write!(f, "{}", e),
+            Self::Runtime(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Self::Syntax(e)
+    }
+}
+
+impl From<InterpretError> for Error {
+    fn from(e: InterpretError) -> Self {
+        Self::Runtime(e)
+    }
+}
+
+/// The result of running yex code
+pub type Result<T> = std::result::Result<T, Error>;
+
+/// An interpreter, with the whole prelude loaded
+///
+/// The globals defined by the code it runs are kept, so a program can define functions with
+/// [`Yex::exec`] and the host can call them later:
+///
+/// ```
+/// let mut yex = yex::Yex::new();
+/// yex.exec("def double x = x * 2").unwrap();
+/// yex.set_global("answer", 21i64);
+///
+/// assert_eq!(yex.eval("double answer").unwrap(), yex::Value::Int(42));
+/// assert_eq!(yex.call("double", &[4i64.into()]).unwrap(), yex::Value::Int(8));
+/// ```
+pub struct Yex {
+    vm: VirtualMachine,
+}
+
+impl Yex {
+    /// Creates an interpreter
+    pub fn new() -> Self {
+        Self {
+            vm: VirtualMachine::default(),
+        }
+    }
+
+    /// Creates an interpreter with the capabilities and the limits of `options`, to run untrusted
+    /// code
+    ///
+    /// ```
+    /// use yex::{Error, VmOptions, Yex};
+    ///
+    /// let mut yex = Yex::with_options(VmOptions::sandbox().max_instructions(1000));
+    /// yex.exec("def spin x = spin x").unwrap();
+    ///
+    /// assert!(matches!(yex.call("spin", &[1i64.into()]), Err(Error::Runtime(_))));
+    /// ```
+    pub fn with_options(options: VmOptions) -> Self {
+        Self {
+            vm: VirtualMachine::builder().options(options).build(),
+        }
+    }
+
+    fn run(&mut self, bytecode: Bytecode, constants: Vec<Value>) -> Result<Value> {
+        let bytecode = self.vm.load(bytecode, constants);
+        let res = self.vm.run(&bytecode).map(|_| self.vm.pop_last().clone());
+        self.vm.reset();
+
+        Ok(res?)
+    }
+
+    /// Runs a program, a list of `def`, `let`, `type` and the other statements of a file
+    pub fn exec(&mut self, source: &str) -> Result<()> {
+        let (bytecode, constants) = front::parse(source)?;
+        self.run(bytecode, constants).map(|_| ())
+    }
+
+    /// Evaluates an expression, returning its value
+    pub fn eval(&mut self, source: &str) -> Result<Value> {
+        let (bytecode, constants) = front::parse_expr(source)?;
+        self.run(bytecode, constants)
+    }
+
+    /// Runs a program like [`Yex::exec`], until it calls `yield` or finishes, so the host can do
+    /// its own work meanwhile
+    ///
+    /// ```
+    /// use yex::{IntoYex, Status, Value, Yex};
+    ///
+    /// let mut yex = Yex::new();
+    /// let status = yex.start("let total = 1 + yield \"ready\"").unwrap();
+    /// assert_eq!(status, Status::Yielded("ready".into_yex()));
+    ///
+    /// assert_eq!(yex.resume(41i64.into()).unwrap(), Status::Done);
+    /// assert_eq!(yex.get_global("total"), Some(Value::Int(42)));
+    /// ```
+    pub fn start(&mut self, source: &str) -> Result<Status> {
+        let (bytecode, constants) = front::parse(source)?;
+        let bytecode = self.vm.load(bytecode, constants);
+        let status = self.vm.run_until_yield(bytecode);
+        self.stopped(status)
+    }
+
+    /// Continues the program that yielded, `value` is the result of its call to `yield`
+    pub fn resume(&mut self, value: Value) -> Result<Status> {
+        let status = self.vm.resume(value);
+        self.stopped(status)
+    }
+
+    fn stopped(&mut self, status: std::result::Result<Status, InterpretError>) -> Result<Status> {
+        // the stack of a suspended program is kept until it finishes
+        if !matches!(status, Ok(Status::Yielded(_))) {
+            self.vm.reset();
+        }
+        Ok(status?)
+    }
+
+    /// Calls the global function `name` with the arguments
+    pub fn call(&mut self, name: &str, args: &[Value]) -> Result<Value> {
+        Ok(self.vm.call_global(name, args)?)
+    }
+
+    /// Sets a global variable, which the code can use like the ones it defines
+    pub fn set_global<T: IntoYex>(&mut self, name: &str, value: T) {
+        self.vm.set_global(name, value.into_yex());
+    }
+
+    /// Registers a rust closure as a global function, see [`VirtualMachine::register_native`]
+    pub fn register_native<F>(&mut self, name: &str, arity: usize, fun: F)
+    where
+        F: Fn(&mut VirtualMachine, &[Value]) -> std::result::Result<Value, InterpretError>
+            + 'static,
+    {
+        self.vm.register_native(name, arity, fun);
+    }
+
+    /// Returns the value of a global variable, if it's defined
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.vm.get_global(name)
+    }
+
+    /// Gives the code a budget of instructions, shared by everything it runs until the host adds
+    /// more, see [`VirtualMachine::set_fuel`]
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        self.vm.set_fuel(fuel);
+    }
+
+    /// Adds `n` instructions to the budget of the code
+    pub fn add_fuel(&mut self, n: u64) {
+        self.vm.add_fuel(n);
+    }
+
+    /// Returns the instructions left in the budget, or `None` if there's no budget
+    pub fn fuel(&self) -> Option<u64> {
+        self.vm.fuel()
+    }
+}
+
+impl Default for Yex {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[test]
+fn errors() {
+    let mut yex = Yex::new();
+
+    assert!(matches!(yex.eval("1 +"), Err(Error::Syntax(_))));
+    yex.exec("def fail x = raise :NopeError \"nope\"").unwrap();
+    match yex.call("fail", &[Value::Nil]) {
+        Err(Error::Runtime(e)) => assert_eq!(e.err.as_str(), "NopeError"),
+        other => panic!("expected a runtime error, found {:?}", other),
+    }
+
+    // the vm is still usable after the errors
+    assert_eq!(yex.eval("1 + 1").unwrap(), Value::Int(2));
+    assert_eq!(yex.get_global("missing"), None);
+}
+
+#[test]
+fn derives() {
+    use vm::{FromYex, IntoYex};
+
+    #[derive(IntoYex, FromYex, Debug, PartialEq)]
+    struct Point {
+        x: i64,
+        y: i64,
+    }
+
+    #[derive(IntoYex, FromYex, Debug, PartialEq)]
+    enum Shape {
+        Square,
+        Circle,
+    }
+
+    let mut yex = Yex::new();
+    yex.exec("struct Point = x y\ndef swap p = %{x: Dict.get :y p, y: Dict.get :x p}")
+        .unwrap();
+
+    let swapped = yex
+        .call("swap", &[Point { x: 1, y: 2 }.into_yex()])
+        .unwrap();
+    assert_eq!(Point::from_yex(&swapped).unwrap(), Point { x: 2, y: 1 });
+
+    // the yex structs with the same fields convert too
+    let point = yex.eval("Point{x: 3, y: 4}").unwrap();
+    assert_eq!(Point::from_yex(&point).unwrap(), Point { x: 3, y: 4 });
+    assert!(Point::from_yex(&yex.eval("%{x: 1}").unwrap()).is_err());
+
+    let shape = yex.eval(":Circle").unwrap();
+    assert_eq!(Shape::from_yex(&shape).unwrap(), Shape::Circle);
+    assert_eq!(Shape::Square.into_yex(), yex.eval(":Square").unwrap());
+    assert!(Shape::from_yex(&yex.eval(":Triangle").unwrap()).is_err());
+}
+
+#[test]
+fn limits() {
+    let error = |options: VmOptions, source: &str| {
+        let mut yex = Yex::with_options(options);
+        yex.exec("def spin x = spin x\ndef deep n = if n == 0 then 0 else 1 + deep (n - 1)")
+            .unwrap();
+        match yex.eval(source) {
+            Err(Error::Runtime(e)) => e.err.as_str().to_string(),
+            other => panic!("expected a runtime error, found {:?}", other),
+        }
+    };
+
+    let options = VmOptions::sandbox().max_instructions(1000);
+    assert_eq!(error(options, "spin 1"), "LimitError");
+    // the limits can't be rescued
+    assert_eq!(error(options, "try spin 1 rescue _ 0"), "LimitError");
+
+    let options = VmOptions::new().max_call_depth(20);
+    assert_eq!(error(options, "deep 30"), "RecursionError");
+
+    let options = VmOptions::new().max_heap(4096);
+    assert_eq!(
+        error(options, "List.map (fn x = [x, x, x]) (0..1000)"),
+        "MemoryError"
+    );
+
+    // each run gets the whole budget again
+    let mut yex = Yex::with_options(
+        VmOptions::sandbox()
+            .max_instructions(1000)
+            .max_call_depth(20),
+    );
+    assert_eq!(yex.get_global("File"), None);
+    yex.exec("def deep n = if n == 0 then 0 else 1 + deep (n - 1)")
+        .unwrap();
+    for _ in 0..5 {
+        assert_eq!(yex.eval("deep 10").unwrap(), Value::Int(10));
+    }
+}
+
+#[test]
+fn fuel() {
+    let mut yex = Yex::new();
+    yex.exec("def spin x = spin x").unwrap();
+
+    yex.set_fuel(Some(500));
+    match yex.eval("try spin 1 rescue _ 0") {
+        Err(Error::Runtime(e)) => assert_eq!(e.err.as_str(), "TimeoutError"),
+        other => panic!("expected a timeout, found {:?}", other),
+    }
+    assert_eq!(yex.fuel(), Some(0));
+
+    // the fuel is kept between the runs
+    yex.add_fuel(100);
+    assert_eq!(yex.eval("1 + 1").unwrap(), Value::Int(2));
+    let left = yex.fuel().unwrap();
+    assert!(left < 100);
+    assert_eq!(yex.eval("1 + 1").unwrap(), Value::Int(2));
+    assert!(yex.fuel().unwrap() < left);
+
+    let mut yex = Yex::with_options(VmOptions::new().timeout(std::time::Duration::from_millis(20)));
+    yex.exec("def spin x = spin x").unwrap();
+    assert!(yex.eval("spin 1").is_err());
+    // the deadline is over with the run
+    assert_eq!(yex.eval("1 + 1").unwrap(), Value::Int(2));
+}
+
+#[test]
+fn yields() {
+    let mut yex = Yex::new();
+    yex.exec("def count n = if n >= 10 then n else count (n + yield n)")
+        .unwrap();
+
+    let mut status = yex.start("let result = count 0").unwrap();
+    let mut yielded = vec![];
+    while let Status::Yielded(value) = status {
+        yielded.push(value);
+        status = yex.resume(Value::Int(4)).unwrap();
+    }
+    assert_eq!(yielded, vec![Value::Int(0), Value::Int(4), Value::Int(8)]);
+    assert_eq!(yex.get_global("result"), Some(Value::Int(12)));
+
+    let error = |res: Result<Status>| match res {
+        Err(Error::Runtime(e)) => e.err.as_str().to_string(),
+        other => panic!("expected a runtime error, found {:?}", other),
+    };
+    assert_eq!(error(yex.resume(Value::Nil)), "YieldError");
+    assert!(yex.exec("let _ = yield 1").is_err());
+    assert_eq!(error(yex.start("let _ = List.map yield [1]")), "YieldError");
+
+    // the program can be interleaved with other code
+    yex.start("let sum = 1 + yield 1").unwrap();
+    assert_eq!(yex.eval("1 + 1").unwrap(), Value::Int(2));
+    assert_eq!(yex.resume(Value::Int(2)).unwrap(), Status::Done);
+    assert_eq!(yex.get_global("sum"), Some(Value::Int(3)));
+}
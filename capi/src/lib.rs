@@ -0,0 +1,356 @@
+#![deny(missing_docs)]
+//! Library for embedding the yex interpreter, rust applications use [`Yex`], which hides the
+//! compiler and the vm, and C applications use the functions declared in `yex.h`
+//!
+//! Every pointer returned by the C API is owned by the caller and must be released with the
+//! matching `*_free` function. Functions that can fail return `NULL` (or `-1`) and store the error
+//! message in the vm, which can be read with [`yex_last_error`].
+mod embed;
+
+use std::{
+    ffi::{c_char, c_int, CStr, CString},
+    ptr, slice,
+};
+
+use vm::literal::TryGet;
+
+pub use embed::{Error, Result, Yex};
+pub use front::ParseError;
+pub use vm::{error::InterpretError, Capabilities, FromYex, IntoYex, Status, Value, VmOptions};
+
+/// An interpreter instance
+pub struct YexVm {
+    yex: Yex,
+    error: Option<CString>,
+}
+
+/// A yex value owned by the host
+pub struct YexValue(Value);
+
+/// The type of a [`YexValue`], as returned by [`yex_value_type`]
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum YexType {
+    /// `nil`
+    Nil,
+    /// Numbers
+    Num,
+    /// Strings
+    Str,
+    /// Symbols
+    Sym,
+    /// Booleans
+    Bool,
+    /// Functions
+    Fn,
+    /// Lists
+    List,
+    /// Tuples
+    Tuple,
+    /// Types, tagged values and every other host value
+    Other,
+    /// Integers, placed last to keep the values of the other variants
+    Int,
+}
+
+impl YexVm {
+    fn fail<E: ToString>(&mut self, err: E) {
+        self.error = CString::new(err.to_string().replace('\0', "")).ok();
+    }
+
+    /// Keeps the error of a result, so it can be read with [`yex_last_error`]
+    fn check<T>(&mut self, res: Result<T>) -> Option<T> {
+        self.error = None;
+        res.map_err(|e| self.fail(e)).ok()
+    }
+}
+
+unsafe fn str_arg<'a>(vm: &mut YexVm, str: *const c_char) -> Option<&'a str> {
+    if str.is_null() {
+        vm.fail("Expected a string, found NULL");
+        return None;
+    }
+
+    CStr::from_ptr(str)
+        .to_str()
+        .map_err(|_| vm.fail("Invalid utf-8 string"))
+        .ok()
+}
+
+fn new_value(value: Value) -> *mut YexValue {
+    Box::into_raw(Box::new(YexValue(value)))
+}
+
+fn new_string(str: String) -> *mut c_char {
+    CString::new(str.replace('\0', ""))
+        .map(CString::into_raw)
+        .unwrap_or(ptr::null_mut())
+}
+
+/// Creates a new vm with the whole prelude loaded
+#[no_mangle]
+pub extern "C" fn yex_vm_new() -> *mut YexVm {
+    Box::into_raw(Box::new(YexVm {
+        yex: Yex::new(),
+        error: None,
+    }))
+}
+
+/// Releases a vm created by [`yex_vm_new`]
+///
+/// # Safety
+/// `vm` must be a pointer returned by [`yex_vm_new`] or `NULL`
+#[no_mangle]
+pub unsafe extern "C" fn yex_vm_free(vm: *mut YexVm) {
+    if !vm.is_null() {
+        drop(Box::from_raw(vm));
+    }
+}
+
+/// Returns the message of the last error raised on `vm`, or `NULL` if the last call succeeded.
+/// The string is owned by the vm and lives until the next call that uses it
+///
+/// # Safety
+/// `vm` must be a valid vm
+#[no_mangle]
+pub unsafe extern "C" fn yex_last_error(vm: *const YexVm) -> *const c_char {
+    (*vm).error.as_ref().map_or(ptr::null(), |e| e.as_ptr())
+}
+
+/// Runs a program (a list of `def`, `let` and `type` declarations), returns `0` on success and
+/// `-1` on error
+///
+/// # Safety
+/// `vm` must be a valid vm and `source` a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn yex_exec(vm: *mut YexVm, source: *const c_char) -> c_int {
+    let vm = &mut *vm;
+    let source = match str_arg(vm, source) {
+        Some(source) => source,
+        None => return -1,
+    };
+
+    let res = vm.yex.exec(source);
+    vm.check(res).map_or(-1, |_| 0)
+}
+
+/// Evaluates an expression, returns its value or `NULL` on error
+///
+/// # Safety
+/// `vm` must be a valid vm and `source` a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn yex_eval(vm: *mut YexVm, source: *const c_char) -> *mut YexValue {
+    let vm = &mut *vm;
+    let source = match str_arg(vm, source) {
+        Some(source) => source,
+        None => return ptr::null_mut(),
+    };
+
+    let res = vm.yex.eval(source);
+    vm.check(res).map_or(ptr::null_mut(), new_value)
+}
+
+/// Calls the global function `name` with `argc` arguments, returns the result or `NULL` on error
+///
+/// # Safety
+/// `vm` must be a valid vm, `name` a nul-terminated string and `argv` an array of `argc` valid
+/// values
+#[no_mangle]
+pub unsafe extern "C" fn yex_call(
+    vm: *mut YexVm,
+    name: *const c_char,
+    argv: *const *const YexValue,
+    argc: usize,
+) -> *mut YexValue {
+    let vm = &mut *vm;
+    let name = match str_arg(vm, name) {
+        Some(name) => name,
+        None => return ptr::null_mut(),
+    };
+
+    let args: Vec<Value> = match argc {
+        0 => vec![],
+        _ => slice::from_raw_parts(argv, argc)
+            .iter()
+            .map(|arg| (**arg).0.clone())
+            .collect(),
+    };
+
+    let res = vm.yex.call(name, &args);
+    vm.check(res).map_or(ptr::null_mut(), new_value)
+}
+
+/// Sets a global variable, the value is copied so it must still be released by the caller
+///
+/// # Safety
+/// `vm` must be a valid vm, `name` a nul-terminated string and `value` a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_set_global(
+    vm: *mut YexVm,
+    name: *const c_char,
+    value: *const YexValue,
+) -> c_int {
+    let vm = &mut *vm;
+    match str_arg(vm, name) {
+        Some(name) => {
+            vm.yex.set_global(name, (*value).0.clone());
+            0
+        }
+        None => -1,
+    }
+}
+
+/// Creates a `nil` value
+#[no_mangle]
+pub extern "C" fn yex_value_nil() -> *mut YexValue {
+    new_value(Value::Nil)
+}
+
+/// Creates a number
+#[no_mangle]
+pub extern "C" fn yex_value_num(n: f64) -> *mut YexValue {
+    new_value(Value::Num(n))
+}
+
+/// Creates an integer
+#[no_mangle]
+pub extern "C" fn yex_value_int(n: i64) -> *mut YexValue {
+    new_value(Value::Int(n))
+}
+
+/// Creates a boolean
+#[no_mangle]
+pub extern "C" fn yex_value_bool(b: bool) -> *mut YexValue {
+    new_value(Value::Bool(b))
+}
+
+/// Creates a string, returns `NULL` if `str` isn't valid utf-8
+///
+/// # Safety
+/// `str` must be a nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_str(str: *const c_char) -> *mut YexValue {
+    match CStr::from_ptr(str).to_str() {
+        Ok(str) => new_value(str.to_string().into()),
+        Err(_) => ptr::null_mut(),
+    }
+}
+
+/// Releases a value
+///
+/// # Safety
+/// `value` must be a value returned by this library or `NULL`
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_free(value: *mut YexValue) {
+    if !value.is_null() {
+        drop(Box::from_raw(value));
+    }
+}
+
+/// Returns the type of a value
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_type(value: *const YexValue) -> YexType {
+    match &(*value).0 {
+        Value::Nil => YexType::Nil,
+        Value::Num(_) => YexType::Num,
+        Value::Int(_) => YexType::Int,
+        Value::Str(_) => YexType::Str,
+        Value::Sym(_) => YexType::Sym,
+        Value::Bool(_) => YexType::Bool,
+        Value::Fn(_) => YexType::Fn,
+        Value::List(_) => YexType::List,
+        Value::Tuple(_) => YexType::Tuple,
+        _ => YexType::Other,
+    }
+}
+
+/// Returns the number stored in `value`, or `0` if it isn't a number
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_as_num(value: *const YexValue) -> f64 {
+    (*value).0.get().unwrap_or(0.0)
+}
+
+/// Returns the integer stored in `value`, or `0` if it isn't an integer or a round float
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_as_int(value: *const YexValue) -> i64 {
+    (*value).0.get().unwrap_or(0)
+}
+
+/// Returns the truthiness of `value`
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_as_bool(value: *const YexValue) -> bool {
+    (*value).0.to_bool()
+}
+
+/// Returns the contents of a string or the name of a symbol, or `NULL` for other values. The
+/// result must be released with [`yex_string_free`]
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_as_str(value: *const YexValue) -> *mut c_char {
+    match &(*value).0 {
+        Value::Str(s) => new_string(s.to_string()),
+        Value::Sym(s) => new_string(s.as_str().to_string()),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Formats any value the same way `println` does. The result must be released with
+/// [`yex_string_free`]
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_to_string(value: *const YexValue) -> *mut c_char {
+    new_string((*value).0.to_string())
+}
+
+/// Returns the number of elements of a list or tuple, or `0` for other values
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_len(value: *const YexValue) -> usize {
+    match &(*value).0 {
+        Value::List(xs) => xs.len(),
+        Value::Tuple(tup) => tup.len(),
+        _ => 0,
+    }
+}
+
+/// Returns the element at `index` of a list or tuple, or `NULL` if it's out of bounds
+///
+/// # Safety
+/// `value` must be a valid value
+#[no_mangle]
+pub unsafe extern "C" fn yex_value_get(value: *const YexValue, index: usize) -> *mut YexValue {
+    match &(*value).0 {
+        Value::List(xs) if index < xs.len() => new_value(xs.index(index)),
+        Value::Tuple(tup) if index < tup.len() => new_value(tup.0[index].clone()),
+        _ => ptr::null_mut(),
+    }
+}
+
+/// Releases a string returned by this library
+///
+/// # Safety
+/// `str` must be a string returned by this library or `NULL`
+#[no_mangle]
+pub unsafe extern "C" fn yex_string_free(str: *mut c_char) {
+    if !str.is_null() {
+        drop(CString::from_raw(str));
+    }
+}
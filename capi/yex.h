@@ -0,0 +1,58 @@
+#ifndef YEX_H
+#define YEX_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+typedef struct YexVm YexVm;
+typedef struct YexValue YexValue;
+
+typedef enum {
+    YEX_NIL,
+    YEX_NUM,
+    YEX_STR,
+    YEX_SYM,
+    YEX_BOOL,
+    YEX_FN,
+    YEX_LIST,
+    YEX_TUPLE,
+    YEX_OTHER,
+    YEX_INT,
+} YexType;
+
+YexVm *yex_vm_new(void);
+void yex_vm_free(YexVm *vm);
+const char *yex_last_error(const YexVm *vm);
+
+int yex_exec(YexVm *vm, const char *source);
+YexValue *yex_eval(YexVm *vm, const char *source);
+YexValue *yex_call(YexVm *vm, const char *name, const YexValue *const *argv, size_t argc);
+int yex_set_global(YexVm *vm, const char *name, const YexValue *value);
+
+YexValue *yex_value_nil(void);
+YexValue *yex_value_num(double n);
+YexValue *yex_value_int(int64_t n);
+YexValue *yex_value_bool(bool b);
+YexValue *yex_value_str(const char *str);
+void yex_value_free(YexValue *value);
+
+YexType yex_value_type(const YexValue *value);
+double yex_value_as_num(const YexValue *value);
+int64_t yex_value_as_int(const YexValue *value);
+bool yex_value_as_bool(const YexValue *value);
+char *yex_value_as_str(const YexValue *value);
+char *yex_value_to_string(const YexValue *value);
+size_t yex_value_len(const YexValue *value);
+YexValue *yex_value_get(const YexValue *value, size_t index);
+void yex_string_free(char *str);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
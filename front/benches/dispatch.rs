@@ -0,0 +1,107 @@
+//! Times the dispatch loop, on straight lines of cheap instructions, where the time is almost
+//! only the dispatch, and on programs that run tight numeric loops, run with
+//! `cargo bench -p front --bench dispatch`
+//!
+//! The time is the cpu time of the thread, so the other processes don't change the results much,
+//! and the best of many runs is shown
+
+use std::time::Duration;
+
+use vm::{Bytecode, OpCode, OpCodeMetadata, Value, VirtualMachine};
+
+/// Lines of instructions, repeated to fill a chunk that still fits in the cache
+const LINES: &[(&str, &[OpCode])] = &[
+    ("nop", &[OpCode::Nop]),
+    ("push pop", &[OpCode::Push(0), OpCode::Pop]),
+    (
+        "add",
+        &[OpCode::Push(0), OpCode::Push(1), OpCode::Add, OpCode::Pop],
+    ),
+    (
+        "locals",
+        &[
+            OpCode::Push(0),
+            OpCode::Save(0),
+            OpCode::Load(0),
+            OpCode::Pop,
+            OpCode::Drop(0),
+        ],
+    ),
+    (
+        "not",
+        &[OpCode::Push(0), OpCode::Not, OpCode::Not, OpCode::Pop],
+    ),
+];
+
+/// The programs that run tight loops
+const PROGRAMS: &[(&str, &str)] = &[
+    (
+        "fib",
+        "def fib n = if n < 2 then n else fib (n - 1) + fib (n - 2)
+let _ = fib 25",
+    ),
+    (
+        "sum",
+        "def sum n acc = if n == 0 then acc else sum (n - 1) (acc + n)
+let _ = sum 1000000 0",
+    ),
+    (
+        "float",
+        "def step n x = if n == 0 then x else step (n - 1) (x * 0.5 + 1.0)
+let _ = step 1000000 0.0",
+    ),
+];
+
+fn main() {
+    for (name, line) in LINES {
+        let code: Bytecode = line
+            .iter()
+            .cycle()
+            .take(line.len() * 100)
+            .map(|op| OpCodeMetadata::new(1, 1, *op))
+            .collect();
+        let mut vm = VirtualMachine::default();
+        vm.set_consts(vec![Value::Int(1), Value::Int(2)]);
+
+        let best = best_of(40, || {
+            for _ in 0..500 {
+                vm.run(&code).unwrap();
+            }
+        });
+        let per_op = best.as_nanos() as f64 / (code.len() * 500) as f64;
+        println!("{:<8} {:>8.2}ns per instruction", name, per_op);
+    }
+
+    for (name, source) in PROGRAMS {
+        let (bytecode, constants) = front::parse(*source).unwrap();
+        let best = best_of(10, || {
+            let mut vm = VirtualMachine::default();
+            let bytecode = vm.load(bytecode.clone(), constants.clone());
+            vm.run(&bytecode).unwrap();
+        });
+        println!("{:<8} {:>10.2?}", name, best);
+    }
+}
+
+/// The shortest time `f` took in `runs` runs
+fn best_of(runs: u32, mut f: impl FnMut()) -> Duration {
+    (0..runs)
+        .map(|_| {
+            let start = cpu_time();
+            f();
+            cpu_time() - start
+        })
+        .min()
+        .unwrap()
+}
+
+/// The time the current thread ran on the cpu
+fn cpu_time() -> Duration {
+    let mut time = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    // SAFETY: `time` is a valid timespec to write to
+    unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut time) };
+    Duration::new(time.tv_sec as u64, time.tv_nsec as u32)
+}
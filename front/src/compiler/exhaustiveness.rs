@@ -0,0 +1,196 @@
+//! Warnings for `match` expressions that miss a variant of a declared type, or have arms that can
+//! never run
+
+use std::collections::HashSet;
+
+use vm::Symbol;
+
+use super::Compiler;
+use crate::{
+    error::codes,
+    parser::ast::{Location, MatchArm, Pattern},
+};
+
+/// The values matched by a pattern
+enum Coverage {
+    /// Every value, like `x` or `_`
+    All,
+    /// Every value of a variant, like `Option.some _`
+    Variant(Symbol),
+    /// Only some values, like `1` or `Option.some 1`
+    Partial,
+}
+
+fn coverage(pattern: &Pattern) -> Coverage {
+    match pattern {
+        Pattern::Id(..) => Coverage::All,
+        Pattern::Variant(path, args)
+            if args
+                .iter()
+                .all(|arg| matches!(coverage(arg), Coverage::All)) =>
+        {
+            Coverage::Variant(tag(path))
+        }
+        _ => Coverage::Partial,
+    }
+}
+
+/// Checks if the arm matches every value, so the arms after it can never run
+pub(super) fn irrefutable(arm: &MatchArm) -> bool {
+    arm.guard.is_none()
+        && arm
+            .conds
+            .iter()
+            .any(|cond| matches!(coverage(cond), Coverage::All))
+}
+
+fn tag(path: &[Symbol]) -> Symbol {
+    let path: Vec<_> = path.iter().map(Symbol::as_str).collect();
+    path.join(".").into()
+}
+
+impl Compiler {
+    /// Returns the declared type that has the variant `tag`
+    fn type_of_variant(&self, tag: Symbol) -> Option<Symbol> {
+        let (ty, _) = tag.as_str().rsplit_once('.')?;
+        let ty = Symbol::from(ty);
+        self.types
+            .get(&ty)
+            .filter(|variants| variants.contains(&tag))
+            .map(|_| ty)
+    }
+
+    /// Warns about missing variants and unreachable arms, arms with guards can always fail, so they
+    /// don't count as covering anything
+    pub(super) fn check_match(&mut self, arms: &[MatchArm], loc: &Location) {
+        let missing = self.check_arms(arms);
+
+        if !missing.is_empty() {
+            self.warn(
+                codes::NON_EXHAUSTIVE,
+                loc,
+                format!("Non-exhaustive match, missing {}", missing.join(", ")),
+            )
+            .help = Some(String::from(
+                "add an arm for each missing variant, or a '_' arm to match the rest",
+            ));
+        }
+    }
+
+    /// Warns about unreachable arms, returning the variants that aren't matched by any arm, the
+    /// `rescue` arms use it directly since unmatched exceptions are just raised again
+    pub(super) fn check_arms(&mut self, arms: &[MatchArm]) -> Vec<String> {
+        let mut covered = HashSet::new();
+        let mut catch_all = false;
+        // the type of the variants matched by the arms, `None` if they're not all of the same type
+        let mut ty = Some(None);
+
+        for arm in arms {
+            if catch_all {
+                self.warn(
+                    codes::UNREACHABLE_ARM,
+                    &arm.location,
+                    "Unreachable arm, the previous arms already match every value",
+                )
+                .help = Some(String::from(
+                    "move it before the arm that matches everything",
+                ));
+                continue;
+            }
+
+            let tags: Vec<_> = arm
+                .conds
+                .iter()
+                .map(|cond| match cond {
+                    Pattern::Variant(path, _) => Some(tag(path)),
+                    _ => None,
+                })
+                .collect();
+
+            // every alternative matches a variant that the previous arms already match
+            if tags
+                .iter()
+                .all(|tag| matches!(tag, Some(tag) if covered.contains(tag)))
+            {
+                let tags: Vec<_> = tags
+                    .iter()
+                    .flatten()
+                    .map(|tag| format!("'{}'", tag))
+                    .collect();
+                self.warn(
+                    codes::UNREACHABLE_ARM,
+                    &arm.location,
+                    format!("Unreachable arm, {} is already matched", tags.join(", ")),
+                );
+            }
+
+            for tag in tags.iter().flatten() {
+                ty = match (ty, self.type_of_variant(*tag)) {
+                    (Some(None), Some(t)) => Some(Some(t)),
+                    (Some(Some(prev)), Some(t)) if prev == t => Some(Some(t)),
+                    _ => None,
+                };
+            }
+
+            if arm.guard.is_some() {
+                continue;
+            }
+
+            for cond in &arm.conds {
+                match coverage(cond) {
+                    Coverage::All => catch_all = true,
+                    Coverage::Variant(tag) => {
+                        covered.insert(tag);
+                    }
+                    Coverage::Partial => {}
+                }
+            }
+
+            // matching every variant is the same as matching every value
+            if let Some(Some(ty)) = ty {
+                catch_all |= self.types[&ty].iter().all(|tag| covered.contains(tag));
+            }
+        }
+
+        let ty = match ty {
+            Some(Some(ty)) if !catch_all => ty,
+            _ => return vec![],
+        };
+
+        self.types[&ty]
+            .iter()
+            .filter(|tag| !covered.contains(*tag))
+            .map(|tag| format!("'{}'", tag))
+            .collect()
+    }
+}
+
+#[test]
+fn missing_and_unreachable() {
+    let source = "type Shape = circle r | square s | point with end
+def area s =
+    match s with
+    | Shape.circle r -> r
+    | Shape.square s if s > 0 -> s
+def name s =
+    match s with
+    | Shape.circle _ | Shape.square _ -> 1
+    | Shape.point -> 2
+    | Shape.circle 1 -> 3
+";
+    let (_, _, diagnostics) = crate::parse_with_diagnostics(source).unwrap();
+    let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "Non-exhaustive match, missing 'Shape.square', 'Shape.point'",
+            "Unreachable arm, the previous arms already match every value"
+        ]
+    );
+
+    let lines: Vec<_> = diagnostics.iter().map(|d| d.location.line).collect();
+    assert_eq!(lines, [3, 10]);
+    assert!(diagnostics
+        .iter()
+        .all(|d| d.severity == crate::Severity::Warning));
+}
@@ -0,0 +1,155 @@
+//! Evaluates the expressions whose value is known at compile time, so the branches that can never
+//! run are dropped, and the constants of modules are stored as values
+
+use std::{cmp::Ordering, collections::HashMap};
+
+use vm::{error::InterpretError, List, Value};
+
+use super::Compiler;
+use crate::{
+    error::codes,
+    parser::ast::{BinOp, Expr, ExprKind, UnOp},
+};
+
+/// The constants an expression can refer to, by their full path, like `Circle.PI`
+pub(crate) type Consts = HashMap<String, Value>;
+
+/// Why the value of an expression isn't known at compile time
+#[derive(Debug)]
+pub(crate) enum NotConst {
+    /// It depends on something that's only known at runtime
+    Unknown,
+    /// Evaluating it raises an error, like an overflow
+    Raises(InterpretError),
+}
+
+impl From<InterpretError> for NotConst {
+    fn from(e: InterpretError) -> Self {
+        NotConst::Raises(e)
+    }
+}
+
+/// The path of a constant referenced by `expr`, like `Circle.PI`
+fn path(expr: &Expr) -> Option<String> {
+    match &expr.kind {
+        ExprKind::Var(name) => Some(name.to_string()),
+        ExprKind::MethodRef { ty, method } => Some(format!("{}.{}", path(ty)?, method)),
+        _ => None,
+    }
+}
+
+/// The value of an expression made only of literals, tuples and lists of them, the `consts`, `if`
+/// and the arithmetic, comparison and logical operators
+pub(crate) fn const_value(expr: &Expr, consts: &Consts) -> Result<Value, NotConst> {
+    let value = |expr| const_value(expr, consts);
+
+    match &expr.kind {
+        ExprKind::Lit(lit) => Ok(lit.clone().into()),
+        ExprKind::Var(_) | ExprKind::MethodRef { .. } => path(expr)
+            .and_then(|path| consts.get(&path).cloned())
+            .ok_or(NotConst::Unknown),
+        ExprKind::Tuple(items) => Ok(items
+            .iter()
+            .map(value)
+            .collect::<Result<Vec<_>, _>>()?
+            .into()),
+        ExprKind::List(items) => {
+            let items = items.iter().map(value).collect::<Result<Vec<_>, _>>()?;
+            Ok(items.into_iter().rev().collect::<List>().into())
+        }
+        // only the branch that's taken has to be known
+        ExprKind::If { cond, then, else_ } => match value(cond)?.to_bool() {
+            true => value(then),
+            false => value(else_),
+        },
+        ExprKind::UnOp(UnOp::Not, expr) => Ok(Value::Bool(!value(expr)?.to_bool())),
+        ExprKind::UnOp(UnOp::Neg, expr) => Ok((-value(expr)?)?),
+        // `false && x` is false no matter what `x` is, since `x` isn't evaluated
+        ExprKind::Binary { left, op, right } => match op {
+            BinOp::And => match value(left)?.to_bool() {
+                false => Ok(Value::Bool(false)),
+                true => Ok(Value::Bool(value(right)?.to_bool())),
+            },
+            BinOp::Or => match value(left)?.to_bool() {
+                true => Ok(Value::Bool(true)),
+                false => Ok(Value::Bool(value(right)?.to_bool())),
+            },
+            BinOp::Add => Ok((value(left)? + value(right)?)?),
+            BinOp::Sub => Ok((value(left)? - value(right)?)?),
+            BinOp::Mul => Ok((value(left)? * value(right)?)?),
+            BinOp::Div => Ok((value(left)? / value(right)?)?),
+            BinOp::Rem => Ok((value(left)? % value(right)?)?),
+            BinOp::Eq => Ok(Value::Bool(value(left)? == value(right)?)),
+            BinOp::Ne => Ok(Value::Bool(value(left)? != value(right)?)),
+            BinOp::Less | BinOp::LessEq | BinOp::Greater | BinOp::GreaterEq => {
+                let ord = value(left)?.ord_cmp(&value(right)?)?;
+                let res = match op {
+                    BinOp::Less => ord == Ordering::Less,
+                    BinOp::LessEq => ord != Ordering::Greater,
+                    BinOp::Greater => ord == Ordering::Greater,
+                    _ => ord != Ordering::Less,
+                };
+                Ok(Value::Bool(res))
+            }
+            _ => Err(NotConst::Unknown),
+        },
+        _ => Err(NotConst::Unknown),
+    }
+}
+
+impl Compiler {
+    /// Returns the truth of an `if` condition that's known at compile time, warning that the other
+    /// branch never runs
+    pub(super) fn const_condition(
+        &mut self,
+        cond: &Expr,
+        then: &Expr,
+        else_: &Expr,
+    ) -> Option<bool> {
+        // the conditions that raise an error are left to raise it at runtime
+        let truth = const_value(cond, &Consts::new()).ok()?.to_bool();
+
+        let (branch, dead) = if truth {
+            ("else", else_)
+        } else {
+            ("then", then)
+        };
+        self.warn(
+            codes::CONSTANT_CONDITION,
+            &dead.location,
+            format!(
+                "The condition is always {}, so the {} branch never runs",
+                truth, branch
+            ),
+        );
+
+        Some(truth)
+    }
+}
+
+#[test]
+fn drops_dead_branches() {
+    let source = "def f x = if 1 < 2 && !false then x else x + 1
+def g x = if nil then x else x * 2
+def h x =
+    match x with
+    | 1 -> :one
+    | _ -> :other
+    | 2 -> :two
+";
+    let (bytecode, _, diagnostics) = crate::parse_with_diagnostics(source).unwrap();
+    let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+    assert_eq!(
+        messages,
+        [
+            "The condition is always true, so the else branch never runs",
+            "The condition is always false, so the then branch never runs",
+            "Unreachable arm, the previous arms already match every value",
+        ]
+    );
+
+    // the defs are compiled without any jumps to the dead code
+    assert!(!bytecode
+        .iter()
+        .any(|op| matches!(op.opcode, vm::OpCode::Jmf(_))));
+}
@@ -0,0 +1,361 @@
+//! The register ir that the compiler lowers the function bodies to, where the optimizations that
+//! follow how values flow through the locals, like liveness, copy propagation and dead save
+//! elimination, run before the bytecode is generated
+//!
+//! The compiler emits the instructions of each body into basic blocks as it walks the ast, the
+//! jumps target labels, which start a new block where they're placed. Once the body is complete
+//! the blocks are optimized and lowered to the stack bytecode, which the peepholes then clean up.
+//! The locals addressed by `Save` and `Load` are the registers, so the optimizations only see the
+//! values stored in them, not the ones passed through the stack.
+
+use std::collections::{HashMap, HashSet};
+
+use vm::{OpCode, OpCodeMetadata};
+
+/// A local of the function frame, the ir sees them as registers, since `Save` and `Load` address
+/// them directly
+pub type Reg = usize;
+
+/// A place in the body that jumps can target before it's known, the jumps target the block where
+/// it's placed once the body is finished
+pub type Label = usize;
+
+/// A straight line of instructions, only entered through the first one and only left through
+/// the last one
+#[derive(Debug, Default)]
+pub struct Block {
+    pub insts: Vec<OpCodeMetadata>,
+}
+
+/// A function body split in basic blocks, so the optimizations can follow how the values flow
+/// through the registers, which the peepholes can't see
+///
+/// While the body is emitted the jumps target labels, once it's finished they target block
+/// indexes, `blocks.len()` being the end of the function
+#[derive(Debug)]
+pub struct Function {
+    pub blocks: Vec<Block>,
+    /// The registers reserved when the function starts, one for every `Save` that was removed,
+    /// since the vm sizes the frame by counting them
+    pub reserved: usize,
+    /// The block where each label was placed
+    labels: Vec<Option<usize>>,
+}
+
+impl Default for Function {
+    fn default() -> Self {
+        Self {
+            blocks: vec![Block::default()],
+            reserved: 0,
+            labels: vec![],
+        }
+    }
+}
+
+impl Function {
+    /// Appends an instruction to the current block, a jump ends it, since the code after it may
+    /// not run
+    pub fn emit(&mut self, op: OpCodeMetadata) {
+        self.blocks.last_mut().unwrap().insts.push(op);
+
+        if matches!(op.opcode, OpCode::Jmp(_) | OpCode::Jmf(_)) {
+            self.blocks.push(Block::default());
+        }
+    }
+
+    /// Creates a label, which has to be placed before the function is finished
+    pub fn label(&mut self) -> Label {
+        self.labels.push(None);
+        self.labels.len() - 1
+    }
+
+    /// Places `label` before the next instruction, which starts a new block
+    pub fn place(&mut self, label: Label) {
+        if !self.blocks.last().unwrap().insts.is_empty() {
+            self.blocks.push(Block::default());
+        }
+        self.labels[label] = Some(self.blocks.len() - 1);
+    }
+
+    /// Makes the jumps target the blocks where their labels were placed, once nothing else will be
+    /// emitted
+    pub fn finish(&mut self) {
+        for op in self
+            .blocks
+            .iter_mut()
+            .flat_map(|block| block.insts.iter_mut())
+        {
+            if let Some(label) = op.opcode.target() {
+                let block = self.labels[label].expect("every label is placed");
+                op.opcode = op.opcode.retarget(block);
+            }
+        }
+    }
+
+    /// Whether a jump targets its own block or one before it, the optimizations rely on every
+    /// instruction running once, and the compiler never emits loops
+    fn loops(&self) -> bool {
+        self.blocks.iter().enumerate().any(|(index, block)| {
+            block
+                .insts
+                .iter()
+                .any(|op| op.opcode.target().is_some_and(|t| t <= index))
+        })
+    }
+
+    /// Turns the blocks back into bytecode, reserving the registers first
+    pub fn lower(self) -> Vec<OpCodeMetadata> {
+        let prologue = usize::from(self.reserved > 0);
+
+        let mut starts = Vec::with_capacity(self.blocks.len() + 1);
+        let mut len = prologue;
+        for block in self.blocks.iter() {
+            starts.push(len);
+            len += block.insts.len();
+        }
+        starts.push(len);
+
+        let mut code = Vec::with_capacity(len);
+        if let Some(first) = self.blocks.iter().find_map(|block| block.insts.first()) {
+            if self.reserved > 0 {
+                code.push(OpCodeMetadata {
+                    opcode: OpCode::Reserve(self.reserved),
+                    ..*first
+                });
+            }
+        }
+
+        for block in self.blocks {
+            for mut op in block.insts {
+                if let Some(t) = op.opcode.target() {
+                    op.opcode = op.opcode.retarget(starts[t]);
+                }
+                code.push(op);
+            }
+        }
+
+        code
+    }
+
+    /// The blocks that may run after `index`
+    fn successors(&self, index: usize, rescues: &[usize]) -> Vec<usize> {
+        let mut next = rescues.to_vec();
+
+        match self.blocks[index].insts.last().map(|op| op.opcode) {
+            Some(OpCode::Jmp(t)) => next.push(t),
+            Some(OpCode::Jmf(t)) => next.extend([t, index + 1]),
+            _ => next.push(index + 1),
+        }
+
+        next.retain(|&block| block < self.blocks.len());
+        next
+    }
+
+    /// The blocks targeted by a `Try`, the vm skips their first instruction when an error is
+    /// rescued
+    fn rescues(&self) -> Vec<usize> {
+        self.blocks
+            .iter()
+            .flat_map(|block| block.insts.iter())
+            .filter_map(|op| match op.opcode {
+                OpCode::Try(t) => Some(t),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// The registers that may be loaded before being saved again, at the end of each block
+    pub fn live_out(&self) -> Vec<HashSet<Reg>> {
+        // any instruction after a `Try` may raise and jump to its rescue block
+        let rescues = self.rescues();
+
+        let mut live_in = vec![HashSet::new(); self.blocks.len()];
+        let mut live_out = vec![HashSet::new(); self.blocks.len()];
+
+        let mut changed = true;
+        while changed {
+            changed = false;
+
+            for index in (0..self.blocks.len()).rev() {
+                let out: HashSet<Reg> = self
+                    .successors(index, &rescues)
+                    .into_iter()
+                    .flat_map(|next| live_in[next].iter().copied())
+                    .collect();
+
+                let mut live = out.clone();
+                for op in self.blocks[index].insts.iter().rev() {
+                    transfer(op.opcode, &mut live);
+                }
+
+                if live != live_in[index] {
+                    live_in[index] = live;
+                    changed = true;
+                }
+                live_out[index] = out;
+            }
+        }
+
+        live_out
+    }
+
+    /// Replaces the saves to registers that are never loaded afterwards by pops
+    pub fn eliminate_dead_saves(&mut self) {
+        let live_out = self.live_out();
+
+        for (block, mut live) in self.blocks.iter_mut().zip(live_out) {
+            for op in block.insts.iter_mut().rev() {
+                if let OpCode::Save(reg) = op.opcode {
+                    if !live.contains(&reg) {
+                        op.opcode = OpCode::Pop;
+                        self.reserved += 1;
+                    }
+                }
+                transfer(op.opcode, &mut live);
+            }
+        }
+    }
+
+    /// Removes the registers that only copy another one, like `let y = x in`, loading the
+    /// original one instead
+    ///
+    /// Only registers saved once are considered, so neither can change after the copy
+    pub fn propagate_copies(&mut self) {
+        let mut saves: HashMap<Reg, usize> = HashMap::new();
+        for op in self.blocks.iter().flat_map(|block| block.insts.iter()) {
+            if let OpCode::Save(reg) = op.opcode {
+                *saves.entry(reg).or_default() += 1;
+            }
+        }
+
+        let rescues = self.rescues();
+        let mut copies: HashMap<Reg, Reg> = HashMap::new();
+        for (index, block) in self.blocks.iter_mut().enumerate() {
+            // the first instruction of a rescue block is kept, since it's skipped
+            let mut ip = usize::from(rescues.contains(&index));
+            while ip + 1 < block.insts.len() {
+                match (block.insts[ip].opcode, block.insts[ip + 1].opcode) {
+                    (OpCode::Load(from), OpCode::Save(to))
+                        if from != to && saves.get(&from) == Some(&1) && saves[&to] == 1 =>
+                    {
+                        copies.insert(to, from);
+                        block.insts.drain(ip..ip + 2);
+                        self.reserved += 1;
+                    }
+                    _ => ip += 1,
+                }
+            }
+        }
+
+        // a copy of a copy loads the original register
+        let resolve = |mut reg: Reg| {
+            while let Some(&from) = copies.get(&reg) {
+                reg = from;
+            }
+            reg
+        };
+
+        for op in self
+            .blocks
+            .iter_mut()
+            .flat_map(|block| block.insts.iter_mut())
+        {
+            if let OpCode::Load(reg) = op.opcode {
+                op.opcode = OpCode::Load(resolve(reg));
+            }
+        }
+    }
+}
+
+/// Updates the live registers going backwards through an instruction
+fn transfer(op: OpCode, live: &mut HashSet<Reg>) {
+    match op {
+        OpCode::Save(reg) => {
+            live.remove(&reg);
+        }
+        OpCode::Load(reg) => {
+            live.insert(reg);
+        }
+        _ => (),
+    }
+}
+
+/// Finishes a function body and generates its bytecode, running the register optimizations
+/// first unless it jumps backwards
+pub fn generate(mut function: Function) -> Vec<OpCodeMetadata> {
+    function.finish();
+    if !function.loops() {
+        function.propagate_copies();
+        function.eliminate_dead_saves();
+    }
+    function.lower()
+}
+
+#[test]
+fn removes_copies() {
+    let ops = |ops: &[OpCode]| {
+        let mut function = Function::default();
+        for op in ops {
+            function.emit(OpCodeMetadata::new(1, 1, *op));
+        }
+        function
+    };
+
+    // def f x = let y = x in let z = y + 1 in x * 2
+    let function = ops(&[
+        OpCode::Save(0),
+        OpCode::Load(0),
+        OpCode::Save(1),
+        OpCode::Load(1),
+        OpCode::Push(0),
+        OpCode::Add,
+        OpCode::Save(2),
+        OpCode::Load(1),
+        OpCode::Push(1),
+        OpCode::Mul,
+    ]);
+    let code: Vec<_> = generate(function).iter().map(|op| op.opcode).collect();
+    assert_eq!(
+        code,
+        [
+            OpCode::Reserve(2),
+            OpCode::Save(0),
+            OpCode::Load(0),
+            OpCode::Push(0),
+            OpCode::Add,
+            OpCode::Pop,
+            OpCode::Load(0),
+            OpCode::Push(1),
+            OpCode::Mul,
+        ]
+    );
+}
+
+#[test]
+fn places_labels() {
+    let mut function = Function::default();
+    let emit = |function: &mut Function, op| function.emit(OpCodeMetadata::new(1, 1, op));
+
+    // if c then a else b
+    emit(&mut function, OpCode::Push(0));
+    let else_ = function.label();
+    emit(&mut function, OpCode::Jmf(else_));
+    emit(&mut function, OpCode::Push(1));
+    let end = function.label();
+    emit(&mut function, OpCode::Jmp(end));
+    function.place(else_);
+    emit(&mut function, OpCode::Push(2));
+    function.place(end);
+
+    let code: Vec<_> = generate(function).iter().map(|op| op.opcode).collect();
+    assert_eq!(
+        code,
+        [
+            OpCode::Push(0),
+            OpCode::Jmf(4),
+            OpCode::Push(1),
+            OpCode::Jmp(5),
+            OpCode::Push(2),
+        ]
+    );
+}
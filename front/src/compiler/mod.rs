@@ -1,18 +1,50 @@
-use std::collections::HashMap;
+mod exhaustiveness;
+mod folding;
+mod ir;
+mod peephole;
+mod types;
+
+pub(crate) use folding::{const_value, Consts, NotConst};
+use types::Signature;
+
+use std::{collections::HashMap, mem, slice};
 
 use vm::{
-    gc::GcRef, stackvec, Bytecode, EnvTable, Fn, FnKind, List, OpCode, OpCodeMetadata, Symbol,
-    Value, YexModule,
+    gc::GcRef, stackvec, Bytecode, EnvTable, Fn, FnInfo, FnKind, List, OpCode, OpCodeMetadata,
+    Symbol, Value, YexModule,
 };
 
-use crate::parser::ast::{
-    BinOp, Bind, Def, Expr, ExprKind, Literal, Location, MatchArm, Pattern, Stmt, StmtKind, VarDecl,
+use crate::{
+    error::{codes, Diagnostic, Severity},
+    parser::ast::{
+        BinOp, Bind, BlockKind, Def, Expr, ExprKind, Literal, Location, MatchArm, Pattern, Stmt,
+        StmtKind, VarDecl,
+    },
 };
 
+/// Where the value captured by a closure comes from, relative to the enclosing function
+#[derive(Debug, Clone, Copy)]
+enum Capture {
+    /// A local of the enclosing function
+    Local(usize),
+    /// A value captured by the enclosing function itself
+    Upval(usize),
+}
+
 #[derive(Default)]
 struct Scope {
-    opcodes: Vec<OpCodeMetadata>,
+    /// The body being emitted, the bytecode is generated from it once the scope is popped
+    code: ir::Function,
     locals: HashMap<Symbol, usize>,
+    /// The names given to each local slot, kept for the debugger once they're out of scope
+    names: Vec<(usize, Symbol)>,
+    upvalues: Vec<(Symbol, Capture)>,
+    /// The locals that weren't loaded yet, with where they were declared
+    unused: HashMap<Symbol, Location>,
+    /// Whether the scope is the body of a generator, where `yield` suspends it
+    generator: bool,
+    /// The types of the locals known while compiling, from their annotations or their values
+    types: HashMap<Symbol, Symbol>,
 }
 
 impl Scope {
@@ -21,11 +53,40 @@ impl Scope {
     }
 }
 
+/// A compiled `test` or `bench` block
+#[derive(Debug)]
+pub struct Block {
+    /// The name after `test` or `bench`
+    pub name: String,
+    /// Where the block starts
+    pub location: Location,
+    /// The body of the block, which runs in the globals defined by the program
+    pub bytecode: Vec<OpCodeMetadata>,
+}
+
 #[derive(Default)]
 pub struct Compiler {
     scope_stack: Vec<Scope>,
     constants: Vec<Value>,
     unique_counter: usize,
+    /// The variants of every type declared in the program, by the type name
+    types: HashMap<Symbol, Vec<Symbol>>,
+    diagnostics: Vec<Diagnostic>,
+    /// Whether the next expression is in tail position, where its value is returned right away,
+    /// so a call there reuses the frame of the function. Taken by `expr`, so only the
+    /// subexpressions compiled with `tail_expr` inherit it
+    tail: bool,
+    /// The private members of the modules declared so far, by the module path
+    privates: HashMap<Symbol, Vec<Symbol>>,
+    /// The module whose functions are being compiled, which can reference its private members
+    module: Option<Symbol>,
+    /// The most arguments taken by the functions defined by the program, by their full path, like
+    /// `Foo.bar`, `None` for the globals that aren't functions or that take any number of arguments
+    arities: HashMap<Symbol, Option<usize>>,
+    /// The annotations of the functions defined by the program, by their full path
+    signatures: HashMap<Symbol, Signature>,
+    /// The prelude, created when a call to a global that the program doesn't define is checked
+    builtins: Option<EnvTable>,
 }
 
 impl Compiler {
@@ -33,10 +94,45 @@ impl Compiler {
         Compiler::default()
     }
 
+    /// Creates a compiler that appends to the constants of an already running program, so the
+    /// bytecode can be executed without relocating it
+    pub fn with_constants(constants: Vec<Value>) -> Self {
+        Compiler {
+            constants,
+            ..Compiler::default()
+        }
+    }
+
     pub fn compile_expr(mut self, expr: &Expr) -> (Bytecode, Vec<Value>) {
         self.scope_stack.push(Scope::new());
         self.expr(expr);
-        (self.scope_stack.pop().unwrap().opcodes, self.constants)
+        (self.pop_scope().0, self.constants)
+    }
+
+    /// Reports a warning, the help note can be set on the returned diagnostic
+    fn warn(
+        &mut self,
+        code: &'static str,
+        loc: &Location,
+        message: impl Into<String>,
+    ) -> &mut Diagnostic {
+        self.diagnostics.push(Diagnostic {
+            severity: Severity::Warning,
+            code,
+            location: *loc,
+            span: None,
+            message: message.into(),
+            help: None,
+        });
+        self.diagnostics.last_mut().unwrap()
+    }
+
+    /// Pops the current scope, generating its bytecode since nothing else will be emitted to it
+    fn pop_scope(&mut self) -> (Bytecode, Scope) {
+        let mut scope = self.scope_stack.pop().unwrap();
+        let mut bytecode = ir::generate(mem::take(&mut scope.code));
+        peephole::optimize(&mut bytecode);
+        (bytecode, scope)
     }
 
     fn scope_mut(&mut self) -> &mut Scope {
@@ -48,13 +144,26 @@ impl Compiler {
     }
 
     fn emit_op(&mut self, op: OpCode, loc: &Location) {
-        self.scope_mut().opcodes.push(OpCodeMetadata {
+        self.scope_mut().code.emit(OpCodeMetadata {
             opcode: op,
             line: loc.line,
             column: loc.column,
         })
     }
 
+    /// Emits a jump to a new label, which is returned so it can be placed once its code is
+    /// emitted
+    fn emit_jump(&mut self, jump: fn(usize) -> OpCode, loc: &Location) -> ir::Label {
+        let label = self.scope_mut().code.label();
+        self.emit_op(jump(label), loc);
+        label
+    }
+
+    /// Places a label returned by `emit_jump` before the next instruction
+    fn place(&mut self, label: ir::Label) {
+        self.scope_mut().code.place(label);
+    }
+
     fn emit_ops(&mut self, ops: &[OpCode], node: &Location) {
         for op in ops {
             self.emit_op(*op, node);
@@ -88,6 +197,9 @@ impl Compiler {
         let len = self.scope().locals.len();
 
         let index = *self.scope_mut().locals.entry(bind).or_insert(len);
+        if !bind.as_str().starts_with('#') && !self.scope().names.contains(&(index, bind)) {
+            self.scope_mut().names.push((index, bind));
+        }
         self.emit_op(OpCode::Save(index), node);
     }
 
@@ -101,75 +213,232 @@ impl Compiler {
     }
 
     fn emit_load(&mut self, bind: &VarDecl, loc: &Location) {
-        if let Some(offset) = self.scope().locals.get(bind) {
-            self.emit_op(OpCode::Load(*offset), loc);
+        let depth = self.scope_stack.len() - 1;
+
+        if let Some(&offset) = self.scope().locals.get(bind) {
+            self.scope_mut().unused.remove(bind);
+            self.emit_op(OpCode::Load(offset), loc);
+        } else if let Some(index) = self.resolve_upvalue(depth, bind) {
+            self.emit_op(OpCode::LoadUpval(index), loc);
         } else {
             self.emit_op(OpCode::Loag(*bind), loc);
         }
     }
 
-    fn if_expr(&mut self, cond: &Expr, then: &Expr, else_: &Expr, loc: &Location) {
+    /// Looks for a local of the enclosing functions, capturing it in every function between the
+    /// scope at `depth` and the one that declared it
+    fn resolve_upvalue(&mut self, depth: usize, name: &Symbol) -> Option<usize> {
+        let scope = &self.scope_stack[depth];
+        if let Some(index) = scope.upvalues.iter().position(|(it, _)| it == name) {
+            return Some(index);
+        }
+
+        let enclosing = depth.checked_sub(1)?;
+        let capture = match self.scope_stack[enclosing].locals.get(name) {
+            Some(&offset) => {
+                self.scope_stack[enclosing].unused.remove(name);
+                Capture::Local(offset)
+            }
+            None => Capture::Upval(self.resolve_upvalue(enclosing, name)?),
+        };
+
+        let upvalues = &mut self.scope_stack[depth].upvalues;
+        upvalues.push((*name, capture));
+        Some(upvalues.len() - 1)
+    }
+
+    /// Warns about the names bound by `pattern` that are already locals of the current function,
+    /// names starting with `_` are ignored
+    fn check_shadowing(&mut self, pattern: &Pattern) {
+        for (name, loc) in pattern.locations() {
+            if !name.as_str().starts_with('_') && self.scope().locals.contains_key(&name) {
+                self.warn(
+                    codes::SHADOWED_VARIABLE,
+                    &loc,
+                    format!("'{}' shadows a variable declared before it", name),
+                )
+                .help = Some(String::from("give it another name, or prefix it with '_'"));
+            }
+        }
+    }
+
+    /// Starts tracking the uses of the declared locals, so the unused ones can be reported where
+    /// they're declared
+    fn track(&mut self, declarations: &[(VarDecl, Location)]) {
+        for (decl, loc) in declarations {
+            if !decl.as_str().starts_with(['_', '#']) {
+                self.scope_mut().unused.insert(*decl, *loc);
+            }
+        }
+    }
+
+    /// Warns about the declarations that were never loaded, removing them from the local scope
+    /// if `remove` is set
+    fn drop_locals(&mut self, declarations: &[VarDecl], remove: bool) {
+        for decl in declarations {
+            if remove {
+                self.scope_mut().locals.remove(decl);
+                self.scope_mut().types.remove(decl);
+            }
+
+            if let Some(loc) = self.scope_mut().unused.remove(decl) {
+                self.warn(
+                    codes::UNUSED_VARIABLE,
+                    &loc,
+                    format!("Unused variable '{}'", decl),
+                )
+                .help = Some(String::from("prefix it with '_' to ignore it"));
+            }
+        }
+    }
+
+    /// Pushes a function, creating a closure if it captures anything from the current scope
+    fn emit_closure(&mut self, func: Value, captures: &[Capture], loc: &Location) {
+        for capture in captures {
+            match *capture {
+                Capture::Local(offset) => self.emit_op(OpCode::Load(offset), loc),
+                Capture::Upval(index) => self.emit_op(OpCode::LoadUpval(index), loc),
+            }
+        }
+
+        self.emit_const(func, loc);
+
+        if !captures.is_empty() {
+            self.emit_op(OpCode::Closure(captures.len()), loc);
+        }
+    }
+
+    fn if_expr(&mut self, cond: &Expr, then: &Expr, else_: &Expr, tail: bool, loc: &Location) {
+        // only the branch that runs is compiled if the condition is known at compile time
+        match self.const_condition(cond, then, else_) {
+            Some(true) => return self.tail_expr(then, tail),
+            Some(false) => return self.tail_expr(else_, tail),
+            None => {}
+        }
+
         // compiles the codition
         self.expr(cond);
 
-        // keeps track of the jump offset
-        let then_label = self.scope().opcodes.len();
-        self.emit_op(OpCode::Jmf(0), loc);
+        // jumps to the else branch if the condition is false
+        let then_label = self.emit_jump(OpCode::Jmf, loc);
 
         // compiles the then branch
-        self.expr(then);
+        self.tail_expr(then, tail);
 
-        // keeps track of the else jump offset
-        let else_label = self.scope().opcodes.len();
-        self.emit_op(OpCode::Jmp(0), loc);
+        // jumps over the else branch
+        let else_label = self.emit_jump(OpCode::Jmp, loc);
 
-        // fix the then jump offset
-        self.scope_mut().opcodes[then_label].opcode = OpCode::Jmf(self.scope().opcodes.len());
+        // the else branch starts here
+        self.place(then_label);
 
-        self.expr(else_);
+        self.tail_expr(else_, tail);
 
-        // fix the else jump offset
-        self.scope_mut().opcodes[else_label].opcode = OpCode::Jmp(self.scope().opcodes.len());
+        // the then branch jumps here
+        self.place(else_label);
     }
 
-    fn match_arm(&mut self, arm: &MatchArm, loc: &Location) -> usize {
-        // creates a stack of jmp indexes to be fixed later
-        let (declarations, fix_stack) = self.match_pattern(&arm.cond, false, loc);
+    fn match_arm(&mut self, arm: &MatchArm, value: &Symbol, tail: bool, loc: &Location) -> usize {
+        let mut declarations = vec![];
+        let mut fix_stack: Vec<usize> = vec![];
+        let mut matched = vec![];
+
+        for (i, cond) in arm.conds.iter().enumerate() {
+            if i != 0 {
+                // the previous alternative jumps to the end of the arm if it matches, or here if
+                // it doesn't
+                matched.push(self.emit_jump(OpCode::Jmp, loc));
+
+                for label in fix_stack.drain(..) {
+                    self.place(label);
+                }
+            }
+
+            // every alternative binds the same names, so they reuse the same locals
+            self.emit_load(value, loc);
+            let (decls, fixes) = self.match_pattern(cond, false, loc);
+            declarations = decls;
+            fix_stack = fixes;
+        }
+
+        for label in matched {
+            self.place(label);
+        }
+
+        // the alternatives bind the same names, the first one is reported
+        self.track(&arm.conds[0].locations());
 
         // emits the guard check if it exists
         let guard_label = if let Some(guard) = &arm.guard {
             self.expr(guard);
-            let label = self.scope().opcodes.len();
-            self.emit_op(OpCode::Jmf(0), loc);
+            let label = self.emit_jump(OpCode::Jmf, loc);
             Some(label)
         } else {
             None
         };
 
-        self.expr(&arm.body);
+        self.tail_expr(&arm.body, tail);
 
         // remove all the declarations from the local scope
-        for decl in declarations {
-            self.scope_mut().locals.remove(&decl);
-        }
+        self.drop_locals(&declarations, true);
 
         // emit a new jump, since we need to jump to the end of the when if the condition was
         // met
-        let jmp_label = self.scope().opcodes.len();
-        self.emit_op(OpCode::Jmp(0), loc);
+        let jmp_label = self.emit_jump(OpCode::Jmp, loc);
 
-        // fix the jump offset
+        // the jumps land here
         for label in fix_stack {
-            self.scope_mut().opcodes[label].opcode = OpCode::Jmf(self.scope().opcodes.len());
+            self.place(label);
         }
 
         guard_label.map(|label| {
-            self.scope_mut().opcodes[label].opcode = OpCode::Jmf(self.scope().opcodes.len());
+            self.place(label);
         });
 
         jmp_label
     }
 
+    /// Matches the pattern of a `let` or a function argument, the value is kept in a local if the
+    /// pattern can fail, which is returned with the jumps taken when it doesn't match
+    fn match_bind(
+        &mut self,
+        pattern: &Pattern,
+        global: bool,
+        loc: &Location,
+    ) -> (Vec<VarDecl>, Option<(Symbol, Vec<usize>)>) {
+        if let Pattern::Id(..) = pattern {
+            let (declarations, _) = self.match_pattern(pattern, global, loc);
+            return (declarations, None);
+        }
+
+        let local = self.emit_unique(loc);
+        self.emit_load(&local, loc);
+
+        let (declarations, fix_stack) = self.match_pattern(pattern, global, loc);
+        (declarations, Some((local, fix_stack)))
+    }
+
+    /// Emits the `MatchFail`s that the patterns returned by `match_bind` jump to, with a jump over
+    /// them for the code that matched
+    fn match_fail(&mut self, fails: Vec<(Symbol, Vec<usize>)>, loc: &Location) {
+        if fails.is_empty() {
+            return;
+        }
+
+        let jmp_label = self.emit_jump(OpCode::Jmp, loc);
+
+        for (local, fix_stack) in fails {
+            for offset in fix_stack {
+                self.place(offset);
+            }
+
+            self.emit_load(&local, loc);
+            self.emit_op(OpCode::MatchFail, loc);
+        }
+
+        // the code that matched jumps here
+        self.place(jmp_label);
+    }
+
     fn match_pattern(
         &mut self,
         pattern: &Pattern,
@@ -183,13 +452,24 @@ impl Compiler {
                 self.emit_op(OpCode::Eq, loc);
 
                 // in case it returns false, emit the necessary jump instruction
-                let label = self.scope().opcodes.len();
-                self.emit_op(OpCode::Jmf(0), loc);
+                let label = self.emit_jump(OpCode::Jmf, loc);
+
+                (vec![], vec![label])
+            }
+
+            Pattern::Pin(name) => {
+                // compares the value against the variable, which may also be a global
+                self.emit_load(name, loc);
+                self.emit_op(OpCode::Eq, loc);
+
+                let label = self.emit_jump(OpCode::Jmf, loc);
 
                 (vec![], vec![label])
             }
 
-            Pattern::Id(id) if id.as_str() != "_" => {
+            Pattern::Id(id, _) if id.as_str() != "_" => {
+                // the new local has an unknown type until it's set by the caller
+                self.scope_mut().types.remove(id);
                 if global {
                     self.emit_op(OpCode::Savg(*id), loc);
                 } else {
@@ -197,7 +477,7 @@ impl Compiler {
                 }
                 (vec![*id], vec![])
             }
-            Pattern::Id(_) => {
+            Pattern::Id(..) => {
                 self.emit_op(OpCode::Pop, loc);
                 <_>::default()
             }
@@ -223,8 +503,7 @@ impl Compiler {
                 let mut declarations = vec![];
 
                 // emits the jump instruction that will jump to the next clause if it doesn't match
-                let mut labels = vec![self.scope().opcodes.len()];
-                self.emit_op(OpCode::Jmf(0), loc);
+                let mut labels = vec![self.emit_jump(OpCode::Jmf, loc)];
 
                 // gets the inner tuple from the tagged value
                 self.emit_load(&local, loc);
@@ -232,12 +511,11 @@ impl Compiler {
 
                 // checks if the two "tuples" have the same length
                 self.emit_op(OpCode::Len, loc);
-                self.emit_lit(&Literal::Num(args.len() as f64), loc);
+                self.emit_lit(&Literal::Int(args.len() as i64), loc);
                 self.emit_op(OpCode::Eq, loc);
 
-                // emit the jump place-holder
-                labels.push(self.scope().opcodes.len());
-                self.emit_op(OpCode::Jmf(0), loc);
+                // jumps to the next clause if it doesn't match
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
 
                 for (idx, arg) in args.iter().enumerate() {
                     self.emit_load(&local, loc);
@@ -259,12 +537,11 @@ impl Compiler {
 
                 self.emit_load(&local, loc);
                 self.emit_op(OpCode::Len, loc);
-                self.emit_const((args.len() as f64).into(), loc);
+                self.emit_const((args.len() as i64).into(), loc);
                 self.emit_op(OpCode::Eq, loc);
 
-                // emit the jump place-holder
-                labels.push(self.scope().opcodes.len());
-                self.emit_op(OpCode::Jmf(0), loc);
+                // jumps to the next clause if it doesn't match
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
 
                 for (index, arg) in args.iter().enumerate() {
                     self.emit_load(&local, loc);
@@ -306,114 +583,500 @@ impl Compiler {
                 (declarations, labels)
             }
 
+            Pattern::Prefix(prefix, rest) => {
+                let local = self.emit_unique(loc);
+                let mut labels = vec![];
+
+                // strings are matched by their prefix
+                self.emit_load(&local, loc);
+                self.emit_op(OpCode::Type, loc);
+                self.emit_op(OpCode::Loag("Str".into()), loc);
+                self.emit_op(OpCode::Eq, loc);
+
+                let list_label = self.emit_jump(OpCode::Jmf, loc);
+
+                self.emit_load(&local, loc);
+                self.emit_const(prefix.clone().into(), loc);
+                self.emit_op(OpCode::Loag("Str".into()), loc);
+                self.emit_op(OpCode::Ref("stripPrefix".into()), loc);
+                self.emit_op(OpCode::Call(2), loc);
+
+                let join_label = self.emit_jump(OpCode::Jmp, loc);
+
+                // lists are matched by their head, like in `x :: xs`
+                self.place(list_label);
+
+                self.emit_load(&local, loc);
+                self.emit_op(OpCode::Loag("List".into()), loc);
+                self.emit_op(OpCode::Ref("head".into()), loc);
+                self.emit_op(OpCode::Call(1), loc);
+                self.emit_const(prefix.clone().into(), loc);
+                self.emit_op(OpCode::Eq, loc);
+
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
+
+                self.emit_load(&local, loc);
+                self.emit_op(OpCode::Loag("List".into()), loc);
+                self.emit_op(OpCode::Ref("tail".into()), loc);
+                self.emit_op(OpCode::Call(1), loc);
+
+                self.place(join_label);
+
+                // `Str.stripPrefix` returns nil if the string doesn't start with the prefix
+                let rest_local = self.emit_unique(loc);
+                self.emit_load(&rest_local, loc);
+                self.emit_const(Value::Nil, loc);
+                self.emit_op(OpCode::Eq, loc);
+                self.emit_op(OpCode::Not, loc);
+
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
+
+                self.emit_load(&rest_local, loc);
+                let (declarations, offsets) = self.match_pattern(rest, global, loc);
+                labels.extend(offsets);
+
+                (declarations, labels)
+            }
+
+            Pattern::Array(elems) => {
+                let local = self.emit_unique(loc);
+                let mut labels = vec![];
+                let mut declarations = vec![];
+
+                // checks if the value is an array with the same length as the pattern
+                self.emit_load(&local, loc);
+                self.emit_op(OpCode::Type, loc);
+                self.emit_op(OpCode::Loag("Array".into()), loc);
+                self.emit_op(OpCode::Eq, loc);
+
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
+
+                self.emit_load(&local, loc);
+                self.emit_op(OpCode::Len, loc);
+                self.emit_const((elems.len() as i64).into(), loc);
+                self.emit_op(OpCode::Eq, loc);
+
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
+
+                for (index, pat) in elems.iter().enumerate() {
+                    self.emit_load(&local, loc);
+                    self.emit_const((index as i64).into(), loc);
+                    self.emit_op(OpCode::Loag("Array".into()), loc);
+                    self.emit_op(OpCode::Ref("get".into()), loc);
+                    self.emit_op(OpCode::Call(2), loc);
+
+                    let (decls, offsets) = self.match_pattern(pat, global, loc);
+                    labels.extend(offsets);
+                    declarations.extend(decls);
+                }
+
+                (declarations, labels)
+            }
+
+            Pattern::Dict(entries) => {
+                let local = self.emit_unique(loc);
+                let mut labels = vec![];
+                let mut declarations = vec![];
+
+                // checks if the value is a dictionary
+                self.emit_load(&local, loc);
+                self.emit_op(OpCode::Type, loc);
+                self.emit_op(OpCode::Loag("Dict".into()), loc);
+                self.emit_op(OpCode::Eq, loc);
+
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
+
+                for (key, pat) in entries {
+                    // checks if the key is in the dictionary
+                    self.emit_load(&local, loc);
+                    self.emit_lit(key, loc);
+                    self.emit_op(OpCode::Loag("Dict".into()), loc);
+                    self.emit_op(OpCode::Ref("has".into()), loc);
+                    self.emit_op(OpCode::Call(2), loc);
+
+                    labels.push(self.emit_jump(OpCode::Jmf, loc));
+
+                    // matches the value against the pattern
+                    self.emit_load(&local, loc);
+                    self.emit_lit(key, loc);
+                    self.emit_op(OpCode::Loag("Dict".into()), loc);
+                    self.emit_op(OpCode::Ref("get".into()), loc);
+                    self.emit_op(OpCode::Call(2), loc);
+
+                    let (decls, offsets) = self.match_pattern(pat, global, loc);
+                    labels.extend(offsets);
+                    declarations.extend(decls);
+                }
+
+                (declarations, labels)
+            }
+
+            Pattern::Struct(path, fields) => {
+                let local = self.emit_unique(loc);
+                let mut labels = vec![];
+                let mut declarations = vec![];
+
+                // the values of a struct are tagged with it's name
+                let name: Vec<_> = path.iter().map(Symbol::as_str).collect();
+                self.emit_load(&local, loc);
+                self.emit_op(OpCode::TagOf, loc);
+                self.emit_const(Symbol::from(name.join(".")).into(), loc);
+                self.emit_op(OpCode::Eq, loc);
+
+                labels.push(self.emit_jump(OpCode::Jmf, loc));
+
+                for (field, pat) in fields {
+                    self.emit_load(&local, loc);
+                    self.emit_op(OpCode::Ref(*field), loc);
+
+                    let (decls, offsets) = self.match_pattern(pat, global, loc);
+                    labels.extend(offsets);
+                    declarations.extend(decls);
+                }
+
+                (declarations, labels)
+            }
+
             Pattern::EmptyList => {
                 self.emit_const(List::new().into(), loc);
                 self.emit_op(OpCode::Eq, loc);
 
-                let offset = self.scope().opcodes.len();
-
-                self.emit_op(OpCode::Jmf(0), loc);
+                let offset = self.emit_jump(OpCode::Jmf, loc);
 
                 (vec![], vec![offset])
             }
         }
     }
 
-    fn match_expr(&mut self, cond: &Expr, arms: &[MatchArm], loc: &Location) {
+    fn match_expr(&mut self, cond: &Expr, arms: &[MatchArm], tail: bool, loc: &Location) {
+        self.check_match(arms, loc);
+
         // compiles the condition
         self.expr(cond);
 
         let local = self.emit_unique(loc);
 
-        // keep track of all the jump offsets
-        let mut jmps = vec![];
-
-        self.emit_const("Starting match".to_string().into(), loc);
-        self.emit_op(OpCode::Pop, loc);
-
-        for arm in arms {
-            self.emit_load(&local, loc);
-            let jmp = self.match_arm(arm, &arm.location);
-            jmps.push(jmp);
-        }
+        let (jmps, exhaustive) = self.match_arms(arms, &local, tail);
 
         // this section is ignored by any arm, since they jump to the next section in the end,
         // thus, if anything here is executed, it's a sign that the match couldn't match anything,
         // which is probably a bug, so it will just raise a new exception
-        self.emit_const("Couldn't match any clause".to_string().into(), loc);
-        self.emit_const(Symbol::from("MatchError").into(), loc);
-        self.emit_op(OpCode::Loag("raise".into()), loc);
-        self.emit_op(OpCode::Call(2), loc);
+        if !exhaustive {
+            self.emit_load(&local, loc);
+            self.emit_op(OpCode::MatchFail, loc);
+        }
 
-        // fix all the jump offsets
-        let ip = self.scope().opcodes.len();
+        // the arms that matched jump here
         for jmp in jmps {
-            self.scope_mut().opcodes[jmp].opcode = OpCode::Jmp(ip);
+            self.place(jmp);
         }
     }
 
-    fn lambda_expr(&mut self, args: &[Pattern], body: &Expr, loc: &Location) -> GcRef<Fn> {
+    /// Compiles the arms of a match against the value saved in `local`, returning the jumps to be
+    /// patched to the end of the match and whether the last arm always matches
+    ///
+    /// The arms after one that always matches are never compiled, `check_arms` warns about them
+    fn match_arms(&mut self, arms: &[MatchArm], local: &Symbol, tail: bool) -> (Vec<usize>, bool) {
+        let mut jmps = vec![];
+
+        for arm in arms {
+            jmps.push(self.match_arm(arm, local, tail, &arm.location));
+
+            if exhaustiveness::irrefutable(arm) {
+                return (jmps, true);
+            }
+        }
+
+        (jmps, false)
+    }
+
+    /// Compiles a function, returning it with the values it captures from the current scope
+    ///
+    /// A generator returns itself once its arguments are matched, the rest of the body runs when
+    /// it's resumed
+    fn lambda_expr(
+        &mut self,
+        args: &[Pattern],
+        generator: bool,
+        body: &Expr,
+        signature: &Signature,
+        check_unused: bool,
+        loc: &Location,
+    ) -> (GcRef<Fn>, Vec<Capture>, Vec<(usize, Symbol)>) {
         // creates the lambda scope
         self.scope_stack.push(Scope::new());
 
-        let mut fix_stack = vec![];
+        let mut fails = vec![];
+        let mut declarations = vec![];
 
         // emit all the patterns, most of them are probably just variable assignments, but some of
         // them may be complex patterns, so we still need to check for the should_pop value
         for arg in args.iter() {
-            let (_, fixes) = self.match_pattern(arg, false, loc);
+            let (decls, fail) = self.match_bind(arg, false, loc);
 
-            fix_stack.extend(fixes);
+            declarations.extend(decls);
+            fails.extend(fail);
         }
 
-        // compiles the body
-        self.expr(body);
+        if check_unused {
+            let locations: Vec<_> = args.iter().flat_map(Pattern::locations).collect();
+            self.track(&locations);
+        }
 
-        // emit a jump to ignore the
-        let jmp_label = self.scope().opcodes.len();
-        self.emit_op(OpCode::Jmp(0), loc);
+        for (name, ty) in signature.args.iter().flatten() {
+            self.scope_mut().types.insert(*name, *ty);
+        }
+        self.check_return(signature.returns, body);
 
-        for offset in fix_stack {
-            self.scope_mut().opcodes[offset].opcode = OpCode::Jmf(self.scope().opcodes.len());
+        if generator {
+            self.emit_op(OpCode::Generator, loc);
+            self.scope_mut().generator = true;
         }
 
-        // emit the call to raise
-        self.emit_const("No match of rhs value".to_string().into(), loc);
-        self.emit_const(Symbol::from("MatchError").into(), loc);
-        self.emit_op(OpCode::Loag("raise".into()), loc);
-        self.emit_op(OpCode::Call(2), loc);
+        // compiles the body, the calls that return its value are tail calls
+        self.tail_expr(body, true);
+        self.drop_locals(&declarations, false);
 
-        // patch the jump to the end
-        self.scope_mut().opcodes[jmp_label].opcode = OpCode::Jmp(self.scope().opcodes.len());
+        self.match_fail(fails, loc);
 
         // pops the lambda scope
-        let Scope { opcodes, .. } = self.scope_stack.pop().unwrap();
+        let (
+            opcodes,
+            Scope {
+                upvalues, names, ..
+            },
+        ) = self.pop_scope();
 
         // convert it to a `Fn` struct
         let func = Fn {
             body: GcRef::new(FnKind::Bytecode(opcodes)),
             arity: args.len(),
             args: stackvec![],
+            info: None,
+            upvalues: vec![],
+            more: None,
+            variadic: signature.variadic,
+        };
+
+        let captures = upvalues.into_iter().map(|(_, capture)| capture).collect();
+        (GcRef::new(func), captures, names)
+    }
+
+    /// Compiles the function of a `def`, attaching its name and parameters so `help` can show them,
+    /// the stubs that fill in its default arguments still need to be created
+    fn def_fn(
+        &mut self,
+        name: Symbol,
+        lambda: &Expr,
+        doc: Option<String>,
+    ) -> (Value, Vec<Capture>) {
+        let (args, variadic, keywords, body, generator) = match &lambda.kind {
+            ExprKind::Lambda {
+                args,
+                variadic,
+                keywords,
+                body,
+                generator,
+                ..
+            } => (args, *variadic, keywords, body, *generator),
+            _ => unreachable!(),
+        };
+
+        let (func, captures, locals) = self.lambda_expr(
+            args,
+            generator,
+            body,
+            &Signature::of(lambda),
+            true,
+            &lambda.location,
+        );
+        let mut params: Vec<_> = args.iter().map(ToString::to_string).collect();
+        if variadic {
+            params.last_mut().unwrap().insert_str(0, "...");
+        }
+
+        // the dictionary of keyword arguments is shown as the keywords it takes
+        if !keywords.is_empty() {
+            params.pop();
+            params.extend(keywords.iter().map(|keyword| format!("{}:", keyword)));
+        }
+
+        let func = Value::Fn(GcRef::new(
+            (*func)
+                .clone()
+                .with_info(FnInfo::new(name, params, doc).with_locals(locals)),
+        ));
+        (func, captures)
+    }
+
+    /// Compiles the value of a `def`, which is usually a function
+    fn def_value(&mut self, name: Symbol, value: &Expr, doc: Option<String>) {
+        match &value.kind {
+            ExprKind::Lambda { args, defaults, .. } => {
+                let (func, captures) = self.def_fn(name, value, doc);
+                self.emit_closure(func, &captures, &value.location);
+                self.emit_defaults(args, defaults, &value.location);
+            }
+            _ => self.expr(value),
+        }
+    }
+
+    /// Compiles the stub that takes the first `arity` arguments, calling the function pushed by
+    /// `emit_full` with the missing ones set to their default values
+    fn default_stub(
+        &mut self,
+        args: &[Pattern],
+        defaults: &[Expr],
+        arity: usize,
+        emit_full: impl FnOnce(&mut Self),
+        loc: &Location,
+    ) -> (GcRef<Fn>, Vec<Capture>) {
+        self.scope_stack.push(Scope::new());
+
+        // the defaults can use the arguments before them if they're just names
+        let params: Vec<Symbol> = args[..arity]
+            .iter()
+            .enumerate()
+            .map(|(i, arg)| match arg {
+                Pattern::Id(name, _) if name.as_str() != "_" => *name,
+                _ => format!("#arg{}", i).into(),
+            })
+            .collect();
+
+        for param in &params {
+            self.emit_save(*param, loc);
+        }
+
+        for param in &params {
+            self.emit_load(param, loc);
+        }
+        for default in &defaults[defaults.len() - (args.len() - arity)..] {
+            self.expr(default);
+        }
+
+        self.emit_op(OpCode::RevN(args.len()), loc);
+        emit_full(self);
+        self.emit_op(OpCode::TCall(args.len()), loc);
+
+        let (opcodes, Scope { upvalues, .. }) = self.pop_scope();
+
+        let captures = upvalues.into_iter().map(|(_, capture)| capture).collect();
+        (GcRef::new(Fn::new_bt(arity, opcodes)), captures)
+    }
+
+    /// Replaces the function on the stack top by the stubs that fill in its default arguments
+    fn emit_defaults(&mut self, args: &[Pattern], defaults: &[Expr], loc: &Location) {
+        if defaults.is_empty() {
+            return;
+        }
+
+        let full = self.emit_unique(loc);
+        self.emit_load(&full, loc);
+
+        // each stub calls the one that takes an argument more when it receives more arguments
+        for arity in (args.len() - defaults.len()..args.len()).rev() {
+            let (stub, captures) = self.default_stub(
+                args,
+                defaults,
+                arity,
+                |this| this.emit_load(&full, loc),
+                loc,
+            );
+            self.emit_closure(Value::Fn(stub), &captures, loc);
+            self.emit_op(OpCode::Overload, loc);
+        }
+    }
+
+    /// Same as `emit_defaults`, but for functions that capture nothing, like methods, so the stubs
+    /// are created at compile time
+    fn const_defaults(
+        &mut self,
+        func: Value,
+        args: &[Pattern],
+        defaults: &[Expr],
+        loc: &Location,
+    ) -> Value {
+        let mut more = match &func {
+            Value::Fn(more) => more.clone(),
+            _ => unreachable!(),
         };
 
-        // push the function onto the stack
-        GcRef::new(func)
+        for arity in (args.len() - defaults.len()..args.len()).rev() {
+            let full = func.clone();
+            let (stub, _) = self.default_stub(
+                args,
+                defaults,
+                arity,
+                |this| {
+                    this.emit_const(full, loc);
+                },
+                loc,
+            );
+            more = GcRef::new((*stub).clone().overloaded(more));
+        }
+
+        Value::Fn(more)
+    }
+
+    /// Calls `List.<method> (fn bind = body)` on the list at the stack top
+    /// Calls `List.method` with `fn bind = body`, reporting the unused names of `bind` only if
+    /// `check_unused` is set
+    fn emit_list_call(
+        &mut self,
+        method: &str,
+        bind: &Pattern,
+        body: &Expr,
+        check_unused: bool,
+        loc: &Location,
+    ) {
+        let bind = slice::from_ref(bind);
+        let (func, captures, _) =
+            self.lambda_expr(bind, false, body, &Signature::default(), check_unused, loc);
+        self.emit_closure(Value::Fn(func), &captures, loc);
+
+        self.emit_op(OpCode::Loag("List".into()), loc);
+        self.emit_op(OpCode::Ref(method.into()), loc);
+        self.emit_op(OpCode::Call(2), loc);
+    }
+
+    /// Compiles an expression that's in tail position if `tail` is set, which is the case for the
+    /// parts of an expression whose value is the value of the whole expression, like the branches
+    /// of an `if`
+    fn tail_expr(&mut self, node: &Expr, tail: bool) {
+        self.tail = tail;
+        self.expr(node);
     }
 
     fn expr(&mut self, node: &Expr) {
         let loc = &node.location;
+        let tail = mem::take(&mut self.tail);
 
         match &node.kind {
             // pushes a literal value onto the stack
             ExprKind::Lit(lit) => self.emit_lit(lit, loc),
 
             // compiles a lambda expression
-            ExprKind::Lambda { args, body } => {
-                let func = self.lambda_expr(args, body, loc);
-                self.emit_const(Value::Fn(func), loc);
+            ExprKind::Lambda {
+                args,
+                defaults,
+                body,
+                generator,
+                ..
+            } => {
+                let (func, captures, _) =
+                    self.lambda_expr(args, *generator, body, &Signature::of(node), true, loc);
+                self.emit_closure(Value::Fn(func), &captures, loc);
+                self.emit_defaults(args, defaults, loc);
             }
 
-            ExprKind::App { callee, args, tail } => {
+            ExprKind::App {
+                callee,
+                args,
+                tail: become_,
+            } => {
+                self.check_arity(callee, args.len(), loc);
+                self.check_call(callee, args, loc);
+
                 // iterate over the arguments
                 // pushing them onto the stack
                 for arg in args.iter() {
@@ -427,77 +1090,61 @@ impl Compiler {
                 // compiles the caller
                 self.expr(callee);
 
-                // emits the `Call` opcode
-                if *tail {
+                // emits the `Call` opcode, `=>` makes it a tail call even outside of tail position
+                if tail || *become_ {
                     self.emit_op(OpCode::TCall(args.len()), loc);
                 } else {
                     self.emit_op(OpCode::Call(args.len()), loc);
                 }
             }
 
-            ExprKind::Var(name) => {
-                // get the local index
-                let pred = self.scope().locals.get(name).copied();
-
-                if let Some(idx) = pred {
-                    // if the variable is in the current scope
-                    // emit the `Load` opcode, which loads a local
-                    self.emit_op(OpCode::Load(idx), loc);
-                } else {
-                    // otherwise emit the `Loag` opcode, which loads a global
-                    self.emit_op(OpCode::Loag(*name), loc);
-                }
-            }
+            // loads a local, a value captured from an enclosing function or a global, in this
+            // order
+            ExprKind::Var(name) => self.emit_load(name, loc),
 
-            ExprKind::If { cond, then, else_ } => self.if_expr(cond, then, else_, loc),
+            ExprKind::If { cond, then, else_ } => self.if_expr(cond, then, else_, tail, loc),
 
-            ExprKind::Match { expr, arms } => self.match_expr(expr, arms, loc),
+            ExprKind::Match { expr, arms } => self.match_expr(expr, arms, tail, loc),
 
             ExprKind::Let { bind, value, body } => {
                 // compiles the value and pushes it on the stack
+                let ty = self.infer(value);
                 self.expr(value);
 
                 // try to match against the value
-                let (declarations, fix_stack) = self.match_pattern(bind, false, loc);
-
-                self.expr(body);
-
-                // remove all the declarations from the local scope
-                for decl in declarations {
-                    self.scope_mut().locals.remove(&decl);
+                self.check_shadowing(bind);
+                let (declarations, fails) = self.match_bind(bind, false, loc);
+                self.track(&bind.locations());
+                if let (Pattern::Id(name, _), Some(ty)) = (bind, ty) {
+                    self.scope_mut().types.insert(*name, ty);
                 }
 
-                // emit a jump to ignore the
-                let jmp_label = self.scope().opcodes.len();
-                self.emit_op(OpCode::Jmp(0), loc);
+                self.tail_expr(body, tail);
 
-                for offset in fix_stack {
-                    self.scope_mut().opcodes[offset].opcode =
-                        OpCode::Jmf(self.scope().opcodes.len());
-                }
-
-                // emit the call to raise
-                self.emit_const("No match of rhs value".to_string().into(), loc);
-                self.emit_const(Symbol::from("MatchError").into(), loc);
-                self.emit_op(OpCode::Loag("raise".into()), loc);
-                self.emit_op(OpCode::Call(2), loc);
+                // remove all the declarations from the local scope
+                self.drop_locals(&declarations, true);
 
-                // patch the jump to the end
-                self.scope_mut().opcodes[jmp_label].opcode =
-                    OpCode::Jmp(self.scope().opcodes.len());
+                self.match_fail(fails.into_iter().collect(), loc);
             }
             ExprKind::Def {
-                bind: Bind { bind, value, .. },
+                bind:
+                    Bind {
+                        bind,
+                        value,
+                        location,
+                    },
                 body,
             } => {
                 // compiles the value
-                self.expr(value);
+                self.def_value(*bind, value, None);
 
                 // emits the `Save` instruction
                 self.emit_save(*bind, loc);
+                self.track(&[(*bind, *location)]);
 
                 // emits a `nil` value, since everything should return something
-                self.expr(body);
+                self.tail_expr(body, tail);
+                self.drop_locals(slice::from_ref(bind), false);
             }
 
             ExprKind::Binary { left, op, right } if op == &BinOp::And => {
@@ -508,16 +1155,15 @@ impl Compiler {
                 self.emit_op(OpCode::Dup, loc);
 
                 // keeps track of the jump location
-                let then_label = self.scope().opcodes.len();
-                self.emit_op(OpCode::Jmf(0), loc);
+                let then_label = self.emit_jump(OpCode::Jmf, loc);
 
-                // pop's the duplicated left value
+                // pop's the duplicated left value, the right side is the value of the whole
+                // expression once it runs
                 self.emit_op(OpCode::Pop, loc);
-                self.expr(right);
+                self.tail_expr(right, tail);
 
-                // fix the jump offset
-                self.scope_mut().opcodes[then_label].opcode =
-                    OpCode::Jmf(self.scope().opcodes.len());
+                // the jumps land here
+                self.place(then_label);
             }
 
             ExprKind::Binary { left, op, right } if op == &BinOp::Or => {
@@ -531,22 +1177,26 @@ impl Compiler {
                 self.emit_op(OpCode::Not, loc);
 
                 // keeps track of the jump location
-                let then_label = self.scope().opcodes.len();
-                self.emit_op(OpCode::Jmf(0), loc);
+                let then_label = self.emit_jump(OpCode::Jmf, loc);
 
-                // pop's the duplicated left value
+                // pop's the duplicated left value, the right side is the value of the whole
+                // expression once it runs
                 self.emit_op(OpCode::Pop, loc);
-                self.expr(right);
+                self.tail_expr(right, tail);
 
-                // fix the jump offset
-                self.scope_mut().opcodes[then_label].opcode =
-                    OpCode::Jmf(self.scope().opcodes.len());
+                // the jumps land here
+                self.place(then_label);
             }
 
             ExprKind::Binary { left, op, right } => {
+                self.check_binary(left, *op, right, loc);
                 self.expr(left);
                 self.expr(right);
-                self.emit_ops((*op).into(), loc);
+
+                match self.specialized(left, *op, right) {
+                    Some(opcode) => self.emit_op(opcode, loc),
+                    None => self.emit_ops((*op).into(), loc),
+                }
             }
 
             ExprKind::List(xs) => {
@@ -577,16 +1227,39 @@ impl Compiler {
                 self.emit_ops((*op).into(), loc);
             }
 
+            ExprKind::Yield(value) => {
+                self.expr(value);
+
+                // the comprehensions and the pipes run in functions of their own, which aren't
+                // generators, so `yield` calls the builtin there
+                if self.scope().generator {
+                    self.emit_op(OpCode::Yield, loc);
+                } else {
+                    self.emit_op(OpCode::Loag("yield".into()), loc);
+                    self.emit_op(OpCode::Call(1), loc);
+                }
+            }
+
             // compiles a method reference access
             ExprKind::MethodRef { ty, method } => {
                 self.expr(ty);
-                self.emit_op(OpCode::Ref(*method), loc);
+                if self.is_private(ty, *method) {
+                    self.emit_op(OpCode::Ref(YexModule::private(*method)), loc);
+                } else {
+                    self.emit_op(OpCode::Ref(*method), loc);
+                }
             }
 
-            ExprKind::Try { body, bind, rescue } => {
+            ExprKind::TupleIndex { tup, index } => {
+                self.expr(tup);
+                self.emit_op(OpCode::TupGet(*index), loc);
+            }
+
+            ExprKind::Try { body, arms } => {
+                self.check_arms(arms);
+
                 // keeps track of the try location
-                let try_label = self.scope().opcodes.len();
-                self.emit_op(OpCode::Try(0), loc);
+                let try_label = self.emit_jump(OpCode::Try, loc);
 
                 // compiles the body
                 self.expr(body);
@@ -595,25 +1268,31 @@ impl Compiler {
                 self.emit_op(OpCode::EndTry, loc);
 
                 // keep track of the new jump location
-                let end_label = self.scope().opcodes.len();
-                self.emit_op(OpCode::Jmp(0), loc);
+                let end_label = self.emit_jump(OpCode::Jmp, loc);
 
-                // fix the try jump offset
-                self.scope_mut().opcodes[try_label].opcode =
-                    OpCode::Try(self.scope().opcodes.len());
+                // the errors are rescued here
+                self.place(try_label);
 
                 // pop the return from the try block
                 self.emit_op(OpCode::Pop, loc);
 
-                // saves the exception to the bind
-                self.emit_save(*bind, loc);
+                // saves the exception and matches it against the rescue arms, which run once the
+                // `try` block ended, unlike its body
+                let local = self.emit_unique(loc);
+                let (jmps, exhaustive) = self.match_arms(arms, &local, tail);
 
-                // compiles the rescue block
-                self.expr(rescue);
+                // the exception isn't handled by any arm, so it's raised again
+                if !exhaustive {
+                    self.emit_load(&local, loc);
+                    self.emit_op(OpCode::Loag("throw".into()), loc);
+                    self.emit_op(OpCode::Call(1), loc);
+                }
 
-                // fix the end of the rescue block
-                self.scope_mut().opcodes[end_label].opcode =
-                    OpCode::Jmp(self.scope().opcodes.len());
+                // the end of the try block and of the rescue arms
+                self.place(end_label);
+                for jmp in jmps {
+                    self.place(jmp);
+                }
             }
 
             ExprKind::Tuple(xs) => {
@@ -623,6 +1302,72 @@ impl Compiler {
 
                 self.emit_op(OpCode::Tup(xs.len()), loc);
             }
+
+            // desugars the interpolation into `Str.show` calls and concatenations
+            ExprKind::Interp(parts) => {
+                for (i, part) in parts.iter().enumerate() {
+                    self.expr(part);
+
+                    if !matches!(part.kind, ExprKind::Lit(Literal::Str(_))) {
+                        self.emit_op(OpCode::Loag("Str".into()), &part.location);
+                        self.emit_op(OpCode::Ref("show".into()), &part.location);
+                        self.emit_op(OpCode::Call(1), &part.location);
+                    }
+
+                    if i != 0 {
+                        self.emit_op(OpCode::Add, loc);
+                    }
+                }
+            }
+
+            ExprKind::Array(xs) => {
+                for x in xs.iter() {
+                    self.expr(x);
+                }
+
+                self.emit_op(OpCode::Array(xs.len()), loc);
+            }
+
+            ExprKind::Dict(entries) => {
+                for (key, value) in entries {
+                    self.expr(key);
+                    self.expr(value);
+                }
+
+                self.emit_op(OpCode::Dict(entries.len()), loc);
+            }
+
+            // the fields are passed to the `Struct` instruction in a dictionary, which is checked
+            // against the fields of the struct when it runs
+            ExprKind::Struct { ty, fields } => {
+                self.struct_fields(fields, loc);
+                self.expr(ty);
+                self.emit_op(OpCode::Struct, loc);
+            }
+
+            ExprKind::Update { value, fields } => {
+                self.expr(value);
+                self.struct_fields(fields, loc);
+                self.emit_op(OpCode::Update, loc);
+            }
+
+            // `[body for bind in source if filter]` is compiled to
+            // `List.map (fn bind = body) (List.filter (fn bind = filter) source)`
+            ExprKind::Comprehension {
+                body,
+                bind,
+                source,
+                filter,
+            } => {
+                self.expr(source);
+
+                if let Some(filter) = filter {
+                    self.emit_list_call("filter", bind, filter, false, loc);
+                }
+
+                // a name used only by the filter is still a used name
+                self.emit_list_call("map", bind, body, filter.is_none(), loc);
+            }
         }
     }
 
@@ -631,8 +1376,8 @@ impl Compiler {
 
         match &node.kind {
             // compiles a `def` statement into a `Savg` instruction
-            StmtKind::Def(Def { bind, value, .. }) => {
-                self.expr(value);
+            StmtKind::Def(Def { bind, value, doc }) => {
+                self.def_value(*bind, value, doc.clone());
                 self.emit_op(OpCode::Savg(*bind), &node.location);
             }
 
@@ -642,26 +1387,9 @@ impl Compiler {
                 self.expr(value);
 
                 // try to match against the value
-                let (_, fix_stack) = self.match_pattern(bind, true, loc);
+                let (_, fails) = self.match_bind(bind, true, loc);
 
-                // emit a jump to ignore the
-                let jmp_label = self.scope().opcodes.len();
-                self.emit_op(OpCode::Jmp(0), loc);
-
-                for offset in fix_stack {
-                    self.scope_mut().opcodes[offset].opcode =
-                        OpCode::Jmf(self.scope().opcodes.len());
-                }
-
-                // emit the call to raise
-                self.emit_const("No match of rhs value".to_string().into(), loc);
-                self.emit_const(Symbol::from("MatchError").into(), loc);
-                self.emit_op(OpCode::Loag("raise".into()), loc);
-                self.emit_op(OpCode::Call(2), loc);
-
-                // patch the jump to the end
-                self.scope_mut().opcodes[jmp_label].opcode =
-                    OpCode::Jmp(self.scope().opcodes.len());
+                self.match_fail(fails.into_iter().collect(), loc);
             }
 
             // compiles a `module` declaration into an YexModule and save the module to a global name
@@ -672,6 +1400,90 @@ impl Compiler {
             } => {
                 self.type_(name, variants, members, &node.location);
             }
+
+            // compiles an `import` statement into an `Import` instruction, saving the module to a
+            // global with the same name
+            StmtKind::Import(name) => {
+                self.emit_op(OpCode::Import(*name), loc);
+                self.emit_op(OpCode::Savg(*name), loc);
+            }
+
+            // compiles a struct into a type, storing the names of it's fields in `#fields`
+            StmtKind::Struct {
+                name,
+                fields,
+                members,
+            } => {
+                let mut table = self.methods(name, members, loc);
+                let fields: Vec<Value> = fields.iter().map(|field| (*field).into()).collect();
+                table.insert("#fields".into(), fields.into());
+
+                self.emit_const(YexModule::new(*name, table).into(), loc);
+                self.emit_op(OpCode::Savg(*name), loc);
+            }
+
+            StmtKind::Trait {
+                name,
+                required,
+                provided,
+            } => {
+                self.trait_(name, required, provided, loc);
+            }
+
+            // compiles an `impl` block into an `Impl` instruction, which stores the methods in
+            // the trait
+            StmtKind::Impl {
+                trait_,
+                ty,
+                members,
+            } => {
+                self.emit_op(OpCode::Loag(*trait_), loc);
+                self.emit_op(OpCode::Loag(*ty), loc);
+
+                let methods = self.methods(ty, members, loc);
+                self.emit_const(YexModule::new(*ty, methods).into(), loc);
+                self.emit_op(OpCode::Impl, loc);
+            }
+
+            // compiles a module into a `Savm` instruction, which adds its functions to the module
+            // at the path
+            StmtKind::Module {
+                path,
+                members,
+                consts,
+                private,
+            } => {
+                self.privates.entry(*path).or_default().extend(private);
+
+                self.module = Some(*path);
+                let mut methods = self.methods(path, members, loc);
+                self.module = None;
+
+                // the parser only accepts constants whose value is known, they can use the ones
+                // before them
+                let mut known = Consts::new();
+                for (name, value) in consts {
+                    let value = const_value(value, &known).unwrap();
+                    known.insert(format!("{}.{}", path, name), value.clone());
+                    methods.insert(*name, value);
+                }
+
+                // the private members are stored under a key that can't be written in the source
+                let mut table = EnvTable::new();
+                for (name, method) in methods.iter() {
+                    if private.contains(&name) {
+                        table.insert(YexModule::private(name), method);
+                    } else {
+                        table.insert(name, method);
+                    }
+                }
+
+                self.emit_const(YexModule::new(*path, table).into(), loc);
+                self.emit_op(OpCode::Savm(*path), loc);
+            }
+
+            // the tests and benchmarks are compiled separately, by `compile_blocks`
+            StmtKind::Block { .. } => {}
         };
 
         self.unique_counter = 0;
@@ -684,15 +1496,7 @@ impl Compiler {
         members: &[Def],
         loc: &Location,
     ) {
-        let mut table = EnvTable::new();
-        for m in members {
-            let func = match &m.value.kind {
-                ExprKind::Lambda { args, body } => Value::Fn(self.lambda_expr(args, body, loc)),
-                _ => unreachable!(),
-            };
-
-            table.insert(m.bind, func);
-        }
+        let mut table = self.methods(decl, members, loc);
 
         let index = self.constants.len();
         self.constants.push(YexModule::default().into()); // place-holder, since I'm still building the type I can't emit it yet.
@@ -701,7 +1505,7 @@ impl Compiler {
 
         for (name, args) in variants {
             if args.is_empty() {
-                patch_list.push((name, name.as_str().split('.').last().unwrap().into()));
+                patch_list.push((name, name.as_str().split('.').next_back().unwrap().into()));
                 continue;
             }
 
@@ -712,16 +1516,24 @@ impl Compiler {
             self.emit_op(OpCode::Push(index), loc);
             self.emit_op(OpCode::Tag(*name), loc);
 
-            let Scope { opcodes, .. } = self.scope_stack.pop().unwrap();
+            let (opcodes, _) = self.pop_scope();
 
             let constructor = Fn {
                 body: GcRef::new(FnKind::Bytecode(opcodes)),
                 arity: args.len(),
                 args: stackvec![],
+                info: Some(GcRef::new(FnInfo::new(
+                    *name,
+                    args.iter().map(ToString::to_string).collect(),
+                    None,
+                ))),
+                upvalues: vec![],
+                more: None,
+                variadic: false,
             };
 
             table.insert(
-                name.as_str().split('.').last().unwrap().into(),
+                name.as_str().split('.').next_back().unwrap().into(),
                 constructor.into(),
             );
         }
@@ -733,7 +1545,7 @@ impl Compiler {
                 type_
                     .mut_ref()
                     .fields
-                    .insert(entry, Value::Tagged(clone, *path, vec![].into()));
+                    .insert(entry, Value::tagged(clone, *path, vec![].into()));
             }
         }
 
@@ -742,11 +1554,407 @@ impl Compiler {
         self.emit_op(OpCode::Savg(*decl), loc);
     }
 
-    pub fn compile_stmts(mut self, stmts: &[Stmt]) -> (Vec<OpCodeMetadata>, Vec<Value>) {
+    /// Checks if `ty.method` references a private member of the module whose functions are being
+    /// compiled, through its full path, like `Foo.Bar.helper` inside of `module Foo.Bar`
+    fn is_private(&self, ty: &Expr, method: Symbol) -> bool {
+        let module = match self.module {
+            Some(module) => module,
+            None => return false,
+        };
+
+        self.global_path(ty).as_deref() == Some(module.as_str())
+            && self
+                .privates
+                .get(&module)
+                .is_some_and(|names| names.contains(&method))
+    }
+
+    /// The dotted path of a global, or of a member of a global module, like `Foo.Bar.baz`, `None`
+    /// if the expression isn't a path or if its first name is a local variable
+    fn global_path(&self, expr: &Expr) -> Option<String> {
+        match &expr.kind {
+            ExprKind::MethodRef { ty, method } => {
+                Some(format!("{}.{}", self.global_path(ty)?, method))
+            }
+            ExprKind::Var(name)
+                if !self.scope_stack.iter().any(|s| s.locals.contains_key(name)) =>
+            {
+                Some(name.to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// Warns about calls that pass more arguments than the function takes, which always raise a
+    /// `CallError`, if the function is defined by the program or by the prelude
+    fn check_arity(&mut self, callee: &Expr, passed: usize, loc: &Location) {
+        let path = match self.global_path(callee) {
+            Some(path) => path,
+            None => return,
+        };
+
+        let arity = match self.arities.get(&Symbol::from(path.as_str())) {
+            Some(arity) => *arity,
+            None => self.builtin_arity(&path),
+        };
+
+        if let Some(arity) = arity.filter(|arity| passed > *arity) {
+            let plural = if arity == 1 { "" } else { "s" };
+            self.warn(
+                codes::TOO_MANY_ARGUMENTS,
+                loc,
+                format!(
+                    "'{}' takes {} argument{}, but it's called with {}",
+                    path, arity, plural, passed
+                ),
+            );
+        }
+    }
+
+    /// The most arguments taken by a function of the prelude, following the overloads created by
+    /// the default arguments
+    fn builtin_arity(&mut self, path: &str) -> Option<usize> {
+        let mut segments = path.split('.');
+        let mut value = self
+            .builtins
+            .get_or_insert_with(vm::prelude)
+            .get(&Symbol::from(segments.next()?))?;
+
+        for segment in segments {
+            value = match &value {
+                Value::Module(module) => module.fields.get(&Symbol::from(segment))?,
+                _ => return None,
+            };
+        }
+
+        let mut fun = match value {
+            Value::Fn(fun) => (*fun).clone(),
+            _ => return None,
+        };
+        loop {
+            if fun.variadic {
+                return None;
+            }
+            match fun.overload() {
+                Some(more) => fun = more,
+                None => return Some(fun.arity),
+            }
+        }
+    }
+
+    /// Pushes the fields of a struct literal or update in a dictionary
+    fn struct_fields(&mut self, fields: &[(VarDecl, Expr)], loc: &Location) {
+        for (name, value) in fields {
+            self.emit_lit(&Literal::Sym(*name), loc);
+            self.expr(value);
+        }
+
+        self.emit_op(OpCode::Dict(fields.len()), loc);
+    }
+
+    /// Compiles the methods of a type or a module, or of the implementation of a trait for it
+    fn methods(&mut self, ty: &VarDecl, members: &[Def], loc: &Location) -> EnvTable {
+        let mut table = EnvTable::new();
+        for m in members {
+            let func = match &m.value.kind {
+                ExprKind::Lambda { args, defaults, .. } => {
+                    let name = format!("{}.{}", ty, m.bind);
+                    // types and modules are declared at the top level, so there's nothing to
+                    // capture
+                    let func = self.def_fn(name.into(), &m.value, m.doc.clone()).0;
+                    self.const_defaults(func, args, defaults, loc)
+                }
+                _ => unreachable!(),
+            };
+
+            table.insert(m.bind, func);
+        }
+
+        table
+    }
+
+    /// Compiles a trait into a type whose methods find the implementation for the type of their
+    /// first argument, the default implementations are stored as `#method`
+    fn trait_(
+        &mut self,
+        decl: &VarDecl,
+        required: &[(VarDecl, Vec<VarDecl>, Option<String>)],
+        provided: &[Def],
+        loc: &Location,
+    ) {
+        let index = self.constants.len();
+        self.constants.push(YexModule::default().into()); // place-holder, the methods need to reference the trait
+
+        let mut table = EnvTable::new();
+        let mut methods: Vec<(Symbol, Vec<String>, usize, Option<String>)> = required
+            .iter()
+            .map(|(name, args, doc)| {
+                (
+                    *name,
+                    args.iter().map(ToString::to_string).collect(),
+                    args.len(),
+                    doc.clone(),
+                )
+            })
+            .collect();
+
+        for (name, default) in self.methods(decl, provided, loc).iter() {
+            let Value::Fn(fun) = &default else {
+                unreachable!()
+            };
+            let (params, doc) = match &fun.info {
+                Some(info) => (info.params.clone(), info.doc.clone()),
+                None => (vec![], None),
+            };
+            methods.push((name, params, fun.arity, doc));
+            table.insert(format!("#{}", name).into(), default);
+        }
+
+        for (name, args, arity, doc) in methods {
+            self.scope_stack.push(Scope::new());
+
+            // the arguments are already on the stack, with the receiver on the top
+            self.emit_op(OpCode::Push(index), loc);
+            self.emit_op(OpCode::Dispatch(name), loc);
+            self.emit_op(OpCode::TCall(arity), loc);
+
+            let (opcodes, _) = self.pop_scope();
+
+            let method = Fn {
+                body: GcRef::new(FnKind::Bytecode(opcodes)),
+                arity,
+                args: stackvec![],
+                info: Some(GcRef::new(FnInfo::new(
+                    format!("{}.{}", decl, name),
+                    args,
+                    doc,
+                ))),
+                upvalues: vec![],
+                more: None,
+                variadic: false,
+            };
+
+            table.insert(name, method.into());
+        }
+
+        self.constants[index] = Value::Module(GcRef::new(YexModule::new(*decl, table)));
+        self.emit_op(OpCode::Push(index), loc);
+        self.emit_op(OpCode::Savg(*decl), loc);
+    }
+
+    /// Compiles the statements along with the body of each block of the given kind, which runs
+    /// after the statements, in the same globals
+    pub fn compile_blocks(
+        mut self,
+        stmts: &[Stmt],
+        wanted: BlockKind,
+    ) -> (Vec<OpCodeMetadata>, Vec<Value>, Vec<Block>, Vec<Diagnostic>) {
+        let bytecode = self.program(stmts);
+
+        let mut blocks = vec![];
+        for stmt in stmts {
+            match &stmt.kind {
+                StmtKind::Block { kind, name, body } if *kind == wanted => {
+                    self.scope_stack.push(Scope::new());
+                    self.expr(body);
+                    blocks.push(Block {
+                        name: name.clone(),
+                        location: stmt.location,
+                        bytecode: self.pop_scope().0,
+                    });
+                }
+                _ => (),
+            }
+        }
+
+        (bytecode, self.constants, blocks, self.diagnostics)
+    }
+
+    /// Stores the arities and the annotations of the functions defined by a statement, so the
+    /// calls to them are checked by `check_arity` and `check_call`
+    fn declare_functions(&mut self, stmt: &Stmt) {
+        match &stmt.kind {
+            StmtKind::Def(Def { bind, value, .. }) => self.declare_fn(*bind, value),
+            StmtKind::Let { bind, value } => {
+                for name in bind.bindings() {
+                    match bind {
+                        Pattern::Id(..) => self.declare_fn(name, value),
+                        _ => {
+                            self.arities.insert(name, None);
+                            self.signatures.remove(&name);
+                        }
+                    }
+                }
+            }
+            StmtKind::Type {
+                name,
+                variants,
+                members,
+            } => {
+                // the variants without fields aren't functions
+                for (variant, fields) in variants {
+                    let arity = Some(fields.len()).filter(|n| *n > 0);
+                    self.arities.insert(*variant, arity);
+                }
+                self.declare_members(name, members);
+            }
+            StmtKind::Struct { name, members, .. } => self.declare_members(name, members),
+            StmtKind::Module { path, members, .. } => self.declare_members(path, members),
+            _ => {}
+        }
+    }
+
+    fn declare_members(&mut self, path: &VarDecl, members: &[Def]) {
+        for member in members {
+            let name = format!("{}.{}", path, member.bind).into();
+            self.declare_fn(name, &member.value);
+        }
+    }
+
+    /// Stores the arity and the annotations of a global, `value` may not be a function
+    fn declare_fn(&mut self, name: Symbol, value: &Expr) {
+        // the most arguments a function takes, since the last ones may have default values
+        let arity = match &value.kind {
+            ExprKind::Lambda {
+                args,
+                variadic: false,
+                ..
+            } => Some(args.len()),
+            _ => None,
+        };
+        self.arities.insert(name, arity);
+
+        match value.kind {
+            ExprKind::Lambda { .. } => self.signatures.insert(name, Signature::of(value)),
+            _ => self.signatures.remove(&name),
+        };
+    }
+
+    /// Compiles the statements, also returning the diagnostics found in them
+    pub fn compile_stmts(
+        mut self,
+        stmts: &[Stmt],
+    ) -> (Vec<OpCodeMetadata>, Vec<Value>, Vec<Diagnostic>) {
+        let bytecode = self.program(stmts);
+        (bytecode, self.constants, self.diagnostics)
+    }
+
+    fn program(&mut self, stmts: &[Stmt]) -> Vec<OpCodeMetadata> {
+        // types can be used before being declared
+        for stmt in stmts {
+            if let StmtKind::Type { name, variants, .. } = &stmt.kind {
+                let variants = variants.iter().map(|(tag, _)| *tag).collect();
+                self.types.insert(*name, variants);
+            }
+            self.declare_functions(stmt);
+        }
+
         self.scope_stack.push(Scope::new());
         for stmt in stmts {
             self.stmt(stmt);
         }
-        (self.scope_stack.pop().unwrap().opcodes, self.constants)
+        self.pop_scope().0
     }
 }
+
+#[test]
+fn unused_and_shadowed() {
+    let source = "def f x y = x
+def g _x = 1
+def h x =
+    let x = x + 1 in
+    let n = 2 in
+    let _m = 3 in
+    x
+def k xs = [1 for x in xs if x > 0]
+def p (a, b) (Some c) = a
+";
+    let (_, _, diagnostics) = crate::parse_with_diagnostics(source).unwrap();
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.location.line, d.location.column, d.message.as_str()))
+        .collect();
+    // the names of patterns are reported where they're bound
+    assert_eq!(
+        messages,
+        [
+            (1, 9, "Unused variable 'y'"),
+            (4, 9, "'x' shadows a variable declared before it"),
+            (5, 9, "Unused variable 'n'"),
+            (9, 11, "Unused variable 'b'"),
+            (9, 20, "Unused variable 'c'"),
+        ]
+    );
+    assert_eq!(
+        diagnostics[0].help.as_deref(),
+        Some("prefix it with '_' to ignore it")
+    );
+}
+
+#[test]
+fn arity_mismatches() {
+    let source = "def f x (y = 1) = x + y
+def g ...xs = xs
+module M with def one x = x end
+let a = f 1 2 3
+let b = f 1 2 + g 1 2 3
+let c = M.one 1 2
+let d = List.map f [1] 3
+let e = let f = fn x _y _z = x in f 1 2 3
+";
+    let (_, _, diagnostics) = crate::parse_with_diagnostics(source).unwrap();
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.location.line, d.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (4, "'f' takes 2 arguments, but it's called with 3"),
+            (6, "'M.one' takes 1 argument, but it's called with 2"),
+            (7, "'List.map' takes 2 arguments, but it's called with 3"),
+        ]
+    );
+}
+
+#[test]
+fn tail_calls() {
+    // the calls to `g` in the body of `f`
+    let calls = |source: &str| -> Vec<OpCode> {
+        let (_, constants) = crate::parse(source).unwrap();
+        let code = constants
+            .iter()
+            .find_map(|value| match value {
+                Value::Fn(fun) => match &*fun.body {
+                    FnKind::Bytecode(code) => Some(code.clone()),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .unwrap();
+
+        code.windows(2)
+            .filter(|ops| ops[0].opcode == OpCode::Loag("g".into()))
+            .map(|ops| ops[1].opcode)
+            .collect()
+    };
+
+    assert_eq!(
+        calls("def f x = if x then g x else x && g x"),
+        [OpCode::TCall(1), OpCode::TCall(1)]
+    );
+    assert_eq!(
+        calls("def f x = x || g (g x)"),
+        [OpCode::Call(1), OpCode::TCall(1)]
+    );
+    assert_eq!(
+        calls("def f x = let y = g x in match y with | 1 -> g y | _ -> y"),
+        [OpCode::Call(1), OpCode::TCall(1)]
+    );
+    // the `try` block must still be running when the call returns, unlike its rescue arms
+    assert_eq!(
+        calls("def f x = try g x rescue e g e"),
+        [OpCode::Call(1), OpCode::TCall(1)]
+    );
+    assert_eq!(calls("def f x = g x + 1"), [OpCode::Call(1)]);
+}
@@ -0,0 +1,140 @@
+use vm::{OpCode, OpCodeMetadata};
+
+/// Removes the obviously useless instructions of a function, like a value that's pushed and
+/// immediately popped or a jump to the next instruction, patching the jumps to the new offsets
+///
+/// Instructions targeted by a `Try` are never removed, since the vm skips the instruction at the
+/// target when an error is rescued
+pub fn optimize(code: &mut Vec<OpCodeMetadata>) {
+    // removing an instruction may expose a new pair, but a few passes are always enough
+    for _ in 0..4 {
+        thread_jumps(code);
+        if !remove_waste(code) {
+            break;
+        }
+    }
+}
+
+/// Makes jumps to an unconditional jump go straight to its target
+fn thread_jumps(code: &mut [OpCodeMetadata]) {
+    for ip in 0..code.len() {
+        let op = code[ip].opcode;
+        if !matches!(op, OpCode::Jmp(_) | OpCode::Jmf(_)) {
+            continue;
+        }
+
+        let mut t = op.target().unwrap();
+        // bounded, so a loop of jumps can't hang the compiler
+        for _ in 0..code.len() {
+            match code.get(t).map(|op| op.opcode) {
+                Some(OpCode::Jmp(next)) if next != t => t = next,
+                _ => break,
+            }
+        }
+        code[ip].opcode = op.retarget(t);
+    }
+}
+
+/// Removes the wasted instructions, returning if anything was removed
+fn remove_waste(code: &mut Vec<OpCodeMetadata>) -> bool {
+    let mut targeted = vec![false; code.len() + 1];
+    let mut protected = vec![false; code.len() + 1];
+    for op in code.iter() {
+        match op.opcode {
+            OpCode::Try(t) => protected[t] = true,
+            op => op.target().into_iter().for_each(|t| targeted[t] = true),
+        }
+    }
+
+    let mut keep = vec![true; code.len()];
+    let mut ip = 0;
+    while ip < code.len() {
+        let op = code[ip].opcode;
+        let next = code.get(ip + 1).map(|op| op.opcode);
+
+        if protected[ip] {
+            ip += 1;
+            continue;
+        }
+
+        match (op, next) {
+            // a value without side effects that's discarded right away, the pop can't be the
+            // target of a jump, since that path still needs it
+            (
+                OpCode::Push(_) | OpCode::Load(_) | OpCode::LoadUpval(_) | OpCode::Dup,
+                Some(OpCode::Pop),
+            ) if !targeted[ip + 1] && !protected[ip + 1] => {
+                keep[ip] = false;
+                keep[ip + 1] = false;
+                ip += 2;
+                continue;
+            }
+            (OpCode::Nop, _) => keep[ip] = false,
+            (OpCode::Jmp(t), _) if t == ip + 1 => keep[ip] = false,
+            // the condition still needs to be popped
+            (OpCode::Jmf(t), _) if t == ip + 1 => code[ip].opcode = OpCode::Pop,
+            _ => (),
+        }
+
+        ip += 1;
+    }
+
+    if keep.iter().all(|keep| *keep) {
+        return false;
+    }
+
+    // the new offset of every instruction, a removed instruction maps to the next kept one
+    let mut offsets = Vec::with_capacity(code.len() + 1);
+    let mut kept = 0;
+    for keep in keep.iter() {
+        offsets.push(kept);
+        kept += *keep as usize;
+    }
+    offsets.push(kept);
+
+    let mut ip = 0;
+    code.retain(|_| {
+        ip += 1;
+        keep[ip - 1]
+    });
+
+    for op in code.iter_mut() {
+        if let Some(t) = op.opcode.target() {
+            op.opcode = op.opcode.retarget(offsets[t]);
+        }
+    }
+
+    true
+}
+
+#[test]
+fn removes_waste() {
+    let ops = |ops: &[OpCode]| -> Vec<OpCodeMetadata> {
+        ops.iter()
+            .map(|op| OpCodeMetadata::new(1, 1, *op))
+            .collect()
+    };
+
+    // push 0; pop; jmf 4; jmp 4; push 1
+    let mut code = ops(&[
+        OpCode::Push(0),
+        OpCode::Pop,
+        OpCode::Jmf(3),
+        OpCode::Jmp(4),
+        OpCode::Push(1),
+    ]);
+    optimize(&mut code);
+
+    let code: Vec<_> = code.iter().map(|op| op.opcode).collect();
+    assert_eq!(code, [OpCode::Pop, OpCode::Push(1)]);
+
+    // the pop is the target of a jump, so it's needed
+    let mut code = ops(&[
+        OpCode::Jmf(2),
+        OpCode::Push(0),
+        OpCode::Pop,
+        OpCode::Push(1),
+    ]);
+    optimize(&mut code);
+    assert_eq!(code.len(), 4);
+}
@@ -0,0 +1,339 @@
+//! Warnings for the values whose types are known while compiling and that are used in a way that
+//! always fails, like calling a number or adding a list to a string, the types come from the
+//! literals and from the annotations, like `(x is Num)`, which aren't checked at runtime
+
+use vm::{OpCode, Symbol};
+
+use super::Compiler;
+use crate::{
+    error::codes,
+    parser::ast::{BinOp, Expr, ExprKind, Literal, Location, Pattern, UnOp, VarDecl},
+};
+
+/// The types whose values can't define operators, so an operation on them fails if it isn't
+/// implemented by the virtual machine
+const BUILTIN: &[&str] = &[
+    "List", "Array", "Seq", "Fn", "Num", "Int", "Str", "Bytes", "Bool", "Nil", "Sym", "Tuple",
+    "Dict", "Range",
+];
+
+/// The arguments of a function and their annotated types
+#[derive(Debug, Clone, Default)]
+pub(super) struct Signature {
+    /// The annotated arguments, with their names
+    pub args: Vec<Option<(VarDecl, VarDecl)>>,
+    /// The annotated return type
+    pub returns: Option<VarDecl>,
+    /// Whether the last argument collects the extra arguments
+    pub variadic: bool,
+    /// The least arguments that call the function instead of partially applying it
+    required: usize,
+}
+
+impl Signature {
+    /// The signature of a lambda, or of a function without annotations if the expression isn't one
+    pub fn of(lambda: &Expr) -> Self {
+        match &lambda.kind {
+            ExprKind::Lambda {
+                args,
+                defaults,
+                variadic,
+                types,
+                returns,
+                ..
+            } => Signature {
+                args: types
+                    .iter()
+                    .zip(args)
+                    .map(|(ty, arg)| match (ty, arg) {
+                        (Some(ty), Pattern::Id(name, _)) => Some((*name, *ty)),
+                        _ => None,
+                    })
+                    .collect(),
+                returns: *returns,
+                variadic: *variadic,
+                required: args.len() - defaults.len() - usize::from(*variadic),
+            },
+            _ => Signature::default(),
+        }
+    }
+}
+
+fn builtin(ty: Symbol) -> bool {
+    BUILTIN.contains(&ty.as_str())
+}
+
+fn numeric(ty: Symbol) -> bool {
+    matches!(ty.as_str(), "Int" | "Num")
+}
+
+/// Checks if a value of type `found` can be used where `expected` is, an `Int` is also a number
+fn compatible(expected: Symbol, found: Symbol) -> bool {
+    expected == found || (expected.as_str() == "Num" && found.as_str() == "Int")
+}
+
+/// The type of the result of an operator, `None` if it's unknown or if it always fails
+fn binary_type(op: BinOp, left: Symbol, right: Symbol) -> Option<&'static str> {
+    let (left, right) = (left.as_str(), right.as_str());
+    let ty = match op {
+        BinOp::Add | BinOp::Sub | BinOp::Mul | BinOp::Rem => match (left, right) {
+            ("Int", "Int") => "Int",
+            ("Int" | "Num", "Int" | "Num") => "Num",
+            ("Str", "Str") => "Str",
+            _ => return None,
+        },
+        BinOp::Div => match (left, right) {
+            ("Int" | "Num", "Int" | "Num") => "Num",
+            ("Str", "Str") => "Str",
+            _ => return None,
+        },
+        BinOp::BitAnd | BinOp::BitOr | BinOp::BitXor | BinOp::Shl | BinOp::Shr => {
+            match (left, right) {
+                ("Int", "Int") => "Int",
+                _ => return None,
+            }
+        }
+        BinOp::Less | BinOp::LessEq | BinOp::Greater | BinOp::GreaterEq | BinOp::Eq | BinOp::Ne
+            if BUILTIN.contains(&left) && BUILTIN.contains(&right) =>
+        {
+            "Bool"
+        }
+        _ => return None,
+    };
+    Some(ty)
+}
+
+/// The operator of the arithmetic operations that are checked
+fn arithmetic(op: BinOp) -> Option<&'static str> {
+    match op {
+        BinOp::Add => Some("+"),
+        BinOp::Sub => Some("-"),
+        BinOp::Mul => Some("*"),
+        BinOp::Div => Some("/"),
+        BinOp::Rem => Some("%"),
+        _ => None,
+    }
+}
+
+impl Compiler {
+    /// The type of an expression, if it's known while compiling, the locals have the type of
+    /// their annotation or of the value they were bound to
+    pub(super) fn infer(&self, expr: &Expr) -> Option<Symbol> {
+        let ty = match &expr.kind {
+            ExprKind::Lit(lit) => match lit {
+                Literal::Num(_) => "Num",
+                Literal::Int(_) => "Int",
+                Literal::Str(_) => "Str",
+                Literal::Bytes(_) => "Bytes",
+                Literal::Bool(_) => "Bool",
+                Literal::Sym(_) => "Sym",
+                Literal::Unit => "Nil",
+            },
+            ExprKind::List(_) | ExprKind::Cons { .. } | ExprKind::Comprehension { .. } => "List",
+            ExprKind::Array(_) => "Array",
+            ExprKind::Tuple(_) => "Tuple",
+            ExprKind::Dict(_) => "Dict",
+            ExprKind::Interp(_) => "Str",
+            ExprKind::Lambda { .. } => "Fn",
+            ExprKind::Var(name) if self.scope().locals.contains_key(name) => {
+                return self.scope().types.get(name).copied()
+            }
+            ExprKind::App { callee, args, .. } => {
+                let path = self.global_path(callee)?;
+                let signature = self.signatures.get(&Symbol::from(path.as_str()))?;
+                return signature
+                    .returns
+                    .filter(|_| args.len() >= signature.required);
+            }
+            ExprKind::Binary { op: BinOp::Is, .. } => "Bool",
+            ExprKind::Binary { left, op, right } => {
+                binary_type(*op, self.infer(left)?, self.infer(right)?)?
+            }
+            ExprKind::UnOp(UnOp::Not, _) => "Bool",
+            ExprKind::UnOp(UnOp::Neg, right) => return self.infer(right).filter(|ty| numeric(*ty)),
+            ExprKind::If { then, else_, .. } => {
+                let ty = self.infer(then)?;
+                return (self.infer(else_)? == ty).then_some(ty);
+            }
+            _ => return None,
+        };
+
+        Some(Symbol::from(ty))
+    }
+
+    /// Warns about calling a value that isn't a function, and about the arguments whose types
+    /// don't match the annotations of the function
+    pub(super) fn check_call(&mut self, callee: &Expr, args: &[Expr], loc: &Location) {
+        if let Some(ty) = self.infer(callee) {
+            if builtin(ty) && ty.as_str() != "Fn" {
+                self.warn(
+                    codes::TYPE_MISMATCH,
+                    loc,
+                    format!("A value of type '{}' can't be called", ty),
+                );
+            }
+        }
+
+        let path = match self.global_path(callee) {
+            Some(path) => path,
+            None => return,
+        };
+        let signature = match self.signatures.get(&Symbol::from(path.as_str())) {
+            Some(signature) => signature.clone(),
+            None => return,
+        };
+
+        for (arg, param) in args.iter().zip(signature.args) {
+            let (name, expected) = match param {
+                Some(param) => param,
+                None => continue,
+            };
+
+            match self.infer(arg) {
+                Some(found) if !compatible(expected, found) => {
+                    self.warn(
+                        codes::TYPE_MISMATCH,
+                        &arg.location,
+                        format!(
+                            "Expected '{}' for '{}' of '{}', found '{}'",
+                            expected, name, path, found
+                        ),
+                    );
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Warns about an arithmetic operation between values of types that don't support it
+    pub(super) fn check_binary(&mut self, left: &Expr, op: BinOp, right: &Expr, loc: &Location) {
+        let symbol = match arithmetic(op) {
+            Some(symbol) => symbol,
+            None => return,
+        };
+
+        let (left, right) = match (self.infer(left), self.infer(right)) {
+            (Some(left), Some(right)) if builtin(left) && builtin(right) => (left, right),
+            _ => return,
+        };
+
+        if binary_type(op, left, right).is_none() {
+            self.warn(
+                codes::TYPE_MISMATCH,
+                loc,
+                format!("Can't apply '{}' to '{}' and '{}'", symbol, left, right),
+            );
+        }
+    }
+
+    /// The opcode that applies an operator to values of the inferred types without looking for
+    /// their operator methods, `None` if the types are unknown or the operator isn't specialized
+    pub(super) fn specialized(&self, left: &Expr, op: BinOp, right: &Expr) -> Option<OpCode> {
+        if op != BinOp::Add {
+            return None;
+        }
+
+        // the sum of two integers is already done without looking for methods
+        match (self.infer(left)?.as_str(), self.infer(right)?.as_str()) {
+            ("Num", "Int" | "Num") | ("Int", "Num") => Some(OpCode::AddNum),
+            ("Str", "Str") => Some(OpCode::ConcatStr),
+            _ => None,
+        }
+    }
+
+    /// Warns about a function whose body doesn't have its annotated return type
+    pub(super) fn check_return(&mut self, returns: Option<VarDecl>, body: &Expr) {
+        let (expected, found) = match (returns, self.infer(body)) {
+            (Some(expected), Some(found)) => (expected, found),
+            _ => return,
+        };
+
+        if !compatible(expected, found) {
+            self.warn(
+                codes::TYPE_MISMATCH,
+                &body.location,
+                format!(
+                    "Expected the function to return '{}', found '{}'",
+                    expected, found
+                ),
+            );
+        }
+    }
+}
+
+#[test]
+fn type_mismatches() {
+    let source = "def add (x is Num) (y is Num) is Num = x + y
+def name (n is Int) is Str = n
+def f (xs is List) (s is Str) =
+    let n = 1 in
+    let _ = n 2 in
+    let _ = xs + s in
+    let x = add 1 2 in
+    let _ = x + 1.5 in
+    xs
+let a = add 1 \"b\"
+let b = add 1.5 2
+let c = [1] + \"a\"
+let d = 1 + 2.5
+let e = let s = \"s\" in s + 1
+let g = fn (v is Vec) = v + v
+";
+    let (_, _, diagnostics) = crate::parse_with_diagnostics(source).unwrap();
+    let messages: Vec<_> = diagnostics
+        .iter()
+        .map(|d| (d.location.line, d.message.as_str()))
+        .collect();
+    assert_eq!(
+        messages,
+        [
+            (2, "Expected the function to return 'Str', found 'Int'"),
+            (5, "A value of type 'Int' can't be called"),
+            (6, "Can't apply '+' to 'List' and 'Str'"),
+            (10, "Expected 'Num' for 'y' of 'add', found 'Str'"),
+            (12, "Can't apply '+' to 'List' and 'Str'"),
+            (14, "Can't apply '+' to 'Str' and 'Int'"),
+        ]
+    );
+}
+
+#[test]
+fn specialized_operators() {
+    use vm::{FnKind, Value, VirtualMachine};
+
+    let source = "def f (x is Num) (s is Str) y = (x + 1.5, s + \"!\", y + 1)
+let a = f 1 \"a\" 2
+let b = f 0.5 [1] 2";
+    let (bt, ct) = crate::parse(source).unwrap();
+
+    let code = ct
+        .iter()
+        .find_map(|value| match value {
+            Value::Fn(fun) => match &*fun.body {
+                FnKind::Bytecode(code) => Some(code.clone()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .unwrap();
+    let adds: Vec<_> = code
+        .iter()
+        .map(|op| op.opcode)
+        .filter(|op| matches!(op, OpCode::Add | OpCode::AddNum | OpCode::ConcatStr))
+        .collect();
+    // the items of a tuple are pushed from the last one
+    assert_eq!(adds, [OpCode::Add, OpCode::ConcatStr, OpCode::AddNum]);
+
+    // the annotations aren't checked, so the values of other types are added like with `+`
+    let mut vm = VirtualMachine::default();
+    let bt = vm.load(bt, ct);
+    let err = vm.run(&bt).unwrap_err();
+    assert!(
+        err.msg.contains("Cannot apply 'Add' operator"),
+        "{}",
+        err.msg
+    );
+
+    let a = vm.get_global("a").unwrap();
+    assert_eq!(format!("{}", a), "(2.5, \"a!\", 3)");
+}
@@ -1,9 +1,45 @@
+use std::fmt::Write;
+
+use crate::parser::ast::{Location, Span};
+
+/// The codes of the errors and warnings, shown next to their severity like `error[E0002]`
+pub mod codes {
+    /// A character that doesn't start any token, or a malformed literal
+    pub const INVALID_TOKEN: &str = "E0001";
+    /// A token where the grammar doesn't allow it
+    pub const SYNTAX: &str = "E0002";
+    /// A keyword argument that the called function doesn't take
+    pub const UNKNOWN_KEYWORD: &str = "E0003";
+    /// A definition that can't be compiled, like clauses with different arities
+    pub const INVALID_DEFINITION: &str = "E0004";
+
+    /// A local that's declared but never used
+    pub const UNUSED_VARIABLE: &str = "W0001";
+    /// A pattern that binds a name of a local declared before it
+    pub const SHADOWED_VARIABLE: &str = "W0002";
+    /// A match that doesn't cover every variant of a type
+    pub const NON_EXHAUSTIVE: &str = "W0003";
+    /// A match arm that never runs, since the arms before it match its values
+    pub const UNREACHABLE_ARM: &str = "W0004";
+    /// An `if` whose condition is known while compiling, so one branch never runs
+    pub const CONSTANT_CONDITION: &str = "W0005";
+    /// A call with more arguments than the function takes
+    pub const TOO_MANY_ARGUMENTS: &str = "W0006";
+    /// A value used in a way that its type doesn't support, like calling a number
+    pub const TYPE_MISMATCH: &str = "W0007";
+}
+
 #[derive(Debug, PartialEq, Eq, Clone, Copy)]
 /// Parse errors
 pub struct ParseError {
     line: usize,
     column: usize,
+    /// The bytes of the token that caused the error, empty if it isn't known
+    span: Span,
+    code: &'static str,
     message: &'static str,
+    help: Option<&'static str>,
+    incomplete: bool,
 }
 
 pub type ParseResult<T> = Result<T, ParseError>;
@@ -14,12 +50,307 @@ impl std::fmt::Display for ParseError {
     }
 }
 
+/// How serious a [`Diagnostic`] is
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Severity {
+    /// The code can't be compiled
+    Error,
+    /// The code compiles, but it probably doesn't do what was intended
+    Warning,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// An issue found while compiling, like an unreachable match arm
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Diagnostic {
+    /// How serious the issue is
+    pub severity: Severity,
+    /// Identifies the kind of issue, one of the [`codes`]
+    pub code: &'static str,
+    /// Where the issue is in the source code
+    pub location: Location,
+    /// The bytes of the source the issue is about, when they're known
+    pub span: Option<Span>,
+    /// What's wrong with the code
+    pub message: String,
+    /// How the issue can be fixed
+    pub help: Option<String>,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "[{}:{}] {}[{}]: {}",
+            self.location.line, self.location.column, self.severity, self.code, self.message
+        )
+    }
+}
+
+/// Syntax errors are reported like the errors found while compiling
+impl From<ParseError> for Diagnostic {
+    fn from(e: ParseError) -> Self {
+        Self {
+            severity: Severity::Error,
+            code: e.code,
+            location: e.location(),
+            span: (e.span != Span::default()).then_some(e.span),
+            message: e.message.to_string(),
+            help: e.help.map(String::from),
+        }
+    }
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BLUE: &str = "\x1b[1;34m";
+
+impl Diagnostic {
+    /// Formats the diagnostic like rustc does, with the line of `source` it's about and a caret
+    /// under the offending token, `file` is the name shown before the location
+    ///
+    /// ```text
+    /// error[E0002]: unexpected token ')'
+    ///  --> main.yex:1:9
+    ///   |
+    /// 1 | let x = )
+    ///   |         ^
+    /// ```
+    ///
+    /// `color` highlights it with ANSI escapes, for terminals
+    pub fn render(&self, file: &str, source: &str, color: bool) -> String {
+        let paint = |code: &'static str| if color { code } else { "" };
+        let severity = match self.severity {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+        };
+
+        let (line, start, width) = self.underline(source);
+        let text = source.lines().nth(line - 1).unwrap_or_default();
+        let gutter = " ".repeat(line.to_string().len());
+
+        // tabs are shown as 4 spaces, so the caret lines up in every terminal
+        let width_of = |s: &str| {
+            s.chars()
+                .map(|c| if c == '\t' { 4 } else { 1 })
+                .sum::<usize>()
+        };
+        let prefix: String = text.chars().take(start).collect();
+        let underlined: String = text.chars().skip(start).take(width).collect();
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}[{}]{}{}: {}{}",
+            paint(severity),
+            self.severity,
+            self.code,
+            paint(RESET),
+            paint(BOLD),
+            self.message,
+            paint(RESET)
+        );
+        let _ = writeln!(
+            out,
+            "{}{}-->{} {}:{}:{}",
+            gutter,
+            paint(BLUE),
+            paint(RESET),
+            file,
+            line,
+            start + 1
+        );
+        let _ = writeln!(out, "{} {}|{}", gutter, paint(BLUE), paint(RESET));
+        let _ = writeln!(
+            out,
+            "{}{} |{} {}",
+            paint(BLUE),
+            line,
+            paint(RESET),
+            text.replace('\t', "    ")
+        );
+        let _ = write!(
+            out,
+            "{} {}|{} {}{}{}{}",
+            gutter,
+            paint(BLUE),
+            paint(RESET),
+            " ".repeat(width_of(&prefix)),
+            paint(severity),
+            "^".repeat(width_of(&underlined).max(1)),
+            paint(RESET)
+        );
+
+        if let Some(help) = &self.help {
+            let _ = write!(
+                out,
+                "\n{} {}={} {}help{}: {}",
+                gutter,
+                paint(BLUE),
+                paint(RESET),
+                paint(BOLD),
+                paint(RESET),
+                help
+            );
+        }
+        out
+    }
+
+    /// Where the diagnostic starts in `source`, counting the columns from the first character of
+    /// each line, unlike [`Diagnostic::location`], which is where the lexer was
+    pub fn position(&self, source: &str) -> Location {
+        let (line, start, _) = self.underline(source);
+        Location {
+            line,
+            column: start + 1,
+        }
+    }
+
+    /// Finds the line (starting from 1) of the source the diagnostic is about, along with the
+    /// character where the underline starts and how many characters it covers
+    fn underline(&self, source: &str) -> (usize, usize, usize) {
+        if let Some(span) = self.span.filter(|span| span.start <= source.len()) {
+            // the end of the source is shown after its last line, instead of the empty line after
+            // the last newline
+            let span = if span.start == source.len() {
+                let end = source.trim_end().len();
+                Span { start: end, end }
+            } else {
+                span
+            };
+
+            let before = &source[..span.start];
+            let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+            let line = before.matches('\n').count() + 1;
+            let start = before[line_start..].chars().count();
+
+            // spans over more than one line are only underlined in the first one
+            let end = source[span.start..]
+                .find('\n')
+                .map_or(span.end, |idx| (span.start + idx).min(span.end));
+            let width = source.get(span.start..end).map_or(1, |s| s.chars().count());
+            return (line, start, width);
+        }
+
+        word_at(source, self.location)
+    }
+}
+
+/// Finds the line of the word at `location`, along with the character where it starts and how
+/// many characters it has, the location is the last character of a token, so the rest of the word
+/// before it is included
+fn word_at(source: &str, Location { line, column }: Location) -> (usize, usize, usize) {
+    let line = line.max(1);
+    let chars: Vec<char> = source
+        .lines()
+        .nth(line - 1)
+        .unwrap_or_default()
+        .chars()
+        .collect();
+    let last = column.saturating_sub(1).min(chars.len());
+    let is_word = |c: &char| c.is_alphanumeric() || *c == '_';
+    let start = chars[..last]
+        .iter()
+        .rposition(|c| !is_word(c))
+        .map_or(0, |idx| idx + 1);
+    let start = if chars.get(last).is_some_and(is_word) {
+        start
+    } else {
+        last
+    };
+    (line, start, last + 1 - start)
+}
+
+/// The bytes of `source` of the word at `location`, like the one where an instruction that raised
+/// an error was compiled from
+pub fn location_span(source: &str, location: Location) -> Span {
+    let (line, start, width) = word_at(source, location);
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(line - 1)
+        .map(str::len)
+        .sum();
+    let text = source[line_start..].lines().next().unwrap_or_default();
+    let byte = |chars| {
+        text.char_indices()
+            .nth(chars)
+            .map_or(text.len(), |(idx, _)| idx)
+    };
+
+    // the calls end after their last token, so a location after the end of the line is its last
+    // character
+    let len = text.chars().count();
+    let start = start.min(len.saturating_sub(1));
+    Span {
+        start: line_start + byte(start),
+        end: line_start + byte((start + width).min(len)),
+    }
+}
+
 impl ParseError {
     pub(crate) fn throw<T>(line: usize, column: usize, message: String) -> Result<T, Self> {
         Err(Self {
             line,
             column,
+            span: Span::default(),
+            code: codes::SYNTAX,
             message: Box::leak(message.into_boxed_str()),
+            help: None,
+            incomplete: false,
         })
     }
+
+    pub(crate) fn with_span(self, span: Span) -> Self {
+        Self { span, ..self }
+    }
+
+    pub(crate) fn with_code(self, code: &'static str) -> Self {
+        Self { code, ..self }
+    }
+
+    pub(crate) fn with_help(self, help: &'static str) -> Self {
+        Self {
+            help: Some(help),
+            ..self
+        }
+    }
+
+    pub(crate) fn at_eof(self) -> Self {
+        Self {
+            incomplete: true,
+            ..self
+        }
+    }
+
+    /// Where the error is in the source code
+    pub fn location(&self) -> Location {
+        Location {
+            line: self.line,
+            column: self.column,
+        }
+    }
+
+    /// What's wrong with the code
+    pub fn message(&self) -> &str {
+        self.message
+    }
+
+    /// Renders the error along with the source it's about, see [`Diagnostic::render`]
+    pub fn render(&self, file: &str, source: &str, color: bool) -> String {
+        Diagnostic::from(*self).render(file, source, color)
+    }
+
+    /// Checks if the error happened because the source ended too early, like in an unclosed
+    /// `match` expression, so more input could fix it
+    pub fn is_incomplete(&self) -> bool {
+        self.incomplete
+    }
 }
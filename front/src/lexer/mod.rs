@@ -1,7 +1,8 @@
 use vm::Symbol;
 
-use crate::error::ParseError;
-use crate::tokens::{fetch_keyword, Token, TokenType};
+use crate::error::{codes, ParseError};
+use crate::parser::ast::Span;
+use crate::tokens::{fetch_keyword, StrPart, Token, TokenType};
 
 const EOF: char = '\0';
 
@@ -10,6 +11,8 @@ pub struct Lexer {
     line: usize,
     column: usize,
     tokens: Vec<char>,
+    /// The byte offset of each character, followed by the length of the source
+    offsets: Vec<usize>,
     idx: usize,
 }
 
@@ -17,8 +20,16 @@ type Tk = Result<Token, ParseError>;
 
 impl Lexer {
     pub fn new<T: Into<String>>(t: T) -> Self {
+        let source = t.into();
+        let offsets = source
+            .char_indices()
+            .map(|(offset, _)| offset)
+            .chain(std::iter::once(source.len()))
+            .collect();
+
         Self {
-            tokens: t.into().chars().collect(),
+            tokens: source.chars().collect(),
+            offsets,
             line: 1,
             column: 1,
             idx: 0,
@@ -35,8 +46,39 @@ impl Lexer {
         self.idx = idx;
     }
 
+    /// Moves the spans of the tokens `offset` bytes forward, used for sources that are part of a
+    /// bigger one, like the interpolated expressions of strings
+    pub fn set_offset(&mut self, offset: usize) {
+        for byte in &mut self.offsets {
+            *byte += offset;
+        }
+    }
+
+    /// The span from the character at `start` to the current one
+    fn span(&self, start: usize) -> Span {
+        let byte = |idx: usize| self.offsets[idx.min(self.offsets.len() - 1)];
+        Span {
+            start: byte(start),
+            end: byte(self.idx + 1),
+        }
+    }
+
     fn throw<A, T: Into<String>>(&self, str: T) -> Result<A, ParseError> {
-        ParseError::throw(self.line, self.column, str.into())
+        ParseError::throw(self.line, self.column, str.into()).map_err(|e| {
+            e.with_code(codes::INVALID_TOKEN)
+                .with_span(self.span(self.idx))
+        })
+    }
+
+    /// Throws an error about the characters from the `state` to the current one, so the whole
+    /// token is underlined
+    fn throw_from<A, T: Into<String>>(
+        &self,
+        (line, column, idx): (usize, usize, usize),
+        str: T,
+    ) -> Result<A, ParseError> {
+        ParseError::throw(line, column, str.into())
+            .map_err(|e| e.with_code(codes::INVALID_TOKEN).with_span(self.span(idx)))
     }
 
     fn get_char(&self, idx: usize) -> char {
@@ -50,8 +92,9 @@ impl Lexer {
     fn next(&mut self) -> char {
         self.idx += 1;
         match self.current() {
+            // the newline belongs to the next line, before its first column
             '\n' => {
-                self.column = 1;
+                self.column = 0;
                 self.line += 1;
             }
             _ => self.column += 1,
@@ -103,6 +146,8 @@ impl Lexer {
             '0' => EOF,
             '\\' => '\\',
             '"' => '"',
+            '{' => '{',
+            '}' => '}',
             'r' => '\r',
             other => self.throw(format!("Unknow escape char '{}'", other))?,
         };
@@ -110,7 +155,8 @@ impl Lexer {
         Ok(char.into())
     }
 
-    fn take_str(&mut self) -> Result<String, ParseError> {
+    fn take_str(&mut self) -> Result<Vec<StrPart>, ParseError> {
+        let mut parts = vec![];
         let mut item = String::new();
 
         while self.current() != '"' {
@@ -119,6 +165,14 @@ impl Lexer {
                     self.next();
                     self.escape_char()?
                 }
+                '{' => {
+                    self.next();
+                    if !item.is_empty() {
+                        parts.push(StrPart::Lit(std::mem::take(&mut item)));
+                    }
+                    parts.push(self.take_interp()?);
+                    continue;
+                }
                 EOF => self.throw("Unclosed delimiter opened here")?,
                 other => {
                     let other = other.to_string();
@@ -130,7 +184,79 @@ impl Lexer {
             item.push_str(&chr);
         }
         self.back();
-        Ok(item)
+
+        if !item.is_empty() || parts.is_empty() {
+            parts.push(StrPart::Lit(item));
+        }
+        Ok(parts)
+    }
+
+    /// Takes the contents of a byte string, `\xNN` escapes a single byte and the other characters
+    /// are encoded in utf-8, byte strings can't be interpolated
+    fn take_bytes(&mut self) -> Result<Vec<u8>, ParseError> {
+        let mut bytes = vec![];
+
+        while self.current() != '"' {
+            match self.current() {
+                '\\' if self.peek_at(1) == 'x' => {
+                    self.next();
+                    bytes.push(self.take_unicode(2)? as u8);
+                    self.next();
+                }
+                '\\' => {
+                    self.next();
+                    bytes.extend(self.escape_char()?.bytes());
+                }
+                EOF => self.throw("Unclosed delimiter opened here")?,
+                other => {
+                    let mut buf = [0; 4];
+                    bytes.extend(other.encode_utf8(&mut buf).bytes());
+                    self.next();
+                }
+            }
+        }
+
+        Ok(bytes)
+    }
+
+    /// Takes the source of an interpolated expression, until the `}` that closes it
+    fn take_interp(&mut self) -> Result<StrPart, ParseError> {
+        let (line, column) = (self.line, self.column);
+        let offset = self.span(self.idx).start;
+        let mut source = String::new();
+        let mut depth = 0;
+        let mut in_str = false;
+
+        loop {
+            let chr = self.current();
+            match chr {
+                EOF => self.throw("Unclosed interpolation opened here")?,
+                '\\' if in_str => {
+                    source.push(chr);
+                    self.next();
+                }
+                '"' => in_str = !in_str,
+                '{' if !in_str => depth += 1,
+                '}' if !in_str && depth == 0 => break,
+                '}' if !in_str => depth -= 1,
+                _ => (),
+            }
+
+            source.push(self.current());
+            self.next();
+        }
+        self.next();
+
+        if source.trim().is_empty() {
+            return self.throw("Empty interpolation");
+        }
+
+        Ok(StrPart::Expr {
+            source,
+            line,
+            column,
+            offset,
+        })
     }
 
     fn peek_at(&self, n: usize) -> char {
@@ -138,6 +264,7 @@ impl Lexer {
     }
 
     fn get(&mut self) -> Tk {
+        let start = self.idx;
         let tk = match self.current() {
             // comments
             '/' if self.peek_at(1) == '/' => {
@@ -156,7 +283,15 @@ impl Lexer {
 
             '/' => TokenType::Div,
             '*' => TokenType::Mul,
+            '%' if self.peek_at(1) == '{' => {
+                self.next();
+                TokenType::DictOpen
+            }
             '%' => TokenType::Rem,
+            '#' if self.peek_at(1) == '[' => {
+                self.next();
+                TokenType::ArrayOpen
+            }
             '=' if self.peek_at(1) == '=' => {
                 self.next();
                 TokenType::Eq
@@ -196,16 +331,61 @@ impl Lexer {
             }
             '"' => {
                 self.next();
-                let a = TokenType::Str(self.take_str()?);
+                let mut parts = self.take_str()?;
+                let a = if let [StrPart::Lit(s)] = parts.as_mut_slice() {
+                    TokenType::Str(std::mem::take(s))
+                } else {
+                    TokenType::Interp(parts)
+                };
                 self.next();
                 a
             }
+            '0' if matches!(self.peek_at(1), 'x' | 'b') && self.peek_at(2).is_ascii_hexdigit() => {
+                let literal = self.state();
+                let prefix = self.next();
+                let radix = if prefix == 'x' { 16 } else { 2 };
+                self.next();
+                let n = self.take_while(|c| c.is_ascii_hexdigit())?;
+
+                match i64::from_str_radix(&n, radix) {
+                    Ok(n) => TokenType::Int(n),
+                    Err(_) => {
+                        self.throw_from(literal, format!("Can't parse number 0{}{}", prefix, n))?
+                    }
+                }
+            }
             c if c.is_numeric() => {
-                let n = self.take_while(|c| c.is_numeric() || c == '.')?;
-                match n.parse::<f64>() {
-                    Ok(n) => TokenType::Num(n),
-                    Err(_) => self.throw(format!("Can't parse number {}", n))?,
+                let literal = self.state();
+                // `t.0.1` indexes a tuple twice, it isn't `t` and the number `0.1`
+                let index = self.idx > 0
+                    && self.get_char(self.idx - 1) == '.'
+                    && (self.idx < 2 || self.get_char(self.idx - 2) != '.');
+
+                let mut n = self.take_while(|c| c.is_numeric())?;
+                // the dot in `1..10` starts a range, not the decimal part
+                if !index && self.peek_at(1) == '.' && self.peek_at(2).is_numeric() {
+                    self.next();
+                    self.next();
+                    n.push('.');
+                    n.push_str(&self.take_while(|c| c.is_numeric())?);
                 }
+
+                // numbers without a decimal point are integers
+                let tk = if n.contains('.') {
+                    n.parse::<f64>().map(TokenType::Num).ok()
+                } else {
+                    n.parse::<i64>().map(TokenType::Int).ok()
+                };
+
+                match tk {
+                    Some(tk) => tk,
+                    None => self.throw_from(literal, format!("Can't parse number {}", n))?,
+                }
+            }
+            'b' if self.peek_at(1) == '"' => {
+                self.next();
+                self.next();
+                TokenType::Bytes(self.take_bytes()?)
             }
             c if c.is_alphabetic() || c == '_' => {
                 let mut tk = self.take_while(|c| c.is_alphanumeric() || c == '_')?;
@@ -253,6 +433,7 @@ impl Lexer {
                 self.next();
                 TokenType::BitXor
             }
+            '^' => TokenType::Caret,
             ',' => TokenType::Comma,
             ';' => TokenType::Semicolon,
             '<' if self.peek_at(1) == '=' => {
@@ -265,6 +446,20 @@ impl Lexer {
                 TokenType::GreaterEq
             }
             '>' => TokenType::Greater,
+            '.' if self.peek_at(1) == '.' && self.peek_at(2) == '.' => {
+                self.next();
+                self.next();
+                TokenType::Ellipsis
+            }
+            '.' if self.peek_at(1) == '.' && self.peek_at(2) == '=' => {
+                self.next();
+                self.next();
+                TokenType::DotDotEq
+            }
+            '.' if self.peek_at(1) == '.' => {
+                self.next();
+                TokenType::DotDot
+            }
             '.' => TokenType::Dot,
 
             // Logical
@@ -297,6 +492,7 @@ impl Lexer {
         Ok(Token {
             line: self.line,
             column: self.column,
+            span: self.span(start),
             token: tk,
         })
     }
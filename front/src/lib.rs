@@ -8,8 +8,13 @@ mod lexer;
 mod parser;
 mod tokens;
 
+pub use compiler::Block;
 use compiler::Compiler;
-pub use error::ParseError;
+pub use error::{codes, location_span, Diagnostic, ParseError, Severity};
+pub use parser::{
+    ast::{BlockKind, Location, Span},
+    defs::Definition,
+};
 
 use error::ParseResult;
 use lexer::Lexer;
@@ -22,10 +27,115 @@ pub fn parse<T: Into<String>>(str: T) -> ParseResult<(Bytecode, Vec<Value>)> {
     let parser = Parser::new(lexer)?;
     let ast = parser.parse()?;
 
+    let compiler = Compiler::new();
+    let (bytecode, constants, _) = compiler.compile_stmts(&ast);
+    Ok((bytecode, constants))
+}
+
+/// Parses a given string, also returning the diagnostics found while compiling it
+pub fn parse_with_diagnostics<T: Into<String>>(
+    str: T,
+) -> ParseResult<(Bytecode, Vec<Value>, Vec<Diagnostic>)> {
+    let lexer = Lexer::new(str);
+    let parser = Parser::new(lexer)?;
+    let ast = parser.parse()?;
+
     let compiler = Compiler::new();
     Ok(compiler.compile_stmts(&ast))
 }
 
+/// Parses a given string, returning its AST printed as an indented tree, one node per line
+pub fn dump_ast<T: Into<String>>(str: T) -> ParseResult<String> {
+    let lexer = Lexer::new(str);
+    let parser = Parser::new(lexer)?;
+    let ast = parser.parse()?;
+
+    Ok(parser::dump::dump(&ast))
+}
+
+/// Parses a given string, returning the globals it defines along with its diagnostics, used by
+/// editors to find where names are defined
+///
+/// The statements with syntax errors are skipped, so the definitions of the other ones are still
+/// found, the code is only compiled (for the warnings) if it has no syntax errors
+pub fn definitions<T: Into<String>>(str: T) -> (Vec<Definition>, Vec<Diagnostic>) {
+    let lexer = Lexer::new(str);
+    let parser = match Parser::new(lexer) {
+        Ok(parser) => parser,
+        Err(e) => return (vec![], vec![e.into()]),
+    };
+    let (ast, errors) = parser.parse_recovering();
+
+    let defs = parser::defs::definitions(&ast);
+    if !errors.is_empty() {
+        return (defs, errors.into_iter().map(Diagnostic::from).collect());
+    }
+
+    let (_, _, diagnostics) = Compiler::new().compile_stmts(&ast);
+    (defs, diagnostics)
+}
+
+/// Compiles a given string like [`parse_with_diagnostics`], but every syntax error is returned,
+/// instead of only the first one
+pub fn compile<T: Into<String>>(
+    str: T,
+) -> Result<(Bytecode, Vec<Value>, Vec<Diagnostic>), Vec<ParseError>> {
+    let lexer = Lexer::new(str);
+    let parser = Parser::new(lexer).map_err(|e| vec![e])?;
+    let (ast, errors) = parser.parse_recovering();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let compiler = Compiler::new();
+    Ok(compiler.compile_stmts(&ast))
+}
+
+/// Compiles a given string like [`compile`], also compiling its `test` or `bench` blocks, which
+/// are skipped by the other functions
+#[allow(clippy::type_complexity)]
+pub fn compile_blocks<T: Into<String>>(
+    str: T,
+    kind: BlockKind,
+) -> Result<(Bytecode, Vec<Value>, Vec<Block>, Vec<Diagnostic>), Vec<ParseError>> {
+    let lexer = Lexer::new(str);
+    let parser = Parser::new(lexer).map_err(|e| vec![e])?;
+    let (ast, errors) = parser.parse_recovering();
+    if !errors.is_empty() {
+        return Err(errors);
+    }
+
+    let compiler = Compiler::new();
+    Ok(compiler.compile_blocks(&ast, kind))
+}
+
+/// Compiles a REPL input, which may be a sequence of statements or a single expression, appending
+/// it's constants to `constants`
+///
+/// The constants are left untouched if the input can't be parsed, use
+/// [`ParseError::is_incomplete`] to check if more input is needed
+pub fn compile_into<T: Into<String>>(
+    str: T,
+    constants: &mut Vec<Value>,
+) -> ParseResult<(Bytecode, Vec<Diagnostic>)> {
+    let lexer = Lexer::new(str);
+    let mut parser = Parser::new(lexer)?;
+
+    if parser.at_stmt()? {
+        let ast = parser.parse()?;
+        let compiler = Compiler::with_constants(std::mem::take(constants));
+        let (bytecode, ct, diagnostics) = compiler.compile_stmts(&ast);
+        *constants = ct;
+        Ok((bytecode, diagnostics))
+    } else {
+        let ast = parser.parse_expr()?;
+        let compiler = Compiler::with_constants(std::mem::take(constants));
+        let (bytecode, ct) = compiler.compile_expr(&ast);
+        *constants = ct;
+        Ok((bytecode, vec![]))
+    }
+}
+
 /// Parses the given string in a single expression
 pub fn parse_expr<T: Into<String>>(str: T) -> ParseResult<(Bytecode, Vec<Value>)> {
     let lexer = Lexer::new(str);
@@ -36,3 +146,458 @@ pub fn parse_expr<T: Into<String>>(str: T) -> ParseResult<(Bytecode, Vec<Value>)
     let compiler = Compiler::new();
     Ok(compiler.compile_expr(&ast))
 }
+
+#[test]
+fn import_module() {
+    use vm::{Symbol, VirtualMachine};
+
+    let mut vm = VirtualMachine::default();
+    vm.set_loader(Box::new(|name: Symbol| {
+        assert_eq!(name, Symbol::from("Geometry"));
+        Ok(parse("def area w h = w * h\nlet unit = 1").unwrap())
+    }));
+
+    let (bt, ct) = parse("import Geometry\nlet x = Geometry.area 2 3").unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+
+    let (bt, ct) = parse("let y = Geometry.area Geometry.unit 4").unwrap();
+    let bt = vm.load(bt, ct);
+    vm.run(&bt).unwrap();
+
+    assert_eq!(vm.get_global("x"), Some(Value::Int(6)));
+    assert_eq!(vm.get_global("y"), Some(Value::Int(4)));
+    assert_eq!(vm.get_global("area"), None);
+}
+
+#[test]
+fn incremental_compilation() {
+    use vm::VirtualMachine;
+
+    let mut vm = VirtualMachine::default();
+
+    let err = compile_into("def square x =", &mut vm.constants).unwrap_err();
+    assert!(err.is_incomplete());
+    assert!(!compile_into("1 )", &mut vm.constants)
+        .unwrap_err()
+        .is_incomplete());
+
+    let (bt, _) = compile_into("def square x =\n  x * 1.5", &mut vm.constants).unwrap();
+    vm.run(&bt).unwrap();
+
+    let (bt, _) = compile_into("square 2", &mut vm.constants).unwrap();
+    vm.run(&bt).unwrap();
+    assert_eq!(vm.pop_last(), &Value::Num(3.0));
+}
+
+#[test]
+fn docstrings() {
+    use vm::VirtualMachine;
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse(
+        "\"Adds one\"\ndef inc x = x + 1\ntrait Show with\n\t\"Shows it\"\n\tdef show x\nend",
+    )
+    .unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+
+    let help = vm.help(&"inc".to_string().into()).unwrap();
+    assert_eq!(help, "inc x\n  arity: 1\n  Adds one");
+
+    let help = vm.help(&"Show".to_string().into()).unwrap();
+    assert_eq!(help, "type Show\n  Show.show x - Shows it");
+}
+
+#[test]
+fn local_names() {
+    use vm::{Symbol, Value, VirtualMachine};
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) =
+        parse("def sq x = let y = x * x in (let z = y in z) + (let w = 1 in w)").unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+
+    let Some(Value::Fn(sq)) = vm.get_global("sq") else {
+        panic!("expected a function");
+    };
+    // the slot of `z` is reused by `w` once it's out of scope
+    let names = [(0, "x"), (1, "y"), (2, "z"), (2, "w")].map(|(i, name)| (i, Symbol::new(name)));
+    assert_eq!(sq.info.as_ref().unwrap().locals, names);
+}
+
+#[test]
+fn thread_heap_limit() {
+    use vm::{gc, Symbol, VirtualMachine, VmOptions};
+
+    let run = |source: &str| {
+        let options = VmOptions::new().max_heap(40_000);
+        let mut vm = VirtualMachine::builder().options(options).build();
+        let (bt, ct) = parse(source).unwrap();
+        vm.set_consts(ct);
+        // the values left by the compiler and the last run would be freed during this one
+        gc::collect();
+        vm.run(&bt)
+    };
+    // the limit is checked when a function is entered, so the list is still alive in `len`
+    let spawn = "def len xs = List.len xs\n\
+                 let worker = Thread.spawn (fn () = len (List.map (fn x = x) (0..300)))\n\
+                 let n = Thread.join worker";
+
+    // each thread stays under the limit, but not both of them together
+    assert!(run(spawn).is_ok());
+    let held = format!("let held = List.map (fn x = x) (0..300)\n{}", spawn);
+    assert_eq!(run(&held).unwrap_err().err, Symbol::from("MemoryError"));
+}
+
+#[test]
+fn builtin_errors() {
+    use vm::{Symbol, VirtualMachine};
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) =
+        parse("let xs = [1, 2]\nlet ys = List.map (fn x = List.get 0 x)\n  [[1], 2]").unwrap();
+    vm.set_consts(ct);
+
+    let err = vm.run(&bt).unwrap_err();
+    assert_eq!(err.builtin, Some(Symbol::from("List.get")));
+    assert_eq!((err.line, err.column), (2, 36));
+
+    let (bt, ct) = parse("let ys = List.map (fn x = x)\n  3").unwrap();
+    let bt = vm.load(bt, ct);
+
+    let err = vm.run(&bt).unwrap_err();
+    assert_eq!(err.builtin, Some(Symbol::from("List.map")));
+    assert_eq!(err.line, 1);
+}
+
+#[test]
+fn shares_constants() {
+    use vm::VirtualMachine;
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse("let greeting = \"hello\"\nlet tag = :ok").unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+    let len = vm.constants.len();
+
+    let (bt, ct) = parse("let both = (greeting == \"hello\", tag == :ok, \"world\")").unwrap();
+    let bt = vm.load(bt, ct);
+    vm.run(&bt).unwrap();
+
+    let both = vm.get_global("both").unwrap();
+    assert_eq!(format!("{}", both), "(true, true, \"world\")");
+    assert!(vm.constants.len() < len + 3);
+}
+
+#[test]
+fn ast_dump() {
+    let ast = dump_ast("let (a, b) = (1, \"s\")\ndef f x = if x then [] else x :: []").unwrap();
+
+    assert_eq!(
+        ast,
+        "Let (a, b) @1:3\n  \
+           value: Tuple @1:14\n    \
+             elem: Lit 1 @1:15\n    \
+             elem: Lit \"s\" @1:21\n\
+         Def f @2:3\n  \
+           Fn x @2:7\n    \
+             body: If @2:14\n      \
+               cond: Var x @2:14\n      \
+               then: List @2:21\n      \
+               else: Cons @2:32\n        \
+                 head: Var x @2:29\n        \
+                 tail: List @2:34\n"
+    );
+}
+
+#[test]
+fn generators() {
+    use vm::VirtualMachine;
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse(
+        "def* count n = let _ = yield n in count (n + 1)\n\
+         let g = count 1\n\
+         let firsts = [Gen.next g, Gen.next g]\n\
+         let taken = Seq.toList (Seq.take 2 g)\n\
+         let pair = Gen.toList ((fn* x = let y = yield x in yield (y, x)) 3)\n\
+         let once = (fn* x = yield x) nil\n\
+         let ends = [Gen.next once, Gen.next once]",
+    )
+    .unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+
+    let global = |name: &str| format!("{}", vm.get_global(name).unwrap());
+    assert_eq!(global("firsts"), "[(Gen.yielded 1), (Gen.yielded 2)]");
+    assert_eq!(global("taken"), "[3, 4]");
+    assert_eq!(global("pair"), "[3, (nil, 3)]");
+    assert_eq!(global("ends"), "[(Gen.yielded nil), (Gen.finished)]");
+
+    // `yield` is only a keyword inside of generators
+    let ast = dump_ast("let f = fn x = yield x").unwrap();
+    assert!(ast.contains("Call") && !ast.contains("Yield"));
+    let ast = dump_ast("let f = fn* x = yield x").unwrap();
+    assert!(ast.contains("Fn* x") && ast.contains("Yield"));
+}
+
+#[test]
+fn nested_modules() {
+    use vm::VirtualMachine;
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse(
+        "module Geo.Shapes with def area w h = w * h end\n\
+         module Geo with def origin () = (0, 0) end\n\
+         module Geo.Shapes with def square s = Geo.Shapes.area s s end\n\
+         let results = (Geo.Shapes.square 3, Geo.origin ())",
+    )
+    .unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+
+    assert_eq!(
+        format!("{}", vm.get_global("results").unwrap()),
+        "(9, (0, 0))"
+    );
+    assert_eq!(
+        vm.help(&"Geo.Shapes.area".to_string().into())
+            .unwrap()
+            .lines()
+            .next(),
+        Some("Geo.Shapes.area w h")
+    );
+}
+
+#[test]
+fn private_members() {
+    use vm::VirtualMachine;
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse(
+        "module Temp with\n\
+         \tdef toF c = Temp.scale c + 32\n\
+         \tpriv def scale c = c * 2\n\
+         end\n\
+         let f = Temp.toF 10",
+    )
+    .unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+    assert_eq!(format!("{}", vm.get_global("f").unwrap()), "52");
+
+    let (bt, ct) = parse("let s = Temp.scale").unwrap();
+    let bt = vm.load(bt, ct);
+    let err = vm.run(&bt).unwrap_err();
+    assert_eq!(err.msg, "'scale' is private to 'Temp'");
+}
+
+#[test]
+fn module_constants() {
+    use vm::VirtualMachine;
+
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse(
+        "module Circle with\n\
+         \tconst PI = 3.5\n\
+         \tconst SIDES = (2 * 3 - 6, [-1])\n\
+         \tconst TAU = 2 * Circle.PI\n\
+         \tconst ROUND = if Circle.TAU > 6 then :yes else Circle.missing\n\
+         \tdef area r = Circle.PI * r * r\n\
+         end\n\
+         let values = (Circle.area 2, Circle.SIDES, Circle.TAU, Circle.ROUND)",
+    )
+    .unwrap();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+    assert_eq!(
+        format!("{}", vm.get_global("values").unwrap()),
+        "(14.0, (0, [-1]), 7.0, :yes)"
+    );
+
+    let errors = compile("module M with\n\tconst N = List.length []\nend").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "The value of the constant 'N' isn't known at compile time"
+    );
+    assert_eq!(errors[0].location().line, 2);
+
+    // the constants of other modules and the ones after it aren't known yet
+    let errors = compile("module M with\n\tconst A = M.B\n\tconst B = 1\nend").unwrap_err();
+    assert_eq!(
+        errors[0].message(),
+        "The value of the constant 'A' isn't known at compile time"
+    );
+
+    let errors = compile("module M with\n\tconst N = 9223372036854775807 + 1\nend").unwrap_err();
+    assert!(errors[0]
+        .message()
+        .starts_with("The value of the constant 'N' raises OverflowError: "));
+}
+
+#[test]
+fn match_errors() {
+    use vm::{Symbol, VirtualMachine};
+
+    let mut vm = VirtualMachine::default();
+    for (source, value) in [
+        ("let (a, b) = (1, 2, 3)", "(1, 2, 3)"),
+        ("def f (x, 1) = x\nlet y = f (2, 3)", "(2, 3)"),
+        ("let y = match [1] with | [] -> 0", "[1]"),
+        ("let \"a\" = \"b\"", "\"b\""),
+    ] {
+        let (bt, ct) = parse(source).unwrap();
+        let bt = vm.load(bt, ct);
+
+        let err = vm.run(&bt).unwrap_err();
+        assert_eq!(err.err, Symbol::from("MatchError"));
+        assert!(err.msg.contains(value), "{}", err.msg);
+    }
+}
+
+#[test]
+fn deep_recursion() {
+    use vm::{Symbol, VirtualMachine};
+
+    // the memoized calls run in the dispatch loop, and the stack is left as it was before `try`
+    let source = "let f = memo (fn n = if n == 0 then 0 else 1 + f (n - 1))
+let a = try f 100000 rescue e e
+let b = f 100";
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse(source).unwrap();
+    let bt = vm.load(bt, ct);
+    vm.run(&bt).unwrap();
+
+    assert_eq!(
+        vm.get_global("a"),
+        Some(Symbol::from("RecursionError").into())
+    );
+    assert_eq!(vm.get_global("b"), Some(Value::Int(100)));
+}
+
+#[test]
+fn shadowed_builtins() {
+    use vm::VirtualMachine;
+
+    // the program can define the globals of the prelude again, but not its own ones
+    let source = "def assert message condition = if condition then message else nil
+let a = assert 1 true
+let a = 2";
+    let mut vm = VirtualMachine::default();
+    let (bt, ct) = parse(source).unwrap();
+    let bt = vm.load(bt, ct);
+    let err = vm.run(&bt).unwrap_err();
+
+    assert_eq!(err.msg, "Tried to reassign global variable 'a'");
+    assert_eq!(vm.get_global("a"), Some(Value::Int(1)));
+}
+
+#[test]
+fn error_recovery() {
+    let errors =
+        compile("let x = )\nlet y = 1\ndef f x =\n  let z = ( in z\ndef g x = x").unwrap_err();
+    let lines: Vec<_> = errors.iter().map(|e| e.location().line).collect();
+    assert_eq!(lines, [1, 4]);
+
+    let (defs, diagnostics) = definitions("let x = )\nlet y = 1\ndef g x = x");
+    let names: Vec<_> = defs.iter().map(|def| def.name.as_str()).collect();
+    assert_eq!(names, ["y", "g"]);
+    assert_eq!(diagnostics.len(), 1);
+}
+
+#[test]
+fn misplaced_placeholders() {
+    let message = "The placeholder '_' can only be an argument of the call after '|>'";
+    for source in [
+        "let xs = 1 |> List.map f [_, 2]",
+        "let y = f (g _)",
+        "let z = _",
+    ] {
+        let errors = compile(source).unwrap_err();
+        assert_eq!(errors[0].message(), message, "{}", source);
+    }
+
+    assert!(compile("let xs = [1] |> List.map (fn x = x) _").is_ok());
+}
+
+#[test]
+fn rendered_errors() {
+    let source = "let x = 1\ndef f y =\n\tlet z = ( in z\n";
+    let errors = compile(source).unwrap_err();
+    assert_eq!(
+        errors[0].render("main.yex", source, false),
+        "error[E0002]: unexpected token 'in'\n \
+         --> main.yex:3:12\n  \
+           |\n\
+         3 |     let z = ( in z\n  \
+           |               ^^"
+    );
+
+    let (_, _, diagnostics) = parse_with_diagnostics("def f x =\n  let yy = 1 in x").unwrap();
+    assert_eq!(
+        diagnostics[0].render("main.yex", "def f x =\n  let yy = 1 in x", false),
+        "warning[W0001]: Unused variable 'yy'\n \
+         --> main.yex:2:7\n  \
+           |\n\
+         2 |   let yy = 1 in x\n  \
+           |       ^^\n  \
+           = help: prefix it with '_' to ignore it"
+    );
+
+    // the whole literal is underlined
+    let source = "let x = 1 + 99999999999999999999";
+    let errors = compile(source).unwrap_err();
+    assert_eq!(
+        errors[0].render("main.yex", source, false),
+        "error[E0001]: Can't parse number 99999999999999999999\n \
+         --> main.yex:1:13\n  \
+           |\n\
+         1 | let x = 1 + 99999999999999999999\n  \
+           |             ^^^^^^^^^^^^^^^^^^^^"
+    );
+}
+
+#[test]
+fn location_spans() {
+    let source = "let y = 3\nlet x = Int.from \"é\"\ndef f x = x + 1\n";
+    let span = |line, column| {
+        let span = location_span(source, Location { line, column });
+        &source[span.start..span.end]
+    };
+
+    assert_eq!(span(2, 16), "from");
+    assert_eq!(span(3, 13), "+");
+    // after the end of the line
+    assert_eq!(span(2, 21), "\"");
+}
+
+#[test]
+fn test_blocks() {
+    use vm::VirtualMachine;
+
+    let source =
+        "def double x = x * 2\ntest \"double\" do\n  let y = double 2 in y;\n  double 3\nend";
+    let (bt, ct, tests, _) = compile_blocks(source, BlockKind::Test).unwrap();
+    assert_eq!(tests.len(), 1);
+    assert_eq!(tests[0].name, "double");
+
+    let mut vm = VirtualMachine::default();
+    vm.set_consts(ct);
+    vm.run(&bt).unwrap();
+    vm.reset();
+    vm.run(&tests[0].bytecode).unwrap();
+    assert_eq!(vm.pop_last(), &vm::Value::Int(6));
+
+    // the test blocks are skipped when the program runs, and when the benchmarks are compiled
+    let (bt, _) = parse(source).unwrap();
+    assert_eq!(bt, parse("def double x = x * 2").unwrap().0);
+    let (_, _, benches, _) = compile_blocks(source, BlockKind::Bench).unwrap();
+    assert!(benches.is_empty());
+
+    let (_, _, benches, _) =
+        compile_blocks("bench \"double\" do 2 * 2 end", BlockKind::Bench).unwrap();
+    assert_eq!(benches.len(), 1);
+    assert_eq!(benches[0].name, "double");
+}
@@ -1,11 +1,25 @@
+use std::fmt;
+
 use vm::{gc::GcRef, OpCode, Symbol, Value};
 
-#[derive(Debug, Clone, Copy, Default)]
+/// A position in the source code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
 pub struct Location {
+    /// The line, starting from 1
     pub line: usize,
+    /// The column, starting from 1
     pub column: usize,
 }
 
+/// A range of the source code, in bytes
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Span {
+    /// The first byte
+    pub start: usize,
+    /// The byte after the last one
+    pub end: usize,
+}
+
 use crate::tokens::TokenType;
 
 pub type VarDecl = Symbol;
@@ -14,12 +28,114 @@ pub type Path = Vec<Symbol>;
 
 #[derive(Debug, Clone)]
 pub enum Pattern {
-    Id(VarDecl),
+    /// A name and where it's written
+    Id(VarDecl, Location),
+    /// `^name`, compares the value against an existing variable instead of binding it
+    Pin(VarDecl),
     Lit(Literal),
     Variant(Path, Vec<Pattern>),
     Tuple(Vec<Pattern>),
     List(Box<Self>, Box<Self>),
+    /// `"prefix" :: rest`, matches strings starting with the prefix and lists starting with it
+    Prefix(String, Box<Self>),
     EmptyList,
+    /// `#[x, y]`, matches arrays with exactly that many elements
+    Array(Vec<Pattern>),
+    /// Matches dictionaries that have every key, the other keys are ignored
+    Dict(Vec<(Literal, Pattern)>),
+    /// `Point{x: 0, y}`, matches values of the struct, the fields left out are ignored
+    Struct(Path, Vec<(VarDecl, Pattern)>),
+}
+
+impl Pattern {
+    /// Returns the names bound by the pattern, sorted by name
+    pub fn bindings(&self) -> Vec<Symbol> {
+        let mut names: Vec<_> = self.locations().into_iter().map(|(name, _)| name).collect();
+        names.sort_by(|a, b| a.as_str().cmp(b.as_str()));
+        names
+    }
+
+    /// Returns the names bound by the pattern with where they're written, in the order they're
+    /// written
+    pub fn locations(&self) -> Vec<(Symbol, Location)> {
+        fn collect(pattern: &Pattern, names: &mut Vec<(Symbol, Location)>) {
+            match pattern {
+                Pattern::Id(name, location) if name.as_str() != "_" => {
+                    names.push((*name, *location))
+                }
+                Pattern::Variant(_, pats) | Pattern::Tuple(pats) | Pattern::Array(pats) => {
+                    pats.iter().for_each(|pat| collect(pat, names))
+                }
+                Pattern::List(head, tail) => {
+                    collect(head, names);
+                    collect(tail, names);
+                }
+                Pattern::Prefix(_, rest) => collect(rest, names),
+                Pattern::Dict(entries) => entries.iter().for_each(|(_, pat)| collect(pat, names)),
+                Pattern::Struct(_, fields) => {
+                    fields.iter().for_each(|(_, pat)| collect(pat, names))
+                }
+                Pattern::Id(..) | Pattern::Pin(_) | Pattern::Lit(_) | Pattern::EmptyList => {}
+            }
+        }
+
+        let mut names = vec![];
+        collect(self, &mut names);
+        names
+    }
+}
+
+impl fmt::Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Pattern::Id(name, _) => write!(f, "{}", name),
+            Pattern::Pin(name) => write!(f, "^{}", name),
+            Pattern::Lit(lit) => write!(f, "{}", Value::from(lit.clone())),
+            Pattern::Variant(path, args) => {
+                let path: Vec<_> = path.iter().map(Symbol::as_str).collect();
+                write!(f, "({}", path.join("."))?;
+                for arg in args {
+                    write!(f, " {}", arg)?;
+                }
+                write!(f, ")")
+            }
+            Pattern::Tuple(elems) => {
+                let elems: Vec<_> = elems.iter().map(ToString::to_string).collect();
+                write!(f, "({})", elems.join(", "))
+            }
+            Pattern::List(head, tail) => write!(f, "({} :: {})", head, tail),
+            Pattern::Prefix(prefix, rest) => write!(f, "({:?} :: {})", prefix, rest),
+            Pattern::EmptyList => write!(f, "[]"),
+            Pattern::Array(elems) => {
+                let elems: Vec<_> = elems.iter().map(ToString::to_string).collect();
+                write!(f, "#[{}]", elems.join(", "))
+            }
+            Pattern::Dict(entries) => {
+                write!(f, "%{{")?;
+                for (i, (key, pat)) in entries.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    match key {
+                        Literal::Sym(name) => write!(f, "{}: {}", name, pat)?,
+                        other => write!(f, "{} => {}", Value::from(other.clone()), pat)?,
+                    }
+                }
+                write!(f, "}}")
+            }
+            Pattern::Struct(path, fields) => {
+                let path: Vec<_> = path.iter().map(Symbol::as_str).collect();
+                write!(f, "{}{{", path.join("."))?;
+                for (i, (name, pat)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", name, pat)?;
+                }
+                write!(f, "}}")
+            }
+        }
+    }
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -43,6 +159,8 @@ pub enum BinOp {
     And,
     Or,
     Is,
+    Range,
+    RangeInclusive,
 }
 
 impl<'a> From<BinOp> for &'a [OpCode] {
@@ -65,6 +183,8 @@ impl<'a> From<BinOp> for &'a [OpCode] {
             BinOp::Eq => &[OpCode::Eq],
             BinOp::Ne => &[OpCode::Eq, OpCode::Not],
             BinOp::Is => &[OpCode::Rev, OpCode::Type, OpCode::Eq],
+            BinOp::Range => &[OpCode::Range(false)],
+            BinOp::RangeInclusive => &[OpCode::Range(true)],
             BinOp::And => unreachable!(),
             BinOp::Or => unreachable!(),
         }
@@ -94,6 +214,8 @@ impl TryFrom<TokenType> for BinOp {
             TokenType::Ne => Ok(BinOp::Ne),
             TokenType::And => Ok(BinOp::And),
             TokenType::Or => Ok(BinOp::Or),
+            TokenType::DotDot => Ok(BinOp::Range),
+            TokenType::DotDotEq => Ok(BinOp::RangeInclusive),
             _ => Err(()),
         }
     }
@@ -145,16 +267,23 @@ impl Bind {
 
 #[derive(Debug)]
 pub struct MatchArm {
-    pub cond: Pattern,
+    /// The alternatives of the arm, like in `| 1 | 2 -> ...`, the body runs if any of them matches
+    pub conds: Vec<Pattern>,
     pub body: Box<Expr>,
     pub guard: Option<Box<Expr>>,
     pub location: Location,
 }
 
 impl MatchArm {
-    pub fn new(cond: Pattern, body: Expr, guard: Option<Expr>, line: usize, column: usize) -> Self {
+    pub fn new(
+        conds: Vec<Pattern>,
+        body: Expr,
+        guard: Option<Expr>,
+        line: usize,
+        column: usize,
+    ) -> Self {
         Self {
-            cond,
+            conds,
             body: Box::new(body),
             guard: guard.map(Box::new),
             location: Location { line, column },
@@ -186,13 +315,21 @@ pub enum ExprKind {
     },
 
     Lambda {
-        args: Vec<Pattern>, // specifies the arguments name and types
-        body: Box<Expr>,    // the function body
+        args: Vec<Pattern>,          // specifies the arguments name and types
+        defaults: Vec<Expr>,         // the default values of the last arguments
+        variadic: bool,              // whether the last argument collects the extra arguments
+        keywords: Vec<VarDecl>, // the keyword arguments, received in a dictionary as the last one
+        types: Vec<Option<VarDecl>>, // the annotated types of the arguments, like `(x is Num)`
+        returns: Option<VarDecl>, // the annotated return type, like `is Num` before the `=`
+        body: Box<Expr>,        // the function body
+        generator: bool,        // whether calling it returns a generator, defined with `fn*`
     },
 
     App {
         callee: Box<Expr>,
         args: Vec<Expr>,
+        /// Set by `=>`, which makes it a tail call anywhere, the calls in tail position are found
+        /// by the compiler
         tail: bool,
     },
 
@@ -201,9 +338,17 @@ pub enum ExprKind {
         method: VarDecl,
     },
 
+    /// A constant index into a tuple, like `t.0`
+    TupleIndex {
+        tup: Box<Expr>,
+        index: usize,
+    },
+
     Var(Symbol),
     Lit(Literal),
     List(Vec<Expr>),
+    /// An array literal, like `#[1, 2, 3]`
+    Array(Vec<Expr>),
 
     Binary {
         left: Box<Expr>,
@@ -217,19 +362,50 @@ pub enum ExprKind {
 
     UnOp(UnOp, Box<Expr>),
 
+    /// A `try` expression, `rescue e body` is a single arm binding the exception to `e`
     Try {
         body: Box<Expr>,
-        bind: VarDecl,
-        rescue: Box<Expr>,
+        arms: Vec<MatchArm>,
     },
 
     Tuple(Vec<Expr>),
+
+    /// A dictionary literal, with the `(key, value)` pairs in source order
+    Dict(Vec<(Expr, Expr)>),
+
+    /// A struct literal, like `Point{x: 1, y: 2}`
+    Struct {
+        ty: Box<Expr>,
+        fields: Vec<(VarDecl, Expr)>,
+    },
+
+    /// `%{value | name: new}`, copies a struct or a dictionary replacing some of its fields
+    Update {
+        value: Box<Expr>,
+        fields: Vec<(VarDecl, Expr)>,
+    },
+
+    /// An interpolated string, the parts are concatenated after being converted to strings
+    Interp(Vec<Expr>),
+
+    /// `yield value` inside a generator, suspends it until `Gen.next` is called again
+    Yield(Box<Expr>),
+
+    /// A list comprehension, like `[body for bind in source if filter]`
+    Comprehension {
+        body: Box<Expr>,
+        bind: Pattern,
+        source: Box<Expr>,
+        filter: Option<Box<Expr>>,
+    },
 }
 
 #[derive(Debug, Clone)]
 pub enum Literal {
     Num(f64),
+    Int(i64),
     Str(String),
+    Bytes(Vec<u8>),
     Bool(bool),
     Sym(Symbol),
     Unit,
@@ -239,7 +415,9 @@ impl PartialEq<Value> for Literal {
     fn eq(&self, other: &Value) -> bool {
         match (self, other) {
             (Literal::Num(a), Value::Num(b)) => a == b,
+            (Literal::Int(a), Value::Int(b)) => a == b,
             (Literal::Str(a), Value::Str(b)) => a == &**b,
+            (Literal::Bytes(a), Value::Bytes(b)) => a == &*b.0,
             (Literal::Bool(a), Value::Bool(b)) => a == b,
             (Literal::Sym(a), Value::Sym(b)) => *a == **b,
             (Literal::Unit, Value::Nil) => true,
@@ -252,7 +430,9 @@ impl From<Literal> for Value {
     fn from(lit: Literal) -> Value {
         match lit {
             Literal::Num(n) => Value::Num(n),
+            Literal::Int(n) => Value::Int(n),
             Literal::Str(s) => Value::Str(GcRef::new(s)),
+            Literal::Bytes(b) => Value::Bytes(b.into()),
             Literal::Bool(b) => Value::Bool(b),
             Literal::Sym(s) => Value::Sym(s.into()),
             Literal::Unit => Value::Nil,
@@ -311,6 +491,8 @@ impl Stmt {
 pub struct Def {
     pub value: Expr,
     pub bind: VarDecl,
+    /// The string right before the `def`, shown by `help`
+    pub doc: Option<String>,
 }
 
 #[derive(Debug)]
@@ -325,4 +507,56 @@ pub enum StmtKind {
         variants: Vec<(VarDecl, Vec<VarDecl>)>,
         members: Vec<Def>,
     },
+    Import(VarDecl),
+    Struct {
+        name: VarDecl,
+        fields: Vec<VarDecl>,
+        members: Vec<Def>,
+    },
+    Trait {
+        name: VarDecl,
+        /// The methods that every implementation must define, with their arguments and docstring
+        required: Vec<(VarDecl, Vec<VarDecl>, Option<String>)>,
+        /// The methods with a default implementation
+        provided: Vec<Def>,
+    },
+    Impl {
+        trait_: VarDecl,
+        ty: VarDecl,
+        members: Vec<Def>,
+    },
+    /// `module Foo.Bar with ... end`, the path is joined with dots, like the tags of variants
+    Module {
+        path: VarDecl,
+        members: Vec<Def>,
+        /// `const NAME = value`, the parser checks that the values are known at compile time
+        consts: Vec<(VarDecl, Expr)>,
+        /// The members declared with `priv`, which only the module's functions can reference
+        private: Vec<VarDecl>,
+    },
+    /// `test "name" do ... end` or `bench "name" do ... end`, only compiled when running the tests
+    /// or the benchmarks
+    Block {
+        kind: BlockKind,
+        name: String,
+        body: Expr,
+    },
+}
+
+/// What a `test` or `bench` block is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockKind {
+    /// Runs once, checking the code with `assert`
+    Test,
+    /// Runs many times, measuring how long it takes
+    Bench,
+}
+
+impl fmt::Display for BlockKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Test => write!(f, "test"),
+            Self::Bench => write!(f, "bench"),
+        }
+    }
 }
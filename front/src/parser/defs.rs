@@ -0,0 +1,131 @@
+use super::ast::{Def, ExprKind, Location, Pattern, Stmt, StmtKind};
+
+/// A global defined by a program, or a member of a type, struct, trait or module it defines
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Definition {
+    /// The global name, the members are prefixed by the name of their type, like `Point.new`
+    pub name: String,
+    /// Where the statement that defines it starts
+    pub location: Location,
+    /// The parameters of functions, `None` for the other values
+    pub params: Option<Vec<String>>,
+    /// The docstring of functions
+    pub doc: Option<String>,
+}
+
+impl Definition {
+    fn new(name: String, location: Location) -> Self {
+        Self {
+            name,
+            location,
+            params: None,
+            doc: None,
+        }
+    }
+
+    fn def(prefix: Option<&str>, def: &Def, location: Location) -> Self {
+        let name = match prefix {
+            Some(prefix) => format!("{}.{}", prefix, def.bind),
+            None => def.bind.to_string(),
+        };
+
+        let params = match &def.value.kind {
+            ExprKind::Lambda { args, variadic, .. } => Some(
+                args.iter()
+                    .enumerate()
+                    .map(|(i, arg)| match arg {
+                        Pattern::Id(name, _) if *variadic && i == args.len() - 1 => {
+                            format!("...{}", name)
+                        }
+                        _ => arg.to_string(),
+                    })
+                    .collect(),
+            ),
+            _ => None,
+        };
+
+        Self {
+            params,
+            doc: def.doc.clone(),
+            ..Self::new(name, location)
+        }
+    }
+}
+
+/// Lists the globals defined by the statements, in the order they're defined
+pub fn definitions(stmts: &[Stmt]) -> Vec<Definition> {
+    let mut defs = vec![];
+
+    for stmt in stmts {
+        let location = stmt.location;
+        let members = |defs: &mut Vec<Definition>, name: &str, members: &[Def]| {
+            for member in members {
+                defs.push(Definition::def(Some(name), member, location));
+            }
+        };
+
+        match &stmt.kind {
+            StmtKind::Def(def) => defs.push(Definition::def(None, def, location)),
+            StmtKind::Let { bind, .. } => {
+                for name in bind.bindings() {
+                    defs.push(Definition::new(name.to_string(), location));
+                }
+            }
+            StmtKind::Type {
+                name,
+                variants,
+                members: defined,
+            } => {
+                defs.push(Definition::new(name.to_string(), location));
+                for (variant, fields) in variants {
+                    defs.push(Definition {
+                        params: Some(fields.iter().map(ToString::to_string).collect()),
+                        ..Definition::new(format!("{}.{}", name, variant), location)
+                    });
+                }
+                members(&mut defs, name.as_str(), defined);
+            }
+            StmtKind::Struct {
+                name,
+                members: defined,
+                ..
+            } => {
+                defs.push(Definition::new(name.to_string(), location));
+                members(&mut defs, name.as_str(), defined);
+            }
+            StmtKind::Trait {
+                name,
+                required,
+                provided,
+            } => {
+                defs.push(Definition::new(name.to_string(), location));
+                for (method, args, doc) in required {
+                    defs.push(Definition {
+                        params: Some(args.iter().map(ToString::to_string).collect()),
+                        doc: doc.clone(),
+                        ..Definition::new(format!("{}.{}", name, method), location)
+                    });
+                }
+                members(&mut defs, name.as_str(), provided);
+            }
+            StmtKind::Module {
+                path,
+                members: defined,
+                consts,
+                private,
+            } => {
+                defs.push(Definition::new(path.to_string(), location));
+                for (name, _) in consts.iter().filter(|(name, _)| !private.contains(name)) {
+                    defs.push(Definition::new(format!("{}.{}", path, name), location));
+                }
+                for member in defined.iter().filter(|def| !private.contains(&def.bind)) {
+                    defs.push(Definition::def(Some(path.as_str()), member, location));
+                }
+            }
+            // the implementations are found through the trait
+            StmtKind::Impl { .. } | StmtKind::Import(_) | StmtKind::Block { .. } => {}
+        }
+    }
+
+    defs
+}
@@ -0,0 +1,360 @@
+use std::fmt::Write;
+
+use vm::Value;
+
+use super::ast::{BlockKind, Def, Expr, ExprKind, Location, MatchArm, Pattern, Stmt, StmtKind};
+
+/// Prints the statements as a tree, one node per line with its location, the children are
+/// indented under their parent and labeled by the part of the node they are:
+///
+/// ```text
+/// Def double @1:3
+///   Fn x @1:12
+///     body: Binary Mul @1:18
+///       left: Var x @1:16
+///       right: Lit 2 @1:20
+/// ```
+///
+/// Patterns are printed with the yex syntax, the output only changes when the AST does
+pub fn dump(stmts: &[Stmt]) -> String {
+    let mut dumper = Dumper {
+        out: String::new(),
+        indent: 0,
+    };
+
+    for stmt in stmts {
+        dumper.stmt(stmt);
+    }
+    dumper.out
+}
+
+struct Dumper {
+    out: String,
+    indent: usize,
+}
+
+impl Dumper {
+    /// Writes a node, `label` is the part of the parent the node is
+    fn line(&mut self, label: Option<&str>, node: &str, location: Location) {
+        let _ = write!(self.out, "{:1$}", "", self.indent * 2);
+        if let Some(label) = label {
+            let _ = write!(self.out, "{}: ", label);
+        }
+        let _ = writeln!(self.out, "{} @{}:{}", node, location.line, location.column);
+    }
+
+    /// Writes a node without a location, like the name of a field
+    fn label(&mut self, node: &str) {
+        let _ = writeln!(self.out, "{:1$}{2}", "", self.indent * 2, node);
+    }
+
+    fn nested(&mut self, f: impl FnOnce(&mut Self)) {
+        self.indent += 1;
+        f(self);
+        self.indent -= 1;
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        let location = stmt.location;
+
+        match &stmt.kind {
+            StmtKind::Def(def) => self.def(def, location),
+            StmtKind::Let { bind, value } => {
+                self.line(None, &format!("Let {}", bind), location);
+                self.nested(|this| this.expr(Some("value"), value));
+            }
+            StmtKind::Type {
+                name,
+                variants,
+                members,
+            } => {
+                self.line(None, &format!("Type {}", name), location);
+                self.nested(|this| {
+                    for (variant, fields) in variants {
+                        let fields: Vec<_> = fields.iter().map(|f| f.as_str()).collect();
+                        this.label(&format!("Variant {}({})", variant, fields.join(", ")));
+                    }
+                    this.members(members, location);
+                });
+            }
+            StmtKind::Import(name) => self.line(None, &format!("Import {}", name), location),
+            StmtKind::Struct {
+                name,
+                fields,
+                members,
+            } => {
+                let fields: Vec<_> = fields.iter().map(|f| f.as_str()).collect();
+                self.line(
+                    None,
+                    &format!("Struct {}{{{}}}", name, fields.join(", ")),
+                    location,
+                );
+                self.nested(|this| this.members(members, location));
+            }
+            StmtKind::Trait {
+                name,
+                required,
+                provided,
+            } => {
+                self.line(None, &format!("Trait {}", name), location);
+                self.nested(|this| {
+                    for (method, args, _) in required {
+                        let args: Vec<_> = args.iter().map(|a| a.as_str()).collect();
+                        this.label(&format!("Required {} {}", method, args.join(" ")));
+                    }
+                    this.members(provided, location);
+                });
+            }
+            StmtKind::Impl {
+                trait_,
+                ty,
+                members,
+            } => {
+                self.line(None, &format!("Impl {} for {}", trait_, ty), location);
+                self.nested(|this| this.members(members, location));
+            }
+            StmtKind::Module {
+                path,
+                members,
+                consts,
+                private,
+            } => {
+                self.line(None, &format!("Module {}", path), location);
+                self.nested(|this| {
+                    for name in private {
+                        this.label(&format!("Private {}", name));
+                    }
+                    for (name, value) in consts {
+                        this.expr(Some(&format!("const {}", name)), value);
+                    }
+                    this.members(members, location);
+                });
+            }
+            StmtKind::Block { kind, name, body } => {
+                let kind = match kind {
+                    BlockKind::Test => "Test",
+                    BlockKind::Bench => "Bench",
+                };
+                self.line(None, &format!("{} {:?}", kind, name), location);
+                self.nested(|this| this.expr(Some("body"), body));
+            }
+        }
+    }
+
+    /// The members don't keep their own location, so they're shown at the statement location
+    fn members(&mut self, members: &[Def], location: Location) {
+        for member in members {
+            self.def(member, location);
+        }
+    }
+
+    fn def(&mut self, def: &Def, location: Location) {
+        self.line(None, &format!("Def {}", def.bind), location);
+        self.nested(|this| {
+            if let Some(doc) = &def.doc {
+                this.label(&format!("doc: {:?}", doc));
+            }
+            this.expr(None, &def.value);
+        });
+    }
+
+    fn exprs(&mut self, label: &str, exprs: &[Expr]) {
+        for expr in exprs {
+            self.expr(Some(label), expr);
+        }
+    }
+
+    fn arms(&mut self, arms: &[MatchArm]) {
+        for arm in arms {
+            let conds: Vec<_> = arm.conds.iter().map(ToString::to_string).collect();
+            self.line(None, &format!("Arm {}", conds.join(" | ")), arm.location);
+            self.nested(|this| {
+                if let Some(guard) = &arm.guard {
+                    this.expr(Some("guard"), guard);
+                }
+                this.expr(Some("body"), &arm.body);
+            });
+        }
+    }
+
+    fn expr(&mut self, label: Option<&str>, expr: &Expr) {
+        let location = expr.location;
+        let line = |this: &mut Self, node: &str| this.line(label, node, location);
+
+        match &expr.kind {
+            ExprKind::If { cond, then, else_ } => {
+                line(self, "If");
+                self.nested(|this| {
+                    this.expr(Some("cond"), cond);
+                    this.expr(Some("then"), then);
+                    this.expr(Some("else"), else_);
+                });
+            }
+            ExprKind::Let { bind, value, body } => {
+                line(self, &format!("Let {}", bind));
+                self.nested(|this| {
+                    this.expr(Some("value"), value);
+                    this.expr(Some("body"), body);
+                });
+            }
+            ExprKind::Def { bind, body } => {
+                line(self, &format!("Def {}", bind.bind));
+                self.nested(|this| {
+                    this.expr(Some("value"), &bind.value);
+                    this.expr(Some("body"), body);
+                });
+            }
+            ExprKind::Match { expr, arms } => {
+                line(self, "Match");
+                self.nested(|this| {
+                    this.expr(Some("expr"), expr);
+                    this.arms(arms);
+                });
+            }
+            ExprKind::Lambda {
+                args,
+                defaults,
+                variadic,
+                keywords,
+                types,
+                returns,
+                body,
+                generator,
+            } => {
+                let mut node = String::from(if *generator { "Fn*" } else { "Fn" });
+                for (i, arg) in args.iter().enumerate() {
+                    match (arg, types.get(i).copied().flatten()) {
+                        (Pattern::Id(name, _), _) if *variadic && i == args.len() - 1 => {
+                            let _ = write!(node, " ...{}", name);
+                        }
+                        (_, Some(ty)) => {
+                            let _ = write!(node, " ({} is {})", arg, ty);
+                        }
+                        _ => {
+                            let _ = write!(node, " {}", arg);
+                        }
+                    }
+                }
+                for keyword in keywords {
+                    let _ = write!(node, " {}:", keyword);
+                }
+                if let Some(ty) = returns {
+                    let _ = write!(node, " is {}", ty);
+                }
+
+                line(self, &node);
+                self.nested(|this| {
+                    this.exprs("default", defaults);
+                    this.expr(Some("body"), body);
+                });
+            }
+            ExprKind::App { callee, args, tail } => {
+                line(self, if *tail { "Call tail" } else { "Call" });
+                self.nested(|this| {
+                    this.expr(Some("callee"), callee);
+                    this.exprs("arg", args);
+                });
+            }
+            ExprKind::MethodRef { ty, method } => {
+                line(self, &format!("MethodRef {}", method));
+                self.nested(|this| this.expr(Some("ty"), ty));
+            }
+            ExprKind::TupleIndex { tup, index } => {
+                line(self, &format!("TupleIndex {}", index));
+                self.nested(|this| this.expr(Some("tuple"), tup));
+            }
+            ExprKind::Var(name) => line(self, &format!("Var {}", name)),
+            ExprKind::Lit(lit) => line(
+                self,
+                &format!("Lit {}", vm::pretty(&Value::from(lit.clone()))),
+            ),
+            ExprKind::List(elems) => {
+                line(self, "List");
+                self.nested(|this| this.exprs("elem", elems));
+            }
+            ExprKind::Array(elems) => {
+                line(self, "Array");
+                self.nested(|this| this.exprs("elem", elems));
+            }
+            ExprKind::Tuple(elems) => {
+                line(self, "Tuple");
+                self.nested(|this| this.exprs("elem", elems));
+            }
+            ExprKind::Interp(parts) => {
+                line(self, "Interp");
+                self.nested(|this| this.exprs("part", parts));
+            }
+            ExprKind::Binary { left, op, right } => {
+                line(self, &format!("Binary {:?}", op));
+                self.nested(|this| {
+                    this.expr(Some("left"), left);
+                    this.expr(Some("right"), right);
+                });
+            }
+            ExprKind::Cons { head, tail } => {
+                line(self, "Cons");
+                self.nested(|this| {
+                    this.expr(Some("head"), head);
+                    this.expr(Some("tail"), tail);
+                });
+            }
+            ExprKind::UnOp(op, value) => {
+                line(self, &format!("Unary {:?}", op));
+                self.nested(|this| this.expr(None, value));
+            }
+            ExprKind::Yield(value) => {
+                line(self, "Yield");
+                self.nested(|this| this.expr(None, value));
+            }
+            ExprKind::Try { body, arms } => {
+                line(self, "Try");
+                self.nested(|this| {
+                    this.expr(Some("body"), body);
+                    this.arms(arms);
+                });
+            }
+            ExprKind::Dict(entries) => {
+                line(self, "Dict");
+                self.nested(|this| {
+                    for (key, value) in entries {
+                        this.expr(Some("key"), key);
+                        this.expr(Some("value"), value);
+                    }
+                });
+            }
+            ExprKind::Struct { ty, fields } => {
+                line(self, "Struct");
+                self.nested(|this| {
+                    this.expr(Some("ty"), ty);
+                    for (name, value) in fields {
+                        this.expr(Some(name.as_str()), value);
+                    }
+                });
+            }
+            ExprKind::Update { value, fields } => {
+                line(self, "Update");
+                self.nested(|this| {
+                    this.expr(Some("value"), value);
+                    for (name, value) in fields {
+                        this.expr(Some(name.as_str()), value);
+                    }
+                });
+            }
+            ExprKind::Comprehension {
+                body,
+                bind,
+                source,
+                filter,
+            } => {
+                line(self, &format!("Comprehension {}", bind));
+                self.nested(|this| {
+                    this.expr(Some("body"), body);
+                    this.expr(Some("source"), source);
+                    if let Some(filter) = filter {
+                        this.expr(Some("filter"), filter);
+                    }
+                });
+            }
+        }
+    }
+}
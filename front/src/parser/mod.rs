@@ -1,21 +1,70 @@
-use std::{collections::HashSet, mem::take};
+use std::{
+    collections::{HashMap, HashSet},
+    mem::take,
+};
 
 use vm::Symbol;
 
 use crate::{
-    error::{ParseError, ParseResult},
+    compiler::{const_value, Consts, NotConst},
+    error::{codes, ParseError, ParseResult},
     lexer::Lexer,
-    tokens::{Token, TokenType as Tkt},
+    tokens::{StrPart, Token, TokenType as Tkt},
 };
 
-use self::ast::{Bind, Def, Expr, ExprKind, Literal, MatchArm, Pattern, Stmt, StmtKind, VarDecl};
+use self::ast::{
+    Bind, BlockKind, Def, Expr, ExprKind, Literal, Location, MatchArm, Pattern, Span, Stmt,
+    StmtKind, VarDecl,
+};
 
 pub mod ast;
+pub mod defs;
+pub mod dump;
 
 pub struct Parser {
     lexer: Lexer,
     current: Token,
     locals: HashSet<Symbol>,
+    /// Whether the next call can use `_` as an argument, which is only allowed on the right side
+    /// of a pipe
+    placeholders: bool,
+    /// Whether the function being parsed is a generator, where `yield` suspends it instead of
+    /// calling the `yield` builtin
+    generator: bool,
+    /// The keyword arguments of the global functions
+    keywords: HashMap<Symbol, Vec<Symbol>>,
+    /// The keyword arguments passed to global functions, checked once every function is parsed
+    keyword_calls: Vec<(Symbol, Symbol, usize, usize, Span)>,
+}
+
+/// The name of the argument that receives the keyword arguments
+const KWARGS: &str = "#kwargs";
+
+/// The error of a `_` that isn't an argument of the call after a pipe, which is reported even
+/// where a call stops at the first argument that can't be parsed
+const MISPLACED_PLACEHOLDER: &str =
+    "The placeholder '_' can only be an argument of the call after '|>'";
+
+/// The operators that types can overload, the others are derived from them, like `>` from `<=`
+const OVERLOADABLE: &[&str] = &[
+    "(+)", "(-)", "(*)", "(/)", "(%)", "(&&&)", "(|||)", "(^^^)", "(<<<)", "(>>>)", "(==)", "(<)",
+    "(<=)",
+];
+
+/// The arguments of a function
+#[derive(Default)]
+struct Params {
+    args: Vec<Pattern>,
+    /// The default values of the last arguments
+    defaults: Vec<Expr>,
+    /// Whether the last argument is a rest argument, like `...xs`
+    variadic: bool,
+    /// The keyword arguments, like `(timeout: 10)`, with their default values
+    keywords: Vec<(Symbol, Location, Expr)>,
+    /// The annotated types of the arguments, like `(x is Num)`
+    types: Vec<Option<VarDecl>>,
+    /// The annotated return type, like `is Num` before the `=`
+    returns: Option<VarDecl>,
 }
 
 impl Parser {
@@ -24,27 +73,95 @@ impl Parser {
             lexer,
             current: Token::default(),
             locals: HashSet::new(),
+            placeholders: false,
+            generator: false,
+            keywords: HashMap::new(),
+            keyword_calls: vec![],
         };
         this.next()?;
         Ok(this)
     }
 
-    pub fn parse(mut self) -> ParseResult<Vec<Stmt>> {
+    pub fn parse(self) -> ParseResult<Vec<Stmt>> {
+        let (stmts, errors) = self.parse_recovering();
+        match errors.into_iter().next() {
+            Some(e) => Err(e),
+            None => Ok(stmts),
+        }
+    }
+
+    /// Parses every statement, skipping the ones with syntax errors, so all the errors are
+    /// reported along with the statements that could be parsed
+    pub fn parse_recovering(mut self) -> (Vec<Stmt>, Vec<ParseError>) {
         let mut stmts = Vec::new();
+        let mut errors = Vec::new();
+
         while self.current.token != Tkt::Eof {
-            match self.current.token {
-                Tkt::Type => {
-                    stmts.push(self.type_()?);
+            let stmt = match self.current.token {
+                Tkt::Type => self.type_(),
+                Tkt::Def | Tkt::Str(_) => self.def_global(),
+                Tkt::Let => self.let_global(),
+                Tkt::Import => self.import(),
+                Tkt::Struct => self.struct_(),
+                Tkt::Trait => self.trait_(),
+                Tkt::Impl => self.impl_(),
+                Tkt::Module => self.module(),
+                Tkt::Test => self.block(BlockKind::Test),
+                Tkt::Bench => self.block(BlockKind::Bench),
+
+                ref other => self
+                    .throw(format!("Unexpected token '{other}'"))
+                    .map_err(|e| e.with_help("the statements of a file start with 'def', 'let', 'type', 'struct', 'trait', 'impl', 'module', 'import', 'test' or 'bench'")),
+            };
+
+            match stmt {
+                Ok(stmt) => stmts.push(stmt),
+                Err(e) => {
+                    errors.push(e);
+                    self.synchronize();
                 }
+            }
+        }
 
-                Tkt::Def => stmts.push(self.def_global()?),
-                Tkt::Let => stmts.push(self.let_global()?),
-
-                ref other => self.throw(format!("Unexpected token '{other}'"))?,
+        for (callee, keyword, line, column, span) in take(&mut self.keyword_calls) {
+            match self.keywords.get(&callee) {
+                Some(keywords) if !keywords.contains(&keyword) => {
+                    let message = format!(
+                        "'{}' has no keyword argument '{}'",
+                        callee.as_str(),
+                        keyword.as_str()
+                    );
+                    let e = ParseError::throw::<()>(line, column, message).unwrap_err();
+                    errors.push(e.with_code(codes::UNKNOWN_KEYWORD).with_span(span));
+                }
+                _ => {}
             }
         }
 
-        Ok(stmts)
+        (stmts, errors)
+    }
+
+    /// Skips the tokens after a syntax error, until a statement that starts a line or that comes
+    /// right after an `end` or a `;`
+    ///
+    /// The statements that don't start a line are usually the local `let`s and `def`s of the
+    /// function that had the error, which would only raise more errors
+    fn synchronize(&mut self) {
+        while self.current.token != Tkt::Eof {
+            let line = self.current.line;
+            let closed = matches!(self.current.token, Tkt::End | Tkt::Semicolon);
+
+            // the lexer skips the characters it can't read, so its errors are skipped as well
+            match self.lexer.next() {
+                Some(Ok(token)) => self.current = token,
+                Some(Err(_)) => continue,
+                None => return,
+            }
+
+            if (closed || self.current.line > line) && self.at_stmt().unwrap_or(false) {
+                return;
+            }
+        }
     }
 
     pub fn let_global(&mut self) -> ParseResult<Stmt> {
@@ -64,8 +181,43 @@ impl Parser {
         Ok(Stmt::new(StmtKind::Let { bind, value }, line, column))
     }
 
+    fn import(&mut self) -> ParseResult<Stmt> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.expect(Tkt::Import)?;
+        let name = self.var_decl()?;
+
+        Ok(Stmt::new(StmtKind::Import(name), line, column))
+    }
+
     pub fn parse_expr(mut self) -> ParseResult<Expr> {
-        self.expr()
+        let expr = self.expr()?;
+
+        if self.current.token != Tkt::Eof {
+            self.throw(format!("Unexpected token '{}'", self.current.token))?;
+        }
+
+        Ok(expr)
+    }
+
+    /// Checks if the input starts with a statement (like `let` or `def`) instead of an expression,
+    /// a string followed by `def` is the docstring of a `def`
+    pub fn at_stmt(&mut self) -> ParseResult<bool> {
+        Ok(match self.current.token {
+            Tkt::Def
+            | Tkt::Let
+            | Tkt::Type
+            | Tkt::Struct
+            | Tkt::Import
+            | Tkt::Trait
+            | Tkt::Impl
+            | Tkt::Module
+            | Tkt::Test
+            | Tkt::Bench => true,
+            Tkt::Str(_) => self.peek()?.token == Tkt::Def,
+            _ => false,
+        })
     }
 
     fn type_(&mut self) -> ParseResult<Stmt> {
@@ -102,11 +254,12 @@ impl Parser {
         let mut members = vec![];
 
         while self.current.token != Tkt::End {
-            self.expect(Tkt::Def)?;
-            let bind = self.var_decl()?;
-            let value = self.function()?;
+            let doc = self.docstring()?;
+            let generator = self.def_keyword()?;
+            let bind = self.member_name()?;
+            let value = self.clauses(bind, generator)?;
 
-            members.push(Def { bind, value })
+            members.push(Def { bind, value, doc })
         }
 
         self.expect(Tkt::End)?;
@@ -122,16 +275,319 @@ impl Parser {
         ))
     }
 
+    fn struct_(&mut self) -> ParseResult<Stmt> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.expect(Tkt::Struct)?;
+        let name = self.var_decl()?;
+        self.expect(Tkt::Assign)?;
+
+        let mut fields = vec![];
+        while let Tkt::Name(field) = self.current.token {
+            if fields.contains(&field) {
+                self.throw(format!("Duplicated field '{}'", field))?;
+            }
+
+            fields.push(field);
+            self.next()?;
+        }
+
+        // the methods are optional, unlike on types, since structs are often just data
+        let mut members = vec![];
+        if self.current.token == Tkt::With {
+            self.next()?;
+
+            while self.current.token != Tkt::End {
+                let doc = self.docstring()?;
+                let generator = self.def_keyword()?;
+                let bind = self.member_name()?;
+                let value = self.clauses(bind, generator)?;
+
+                members.push(Def { bind, value, doc })
+            }
+
+            self.expect(Tkt::End)?;
+        }
+
+        Ok(Stmt::new(
+            StmtKind::Struct {
+                name,
+                fields,
+                members,
+            },
+            line,
+            column,
+        ))
+    }
+
+    fn trait_(&mut self) -> ParseResult<Stmt> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.expect(Tkt::Trait)?;
+        let name = self.var_decl()?;
+        self.expect(Tkt::With)?;
+
+        let mut required = vec![];
+        let mut provided = vec![];
+
+        while self.current.token != Tkt::End {
+            let doc = self.docstring()?;
+            let generator = self.def_keyword()?;
+            let (line, column) = (self.current.line, self.current.column);
+            let bind = self.var_decl()?;
+
+            // a method without a body only declares its arguments, so it must be implemented
+            let state = self.state();
+            let mut args = vec![];
+            while let Tkt::Name(arg) = self.current.token {
+                args.push(arg);
+                self.next()?;
+            }
+
+            let arity = if matches!(self.current.token, Tkt::Def | Tkt::End | Tkt::Str(_)) {
+                let arity = args.len();
+                required.push((bind, args, doc));
+                arity
+            } else {
+                self.set_state(state);
+                let value = self.clauses(bind, generator)?;
+                let arity = match &value.kind {
+                    ExprKind::Lambda { args, .. } => args.len(),
+                    _ => unreachable!(),
+                };
+                provided.push(Def { bind, value, doc });
+                arity
+            };
+
+            if arity == 0 {
+                let message = format!(
+                    "The trait method '{}' must take the value it's called on as an argument",
+                    bind.as_str()
+                );
+                ParseError::throw(line, column, message)
+                    .map_err(|e| e.with_code(codes::INVALID_DEFINITION))?;
+            }
+        }
+
+        self.expect(Tkt::End)?;
+
+        Ok(Stmt::new(
+            StmtKind::Trait {
+                name,
+                required,
+                provided,
+            },
+            line,
+            column,
+        ))
+    }
+
+    /// Parses `test "name" do ... end` and `bench "name" do ... end`, `do` isn't a keyword, so it
+    /// can still be used as a name
+    ///
+    /// The body is a sequence of expressions separated by `;`, only the last value is kept
+    fn block(&mut self, kind: BlockKind) -> ParseResult<Stmt> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.next()?;
+        let name = match &self.current.token {
+            Tkt::Str(name) => name.clone(),
+            other => self.throw(format!("Expected the name of the {kind}, found '{other}'"))?,
+        };
+        self.next()?;
+        self.expect(Tkt::Name(Symbol::from("do")))?;
+
+        self.locals = HashSet::new();
+        let mut exprs = vec![self.expr()?];
+        while self.current.token == Tkt::Semicolon {
+            self.next()?;
+            exprs.push(self.expr()?);
+        }
+        self.expect(Tkt::End)?;
+
+        let mut body = exprs.pop().unwrap();
+        while let Some(expr) = exprs.pop() {
+            let (line, column) = (expr.line(), expr.column());
+            body = Expr::new(
+                ExprKind::Let {
+                    bind: Pattern::Id(Symbol::from("_"), Location { line, column }),
+                    value: Box::new(expr),
+                    body: Box::new(body),
+                },
+                line,
+                column,
+            );
+        }
+
+        Ok(Stmt::new(
+            StmtKind::Block { kind, name, body },
+            line,
+            column,
+        ))
+    }
+
+    fn impl_(&mut self) -> ParseResult<Stmt> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.expect(Tkt::Impl)?;
+        let trait_ = self.var_decl()?;
+        self.expect(Tkt::For)?;
+        let ty = self.var_decl()?;
+        self.expect(Tkt::With)?;
+
+        let mut members = vec![];
+
+        while self.current.token != Tkt::End {
+            let doc = self.docstring()?;
+            let generator = self.def_keyword()?;
+            let bind = self.var_decl()?;
+            let value = self.clauses(bind, generator)?;
+
+            members.push(Def { bind, value, doc })
+        }
+
+        self.expect(Tkt::End)?;
+
+        Ok(Stmt::new(
+            StmtKind::Impl {
+                trait_,
+                ty,
+                members,
+            },
+            line,
+            column,
+        ))
+    }
+
+    /// Parses `module Foo.Bar with ... end`, a group of functions and constants that's added to the
+    /// module at the path, which is created if it doesn't exist, the members after `priv` are private
+    fn module(&mut self) -> ParseResult<Stmt> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.expect(Tkt::Module)?;
+        let path = self.path()?;
+        self.expect(Tkt::With)?;
+
+        let mut members = vec![];
+        let mut consts = vec![];
+        let mut private = vec![];
+        let mut known = Consts::new();
+
+        while self.current.token != Tkt::End {
+            let doc = self.docstring()?;
+            let is_private = self.current.token == Tkt::Priv;
+            if is_private {
+                self.next()?;
+            }
+
+            if self.current.token == Tkt::Const {
+                let (name, value) = self.const_(path, &mut known)?;
+                if is_private {
+                    private.push(name);
+                }
+                consts.push((name, value));
+                continue;
+            }
+
+            let generator = self.def_keyword()?;
+            let bind = self.var_decl()?;
+            let value = self.clauses(bind, generator)?;
+
+            if is_private {
+                private.push(bind);
+            }
+            members.push(Def { bind, value, doc })
+        }
+
+        self.expect(Tkt::End)?;
+
+        Ok(Stmt::new(
+            StmtKind::Module {
+                path,
+                members,
+                consts,
+                private,
+            },
+            line,
+            column,
+        ))
+    }
+
+    /// Parses `const NAME = value` inside of the module at `path`, the value must be known at
+    /// compile time, so it can only be made of literals, operators and the constants in `known`,
+    /// which it's added to
+    fn const_(&mut self, path: VarDecl, known: &mut Consts) -> ParseResult<(VarDecl, Expr)> {
+        self.expect(Tkt::Const)?;
+        let (line, column) = (self.current.line, self.current.column);
+        let name = self.var_decl()?;
+        self.expect(Tkt::Assign)?;
+
+        self.locals = HashSet::new();
+        let value = self.expr()?;
+        match const_value(&value, known) {
+            Ok(constant) => {
+                known.insert(format!("{}.{}", path, name), constant);
+            }
+            Err(NotConst::Unknown) => {
+                let message = format!(
+                    "The value of the constant '{}' isn't known at compile time",
+                    name
+                );
+                ParseError::throw(line, column, message).map_err(|e| {
+                    e.with_code(codes::INVALID_DEFINITION).with_help(
+                        "constants can only be made of literals, tuples, lists, operators, `if` \
+                         and the constants defined before them in the module",
+                    )
+                })?;
+            }
+            Err(NotConst::Raises(e)) => {
+                let message = format!(
+                    "The value of the constant '{}' raises {}: {}",
+                    name, e.err, e.msg
+                );
+                ParseError::throw(line, column, message)
+                    .map_err(|e| e.with_code(codes::INVALID_DEFINITION))?;
+            }
+        }
+
+        Ok((name, value))
+    }
+
+    /// Parses the docstring of a `def`, which is a string right before it, or before `priv def`
+    fn docstring(&mut self) -> ParseResult<Option<String>> {
+        match self.current.token.clone() {
+            Tkt::Str(doc) if matches!(self.peek()?.token, Tkt::Def | Tkt::Priv) => {
+                self.next()?;
+                Ok(Some(doc))
+            }
+            _ => Ok(None),
+        }
+    }
+
     fn def_global(&mut self) -> ParseResult<Stmt> {
         let line = self.current.line;
         let column = self.current.column;
 
-        self.expect(Tkt::Def)?;
+        let doc = self.docstring()?;
+        let generator = self.def_keyword()?;
 
         let bind = self.var_decl()?;
-        let value = self.function()?;
+        let value = self.clauses(bind, generator)?;
+
+        if let ExprKind::Lambda { keywords, .. } = &value.kind {
+            self.keywords.insert(bind, keywords.clone());
+        }
 
-        Ok(Stmt::new(StmtKind::Def(Def { bind, value }), line, column))
+        Ok(Stmt::new(
+            StmtKind::Def(Def { bind, value, doc }),
+            line,
+            column,
+        ))
     }
 
     fn next(&mut self) -> ParseResult<()> {
@@ -149,7 +605,17 @@ impl Parser {
     }
 
     fn throw<T>(&self, err: impl Into<String>) -> ParseResult<T> {
-        ParseError::throw(self.current.line, self.current.column, err.into())
+        let res = ParseError::throw(self.current.line, self.current.column, err.into())
+            .map_err(|e| e.with_span(self.current.span));
+
+        if self.current.token == Tkt::Eof {
+            res.map_err(|e| {
+                e.at_eof()
+                    .with_help("the source ends before this is complete, it may be missing an 'end' or a closing bracket")
+            })
+        } else {
+            res
+        }
     }
 
     fn expect(&mut self, expected: Tkt) -> ParseResult<()> {
@@ -159,7 +625,8 @@ impl Parser {
 
     fn check_unused(&self, name: &Symbol) -> ParseResult<()> {
         if self.locals.contains(name) && name.as_str() != "_" {
-            self.throw(format!("Can't shadow name '{}'", name.as_str()))?;
+            self.throw(format!("Can't shadow name '{}'", name.as_str()))
+                .map_err(|e| e.with_help("give it another name"))?;
         }
 
         Ok(())
@@ -188,6 +655,14 @@ impl Parser {
         self.skip(tokens)
     }
 
+    /// Where the current token starts
+    fn location(&self) -> Location {
+        Location {
+            line: self.current.line,
+            column: self.current.column,
+        }
+    }
+
     fn state(&self) -> (Token, (usize, usize, usize)) {
         (self.current.clone(), self.lexer.state())
     }
@@ -234,12 +709,153 @@ impl Parser {
         ))
     }
 
-    fn args(&mut self) -> ParseResult<Vec<(Vec<Symbol>, Pattern)>> {
-        let mut args = vec![self.primary_pat()?];
-        while self.current.token != Tkt::Assign {
-            args.push(self.primary_pat()?);
+    /// Parses the arguments of a function, adding the names they bind to `ids`
+    fn args(&mut self, ids: &mut Vec<Symbol>) -> ParseResult<Params> {
+        let mut params = Params::default();
+
+        loop {
+            if self.current.token == Tkt::Ellipsis {
+                self.next()?;
+                let location = self.location();
+                params.args.push(Pattern::Id(self.var_decl()?, location));
+
+                if !params.defaults.is_empty() || !params.keywords.is_empty() {
+                    self.throw(
+                        "A function can't have both a rest argument and default or keyword arguments",
+                    )?;
+                }
+                params.returns = self.return_type()?;
+                self.assert(Tkt::Assign)
+                    .or_else(|_| self.throw("The rest argument must be the last one"))?;
+
+                params.variadic = true;
+                return Ok(params);
+            }
+
+            match self.default_arg()? {
+                Some((name, location, default, true)) => {
+                    params.keywords.push((name, location, default))
+                }
+                Some(_) if !params.keywords.is_empty() => {
+                    self.throw("A function can't have both default and keyword arguments")?
+                }
+                Some((name, location, default, false)) => {
+                    params.args.push(Pattern::Id(name, location));
+                    params.defaults.push(default);
+                }
+                None if !params.keywords.is_empty() => {
+                    self.throw("Expected a keyword argument, the arguments after one must be keyword arguments too")?
+                }
+                None if !params.defaults.is_empty() => {
+                    self.throw("Expected a default value, the arguments after one with a default value must have one too")?
+                }
+                None => match self.annotated_arg()? {
+                    Some((name, location, ty)) => {
+                        params.types.resize(params.args.len(), None);
+                        params.types.push(Some(ty));
+                        params.args.push(Pattern::Id(name, location));
+                    }
+                    None => {
+                        let (names, arg) = self.primary_pat()?;
+                        ids.extend(names);
+                        params.args.push(arg);
+                    }
+                },
+            }
+
+            if let Tkt::Assign | Tkt::Is = self.current.token {
+                break;
+            }
+        }
+
+        params.returns = self.return_type()?;
+
+        if params.args.len() == params.defaults.len() {
+            self.throw("A function needs at least one argument without a default value")?;
+        }
+
+        if !params.defaults.is_empty() && !params.keywords.is_empty() {
+            self.throw("A function can't have both default and keyword arguments")?;
+        }
+
+        Ok(params)
+    }
+
+    /// Parses an argument with a default value, like `(y = 10)`, or a keyword argument, like
+    /// `(timeout: 10)`, returning whether it's a keyword argument
+    fn default_arg(&mut self) -> ParseResult<Option<(Symbol, Location, Expr, bool)>> {
+        if self.current.token != Tkt::Lparen {
+            return Ok(None);
+        }
+
+        let state = self.state();
+        self.next()?;
+
+        let location = self.location();
+        let name = match self.current.token {
+            Tkt::Name(name) => name,
+            _ => {
+                self.set_state(state);
+                return Ok(None);
+            }
+        };
+
+        let keyword = match self.peek()?.token {
+            Tkt::Assign => false,
+            Tkt::Colon => true,
+            _ => {
+                self.set_state(state);
+                return Ok(None);
+            }
+        };
+
+        self.next()?;
+        self.next()?;
+        let default = self.expr()?;
+        self.expect(Tkt::Rparen)?;
+
+        Ok(Some((name, location, default, keyword)))
+    }
+
+    /// Parses an argument annotated with its type, like `(x is Num)`
+    fn annotated_arg(&mut self) -> ParseResult<Option<(Symbol, Location, VarDecl)>> {
+        if self.current.token != Tkt::Lparen {
+            return Ok(None);
+        }
+
+        let state = self.state();
+        self.next()?;
+
+        let location = self.location();
+        let name = match self.current.token {
+            Tkt::Name(name) => name,
+            _ => {
+                self.set_state(state);
+                return Ok(None);
+            }
+        };
+
+        if self.peek()?.token != Tkt::Is {
+            self.set_state(state);
+            return Ok(None);
+        }
+
+        self.next()?;
+        self.next()?;
+        let ty = self.path()?;
+        self.expect(Tkt::Rparen)?;
+
+        Ok(Some((name, location, ty)))
+    }
+
+    /// Parses the return type of a function, like `is Num`, if it's annotated
+    fn return_type(&mut self) -> ParseResult<Option<VarDecl>> {
+        if self.current.token != Tkt::Is {
+            return Ok(None);
         }
-        Ok(args)
+
+        self.next()?;
+        self.path().map(Some)
     }
 
     fn become_(&mut self) -> ParseResult<Expr> {
@@ -274,6 +890,12 @@ impl Parser {
 
         self.expect(Tkt::With)?;
 
+        let arms = self.match_arms()?;
+
+        Ok(Expr::new(ExprKind::Match { expr, arms }, line, column))
+    }
+
+    fn match_arms(&mut self) -> ParseResult<Vec<MatchArm>> {
         let mut arms = vec![];
 
         let mut last_state = self.state();
@@ -284,11 +906,11 @@ impl Parser {
         self.set_state(last_state);
 
         // this throws all errors and exit
-        if self.current.token == Tkt::Bar {
+        if arms.is_empty() || self.current.token == Tkt::Bar {
             self.match_arm()?;
         }
 
-        Ok(Expr::new(ExprKind::Match { expr, arms }, line, column))
+        Ok(arms)
     }
 
     fn match_arm(&mut self) -> ParseResult<MatchArm> {
@@ -297,6 +919,19 @@ impl Parser {
         self.expect(Tkt::Bar)?;
 
         let (ids, cond) = self.pattern()?;
+        let mut conds = vec![cond];
+
+        // the alternatives must bind the same names, since the body can use any of them
+        while self.current.token == Tkt::Bar {
+            self.next()?;
+
+            let (_, alt) = self.pattern()?;
+            if alt.bindings() != conds[0].bindings() {
+                self.throw("All the alternatives of a pattern must bind the same names")?;
+            }
+
+            conds.push(alt);
+        }
 
         let guard = if self.current.token == Tkt::If {
             self.next()?;
@@ -313,7 +948,7 @@ impl Parser {
             self.locals.remove(&id);
         }
 
-        Ok(MatchArm::new(cond, body, guard, line, column))
+        Ok(MatchArm::new(conds, body, guard, line, column))
     }
 
     fn try_(&mut self) -> ParseResult<Expr> {
@@ -326,54 +961,242 @@ impl Parser {
 
         self.expect(Tkt::Rescue)?;
 
-        let bind = self.var_decl()?;
+        // the exception can be matched like in a `match`, or just bound to a name
+        let arms = if self.current.token == Tkt::Bar {
+            self.match_arms()?
+        } else {
+            let (line, column) = (self.current.line, self.current.column);
+            let bind = self.var_decl()?;
+            let rescue = self.expr()?;
+
+            vec![MatchArm::new(
+                vec![Pattern::Id(bind, Location { line, column })],
+                rescue,
+                None,
+                line,
+                column,
+            )]
+        };
+
+        Ok(Expr::new(ExprKind::Try { body, arms }, line, column))
+    }
+
+    fn fn_(&mut self) -> ParseResult<Expr> {
+        self.expect(Tkt::Fn)?;
+        let generator = self.generator_mark()?;
+        self.function(generator)
+    }
+
+    /// Parses `def`, returning if it's `def*`, which defines a generator
+    fn def_keyword(&mut self) -> ParseResult<bool> {
+        self.expect(Tkt::Def)?;
+        self.generator_mark()
+    }
+
+    /// Skips the `*` after `fn` or `def` that makes the function a generator
+    fn generator_mark(&mut self) -> ParseResult<bool> {
+        if self.current.token != Tkt::Mul {
+            return Ok(false);
+        }
+
+        self.next()?;
+        Ok(true)
+    }
+
+    /// Parses the arguments and the body of a function, `yield` suspends the function if it's a
+    /// generator
+    fn function(&mut self, generator: bool) -> ParseResult<Expr> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        let locals = take(&mut self.locals);
+
+        let mut ids = vec![];
+        let Params {
+            mut args,
+            mut defaults,
+            variadic,
+            keywords,
+            types,
+            returns,
+        } = self.args(&mut ids)?;
+
+        let outer = std::mem::replace(&mut self.generator, generator);
+        let body = self.fn_body();
+        self.generator = outer;
+
+        let mut body = body?;
+
+        // the keyword arguments are received in a dictionary, which is empty if none is passed
+        let names = keywords.iter().map(|(name, ..)| *name).collect();
+        if !keywords.is_empty() {
+            body = keyword_body(keywords, body);
+            args.push(Pattern::Id(Symbol::from(KWARGS), Location { line, column }));
+            defaults.push(Expr::new(ExprKind::Dict(vec![]), line, column));
+        }
+
+        for id in ids {
+            self.locals.remove(&id);
+        }
+
+        self.locals = locals;
+
+        Ok(Expr::new(
+            ExprKind::Lambda {
+                args,
+                defaults,
+                variadic,
+                keywords: names,
+                types,
+                returns,
+                body: Box::new(body),
+                generator,
+            },
+            line,
+            column,
+        ))
+    }
+
+    /// Parses a function and the clauses right after it, like `def fib 0 = 0` followed by
+    /// `def fib n = ...`, merging them into a single function that matches its arguments against
+    /// each clause in order
+    fn clauses(&mut self, name: Symbol, generator: bool) -> ParseResult<Expr> {
+        let first = self.function(generator)?;
+        let mut clauses = vec![];
+
+        while self.next_clause(name, generator)? {
+            self.def_keyword()?;
+            self.member_name()?;
+            clauses.push(self.function(generator)?);
+        }
+
+        if clauses.is_empty() {
+            return Ok(first);
+        }
+
+        let (line, column) = (first.line(), first.column());
+        // the annotations of the first clause apply to the whole function
+        let (arity, types, returns) = match &first.kind {
+            ExprKind::Lambda {
+                args,
+                types,
+                returns,
+                ..
+            } => (args.len(), types.clone(), *returns),
+            _ => unreachable!(),
+        };
+
+        let mut arms = vec![];
+        for clause in std::iter::once(first).chain(clauses) {
+            let (line, column) = (clause.line(), clause.column());
+
+            let (args, body) = match clause.kind {
+                ExprKind::Lambda {
+                    defaults, variadic, ..
+                } if !defaults.is_empty() || variadic => {
+                    let message = format!(
+                        "The clauses of '{}' can't have default or rest arguments",
+                        name.as_str()
+                    );
+                    return ParseError::throw(line, column, message)
+                        .map_err(|e| e.with_code(codes::INVALID_DEFINITION));
+                }
+                ExprKind::Lambda { args, body, .. } => (args, body),
+                _ => unreachable!(),
+            };
+
+            if args.len() != arity {
+                let message = format!(
+                    "The clauses of '{}' take {} arguments, but this one takes {}",
+                    name.as_str(),
+                    arity,
+                    args.len()
+                );
+                return ParseError::throw(line, column, message)
+                    .map_err(|e| e.with_code(codes::INVALID_DEFINITION));
+            }
+
+            let cond = match <[Pattern; 1]>::try_from(args) {
+                Ok([arg]) => arg,
+                Err(args) => Pattern::Tuple(args),
+            };
+            arms.push(MatchArm::new(vec![cond], *body, None, line, column));
+        }
 
-        let rescue = Box::new(self.expr()?);
+        let params: Vec<_> = (0..arity)
+            .map(|i| Symbol::from(format!("#arg{}", i)))
+            .collect();
+        let mut values: Vec<_> = params
+            .iter()
+            .map(|param| Expr::new(ExprKind::Var(*param), line, column))
+            .collect();
+
+        let expr = match values.len() {
+            1 => values.remove(0),
+            _ => Expr::new(ExprKind::Tuple(values), line, column),
+        };
 
         Ok(Expr::new(
-            ExprKind::Try { body, bind, rescue },
+            ExprKind::Lambda {
+                args: params
+                    .into_iter()
+                    .map(|param| Pattern::Id(param, Location { line, column }))
+                    .collect(),
+                defaults: vec![],
+                variadic: false,
+                keywords: vec![],
+                types,
+                returns,
+                body: Box::new(Expr::new(
+                    ExprKind::Match {
+                        expr: Box::new(expr),
+                        arms,
+                    },
+                    line,
+                    column,
+                )),
+                generator,
+            },
             line,
             column,
         ))
     }
 
-    fn fn_(&mut self) -> ParseResult<Expr> {
-        self.expect(Tkt::Fn)?;
-        self.function()
-    }
-
-    fn function(&mut self) -> ParseResult<Expr> {
-        let line = self.current.line;
-        let column = self.current.column;
-
-        let locals = take(&mut self.locals);
+    /// Checks if the current token starts another clause of `name`
+    fn next_clause(&mut self, name: Symbol, generator: bool) -> ParseResult<bool> {
+        if self.current.token != Tkt::Def {
+            return Ok(false);
+        }
 
-        let mut ids = vec![];
-        let mut args = vec![];
+        let state = self.state();
+        let same = matches!(self.def_keyword(), Ok(marked) if marked == generator)
+            && matches!(self.member_name(), Ok(member) if member == name);
+        self.set_state(state);
 
-        let pats = self.args()?;
+        Ok(same)
+    }
 
-        for (names, arg) in pats {
-            ids.extend(names);
-            args.push(arg);
+    /// Parses the name of a type member, which can be an operator like `(+)` to overload it
+    fn member_name(&mut self) -> ParseResult<VarDecl> {
+        if self.current.token != Tkt::Lparen {
+            return self.var_decl();
         }
 
-        let body = self.fn_body()?;
+        self.next()?;
+        let token = self.current.token.clone();
+        let op = match self.operator_section()? {
+            Some(op) => op,
+            None => self.throw(format!(
+                "Expected an operator, found '{}'",
+                self.current.token
+            ))?,
+        };
 
-        for id in ids {
-            self.locals.remove(&id);
+        if !OVERLOADABLE.contains(&op.as_str()) {
+            self.throw(format!("The '{}' operator can't be overloaded", token))?;
         }
 
-        self.locals = locals;
-
-        Ok(Expr::new(
-            ExprKind::Lambda {
-                args,
-                body: Box::new(body),
-            },
-            line,
-            column,
-        ))
+        Ok(op)
     }
 
     fn fn_body(&mut self) -> ParseResult<Expr> {
@@ -381,6 +1204,18 @@ impl Parser {
         self.expr()
     }
 
+    /// Parses a dotted path, like `Geometry.Shapes`
+    fn path(&mut self) -> ParseResult<VarDecl> {
+        let mut path = self.var_decl()?.as_str().to_string();
+        while self.current.token == Tkt::Dot {
+            self.next()?;
+            path.push('.');
+            path.push_str(self.var_decl()?.as_str());
+        }
+
+        Ok(path.into())
+    }
+
     fn var_decl(&mut self) -> ParseResult<VarDecl> {
         let name = match self.current.token {
             Tkt::Name(id) => id,
@@ -410,7 +1245,12 @@ impl Parser {
         };
 
         match rhs {
-            Some(rhs) => Ok((identifiers, Pattern::List(Box::new(lhs), Box::new(rhs)))),
+            Some(rhs) => match lhs {
+                Pattern::Lit(Literal::Str(prefix)) => {
+                    Ok((identifiers, Pattern::Prefix(prefix, Box::new(rhs))))
+                }
+                lhs => Ok((identifiers, Pattern::List(Box::new(lhs), Box::new(rhs)))),
+            },
             None => Ok((identifiers, lhs)),
         }
     }
@@ -420,12 +1260,17 @@ impl Parser {
             return self.primary_pat();
         }
 
+        let location = self.location();
         let mut path = vec![self.var_decl()?];
         while let Tkt::Dot = self.current.token {
             self.next()?;
             path.push(self.var_decl()?);
         }
 
+        if self.current.token == Tkt::Lbrace {
+            return self.struct_pat(path);
+        }
+
         let mut last_state = self.state();
         let mut patterns = vec![];
         let mut identifiers = vec![];
@@ -440,7 +1285,7 @@ impl Parser {
         self.set_state(last_state);
 
         if path.len() == 1 && patterns.is_empty() {
-            Ok((vec![], Pattern::Id(path.pop().unwrap())))
+            Ok((vec![], Pattern::Id(path.pop().unwrap(), location)))
         } else {
             Ok((identifiers, Pattern::Variant(path, patterns)))
         }
@@ -451,11 +1296,25 @@ impl Parser {
 
         let pat = match self.current.token {
             Tkt::Num(n) => Pattern::Lit(Literal::Num(n)),
+            Tkt::Int(n) => Pattern::Lit(Literal::Int(n)),
             Tkt::Str(ref s) => Pattern::Lit(Literal::Str(s.to_string())),
+            Tkt::Bytes(ref b) => Pattern::Lit(Literal::Bytes(b.clone())),
+            Tkt::Sym(s) => Pattern::Lit(Literal::Sym(s)),
             Tkt::Nil => Pattern::Lit(Literal::Unit),
             Tkt::True => Pattern::Lit(Literal::Bool(true)),
             Tkt::False => Pattern::Lit(Literal::Bool(false)),
-            Tkt::Name(name) if peek != Tkt::Dot => Pattern::Id(name),
+            Tkt::Name(name) if peek == Tkt::Lbrace => {
+                self.next()?;
+                return self.struct_pat(vec![name]);
+            }
+            Tkt::Name(name) if peek != Tkt::Dot => Pattern::Id(name, self.location()),
+            Tkt::Caret => match peek {
+                Tkt::Name(name) => {
+                    self.next()?;
+                    Pattern::Pin(name)
+                }
+                other => self.throw(format!("Expected a name after '^', found '{other}'"))?,
+            },
             Tkt::Lparen => {
                 self.next()?;
 
@@ -485,6 +1344,8 @@ impl Parser {
                 self.assert(Tkt::Rbrack)?;
                 Pattern::EmptyList
             }
+            Tkt::DictOpen => return self.dict_pat(),
+            Tkt::ArrayOpen => return self.array_pat(),
             ref other => self.throw(format!("Expected pattern, found '{other}'"))?,
         };
 
@@ -493,6 +1354,106 @@ impl Parser {
         Ok((vec![], pat))
     }
 
+    /// Parses an array pattern, like `#[x, y, _]`
+    fn array_pat(&mut self) -> ParseResult<(Vec<Symbol>, Pattern)> {
+        self.expect(Tkt::ArrayOpen)?;
+
+        let mut pats = vec![];
+        let mut identifiers = vec![];
+
+        while self.current.token != Tkt::Rbrack {
+            let (ids, pat) = self.pattern()?;
+            identifiers.extend(ids);
+            pats.push(pat);
+
+            if self.current.token != Tkt::Rbrack {
+                self.expect_and_skip(Tkt::Comma)?;
+            }
+        }
+
+        self.expect(Tkt::Rbrack)?;
+
+        Ok((identifiers, Pattern::Array(pats)))
+    }
+
+    /// Parses a dictionary pattern, like `%{name: n, "age" => a}`, the keys must be literals
+    fn dict_pat(&mut self) -> ParseResult<(Vec<Symbol>, Pattern)> {
+        self.expect(Tkt::DictOpen)?;
+
+        let mut entries = vec![];
+        let mut identifiers = vec![];
+
+        while self.current.token != Tkt::Rbrace {
+            // `name: pattern` is the same as `:name => pattern`
+            let shorthand =
+                matches!(self.current.token, Tkt::Name(_)) && self.peek()?.token == Tkt::Colon;
+
+            let key = match self.current.token {
+                Tkt::Name(name) if shorthand => Literal::Sym(name),
+                Tkt::Num(n) => Literal::Num(n),
+                Tkt::Int(n) => Literal::Int(n),
+                Tkt::Str(ref s) => Literal::Str(s.to_string()),
+                Tkt::Bytes(ref b) => Literal::Bytes(b.clone()),
+                Tkt::Sym(s) => Literal::Sym(s),
+                Tkt::True => Literal::Bool(true),
+                Tkt::False => Literal::Bool(false),
+                Tkt::Nil => Literal::Unit,
+                ref other => self.throw(format!("Expected dictionary key, found '{other}'"))?,
+            };
+
+            self.next()?;
+            if shorthand {
+                self.expect(Tkt::Colon)?;
+            } else {
+                self.expect(Tkt::FatArrow)?;
+            }
+
+            let (ids, pat) = self.pattern()?;
+            identifiers.extend(ids);
+            entries.push((key, pat));
+
+            if self.current.token != Tkt::Rbrace {
+                self.expect_and_skip(Tkt::Comma)?;
+            }
+        }
+
+        self.expect(Tkt::Rbrace)?;
+
+        Ok((identifiers, Pattern::Dict(entries)))
+    }
+
+    /// Parses the fields of a struct pattern, like `Point{x: 0, y}`, where `y` is the same as
+    /// `y: y`
+    fn struct_pat(&mut self, path: Vec<VarDecl>) -> ParseResult<(Vec<Symbol>, Pattern)> {
+        self.expect(Tkt::Lbrace)?;
+
+        let mut fields = vec![];
+        let mut identifiers = vec![];
+
+        while self.current.token != Tkt::Rbrace {
+            let location = self.location();
+            let name = self.var_decl()?;
+
+            let pat = if self.current.token == Tkt::Colon {
+                self.next()?;
+                let (ids, pat) = self.pattern()?;
+                identifiers.extend(ids);
+                pat
+            } else {
+                Pattern::Id(name, location)
+            };
+            fields.push((name, pat));
+
+            if self.current.token != Tkt::Rbrace {
+                self.expect_and_skip(Tkt::Comma)?;
+            }
+        }
+
+        self.expect(Tkt::Rbrace)?;
+
+        Ok((identifiers, Pattern::Struct(path, fields)))
+    }
+
     fn let_(&mut self) -> ParseResult<Expr> {
         let line = self.current.line;
         let column = self.current.column;
@@ -525,7 +1486,7 @@ impl Parser {
     }
 
     fn def_(&mut self) -> ParseResult<Expr> {
-        self.expect(Tkt::Def)?;
+        let generator = self.def_keyword()?;
 
         let line = self.current.line;
         let column = self.current.column;
@@ -535,7 +1496,7 @@ impl Parser {
         self.check_unused(&name)?;
         self.locals.insert(name);
 
-        let value = self.function()?;
+        let value = self.function(generator)?;
 
         self.expect(Tkt::In)?;
 
@@ -562,10 +1523,21 @@ impl Parser {
             let line = self.current.line;
             let column = self.current.column;
 
+            self.placeholders = true;
+            let callee = self.logic_or()?;
+            self.placeholders = false;
+
+            let callee = match callee.kind {
+                ExprKind::App { callee, args, .. } if args.iter().any(is_placeholder) => {
+                    placeholder_lambda(*callee, args, line, column)
+                }
+                kind => Expr::new(kind, callee.location.line, callee.location.column),
+            };
+
             left = Expr::new(
                 ExprKind::App {
                     args: vec![left],
-                    callee: Box::new(self.logic_or()?),
+                    callee: Box::new(callee),
                     tail: false,
                 },
                 line,
@@ -675,12 +1647,12 @@ impl Parser {
     }
 
     fn cmp(&mut self) -> ParseResult<Expr> {
-        let mut left = self.cons()?;
+        let mut left = self.range()?;
 
         while let Tkt::Less | Tkt::LessEq | Tkt::Greater | Tkt::GreaterEq = self.current.token {
             let op = self.current.clone();
             self.next()?;
-            let right = self.cons()?;
+            let right = self.range()?;
 
             left = Expr::new(
                 ExprKind::Binary {
@@ -696,6 +1668,29 @@ impl Parser {
         Ok(left)
     }
 
+    fn range(&mut self) -> ParseResult<Expr> {
+        let left = self.cons()?;
+
+        // ranges aren't associative, `1..2..3` doesn't make sense
+        if let Tkt::DotDot | Tkt::DotDotEq = self.current.token {
+            let op = self.current.clone();
+            self.next()?;
+            let right = self.cons()?;
+
+            return Ok(Expr::new(
+                ExprKind::Binary {
+                    left: Box::new(left),
+                    op: op.token.try_into().unwrap(),
+                    right: Box::new(right),
+                },
+                op.line,
+                op.column,
+            ));
+        }
+
+        Ok(left)
+    }
+
     fn cons(&mut self) -> ParseResult<Expr> {
         let mut left = self.bitwise()?;
 
@@ -800,6 +1795,14 @@ impl Parser {
     }
 
     fn call(&mut self) -> ParseResult<Expr> {
+        if self.generator
+            && matches!(self.current.token, Tkt::Name(name) if name.as_str() == "yield")
+        {
+            return self.yield_();
+        }
+
+        // nested calls can't use the placeholder of the pipe
+        let placeholders = take(&mut self.placeholders);
         let callee = self.method_ref()?;
 
         let line = self.current.line;
@@ -807,14 +1810,65 @@ impl Parser {
 
         let mut last_state = self.state();
         let mut args = vec![];
+        let mut kwargs = vec![];
+
+        loop {
+            // `name: value` passes a keyword argument
+            if matches!(self.current.token, Tkt::Name(_)) && self.peek()?.token == Tkt::Colon {
+                let (keyword, line, column, span) = match self.current.token {
+                    Tkt::Name(name) => (
+                        name,
+                        self.current.line,
+                        self.current.column,
+                        self.current.span,
+                    ),
+                    _ => unreachable!(),
+                };
+                self.next()?;
+                self.next()?;
+
+                if let ExprKind::Var(callee) = callee.kind {
+                    if !self.locals.contains(&callee) {
+                        self.keyword_calls
+                            .push((callee, keyword, line, column, span));
+                    }
+                }
+
+                let key = Expr::new(ExprKind::Lit(Literal::Sym(keyword)), line, column);
+                kwargs.push((key, self.method_ref()?));
+                last_state = self.state();
+                continue;
+            }
+
+            let arg = match self.current.token {
+                Tkt::Name(name) if placeholders && name.as_str() == "_" => {
+                    let arg =
+                        Expr::new(ExprKind::Var(name), self.current.line, self.current.column);
+                    self.next()?;
+                    arg
+                }
+                _ => match self.method_ref() {
+                    Ok(_) if !kwargs.is_empty() => {
+                        self.set_state(last_state);
+                        return self.throw("Keyword arguments must come after the other arguments");
+                    }
+                    Ok(arg) => arg,
+                    Err(e) if e.message() == MISPLACED_PLACEHOLDER => return Err(e),
+                    Err(_) => break,
+                },
+            };
 
-        while let Ok(arg) = self.method_ref() {
             args.push(arg);
             last_state = self.state();
         }
 
         self.set_state(last_state);
 
+        // the keyword arguments are passed in a dictionary, as the last argument
+        if !kwargs.is_empty() {
+            args.push(Expr::new(ExprKind::Dict(kwargs), line, column));
+        }
+
         if args.is_empty() {
             Ok(callee)
         } else {
@@ -829,26 +1883,97 @@ impl Parser {
         }
     }
 
+    /// Parses `yield value` inside a generator, the value is a whole call, like in `yield f x`
+    fn yield_(&mut self) -> ParseResult<Expr> {
+        let (line, column) = (self.current.line, self.current.column);
+        self.next()?;
+
+        let value = self.call()?;
+        Ok(Expr::new(ExprKind::Yield(Box::new(value)), line, column))
+    }
+
     fn method_ref(&mut self) -> ParseResult<Expr> {
         let mut ty = self.primary()?;
 
-        while self.current.token == Tkt::Dot {
-            self.next()?;
-            let method = self.var_decl()?;
+        loop {
+            match self.current.token {
+                Tkt::Dot => {
+                    self.next()?;
+
+                    if let Tkt::Int(index) = self.current.token {
+                        let (line, column) = (ty.line(), ty.column());
+                        self.next()?;
+
+                        ty = Expr::new(
+                            ExprKind::TupleIndex {
+                                tup: Box::new(ty),
+                                index: index as usize,
+                            },
+                            line,
+                            column,
+                        );
+                        continue;
+                    }
 
-            ty = Expr::new(
-                ExprKind::MethodRef {
-                    ty: Box::new(ty),
-                    method,
-                },
-                self.current.line,
-                self.current.column,
-            );
+                    let method = self.var_decl()?;
+
+                    ty = Expr::new(
+                        ExprKind::MethodRef {
+                            ty: Box::new(ty),
+                            method,
+                        },
+                        self.current.line,
+                        self.current.column,
+                    );
+                }
+                Tkt::Lbrace => {
+                    let (line, column) = (ty.line(), ty.column());
+                    self.next()?;
+                    let fields = self.struct_fields()?;
+
+                    ty = Expr::new(
+                        ExprKind::Struct {
+                            ty: Box::new(ty),
+                            fields,
+                        },
+                        line,
+                        column,
+                    );
+                }
+                _ => break,
+            }
         }
 
         Ok(ty)
     }
 
+    /// Parses the fields of a struct literal or update until the closing brace, `x` is the same
+    /// as `x: x`
+    fn struct_fields(&mut self) -> ParseResult<Vec<(VarDecl, Expr)>> {
+        let mut fields = vec![];
+
+        while self.current.token != Tkt::Rbrace {
+            let (line, column) = (self.current.line, self.current.column);
+            let name = self.var_decl()?;
+
+            let value = if self.current.token == Tkt::Colon {
+                self.next()?;
+                self.expr()?
+            } else {
+                Expr::new(ExprKind::Var(name), line, column)
+            };
+            fields.push((name, value));
+
+            if self.current.token != Tkt::Rbrace {
+                self.expect_and_skip(Tkt::Comma)?;
+            }
+        }
+
+        self.expect(Tkt::Rbrace)?;
+
+        Ok(fields)
+    }
+
     fn list(&mut self) -> ParseResult<Expr> {
         let line = self.current.line;
         let column = self.current.column;
@@ -859,6 +1984,10 @@ impl Parser {
         while self.current.token != Tkt::Rbrack {
             exprs.push(self.expr()?); // compiles the argument
 
+            if exprs.len() == 1 && self.current.token == Tkt::For {
+                return self.comprehension(exprs.pop().unwrap(), line, column);
+            }
+
             if self.current.token != Tkt::Rbrack {
                 self.expect_and_skip(Tkt::Comma)?;
             }
@@ -869,6 +1998,126 @@ impl Parser {
         Ok(Expr::new(ExprKind::List(exprs), line, column))
     }
 
+    /// Parses an array literal, like `#[1, 2, 3]`
+    fn array(&mut self) -> ParseResult<Expr> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.expect(Tkt::ArrayOpen)?;
+
+        let mut exprs = Vec::new();
+        while self.current.token != Tkt::Rbrack {
+            exprs.push(self.expr()?);
+
+            if self.current.token != Tkt::Rbrack {
+                self.expect_and_skip(Tkt::Comma)?;
+            }
+        }
+
+        self.expect(Tkt::Rbrack)?;
+
+        Ok(Expr::new(ExprKind::Array(exprs), line, column))
+    }
+
+    /// Parses the rest of a list comprehension, after its body
+    fn comprehension(&mut self, body: Expr, line: usize, column: usize) -> ParseResult<Expr> {
+        self.expect(Tkt::For)?;
+
+        let (_, bind) = self.pattern()?;
+        self.expect(Tkt::In)?;
+
+        let source = self.expr()?;
+
+        let filter = if self.current.token == Tkt::If {
+            self.next()?;
+            Some(Box::new(self.expr()?))
+        } else {
+            None
+        };
+
+        self.expect(Tkt::Rbrack)?;
+
+        Ok(Expr::new(
+            ExprKind::Comprehension {
+                body: Box::new(body),
+                bind,
+                source: Box::new(source),
+                filter,
+            },
+            line,
+            column,
+        ))
+    }
+
+    /// Parses a dictionary literal, keys can be written as `name: value`, which is the same as
+    /// `:name => value`, or as `key => value`, where the key can be any expression
+    fn dict(&mut self) -> ParseResult<Expr> {
+        let line = self.current.line;
+        let column = self.current.column;
+
+        self.expect(Tkt::DictOpen)?;
+
+        // the first key may be the value of an update, like `%{value | name: new}`, which copies a
+        // struct or a dictionary replacing some of its fields
+        let mut first = None;
+        let shorthand =
+            matches!(self.current.token, Tkt::Name(_)) && self.peek()?.token == Tkt::Colon;
+        if self.current.token != Tkt::Rbrace && !shorthand {
+            let value = self.expr()?;
+
+            if self.current.token == Tkt::Bar {
+                self.next()?;
+                let fields = self.struct_fields()?;
+
+                return Ok(Expr::new(
+                    ExprKind::Update {
+                        value: Box::new(value),
+                        fields,
+                    },
+                    line,
+                    column,
+                ));
+            }
+
+            self.expect(Tkt::FatArrow)?;
+            first = Some(value);
+        }
+
+        let mut entries = Vec::new();
+        while self.current.token != Tkt::Rbrace {
+            let peek = self.peek()?.token;
+
+            let key = match self.current.token {
+                _ if first.is_some() => first.take().unwrap(),
+                Tkt::Name(name) if peek == Tkt::Colon => {
+                    let key = Expr::new(
+                        ExprKind::Lit(Literal::Sym(name)),
+                        self.current.line,
+                        self.current.column,
+                    );
+                    self.next()?;
+                    self.next()?;
+                    key
+                }
+                _ => {
+                    let key = self.expr()?;
+                    self.expect(Tkt::FatArrow)?;
+                    key
+                }
+            };
+
+            entries.push((key, self.expr()?));
+
+            if self.current.token != Tkt::Rbrace {
+                self.expect_and_skip(Tkt::Comma)?;
+            }
+        }
+
+        self.expect(Tkt::Rbrace)?;
+
+        Ok(Expr::new(ExprKind::Dict(entries), line, column))
+    }
+
     fn tuple(&mut self) -> ParseResult<Expr> {
         let line = self.current.line;
         let column = self.current.column;
@@ -877,6 +2126,10 @@ impl Parser {
 
         self.expect(Tkt::Lparen)?;
 
+        if let Some(op) = self.operator_section()? {
+            return Ok(Expr::new(ExprKind::Var(op), line, column));
+        }
+
         while self.current.token != Tkt::Rparen {
             exprs.push(self.expr()?); // compiles the argument
 
@@ -894,6 +2147,87 @@ impl Parser {
         }
     }
 
+    /// Parses an operator wrapped in parenthesis, like `(+)`, into the name of its prelude function
+    fn operator_section(&mut self) -> ParseResult<Option<Symbol>> {
+        let op = self.current.token.clone();
+        if self.peek()?.token != Tkt::Rparen {
+            return Ok(None);
+        }
+
+        match op {
+            Tkt::Add
+            | Tkt::Sub
+            | Tkt::Mul
+            | Tkt::Div
+            | Tkt::Rem
+            | Tkt::Eq
+            | Tkt::Ne
+            | Tkt::Greater
+            | Tkt::GreaterEq
+            | Tkt::Less
+            | Tkt::LessEq
+            | Tkt::Cons
+            | Tkt::BitOr
+            | Tkt::BitAnd
+            | Tkt::BitXor
+            | Tkt::Shr
+            | Tkt::Shl
+            | Tkt::Not
+            | Tkt::DotDot
+            | Tkt::DotDotEq => {}
+            Tkt::And | Tkt::Or => {
+                return self.throw(format!(
+                    "'{}' short-circuits, so it can't be used as a function, use a lambda instead",
+                    op
+                ))
+            }
+            _ => return Ok(None),
+        }
+
+        self.next()?;
+        self.expect(Tkt::Rparen)?;
+        Ok(Some(Symbol::from(format!("({})", op))))
+    }
+
+    fn interp(&mut self, parts: Vec<StrPart>, line: usize, column: usize) -> ParseResult<Expr> {
+        let mut exprs = vec![];
+
+        for part in parts {
+            match part {
+                StrPart::Lit(s) => {
+                    exprs.push(Expr::new(ExprKind::Lit(Literal::Str(s)), line, column))
+                }
+                StrPart::Expr {
+                    source,
+                    line,
+                    column,
+                    offset,
+                } => {
+                    let mut lexer = Lexer::new(source);
+                    lexer.set_state((line, column, 0));
+                    lexer.set_offset(offset);
+
+                    let mut parser = Parser {
+                        lexer,
+                        current: Token::default(),
+                        locals: self.locals.clone(),
+                        placeholders: false,
+                        generator: self.generator,
+                        keywords: HashMap::new(),
+                        keyword_calls: vec![],
+                    };
+                    parser.next()?;
+
+                    exprs.push(parser.expr()?);
+                    self.keyword_calls.append(&mut parser.keyword_calls);
+                    parser.assert(Tkt::Eof)?;
+                }
+            }
+        }
+
+        Ok(Expr::new(ExprKind::Interp(exprs), line, column))
+    }
+
     fn primary(&mut self) -> ParseResult<Expr> {
         let line = self.current.line;
         let column = self.current.column;
@@ -904,10 +2238,22 @@ impl Parser {
                 self.next()?;
                 Expr::new(ExprKind::Lit(Literal::Num(n)), line, column)
             }
+            Tkt::Int(n) => {
+                self.next()?;
+                Expr::new(ExprKind::Lit(Literal::Int(n)), line, column)
+            }
             Tkt::Str(s) => {
                 self.next()?;
                 Expr::new(ExprKind::Lit(Literal::Str(s)), line, column)
             }
+            Tkt::Bytes(b) => {
+                self.next()?;
+                Expr::new(ExprKind::Lit(Literal::Bytes(b)), line, column)
+            }
+            Tkt::Interp(parts) => {
+                self.next()?;
+                self.interp(parts, line, column)?
+            }
             Tkt::True => {
                 self.next()?;
                 Expr::new(ExprKind::Lit(Literal::Bool(true)), line, column)
@@ -916,7 +2262,14 @@ impl Parser {
                 self.next()?;
                 Expr::new(ExprKind::Lit(Literal::Bool(false)), line, column)
             }
-            Tkt::Name(s) if s.as_str() != "_" => {
+            Tkt::Name(s) if s.as_str() == "_" => {
+                self.throw(MISPLACED_PLACEHOLDER).map_err(|e| {
+                    e.with_help(
+                        "a function can take the piped value anywhere, like `x |> fn x = f [x, 2]`",
+                    )
+                })?
+            }
+            Tkt::Name(s) => {
                 self.next()?;
                 Expr::new(ExprKind::Var(s), line, column)
             }
@@ -926,6 +2279,8 @@ impl Parser {
             }
             Tkt::Lbrack => self.list()?,
             Tkt::Lparen => self.tuple()?,
+            Tkt::DictOpen => self.dict()?,
+            Tkt::ArrayOpen => self.array()?,
             Tkt::Nil => {
                 self.next()?;
                 Expr::new(ExprKind::Lit(Literal::Unit), line, column)
@@ -947,3 +2302,95 @@ impl Parser {
         Ok(obj)
     }
 }
+
+fn is_placeholder(expr: &Expr) -> bool {
+    matches!(expr.kind, ExprKind::Var(name) if name.as_str() == "_")
+}
+
+/// Desugars the right side of `x |> f a _ b` into `fn #pipe = f a #pipe b`, so the piped value
+/// goes in the place of the `_` arguments
+fn placeholder_lambda(callee: Expr, args: Vec<Expr>, line: usize, column: usize) -> Expr {
+    let piped = Symbol::from("#pipe");
+
+    let args = args
+        .into_iter()
+        .map(|arg| {
+            if is_placeholder(&arg) {
+                Expr::new(ExprKind::Var(piped), arg.location.line, arg.location.column)
+            } else {
+                arg
+            }
+        })
+        .collect();
+
+    let body = Expr::new(
+        ExprKind::App {
+            callee: Box::new(callee),
+            args,
+            tail: false,
+        },
+        line,
+        column,
+    );
+
+    Expr::new(
+        ExprKind::Lambda {
+            args: vec![Pattern::Id(piped, Location { line, column })],
+            defaults: vec![],
+            variadic: false,
+            keywords: vec![],
+            types: vec![],
+            returns: None,
+            body: Box::new(body),
+            generator: false,
+        },
+        line,
+        column,
+    )
+}
+
+/// Binds each keyword argument to its value in the dictionary of keyword arguments, or to its
+/// default value if it wasn't passed
+fn keyword_body(keywords: Vec<(Symbol, Location, Expr)>, body: Expr) -> Expr {
+    let (line, column) = (body.line(), body.column());
+    let kwargs = Symbol::from(KWARGS);
+
+    keywords
+        .into_iter()
+        .rev()
+        .fold(body, |body, (name, location, default)| {
+            // `Dict.<method> :name #kwargs`
+            let dict_call = |method: &str| {
+                let callee = ExprKind::MethodRef {
+                    ty: Box::new(Expr::new(ExprKind::Var("Dict".into()), line, column)),
+                    method: method.into(),
+                };
+                let args = vec![
+                    Expr::new(ExprKind::Lit(Literal::Sym(name)), line, column),
+                    Expr::new(ExprKind::Var(kwargs), line, column),
+                ];
+                let call = ExprKind::App {
+                    callee: Box::new(Expr::new(callee, line, column)),
+                    args,
+                    tail: false,
+                };
+                Box::new(Expr::new(call, line, column))
+            };
+
+            let value = ExprKind::If {
+                cond: dict_call("has"),
+                then: dict_call("get"),
+                else_: Box::new(default),
+            };
+
+            Expr::new(
+                ExprKind::Let {
+                    bind: Pattern::Id(name, location),
+                    value: Box::new(Expr::new(value, line, column)),
+                    body: Box::new(body),
+                },
+                line,
+                column,
+            )
+        })
+}
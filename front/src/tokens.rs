@@ -1,8 +1,26 @@
+use crate::parser::ast::Span;
+
+/// A segment of an interpolated string
 #[derive(Debug, PartialEq, Clone)]
+pub enum StrPart {
+    Lit(String),
+    /// The source of an interpolated expression and where it starts, `offset` is its first byte
+    Expr {
+        source: String,
+        line: usize,
+        column: usize,
+        offset: usize,
+    },
+}
+
+#[derive(Debug, PartialEq, Clone, Default)]
 pub enum TokenType {
     // Literals
     Num(f64),
+    Int(i64),
     Str(String),
+    Bytes(Vec<u8>),
+    Interp(Vec<StrPart>),
     Sym(vm::Symbol),
     Name(vm::Symbol),
     True,
@@ -16,6 +34,7 @@ pub enum TokenType {
     Def,
     Let,
     In,
+    For,
     Fn,
     Type,
     Match,
@@ -24,6 +43,15 @@ pub enum TokenType {
     Try,
     Rescue,
     Is,
+    Import,
+    Trait,
+    Impl,
+    Struct,
+    Module,
+    Priv,
+    Const,
+    Test,
+    Bench,
 
     // mathematical operators
     Add,
@@ -59,6 +87,8 @@ pub enum TokenType {
     Rbrack,
     Lbrace,
     Rbrace,
+    DictOpen,
+    ArrayOpen,
     Comma,
     Colon,
     Semicolon,
@@ -66,23 +96,34 @@ pub enum TokenType {
     Arrow,
     FatArrow,
     Dot,
+    DotDot,
+    DotDotEq,
+    Ellipsis,
     Pipe,
     Bar,
+    Caret,
 
+    #[default]
     Eof,
 }
 
-impl Default for TokenType {
-    fn default() -> Self {
-        Self::Eof
-    }
-}
-
 impl std::fmt::Display for TokenType {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let res = match self {
             Self::Num(n) => n.to_string(),
+            Self::Int(n) => n.to_string(),
             Self::Str(s) => "\"".to_owned() + s + "\"",
+            Self::Bytes(b) => vm::Value::Bytes(b.clone().into()).to_string(),
+            Self::Interp(parts) => {
+                let mut str = String::from('"');
+                for part in parts {
+                    match part {
+                        StrPart::Lit(s) => str.push_str(s),
+                        StrPart::Expr { source, .. } => str.push_str(&format!("{{{}}}", source)),
+                    }
+                }
+                str + "\""
+            }
             Self::Sym(s) => format!(":{}", s),
             Self::Name(v) => format!("{}", v),
             Self::True => "true".to_string(),
@@ -95,6 +136,7 @@ impl std::fmt::Display for TokenType {
             Self::Def => "def".into(),
             Self::Let => "let".into(),
             Self::In => "in".into(),
+            Self::For => "for".into(),
             Self::Fn => "fn".into(),
             Self::Type => "type".into(),
             Self::Match => "match".into(),
@@ -103,6 +145,15 @@ impl std::fmt::Display for TokenType {
             Self::Try => "try".into(),
             Self::Rescue => "rescue".into(),
             Self::Is => "is".into(),
+            Self::Import => "import".into(),
+            Self::Trait => "trait".into(),
+            Self::Impl => "impl".into(),
+            Self::Struct => "struct".into(),
+            Self::Module => "module".into(),
+            Self::Priv => "priv".into(),
+            Self::Const => "const".into(),
+            Self::Test => "test".into(),
+            Self::Bench => "bench".into(),
 
             Self::Add => '+'.into(),
             Self::Sub => '-'.into(),
@@ -118,6 +169,9 @@ impl std::fmt::Display for TokenType {
             Self::Assign => '='.into(),
             Self::Cons => "::".into(),
             Self::Dot => '.'.into(),
+            Self::DotDot => "..".into(),
+            Self::DotDotEq => "..=".into(),
+            Self::Ellipsis => "...".into(),
 
             Self::BitAnd => "&&&".into(),
             Self::BitOr => "|||".into(),
@@ -135,6 +189,8 @@ impl std::fmt::Display for TokenType {
             Self::Rbrack => ']'.into(),
             Self::Lbrace => '{'.into(),
             Self::Rbrace => '}'.into(),
+            Self::DictOpen => "%{".into(),
+            Self::ArrayOpen => "#[".into(),
             Self::Comma => ','.into(),
             Self::Colon => ':'.into(),
             Self::Semicolon => ';'.into(),
@@ -143,6 +199,7 @@ impl std::fmt::Display for TokenType {
             Self::FatArrow => "=>".into(),
             Self::Pipe => "|>".into(),
             Self::Bar => '|'.into(),
+            Self::Caret => '^'.into(),
 
             Self::Eof => "<eof>".into(),
         };
@@ -159,6 +216,7 @@ pub fn fetch_keyword<T: AsRef<str>>(word: T) -> Option<TokenType> {
         "def" => Some(TokenType::Def),
         "let" => Some(TokenType::Let),
         "in" => Some(TokenType::In),
+        "for" => Some(TokenType::For),
         "true" => Some(TokenType::True),
         "false" => Some(TokenType::False),
         "nil" => Some(TokenType::Nil),
@@ -170,6 +228,15 @@ pub fn fetch_keyword<T: AsRef<str>>(word: T) -> Option<TokenType> {
         "try" => Some(TokenType::Try),
         "rescue" => Some(TokenType::Rescue),
         "is" => Some(TokenType::Is),
+        "import" => Some(TokenType::Import),
+        "trait" => Some(TokenType::Trait),
+        "impl" => Some(TokenType::Impl),
+        "struct" => Some(TokenType::Struct),
+        "module" => Some(TokenType::Module),
+        "priv" => Some(TokenType::Priv),
+        "const" => Some(TokenType::Const),
+        "test" => Some(TokenType::Test),
+        "bench" => Some(TokenType::Bench),
         _ => None,
     }
 }
@@ -178,6 +245,8 @@ pub fn fetch_keyword<T: AsRef<str>>(word: T) -> Option<TokenType> {
 pub struct Token {
     pub line: usize,
     pub column: usize,
+    /// The bytes of the source the token was read from
+    pub span: Span,
     pub token: TokenType,
 }
 
@@ -186,6 +255,7 @@ impl Default for Token {
         Self {
             line: 0,
             column: 0,
+            span: Span::default(),
             token: TokenType::Eof,
         }
     }
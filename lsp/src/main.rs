@@ -0,0 +1,91 @@
+mod server;
+
+use serde_json::{json, Value};
+use server::Server;
+use std::{
+    io::{self, BufRead, Write},
+    process::exit,
+};
+
+/// Reads a message, which is a JSON body after a `Content-Length` header
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header)? == 0 {
+            return Ok(None);
+        }
+
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse().ok();
+        }
+    }
+
+    let length = length
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "missing the Content-Length"))?;
+
+    let mut body = vec![0; length];
+    input.read_exact(&mut body)?;
+    serde_json::from_slice(&body)
+        .map(Some)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+/// The language server, which talks JSON-RPC through stdin and stdout
+fn main() {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut output = io::stdout();
+
+    let mut server = Server::new();
+    let mut shutdown = false;
+
+    loop {
+        let message = match read_message(&mut input) {
+            Ok(Some(message)) => message,
+            Ok(None) => break,
+            Err(e) => {
+                eprintln!("yex-lsp: {}", e);
+                continue;
+            }
+        };
+
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+
+        match method {
+            "exit" => exit(if shutdown { 0 } else { 1 }),
+            "shutdown" => shutdown = true,
+            _ => (),
+        }
+
+        let (result, notifications) = server.handle(method, params);
+        for notification in notifications {
+            write_message(&mut output, &notification).ok();
+        }
+
+        // only requests have an id, notifications aren't answered
+        if let Some(id) = message.get("id") {
+            let response = match result {
+                Some(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                None => json!({
+                    "jsonrpc": "2.0",
+                    "id": id,
+                    "error": { "code": -32601, "message": format!("Unknown method '{}'", method) },
+                }),
+            };
+            write_message(&mut output, &response).ok();
+        }
+    }
+}
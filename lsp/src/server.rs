@@ -0,0 +1,369 @@
+use front::{Definition, Diagnostic, Location, Severity};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+use vm::VirtualMachine;
+
+/// An open file, with the globals it defines
+struct Document {
+    text: String,
+    /// The statements with syntax errors are skipped, so the definitions can still be found while
+    /// a line is half written
+    defs: Vec<Definition>,
+}
+
+/// Where a name is defined
+struct Found {
+    uri: String,
+    def: Option<Definition>,
+}
+
+/// The state of the server, the open documents by uri
+pub struct Server {
+    documents: HashMap<String, Document>,
+    /// A vm with the prelude, used to show the documentation of the builtins
+    vm: VirtualMachine,
+}
+
+impl Server {
+    pub fn new() -> Self {
+        Self {
+            documents: HashMap::new(),
+            vm: VirtualMachine::default(),
+        }
+    }
+
+    /// Handles a message, returning the result of requests (`None` if the method is unknown) and
+    /// the notifications sent to the editor
+    pub fn handle(&mut self, method: &str, params: &Value) -> (Option<Value>, Vec<Value>) {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+
+        match method {
+            "initialize" => (
+                Some(json!({
+                    "capabilities": {
+                        // the whole text is sent on every change
+                        "textDocumentSync": 1,
+                        "definitionProvider": true,
+                        "hoverProvider": true,
+                    },
+                    "serverInfo": { "name": "yex-lsp" },
+                })),
+                vec![],
+            ),
+            "shutdown" => (Some(Value::Null), vec![]),
+            "textDocument/didOpen" => {
+                let text = params["textDocument"]["text"].as_str().unwrap_or_default();
+                (None, vec![self.update(uri, text.to_string())])
+            }
+            "textDocument/didChange" => {
+                let changes = params["contentChanges"].as_array();
+                match changes.and_then(|changes| changes.last()?["text"].as_str()) {
+                    Some(text) => (None, vec![self.update(uri, text.to_string())]),
+                    None => (None, vec![]),
+                }
+            }
+            "textDocument/didClose" => {
+                self.documents.remove(uri);
+                (None, vec![publish(uri, vec![])])
+            }
+            "textDocument/definition" => {
+                let found = self.find(uri, &params["position"]);
+                let location = found.map(|found| {
+                    let location = found
+                        .def
+                        .map(|def| def.location)
+                        .unwrap_or(Location { line: 1, column: 1 });
+                    json!({ "uri": found.uri, "range": range(location) })
+                });
+                (Some(location.unwrap_or(Value::Null)), vec![])
+            }
+            "textDocument/hover" => {
+                let hover = self
+                    .hover(uri, &params["position"])
+                    .map(|text| json!({ "contents": { "kind": "markdown", "value": text } }));
+                (Some(hover.unwrap_or(Value::Null)), vec![])
+            }
+            _ => (None, vec![]),
+        }
+    }
+
+    /// Parses the new text of a document, returning the notification with its diagnostics
+    fn update(&mut self, uri: &str, text: String) -> Value {
+        let (defs, diagnostics) = front::definitions(text.as_str());
+
+        let document = self
+            .documents
+            .entry(uri.to_string())
+            .or_insert_with(|| Document {
+                text: String::new(),
+                defs: vec![],
+            });
+        document.text = text;
+        document.defs = defs;
+
+        publish(uri, diagnostics)
+    }
+
+    /// Finds the definition of the name at `position`, `Module.name` is looked up in the file of
+    /// the module, next to the document
+    fn find(&self, uri: &str, position: &Value) -> Option<Found> {
+        let document = self.documents.get(uri)?;
+        let name = name_at(&document.text, position)?;
+
+        if let Some(def) = document.defs.iter().find(|def| def.name == name) {
+            return Some(Found {
+                uri: uri.to_string(),
+                def: Some(def.clone()),
+            });
+        }
+
+        // the modules imported by a module are its members too, so `Geometry.Shapes.area` can be
+        // in `shapes.yex`, imported by `geometry.yex`
+        let mut file = path_of(uri)?;
+        let mut name = name.as_str();
+        loop {
+            let (module, member) = match name.split_once('.') {
+                Some((module, member)) => (module, Some(member)),
+                None => (name, None),
+            };
+            file = module_file(&file, module)?;
+
+            let member = match member {
+                Some(member) => member,
+                // the module name goes to the start of its file
+                None => {
+                    return Some(Found {
+                        uri: uri_of(&file),
+                        def: None,
+                    })
+                }
+            };
+
+            let (defs, _) = front::definitions(fs::read_to_string(&file).ok()?);
+            if let Some(def) = defs.into_iter().find(|def| def.name == member) {
+                return Some(Found {
+                    uri: uri_of(&file),
+                    def: Some(def),
+                });
+            }
+            name = member;
+        }
+    }
+
+    /// Shows the parameters and the docstring of the name at `position`, the builtins show the
+    /// documentation of `help`
+    fn hover(&self, uri: &str, position: &Value) -> Option<String> {
+        if let Some(Found { def: Some(def), .. }) = self.find(uri, position) {
+            let mut text = String::from("```\n");
+            text.push_str(&def.name);
+            if let Some(params) = &def.params {
+                for param in params {
+                    text.push(' ');
+                    text.push_str(param);
+                }
+            }
+            text.push_str("\n```");
+
+            if let Some(params) = &def.params {
+                text.push_str(&format!("\n\narity {}", params.len()));
+            }
+            if let Some(doc) = &def.doc {
+                text.push_str("\n\n");
+                text.push_str(doc);
+            }
+            return Some(text);
+        }
+
+        let document = self.documents.get(uri)?;
+        let name = name_at(&document.text, position)?;
+        let help = self.vm.help(&name.into()).ok()?;
+        Some(format!("```\n{}\n```", help))
+    }
+}
+
+fn publish(uri: &str, diagnostics: Vec<Diagnostic>) -> Value {
+    let diagnostics: Vec<Value> = diagnostics
+        .into_iter()
+        .map(|diagnostic| {
+            let message = match diagnostic.help {
+                Some(help) => format!("{}\nhelp: {}", diagnostic.message, help),
+                None => diagnostic.message,
+            };
+            json!({
+                "range": range(diagnostic.location),
+                "severity": match diagnostic.severity {
+                    Severity::Error => 1,
+                    Severity::Warning => 2,
+                },
+                "code": diagnostic.code,
+                "source": "yex",
+                "message": message,
+            })
+        })
+        .collect();
+
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+/// The range of a single character, the positions of the editor start from 0
+fn range(location: Location) -> Value {
+    let line = location.line.saturating_sub(1);
+    let column = location.column.saturating_sub(1);
+    json!({
+        "start": { "line": line, "character": column },
+        "end": { "line": line, "character": column + 1 },
+    })
+}
+
+fn is_name(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Returns the name at `position`, with the module names before it, so the cursor on `map` in
+/// `List.map` returns `List.map` and the cursor on `List` returns `List`
+///
+/// The editor counts the characters of a line in UTF-16 code units
+fn name_at(text: &str, position: &Value) -> Option<String> {
+    let line = text.lines().nth(position["line"].as_u64()? as usize)?;
+    let chars: Vec<char> = line.chars().collect();
+    let character = position["character"].as_u64()? as usize;
+
+    let mut units = 0;
+    let at = chars
+        .iter()
+        .position(|c| {
+            units += c.len_utf16();
+            units > character
+        })
+        .unwrap_or(chars.len());
+
+    let mut start = at;
+    while start > 0 && (is_name(chars[start - 1]) || chars[start - 1] == '.') {
+        start -= 1;
+    }
+    let mut end = at;
+    while end < chars.len() && is_name(chars[end]) {
+        end += 1;
+    }
+
+    let name: String = chars[start..end].iter().collect();
+    let name = name.trim_matches('.');
+    (!name.is_empty()).then(|| name.to_string())
+}
+
+/// The path of a `file://` uri, whose bytes outside of ASCII letters, digits and a few symbols are
+/// percent-encoded
+fn path_of(uri: &str) -> Option<PathBuf> {
+    let path = uri.strip_prefix("file://")?.as_bytes();
+
+    let mut bytes = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        if path[i] == b'%' {
+            let hex = std::str::from_utf8(path.get(i + 1..i + 3)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            bytes.push(path[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(bytes).ok().map(PathBuf::from)
+}
+
+/// The `file://` uri of a path, the inverse of [`path_of`]
+fn uri_of(path: &Path) -> String {
+    let mut uri = String::from("file://");
+    for &byte in path.to_string_lossy().as_bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'/' | b'-' | b'_' | b'.' | b'~' => {
+                uri.push(byte as char)
+            }
+            _ => uri.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    uri
+}
+
+/// The file of a module imported by `file`, the same way `yex` looks for it
+fn module_file(file: &Path, module: &str) -> Option<PathBuf> {
+    let mut chars = module.chars();
+    let first = chars.next()?;
+    if !first.is_uppercase() {
+        return None;
+    }
+
+    let name = format!("{}{}.yex", first.to_lowercase(), chars.as_str());
+    let file = file.parent()?.join(name);
+    file.exists().then_some(file)
+}
+
+#[test]
+fn names() {
+    let text = "let x = List.map f xs\n";
+    let at = |character| name_at(text, &json!({ "line": 0, "character": character }));
+
+    assert_eq!(at(4).as_deref(), Some("x"));
+    assert_eq!(at(9).as_deref(), Some("List"));
+    assert_eq!(at(14).as_deref(), Some("List.map"));
+    assert_eq!(at(7), None);
+
+    // `é` is a single code unit, and `𝔸` two of them
+    let text = "let é𝔸 = List.map\n";
+    let at = |character| name_at(text, &json!({ "line": 0, "character": character }));
+    assert_eq!(at(4).as_deref(), Some("é𝔸"));
+    assert_eq!(at(10).as_deref(), Some("List"));
+    assert_eq!(at(15).as_deref(), Some("List.map"));
+}
+
+#[test]
+fn uris() {
+    let path = path_of("file:///home/me/my%20files/caf%C3%A9%23.yex").unwrap();
+    assert_eq!(path, PathBuf::from("/home/me/my files/café#.yex"));
+    assert_eq!(uri_of(&path), "file:///home/me/my%20files/caf%C3%A9%23.yex");
+
+    assert_eq!(path_of("file:///a%2"), None);
+}
+
+#[test]
+fn nested_modules() {
+    let dir = std::env::temp_dir().join("yex_lsp_nested");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(
+        dir.join("geometry.yex"),
+        "import Shapes\nmodule Util with\n  def id x = x\nend\n",
+    )
+    .unwrap();
+    fs::write(dir.join("shapes.yex"), "\ndef area r = r * r\n").unwrap();
+
+    let uri = uri_of(&dir.join("main.yex"));
+    let text = "import Geometry\nlet a = Geometry.Shapes.area 2\nlet b = Geometry.Util.id 1\n";
+    let mut server = Server::new();
+    server.update(&uri, text.to_string());
+
+    let found = |line, character| {
+        let found = server.find(&uri, &json!({ "line": line, "character": character }))?;
+        Some((found.uri, found.def.map(|def| def.name)))
+    };
+    assert_eq!(
+        found(1, 25),
+        Some((uri_of(&dir.join("shapes.yex")), Some("area".to_string())))
+    );
+    assert_eq!(found(1, 18), Some((uri_of(&dir.join("shapes.yex")), None)));
+    assert_eq!(
+        found(2, 22),
+        Some((
+            uri_of(&dir.join("geometry.yex")),
+            Some("Util.id".to_string())
+        ))
+    );
+}
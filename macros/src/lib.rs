@@ -0,0 +1,471 @@
+#![deny(missing_docs)]
+//! Procedural macros that generate the glue needed to expose rust functions and types to the yex
+//! virtual machine
+use proc_macro::TokenStream;
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::{format_ident, quote};
+use syn::{
+    parse::Parser, parse_macro_input, parse_quote, punctuated::Punctuated, Attribute, Data,
+    DeriveInput, Expr, ExprLit, Fields, FnArg, Generics, ImplItem, ItemFn, ItemImpl, Lit, Meta,
+    ReturnType, Signature, Token, Type,
+};
+
+/// Turns a rust function into a native yex function, converting the arguments with `FromYex` and
+/// the return value with `IntoYex`.
+///
+/// The function may take a `&mut VirtualMachine` as the first argument, which isn't counted on
+/// the yex arity, and may return an `InterpretResult` to raise errors.
+///
+/// ```
+/// use vm::{yex_fn, VirtualMachine};
+///
+/// #[yex_fn]
+/// fn add(a: f64, b: f64) -> f64 {
+///     a + b
+/// }
+///
+/// let mut vm = VirtualMachine::default();
+/// vm.register("add", 2, add);
+/// ```
+#[proc_macro_attribute]
+pub fn yex_fn(attr: TokenStream, item: TokenStream) -> TokenStream {
+    if !attr.is_empty() {
+        return syn::Error::new(
+            Span::call_site(),
+            "arguments are only supported inside `#[yex_module]`",
+        )
+        .to_compile_error()
+        .into();
+    }
+
+    let mut func = parse_macro_input!(item as ItemFn);
+    let vis = func.vis.clone();
+    let name = func.sig.ident.clone();
+
+    func.sig.ident = format_ident!("inner");
+    func.vis = syn::Visibility::Inherited;
+
+    let body = match native_body(&func.sig, quote!(inner)) {
+        Ok((body, _)) => body,
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    quote! {
+        #vis fn #name(
+            vm: *mut ::vm::VirtualMachine,
+            args: ::std::vec::Vec<::vm::Value>,
+        ) -> ::vm::error::InterpretResult<::vm::Value> {
+            #func
+            #body
+        }
+    }
+    .into()
+}
+
+/// Exposes the associated functions of an `impl` block as a yex module, generating a
+/// `yex_module()` function that builds the `YexModule`.
+///
+/// The module name defaults to the type name and can be changed with
+/// `#[yex_module(name = "Name")]`, functions can be renamed with `#[yex_fn(name = "name?")]`.
+///
+/// ```
+/// use vm::{error::InterpretResult, raise, yex_module, VirtualMachine};
+///
+/// struct Math;
+///
+/// #[yex_module]
+/// impl Math {
+///     fn double(n: f64) -> f64 {
+///         n * 2.0
+///     }
+///
+///     #[yex_fn(name = "sqrt!")]
+///     fn checked_sqrt(n: f64) -> InterpretResult<f64> {
+///         if n < 0.0 {
+///             raise!(ValueError, "Can't take the square root of '{}'", n)
+///         } else {
+///             Ok(n.sqrt())
+///         }
+///     }
+/// }
+///
+/// let vm = VirtualMachine::builder().module(Math::yex_module()).build();
+/// assert!(vm.get_global("Math").is_some());
+/// ```
+#[proc_macro_attribute]
+pub fn yex_module(attr: TokenStream, item: TokenStream) -> TokenStream {
+    let mut imp = parse_macro_input!(item as ItemImpl);
+
+    let ty = &imp.self_ty;
+    let module_name = match name_arg(attr.into()) {
+        Ok(Some(name)) => name,
+        Ok(None) => match &**ty {
+            Type::Path(path) => path.path.segments.last().unwrap().ident.to_string(),
+            _ => {
+                return syn::Error::new_spanned(ty, "expected a type name")
+                    .to_compile_error()
+                    .into()
+            }
+        },
+        Err(e) => return e.to_compile_error().into(),
+    };
+
+    let mut registers = vec![];
+
+    for item in imp.items.iter_mut() {
+        let method = match item {
+            ImplItem::Fn(method) if method.sig.receiver().is_none() => method,
+            _ => continue,
+        };
+
+        let name = match take_fn_name(&mut method.attrs) {
+            Ok(name) => name.unwrap_or_else(|| method.sig.ident.to_string()),
+            Err(e) => return e.to_compile_error().into(),
+        };
+
+        let ident = &method.sig.ident;
+        let (body, arity) = match native_body(&method.sig, quote!(<#ty>::#ident)) {
+            Ok(res) => res,
+            Err(e) => return e.to_compile_error().into(),
+        };
+
+        registers.push(quote! {
+            module.register(#name, #arity, {
+                fn native(
+                    vm: *mut ::vm::VirtualMachine,
+                    args: ::std::vec::Vec<::vm::Value>,
+                ) -> ::vm::error::InterpretResult<::vm::Value> {
+                    #body
+                }
+                native
+            });
+        });
+    }
+
+    let (impl_generics, _, where_clause) = imp.generics.split_for_impl();
+    let self_ty = &imp.self_ty;
+
+    quote! {
+        #imp
+
+        impl #impl_generics #self_ty #where_clause {
+            /// Builds the yex module with all the exported functions
+            pub fn yex_module() -> ::vm::YexModule {
+                let mut module = ::vm::YexModule::with_name(#module_name);
+                #(#registers)*
+                module
+            }
+        }
+    }
+    .into()
+}
+
+/// Derives `IntoYex`, converting the structs with named fields to a dictionary keyed by symbols
+/// (`%{x: 1, y: 2}`), the tuple structs to a tuple (or to the value they wrap, if they have a
+/// single field), the unit structs to `nil` and the enums without fields to a symbol with the
+/// name of the variant
+///
+/// ```
+/// use vm::{IntoYex, Value};
+///
+/// #[derive(IntoYex)]
+/// struct Point {
+///     x: i64,
+///     y: i64,
+/// }
+///
+/// let mut vm = vm::VirtualMachine::default();
+/// vm.set_global("p", Point { x: 1, y: 2 }.into_yex());
+/// ```
+#[proc_macro_derive(IntoYex)]
+pub fn derive_into_yex(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let name = &input.ident;
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let entries = fields.named.iter().map(|field| {
+                    let ident = field.ident.as_ref().unwrap();
+                    let key = ident.to_string();
+                    quote!((#key, ::vm::IntoYex::into_yex(self.#ident)))
+                });
+                quote!(::vm::literal::convert::record([#(#entries),*]))
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                quote!(::vm::IntoYex::into_yex(self.0))
+            }
+            Fields::Unnamed(fields) => {
+                let items = (0..fields.unnamed.len()).map(|idx| {
+                    let idx = syn::Index::from(idx);
+                    quote!(::vm::IntoYex::into_yex(self.#idx))
+                });
+                quote!(::vm::Value::from(::std::vec![#(#items),*]))
+            }
+            Fields::Unit => quote!(::vm::Value::Nil),
+        },
+        Data::Enum(data) => {
+            let arms = data.variants.iter().map(|variant| {
+                let ident = &variant.ident;
+                let sym = ident.to_string();
+                match variant.fields {
+                    Fields::Unit => Ok(quote!(Self::#ident => #sym)),
+                    _ => Err(syn::Error::new_spanned(
+                        variant,
+                        "only the enums without fields can be converted to yex",
+                    )),
+                }
+            });
+            let arms = match arms.collect::<syn::Result<Vec<_>>>() {
+                Ok(arms) => arms,
+                Err(e) => return e.to_compile_error().into(),
+            };
+            quote!(::vm::Value::from(::vm::Symbol::new(match self { #(#arms),* })))
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "unions can't be converted to yex")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let generics = bounded(&input.generics, quote!(::vm::IntoYex));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::vm::IntoYex for #name #ty_generics #where_clause {
+            fn into_yex(self) -> ::vm::Value {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// Derives `FromYex`, the opposite of `#[derive(IntoYex)]`, the structs with named fields can also
+/// be converted from a yex struct with the same fields, and the missing fields raise a
+/// `FieldError`
+///
+/// ```
+/// use vm::{FromYex, Value};
+///
+/// #[derive(FromYex, Debug, PartialEq)]
+/// enum Color {
+///     Red,
+///     Green,
+/// }
+///
+/// let red = Value::from(vm::Symbol::new("Red"));
+/// assert_eq!(Color::from_yex(&red).unwrap(), Color::Red);
+/// ```
+#[proc_macro_derive(FromYex)]
+pub fn derive_from_yex(item: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(item as DeriveInput);
+    let name = &input.ident;
+
+    let body = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => {
+                let fields = fields.named.iter().map(|field| {
+                    let ident = field.ident.as_ref().unwrap();
+                    let ty = &field.ty;
+                    let key = ident.to_string();
+                    quote! {
+                        #ident: <#ty as ::vm::FromYex>::from_yex(
+                            &::vm::literal::convert::field(value, #key)?
+                        )?
+                    }
+                });
+                quote!(Ok(Self { #(#fields),* }))
+            }
+            Fields::Unnamed(fields) if fields.unnamed.len() == 1 => {
+                let ty = &fields.unnamed[0].ty;
+                quote!(Ok(Self(<#ty as ::vm::FromYex>::from_yex(value)?)))
+            }
+            Fields::Unnamed(fields) => {
+                let len = fields.unnamed.len();
+                let items = fields.unnamed.iter().enumerate().map(|(idx, field)| {
+                    let ty = &field.ty;
+                    quote!(<#ty as ::vm::FromYex>::from_yex(&tup.0[#idx])?)
+                });
+                quote! {
+                    let tup = ::vm::literal::convert::tuple(value, #len)?;
+                    Ok(Self(#(#items),*))
+                }
+            }
+            Fields::Unit => quote!(Ok(Self)),
+        },
+        Data::Enum(data) => {
+            let mut arms = vec![];
+            let mut names = vec![];
+            for variant in &data.variants {
+                if !matches!(variant.fields, Fields::Unit) {
+                    return syn::Error::new_spanned(
+                        variant,
+                        "only the enums without fields can be converted from yex",
+                    )
+                    .to_compile_error()
+                    .into();
+                }
+
+                let ident = &variant.ident;
+                let sym = ident.to_string();
+                arms.push(quote!(#sym => Ok(Self::#ident)));
+                names.push(format!(":{}", sym));
+            }
+
+            let expected = names.join(", ");
+            quote! {
+                let sym: ::vm::Symbol = ::vm::literal::TryGet::get(value)?;
+                match sym.as_str() {
+                    #(#arms,)*
+                    _ => ::vm::raise!(
+                        TypeError,
+                        "Expected one of {}, found '{}'",
+                        #expected,
+                        value
+                    ),
+                }
+            }
+        }
+        Data::Union(_) => {
+            return syn::Error::new_spanned(name, "unions can't be converted from yex")
+                .to_compile_error()
+                .into()
+        }
+    };
+
+    let generics = bounded(&input.generics, quote!(::vm::FromYex));
+    let (impl_generics, ty_generics, where_clause) = generics.split_for_impl();
+
+    quote! {
+        impl #impl_generics ::vm::FromYex for #name #ty_generics #where_clause {
+            fn from_yex(value: &::vm::Value) -> ::vm::error::InterpretResult<Self> {
+                #body
+            }
+        }
+    }
+    .into()
+}
+
+/// Adds the `bound` to every type parameter
+fn bounded(generics: &Generics, bound: TokenStream2) -> Generics {
+    let mut generics = generics.clone();
+    for param in generics.type_params_mut() {
+        param.bounds.push(parse_quote!(#bound));
+    }
+    generics
+}
+
+/// Generates the body of a native function that converts `args` and calls `path`, returns the
+/// body and the yex arity of the function
+fn native_body(sig: &Signature, path: TokenStream2) -> syn::Result<(TokenStream2, usize)> {
+    let mut call_args = vec![];
+    let mut conversions = vec![];
+    let mut arity = 0;
+
+    for (index, input) in sig.inputs.iter().enumerate() {
+        let ty = match input {
+            FnArg::Typed(pat) => &pat.ty,
+            FnArg::Receiver(recv) => {
+                return Err(syn::Error::new_spanned(
+                    recv,
+                    "yex functions can't take `self`",
+                ))
+            }
+        };
+
+        if index == 0 && is_vm_ref(ty) {
+            call_args.push(quote!(unsafe { &mut *vm }));
+            continue;
+        }
+
+        let arg = Ident::new(&format!("arg{arity}"), Span::call_site());
+        conversions.push(quote! {
+            let #arg: #ty = <#ty as ::vm::FromYex>::from_yex(&args[#arity])?;
+        });
+        call_args.push(quote!(#arg));
+        arity += 1;
+    }
+
+    let call = quote!(#path(#(#call_args),*));
+    let ret = match &sig.output {
+        ReturnType::Default => quote! {
+            #call;
+            Ok(::vm::Value::Nil)
+        },
+        ReturnType::Type(_, ty) if is_interpret_result(ty) => quote! {
+            Ok(::vm::IntoYex::into_yex(#call?))
+        },
+        ReturnType::Type(..) => quote! {
+            Ok(::vm::IntoYex::into_yex(#call))
+        },
+    };
+
+    Ok((
+        quote! {
+            let _ = vm;
+            #(#conversions)*
+            #ret
+        },
+        arity,
+    ))
+}
+
+fn is_vm_ref(ty: &Type) -> bool {
+    match ty {
+        Type::Reference(r) if r.mutability.is_some() => {
+            matches!(&*r.elem, Type::Path(p) if p.path.segments.last().unwrap().ident == "VirtualMachine")
+        }
+        _ => false,
+    }
+}
+
+fn is_interpret_result(ty: &Type) -> bool {
+    matches!(ty, Type::Path(p) if p.path.segments.last().unwrap().ident == "InterpretResult")
+}
+
+/// Parses a `name = "..."` argument list
+fn name_arg(attr: TokenStream2) -> syn::Result<Option<String>> {
+    let metas = Punctuated::<Meta, Token![,]>::parse_terminated.parse2(attr)?;
+
+    let mut name = None;
+    for meta in metas {
+        match meta {
+            Meta::NameValue(nv) if nv.path.is_ident("name") => match nv.value {
+                Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }) => name = Some(s.value()),
+                other => return Err(syn::Error::new_spanned(other, "expected a string")),
+            },
+            other => return Err(syn::Error::new_spanned(other, "unknown argument")),
+        }
+    }
+
+    Ok(name)
+}
+
+/// Removes the `#[yex_fn]` attributes from a method, returning the yex name if it was renamed
+fn take_fn_name(attrs: &mut Vec<Attribute>) -> syn::Result<Option<String>> {
+    let mut name = None;
+    let mut err = None;
+
+    attrs.retain(|attr| {
+        if !attr.path().is_ident("yex_fn") {
+            return true;
+        }
+
+        if let Meta::List(list) = &attr.meta {
+            match name_arg(list.tokens.clone()) {
+                Ok(n) => name = n,
+                Err(e) => err = Some(e),
+            }
+        }
+        false
+    });
+
+    match err {
+        Some(e) => Err(e),
+        None => Ok(name),
+    }
+}
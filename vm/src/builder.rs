@@ -0,0 +1,155 @@
+use crate::{
+    env::EnvTable, error::InterpretResult, literal::fun::NativeFn, prelude, Capabilities, Fn,
+    FnInfo, ModuleLoader, Registry, Symbol, Value, VirtualMachine, VmOptions, YexModule,
+};
+
+/// Builds a [`VirtualMachine`] with extra globals and native functions registered on top of the
+/// prelude, so hosts can extend the language without touching the prelude itself
+///
+/// ```
+/// use vm::{error::InterpretResult, literal::TryGet, Value, VirtualMachine, YexModule};
+///
+/// fn double(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+///     let n: f64 = args[0].get()?;
+///     Ok(Value::Num(n * 2.0))
+/// }
+///
+/// let mut math = YexModule::with_name("Math");
+/// math.register("double", 1, double);
+///
+/// let vm = VirtualMachine::builder()
+///     .register("double", 1, double)
+///     .module(math)
+///     .build();
+///
+/// assert!(vm.get_global("double").is_some());
+/// assert!(vm.get_global("Math").is_some());
+/// ```
+pub struct VmBuilder {
+    globals: EnvTable,
+    options: VmOptions,
+    registry: Option<Registry>,
+    loader: Option<ModuleLoader>,
+}
+
+impl VmBuilder {
+    /// Creates a new builder, the prelude is loaded with every capability by default
+    pub fn new() -> Self {
+        Self {
+            globals: EnvTable::new(),
+            options: VmOptions::new(),
+            registry: None,
+            loader: None,
+        }
+    }
+
+    /// Sets the capabilities of the scripts, only the prelude functions allowed by them are
+    /// installed
+    #[must_use]
+    pub fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.options = self.options.capabilities(capabilities);
+        self
+    }
+
+    /// Sets the capabilities and the limits of the scripts, see [`VmOptions`]
+    #[must_use]
+    pub fn options(mut self, options: VmOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Uses a shared [`Registry`] instead of loading the prelude, the capabilities are ignored since
+    /// the registry already decides which functions are available
+    #[must_use]
+    pub fn registry(mut self, registry: &Registry) -> Self {
+        self.registry = Some(registry.clone());
+        self
+    }
+
+    /// Registers a native function as a global
+    #[must_use]
+    pub fn register<T: Into<Symbol>>(mut self, name: T, arity: usize, fun: NativeFn) -> Self {
+        self.globals
+            .insert(name.into(), Fn::new_native(arity, fun).into());
+        self
+    }
+
+    /// Registers a rust closure as a global function, see [`VirtualMachine::register_native`]
+    #[must_use]
+    pub fn register_native<T, F>(mut self, name: T, arity: usize, fun: F) -> Self
+    where
+        T: Into<Symbol>,
+        F: core::ops::Fn(&mut VirtualMachine, &[Value]) -> InterpretResult<Value> + 'static,
+    {
+        let name = name.into();
+        let fun = Fn::from_closure(arity, fun).with_info(FnInfo::new(name, vec![], None));
+        self.globals.insert(name, fun.into());
+        self
+    }
+
+    /// Registers a module as a global, using the module name
+    #[must_use]
+    pub fn module(mut self, module: YexModule) -> Self {
+        self.globals.insert(module.name, module.into());
+        self
+    }
+
+    /// Sets a global to an arbitrary value
+    #[must_use]
+    pub fn global<T: Into<Symbol>>(mut self, name: T, value: Value) -> Self {
+        self.globals.insert(name.into(), value);
+        self
+    }
+
+    /// Sets the [`ModuleLoader`] used by the `import` statement
+    #[must_use]
+    pub fn loader(mut self, loader: ModuleLoader) -> Self {
+        self.loader = Some(loader);
+        self
+    }
+
+    /// Creates the [`VirtualMachine`]
+    pub fn build(mut self) -> VirtualMachine {
+        let loader = self.loader.take();
+        let options = self.options;
+        let mut vm = match self.registry {
+            Some(registry) => {
+                let mut vm = VirtualMachine::with_registry(&registry);
+                vm.builtins = self.globals;
+                vm
+            }
+            None => VirtualMachine::with_globals(self.globals()),
+        };
+        vm.loader = loader;
+        vm.set_options(options);
+        vm
+    }
+
+    /// Freezes the globals into a [`Registry`] that can be shared by many vms
+    pub fn freeze(self) -> Registry {
+        match self.registry {
+            Some(ref registry) => {
+                let mut globals = (*registry.globals).clone();
+                for (name, value) in self.globals.iter() {
+                    globals.insert(name, value);
+                }
+                Registry::with_constants(globals, registry.constants.clone())
+            }
+            None => Registry::new(self.globals()),
+        }
+    }
+
+    fn globals(self) -> EnvTable {
+        let mut globals = prelude::prelude_with(self.options.capabilities);
+        for (name, value) in self.globals.iter() {
+            globals.insert(name, value);
+        }
+        globals
+    }
+}
+
+impl Default for VmBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
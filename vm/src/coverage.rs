@@ -0,0 +1,139 @@
+//! Coverage tracking, a vm with coverage enabled records the location of each instruction it runs,
+//! so a report can show the lines that the program (or its tests) never ran
+use alloc::{boxed::Box, collections::BTreeSet, vec::Vec};
+
+use crate::{literal::fun::walk_code, task::Frame, Bytecode, Symbol, Value, VirtualMachine};
+
+/// The lines and columns of the instructions that ran in a module
+type Locations = BTreeSet<(usize, usize)>;
+
+/// The lines and columns of the instructions that ran, by module
+///
+/// The module is `None` for the code passed to [`VirtualMachine::run`] and the functions it
+/// defines, like in [`crate::debug::Location`]
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Coverage {
+    modules: Vec<(Option<Symbol>, Locations)>,
+}
+
+impl Coverage {
+    fn hit(&mut self, module: Option<Symbol>, line: usize, column: usize) {
+        let locations = match self.modules.iter().position(|(name, _)| *name == module) {
+            Some(idx) => &mut self.modules[idx].1,
+            None => {
+                self.modules.push((module, Locations::new()));
+                &mut self.modules.last_mut().unwrap().1
+            }
+        };
+        locations.insert((line, column));
+    }
+
+    /// The modules with code that ran
+    pub fn modules(&self) -> impl Iterator<Item = Option<Symbol>> + '_ {
+        self.modules.iter().map(|(name, _)| *name)
+    }
+
+    /// The lines and columns that ran in a module, sorted
+    pub fn locations(&self, module: Option<Symbol>) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.modules
+            .iter()
+            .filter(move |(name, _)| *name == module)
+            .flat_map(|(_, locations)| locations.iter().copied())
+    }
+
+    /// The lines that ran in a module
+    pub fn lines(&self, module: Option<Symbol>) -> BTreeSet<usize> {
+        self.locations(module).map(|(line, _)| line).collect()
+    }
+
+    /// Adds the locations that ran in another vm, like the vms of each test
+    pub fn merge(&mut self, other: &Coverage) {
+        for (module, locations) in &other.modules {
+            for &(line, column) in locations {
+                self.hit(*module, line, column);
+            }
+        }
+    }
+}
+
+/// The lines with instructions in a program and in the functions of its constants, which are the
+/// lines a coverage report expects to run
+pub fn lines(bytecode: &Bytecode, constants: &[Value]) -> BTreeSet<usize> {
+    let mut lines: BTreeSet<usize> = bytecode.iter().map(|op| op.line).collect();
+
+    let mut seen = BTreeSet::new();
+    for value in constants {
+        walk_code(value, &mut seen, &mut |_, bt| {
+            lines.extend(bt.iter().map(|op| op.line))
+        });
+    }
+
+    // the instructions generated by the compiler have no line
+    lines.remove(&0);
+    lines
+}
+
+impl VirtualMachine {
+    /// Starts recording the location of each instruction that runs, which makes the program run
+    /// slower
+    pub fn enable_coverage(&mut self) {
+        if self.coverage.is_none() {
+            self.coverage = Some(Box::default());
+        }
+    }
+
+    /// The locations that ran since [`VirtualMachine::enable_coverage`] was called
+    pub fn coverage(&self) -> Option<&Coverage> {
+        self.coverage.as_deref()
+    }
+
+    /// Called before each instruction while the coverage is recorded
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn cover(&mut self, frame: &Frame, line: usize, column: usize) {
+        if line == 0 {
+            return;
+        }
+
+        let module = self.location(frame.bytecode as usize, line).module;
+        if let Some(coverage) = &mut self.coverage {
+            coverage.hit(module, line, column);
+        }
+    }
+}
+
+#[test]
+fn covered_lines() {
+    use crate::{OpCode, OpCodeMetadata};
+
+    // `if false then 1 else 2`, the line 2 never runs
+    let bytecode: Bytecode = [
+        (1, OpCode::Push(0)),
+        (1, OpCode::Jmf(4)),
+        (2, OpCode::Push(1)),
+        (2, OpCode::Jmp(5)),
+        (3, OpCode::Push(2)),
+    ]
+    .into_iter()
+    .map(|(line, op)| OpCodeMetadata::new(line, 1, op))
+    .collect();
+    let constants = vec![Value::Bool(false), Value::Int(1), Value::Int(2)];
+
+    let mut vm = VirtualMachine::default();
+    vm.set_consts(constants.clone());
+    vm.enable_coverage();
+    vm.run(&bytecode).unwrap();
+
+    let coverage = vm.coverage().unwrap();
+    assert_eq!(coverage.lines(None), BTreeSet::from([1, 3]));
+    assert_eq!(lines(&bytecode, &constants), BTreeSet::from([1, 2, 3]));
+    assert_eq!(coverage.modules().collect::<Vec<_>>(), vec![None]);
+
+    // the functions of a module are counted too
+    let mut fields = crate::EnvTable::new();
+    let code = vec![OpCodeMetadata::new(7, 1, OpCode::Load(0))];
+    let id = crate::Fn::new_bt(1, code);
+    fields.insert("id".into(), Value::Fn(crate::gc::GcRef::new(id)));
+    let module = crate::YexModule::new("M".into(), fields);
+    assert_eq!(lines(&vec![], &[module.into()]), BTreeSet::from([7]));
+}
@@ -0,0 +1,274 @@
+//! Debugging support, a [`Debugger`] attached to a vm stops the program at breakpoints and after
+//! each step, and can inspect the program state while it's stopped
+use alloc::{boxed::Box, collections::BTreeSet, vec::Vec};
+
+use crate::{
+    gc::GcRef, literal::fun::walk_code, task::Frame, Bytecode, Fn, Symbol, Value, VirtualMachine,
+};
+
+/// How the program continues after the debugger stopped it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    /// Runs until it reaches a breakpoint
+    Continue,
+    /// Stops at the next line, inside the functions it calls too
+    Into,
+    /// Stops at the next line of the running function, or once it returns
+    Over,
+    /// Stops once the running function returns
+    Out,
+}
+
+/// A line of the program, the module is `None` for the code passed to [`VirtualMachine::run`]
+/// and the functions it defines
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    /// The imported module the code comes from
+    pub module: Option<Symbol>,
+    /// The line of the module source
+    pub line: usize,
+}
+
+/// A function waiting for the functions it called to return
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Call {
+    /// The function name, `None` for anonymous functions and the top level code
+    pub name: Option<Symbol>,
+    /// The line it's running
+    pub at: Location,
+    /// Whether it's the code passed to [`VirtualMachine::run`] or the code of a module, instead of
+    /// a function
+    pub top_level: bool,
+}
+
+/// The state of the program where the debugger stopped it
+#[derive(Debug)]
+pub struct Pause<'a> {
+    /// The line about to run
+    pub at: Location,
+    /// The column of the first instruction of the line
+    pub column: usize,
+    /// Whether the program stopped at a breakpoint, instead of after a step
+    pub breakpoint: bool,
+    /// The locals of the running function, in the order they were declared
+    pub locals: &'a [Value],
+    /// The names of the locals, with the index of each one in `locals`, empty for the top level
+    /// code and the anonymous functions
+    pub names: &'a [(usize, Symbol)],
+    /// The values of the expressions being evaluated, the last one is the top of the stack
+    pub stack: &'a [Value],
+    /// The running functions, the one that stopped is the last one
+    pub backtrace: Vec<Call>,
+}
+
+/// Stops the program at breakpoints and after steps, so it can be inspected
+///
+/// A vm with a debugger checks the location of each instruction, so the program runs slower even
+/// when it doesn't stop
+pub trait Debugger {
+    /// Whether the program stops before running the line `at`
+    fn breaks(&self, at: Location) -> bool;
+
+    /// Called when the program stops, returns how it continues
+    fn pause(&mut self, vm: &VirtualMachine, pause: &Pause<'_>) -> Step;
+}
+
+/// The debugger attached to a vm, with the location of the last instructions it ran
+pub(crate) struct Debugging {
+    debugger: Box<dyn Debugger>,
+    step: Step,
+    /// The code, the line and the depth where the program last stopped
+    paused: (usize, usize, usize),
+    /// The code and the line of the last instruction that had a line
+    last: (usize, usize),
+}
+
+impl VirtualMachine {
+    /// Attaches a debugger, which stops the program before it runs the first line
+    pub fn set_debugger<D: Debugger + 'static>(&mut self, debugger: D) {
+        self.debugger = Some(Box::new(Debugging {
+            debugger: Box::new(debugger),
+            step: Step::Into,
+            paused: (0, 0, 0),
+            last: (0, 0),
+        }));
+    }
+
+    /// Records that `bytecode` and the functions defined by the constants after `constants` come
+    /// from the module `name`, only while a debugger is attached, or the coverage or the profile
+    /// are recorded
+    pub(crate) fn debug_module(&mut self, name: Symbol, bytecode: &Bytecode, constants: usize) {
+        if self.debugger.is_none() && self.coverage.is_none() && self.profile.is_none() {
+            return;
+        }
+
+        self.code_modules.insert(address(bytecode), name);
+        let mut seen = BTreeSet::new();
+        for value in self
+            .shared
+            .iter()
+            .chain(self.constants.iter())
+            .skip(constants)
+        {
+            walk_code(value, &mut seen, &mut |_, bt| {
+                self.code_modules.insert(address(bt), name);
+            });
+        }
+    }
+
+    /// Forgets the top level code of a module, once it ran
+    pub(crate) fn forget_module(&mut self, bytecode: &Bytecode) {
+        self.code_modules.remove(&address(bytecode));
+    }
+
+    /// Called before each instruction while a debugger is attached, stops the program if it
+    /// entered a line with a breakpoint, or the line where the last step ends
+    #[cold]
+    #[inline(never)]
+    pub(crate) fn debug(&mut self, frame: &Frame, line: usize, column: usize) {
+        // the instructions generated by the compiler have no line
+        if line == 0 {
+            return;
+        }
+
+        let Some(mut debugging) = self.debugger.take() else {
+            return;
+        };
+
+        let code = frame.bytecode as usize;
+        let entered = (code, line) != debugging.last;
+        debugging.last = (code, line);
+
+        if entered {
+            let at = self.location(code, line);
+            let depth = self.frames.len() + self.depth;
+            let (paused_code, paused_line, paused_depth) = debugging.paused;
+
+            let breakpoint = debugging.debugger.breaks(at);
+            let stop = breakpoint
+                || match debugging.step {
+                    Step::Continue => false,
+                    Step::Into => true,
+                    Step::Over => {
+                        depth < paused_depth
+                            || depth == paused_depth && (code, line) != (paused_code, paused_line)
+                    }
+                    Step::Out => depth < paused_depth,
+                };
+
+            if stop {
+                let mut backtrace: Vec<Call> = self
+                    .frames
+                    .iter()
+                    .map(|caller| {
+                        let code = caller.bytecode as usize;
+                        let line = unsafe { &*caller.bytecode }[caller.ip].line;
+                        Call {
+                            name: name(caller.fun.as_ref()),
+                            at: self.location(code, line),
+                            top_level: caller.fun.is_none(),
+                        }
+                    })
+                    .collect();
+                backtrace.push(Call {
+                    name: name(frame.fun.as_ref()),
+                    at,
+                    top_level: frame.fun.is_none(),
+                });
+
+                let pause = Pause {
+                    at,
+                    column,
+                    breakpoint,
+                    locals: &self.locals[self.used_locals - frame.locals..self.used_locals],
+                    names: frame
+                        .fun
+                        .as_ref()
+                        .and_then(|fun| fun.info.as_ref())
+                        .map_or(&[], |info| &info.locals),
+                    stack: &self.stack,
+                    backtrace,
+                };
+
+                debugging.step = debugging.debugger.pause(self, &pause);
+                debugging.paused = (code, line, depth);
+            }
+        }
+
+        self.debugger = Some(debugging);
+    }
+}
+
+impl VirtualMachine {
+    /// The location of a line of `code`, which is the address of the code
+    pub(crate) fn location(&self, code: usize, line: usize) -> Location {
+        Location {
+            module: self.code_modules.get(&code).copied(),
+            line,
+        }
+    }
+}
+
+fn address(bytecode: &Bytecode) -> usize {
+    bytecode as *const Bytecode as usize
+}
+
+fn name(fun: Option<&GcRef<Fn>>) -> Option<Symbol> {
+    Some(fun?.info.as_ref()?.name)
+}
+
+#[test]
+fn breakpoints() {
+    use crate::{OpCode, OpCodeMetadata};
+    use alloc::rc::Rc;
+    use core::cell::RefCell;
+
+    /// The line of each pause, with the locals
+    type Pauses = Rc<RefCell<Vec<(usize, Vec<Value>)>>>;
+    struct Recorder(Pauses);
+
+    impl Debugger for Recorder {
+        fn breaks(&self, at: Location) -> bool {
+            at.line == 3
+        }
+
+        fn pause(&mut self, _: &VirtualMachine, pause: &Pause<'_>) -> Step {
+            let main = Call {
+                name: None,
+                at: pause.at,
+                top_level: true,
+            };
+            assert_eq!(pause.backtrace, [main]);
+
+            self.0
+                .borrow_mut()
+                .push((pause.at.line, pause.locals.to_vec()));
+            Step::Continue
+        }
+    }
+
+    let bytecode: Bytecode = [
+        (1, OpCode::Push(0)),
+        (1, OpCode::Save(0)),
+        (2, OpCode::Push(1)),
+        (2, OpCode::Save(1)),
+        (3, OpCode::Load(0)),
+        (3, OpCode::Load(1)),
+        (3, OpCode::Add),
+    ]
+    .into_iter()
+    .map(|(line, op)| OpCodeMetadata::new(line, 1, op))
+    .collect();
+
+    let pauses = Rc::new(RefCell::new(Vec::new()));
+    let mut vm = VirtualMachine::default();
+    vm.set_consts(vec![Value::Int(1), Value::Int(2)]);
+    vm.set_debugger(Recorder(pauses.clone()));
+    vm.run(&bytecode).unwrap();
+
+    // it stops at the first line, and then only at the breakpoint
+    assert_eq!(
+        *pauses.borrow(),
+        vec![(1, vec![]), (3, vec![Value::Int(1), Value::Int(2)]),]
+    );
+}
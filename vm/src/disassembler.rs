@@ -0,0 +1,191 @@
+use alloc::{collections::BTreeSet, string::String};
+use core::fmt::{self, Display, Formatter, Write};
+
+use crate::{
+    literal::{fun::walk_code, pretty::pretty},
+    Bytecode, OpCode, Value,
+};
+
+/// Pretty-prints bytecode, one instruction per line, along with the code of the functions in the
+/// constants
+///
+/// Each line has the index of the instruction (marked with `>` if some jump lands on it), the
+/// source location and the instruction, the constants it pushes are shown after it. Jumps to
+/// `<end>` return from the code:
+///
+/// ```text
+/// <main>:
+///   0000    1:1    Push 0          ; <fn double/1>
+///   0001    1:1    Savg double
+///   0002 >  2:9    Push 1          ; 21
+/// ```
+pub struct Disassembler<'a> {
+    bytecode: &'a Bytecode,
+    constants: &'a [Value],
+}
+
+impl<'a> Disassembler<'a> {
+    /// Creates a disassembler for a program and its constants
+    pub fn new(bytecode: &'a Bytecode, constants: &'a [Value]) -> Self {
+        Self {
+            bytecode,
+            constants,
+        }
+    }
+
+    fn code(&self, f: &mut Formatter<'_>, bytecode: &Bytecode) -> fmt::Result {
+        let targets: BTreeSet<usize> = bytecode
+            .iter()
+            .filter_map(|op| op.opcode.target())
+            .collect();
+
+        for (ip, op) in bytecode.iter().enumerate() {
+            let mark = if targets.contains(&ip) { '>' } else { ' ' };
+            let location = format!("{}:{}", op.line, op.column);
+            let instruction = instruction(op.opcode);
+
+            write!(f, "  {:04} {}  {:<7}", ip, mark, location)?;
+            match op.opcode {
+                OpCode::Push(idx) => {
+                    let constant = match self.constants.get(idx) {
+                        Some(value) => constant(value),
+                        None => String::from("<missing>"),
+                    };
+                    writeln!(f, "{:<16}; {}", instruction, constant)?;
+                }
+                _ => writeln!(f, "{}", instruction)?,
+            }
+        }
+
+        // jumping past the last instruction returns from the code
+        if targets.contains(&bytecode.len()) {
+            writeln!(f, "  {:04} >  <end>", bytecode.len())?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Display for Disassembler<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        writeln!(f, "<main>:")?;
+        self.code(f, self.bytecode)?;
+
+        let mut seen = BTreeSet::new();
+        let mut result = Ok(());
+        for (idx, value) in self.constants.iter().enumerate() {
+            walk_code(value, &mut seen, &mut |fun, bytecode| {
+                if result.is_ok() {
+                    result = writeln!(f)
+                        .and_then(|_| {
+                            let name = constant(&Value::Fn(fun.clone()));
+                            writeln!(f, "{} (constant {}):", name, idx)
+                        })
+                        .and_then(|_| self.code(f, bytecode));
+                }
+            });
+        }
+
+        result
+    }
+}
+
+/// Formats an instruction as its name followed by the operands, jump targets are padded like the
+/// instruction indexes
+fn instruction(op: OpCode) -> String {
+    let debug = format!("{:?}", op);
+    let name = debug.split('(').next().unwrap_or_default();
+
+    let mut out = String::from(name);
+    let _ = match op {
+        OpCode::Jmp(ip) | OpCode::Jmf(ip) | OpCode::Try(ip) => write!(out, " {:04}", ip),
+        OpCode::Loag(sym)
+        | OpCode::Savg(sym)
+        | OpCode::Ref(sym)
+        | OpCode::Import(sym)
+        | OpCode::Tag(sym)
+        | OpCode::Dispatch(sym) => write!(out, " {}", sym),
+        OpCode::Swap(a, b) => write!(out, " {} {}", a, b),
+        OpCode::Range(inclusive) => write!(out, " {}", inclusive),
+        OpCode::Push(n)
+        | OpCode::Load(n)
+        | OpCode::Save(n)
+        | OpCode::Reserve(n)
+        | OpCode::LoadUpval(n)
+        | OpCode::Closure(n)
+        | OpCode::Drop(n)
+        | OpCode::Call(n)
+        | OpCode::TCall(n)
+        | OpCode::RevN(n)
+        | OpCode::Tup(n)
+        | OpCode::Array(n)
+        | OpCode::Dict(n)
+        | OpCode::TupGet(n) => write!(out, " {}", n),
+        _ => Ok(()),
+    };
+    out
+}
+
+/// Formats a constant, functions are shown by their name and arity instead of their code
+fn constant(value: &Value) -> String {
+    match value {
+        Value::Fn(fun) => match &fun.info {
+            Some(info) => format!("<fn {}/{}>", info.name, fun.arity),
+            None => format!("<fn/{}>", fun.arity),
+        },
+        other => pretty(other),
+    }
+}
+
+#[test]
+fn disassemble() {
+    use crate::{Fn, OpCodeMetadata};
+
+    let ops = |ops: &[OpCode]| -> Bytecode {
+        ops.iter()
+            .map(|op| OpCodeMetadata::new(1, 1, *op))
+            .collect()
+    };
+
+    let double = Fn::new_bt(1, ops(&[OpCode::Load(0), OpCode::Push(1), OpCode::Mul]));
+    let constants = [Value::Fn(crate::gc::GcRef::new(double)), Value::Int(2)];
+    let main = ops(&[
+        OpCode::Push(1),
+        OpCode::Jmf(4),
+        OpCode::Loag("x".into()),
+        OpCode::Push(0),
+    ]);
+
+    assert_eq!(
+        Disassembler::new(&main, &constants).to_string(),
+        "<main>:\n  \
+         0000    1:1    Push 1          ; 2\n  \
+         0001    1:1    Jmf 0004\n  \
+         0002    1:1    Loag x\n  \
+         0003    1:1    Push 0          ; <fn/1>\n  \
+         0004 >  <end>\n\
+         \n\
+         <fn/1> (constant 0):\n  \
+         0000    1:1    Load 0\n  \
+         0001    1:1    Push 1          ; 2\n  \
+         0002    1:1    Mul\n"
+    );
+
+    // the functions of a module are listed under the constant of the module
+    let mut fields = crate::EnvTable::new();
+    let half = Fn::new_bt(1, ops(&[OpCode::Load(0), OpCode::Push(1), OpCode::Div]));
+    fields.insert("half".into(), Value::Fn(crate::gc::GcRef::new(half)));
+    let module = crate::YexModule::new("Math".into(), fields);
+    let constants = [module.into(), Value::Int(2)];
+
+    assert_eq!(
+        Disassembler::new(&ops(&[OpCode::Push(0)]), &constants).to_string(),
+        "<main>:\n  \
+         0000    1:1    Push 0          ; type 'Math'\n\
+         \n\
+         <fn/1> (constant 0):\n  \
+         0000    1:1    Load 0\n  \
+         0001    1:1    Push 1          ; 2\n  \
+         0002    1:1    Div\n"
+    );
+}
@@ -1,6 +1,6 @@
-use std::{
-    alloc::{alloc, dealloc, Layout},
-    ptr::null_mut,
+use alloc::alloc::{alloc, dealloc, Layout};
+use core::{
+    ptr::{self, null_mut},
     slice,
 };
 
@@ -17,7 +17,7 @@ struct Entry {
     pub value: Value,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 #[repr(C)]
 /// A table of key-value pairs
 pub struct EnvTable {
@@ -54,7 +54,7 @@ impl EnvTable {
     }
 
     unsafe fn find_entry(entries: *mut Entry, capacity: usize, key: &Symbol) -> (*mut Entry, bool) {
-        let mut index = key.hash & (capacity - 1);
+        let mut index = key.hash() & (capacity - 1);
         let mut last_null: *mut Entry = null_mut();
 
         loop {
@@ -122,6 +122,8 @@ impl EnvTable {
         }
 
         unsafe {
+            // the moved entries were swapped with empty ones
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.entries, self.capacity));
             dealloc(
                 self.entries as *mut u8,
                 Layout::array::<Entry>(self.capacity).unwrap(),
@@ -155,6 +157,16 @@ impl EnvTable {
         self.count == 0
     }
 
+    /// Iterates over the values without cloning them
+    pub(crate) fn values(&self) -> impl Iterator<Item = &Value> {
+        unsafe {
+            slice::from_raw_parts(self.entries, self.capacity)
+                .iter()
+                .filter(|it| it.key.is_some())
+                .map(|it| &it.value)
+        }
+    }
+
     /// Iterates over the table
     pub fn iter(&self) -> impl Iterator<Item = (Key, Value)> {
         unsafe {
@@ -166,8 +178,8 @@ impl EnvTable {
     }
 }
 
-impl std::fmt::Display for EnvTable {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for EnvTable {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{{")?;
         for (index, (key, value)) in self.iter().enumerate() {
             if index == self.len() - 1 {
@@ -180,6 +192,16 @@ impl std::fmt::Display for EnvTable {
     }
 }
 
+impl Clone for EnvTable {
+    fn clone(&self) -> Self {
+        let mut table = Self::with_capacity(self.capacity);
+        for (key, value) in self.iter() {
+            table.insert(key, value);
+        }
+        table
+    }
+}
+
 impl Default for EnvTable {
     fn default() -> Self {
         Self::new()
@@ -189,6 +211,7 @@ impl Default for EnvTable {
 impl Drop for EnvTable {
     fn drop(&mut self) {
         unsafe {
+            ptr::drop_in_place(ptr::slice_from_raw_parts_mut(self.entries, self.capacity));
             dealloc(
                 self.entries as *mut u8,
                 Layout::array::<Entry>(self.capacity).unwrap(),
@@ -204,6 +227,6 @@ impl PartialEq for EnvTable {
         }
 
         self.iter()
-            .all(|(key, value)| other.get(&key).map_or(false, |v| value == v))
+            .all(|(key, value)| other.get(&key).is_some_and(|v| value == v))
     }
 }
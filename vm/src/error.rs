@@ -1,25 +1,65 @@
-use std::{fmt, io};
+use alloc::{boxed::Box, string::String};
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io;
 
-use crate::{raise_err, Symbol};
+#[cfg(feature = "std")]
+use crate::raise_err;
+use crate::{Symbol, Value};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct InterpretError {
     pub msg: String,
     pub err: Symbol,
+    /// The value raised with `throw`, the `rescue` block receives it instead of the error name
+    pub value: Option<Box<Value>>,
+    /// The location is `0:0` until the frame running the instruction that raised it fills it in
     pub line: usize,
     pub column: usize,
+    /// The native function that raised the error, the location is the one of its call
+    pub builtin: Option<Symbol>,
+}
+
+impl InterpretError {
+    /// The value received by the `rescue` block, the raised value or the error name
+    pub fn rescued(&self) -> Value {
+        self.value
+            .as_deref()
+            .cloned()
+            .unwrap_or_else(|| self.err.into())
+    }
+
+    /// Whether the error location was filled in already
+    pub fn is_located(&self) -> bool {
+        self.line != 0
+    }
+
+    /// Sets the location of the error, if it wasn't set yet
+    pub fn locate(&mut self, line: usize, column: usize) {
+        if !self.is_located() {
+            self.line = line;
+            self.column = column;
+        }
+    }
 }
 
 impl fmt::Display for InterpretError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "[{}:{}] :{}\n  {}",
-            self.line, self.column, self.err, self.msg
-        )
+        // the errors raised outside of a program, like when loading it, have no location
+        if self.is_located() {
+            write!(f, "[{}:{}] ", self.line, self.column)?;
+        }
+        write!(f, ":{}", self.err)?;
+
+        if let Some(builtin) = self.builtin {
+            write!(f, " in '{}'", builtin)?;
+        }
+
+        write!(f, "\n  {}", self.msg)
     }
 }
 
+#[cfg(feature = "std")]
 impl From<io::Error> for InterpretError {
     fn from(_: io::Error) -> Self {
         raise_err!(IOError, "Internal IO error")
@@ -1,53 +1,594 @@
-use std::{cell::Cell, fmt::Debug, ptr::NonNull};
+//! Reference counted values, freed incrementally, with a collector for the cycles
+//!
+//! When the last reference to a value is dropped, the value is queued instead of being freed right
+//! away, and every allocation frees a few of the queued values. Freeing a value only queues the
+//! values it references, so dropping a long list costs the same as dropping a single node, instead
+//! of freeing (and recursing over) every node at once.
+//!
+//! Reference counts never reach zero for values that reference each other, like a module with a
+//! field holding the module itself, so they're found by trial deletion: when a reference to a
+//! value that can hold others is dropped and it's still referenced, the value is kept as a
+//! possible root of a cycle. Once enough of them are kept, and by [`collect`], the references
+//! between the values reachable from the roots are subtracted, and the ones left without
+//! references are only kept alive by the cycles, so they're freed. The values are followed through
+//! their [`Trace`] implementation, the ones held by user data aren't visible to it.
+//!
+//! Without the `std` feature there's no thread local queue, so values are freed right away, the
+//! cycles aren't collected, and there are no statistics.
 
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::vec::Vec;
+#[cfg(feature = "std")]
+use core::cell::RefCell;
+use core::{
+    cell::Cell,
+    fmt::Debug,
+    mem::{self, ManuallyDrop},
+    ptr::NonNull,
+    time::Duration,
+};
+
+/// How many queued values each allocation frees by default, more than one so the queue shrinks
+/// while the program allocates
+const FREED_PER_ALLOCATION: usize = 2;
+
+/// How many possible roots of cycles are kept before they're collected, doubled while the
+/// collections find no garbage, so the values kept alive don't get traversed over and over
+#[cfg(feature = "std")]
+const CYCLE_ROOTS: usize = 10_000;
+
+/// The memory statistics of the current thread
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    /// The bytes allocated, including the ones freed since
+    pub allocated: usize,
+    /// The bytes freed
+    pub freed: usize,
+    /// The values waiting to be freed
+    pub pending: usize,
+    /// How many times `collect` ran
+    pub collections: usize,
+    /// The values freed because they were only referenced by cycles
+    pub cycles: usize,
+    /// The longest time `collect` took
+    pub max_pause: Duration,
+    /// The time spent in `collect`
+    pub total_pause: Duration,
+}
+
+impl Stats {
+    const EMPTY: Self = Self {
+        allocated: 0,
+        freed: 0,
+        pending: 0,
+        collections: 0,
+        cycles: 0,
+        max_pause: Duration::ZERO,
+        total_pause: Duration::ZERO,
+    };
+
+    /// The bytes still allocated
+    pub fn live(&self) -> usize {
+        self.allocated - self.freed
+    }
+}
+
+/// The values that can reference other values through a [`GcRef`], so the cycle collector can
+/// follow them
+pub trait Trace {
+    /// Whether the values never reference others, so they can't be part of a cycle and the
+    /// collector skips them
+    const LEAF: bool = false;
+
+    /// Visits every [`GcRef`] held by the value, without cloning them, since the collector
+    /// counts the references
+    fn trace(&self, _tracer: &mut Tracer<'_>) {}
+}
+
+/// Visits the references held by a value, see [`Trace`]
+pub struct Tracer<'a>(&'a mut dyn FnMut(NonNull<Header>));
+
+impl Tracer<'_> {
+    /// Visits a reference held by the traced value
+    pub fn visit<T: Trace>(&mut self, value: &GcRef<T>) {
+        if !T::LEAF {
+            (self.0)(value.inner.cast());
+        }
+    }
+}
+
+macro_rules! leaves {
+    ($($ty:ty),* $(,)?) => {
+        $(impl Trace for $ty {
+            const LEAF: bool = true;
+        })*
+    };
+}
+
+leaves!(
+    alloc::string::String,
+    alloc::vec::Vec<u8>,
+    Box<dyn core::any::Any>,
+    i32,
+    i64,
+    usize,
+);
+
+/// The state of a value for the cycle collector, the names follow the paper by Bacon and Rajan
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+enum Color {
+    /// In use, or not visited yet
+    Black,
+    /// Visited while subtracting the references between the possible cycles
+    Gray,
+    /// Left without references once they were subtracted, so it's garbage
+    White,
+    /// A possible root of a cycle
+    Purple,
+    /// Being freed by the collector, the references dropped by the other garbage are ignored
+    Collecting,
+    /// Freed while it was a possible root, the memory is released once the roots are visited
+    Dropped,
+}
+
+/// What the collector needs to know about the type of a value
+#[cfg_attr(not(feature = "std"), allow(dead_code))]
+struct Kind {
+    leaf: bool,
+    trace: unsafe fn(NonNull<Header>, &mut Tracer<'_>),
+    drop_inner: unsafe fn(NonNull<Header>),
+    dealloc: unsafe fn(NonNull<Header>),
+}
+
+/// The part of every value shared by all the types, the first field of a [`Ref`]
+pub struct Header {
+    count: Cell<usize>,
+    color: Cell<Color>,
+    /// Whether the value is in the roots of the cycle collector
+    buffered: Cell<bool>,
+    kind: &'static Kind,
+}
+
+#[repr(C)]
 struct Ref<T> {
-    pub(in crate::gc) inner: T,
-    pub(in crate::gc) count: Cell<usize>,
+    header: Header,
+    inner: ManuallyDrop<T>,
+}
+
+impl<T: Trace> Ref<T> {
+    const KIND: Kind = Kind {
+        leaf: T::LEAF,
+        trace: trace::<T>,
+        drop_inner: drop_inner::<T>,
+        dealloc: dealloc::<T>,
+    };
+}
+
+unsafe fn trace<T: Trace>(ptr: NonNull<Header>, tracer: &mut Tracer<'_>) {
+    ptr.cast::<Ref<T>>().as_ref().inner.trace(tracer)
+}
+
+unsafe fn drop_inner<T>(ptr: NonNull<Header>) {
+    ManuallyDrop::drop(&mut (*ptr.cast::<Ref<T>>().as_ptr()).inner)
+}
+
+unsafe fn dealloc<T>(ptr: NonNull<Header>) {
+    record(|stats| stats.freed += mem::size_of::<Ref<T>>());
+    drop(Box::from_raw(ptr.cast::<Ref<T>>().as_ptr()));
+}
+
+/// Frees a value whose last reference was dropped, the ones that are roots of the cycle collector
+/// are released once it visits them
+///
+/// # Safety
+/// The value must not be referenced anymore
+unsafe fn free(ptr: NonNull<Header>) {
+    let header = ptr.as_ref();
+    (header.kind.drop_inner)(ptr);
+
+    if header.buffered.get() {
+        header.color.set(Color::Dropped);
+    } else {
+        (header.kind.dealloc)(ptr);
+    }
+}
+
+/// The garbage queue, the roots of the cycle collector and the statistics of a thread
+#[cfg(feature = "std")]
+struct Heap {
+    // the values still queued when the thread exits are leaked, freeing them there would recurse
+    // over them like the queue is meant to avoid, `collect` frees them before that
+    queue: RefCell<Vec<NonNull<Header>>>,
+    /// The values that may be part of a cycle, since a reference to them was dropped
+    roots: RefCell<Vec<NonNull<Header>>>,
+    /// How many roots are kept before the cycles are collected
+    threshold: Cell<usize>,
+    /// Set while the cycles are collected, so freeing them doesn't start another collection
+    collecting: Cell<bool>,
+    stats: Cell<Stats>,
+    step: Cell<usize>,
+}
+
+#[cfg(feature = "std")]
+std::thread_local! {
+    static HEAP: Heap = const {
+        Heap {
+            queue: RefCell::new(Vec::new()),
+            roots: RefCell::new(Vec::new()),
+            threshold: Cell::new(CYCLE_ROOTS),
+            collecting: Cell::new(false),
+            stats: Cell::new(Stats::EMPTY),
+            step: Cell::new(FREED_PER_ALLOCATION),
+        }
+    };
+}
+
+#[cfg(feature = "std")]
+fn record(f: impl FnOnce(&mut Stats)) {
+    let _ = HEAP.try_with(|heap| {
+        let mut stats = heap.stats.get();
+        f(&mut stats);
+        heap.stats.set(stats);
+    });
+}
+
+#[cfg(not(feature = "std"))]
+fn record(_: impl FnOnce(&mut Stats)) {}
+
+/// Queues a value to be freed later, or frees it right away if the thread is exiting
+#[cfg(feature = "std")]
+fn defer(ptr: NonNull<Header>) {
+    let mut garbage = Some(ptr);
+    let _ = HEAP.try_with(|heap| heap.queue.borrow_mut().extend(garbage.take()));
+
+    if let Some(ptr) = garbage {
+        unsafe { free(ptr) };
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn defer(ptr: NonNull<Header>) {
+    unsafe { free(ptr) };
+}
+
+/// Keeps a value that is still referenced after a reference to it was dropped, since the
+/// remaining references may come from a cycle
+#[cfg(feature = "std")]
+fn possible_root(ptr: NonNull<Header>) {
+    let header = unsafe { ptr.as_ref() };
+    if header.color.get() == Color::Collecting {
+        return;
+    }
+
+    header.color.set(Color::Purple);
+    if !header.buffered.get() {
+        let pushed = HEAP.try_with(|heap| heap.roots.borrow_mut().push(ptr));
+        header.buffered.set(pushed.is_ok());
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn possible_root(_: NonNull<Header>) {}
+
+/// Frees up to `n` queued values, returning how many were freed
+#[cfg(feature = "std")]
+fn step(n: usize) -> usize {
+    for freed in 0..n {
+        // the borrow ends before freeing, since it queues the values referenced by this one
+        match HEAP.try_with(|heap| heap.queue.borrow_mut().pop()) {
+            Ok(Some(ptr)) => unsafe { free(ptr) },
+            _ => return freed,
+        }
+    }
+
+    n
+}
+
+#[cfg(not(feature = "std"))]
+fn step(_: usize) -> usize {
+    0
+}
+
+/// How many queued values each allocation frees
+#[cfg(feature = "std")]
+fn step_size() -> usize {
+    HEAP.try_with(|heap| heap.step.get())
+        .unwrap_or(FREED_PER_ALLOCATION)
+}
+
+#[cfg(not(feature = "std"))]
+fn step_size() -> usize {
+    FREED_PER_ALLOCATION
+}
+
+/// Sets how many queued values each allocation frees, higher values keep less memory queued
+/// while lower ones spend less time in each allocation (at least one is freed)
+#[cfg(feature = "std")]
+pub fn set_step_size(n: usize) {
+    let _ = HEAP.try_with(|heap| heap.step.set(n.max(1)));
+}
+
+#[cfg(not(feature = "std"))]
+pub fn set_step_size(_: usize) {}
+
+/// Frees every queued value, including the ones queued while freeing them, and the cycles
+pub fn collect() {
+    #[cfg(feature = "std")]
+    let start = std::time::Instant::now();
+
+    while step(usize::MAX) > 0 {}
+    #[cfg(feature = "std")]
+    if collect_cycles() > 0 {
+        while step(usize::MAX) > 0 {}
+    }
+
+    #[cfg(feature = "std")]
+    record(|stats| {
+        let pause = start.elapsed();
+        stats.collections += 1;
+        stats.max_pause = stats.max_pause.max(pause);
+        stats.total_pause += pause;
+    });
+}
+
+/// Calls `f` with every value referenced by the one at `ptr`
+///
+/// # Safety
+/// The value must be alive
+#[cfg(feature = "std")]
+unsafe fn children(ptr: NonNull<Header>, mut f: impl FnMut(&Header, NonNull<Header>)) {
+    let kind = ptr.as_ref().kind;
+    (kind.trace)(ptr, &mut Tracer(&mut |child| f(child.as_ref(), child)));
+}
+
+/// Subtracts the references from the values reachable from `root`, coloring them gray
+#[cfg(feature = "std")]
+unsafe fn mark_gray(root: NonNull<Header>) {
+    let mut stack = alloc::vec![root];
+    while let Some(ptr) = stack.pop() {
+        let header = ptr.as_ref();
+        if header.color.get() == Color::Gray {
+            continue;
+        }
+
+        header.color.set(Color::Gray);
+        children(ptr, |child, ptr| {
+            child.count.set(child.count.get() - 1);
+            stack.push(ptr);
+        });
+    }
+}
+
+/// Colors white the gray values left without references, and restores the ones still
+/// referenced from outside, with everything they reach
+#[cfg(feature = "std")]
+unsafe fn scan(root: NonNull<Header>) {
+    let mut stack = alloc::vec![root];
+    while let Some(ptr) = stack.pop() {
+        let header = ptr.as_ref();
+        if header.color.get() != Color::Gray {
+            continue;
+        }
+
+        if header.count.get() > 0 {
+            scan_black(ptr);
+        } else {
+            header.color.set(Color::White);
+            children(ptr, |_, ptr| stack.push(ptr));
+        }
+    }
+}
+
+/// Adds back the references from the values reachable from `root`, coloring them black
+#[cfg(feature = "std")]
+unsafe fn scan_black(root: NonNull<Header>) {
+    root.as_ref().color.set(Color::Black);
+
+    let mut stack = alloc::vec![root];
+    while let Some(ptr) = stack.pop() {
+        children(ptr, |child, ptr| {
+            child.count.set(child.count.get() + 1);
+            if child.color.get() != Color::Black {
+                child.color.set(Color::Black);
+                stack.push(ptr);
+            }
+        });
+    }
+}
+
+/// Gathers the white values reachable from `root` into `garbage`
+#[cfg(feature = "std")]
+unsafe fn collect_white(root: NonNull<Header>, garbage: &mut Vec<NonNull<Header>>) {
+    let mut stack = alloc::vec![root];
+    while let Some(ptr) = stack.pop() {
+        let header = ptr.as_ref();
+        if header.color.get() != Color::White || header.buffered.get() {
+            continue;
+        }
+
+        header.color.set(Color::Collecting);
+        garbage.push(ptr);
+        children(ptr, |_, ptr| stack.push(ptr));
+    }
+}
+
+/// Frees the values only referenced by cycles, returning how many were freed
+#[cfg(feature = "std")]
+fn collect_cycles() -> usize {
+    let Ok(roots) = HEAP.try_with(|heap| {
+        if heap.collecting.replace(true) {
+            return None;
+        }
+        Some(mem::take(&mut *heap.roots.borrow_mut()))
+    }) else {
+        return 0;
+    };
+    let Some(roots) = roots else {
+        return 0;
+    };
+
+    // SAFETY:
+    // The roots are alive while they're buffered, the ones freed are only released here, and
+    // the values reachable from alive values are alive
+    let garbage = unsafe {
+        let mut candidates = Vec::new();
+        for ptr in roots {
+            let header = ptr.as_ref();
+            match header.color.get() {
+                Color::Purple if header.count.get() > 0 => {
+                    mark_gray(ptr);
+                    candidates.push(ptr);
+                }
+                Color::Dropped => {
+                    header.buffered.set(false);
+                    (header.kind.dealloc)(ptr);
+                }
+                // either queued to be freed, or grayed from another root
+                _ => header.buffered.set(false),
+            }
+        }
+
+        for &ptr in &candidates {
+            scan(ptr);
+        }
+
+        let mut garbage = Vec::new();
+        for &ptr in &candidates {
+            ptr.as_ref().buffered.set(false);
+        }
+        for ptr in candidates {
+            collect_white(ptr, &mut garbage);
+        }
+
+        // the references between the garbage are ignored while dropping it, since it's colored
+        // as collecting, the count only keeps it from being queued
+        for ptr in &garbage {
+            ptr.as_ref().count.set(usize::MAX / 2);
+        }
+        for &ptr in &garbage {
+            (ptr.as_ref().kind.drop_inner)(ptr);
+        }
+        for &ptr in &garbage {
+            (ptr.as_ref().kind.dealloc)(ptr);
+        }
+
+        garbage.len()
+    };
+
+    record(|stats| stats.cycles += garbage);
+    let _ = HEAP.try_with(|heap| heap.collecting.set(false));
+    garbage
+}
+
+/// Collects the cycles once enough possible roots are kept
+#[cfg(feature = "std")]
+fn collect_cycles_if_needed() {
+    let Ok((roots, threshold)) =
+        HEAP.try_with(|heap| (heap.roots.borrow().len(), heap.threshold.get()))
+    else {
+        return;
+    };
+
+    if roots >= threshold {
+        let threshold = match collect_cycles() {
+            0 => threshold.saturating_mul(2),
+            _ => CYCLE_ROOTS,
+        };
+        let _ = HEAP.try_with(|heap| heap.threshold.set(threshold));
+    }
+}
+
+/// The number of values waiting to be freed
+#[cfg(feature = "std")]
+pub fn pending() -> usize {
+    HEAP.try_with(|heap| heap.queue.borrow().len()).unwrap_or(0)
+}
+
+#[cfg(not(feature = "std"))]
+pub fn pending() -> usize {
+    0
+}
+
+/// Returns the memory statistics of the current thread, they're always empty without the `std`
+/// feature
+#[cfg(feature = "std")]
+pub fn stats() -> Stats {
+    let stats = HEAP
+        .try_with(|heap| heap.stats.get())
+        .unwrap_or(Stats::EMPTY);
+    Stats {
+        pending: pending(),
+        ..stats
+    }
+}
+
+#[cfg(not(feature = "std"))]
+pub fn stats() -> Stats {
+    Stats::EMPTY
 }
 
 pub struct GcRef<T> {
     inner: NonNull<Ref<T>>,
 }
 
-impl<T> GcRef<T> {
+impl<T: Trace> GcRef<T> {
     pub fn new(constant: T) -> Self {
+        step(step_size());
+        #[cfg(feature = "std")]
+        collect_cycles_if_needed();
+        record(|stats| stats.allocated += mem::size_of::<Ref<T>>());
+
         // SAFETY:
         // We pass the box to into_raw after the allocation, everything is properly aligned and
         // nothing can be null
         unsafe {
             Self {
                 inner: NonNull::new_unchecked(Box::into_raw(Box::new(Ref {
-                    inner: constant,
-                    count: Cell::new(1),
+                    header: Header {
+                        count: Cell::new(1),
+                        color: Cell::new(Color::Black),
+                        buffered: Cell::new(false),
+                        kind: &Ref::<T>::KIND,
+                    },
+                    inner: ManuallyDrop::new(constant),
                 }))),
             }
         }
     }
+}
 
+impl<T> GcRef<T> {
     pub unsafe fn mut_ref(&mut self) -> &mut T {
         &mut (*self.inner.as_ptr()).inner
     }
 
+    /// Returns the address of the value, which identifies it while it's alive
+    pub(crate) fn addr(&self) -> usize {
+        self.inner.as_ptr() as usize
+    }
+
     fn from_inner(inner: NonNull<Ref<T>>) -> Self {
         Self { inner }
     }
 
+    fn header(&self) -> &Header {
+        unsafe { &self.inner.as_ref().header }
+    }
+
     fn inc_ref(&self) {
-        unsafe {
-            let ref_count = self.ref_count();
-            self.inner.as_ref().count.set(ref_count + 1)
-        }
+        let count = &self.header().count;
+        count.set(count.get() + 1)
     }
 
     fn dec_ref(&self) {
-        unsafe {
-            let ref_count = self.ref_count();
-            self.inner.as_ref().count.set(ref_count - 1)
-        }
+        let count = &self.header().count;
+        count.set(count.get() - 1)
     }
 
     fn ref_count(&self) -> usize {
-        unsafe { self.inner.as_ref().count.get() }
+        self.header().count.get()
     }
 }
 
@@ -59,7 +600,7 @@ impl<T> Clone for GcRef<T> {
     }
 }
 
-impl<T> std::ops::Deref for GcRef<T> {
+impl<T> core::ops::Deref for GcRef<T> {
     type Target = T;
     #[inline(always)]
     fn deref(&self) -> &Self::Target {
@@ -73,7 +614,9 @@ impl<T> Drop for GcRef<T> {
         self.dec_ref();
 
         if self.ref_count() == 0 {
-            unsafe { drop(Box::from_raw(self.inner.as_ptr())) };
+            defer(self.inner.cast());
+        } else if !self.header().kind.leaf {
+            possible_root(self.inner.cast());
         }
     }
 }
@@ -87,7 +630,53 @@ impl<T: PartialEq> PartialEq for GcRef<T> {
 impl<T: Eq> Eq for GcRef<T> {}
 
 impl<T: Debug> Debug for GcRef<T> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:#?}", **self)
     }
 }
+
+#[test]
+fn drops_long_lists() {
+    use crate::List;
+
+    let mut xs = List::new();
+    for i in 0..1_000_000 {
+        xs = xs.prepend(i.into());
+    }
+
+    // freeing it right away would recurse once per node
+    drop(xs);
+    assert!(pending() > 0);
+
+    collect();
+    assert_eq!(pending(), 0);
+}
+
+#[test]
+fn collects_cycles() {
+    use crate::{Tuple, Value};
+
+    let cycle = || {
+        let mut tuple = Tuple::from(vec![Value::Nil, Value::Int(1)]);
+        let this = Value::Tuple(tuple.clone());
+        // SAFETY: nothing else reads the tuple while it's changed
+        unsafe { tuple.0.mut_ref()[0] = this };
+        tuple
+    };
+
+    collect();
+    let before = stats();
+
+    // still referenced from outside, so it's kept
+    let kept = cycle();
+    collect();
+    assert_eq!(stats().cycles, before.cycles);
+    assert_eq!(kept.0[1], Value::Int(1));
+
+    drop(kept);
+    drop(cycle());
+    collect();
+    let after = stats();
+    assert_eq!(after.cycles - before.cycles, 2);
+    assert_eq!(after.live(), before.live());
+}
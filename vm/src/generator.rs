@@ -0,0 +1,183 @@
+//! Generators, the functions defined with `fn*` that produce their values lazily
+//!
+//! Calling a generator function only matches its arguments, then the `Generator` instruction moves
+//! the frame and its locals to the heap and returns them as a generator. Each `Gen.next` runs the
+//! frame again until it reaches a `yield`, which moves the frame back to the generator along with
+//! the values it left on the stack, so a generator only runs while it's asked for a value
+
+use alloc::{boxed::Box, vec::Vec};
+use core::{cell::RefCell, mem};
+
+use crate::{
+    error::InterpretResult, literal::ffi::userdata::UserData, raise, task::Frame, Value,
+    VirtualMachine, NIL,
+};
+
+/// A generator, stored in the vm as userdata
+pub(crate) struct Generator(RefCell<State>);
+
+enum State {
+    /// Waits for `Gen.next`
+    Suspended(Box<Saved>),
+    /// Runs inside of a `Gen.next`, so it can't be resumed again
+    Running,
+    /// The function returned or raised an error
+    Done,
+}
+
+/// The frame of a generator that isn't running
+struct Saved {
+    /// The frame, pointing to the instruction that suspended it
+    frame: Frame,
+    locals: Vec<Value>,
+    /// The values the frame left on the stack, like the left side of `x + yield y`
+    stack: Vec<Value>,
+    /// Set once it yielded, so `nil` is pushed as the result of the `yield` it resumes from
+    started: bool,
+}
+
+impl VirtualMachine {
+    /// Checks if `frame`, with `base` frames under it, is the frame of the generator that's
+    /// running, which happens when the generator tail calls a generator function
+    ///
+    /// The generator continues as the one it called, so the generators that call themselves in
+    /// tail position, like `def* count n = let _ = yield n in count (n + 1)`, loop forever
+    pub(crate) fn in_generator(&self, base: usize) -> bool {
+        self.frames.len() == base && self.generators.last() == Some(&self.depth)
+    }
+
+    /// Moves `frame` and its locals to a new generator, the frame is left finished so the dispatch
+    /// loop returns from it, with the generator as the result
+    pub(crate) fn generator(&mut self, frame: &mut Frame) -> Value {
+        let locals = self.generator_locals(frame.locals);
+
+        // SAFETY: the code is kept alive by the function of the frame
+        let len = unsafe { &*frame.bytecode }.len();
+        let saved = Frame {
+            bytecode: frame.bytecode,
+            upvalues: frame.upvalues,
+            ip: mem::replace(&mut frame.ip, len),
+            locals: mem::take(&mut frame.locals),
+            try_stack: mem::take(&mut frame.try_stack),
+            fun: frame.fun.clone(),
+        };
+
+        let state = State::Suspended(Box::new(Saved {
+            frame: saved,
+            locals,
+            stack: Vec::new(),
+            started: false,
+        }));
+        Value::UserData(UserData::new(Generator(RefCell::new(state))))
+    }
+
+    /// Runs the generator until it yields a value, returning `None` once it's done
+    pub(crate) fn resume_generator(
+        &mut self,
+        generator: &Generator,
+    ) -> InterpretResult<Option<Value>> {
+        let state = mem::replace(&mut *generator.0.borrow_mut(), State::Running);
+        let saved = match state {
+            State::Suspended(saved) => saved,
+            State::Running => return raise!(GenError, "The generator is already running"),
+            State::Done => {
+                *generator.0.borrow_mut() = State::Done;
+                return Ok(None);
+            }
+        };
+        let Saved {
+            mut frame,
+            locals,
+            stack,
+            started,
+        } = *saved;
+
+        // like any other call, the frame takes a local more than its own
+        self.used_locals += 1;
+        self.grow_locals(locals.len());
+        for (i, local) in locals.into_iter().enumerate() {
+            self.locals[self.used_locals + i] = local;
+        }
+        self.used_locals += frame.locals;
+
+        let base = self.stack.len();
+        for (_, height) in &mut frame.try_stack {
+            *height += base;
+        }
+        for value in stack {
+            self.push(value);
+        }
+        if started {
+            self.push(NIL);
+        }
+        frame.ip += 1;
+
+        self.generators.push(self.depth + 1);
+        let res = self.run_frame(frame);
+        self.generators.pop();
+        let (state, res) = match (res, self.yielded.take()) {
+            (Ok(()), Some(mut frame)) => {
+                let value = self.pop();
+                let locals = self.generator_locals(frame.locals);
+
+                // the stack may be somewhere else when the generator is resumed
+                for (_, height) in &mut frame.try_stack {
+                    *height -= base;
+                }
+                let mut stack = Vec::new();
+                while self.stack.len() > base {
+                    stack.push(self.pop());
+                }
+                stack.reverse();
+
+                let saved = Saved {
+                    frame,
+                    locals,
+                    stack,
+                    started: true,
+                };
+                (State::Suspended(Box::new(saved)), Ok(Some(value)))
+            }
+            // the function returned, its result is discarded
+            (Ok(()), None) => {
+                self.pop();
+                (State::Done, Ok(None))
+            }
+            (Err(e), _) => (State::Done, Err(e)),
+        };
+
+        self.used_locals -= 1;
+        *generator.0.borrow_mut() = state;
+        res
+    }
+
+    /// Takes the last `n` locals, which belong to the frame of a generator
+    fn generator_locals(&mut self, n: usize) -> Vec<Value> {
+        let start = self.used_locals - n;
+        let locals = self.locals[start..self.used_locals]
+            .iter_mut()
+            .map(|local| mem::replace(local, NIL))
+            .collect();
+
+        self.used_locals = start;
+        locals
+    }
+}
+
+/// Returns the generator inside of `value`
+pub(crate) fn generator_of(value: &Value) -> InterpretResult<&Generator> {
+    match value {
+        Value::UserData(data) => match data.downcast_ref::<Generator>() {
+            Some(generator) => Ok(generator),
+            None => raise!(TypeError, "Expected a generator, found '{}'", value),
+        },
+        other => raise!(TypeError, "Expected a generator, found '{}'", other),
+    }
+}
+
+impl Generator {
+    /// Checks if the generator can't produce more values
+    pub(crate) fn is_done(&self) -> bool {
+        matches!(&*self.0.borrow(), State::Done)
+    }
+}
@@ -1,48 +1,89 @@
+#![cfg_attr(not(feature = "std"), no_std)]
 #![deny(missing_docs)]
 #![allow(unused_unsafe)]
 #![deny(clippy::all)]
 #![allow(clippy::unit_arg, clippy::option_map_unit_fn)]
 //! Virtual Machine implementation for the yex programming language
+//!
+//! The `std` feature (enabled by default) provides the FFI module, the IO and process functions of
+//! the prelude and [`VirtualMachine::run_with_timeout`], without it the crate only needs `alloc`.
+#[macro_use]
+extern crate alloc;
+
+mod builder;
+pub mod coverage;
+pub mod debug;
+mod disassembler;
 #[allow(missing_docs)]
 pub mod env;
 #[allow(missing_docs)]
 pub mod error;
 #[doc(hidden)]
 pub mod gc;
+mod generator;
 #[allow(missing_docs)]
 pub mod literal;
 mod opcode;
+mod options;
 mod prelude;
+pub mod profile;
+mod registry;
+mod resumable;
+mod serialize;
 mod stack;
+mod task;
+#[cfg(feature = "std")]
+mod trace;
 
+use alloc::{boxed::Box, string::String, sync::Arc, vec::Vec};
 use gc::GcRef;
 use literal::{
-    fun::{FnArgs, NativeFn},
+    fun::{walk_code, FnArgs, MAX_APPLIED},
     TryGet,
 };
 
-use crate::error::InterpretResult;
+use crate::{
+    error::InterpretResult,
+    task::{Frame, Resume, Scheduler, Switched},
+};
 
 pub use crate::{
+    builder::VmBuilder,
+    disassembler::Disassembler,
     env::EnvTable,
     literal::{
-        fun::{Fn, FnKind},
+        array::Array,
+        convert::{FromYex, IntoYex},
+        dict::Dict,
+        fun::{Fn, FnInfo, FnKind, NativeFn},
         list::List,
+        pretty::pretty,
+        range::Range,
         symbol::Symbol,
         tuple::Tuple,
         yexmodule::YexModule,
         Value,
     },
     opcode::{OpCode, OpCodeMetadata},
+    options::VmOptions,
+    prelude::{prelude, Capabilities},
+    registry::Registry,
+    resumable::Status,
+    serialize::{deserialize, is_compiled, serialize, Packed},
     stack::StackVec,
 };
 
+#[cfg(feature = "std")]
+pub use crate::trace::Trace;
+
+pub use macros::{yex_fn, yex_module, FromYex, IntoYex};
+
 const STACK_SIZE: usize = 512;
+/// The values that the stack keeps free when a function is entered, for the values pushed by it
+/// before it calls another one
+const STACK_HEADROOM: usize = 64;
 const NIL: Value = Value::Nil;
 
-static mut LINE: usize = 1;
-static mut COLUMN: usize = 1;
-
 #[macro_export]
 #[doc(hidden)]
 macro_rules! raise {
@@ -55,14 +96,13 @@ macro_rules! raise {
 #[doc(hidden)]
 macro_rules! raise_err {
     ($error: ident, $($fmtargs:expr),*) => {
-        unsafe {
-            let msg = $crate::Symbol::new(stringify!($error));
-            $crate::error::InterpretError {
-                line: $crate::LINE,
-                column: $crate::COLUMN,
-                err: msg,
-                msg: format!($($fmtargs),*),
-            }
+        $crate::error::InterpretError {
+            line: 0,
+            column: 0,
+            err: $crate::Symbol::new(stringify!($error)),
+            msg: format!($($fmtargs),*),
+            value: None,
+            builtin: None,
         }
     };
 }
@@ -73,23 +113,105 @@ type Stack = StackVec<Value, STACK_SIZE>;
 /// be loaded
 pub type Bytecode = Vec<OpCodeMetadata>;
 
+/// Loads the code of a module from it's name, used by the `import` statement
+///
+/// The vm doesn't know where modules are stored, so the host decides how a name is resolved (like
+/// reading `foo.yex` next to the main file) and compiles it
+pub type ModuleLoader = Box<dyn FnMut(Symbol) -> InterpretResult<(Bytecode, Vec<Value>)>>;
+
 type BytecodeRef<'a> = &'a Bytecode;
-use std::{mem::swap, ops, ptr};
+
+/// The function that a call runs, once it's prepared
+pub(crate) enum Callee {
+    /// The function was native or partially applied, the result is on the stack already
+    Done,
+    /// A bytecode function, which runs on a new frame
+    Enter(GcRef<Fn>),
+    /// A bytecode function called in tail position, which takes over the current frame
+    Replace(GcRef<Fn>),
+}
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::{
+    mem::{self, swap},
+    ops, ptr,
+};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
 /// Implements the Yex virtual machine, which runs the [`crate::OpCode`] instructions in a stack
 /// model
 pub struct VirtualMachine {
     stack: Stack,
     locals: Vec<Value>,
     used_locals: usize,
-    /// the constants of the bytecode
+    /// the constants of the bytecode, after the ones of the registry the vm was created with
     pub constants: Vec<Value>,
+    /// the constants of the registry the vm was created with, shared with the other vms
+    shared: Arc<[Value]>,
     globals: EnvTable,
+    /// the globals the vm was created with, like the prelude, which the program can shadow
+    builtins: EnvTable,
+    registry: Option<Registry>,
+    loader: Option<ModuleLoader>,
+    modules: EnvTable,
+    importing: Vec<Symbol>,
+    /// The last error caught by a `rescue` block, so `throw` can raise it again
+    rescued: Option<error::InterpretError>,
+    /// The command line arguments of the script, returned by `Sys.args`
+    args: Vec<String>,
+    /// The frames of the running task that called other functions
+    frames: Vec<Frame>,
+    /// How many dispatch loops are running, native functions that call back into the vm start a
+    /// new one
+    depth: usize,
+    /// How many native functions are running, inside each other
+    natives: usize,
+    scheduler: Scheduler,
+    /// The debugger attached by [`VirtualMachine::set_debugger`]
+    debugger: Option<Box<debug::Debugging>>,
+    /// The locations that ran, once [`VirtualMachine::enable_coverage`] is called
+    coverage: Option<Box<coverage::Coverage>>,
+    /// The samples taken, once [`VirtualMachine::enable_profiling`] is called
+    profile: Option<Box<profile::Profile>>,
+    /// The frames that called the native functions which are running, after the number of frames
+    /// under them, so the samples taken when the natives call back into the vm see them, only
+    /// kept while profiling
+    native_callers: Vec<(usize, *const Frame)>,
+    /// The module of the imported code, by the address of the code, only kept while a debugger is
+    /// attached, or the coverage or the profile are recorded
+    code_modules: BTreeMap<usize, Symbol>,
+    /// The options the vm was built with, the threads the scripts start get the same ones
+    options: VmOptions,
+    /// The program started by [`VirtualMachine::run_until_yield`], until it finishes
+    resumable: Option<Box<resumable::Resumable>>,
+    /// The frame of the generator that just yielded, taken back once its dispatch loop returns
+    yielded: Option<Frame>,
+    /// The depth of the dispatch loops that run generators
+    generators: Vec<usize>,
+    /// The resources used by the current run, only kept when the options have limits or the vm
+    /// has fuel
+    usage: Option<Box<options::Usage>>,
+    #[cfg(feature = "std")]
+    deadline: Option<Instant>,
+    #[cfg(feature = "std")]
+    trace: Option<Trace>,
 }
 
 impl VirtualMachine {
-    /// Reset the instruction pointer and the stack
+    /// Creates a [`VmBuilder`], used to register native functions and modules before running
+    pub fn builder() -> VmBuilder {
+        VmBuilder::new()
+    }
+
+    /// Reset the instruction pointer and the stack, the values of a program suspended by
+    /// [`VirtualMachine::run_until_yield`] are kept
     pub fn reset(&mut self) {
-        self.stack = stackvec![];
+        let keep = self.suspended_stack();
+        if keep == 0 {
+            self.stack = stackvec![];
+        }
+        while self.stack.len() > keep {
+            self.pop();
+        }
     }
 
     /// sets the constants for execution
@@ -97,6 +219,94 @@ impl VirtualMachine {
         self.constants = constants;
     }
 
+    /// Appends the constants of a separately compiled program to the vm constants, patching the
+    /// program bytecode (and the bytecode of the functions it defines) to the new indexes
+    ///
+    /// Symbols and short strings already in the vm constants are reused instead of appended again
+    pub fn load(&mut self, mut bytecode: Bytecode, constants: Vec<Value>) -> Bytecode {
+        let indexes = {
+            let mut shared: BTreeMap<(bool, &str), usize> = self
+                .shared
+                .iter()
+                .chain(self.constants.iter())
+                .enumerate()
+                .filter_map(|(idx, value)| Some((shared_key(value)?, idx)))
+                .collect();
+
+            let mut next = self.shared.len() + self.constants.len();
+            let mut indexes = Vec::with_capacity(constants.len());
+            for value in constants.iter() {
+                let idx = match shared_key(value) {
+                    Some(key) => *shared.entry(key).or_insert(next),
+                    None => next,
+                };
+                if idx == next {
+                    next += 1;
+                }
+                indexes.push(idx);
+            }
+            indexes
+        };
+
+        let mut seen = BTreeSet::new();
+        relocate(&mut bytecode, &indexes);
+        for value in constants.iter() {
+            relocate_value(value, &indexes, &mut seen);
+        }
+
+        for (value, idx) in constants.into_iter().zip(indexes) {
+            if idx == self.shared.len() + self.constants.len() {
+                self.constants.push(value);
+            }
+        }
+        bytecode
+    }
+
+    /// Creates a vm that uses the globals and constants of a shared [`Registry`]
+    pub fn with_registry(registry: &Registry) -> Self {
+        let mut vm = Self::with_globals(EnvTable::new());
+        vm.shared = registry.constants.clone();
+        vm.registry = Some(registry.clone());
+        vm
+    }
+
+    /// Freezes the vm globals and constants into a [`Registry`], usually after running the code of
+    /// a library
+    pub fn freeze(self) -> Registry {
+        let globals = match self.registry {
+            Some(ref registry) => {
+                let mut globals = (*registry.globals).clone();
+                for (name, value) in self.builtins.iter().chain(self.globals.iter()) {
+                    globals.insert(name, value);
+                }
+                globals
+            }
+            None => {
+                let mut globals = self.builtins;
+                for (name, value) in self.globals.iter() {
+                    globals.insert(name, value);
+                }
+                globals
+            }
+        };
+
+        let constants = if self.constants.is_empty() {
+            self.shared
+        } else {
+            self.shared.iter().cloned().chain(self.constants).collect()
+        };
+        Registry::with_constants(globals, constants)
+    }
+
+    /// The constant at `idx`, the first indexes are the constants of the registry
+    #[inline(always)]
+    fn constant(&self, idx: usize) -> &Value {
+        match idx.checked_sub(self.shared.len()) {
+            Some(idx) => &self.constants[idx],
+            None => &self.shared[idx],
+        }
+    }
+
     /// Pop's the last value on the stack
     pub fn pop_last(&self) -> &Value {
         self.stack.last().unwrap_or(&Value::Nil)
@@ -104,7 +314,11 @@ impl VirtualMachine {
 
     /// Get the value of a global variable
     pub fn get_global<T: Into<Symbol>>(&self, name: T) -> Option<Value> {
-        self.globals.get(&name.into())
+        let name = name.into();
+        self.globals
+            .get(&name)
+            .or_else(|| self.builtins.get(&name))
+            .or_else(|| self.registry.as_ref()?.globals.get(&name))
     }
 
     /// Set the value of a global variable
@@ -112,73 +326,626 @@ impl VirtualMachine {
         self.globals.insert(name.into(), value);
     }
 
+    /// Returns the documentation of a value, as shown by the `help` builtin, strings and symbols
+    /// are looked up as global names (like `"List.map"`)
+    pub fn help(&self, value: &Value) -> InterpretResult<String> {
+        prelude::help_text(self, value)
+    }
+
+    /// Registers a native function as a global variable
+    pub fn register<T: Into<Symbol>>(&mut self, name: T, arity: usize, fun: NativeFn) {
+        self.set_global(name, Fn::new_native(arity, fun).into());
+    }
+
+    /// Registers a rust closure as a global function, which can capture the state of the host
+    ///
+    /// ```
+    /// # use vm::{VirtualMachine, Value};
+    /// let mut vm = VirtualMachine::default();
+    /// let offset = 10;
+    /// vm.register_native("shift", 1, move |_, args| match args[0] {
+    ///     Value::Int(n) => Ok(Value::Int(n + offset)),
+    ///     _ => vm::raise!(TypeError, "Expected an int"),
+    /// });
+    ///
+    /// assert_eq!(vm.call_global("shift", &[Value::Int(1)]).unwrap(), Value::Int(11));
+    /// ```
+    pub fn register_native<T, F>(&mut self, name: T, arity: usize, fun: F)
+    where
+        T: Into<Symbol>,
+        F: core::ops::Fn(&mut VirtualMachine, &[Value]) -> InterpretResult<Value> + 'static,
+    {
+        let name = name.into();
+        let fun = Fn::from_closure(arity, fun).with_info(FnInfo::new(name, vec![], None));
+        self.set_global(name, fun.into());
+    }
+
+    /// Calls a function value with the given arguments, returning it's result
+    pub fn call_value(&mut self, fun: Value, args: &[Value]) -> InterpretResult<Value> {
+        let len = self.stack.len();
+
+        for arg in args.iter().rev() {
+            self.push(arg.clone());
+        }
+        self.push(fun);
+
+        match self.call(args.len()) {
+            Ok(()) => Ok(self.pop()),
+            Err(e) => {
+                while self.stack.len() > len {
+                    self.pop();
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Calls the global function `name` with the given arguments
+    ///
+    /// ```
+    /// use vm::{Value, VirtualMachine};
+    ///
+    /// let mut vm = VirtualMachine::default();
+    /// let ty = vm.call_global("type", &[Value::Num(1.0)]).unwrap();
+    /// assert_eq!(ty, vm.get_global("Num").unwrap());
+    /// ```
+    pub fn call_global<T: Into<Symbol>>(
+        &mut self,
+        name: T,
+        args: &[Value],
+    ) -> InterpretResult<Value> {
+        let name = name.into();
+        let fun = match self.get_global(name) {
+            Some(fun) => fun,
+            None => raise!(NameError, "Undefined variable '{}'", name)?,
+        };
+
+        self.call_value(fun, args)
+    }
+
+    /// Calls the method `name` from `module` with the given arguments
+    pub fn call_method<T: Into<Symbol>>(
+        &mut self,
+        module: &Value,
+        name: T,
+        args: &[Value],
+    ) -> InterpretResult<Value> {
+        let name = name.into();
+        let ty: GcRef<YexModule> = module.get()?;
+
+        let method = ty.fields.get(&name).ok_or(raise_err!(
+            FieldError,
+            "Undefined method '{}' for type '{}'",
+            name,
+            ty.name
+        ))?;
+
+        self.call_value(method, args)
+    }
+
+    /// Sets the command line arguments of the script, returned by `Sys.args`
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    /// Sets the [`ModuleLoader`] used by the `import` statement, without one importing a module
+    /// raises an `ImportError`
+    pub fn set_loader(&mut self, loader: ModuleLoader) {
+        self.loader = Some(loader);
+    }
+
+    /// Imports the module `name`, returning it's globals as the fields of a module
+    ///
+    /// The module runs with the globals defined before the import, and only the globals it
+    /// defines are exported, modules are cached so their code runs only once per vm
+    pub fn import<T: Into<Symbol>>(&mut self, name: T) -> InterpretResult<Value> {
+        let name = name.into();
+        if let Some(module) = self.modules.get(&name) {
+            return Ok(module);
+        }
+
+        if self.importing.contains(&name) {
+            raise!(ImportError, "Circular import of module '{}'", name)?;
+        }
+
+        let mut loader = match self.loader.take() {
+            Some(loader) => loader,
+            None => raise!(
+                ImportError,
+                "Can't import module '{}', no loader was set",
+                name
+            )?,
+        };
+        let res = loader(name);
+        self.loader = Some(loader);
+
+        let (bytecode, constants) = res?;
+        let loaded = self.shared.len() + self.constants.len();
+        let bytecode = self.load(bytecode, constants);
+        self.debug_module(name, &bytecode, loaded);
+
+        let outer = self.globals.clone();
+        let len = self.stack.len();
+
+        self.importing.push(name);
+        let res = self.run(&bytecode);
+        self.importing.pop();
+        self.forget_module(&bytecode);
+
+        while self.stack.len() > len {
+            self.pop();
+        }
+        let inner = core::mem::replace(&mut self.globals, outer);
+        res?;
+
+        let mut fields = EnvTable::new();
+        for (key, value) in inner.iter() {
+            if self.globals.get(&key).is_none() {
+                fields.insert(key, value);
+            }
+        }
+
+        let module: Value = YexModule::new(name, fields).into();
+        self.modules.insert(name, module.clone());
+        Ok(module)
+    }
+
+    /// Executes the bytecode, raising a `TimeoutError` if it doesn't finish in `timeout`
+    ///
+    /// The deadline is checked at safepoints (function calls and tail calls), so native functions
+    /// that don't call back into the vm aren't interrupted
+    #[cfg(feature = "std")]
+    pub fn run_with_timeout(
+        &mut self,
+        bytecode: BytecodeRef,
+        timeout: Duration,
+    ) -> InterpretResult<()> {
+        let used_locals = self.used_locals;
+        let old_deadline = self.deadline.replace(Instant::now() + timeout);
+
+        let res = self.run(bytecode);
+
+        self.deadline = old_deadline;
+        if res.is_err() {
+            self.used_locals = used_locals;
+        }
+
+        res
+    }
+
+    /// Sets the [`Trace`] used to record or replay the nondeterministic inputs of the program
+    #[cfg(feature = "std")]
+    pub fn set_trace(&mut self, trace: Trace) {
+        self.trace = Some(trace);
+    }
+
+    /// Computes a nondeterministic input (like a line read from stdin), recording it if a trace
+    /// is being recorded or returning the recorded value if it's being replayed
+    ///
+    /// Every native function that depends on the outside world should go through here, so the
+    /// program can be replayed deterministically
+    #[cfg(feature = "std")]
+    pub fn nondeterministic<T: Into<Symbol>>(
+        &mut self,
+        kind: T,
+        compute: impl FnOnce() -> InterpretResult<Value>,
+    ) -> InterpretResult<Value> {
+        match &mut self.trace {
+            Some(trace) => trace.entry(kind.into(), compute),
+            None => compute(),
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn deadline_expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    #[cfg(not(feature = "std"))]
+    fn deadline_expired(&self) -> bool {
+        false
+    }
+
+    #[inline(always)]
+    #[cfg(feature = "std")]
+    fn check_deadline(&self) -> InterpretResult<()> {
+        if self.deadline.is_some() && self.deadline_expired() {
+            raise!(TimeoutError, "Execution timed out")
+        } else {
+            Ok(())
+        }
+    }
+
+    #[inline(always)]
+    #[cfg(not(feature = "std"))]
+    fn check_deadline(&self) -> InterpretResult<()> {
+        Ok(())
+    }
+
+    /// Raises a `RecursionError` when the stack is almost full, which happens when a function
+    /// recurses too deep before returning, the script can rescue it
+    #[inline(always)]
+    fn check_stack(&self) -> InterpretResult<()> {
+        if self.stack.len() + STACK_HEADROOM > STACK_SIZE {
+            raise!(
+                RecursionError,
+                "Maximum recursion depth exceeded, the stack holds at most {} values",
+                STACK_SIZE
+            )
+        } else {
+            Ok(())
+        }
+    }
+
     /// Executes a given set of bytecode instructions
     pub fn run(&mut self, bytecode: BytecodeRef) -> InterpretResult<()> {
-        let bytecode = bytecode;
-        let mut try_stack = vec![];
-
-        let mut ip = 0;
-        let mut frame_locals = 0;
-
-        while ip < bytecode.len() {
-            let op = unsafe {
-                let op = bytecode[ip];
-                LINE = op.line;
-                COLUMN = op.column;
-                op.opcode
-            };
+        self.run_frame(Frame::borrowed(bytecode, &[]))
+    }
+
+    /// Executes the code of a frame in a new dispatch loop
+    fn run_frame(&mut self, frame: Frame) -> InterpretResult<()> {
+        self.enter_loop(frame, self.frames.len(), self.scheduler.current)
+    }
 
+    /// Starts a dispatch loop that runs `frame`, `base` is the number of frames under it and
+    /// `entering` is the task that runs it
+    fn enter_loop(&mut self, frame: Frame, base: usize, entering: usize) -> InterpretResult<()> {
+        if self.depth == 0 {
+            self.start_run();
+        }
+        self.depth += 1;
+        let res = self.run_loop(frame, base, entering);
+        self.depth -= 1;
+        if self.depth == 0 {
+            self.end_run();
+        }
+        res
+    }
+
+    /// The dispatch loop, the functions called by the code run in new frames of the same loop,
+    /// which returns once `frame` returns
+    ///
+    /// `entering` is the task that started the loop, the other tasks only run while it's
+    /// suspended
+    fn run_loop(&mut self, mut frame: Frame, base: usize, entering: usize) -> InterpretResult<()> {
+        self.check_deadline()?;
+        self.check_limits(self.frames.len() + self.depth)?;
+        self.check_stack()?;
+
+        loop {
+            // SAFETY: the code of the frame is kept alive by the frame itself, or by the caller
+            // of `run_frame`, and the `GcRef` contents never move
+            let bytecode = unsafe { &*frame.bytecode };
+
+            if frame.ip >= bytecode.len() {
+                self.used_locals -= frame.locals;
+
+                if self.frames.len() > base {
+                    frame = self.frames.pop().unwrap();
+                    self.used_locals -= 1;
+                    frame.ip += 1;
+                    continue;
+                }
+
+                // only the loop that runs the program switches tasks, not the ones started by
+                // native functions
+                let switched = if self.depth != 1 || self.natives != 0 {
+                    Ok(Switched::Return)
+                } else if self.scheduler.current == entering {
+                    self.switch_task(frame, Resume::Finished, entering)
+                } else {
+                    self.finish_task(entering)
+                };
+
+                match self.resume_switched(switched, entering) {
+                    Ok(Some(next)) => {
+                        frame = next;
+                        continue;
+                    }
+                    Ok(None) => return Ok(()),
+                    Err(e) => return Err(e),
+                }
+            }
+
+            let OpCodeMetadata {
+                line,
+                column,
+                opcode: op,
+            } = bytecode[frame.ip];
+
+            if self.debugger.is_some() {
+                self.debug(&frame, line, column);
+            }
+            if self.coverage.is_some() {
+                self.cover(&frame, line, column);
+            }
+            if self.profile.is_some() {
+                self.sample(&frame);
+            }
+            if self.usage.is_some() {
+                if let Err(e) = self.count_instruction() {
+                    match self.rescue(frame, e, line, column, base, entering) {
+                        Ok(rescued) => frame = rescued,
+                        Err(e) => return Err(e),
+                    }
+                    frame.ip += 1;
+                    continue;
+                }
+            }
             self.debug_stack(&op);
 
             let res = match op {
                 OpCode::Try(offset) => {
-                    try_stack.push(offset);
+                    frame.try_stack.push((offset, self.stack.len()));
                     Ok(())
                 }
 
                 OpCode::EndTry => {
-                    try_stack.pop();
+                    frame.try_stack.pop();
                     Ok(())
                 }
 
                 OpCode::Jmp(offset) => {
-                    ip = offset;
+                    frame.ip = offset;
                     continue;
                 }
 
                 OpCode::Jmf(offset) => {
                     if !self.pop().to_bool() {
-                        ip = offset;
+                        frame.ip = offset;
                         continue;
                     }
                     Ok(())
                 }
 
-                OpCode::TCall(arity) => {
-                    self.valid_tail_call(arity, bytecode)?;
-                    ip = 0;
+                // the upvalues come from the `Closure` that created the function, so the index
+                // of a loaded program can't be checked before it runs
+                OpCode::LoadUpval(index) => {
+                    // SAFETY: the upvalues are kept alive like the code of the frame
+                    let upvalues = unsafe { &*frame.upvalues };
+                    match upvalues.get(index) {
+                        Some(value) => {
+                            self.push(value.clone());
+                            Ok(())
+                        }
+                        None => raise!(ValueError, "Invalid upvalue {}", index),
+                    }
+                }
+
+                OpCode::Call(arity) | OpCode::TCall(arity) => {
+                    let profiling = self.profile.is_some();
+                    if profiling {
+                        self.native_callers.push((self.frames.len(), &frame));
+                    }
+                    let callee = match op {
+                        OpCode::TCall(_) => self.tail_call(arity),
+                        _ => self.prepare_call(arity),
+                    };
+                    if profiling {
+                        self.native_callers.pop();
+                    }
+
+                    match callee {
+                        Ok(Callee::Enter(fun)) => match self
+                            .check_deadline()
+                            .and_then(|()| self.check_limits(self.frames.len() + self.depth + 1))
+                            .and_then(|()| self.check_stack())
+                        {
+                            Ok(()) => {
+                                self.used_locals += 1;
+                                self.frames.push(mem::replace(&mut frame, Frame::call(fun)));
+                                continue;
+                            }
+                            Err(e) => Err(e),
+                        },
+                        Ok(Callee::Replace(fun)) => match self
+                            .check_deadline()
+                            .and_then(|()| self.check_limits(self.frames.len() + self.depth))
+                        {
+                            Ok(()) => {
+                                // the callee reuses this frame, so the current locals can be
+                                // discarded
+                                self.used_locals -= frame.locals;
+                                frame = Frame::call(fun);
+                                continue;
+                            }
+                            Err(e) => Err(e),
+                        },
+                        Ok(Callee::Done) => match self.scheduler.suspend.take() {
+                            Some(Resume::Host(value)) => {
+                                // `yield` left a placeholder too
+                                self.pop();
+                                self.suspend_to_host(frame, base, entering, value);
+                                return Ok(());
+                            }
+                            Some(resume) => {
+                                // the native function that suspended the task left a placeholder
+                                self.pop();
+                                let switched = self.switch_task(frame, resume, entering);
+
+                                match self.resume_switched(switched, entering) {
+                                    Ok(Some(next)) => {
+                                        frame = next;
+                                        continue;
+                                    }
+                                    Ok(None) => return Ok(()),
+                                    Err(e) => return Err(e),
+                                }
+                            }
+                            None => Ok(()),
+                        },
+                        Err(e) => Err(e),
+                    }
+                }
+
+                OpCode::Generator if self.in_generator(base) => Ok(()),
+                OpCode::Generator => {
+                    let generator = self.generator(&mut frame);
+                    self.push(generator);
                     continue;
                 }
 
-                _ => self.run_op(op, &mut frame_locals),
+                // generators run in their own dispatch loop, which returns to `Gen.next`
+                OpCode::Yield => {
+                    self.yielded = Some(frame);
+                    return Ok(());
+                }
+
+                // the most common instructions, handled here so they take a single jump, since
+                // the opcodes matched by `run_op` are dispatched again
+                OpCode::Push(idx) => {
+                    let value = self.constant(idx).clone();
+                    self.push(value);
+                    Ok(())
+                }
+                OpCode::Pop => {
+                    self.pop();
+                    Ok(())
+                }
+                OpCode::Load(offset) => {
+                    let value = self.locals[offset + self.used_locals - frame.locals].clone();
+                    self.push(value);
+                    Ok(())
+                }
+                OpCode::Save(offset) => {
+                    let value = self.pop();
+                    self.grow_locals(1);
+
+                    self.locals[offset + (self.used_locals - frame.locals)] = value;
+                    self.used_locals += 1;
+                    frame.locals += 1;
+                    Ok(())
+                }
+                OpCode::Drop(_) if frame.locals == 0 => {
+                    raise!(ValueError, "Dropped a local that wasn't saved")
+                }
+                OpCode::Drop(_) => {
+                    frame.locals -= 1;
+                    self.used_locals -= 1;
+                    Ok(())
+                }
+                OpCode::RevN(n) => {
+                    self.stack.reverse_last(n);
+                    Ok(())
+                }
+                OpCode::Loag(name) => match self.get_global(name) {
+                    Some(value) => {
+                        self.push(value);
+                        Ok(())
+                    }
+                    None => raise!(NameError, "Undefined variable '{}'", name),
+                },
+                OpCode::Add => self.binop("(+)", i64::checked_add, |a, b| a + b),
+                OpCode::Sub => self.binop("(-)", i64::checked_sub, |a, b| a - b),
+                OpCode::Mul => self.binop("(*)", i64::checked_mul, |a, b| a * b),
+                OpCode::Eq => self.binop("(==)", |x, y| Some(x == y), |a, b| Ok(a == b)),
+                OpCode::Less => {
+                    self.binop("(<)", |x, y| Some(x < y), |a, b| Ok(a.ord_cmp(&b)?.is_lt()))
+                }
+
+                _ => self.run_op(op, &mut frame.locals),
             };
 
             if let Err(e) = res {
-                if try_stack.is_empty() {
-                    return Err(e);
+                match self.rescue(frame, e, line, column, base, entering) {
+                    Ok(rescued) => frame = rescued,
+                    Err(e) => return Err(e),
                 }
+            }
 
-                let try_ip = try_stack.pop().unwrap();
-                self.push(e.err.into());
-                ip = try_ip;
+            frame.ip += 1;
+        }
+    }
+
+    /// Continues the dispatch loop after switching tasks, returning the frame that runs next, or
+    /// `None` if the loop returns
+    fn resume_switched(
+        &mut self,
+        switched: InterpretResult<Switched>,
+        entering: usize,
+    ) -> InterpretResult<Option<Frame>> {
+        match switched {
+            Ok(Switched::Run { mut frame, advance }) => {
+                if advance {
+                    frame.ip += 1;
+                }
+                Ok(Some(frame))
+            }
+            Ok(Switched::Raise { frame, error }) => {
+                let OpCodeMetadata { line, column, .. } = unsafe { &*frame.bytecode }[frame.ip];
+                self.rescue(frame, error, line, column, 0, entering)
+                    .map(|mut frame| {
+                        frame.ip += 1;
+                        Some(frame)
+                    })
+            }
+            Ok(Switched::Return) => Ok(None),
+            // the task that was starting failed
+            Err(e) => {
+                if let Some(frame) = self.fail_task(entering) {
+                    self.unwind(frame, 0);
+                }
+                Err(e)
+            }
+        }
+    }
+
+    /// Finds the `try` block that rescues `e`, raised at `line` and `column` while running
+    /// `frame`, returning the frame of the block, the frames that don't rescue it are dropped
+    ///
+    /// If no frame above `base` rescues it the error is returned, the errors that aren't rescued
+    /// by a task end the whole program
+    fn rescue(
+        &mut self,
+        mut frame: Frame,
+        mut e: error::InterpretError,
+        line: usize,
+        column: usize,
+        base: usize,
+        entering: usize,
+    ) -> InterpretResult<Frame> {
+        e.locate(line, column);
+
+        // timeouts and limits can't be rescued, otherwise the script could just ignore them
+        if !self.deadline_expired() && !self.limit_exceeded() {
+            loop {
+                if let Some((try_ip, height)) = frame.try_stack.pop() {
+                    // drops the values pushed inside the block, like the arguments of a call
+                    // that failed
+                    while self.stack.len() > height {
+                        self.pop();
+                    }
+                    self.push(e.rescued());
+                    self.rescued = Some(e);
+                    frame.ip = try_ip;
+                    return Ok(frame);
+                }
+
+                if self.frames.len() == base {
+                    break;
+                }
+
+                self.used_locals -= frame.locals + 1;
+                frame = self.frames.pop().unwrap();
             }
+        }
 
-            ip += 1;
+        self.unwind(frame, base);
+        if let Some(frame) = self.fail_task(entering) {
+            self.unwind(frame, base);
         }
+        Err(e)
+    }
 
-        self.used_locals -= frame_locals;
+    /// Drops `frame` and the frames above `base`, along with their locals
+    fn unwind(&mut self, frame: Frame, base: usize) {
+        self.used_locals -= frame.locals;
 
-        Ok(())
+        while self.frames.len() > base {
+            let frame = self.frames.pop().unwrap();
+            self.used_locals -= frame.locals + 1;
+        }
     }
 
     #[inline(always)]
@@ -187,14 +954,6 @@ impl VirtualMachine {
             OpCode::Nop => (),
 
             // Stack manipulation
-            OpCode::Push(value) => {
-                let value = self.constants[value].clone();
-                self.push(value);
-            }
-            OpCode::Pop => {
-                self.pop();
-            }
-
             OpCode::Dup => {
                 let value = self.pop();
                 self.push(value.clone());
@@ -214,43 +973,74 @@ impl VirtualMachine {
                 self.push(a);
             }
 
-            OpCode::RevN(n) => {
-                let mut vec = vec![];
+            // function calls
+            OpCode::Closure(n) => {
+                let fun: GcRef<Fn> = self.pop().get()?;
+
+                let mut upvalues = Vec::with_capacity(n);
                 for _ in 0..n {
-                    vec.push(self.pop());
-                }
-                for elem in vec.into_iter() {
-                    self.push(elem);
+                    upvalues.push(self.pop());
                 }
+                upvalues.reverse();
+
+                let mut closure = (*fun).clone();
+                closure.upvalues = upvalues;
+                self.push(Value::Fn(GcRef::new(closure)));
             }
 
-            // function calls
-            OpCode::Call(arity) => self.call(arity)?,
+            OpCode::Overload => {
+                let stub: GcRef<Fn> = self.pop().get()?;
+                let more: GcRef<Fn> = self.pop().get()?;
+                self.push(Value::Fn(GcRef::new((*stub).clone().overloaded(more))));
+            }
 
-            // mathematical operators
-            OpCode::Add => self.binop(|a, b| a + b)?,
-            OpCode::Sub => self.binop(|a, b| a - b)?,
-            OpCode::Mul => self.binop(|a, b| a * b)?,
-            OpCode::Div => self.binop(|a, b| a / b)?,
-            OpCode::Rem => self.binop(|a, b| a % b)?,
+            // mathematical operators, the common ones are in the dispatch loop
+            OpCode::Div => self.binop("(/)", |x, y| Some(x as f64 / y as f64), |a, b| a / b)?,
+            OpCode::Rem => self.binop("(%)", i64::checked_rem, |a, b| a % b)?,
+
+            // the operators specialized by the compiler, which skip looking for operator methods
+            OpCode::AddNum => {
+                let sum = match (self.stack.peek(1), self.stack.peek(0)) {
+                    (Some(Value::Num(x)), Some(Value::Num(y))) => Some(x + y),
+                    (Some(Value::Int(x)), Some(Value::Num(y))) => Some(*x as f64 + y),
+                    (Some(Value::Num(x)), Some(Value::Int(y))) => Some(x + *y as f64),
+                    _ => None,
+                };
 
-            // bitwise operators
-            OpCode::BitAnd => self.binop(|a, b| a & b)?,
-            OpCode::BitOr => self.binop(|a, b| a | b)?,
-            OpCode::Xor => self.binop(|a, b| a ^ b)?,
-            OpCode::Shl => self.binop(|a, b| a << b)?,
-            OpCode::Shr => self.binop(|a, b| a >> b)?,
-
-            // comparison operators
-            OpCode::Eq => self.binop(|a, b| Ok(a == b))?,
-            OpCode::Less => {
-                let (a, b) = self.pop_two();
-                self.push(a.ord_cmp(&b)?.is_lt().into());
-            }
-            OpCode::LessEq => {
-                let (a, b) = self.pop_two();
-                self.push(a.ord_cmp(&b)?.is_le().into());
+                match sum {
+                    Some(sum) => {
+                        // SAFETY: numbers don't need to be dropped
+                        unsafe { self.stack.set_len(self.stack.len() - 2) };
+                        self.push(Value::Num(sum));
+                    }
+                    // integers may overflow, and the inferred types may be wrong
+                    None => self.binop("(+)", i64::checked_add, |a, b| a + b)?,
+                }
             }
+            OpCode::ConcatStr => match self.pop_two() {
+                (Value::Str(a), Value::Str(b)) => {
+                    self.push(Value::Str(GcRef::new(String::clone(&a) + &b)))
+                }
+                (a, b) => {
+                    self.push(a);
+                    self.push(b);
+                    self.binop("(+)", i64::checked_add, |a, b| a + b)?
+                }
+            },
+
+            // bitwise operators
+            OpCode::BitAnd => self.binop("(&&&)", |x, y| Some(x & y), |a, b| a & b)?,
+            OpCode::BitOr => self.binop("(|||)", |x, y| Some(x | y), |a, b| a | b)?,
+            OpCode::Xor => self.binop("(^^^)", |x, y| Some(x ^ y), |a, b| a ^ b)?,
+            OpCode::Shl => self.binop("(<<<)", |_, _| None::<i64>, |a, b| a << b)?,
+            OpCode::Shr => self.binop("(>>>)", |_, _| None::<i64>, |a, b| a >> b)?,
+
+            // comparison operators, `Eq` and `Less` are in the dispatch loop
+            OpCode::LessEq => self.binop(
+                "(<=)",
+                |x, y| Some(x <= y),
+                |a, b| Ok(a.ord_cmp(&b)?.is_le()),
+            )?,
 
             // unary operators
             OpCode::Not => {
@@ -259,7 +1049,7 @@ impl VirtualMachine {
             }
             OpCode::Len => {
                 let value = self.pop();
-                self.push(Value::Num(value.len() as f64));
+                self.push(Value::Int(value.len() as i64));
             }
             OpCode::Neg => {
                 let value = self.pop();
@@ -267,37 +1057,16 @@ impl VirtualMachine {
             }
 
             // locals manipulation
-            OpCode::Load(offset) => {
-                let value = self.locals[offset + self.used_locals - *frame_locals].clone();
-                self.push(value);
-            }
-            OpCode::Save(offset) => {
-                let value = self.pop();
-
-                if self.used_locals + 1 >= self.locals.capacity() {
-                    let cap = self.locals.capacity();
-                    self.locals.resize(cap, NIL);
-                }
-
-                self.locals[offset + (self.used_locals - *frame_locals)] = value;
-                self.used_locals += 1;
-                *frame_locals += 1;
-            }
-            OpCode::Drop(_) => {
-                *frame_locals -= 1;
-                self.used_locals -= 1;
+            OpCode::Reserve(n) => {
+                self.grow_locals(n);
+                self.used_locals += n;
+                *frame_locals += n;
             }
 
             // globals manipulation
-            OpCode::Loag(name) => {
-                let value = match self.get_global(name) {
-                    Some(value) => value,
-                    None => raise!(NameError, "Undefined variable '{}'", name)?,
-                };
-                self.push(value);
-            }
             OpCode::Savg(name) => {
                 let value = self.pop();
+                // the builtins can be shadowed, only the globals of the program are checked
                 if self.globals.get(&name).is_some() {
                     raise!(NameError, "Tried to reassign global variable '{}'", name)?;
                 }
@@ -322,17 +1091,95 @@ impl VirtualMachine {
             }
 
             OpCode::Ref(method) => {
-                let ty: GcRef<YexModule> = self.pop().get()?;
+                // the fields of structs are accessed like the methods of types
+                let value = match self.pop() {
+                    Value::Tagged(tagged) if tagged.0.struct_fields().is_some() => {
+                        let (ty, _, tup) = &*tagged;
+                        ty.field(tup, method)?
+                    }
+                    other => {
+                        let ty: GcRef<YexModule> = other.get()?;
+                        match ty.fields.get(&method) {
+                            Some(value) => value,
+                            None if ty.fields.get(&YexModule::private(method)).is_some() => {
+                                return raise!(
+                                    FieldError,
+                                    "'{}' is private to '{}'",
+                                    method,
+                                    ty.name
+                                );
+                            }
+                            None => {
+                                return raise!(
+                                    FieldError,
+                                    "Undefined method '{}' for type '{}'",
+                                    method,
+                                    ty.name
+                                )
+                            }
+                        }
+                    }
+                };
 
-                let method = ty.fields.get(&method).ok_or(raise_err!(
-                    FieldError,
-                    "Undefined method '{}' for type '{}'",
-                    method,
-                    ty.name
-                ))?;
+                self.push(value);
+            }
 
+            OpCode::Dispatch(method) => {
+                let trait_: GcRef<YexModule> = self.pop().get()?;
+                let method = trait_.dispatch(self.pop_last(), method)?;
                 self.push(method);
             }
+            OpCode::Impl => {
+                let methods: GcRef<YexModule> = self.pop().get()?;
+                let ty: GcRef<YexModule> = self.pop().get()?;
+                let mut trait_: GcRef<YexModule> = self.pop().get()?;
+
+                // SAFETY: the trait fields are only borrowed while a method is being resolved,
+                // never while the vm runs code
+                unsafe { trait_.mut_ref().implement(ty.name, &methods)? };
+            }
+
+            OpCode::Savm(path) => {
+                let module: GcRef<YexModule> = self.pop().get()?;
+                let mut names = path.as_str().split('.').map(Symbol::from);
+                let root = names.next().unwrap();
+                let nested: Vec<_> = names.collect();
+
+                let value = YexModule::nest(self.get_global(root), root, &nested, &module)?;
+                self.set_global(root, value);
+            }
+
+            OpCode::Struct => {
+                let ty: GcRef<YexModule> = self.pop().get()?;
+                let fields: Dict = self.pop().get()?;
+                self.push(YexModule::instantiate(&ty, &fields)?);
+            }
+            OpCode::Update => {
+                let fields: Dict = self.pop().get()?;
+
+                let value = match self.pop() {
+                    Value::Tagged(tagged) if tagged.0.struct_fields().is_some() => {
+                        let (ty, tag, tup) = &*tagged;
+                        let tup = ty.update(tup, &fields)?;
+                        Value::tagged(ty.clone(), *tag, tup)
+                    }
+                    Value::Dict(mut dict) => {
+                        for (key, value) in fields.iter() {
+                            dict = dict.insert(key.clone(), value.clone())?;
+                        }
+                        dict.into()
+                    }
+                    other => {
+                        return raise!(
+                            TypeError,
+                            "Can't update '{}', expected a struct or a dictionary",
+                            other
+                        )
+                    }
+                };
+
+                self.push(value);
+            }
 
             OpCode::Tup(len) => {
                 let mut tup = vec![];
@@ -342,21 +1189,65 @@ impl VirtualMachine {
                 self.push(tup.into());
             }
 
+            OpCode::Range(inclusive) => {
+                let end: i64 = self.pop().get()?;
+                let start: i64 = self.pop().get()?;
+
+                let range = if inclusive {
+                    Range::inclusive(start, end)
+                } else {
+                    Range::new(start, end)
+                };
+                self.push(range.into());
+            }
+            OpCode::Import(name) => {
+                let module = self.import(name)?;
+                self.push(module);
+            }
+            OpCode::Array(len) => {
+                let mut xs = Vec::with_capacity(len);
+                for _ in 0..len {
+                    xs.push(self.pop());
+                }
+
+                self.push(xs.into_iter().rev().collect::<Array>().into());
+            }
+
+            OpCode::Dict(len) => {
+                let mut entries = Vec::with_capacity(len);
+                for _ in 0..len {
+                    let value = self.pop();
+                    let key = self.pop();
+                    entries.push((key, value));
+                }
+                entries.reverse();
+
+                self.push(Dict::from_entries(entries)?.into());
+            }
+
             OpCode::TupGet(index) => {
                 let tup: Tuple = self.pop().get()?;
-                let elem = tup.0.get(index).unwrap(); // this SHOULD be unreachable
-                self.push(elem.clone());
+                // patterns check the length first, but `t.0` can be out of bounds
+                match tup.0.get(index) {
+                    Some(elem) => self.push(elem.clone()),
+                    None => raise!(
+                        ValueError,
+                        "Index {} is out of bounds for a tuple of length {}",
+                        index,
+                        tup.len()
+                    )?,
+                }
             }
 
             OpCode::Tag(tag) => {
                 let module: GcRef<YexModule> = self.pop().get()?;
                 let tup: Tuple = self.pop().get()?;
 
-                self.push(Value::Tagged(module, tag, tup))
+                self.push(Value::tagged(module, tag, tup))
             }
 
             OpCode::TagOf => match self.pop() {
-                Value::Tagged(_, tag, _) => self.push(tag.into()),
+                Value::Tagged(tagged) => self.push(tagged.1.into()),
                 _ => self.push(NIL),
             },
 
@@ -365,39 +1256,74 @@ impl VirtualMachine {
                 self.push(tup.into());
             }
 
-            // these opcodes are handled by the run function, since they can manipulate the ip
-            OpCode::Try(..)
+            // the value is shown as it's written, so the strings are quoted
+            OpCode::MatchFail => {
+                return raise!(MatchError, "No pattern matched {}", pretty(&self.pop()));
+            }
+
+            // these opcodes are handled by the dispatch loop, since they manipulate the ip or read
+            // the current frame, or because they're the most common ones
+            OpCode::Push(_)
+            | OpCode::Pop
+            | OpCode::Load(_)
+            | OpCode::Save(_)
+            | OpCode::Drop(_)
+            | OpCode::RevN(_)
+            | OpCode::Loag(_)
+            | OpCode::Add
+            | OpCode::Sub
+            | OpCode::Mul
+            | OpCode::Eq
+            | OpCode::Less
+            | OpCode::Try(..)
             | OpCode::EndTry
             | OpCode::Jmp(..)
             | OpCode::Jmf(..)
-            | OpCode::TCall(..) => unreachable!(),
+            | OpCode::Call(..)
+            | OpCode::TCall(..)
+            | OpCode::LoadUpval(..)
+            | OpCode::Generator
+            | OpCode::Yield => unreachable!(),
         };
 
         Ok(())
     }
 
-    #[cfg(debug_assertions)]
+    #[cfg(all(debug_assertions, feature = "std"))]
     /// Debug the values on the stack and in the bytecode
     pub fn debug_stack(&self, instruction: &OpCode) {
         eprintln!("Stack: {:#?} ({instruction:?})", self.stack);
     }
 
-    #[cfg(not(debug_assertions))]
+    #[cfg(not(all(debug_assertions, feature = "std")))]
     /// Debug the values on the stack and in the bytecode
     pub fn debug_stack(&self, _: &OpCode) {}
 
+    /// Makes room for `n` more locals, the locals of tasks start small
+    #[inline(always)]
+    fn grow_locals(&mut self, n: usize) {
+        if self.used_locals + n >= self.locals.len() {
+            let len = (self.used_locals + n + 1).max(self.locals.len() * 2);
+            self.locals.resize(len, NIL);
+        }
+    }
+
     #[inline(always)]
-    fn call_args(&mut self, arity: usize, fun: &Fn) -> Option<FnArgs> {
-        if fun.is_bytecode() && fun.args.is_empty() {
-            return None;
+    fn call_args(&mut self, arity: usize, fun: &Fn) -> InterpretResult<Option<FnArgs>> {
+        // bytecode functions take their arguments from the stack, where the applied ones go above
+        // the new ones, since they come first
+        if fun.is_bytecode() {
+            for arg in fun.args.iter() {
+                self.push(arg.clone());
+            }
+            return Ok(None);
         }
 
+        check_applied(arity + fun.args.len())?;
         let mut args = stackvec![];
 
-        let mut i = 1;
-        for _ in 0..arity {
+        for i in 1..=arity {
             unsafe { args.insert_at(arity - i, self.pop()) };
-            i += 1;
         }
 
         unsafe { args.set_len(arity) };
@@ -406,14 +1332,49 @@ impl VirtualMachine {
             args.push(arg.clone());
         }
 
-        Some(args)
+        Ok(Some(args))
     }
 
+    /// Calls the function on the top of the stack with the `arity` arguments below it, pushing
+    /// the result
     #[inline(always)]
     pub(crate) fn call(&mut self, arity: usize) -> InterpretResult<()> {
-        let fun: GcRef<Fn> = self.pop().get()?;
+        match self.prepare_call(arity)? {
+            Callee::Enter(fun) => self.call_bytecode(fun),
+            _ => Ok(()),
+        }
+    }
+
+    /// Prepares a call to the function on the top of the stack, native functions and partial
+    /// applications are done right away, while bytecode functions are returned with their
+    /// arguments on the stack, so they can run on a new frame
+    #[inline(always)]
+    fn prepare_call(&mut self, mut arity: usize) -> InterpretResult<Callee> {
+        let mut fun: GcRef<Fn> = self.pop().get()?;
+
+        // functions with default arguments dispatch to the stub that takes every argument passed
+        while arity > fun.arity {
+            match fun.overload() {
+                Some(more) => fun = GcRef::new(more),
+                None => break,
+            }
+        }
+
+        // the rest argument receives the arguments after the fixed ones, even if there are none
+        if fun.variadic && arity + 1 >= fun.arity {
+            self.collect_rest(arity, fun.arity - 1);
+            arity = fun.arity;
+        }
 
+        // calling a function with less arguments than it takes partially applies it
         if arity < fun.arity {
+            // the arguments are dropped, so a `try` that rescues the error gets a clean stack
+            if let Err(e) = check_applied(arity + fun.args.len()) {
+                for _ in 0..arity {
+                    self.pop();
+                }
+                return Err(e);
+            }
             let mut args = stackvec![];
 
             for _ in 0..arity {
@@ -421,10 +1382,10 @@ impl VirtualMachine {
             }
 
             self.push(Value::Fn(GcRef::new(fun.apply(args))));
-            return Ok(());
+            return Ok(Callee::Done);
         }
 
-        let args = self.call_args(arity, &fun);
+        let args = self.call_args(arity, &fun)?;
 
         if arity > fun.arity {
             raise!(
@@ -435,53 +1396,80 @@ impl VirtualMachine {
         }
 
         match &*fun.body {
-            FnKind::Bytecode(bytecode) => self.call_bytecode(bytecode, args),
-            FnKind::Native(ptr) => self.call_native(*ptr, args),
+            FnKind::Bytecode(_) => Ok(Callee::Enter(fun)),
+            FnKind::Native(ptr) => {
+                let name = fun.info.as_ref().map(|info| info.name);
+                self.call_native(*ptr, args, name)?;
+                Ok(Callee::Done)
+            }
         }
     }
 
-    #[inline(always)]
-    fn call_bytecode(
-        &mut self,
-        bytecode: BytecodeRef,
-        args: Option<FnArgs>,
-    ) -> InterpretResult<()> {
-        self.used_locals += 1;
+    /// Replaces the arguments after the first `fixed` ones, of the `arity` on the stack top, by a
+    /// list of them
+    fn collect_rest(&mut self, arity: usize, fixed: usize) {
+        let args: Vec<Value> = (0..fixed).map(|_| self.pop()).collect();
+        let rest: Vec<Value> = (fixed..arity).map(|_| self.pop()).collect();
+        let rest: List = rest.into_iter().rev().collect();
 
-        args.map(|stack| {
-            for arg in stack {
-                self.push(arg)
-            }
-        });
+        self.push(rest.into());
+        for arg in args.into_iter().rev() {
+            self.push(arg);
+        }
+    }
 
-        self.run(bytecode)?;
+    /// Runs a bytecode function in a new dispatch loop, used when native functions call back
+    /// into the vm
+    #[inline(always)]
+    fn call_bytecode(&mut self, fun: GcRef<Fn>) -> InterpretResult<()> {
+        self.used_locals += 1;
+        let res = self.run_frame(Frame::call(fun));
         self.used_locals -= 1;
-        Ok(())
+        res
     }
 
+    /// Calls a native function, the errors it raises itself are attributed to `name`
     #[inline(always)]
-    fn call_native(&mut self, fp: NativeFn, args: Option<FnArgs>) -> InterpretResult<()> {
-        let args = args.unwrap_or_else(FnArgs::new).reverse().into();
+    fn call_native(
+        &mut self,
+        fp: NativeFn,
+        args: Option<FnArgs>,
+        name: Option<Symbol>,
+    ) -> InterpretResult<()> {
+        let args = args.unwrap_or_default().reverse().into();
+
+        self.natives += 1;
         let result = fp(self, args);
+        self.natives -= 1;
+
+        // errors raised by the functions it calls were already located where they happened
+        let result = result.map_err(|mut e| {
+            if !e.is_located() && e.builtin.is_none() {
+                e.builtin = name;
+            }
+            e
+        });
         self.try_push(result)
     }
 
+    /// Prepares a call in tail position, the callee takes over the current frame if it takes
+    /// exactly the arguments passed, otherwise it's called like any other function
     #[inline]
-    fn valid_tail_call(&mut self, arity: usize, frame: BytecodeRef) -> InterpretResult<()> {
+    fn tail_call(&mut self, arity: usize) -> InterpretResult<Callee> {
         let fun: GcRef<Fn> = self.pop().get()?;
 
-        match &*fun.body {
-            FnKind::Bytecode(_) if fun.arity != arity => {
-                raise!(TailCallError, "")
-            }
-            FnKind::Bytecode(bytecode) if bytecode != frame => {
-                raise!(TailCallError, "")
-            }
-            FnKind::Native(_) => {
-                raise!(TailCallError, "")
+        if !fun.is_bytecode() || fun.variadic || arity != fun.arity {
+            self.push(Value::Fn(fun));
+            return self.prepare_call(arity);
+        }
+
+        if let Some(args) = self.call_args(arity, &fun)? {
+            for arg in args {
+                self.push(arg);
             }
-            FnKind::Bytecode(_) => Ok(()),
         }
+
+        Ok(Callee::Replace(fun))
     }
 
     #[track_caller]
@@ -494,14 +1482,42 @@ impl VirtualMachine {
         self.stack.pop()
     }
 
-    fn binop<T, F>(&mut self, f: F) -> InterpretResult<()>
+    /// Applies a binary operator to the two values on the top of the stack, calling the method
+    /// named `op` instead if one of them is a tagged value whose type overloads the operator
+    ///
+    /// `int` is the fast path taken when both values are integers, it returns `None` to fall back
+    /// to `f`, which raises the errors (like overflows)
+    #[inline(always)]
+    fn binop<I, U, T, F>(&mut self, op: &str, int: I, f: F) -> InterpretResult<()>
     where
+        I: FnOnce(i64, i64) -> Option<U>,
+        U: Into<Value>,
         T: Into<Value>,
         F: ops::Fn(Value, Value) -> InterpretResult<T>,
     {
+        if let (Some(Value::Int(x)), Some(Value::Int(y))) = (self.stack.peek(1), self.stack.peek(0))
+        {
+            if let Some(result) = int(*x, *y) {
+                // SAFETY: integers don't need to be dropped
+                unsafe { self.stack.set_len(self.stack.len() - 2) };
+                self.push(result.into());
+                return Ok(());
+            }
+        }
+
         let a = self.pop();
         let b = self.pop();
-        Ok(self.push(f(b, a)?.into()))
+
+        match operator_method(op, &b, &a) {
+            Some(method) => {
+                // the methods take their arguments in the order they appear in the expression
+                self.push(a);
+                self.push(b);
+                self.push(method);
+                self.call(2)
+            }
+            None => Ok(self.push(f(b, a)?.into())),
+        }
     }
 
     fn pop_two(&mut self) -> (Value, Value) {
@@ -515,17 +1531,102 @@ impl VirtualMachine {
     }
 }
 
-impl Default for VirtualMachine {
-    fn default() -> Self {
+impl VirtualMachine {
+    fn with_globals(globals: EnvTable) -> Self {
         const STACK: Stack = StackVec::new();
 
-        let prelude = prelude::prelude();
         Self {
             stack: STACK,
             locals: vec![NIL; u16::MAX.into()],
             used_locals: 0,
             constants: Vec::new(),
-            globals: prelude,
+            shared: Arc::from([]),
+            globals: EnvTable::new(),
+            builtins: globals,
+            registry: None,
+            loader: None,
+            modules: EnvTable::new(),
+            importing: Vec::new(),
+            rescued: None,
+            args: Vec::new(),
+            frames: Vec::new(),
+            depth: 0,
+            natives: 0,
+            scheduler: Scheduler::default(),
+            debugger: None,
+            coverage: None,
+            profile: None,
+            native_callers: Vec::new(),
+            code_modules: BTreeMap::new(),
+            options: VmOptions::new(),
+            usage: None,
+            resumable: None,
+            yielded: None,
+            generators: Vec::new(),
+            #[cfg(feature = "std")]
+            deadline: None,
+            #[cfg(feature = "std")]
+            trace: None,
         }
     }
 }
+
+impl Default for VirtualMachine {
+    fn default() -> Self {
+        Self::with_globals(prelude::prelude())
+    }
+}
+
+/// Checks if `n` arguments fit in a [`FnArgs`]
+/// Returns the method named `op` that overloads an operator, looking at the type of the left
+/// operand first, so `vec * 2` and `2 * vec` both use the `(*)` method of `vec`
+pub(crate) fn operator_method(op: &str, a: &Value, b: &Value) -> Option<Value> {
+    [a, b].into_iter().find_map(|operand| match operand {
+        Value::Tagged(tagged) => tagged.0.fields.get(&Symbol::new(op)),
+        _ => None,
+    })
+}
+
+fn check_applied(n: usize) -> InterpretResult<()> {
+    if n > MAX_APPLIED {
+        raise!(
+            CallError,
+            "Can't apply {} arguments at once, at most {} can be applied to a partially applied or native function",
+            n,
+            MAX_APPLIED
+        )?;
+    }
+    Ok(())
+}
+
+/// Strings up to this length are shared between the constants of every loaded program
+const SHARED_STR_LEN: usize = 64;
+
+/// The key of the constants that [`VirtualMachine::load`] shares between programs
+fn shared_key(value: &Value) -> Option<(bool, &str)> {
+    match value {
+        Value::Sym(sym) => Some((true, sym.as_str())),
+        Value::Str(str) if str.len() <= SHARED_STR_LEN => Some((false, str.as_str())),
+        _ => None,
+    }
+}
+
+/// Patches the constant indexes of `bytecode`, `indexes` maps the old indexes to the new ones
+fn relocate(bytecode: &mut Bytecode, indexes: &[usize]) {
+    for op in bytecode.iter_mut() {
+        if let OpCode::Push(idx) = &mut op.opcode {
+            *idx = indexes[*idx];
+        }
+    }
+}
+
+/// Relocates the functions stored in a constant, `seen` keeps track of the function bodies that
+/// were already patched, since the same body can be reachable from more than one constant
+fn relocate_value(value: &Value, indexes: &[usize], seen: &mut BTreeSet<usize>) {
+    walk_code(value, seen, &mut |fun, _| {
+        let mut body = fun.body.clone();
+        if let FnKind::Bytecode(bt) = unsafe { body.mut_ref() } {
+            relocate(bt, indexes);
+        }
+    });
+}
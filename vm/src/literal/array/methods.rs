@@ -0,0 +1,81 @@
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
+
+use crate::{
+    error::InterpretResult,
+    literal::{list::Items, nil, TryGet, Value},
+    raise, List, VirtualMachine,
+};
+
+use super::Array;
+
+pub fn new(_: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Array::new().into())
+}
+
+pub fn get(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let xs: Array = args[1].get()?;
+    let index: usize = args[0].get()?;
+
+    Ok(xs.get(index).unwrap_or_else(nil))
+}
+
+pub fn set(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let xs: Array = args[2].get()?;
+    let index: usize = args[0].get()?;
+
+    match xs.set(index, args[1].clone()) {
+        Some(xs) => Ok(xs.into()),
+        None => raise!(
+            ValueError,
+            "Index {} is out of bounds for an array of length {}",
+            index,
+            xs.len()
+        ),
+    }
+}
+
+pub fn push(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let xs: Array = args[1].get()?;
+    Ok(xs.push(args[0].clone()).into())
+}
+
+pub fn concat(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let xs: Array = args[0].get()?;
+    let ys: Array = args[1].get()?;
+    Ok(xs.concat(&ys).into())
+}
+
+pub fn len(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let xs: Array = args[0].get()?;
+    Ok((xs.len() as i64).into())
+}
+
+pub fn from_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Items::new(&args[0])?.collect::<Array>().into())
+}
+
+pub fn to_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let xs: Array = args[0].get()?;
+    let items: Vec<_> = xs.iter().collect();
+    Ok(items.into_iter().rev().collect::<List>().into())
+}
+
+pub fn show(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let xs: Array = args[0].get()?;
+
+    let mut s = String::from("#[");
+
+    for x in xs.iter() {
+        write!(s, "{}, ", super::super::show(vm, vec![x])?).unwrap();
+    }
+
+    if !xs.is_empty() {
+        s.pop();
+        s.pop();
+    }
+
+    s.push(']');
+
+    Ok(s.into())
+}
@@ -0,0 +1,320 @@
+pub mod methods;
+
+use alloc::{string::ToString, vec::Vec};
+use core::fmt;
+
+use crate::{
+    gc::{GcRef, Trace, Tracer},
+    Value,
+};
+
+const BITS: usize = 5;
+const WIDTH: usize = 1 << BITS;
+const MASK: usize = WIDTH - 1;
+
+#[derive(Clone)]
+enum Node {
+    Branch(Vec<GcRef<Node>>),
+    Leaf(Vec<Value>),
+}
+
+impl Trace for Node {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        match self {
+            Node::Branch(children) => children.iter().for_each(|child| tracer.visit(child)),
+            Node::Leaf(values) => values.iter().for_each(|value| value.trace(tracer)),
+        }
+    }
+}
+
+impl Node {
+    fn children(&self) -> &[GcRef<Node>] {
+        match self {
+            Node::Branch(children) => children,
+            Node::Leaf(_) => unreachable!(),
+        }
+    }
+
+    fn values(&self) -> &[Value] {
+        match self {
+            Node::Leaf(values) => values,
+            Node::Branch(_) => unreachable!(),
+        }
+    }
+
+    /// Wraps `node` in branches until it reaches `level`
+    fn path(level: usize, node: GcRef<Node>) -> GcRef<Node> {
+        if level == 0 {
+            node
+        } else {
+            GcRef::new(Node::Branch(vec![Self::path(level - BITS, node)]))
+        }
+    }
+}
+
+#[derive(Clone)]
+struct Trie {
+    len: usize,
+    /// How many bits of an index the root consumes
+    shift: usize,
+    root: GcRef<Node>,
+    /// The last elements, which aren't in the tree yet, so pushing doesn't copy a path of it
+    tail: Vec<Value>,
+}
+
+impl Trace for Trie {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        tracer.visit(&self.root);
+        self.tail.iter().for_each(|value| value.trace(tracer));
+    }
+}
+
+impl Default for Trie {
+    fn default() -> Self {
+        Self {
+            len: 0,
+            shift: BITS,
+            root: GcRef::new(Node::Branch(vec![])),
+            tail: vec![],
+        }
+    }
+}
+
+impl Trie {
+    /// The index of the first element in the tail
+    fn tail_offset(&self) -> usize {
+        if self.len < WIDTH {
+            0
+        } else {
+            ((self.len - 1) >> BITS) << BITS
+        }
+    }
+
+    fn get(&self, index: usize) -> &Value {
+        if index >= self.tail_offset() {
+            return &self.tail[index - self.tail_offset()];
+        }
+
+        let mut node = &self.root;
+        let mut level = self.shift;
+        while level > 0 {
+            node = &node.children()[(index >> level) & MASK];
+            level -= BITS;
+        }
+        &node.values()[index & MASK]
+    }
+
+    fn push(&mut self, value: Value) {
+        if self.len - self.tail_offset() < WIDTH {
+            self.tail.push(value);
+            self.len += 1;
+            return;
+        }
+
+        let leaf = GcRef::new(Node::Leaf(core::mem::replace(&mut self.tail, vec![value])));
+
+        // the root is full, so the tree grows a level
+        if (self.len >> BITS) > (1 << self.shift) {
+            let path = Node::path(self.shift, leaf);
+            self.root = GcRef::new(Node::Branch(vec![self.root.clone(), path]));
+            self.shift += BITS;
+        } else {
+            self.root = self.push_leaf(self.shift, &self.root, leaf);
+        }
+
+        self.len += 1;
+    }
+
+    /// Copies the path to the last leaf, adding `leaf` after it
+    fn push_leaf(&self, level: usize, node: &GcRef<Node>, leaf: GcRef<Node>) -> GcRef<Node> {
+        let mut children = node.children().to_vec();
+        let index = ((self.len - 1) >> level) & MASK;
+
+        let child = if level == BITS {
+            leaf
+        } else if let Some(child) = children.get(index) {
+            self.push_leaf(level - BITS, child, leaf)
+        } else {
+            Node::path(level - BITS, leaf)
+        };
+
+        if index < children.len() {
+            children[index] = child;
+        } else {
+            children.push(child);
+        }
+
+        GcRef::new(Node::Branch(children))
+    }
+
+    fn set(&mut self, index: usize, value: Value) {
+        let offset = self.tail_offset();
+        if index >= offset {
+            self.tail[index - offset] = value;
+        } else {
+            self.root = Self::set_in(self.shift, &self.root, index, value);
+        }
+    }
+
+    /// Copies the path to the element at `index`, replacing it by `value`
+    fn set_in(level: usize, node: &GcRef<Node>, index: usize, value: Value) -> GcRef<Node> {
+        let node = match &**node {
+            Node::Leaf(values) => {
+                let mut values = values.clone();
+                values[index & MASK] = value;
+                Node::Leaf(values)
+            }
+            Node::Branch(children) => {
+                let mut children = children.clone();
+                let slot = (index >> level) & MASK;
+                children[slot] = Self::set_in(level - BITS, &children[slot], index, value);
+                Node::Branch(children)
+            }
+        };
+
+        GcRef::new(node)
+    }
+}
+
+/// A yex array, a persistent vector with fast indexing, updating and pushing
+///
+/// The elements are stored in a tree with 32 children per node, so getting or setting an element
+/// only visits a handful of nodes, and the updated array shares everything else with the
+/// original one
+#[derive(Clone)]
+pub struct Array(GcRef<Trie>);
+
+impl Array {
+    /// Creates an empty array
+    pub fn new() -> Self {
+        Self(GcRef::new(Trie::default()))
+    }
+
+    /// Returns the number of elements
+    pub fn len(&self) -> usize {
+        self.0.len
+    }
+
+    /// Checks if the array has no elements
+    pub fn is_empty(&self) -> bool {
+        self.0.len == 0
+    }
+
+    /// Returns the address of the trie, which identifies the array
+    pub(crate) fn addr(&self) -> usize {
+        self.0.addr()
+    }
+
+    /// Returns the element at `index`, if there's one
+    pub fn get(&self, index: usize) -> Option<Value> {
+        (index < self.len()).then(|| self.0.get(index).clone())
+    }
+
+    /// Creates a new array with the element at `index` replaced, returns `None` if the index is
+    /// out of bounds
+    pub fn set(&self, index: usize, value: Value) -> Option<Self> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut trie = (*self.0).clone();
+        trie.set(index, value);
+        Some(Self(GcRef::new(trie)))
+    }
+
+    /// Creates a new array with `value` after the last element
+    pub fn push(&self, value: Value) -> Self {
+        let mut trie = (*self.0).clone();
+        trie.push(value);
+        Self(GcRef::new(trie))
+    }
+
+    /// Creates a new array with the elements of `other` after the elements of `self`
+    pub fn concat(&self, other: &Self) -> Self {
+        let mut trie = (*self.0).clone();
+        for value in other.iter() {
+            trie.push(value);
+        }
+        Self(GcRef::new(trie))
+    }
+
+    /// Returns an iterator over the elements
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            array: self,
+            index: 0,
+        }
+    }
+}
+
+impl Trace for Array {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        tracer.visit(&self.0);
+    }
+}
+
+impl Default for Array {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl FromIterator<Value> for Array {
+    fn from_iter<T: IntoIterator<Item = Value>>(iter: T) -> Self {
+        let mut trie = Trie::default();
+        for value in iter {
+            trie.push(value);
+        }
+        Self(GcRef::new(trie))
+    }
+}
+
+/// Iterates over the elements of an array
+pub struct Iter<'a> {
+    array: &'a Array,
+    index: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = Value;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.array.get(self.index)?;
+        self.index += 1;
+        Some(value)
+    }
+}
+
+impl PartialEq for Array {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| a == b)
+    }
+}
+
+impl fmt::Debug for Array {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl fmt::Display for Array {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let items: Vec<_> = self.iter().map(|x| x.to_string()).collect();
+        write!(f, "#[{}]", items.join(", "))
+    }
+}
+
+#[test]
+fn persistent() {
+    let xs: Array = (0..5000).map(Value::Int).collect();
+    let ys = xs.set(1234, Value::Nil).unwrap().push(Value::Int(5000));
+
+    assert_eq!(xs.len(), 5000);
+    assert_eq!(ys.len(), 5001);
+    assert_eq!(xs.get(1234), Some(Value::Int(1234)));
+    assert_eq!(ys.get(1234), Some(Value::Nil));
+    assert_eq!(ys.get(4999), Some(Value::Int(4999)));
+    assert_eq!(ys.get(5000), Some(Value::Int(5000)));
+    assert_eq!(xs.get(5000), None);
+    assert!(xs.iter().eq((0..5000).map(Value::Int)));
+}
@@ -0,0 +1,141 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    error::InterpretResult,
+    literal::{list::Items, nil, TryGet},
+    raise, raise_err, List, Value, VirtualMachine,
+};
+
+use super::Bytes;
+
+pub fn get(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[1].get()?;
+    let index: usize = args[0].get()?;
+
+    Ok(bytes
+        .0
+        .get(index)
+        .map(|byte| Value::Int(*byte as i64))
+        .unwrap_or_else(nil))
+}
+
+pub fn slice(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[2].get()?;
+    let start: usize = args[0].get()?;
+    let end: usize = args[1].get()?;
+
+    match bytes.0.get(start..end) {
+        Some(slice) => Ok(Bytes::from(slice.to_vec()).into()),
+        None => raise!(
+            ValueError,
+            "Can't slice {}..{} out of {} bytes",
+            start,
+            end,
+            bytes.len()
+        ),
+    }
+}
+
+pub fn len(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[0].get()?;
+    Ok((bytes.len() as i64).into())
+}
+
+pub fn concat(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let left: Bytes = args[0].get()?;
+    let right: Bytes = args[1].get()?;
+
+    let mut bytes = left.0.to_vec();
+    bytes.extend_from_slice(&right.0);
+    Ok(Bytes::from(bytes).into())
+}
+
+pub fn from_str(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let str: String = args[0].get()?;
+    Ok(Bytes::from(str.into_bytes()).into())
+}
+
+pub fn to_str(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[0].get()?;
+
+    match String::from_utf8(bytes.0.to_vec()) {
+        Ok(str) => Ok(str.into()),
+        Err(e) => raise!(
+            ValueError,
+            "Invalid utf-8 at byte {}",
+            e.utf8_error().valid_up_to()
+        ),
+    }
+}
+
+pub fn from_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes = Items::new(&args[0])?
+        .map(|item| match item {
+            Value::Int(n) if (0..=255).contains(&n) => Ok(n as u8),
+            other => raise!(ValueError, "'{}' isn't a byte", other),
+        })
+        .collect::<InterpretResult<Vec<_>>>()?;
+
+    Ok(Bytes::from(bytes).into())
+}
+
+pub fn to_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[0].get()?;
+    Ok(bytes
+        .0
+        .iter()
+        .rev()
+        .map(|byte| Value::Int(*byte as i64))
+        .collect::<List>()
+        .into())
+}
+
+/// Integers are encoded in 8 big endian bytes
+pub fn from_int(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let n: i64 = args[0].get()?;
+    Ok(Bytes::from(n.to_be_bytes().to_vec()).into())
+}
+
+/// Decodes up to 8 big endian bytes, 8 bytes are read as a signed integer
+pub fn to_int(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[0].get()?;
+
+    if bytes.len() > 8 {
+        return raise!(
+            ValueError,
+            "Can't decode {} bytes as an integer, at most 8 fit in one",
+            bytes.len()
+        );
+    }
+
+    let mut buf = [0; 8];
+    buf[8 - bytes.len()..].copy_from_slice(&bytes.0);
+    Ok(Value::Int(i64::from_be_bytes(buf)))
+}
+
+/// Floats are encoded in their 8 big endian IEEE 754 bytes
+pub fn from_num(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let n: f64 = args[0].get()?;
+    Ok(Bytes::from(n.to_be_bytes().to_vec()).into())
+}
+
+pub fn to_num(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[0].get()?;
+
+    let buf: [u8; 8] = bytes.0.as_slice().try_into().map_err(|_| {
+        raise_err!(
+            ValueError,
+            "Can't decode {} bytes as a float, it takes exactly 8",
+            bytes.len()
+        )
+    })?;
+    Ok(Value::Num(f64::from_be_bytes(buf)))
+}
+
+pub fn show(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let bytes: Bytes = args[0].get()?;
+    Ok(bytes.to_string().into())
+}
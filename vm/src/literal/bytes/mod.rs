@@ -0,0 +1,47 @@
+pub mod methods;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::gc::GcRef;
+
+/// A yex byte string, an immutable sequence of bytes, which unlike strings doesn't have to be
+/// valid utf-8
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bytes(pub GcRef<Vec<u8>>);
+
+impl From<Vec<u8>> for Bytes {
+    fn from(bytes: Vec<u8>) -> Self {
+        Bytes(GcRef::new(bytes))
+    }
+}
+
+impl Bytes {
+    /// Returns the number of bytes
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// Checks if there are no bytes
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "b\"")?;
+        for byte in self.0.iter() {
+            match byte {
+                b'"' => write!(f, "\\\"")?,
+                b'\\' => write!(f, "\\\\")?,
+                b'\n' => write!(f, "\\n")?,
+                b'\t' => write!(f, "\\t")?,
+                b'\r' => write!(f, "\\r")?,
+                b' '..=b'~' => write!(f, "{}", *byte as char)?,
+                other => write!(f, "\\x{:02x}", other)?,
+            }
+        }
+        write!(f, "\"")
+    }
+}
@@ -0,0 +1,269 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+#[cfg(feature = "std")]
+use std::{collections::HashMap, hash::Hash};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::dict::Dict,
+    literal::{nil, result, TryGet},
+    raise, Fn, List, Symbol, Tuple, Value, YexModule,
+};
+
+/// Converts a rust value into a yex [`Value`]
+pub trait IntoYex {
+    /// Performs the conversion
+    fn into_yex(self) -> Value;
+}
+
+/// Converts a yex [`Value`] into a rust value, raising a `TypeError` if the value doesn't have the
+/// expected shape
+pub trait FromYex: Sized {
+    /// Performs the conversion
+    fn from_yex(value: &Value) -> InterpretResult<Self>;
+}
+
+macro_rules! impl_primitive {
+    ($($ty:ty),+ $(,)?) => {
+        $(
+            impl IntoYex for $ty {
+                fn into_yex(self) -> Value {
+                    self.into()
+                }
+            }
+
+            impl FromYex for $ty {
+                fn from_yex(value: &Value) -> InterpretResult<Self> {
+                    value.get()
+                }
+            }
+        )+
+    };
+}
+
+impl_primitive!(f64, i64, bool, String, Symbol, List, Tuple, Dict);
+
+impl IntoYex for Value {
+    fn into_yex(self) -> Value {
+        self
+    }
+}
+
+impl FromYex for Value {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        Ok(value.clone())
+    }
+}
+
+impl IntoYex for &str {
+    fn into_yex(self) -> Value {
+        self.to_string().into()
+    }
+}
+
+impl IntoYex for () {
+    fn into_yex(self) -> Value {
+        nil()
+    }
+}
+
+impl FromYex for usize {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        value.get()
+    }
+}
+
+impl FromYex for isize {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        value.get()
+    }
+}
+
+impl FromYex for GcRef<Fn> {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        value.get()
+    }
+}
+
+impl FromYex for GcRef<YexModule> {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        value.get()
+    }
+}
+
+impl<T: IntoYex> IntoYex for Option<T> {
+    fn into_yex(self) -> Value {
+        self.map_or_else(nil, T::into_yex)
+    }
+}
+
+impl<T: FromYex> FromYex for Option<T> {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        match value {
+            Value::Nil => Ok(None),
+            other => T::from_yex(other).map(Some),
+        }
+    }
+}
+
+impl<T: IntoYex> IntoYex for Vec<T> {
+    fn into_yex(self) -> Value {
+        let xs: List = self.into_iter().rev().map(T::into_yex).collect();
+        xs.into()
+    }
+}
+
+impl<T: FromYex> FromYex for Vec<T> {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        let xs: List = value.get()?;
+        xs.iter().map(|x| T::from_yex(&x)).collect()
+    }
+}
+
+/// Results are converted to `Result.ok x` and `Result.fail err`
+impl<T: IntoYex, E: IntoYex> IntoYex for Result<T, E> {
+    fn into_yex(self) -> Value {
+        match self {
+            Ok(x) => result::ok(vec![x.into_yex()]),
+            Err(e) => result::fail(vec![e.into_yex()]),
+        }
+    }
+}
+
+impl<T: FromYex, E: FromYex> FromYex for Result<T, E> {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        if let Value::Tagged(tagged) = value {
+            let (_, tag, tup) = &**tagged;
+            match tag.as_str() {
+                "Result.ok" if tup.len() == 1 => return T::from_yex(&tup.0[0]).map(Ok),
+                "Result.fail" if tup.len() == 1 => return E::from_yex(&tup.0[0]).map(Err),
+                _ => (),
+            }
+        }
+
+        raise!(TypeError, "Expected a result, found '{}'", value)
+    }
+}
+
+/// Maps are converted to a [`Dict`]
+///
+/// # Panics
+/// Panics if a key can't be used as a dictionary key, like a function
+#[cfg(feature = "std")]
+impl<K: IntoYex, V: IntoYex> IntoYex for HashMap<K, V> {
+    fn into_yex(self) -> Value {
+        let entries = self.into_iter().map(|(k, v)| (k.into_yex(), v.into_yex()));
+        Dict::from_entries(entries)
+            .expect("invalid dictionary key")
+            .into()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<K: FromYex + Eq + Hash, V: FromYex> FromYex for HashMap<K, V> {
+    fn from_yex(value: &Value) -> InterpretResult<Self> {
+        let dict: Dict = value.get()?;
+        dict.iter()
+            .map(|(k, v)| Ok((K::from_yex(k)?, V::from_yex(v)?)))
+            .collect()
+    }
+}
+
+macro_rules! impl_tuple {
+    ($($len:literal => ($($name:ident: $idx:tt),+));+ $(;)?) => {
+        $(
+            impl<$($name: IntoYex),+> IntoYex for ($($name,)+) {
+                fn into_yex(self) -> Value {
+                    vec![$(self.$idx.into_yex()),+].into()
+                }
+            }
+
+            impl<$($name: FromYex),+> FromYex for ($($name,)+) {
+                fn from_yex(value: &Value) -> InterpretResult<Self> {
+                    let tup = tuple(value, $len)?;
+                    Ok(($($name::from_yex(&tup.0[$idx])?,)+))
+                }
+            }
+        )+
+    };
+}
+
+impl_tuple!(
+    1 => (A: 0);
+    2 => (A: 0, B: 1);
+    3 => (A: 0, B: 1, C: 2);
+    4 => (A: 0, B: 1, C: 2, D: 3);
+);
+
+/// Creates a dictionary keyed by symbols, like `%{x: 1, y: 2}`, which is what
+/// `#[derive(IntoYex)]` converts the structs with named fields to
+pub fn record<const N: usize>(fields: [(&str, Value); N]) -> Value {
+    let entries = fields
+        .into_iter()
+        .map(|(name, value)| (Value::from(Symbol::new(name)), value));
+    Dict::from_entries(entries)
+        .expect("symbols are valid keys")
+        .into()
+}
+
+/// Returns the field `name` of a dictionary keyed by symbols or of a struct, raising a
+/// `FieldError` if it's missing, used by `#[derive(FromYex)]`
+pub fn field(value: &Value, name: &str) -> InterpretResult<Value> {
+    let found = match value {
+        Value::Dict(dict) => dict.get(&Symbol::new(name).into())?,
+        Value::Tagged(tagged) if tagged.0.struct_fields().is_some() => {
+            Some(tagged.0.field(&tagged.2, Symbol::new(name))?)
+        }
+        other => {
+            return raise!(
+                TypeError,
+                "Expected a dictionary or a struct with the field '{}', found '{}'",
+                name,
+                other
+            )
+        }
+    };
+
+    match found {
+        Some(value) => Ok(value),
+        None => raise!(FieldError, "Missing field '{}' in '{}'", name, value),
+    }
+}
+
+/// Returns the elements of a tuple of length `len`, raising a `TypeError` for other values
+pub fn tuple(value: &Value, len: usize) -> InterpretResult<Tuple> {
+    let tup: Tuple = value.get()?;
+    if tup.len() != len {
+        return raise!(
+            TypeError,
+            "Expected a tuple of length {}, found '{}'",
+            len,
+            value
+        );
+    }
+    Ok(tup)
+}
+
+#[test]
+fn roundtrip() {
+    let xs = vec![(1.0, "one".to_string()), (2.0, "two".to_string())];
+    let value = xs.clone().into_yex();
+    assert_eq!(Vec::<(f64, String)>::from_yex(&value).unwrap(), xs);
+
+    let none: Option<bool> = None;
+    assert_eq!(Option::<bool>::from_yex(&none.into_yex()).unwrap(), None);
+    assert!(f64::from_yex(&"oops".into_yex()).is_err());
+
+    let res: Result<i64, String> = Err("nope".to_string());
+    assert_eq!(Result::from_yex(&res.clone().into_yex()).unwrap(), res);
+    assert!(Result::<i64, String>::from_yex(&Value::Int(1)).is_err());
+
+    #[cfg(feature = "std")]
+    {
+        let map: HashMap<String, f64> = [("a".to_string(), 1.0)].into_iter().collect();
+        assert_eq!(HashMap::from_yex(&map.clone().into_yex()).unwrap(), map);
+    }
+}
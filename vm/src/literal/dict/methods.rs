@@ -0,0 +1,59 @@
+use alloc::vec::Vec;
+
+use crate::{
+    error::InterpretResult,
+    literal::{nil, TryGet, Value},
+    List, VirtualMachine,
+};
+
+use super::Dict;
+
+pub fn new(_: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Dict::new().into())
+}
+
+pub fn get(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[1].get()?;
+    Ok(dict.get(&args[0])?.unwrap_or_else(nil))
+}
+
+pub fn insert(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[2].get()?;
+    Ok(dict.insert(args[0].clone(), args[1].clone())?.into())
+}
+
+pub fn remove(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[1].get()?;
+    Ok(dict.remove(&args[0])?.into())
+}
+
+pub fn has(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[1].get()?;
+    Ok(dict.get(&args[0])?.is_some().into())
+}
+
+pub fn keys(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[0].get()?;
+    let keys: Vec<_> = dict.iter().map(|(key, _)| key.clone()).collect();
+    Ok(keys.into_iter().rev().collect::<List>().into())
+}
+
+pub fn values(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[0].get()?;
+    let values: Vec<_> = dict.iter().map(|(_, value)| value.clone()).collect();
+    Ok(values.into_iter().rev().collect::<List>().into())
+}
+
+pub fn to_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[0].get()?;
+    let entries: Vec<Value> = dict
+        .iter()
+        .map(|(key, value)| vec![key.clone(), value.clone()].into())
+        .collect();
+    Ok(entries.into_iter().rev().collect::<List>().into())
+}
+
+pub fn len(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let dict: Dict = args[0].get()?;
+    Ok((dict.len() as i64).into())
+}
@@ -0,0 +1,349 @@
+pub mod methods;
+
+use alloc::vec::Vec;
+use core::fmt;
+
+use crate::{
+    error::InterpretResult,
+    gc::{GcRef, Trace, Tracer},
+    literal::exact_int,
+    raise, Value,
+};
+
+#[derive(Clone)]
+struct Entry {
+    hash: u64,
+    key: Value,
+    value: Value,
+}
+
+#[derive(Clone, Default)]
+struct Table {
+    /// The entries, in insertion order
+    entries: Vec<Entry>,
+    /// Open addressing index of `entries`, its length is a power of two and at least twice the
+    /// number of entries, so probing always finds an empty slot
+    slots: Vec<Option<usize>>,
+}
+
+impl Trace for Table {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        for entry in &self.entries {
+            entry.key.trace(tracer);
+            entry.value.trace(tracer);
+        }
+    }
+}
+
+impl Table {
+    /// Finds the entry with `key`, or the empty slot where it should be placed
+    fn find(&self, hash: u64, key: &Value) -> Result<usize, usize> {
+        if self.slots.is_empty() {
+            return Err(0);
+        }
+
+        let mask = self.slots.len() - 1;
+        let mut slot = hash as usize & mask;
+
+        loop {
+            match self.slots[slot] {
+                None => return Err(slot),
+                Some(index) => {
+                    let entry = &self.entries[index];
+                    if entry.hash == hash && entry.key == *key {
+                        return Ok(index);
+                    }
+                }
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    fn set(&mut self, key: Value, value: Value) -> InterpretResult<()> {
+        let hash = hash_key(&key)?;
+
+        match self.find(hash, &key) {
+            Ok(index) => self.entries[index].value = value,
+            Err(slot) => {
+                self.entries.push(Entry { hash, key, value });
+
+                if self.entries.len() * 2 > self.slots.len() {
+                    self.reindex();
+                } else {
+                    self.slots[slot] = Some(self.entries.len() - 1);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn reindex(&mut self) {
+        let len = (self.entries.len() * 2).max(8).next_power_of_two();
+        self.slots = vec![None; len];
+
+        for index in 0..self.entries.len() {
+            if let Err(slot) = self.find(self.entries[index].hash, &self.entries[index].key) {
+                self.slots[slot] = Some(index);
+            }
+        }
+    }
+}
+
+/// A yex dictionary, a hash map that keeps the insertion order of its keys
+///
+/// Dictionaries are immutable like every other yex value, so inserting or removing a key creates
+/// a new dictionary
+#[derive(Clone)]
+pub struct Dict(GcRef<Table>);
+
+impl Dict {
+    /// Creates an empty dictionary
+    pub fn new() -> Self {
+        Self(GcRef::new(Table::default()))
+    }
+
+    /// Creates a dictionary from a list of entries, the last value of a repeated key is kept
+    pub fn from_entries<I: IntoIterator<Item = (Value, Value)>>(
+        entries: I,
+    ) -> InterpretResult<Self> {
+        let mut table = Table::default();
+        for (key, value) in entries {
+            table.set(key, value)?;
+        }
+        Ok(Self(GcRef::new(table)))
+    }
+
+    /// Returns the number of entries
+    pub fn len(&self) -> usize {
+        self.0.entries.len()
+    }
+
+    /// Checks if the dictionary has no entries
+    pub fn is_empty(&self) -> bool {
+        self.0.entries.is_empty()
+    }
+
+    /// Returns the address of the entries, which identifies the dictionary
+    pub(crate) fn addr(&self) -> usize {
+        self.0.addr()
+    }
+
+    /// Returns the value of `key`, raising a `TypeError` if the key can't be hashed
+    pub fn get(&self, key: &Value) -> InterpretResult<Option<Value>> {
+        let hash = hash_key(key)?;
+        Ok(self
+            .0
+            .find(hash, key)
+            .ok()
+            .map(|index| self.0.entries[index].value.clone()))
+    }
+
+    /// Returns a new dictionary with `key` set to `value`
+    pub fn insert(&self, key: Value, value: Value) -> InterpretResult<Self> {
+        let mut table = (*self.0).clone();
+        table.set(key, value)?;
+        Ok(Self(GcRef::new(table)))
+    }
+
+    /// Sets `key` to `value` in place, only for dictionaries no yex code can reach, like the caches
+    /// of the native functions
+    pub(crate) fn set(&mut self, key: Value, value: Value) -> InterpretResult<()> {
+        unsafe { self.0.mut_ref() }.set(key, value)
+    }
+
+    /// Returns a new dictionary without `key`
+    pub fn remove(&self, key: &Value) -> InterpretResult<Self> {
+        let hash = hash_key(key)?;
+
+        let index = match self.0.find(hash, key) {
+            Ok(index) => index,
+            Err(_) => return Ok(self.clone()),
+        };
+
+        let mut table = (*self.0).clone();
+        table.entries.remove(index);
+        table.reindex();
+        Ok(Self(GcRef::new(table)))
+    }
+
+    /// Iterates over the entries, in insertion order
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = (&Value, &Value)> {
+        self.0
+            .entries
+            .iter()
+            .map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl Trace for Dict {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        tracer.visit(&self.0);
+    }
+}
+
+/// The caches of the natives, a dictionary borrowed while it's traced hides its values, so
+/// they're kept alive
+impl Trace for core::cell::RefCell<Dict> {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        if let Ok(dict) = self.try_borrow() {
+            dict.trace(tracer);
+        }
+    }
+}
+
+impl Default for Dict {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Two dictionaries are equal if they have the same entries, in any order
+impl PartialEq for Dict {
+    fn eq(&self, other: &Self) -> bool {
+        self.len() == other.len()
+            && self
+                .iter()
+                .all(|(key, value)| matches!(other.get(key), Ok(Some(v)) if v == *value))
+    }
+}
+
+impl fmt::Debug for Dict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl fmt::Display for Dict {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "%{{")?;
+
+        for (i, (key, value)) in self.iter().enumerate() {
+            if i != 0 {
+                write!(f, ", ")?;
+            }
+
+            match key {
+                Value::Sym(sym) => write!(f, "{}: {}", sym.0, value)?,
+                other => write!(f, "{} => {}", other, value)?,
+            }
+        }
+
+        write!(f, "}}")
+    }
+}
+
+/// Hashes a dictionary key with FNV-1a, only immutable values can be used as keys
+fn hash_key(key: &Value) -> InterpretResult<u64> {
+    let mut hash = 0xcbf29ce484222325;
+    write_key(key, &mut hash)?;
+    Ok(hash)
+}
+
+fn write_key(key: &Value, hash: &mut u64) -> InterpretResult<()> {
+    let mut write = |bytes: &[u8]| {
+        for b in bytes {
+            *hash ^= *b as u64;
+            *hash = hash.wrapping_mul(0x100000001b3);
+        }
+    };
+
+    match key {
+        Value::Nil => write(&[0]),
+        Value::Bool(b) => write(&[1, *b as u8]),
+        // integers and round floats are equal, so they need the same hash
+        Value::Int(n) => {
+            write(&[2]);
+            write(&n.to_le_bytes());
+        }
+        Value::Num(n) => match exact_int(*n) {
+            Some(n) => {
+                write(&[2]);
+                write(&n.to_le_bytes());
+            }
+            None => {
+                write(&[8]);
+                write(&n.to_bits().to_le_bytes());
+            }
+        },
+        Value::Str(s) => {
+            write(&[3]);
+            write(s.as_bytes());
+        }
+        Value::Sym(s) => {
+            write(&[4]);
+            write(&(s.0.hash() as u64).to_le_bytes());
+        }
+        Value::Tuple(tup) => {
+            write(&[5]);
+            write(&(tup.len() as u64).to_le_bytes());
+            for x in tup.0.iter() {
+                write_key(x, hash)?;
+            }
+        }
+        Value::List(xs) => {
+            write(&[6]);
+            write(&(xs.len() as u64).to_le_bytes());
+            for x in xs.iter() {
+                write_key(&x, hash)?;
+            }
+        }
+        Value::Bytes(b) => {
+            write(&[11]);
+            write(&b.0);
+        }
+        Value::Array(xs) => {
+            write(&[10]);
+            write(&(xs.len() as u64).to_le_bytes());
+            for x in xs.iter() {
+                write_key(&x, hash)?;
+            }
+        }
+        Value::Tagged(tagged) => {
+            let (_, tag, tup) = &**tagged;
+            write(&[7]);
+            write(&(tag.hash() as u64).to_le_bytes());
+            for x in tup.0.iter() {
+                write_key(x, hash)?;
+            }
+        }
+        Value::Range(r) => {
+            // equal ranges have the same bounds
+            write(&[9]);
+            if let Some((first, last)) = r.bounds() {
+                write(&first.to_le_bytes());
+                write(&last.to_le_bytes());
+            }
+        }
+        other => return raise!(TypeError, "Can't use '{}' as a dictionary key", other),
+    }
+
+    Ok(())
+}
+
+#[test]
+fn insert_and_remove() {
+    let dict =
+        Dict::from_entries((0..20).map(|n| (Value::Num(n as f64), Value::Num(n as f64 * 2.0))))
+            .unwrap();
+    assert_eq!(dict.len(), 20);
+    assert_eq!(dict.get(&Value::Num(7.0)).unwrap(), Some(Value::Num(14.0)));
+
+    let updated = dict.insert(Value::Num(7.0), Value::Nil).unwrap();
+    assert_eq!(updated.get(&Value::Num(7.0)).unwrap(), Some(Value::Nil));
+    assert_eq!(dict.get(&Value::Num(7.0)).unwrap(), Some(Value::Num(14.0)));
+
+    let removed = dict.remove(&Value::Num(3.0)).unwrap();
+    assert_eq!(removed.len(), 19);
+    assert_eq!(removed.get(&Value::Num(3.0)).unwrap(), None);
+    assert_eq!(
+        removed.get(&Value::Num(19.0)).unwrap(),
+        Some(Value::Num(38.0))
+    );
+
+    let reordered =
+        Dict::from_entries(dict.iter().map(|(k, v)| (k.clone(), v.clone())).rev()).unwrap();
+    assert_eq!(reordered, dict);
+    assert!(dict.get(&Value::List(Default::default())).is_ok());
+    assert_eq!(dict.get(&Value::Int(7)).unwrap(), Some(Value::Num(14.0)));
+}
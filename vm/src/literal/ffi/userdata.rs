@@ -1,12 +1,25 @@
-use std::{
+use alloc::{boxed::Box, sync::Arc};
+use core::{
     any::Any,
     ops::{Deref, DerefMut},
 };
 
 use crate::gc::GcRef;
+
+/// Data that can be shared by vms running in other threads, userdata holding it is moved to
+/// them by reference instead of being copied (see [`crate::Packed`])
+pub type Shared = Arc<dyn Any + Send + Sync>;
+
 #[derive(Debug, Clone)]
 pub struct UserData(GcRef<Box<dyn Any>>);
 
+impl UserData {
+    /// Wraps a rust value so it can be stored inside of a yex value
+    pub fn new<T: Any>(data: T) -> Self {
+        Self(GcRef::new(Box::new(data)))
+    }
+}
+
 impl PartialEq for UserData {
     fn eq(&self, _: &Self) -> bool {
         false
@@ -16,13 +29,13 @@ impl PartialEq for UserData {
 impl Deref for UserData {
     type Target = dyn Any;
     fn deref(&self) -> &Self::Target {
-        &self.0
+        &**self.0
     }
 }
 
 impl DerefMut for UserData {
     fn deref_mut(&mut self) -> &mut Self::Target {
-        &mut self.0
+        unsafe { &mut **self.0.mut_ref() }
     }
 }
 
@@ -1,11 +1,21 @@
 use crate::{
-    error::InterpretResult, gc::GcRef, stackvec, Bytecode, StackVec, Value, VirtualMachine,
+    error::InterpretResult,
+    gc::{GcRef, Trace, Tracer},
+    literal::ffi::userdata::UserData,
+    stackvec, Bytecode, StackVec, Symbol, Value, VirtualMachine,
 };
+use alloc::{boxed::Box, collections::BTreeSet, string::String, vec::Vec};
 pub type NativeFn = fn(*mut VirtualMachine, Vec<Value>) -> InterpretResult<Value>;
+
+/// A rust closure called like a native function, see [`Fn::from_closure`]
+type Closure = Box<dyn core::ops::Fn(&mut VirtualMachine, &[Value]) -> InterpretResult<Value>>;
 pub type FnBody = GcRef<FnKind>;
-pub type FnArgs = StackVec<Value, 8>;
+/// The most arguments that can be applied to a function, partially applied functions keep them in
+/// a [`FnArgs`]
+pub const MAX_APPLIED: usize = 8;
+pub type FnArgs = StackVec<Value, MAX_APPLIED>;
 
-#[derive(Debug, Clone, PartialEq, Eq)]
+#[derive(Debug, Clone)]
 /// The kind of a function.
 pub enum FnKind {
     /// A native function.
@@ -14,7 +24,59 @@ pub enum FnKind {
     Bytecode(Bytecode),
 }
 
-#[derive(PartialEq, Clone)]
+impl PartialEq for FnKind {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Self::Native(a), Self::Native(b)) => core::ptr::fn_addr_eq(*a, *b),
+            (Self::Bytecode(a), Self::Bytecode(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for FnKind {}
+
+impl Trace for FnKind {
+    const LEAF: bool = true;
+}
+
+/// The documentation of a function, shown by the `help` builtin
+#[derive(Debug, PartialEq, Clone, Default)]
+pub struct FnInfo {
+    /// The function name, prefixed by the type name for methods
+    pub name: Symbol,
+    /// The name (or the pattern) of each parameter
+    pub params: Vec<String>,
+    /// The doc comment
+    pub doc: Option<String>,
+    /// The names of the locals with the slot of each one, in the order they're declared, the
+    /// slots of the locals out of scope are reused by the ones declared after them
+    pub locals: Vec<(usize, Symbol)>,
+}
+
+impl Trace for FnInfo {
+    const LEAF: bool = true;
+}
+
+impl FnInfo {
+    /// Creates the documentation for a function
+    pub fn new<T: Into<Symbol>>(name: T, params: Vec<String>, doc: Option<String>) -> Self {
+        Self {
+            name: name.into(),
+            params,
+            doc,
+            locals: Vec::new(),
+        }
+    }
+
+    /// Sets the names of the locals, shown by the debugger
+    pub fn with_locals(mut self, locals: Vec<(usize, Symbol)>) -> Self {
+        self.locals = locals;
+        self
+    }
+}
+
+#[derive(Clone)]
 /// Yex function struct
 pub struct Fn {
     /// The number of argument the function receives
@@ -23,6 +85,37 @@ pub struct Fn {
     pub body: FnBody,
     /// The function Arguments
     pub args: FnArgs,
+    /// The function documentation, if any
+    pub info: Option<GcRef<FnInfo>>,
+    /// The values captured from the enclosing functions, in the order they're referenced by
+    /// `LoadUpval`
+    pub upvalues: Vec<Value>,
+    /// The function called instead when more than `arity` arguments are passed, set on the stubs
+    /// that fill in default arguments
+    pub more: Option<GcRef<Fn>>,
+    /// Whether the last argument collects the extra arguments passed into a list
+    pub variadic: bool,
+}
+
+/// The documentation isn't part of the function identity
+impl PartialEq for Fn {
+    fn eq(&self, other: &Self) -> bool {
+        self.arity == other.arity
+            && self.variadic == other.variadic
+            && self.body == other.body
+            && self.args == other.args
+            && self.upvalues == other.upvalues
+    }
+}
+
+impl Trace for Fn {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        self.args.iter().for_each(|arg| arg.trace(tracer));
+        self.upvalues.iter().for_each(|value| value.trace(tracer));
+        if let Some(more) = &self.more {
+            tracer.visit(more);
+        }
+    }
 }
 
 impl Fn {
@@ -32,6 +125,10 @@ impl Fn {
             arity,
             body: GcRef::new(FnKind::Bytecode(body)),
             args: FnArgs::new(),
+            info: None,
+            upvalues: vec![],
+            more: None,
+            variadic: false,
         }
     }
 
@@ -41,9 +138,43 @@ impl Fn {
             arity,
             body: GcRef::new(FnKind::Native(native)),
             args: FnArgs::new(),
+            info: None,
+            upvalues: vec![],
+            more: None,
+            variadic: false,
         }
     }
 
+    /// Creates a native function that calls a rust closure, which can capture the state of the
+    /// host, unlike a [`NativeFn`]
+    ///
+    /// The closure is kept as the first argument applied to the function, like the cache of
+    /// `memo`, and receives the arguments after it
+    pub fn from_closure<F>(arity: usize, closure: F) -> Self
+    where
+        F: core::ops::Fn(&mut VirtualMachine, &[Value]) -> InterpretResult<Value> + 'static,
+    {
+        let closure: Closure = Box::new(closure);
+        Self {
+            args: stackvec![Value::UserData(UserData::new(closure))],
+            ..Self::new_native(arity, call_closure)
+        }
+    }
+
+    /// Makes the last argument collect the extra arguments passed into a list
+    #[must_use]
+    pub fn variadic(mut self) -> Self {
+        self.variadic = true;
+        self
+    }
+
+    /// Attaches documentation to the function
+    #[must_use]
+    pub fn with_info(mut self, info: FnInfo) -> Self {
+        self.info = Some(GcRef::new(info));
+        self
+    }
+
     /// Converts the Fn to a GcRef<Fn>
     #[must_use]
     pub fn to_gcref(self) -> GcRef<Fn> {
@@ -61,9 +192,35 @@ impl Fn {
             arity: self.arity + self.args.len() - args.len(),
             body: self.body.clone(),
             args,
+            info: self.info.clone(),
+            upvalues: self.upvalues.clone(),
+            more: self.more.clone(),
+            variadic: self.variadic,
         }
     }
 
+    /// Makes `more` the function called when more arguments are passed, the documentation of
+    /// `more` is used if the function has none
+    #[must_use]
+    pub fn overloaded(mut self, more: GcRef<Fn>) -> Self {
+        if self.info.is_none() {
+            self.info = more.info.clone();
+        }
+        self.more = Some(more);
+        self
+    }
+
+    /// Returns the function that takes more arguments, with the arguments already applied to this
+    /// one
+    pub fn overload(&self) -> Option<Self> {
+        let more = self.more.as_ref()?;
+        Some(Fn {
+            arity: more.arity - self.args.len(),
+            args: self.args.clone(),
+            ..(**more).clone()
+        })
+    }
+
     /// Checks if the function is a native function
     pub fn is_native(&self) -> bool {
         matches!(*self.body, FnKind::Native(_))
@@ -75,14 +232,86 @@ impl Fn {
     }
 }
 
-impl std::fmt::Debug for Fn {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+/// Calls `visit` with each bytecode function reachable from a constant, which are the function
+/// itself, the ones it calls when more arguments are passed and the functions stored in modules
+/// and types, with their methods
+///
+/// `seen` holds the addresses of the bodies and modules already visited, so each body is visited
+/// once even if it's reachable from more than one constant
+pub(crate) fn walk_code(
+    value: &Value,
+    seen: &mut BTreeSet<usize>,
+    visit: &mut dyn FnMut(&GcRef<Fn>, &Bytecode),
+) {
+    match value {
+        Value::Fn(fun) => {
+            // the stubs of default arguments call the functions after them
+            let mut fun = Some(fun);
+            while let Some(current) = fun {
+                if let FnKind::Bytecode(bytecode) = &*current.body {
+                    if seen.insert(current.body.addr()) {
+                        visit(current, bytecode);
+                    }
+                }
+                fun = current.more.as_ref();
+            }
+        }
+        Value::Module(module) if seen.insert(module.addr()) => {
+            for (_, field) in module.fields.iter() {
+                walk_code(&field, seen, visit);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Calls the closure of a function created by [`Fn::from_closure`]
+fn call_closure(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let closure = match &args[0] {
+        Value::UserData(data) => data.downcast_ref::<Closure>().unwrap(),
+        _ => unreachable!(),
+    };
+
+    closure(vm, &args[1..])
+}
+
+impl core::fmt::Debug for Fn {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "Fn {{ arity: {}, body: {:?} }}", self.arity, self.body)
     }
 }
 
-impl std::fmt::Display for Fn {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Fn {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "<fun({})>", self.arity)
     }
 }
+
+#[test]
+fn closures() {
+    use alloc::rc::Rc;
+    use core::cell::Cell;
+
+    let calls = Rc::new(Cell::new(0));
+    let counted = calls.clone();
+
+    let mut vm = VirtualMachine::default();
+    vm.register_native("sub", 2, move |_, args| {
+        counted.set(counted.get() + 1);
+        match (&args[0], &args[1]) {
+            (Value::Int(a), Value::Int(b)) => Ok(Value::Int(a - b)),
+            _ => crate::raise!(TypeError, "Expected two ints"),
+        }
+    });
+
+    // partially applied, the arguments still come after the closure
+    let sub = vm.call_global("sub", &[Value::Int(5)]).unwrap();
+    assert_eq!(vm.call_value(sub, &[Value::Int(3)]).unwrap(), Value::Int(2));
+    assert_eq!(calls.get(), 1);
+
+    let e = vm
+        .call_global("sub", &[Value::Nil, Value::Nil])
+        .unwrap_err();
+    assert_eq!(e.builtin, Some(Symbol::from("sub")));
+}
@@ -0,0 +1,32 @@
+use alloc::vec::Vec;
+
+use crate::{error::InterpretResult, raise, Value, VirtualMachine};
+
+use super::TryGet;
+
+/// Integer division, rounding towards zero like the `%` operator
+pub fn div(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let x: i64 = args[0].get()?;
+    let y: i64 = args[1].get()?;
+
+    match x.checked_div(y) {
+        Some(n) => Ok(Value::Int(n)),
+        None if y == 0 => raise!(ZeroDivisionError, "Cannot divide '{}' by zero", x),
+        None => raise!(OverflowError, "'{} / {}' overflows a 64 bit integer", x, y),
+    }
+}
+
+/// Converts a float (dropping its fractional part) or a string to an integer
+pub fn from(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    match &args[0] {
+        Value::Int(n) => Ok(Value::Int(*n)),
+        Value::Num(n) if n.is_finite() && *n >= i64::MIN as f64 && *n < i64::MAX as f64 => {
+            Ok(Value::Int(*n as i64))
+        }
+        Value::Str(s) => match s.trim().parse::<i64>() {
+            Ok(n) => Ok(Value::Int(n)),
+            Err(_) => raise!(ValueError, "Cannot convert '{}' to an integer", s.as_str()),
+        },
+        other => raise!(ValueError, "Cannot convert '{}' to an integer", other),
+    }
+}
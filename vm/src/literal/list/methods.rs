@@ -1,12 +1,14 @@
-use std::fmt::Write;
+use alloc::{string::String, vec::Vec};
+use core::{cmp::Ordering, fmt::Write};
 
 use crate::{
     error::InterpretResult,
-    literal::{nil, TryGet, Value},
-    VirtualMachine,
+    gc::GcRef,
+    literal::{nil, range, TryGet, Value},
+    raise, Symbol, Tuple, VirtualMachine, YexModule,
 };
 
-use super::List;
+use super::{Items, List};
 
 pub fn rev(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let xs: List = args[0].get()?;
@@ -15,11 +17,9 @@ pub fn rev(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
 
 pub fn map(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let vm = unsafe { &mut *vm };
-    let xs: List = args[1].get()?;
     let fun = &args[0];
 
-    let xs: InterpretResult<List> = xs
-        .iter()
+    let xs: InterpretResult<List> = Items::new(&args[1])?
         .map(|it| {
             vm.push(it);
             vm.push(fun.clone());
@@ -37,11 +37,10 @@ pub fn map(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value>
 pub fn fold(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let vm = unsafe { &mut *vm };
 
-    let xs: List = args[2].get()?;
     let mut acc = args[1].clone();
     let fun = args[0].clone();
 
-    for it in xs.iter() {
+    for it in Items::new(&args[2])? {
         vm.push(it);
         vm.push(acc);
         vm.push(fun.clone());
@@ -54,15 +53,62 @@ pub fn fold(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value>
     Ok(acc)
 }
 
+/// Wraps `args` in the `List.continue` or the `List.halt` tag
+fn step(tag: &str, args: Vec<Value>) -> Value {
+    let this = GcRef::new(YexModule::default());
+    let tup = Tuple(GcRef::new(args.into_boxed_slice()));
+    Value::tagged(this, Symbol::from(tag), tup)
+}
+
+pub fn cont(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(step("List.continue", args))
+}
+
+pub fn halt(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(step("List.halt", args))
+}
+
+/// Like `fold`, but `f` returns `List.continue acc` to keep going or `List.halt acc` to stop
+/// before the end of the list
+pub fn reduce_while(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+
+    let mut acc = args[1].clone();
+    let fun = args[0].clone();
+
+    for it in Items::new(&args[2])? {
+        vm.push(it);
+        vm.push(acc);
+        vm.push(fun.clone());
+
+        vm.call(2)?;
+
+        match vm.pop() {
+            Value::Tagged(step) if step.1.as_str() == "List.continue" => acc = step.2 .0[0].clone(),
+            Value::Tagged(step) if step.1.as_str() == "List.halt" => {
+                return Ok(step.2 .0[0].clone())
+            }
+            other => {
+                return raise!(
+                    TypeError,
+                    "Expected 'List.continue' or 'List.halt', found '{}'",
+                    other
+                )
+            }
+        }
+    }
+
+    Ok(acc)
+}
+
 pub fn filter(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let vm = unsafe { &mut *vm };
 
-    let xs: List = args[1].get()?;
     let fun = &args[0];
 
     let mut ys = List::new();
 
-    for x in xs.iter() {
+    for x in Items::new(&args[1])? {
         vm.push(x.clone());
         vm.push(fun.clone());
 
@@ -77,6 +123,143 @@ pub fn filter(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Valu
     Ok(ys.rev().into())
 }
 
+/// Calls `fun` with `x`, checking if it returned a truthy value
+fn test(vm: &mut VirtualMachine, fun: &Value, x: Value) -> InterpretResult<bool> {
+    vm.push(x);
+    vm.push(fun.clone());
+    vm.call(1)?;
+    Ok(vm.pop().to_bool())
+}
+
+pub fn take(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let n: usize = args[0].get()?;
+    let xs: Vec<_> = Items::new(&args[1])?.take(n).collect();
+
+    Ok(xs.into_iter().rev().collect::<List>().into())
+}
+
+pub fn take_while(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = &args[0];
+
+    let mut ys = List::new();
+    for x in Items::new(&args[1])? {
+        if !test(vm, fun, x.clone())? {
+            break;
+        }
+        ys = ys.prepend(x);
+    }
+
+    Ok(ys.rev().into())
+}
+
+pub fn flat_map(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = &args[0];
+
+    let mut ys = List::new();
+    for x in Items::new(&args[1])? {
+        vm.push(x);
+        vm.push(fun.clone());
+        vm.call(1)?;
+
+        let xs = vm.pop();
+        for y in Items::new(&xs)? {
+            ys = ys.prepend(y);
+        }
+    }
+
+    Ok(ys.rev().into())
+}
+
+pub fn zip(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let pairs: Vec<_> = Items::new(&args[0])?
+        .zip(Items::new(&args[1])?)
+        .map(|(x, y)| Value::from(vec![x, y]))
+        .collect();
+
+    Ok(pairs.into_iter().rev().collect::<List>().into())
+}
+
+pub fn zip_with(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = &args[0];
+
+    let mut zs = List::new();
+    for (x, y) in Items::new(&args[1])?.zip(Items::new(&args[2])?) {
+        vm.push(y);
+        vm.push(x);
+        vm.push(fun.clone());
+        vm.call(2)?;
+
+        zs = zs.prepend(vm.pop());
+    }
+
+    Ok(zs.rev().into())
+}
+
+pub fn any(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+
+    for x in Items::new(&args[1])? {
+        if test(vm, &args[0], x)? {
+            return Ok(true.into());
+        }
+    }
+
+    Ok(false.into())
+}
+
+pub fn all(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+
+    for x in Items::new(&args[1])? {
+        if !test(vm, &args[0], x)? {
+            return Ok(false.into());
+        }
+    }
+
+    Ok(true.into())
+}
+
+pub fn index_of(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let index = Items::new(&args[1])?.position(|x| x == args[0]);
+    Ok(index.map(|n| Value::Int(n as i64)).unwrap_or_else(nil))
+}
+
+/// Sorts numbers in ascending order, the sort is stable
+pub fn sort(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let mut xs: Vec<_> = Items::new(&args[0])?.collect();
+
+    // `sort_by` can't stop halfway, so the first error is kept and raised after it
+    let mut error = None;
+    xs.sort_by(|a, b| {
+        a.ord_cmp(b).unwrap_or_else(|e| {
+            error.get_or_insert(e);
+            Ordering::Equal
+        })
+    });
+
+    if let Some(e) = error {
+        return Err(e);
+    }
+
+    Ok(xs.into_iter().rev().collect::<List>().into())
+}
+
+/// Removes repeated elements, keeping the first occurrence of each one
+pub fn unique(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let mut seen: Vec<Value> = Vec::new();
+
+    for x in Items::new(&args[0])? {
+        if !seen.contains(&x) {
+            seen.push(x);
+        }
+    }
+
+    Ok(seen.into_iter().rev().collect::<List>().into())
+}
+
 pub fn head(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let xs: List = args[0].get()?;
     Ok(xs.head().unwrap_or(Value::Nil))
@@ -110,11 +293,9 @@ pub fn join(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value>
 pub fn find(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let vm = unsafe { &mut *vm };
 
-    let xs: List = args[1].get()?;
-
     let fun = &args[0];
 
-    for x in xs.iter() {
+    for x in Items::new(&args[1])? {
         vm.push(x.clone());
         vm.push(fun.clone());
 
@@ -128,10 +309,13 @@ pub fn find(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value>
     Ok(nil())
 }
 
-pub fn len(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
-    let xs: List = args[0].get()?;
+pub fn len(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    if let Value::Range(_) = &args[0] {
+        return range::methods::len(vm, args);
+    }
 
-    Ok((xs.len() as f64).into())
+    let xs: List = args[0].get()?;
+    Ok((xs.len() as i64).into())
 }
 
 pub fn show(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
@@ -154,7 +338,12 @@ pub fn show(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value>
 }
 
 pub fn to_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
-    Ok(args[0].clone())
+    if let Value::List(_) = &args[0] {
+        return Ok(args[0].clone());
+    }
+
+    let xs: Vec<_> = Items::new(&args[0])?.collect();
+    Ok(xs.into_iter().rev().collect::<List>().into())
 }
 
 pub fn new(_: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
@@ -1,8 +1,15 @@
 pub(crate) mod methods;
 
 use crate::{
-    gc::GcRef,
-    literal::{nil, Value},
+    error::InterpretResult,
+    gc::{GcRef, Trace, Tracer},
+    literal::{array, nil, Value},
+    raise,
+};
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
 };
 
 type Link = Option<GcRef<Node>>;
@@ -17,6 +24,23 @@ pub struct Node {
     next: Link,
 }
 
+impl Trace for Node {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        self.elem.trace(tracer);
+        if let Some(next) = &self.next {
+            tracer.visit(next);
+        }
+    }
+}
+
+impl Trace for List {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        if let Some(head) = &self.head {
+            tracer.visit(head);
+        }
+    }
+}
+
 impl List {
     /// Creates a List
     pub const fn new() -> Self {
@@ -25,7 +49,12 @@ impl List {
 
     /// Checks if the list is empty
     pub fn is_empty(&self) -> bool {
-        self.head == None
+        self.head.is_none()
+    }
+
+    /// Returns the address of the first node, or zero if the list is empty
+    pub(crate) fn addr(&self) -> usize {
+        self.head.as_ref().map_or(0, GcRef::addr)
     }
 
     /// Prepends a value to the end, returning the list
@@ -42,10 +71,7 @@ impl List {
     #[must_use]
     pub fn tail(&self) -> Self {
         let tail = self.head.as_ref().map(|node| node.next.clone());
-        let tail = match tail {
-            Some(v) => v,
-            None => None,
-        };
+        let tail = tail.unwrap_or_default();
 
         Self { head: tail }
     }
@@ -73,7 +99,7 @@ impl List {
     pub fn len(&self) -> usize {
         let mut xs = self.head.as_ref();
         let mut count = 0;
-        while xs != None {
+        while xs.is_some() {
             xs = xs.unwrap().next.as_ref();
             count += 1;
         }
@@ -92,7 +118,7 @@ impl List {
     }
 
     /// Iterate over all elements of `self`
-    pub fn iter(&self) -> Iter {
+    pub fn iter(&self) -> Iter<'_> {
         Iter {
             next: self.head.as_deref(),
         }
@@ -146,8 +172,14 @@ impl List {
     }
 }
 
-impl std::fmt::Display for List {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl Default for List {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl core::fmt::Display for List {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "[")?;
         for (len, value) in self.iter().enumerate() {
             if len != self.len() - 1 {
@@ -174,6 +206,41 @@ impl<'a> Iterator for Iter<'a> {
     }
 }
 
+/// The elements of a list, an array or a range, so the `List` functions work with all of them
+/// without turning them into a list
+pub enum Items<'a> {
+    List(Iter<'a>),
+    Array(array::Iter<'a>),
+    Range(core::ops::RangeInclusive<i64>),
+}
+
+impl Items<'_> {
+    /// Iterates over `value`, raising a `TypeError` if it isn't a list, an array or a range
+    pub fn new(value: &Value) -> InterpretResult<Items<'_>> {
+        match value {
+            Value::List(xs) => Ok(Items::List(xs.iter())),
+            Value::Array(xs) => Ok(Items::Array(xs.iter())),
+            Value::Range(r) => Ok(Items::Range(r.iter())),
+            other => raise!(
+                TypeError,
+                "Unexpected type '{}', expected type was 'List'",
+                other.type_of().name
+            ),
+        }
+    }
+}
+
+impl Iterator for Items<'_> {
+    type Item = Value;
+    fn next(&mut self) -> Option<Self::Item> {
+        match self {
+            Items::List(xs) => xs.next(),
+            Items::Array(xs) => xs.next(),
+            Items::Range(r) => r.next().map(Value::Int),
+        }
+    }
+}
+
 impl FromIterator<Value> for List {
     fn from_iter<T: IntoIterator<Item = Value>>(iter: T) -> Self {
         let mut list = Self::new();
@@ -1,4 +1,9 @@
-use std::{
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{
     any::Any,
     cmp::Ordering,
     mem,
@@ -6,37 +11,60 @@ use std::{
 };
 
 //pub mod file;
+pub mod array;
+pub mod bytes;
+pub mod convert;
+pub mod dict;
 pub mod ffi;
 pub mod fun;
+pub mod int;
 pub mod list;
+pub mod num;
+pub mod pretty;
+pub mod range;
 pub mod result;
+pub mod seq;
 pub mod str;
 pub mod symbol;
 pub mod tuple;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 pub mod yexmodule;
 
-use crate::{error::InterpretResult, gc::GcRef, raise, VirtualMachine};
+use crate::{
+    error::InterpretResult,
+    gc::{GcRef, Trace, Tracer},
+    raise, VirtualMachine,
+};
 
+use array::Array;
+use bytes::Bytes;
 use fun::Fn;
 use list::List;
+use seq::Seq;
 use symbol::Symbol;
 use yexmodule::YexModule;
 
-use self::{
-    ffi::{userdata::UserData, Ffi},
-    symbol::YexSymbol,
-    tuple::Tuple,
-};
+#[cfg(feature = "std")]
+use self::ffi::Ffi;
+use self::{dict::Dict, ffi::userdata::UserData, range::Range, symbol::YexSymbol, tuple::Tuple};
 
 pub fn show(_: *mut VirtualMachine, x: Vec<Value>) -> InterpretResult<String> {
     match &x[0] {
         Value::Sym(s) => Ok(s.to_string()),
         Value::Str(s) => Ok(s.to_string()),
+        Value::Bytes(b) => Ok(b.to_string()),
         Value::List(l) => Ok(l.to_string()),
+        Value::Array(xs) => Ok(xs.to_string()),
+        Value::Seq(xs) => Ok(xs.to_string()),
         Value::Tuple(t) => Ok(t.to_string()),
+        Value::Dict(d) => Ok(d.to_string()),
+        Value::Range(r) => Ok(r.to_string()),
         tag @ Value::Tagged(..) => Ok(tag.to_string()),
-        Value::Num(n) => Ok(n.to_string()),
+        Value::Num(n) => Ok(num::format(*n)),
+        Value::Int(n) => Ok(n.to_string()),
         Value::Bool(b) => Ok(b.to_string()),
+        #[cfg(feature = "std")]
         Value::FFI(f) => Ok(f.to_string()),
         Value::UserData(u) => Ok(format!("<userdata({:?})>", u.type_id())),
         Value::Fn(f) => Ok(format!("fn({})", f.arity)),
@@ -73,6 +101,12 @@ impl From<f64> for Value {
     }
 }
 
+impl From<i64> for Value {
+    fn from(i: i64) -> Self {
+        Value::Int(i)
+    }
+}
+
 impl From<String> for Value {
     fn from(s: String) -> Self {
         Value::Str(GcRef::new(s))
@@ -91,6 +125,36 @@ impl From<YexModule> for Value {
     }
 }
 
+impl From<Dict> for Value {
+    fn from(d: Dict) -> Self {
+        Value::Dict(d)
+    }
+}
+
+impl From<Range> for Value {
+    fn from(r: Range) -> Self {
+        Value::Range(GcRef::new(r))
+    }
+}
+
+impl From<Bytes> for Value {
+    fn from(b: Bytes) -> Self {
+        Value::Bytes(b)
+    }
+}
+
+impl From<Seq> for Value {
+    fn from(xs: Seq) -> Self {
+        Value::Seq(xs)
+    }
+}
+
+impl From<Array> for Value {
+    fn from(xs: Array) -> Self {
+        Value::Array(xs)
+    }
+}
+
 impl From<List> for Value {
     fn from(l: List) -> Self {
         Value::List(l)
@@ -103,13 +167,30 @@ impl From<Fn> for Value {
     }
 }
 
+/// The type, the tag and the fields of a tagged tuple
+pub type TaggedTuple = (GcRef<YexModule>, Symbol, Tuple);
+
+impl Trace for TaggedTuple {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        tracer.visit(&self.0);
+        self.2.trace(tracer);
+    }
+}
+
 /// Immediate values that can be consumed
-#[derive(Debug, PartialEq)]
+///
+/// Every variant holds at most a word, the bigger ones live behind a [`GcRef`], so values are
+/// two words long and cheap to move around the stack
+#[derive(Debug, Default)]
 pub enum Value {
     /// float-precision numbers
     Num(f64),
+    /// 64 bit signed integers
+    Int(i64),
     /// Strings
     Str(GcRef<String>),
+    /// Byte strings
+    Bytes(Bytes),
     /// erlang-like atoms
     Sym(YexSymbol),
     /// Booleans
@@ -118,42 +199,122 @@ pub enum Value {
     Fn(GcRef<Fn>),
     /// Yex lists
     List(List),
+    /// Persistent vectors, with fast indexing
+    Array(Array),
+    /// Lazy sequences
+    Seq(Seq),
     /// Yex user-defined types
     Module(GcRef<YexModule>),
     /// Tuples
     Tuple(Tuple),
     /// Tagged tuples
-    Tagged(GcRef<YexModule>, Symbol, Tuple),
+    Tagged(GcRef<TaggedTuple>),
+    /// Dictionaries
+    Dict(Dict),
+    /// Lazy ranges of integers
+    Range(GcRef<Range>),
     /// FFI User Data
     UserData(UserData),
     /// External Libraries
-    FFI(Ffi),
+    #[cfg(feature = "std")]
+    FFI(GcRef<Ffi>),
     /// null
+    #[default]
     Nil,
 }
 
+impl Trace for Value {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        match self {
+            Value::Fn(f) => tracer.visit(f),
+            Value::List(xs) => xs.trace(tracer),
+            Value::Array(xs) => xs.trace(tracer),
+            Value::Seq(seq) => seq.trace(tracer),
+            Value::Module(module) => tracer.visit(module),
+            Value::Tuple(tuple) => tuple.trace(tracer),
+            Value::Tagged(tagged) => tracer.visit(tagged),
+            Value::Dict(dict) => dict.trace(tracer),
+            _ => {}
+        }
+    }
+}
+
 impl Clone for Value {
     fn clone(&self) -> Self {
         use Value::*;
 
         match self {
             List(xs) => List(xs.clone()),
+            Array(xs) => Array(xs.clone()),
+            Seq(xs) => Seq(xs.clone()),
             Str(str) => Str(GcRef::clone(str)),
+            Bytes(b) => Bytes(b.clone()),
             Fn(f) => Fn(GcRef::clone(f)),
             Bool(b) => Bool(*b),
             Num(n) => Num(*n),
+            Int(n) => Int(*n),
             Sym(s) => Sym(*s),
             Module(t) => Module(t.clone()),
             Tuple(t) => Tuple(t.clone()),
+            #[cfg(feature = "std")]
             FFI(f) => FFI(f.clone()),
             UserData(u) => UserData(u.clone()),
-            Tagged(m, s, t) => Tagged(m.clone(), *s, t.clone()),
+            Tagged(t) => Tagged(t.clone()),
+            Dict(d) => Dict(d.clone()),
+            Range(r) => Range(r.clone()),
             Nil => Nil,
         }
     }
 }
 
+/// Returns the integer equal to `n`, if there's one
+pub(crate) fn exact_int(n: f64) -> Option<i64> {
+    // `i64::MAX as f64` rounds up to 2^63, which doesn't fit in an i64
+    if n % 1.0 == 0.0 && n >= i64::MIN as f64 && n < i64::MAX as f64 {
+        Some(n as i64)
+    } else {
+        None
+    }
+}
+
+/// Integers and floats are compared by their value, so `1 == 1.0`
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        use Value::*;
+
+        match (self, other) {
+            (Num(a), Num(b)) => a == b,
+            (Int(a), Int(b)) => a == b,
+            (Int(a), Num(b)) | (Num(b), Int(a)) => exact_int(*b) == Some(*a),
+            (Str(a), Str(b)) => a == b,
+            (Sym(a), Sym(b)) => a == b,
+            (Bool(a), Bool(b)) => a == b,
+            (Fn(a), Fn(b)) => a == b,
+            (List(a), List(b)) => a == b,
+            (Bytes(a), Bytes(b)) => a == b,
+            (Array(a), Array(b)) => a == b,
+            (Seq(a), Seq(b)) => a == b,
+            // types are compared by name, since the unit variants make them cyclic
+            (Module(a), Module(b)) => a.name == b.name,
+            (Tuple(a), Tuple(b)) => a == b,
+            (Tagged(a), Tagged(b)) => a.0.name == b.0.name && a.1 == b.1 && a.2 == b.2,
+            (Dict(a), Dict(b)) => a == b,
+            (Range(a), Range(b)) => a == b,
+            (UserData(a), UserData(b)) => a == b,
+            #[cfg(feature = "std")]
+            (FFI(a), FFI(b)) => a == b,
+            (Nil, Nil) => true,
+            _ => false,
+        }
+    }
+}
+
 impl Value {
+    /// Creates a tagged tuple of the type `ty`
+    pub fn tagged(ty: GcRef<YexModule>, tag: Symbol, fields: Tuple) -> Self {
+        Value::Tagged(GcRef::new((ty, tag, fields)))
+    }
+
     /// checks if the constant is `nil`
     pub fn is_nil(&self) -> bool {
         self == &Self::Nil
@@ -164,16 +325,23 @@ impl Value {
     pub fn len(&self) -> usize {
         match self {
             Value::List(xs) => xs.len(),
+            Value::Array(xs) => xs.len(),
+            Value::Seq(xs) => mem::size_of_val(xs),
             Value::Num(_) => mem::size_of::<f64>(),
+            Value::Int(_) => mem::size_of::<i64>(),
             Value::Sym(_) => mem::size_of::<Symbol>(),
             Value::Str(s) => s.len(),
-            Value::Fn(f) => mem::size_of_val(&f),
+            Value::Bytes(b) => b.len(),
+            Value::Fn(f) => mem::size_of_val(f),
             Value::Bool(_) => mem::size_of::<bool>(),
-            Value::Module(t) => mem::size_of_val(&t),
+            Value::Module(t) => mem::size_of_val(t),
             Value::Tuple(t) => t.len(),
-            Value::FFI(f) => mem::size_of_val(f),
+            #[cfg(feature = "std")]
+            Value::FFI(f) => mem::size_of_val(&**f),
             Value::UserData(d) => mem::size_of_val(d),
-            Value::Tagged(_, _, t) => t.len(),
+            Value::Tagged(t) => t.2.len(),
+            Value::Dict(d) => d.len(),
+            Value::Range(r) => r.len(),
             Value::Nil => 4,
         }
     }
@@ -181,11 +349,14 @@ impl Value {
     /// Compares the left and the right value
     pub fn ord_cmp(&self, rhs: &Self) -> InterpretResult<Ordering> {
         let (left, right) = match (self, rhs) {
-            (Self::Num(left), Self::Num(right)) => (left, right),
+            (Self::Int(left), Self::Int(right)) => return Ok(left.cmp(right)),
+            (Self::Num(left), Self::Num(right)) => (*left, *right),
+            (Self::Int(left), Self::Num(right)) => (*left as f64, *right),
+            (Self::Num(left), Self::Int(right)) => (*left, *right as f64),
             (l, r) => raise!(TypeError, "cmp not supported with '{}' and '{}'", l, r)?,
         };
 
-        match left.partial_cmp(right) {
+        match left.partial_cmp(&right) {
             Some(ord) => Ok(ord),
             None => raise!(TypeError, "Cannot compare '{}' and '{}'", left, right),
         }
@@ -200,51 +371,90 @@ impl Value {
             Sym(_) => true,
             Str(s) if s.is_empty() => false,
             Str(_) => true,
+            Bytes(b) => !b.is_empty(),
             Num(n) if *n == 0.0 => false,
             Num(_) => true,
+            Int(n) => *n != 0,
             Nil => false,
             List(xs) => !xs.is_empty(),
+            Array(xs) => !xs.is_empty(),
+            Seq(_) => true,
             Fn(_) => true,
+            #[cfg(feature = "std")]
             FFI(_) => true,
             Module(_) => true,
             Tuple(_) => true,
             UserData(_) => true,
             Tagged(..) => true,
+            Dict(d) => !d.is_empty(),
+            Range(r) => !r.is_empty(),
         }
     }
 
+    /// Returns the name of the type of the value, without building the type like `type_of`
+    pub fn type_name(&self) -> Symbol {
+        use Value::*;
+
+        let name = match self {
+            Module(t) => return t.name,
+            Tagged(t) => return t.0.name,
+            List(_) => "List",
+            Array(_) => "Array",
+            Seq(_) => "Seq",
+            Fn(_) => "Fn",
+            Num(_) => "Num",
+            Int(_) => "Int",
+            Str(_) => "Str",
+            Bytes(_) => "Bytes",
+            Bool(_) => "Bool",
+            Nil => "Nil",
+            Sym(_) => "Sym",
+            Tuple(_) => "Tuple",
+            Dict(_) => "Dict",
+            Range(_) => "Range",
+            #[cfg(feature = "std")]
+            FFI(_) => "FFI",
+            UserData(_) => "UserData",
+        };
+
+        Symbol::new(name)
+    }
+
     /// returns the type of the value
     pub fn type_of(&self) -> GcRef<YexModule> {
         use Value::*;
 
         match self {
-            Module(t) | Tagged(t, _, _) => return t.clone(),
+            Module(t) => return t.clone(),
+            Tagged(t) => return t.0.clone(),
             _ => {}
         };
 
         let ty = match self {
             List(_) => YexModule::list(),
+            Array(_) => YexModule::array(),
+            Seq(_) => YexModule::seq(),
             Fn(_) => YexModule::fun(),
             Num(_) => YexModule::num(),
+            Int(_) => YexModule::int(),
             Str(_) => YexModule::str(),
+            Bytes(_) => YexModule::bytes(),
             Bool(_) => YexModule::bool(),
             Nil => YexModule::nil(),
             Sym(_) => YexModule::sym(),
             Tuple(_) => YexModule::tuple(),
+            Dict(_) => YexModule::dict(),
+            Range(_) => YexModule::range(),
+            #[cfg(feature = "std")]
             FFI(_) => YexModule::ffi(),
-            Module(_) | UserData(_) | Tagged(..) => unreachable!(),
+            UserData(_) => YexModule::with_name("UserData"),
+            Module(_) | Tagged(..) => unreachable!(),
         };
 
         GcRef::new(ty)
     }
 }
 
-impl Default for Value {
-    fn default() -> Self {
-        Self::Nil
-    }
-}
-
 type ConstantErr = InterpretResult<Value>;
 
 impl From<Value> for bool {
@@ -253,21 +463,45 @@ impl From<Value> for bool {
     }
 }
 
-impl std::fmt::Display for Value {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Display for Value {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         use Value::*;
         let tk = match self {
             Fn(f) => format!("fn({})", f.arity),
             Nil => "nil".to_string(),
             List(xs) => format!("{}", *xs),
+            Array(xs) => format!("{xs}"),
+            Seq(xs) => format!("{xs}"),
             Str(s) => "\"".to_owned() + s + "\"",
+            Bytes(b) => format!("{b}"),
             Sym(s) => format!("{}", s),
-            Num(n) => n.to_string(),
+            Num(n) => num::format(*n),
+            Int(n) => n.to_string(),
             Module(t) => format!("type '{}'", t.name),
             Tuple(t) => format!("{t}"),
+            Dict(d) => format!("{d}"),
+            Range(r) => format!("{}", **r),
             UserData(u) => format!("<userdata({:?})>", u.type_id()),
+            #[cfg(feature = "std")]
             FFI(f) => f.to_string(),
-            Tagged(_, tag, value) => {
+            Tagged(tagged) if tagged.0.struct_fields().is_some() => {
+                let (ty, tag, value) = &**tagged;
+                write!(f, "{}{{", tag.as_str())?;
+                let fields = ty.struct_fields().unwrap();
+                for (i, (name, item)) in fields.0.iter().zip(value.0.iter()).enumerate() {
+                    if i != 0 {
+                        write!(f, ", ")?;
+                    }
+                    if let Sym(name) = name {
+                        write!(f, "{}: {item}", name.0)?;
+                    }
+                }
+                write!(f, "}}")?;
+
+                return Ok(());
+            }
+            Tagged(tagged) => {
+                let (_, tag, value) = &**tagged;
                 write!(f, "({}", tag.as_str())?;
                 for item in value.0.iter() {
                     write!(f, " {item}")?;
@@ -282,15 +516,33 @@ impl std::fmt::Display for Value {
     }
 }
 
+/// Applies an integer operation, raising an error instead of wrapping around on overflow
+fn int_op(x: i64, y: i64, op: &str, f: fn(i64, i64) -> Option<i64>) -> ConstantErr {
+    match f(x, y) {
+        Some(n) => Ok(Value::Int(n)),
+        None if y == 0 => raise!(ZeroDivisionError, "Cannot divide '{}' by zero", x),
+        None => raise!(
+            OverflowError,
+            "'{} {} {}' overflows a 64 bit integer",
+            x,
+            op,
+            y
+        ),
+    }
+}
+
 macro_rules! impl_numeric {
-    ($($t:ident $op:tt $fn:ident);+$(;)?) => {
+    ($($t:ident $op:tt $fn:ident $checked:ident);+$(;)?) => {
         $(
             impl $t for Value {
                 type Output = ConstantErr;
 
                 fn $fn(self, rhs: Self) -> Self::Output {
                     match (self, rhs) {
+                        (Self::Int(x), Self::Int(y)) => int_op(x, y, stringify!($op), i64::$checked),
                         (Self::Num(x), Self::Num(y)) => Ok(Self::Num(x $op y)),
+                        (Self::Int(x), Self::Num(y)) => Ok(Self::Num(x as f64 $op y)),
+                        (Self::Num(x), Self::Int(y)) => Ok(Self::Num(x $op y as f64)),
                         (Self::Str(x), Self::Str(y)) => Ok(Self::Str(GcRef::new(x.to_string() + &y))),
                         (l, r) => raise!(TypeError, "Cannot apply '{}' operator between '{}' and '{}'", stringify!($t), l, r),
                     }
@@ -301,23 +553,51 @@ macro_rules! impl_numeric {
 }
 
 impl_numeric!(
-    Add + add;
-    Sub - sub;
-    Mul * mul;
-    Div / div;
-    Rem % rem;
+    Add + add checked_add;
+    Sub - sub checked_sub;
+    Mul * mul checked_mul;
+    Rem % rem checked_rem;
 );
 
+/// Division always returns a float, `Int.div` does the integer division
+impl Div for Value {
+    type Output = ConstantErr;
+
+    fn div(self, rhs: Self) -> Self::Output {
+        match (self, rhs) {
+            (Self::Int(x), Self::Int(y)) => Ok(Self::Num(x as f64 / y as f64)),
+            (Self::Num(x), Self::Num(y)) => Ok(Self::Num(x / y)),
+            (Self::Int(x), Self::Num(y)) => Ok(Self::Num(x as f64 / y)),
+            (Self::Num(x), Self::Int(y)) => Ok(Self::Num(x / y as f64)),
+            (Self::Str(x), Self::Str(y)) => Ok(Self::Str(GcRef::new(x.to_string() + &y))),
+            (l, r) => raise!(
+                TypeError,
+                "Cannot apply 'Div' operator between '{}' and '{}'",
+                l,
+                r
+            ),
+        }
+    }
+}
+
+/// Shifts by a negative amount or by more than 63 bits are invalid
+fn shift(x: i64, y: i64, f: fn(i64, u32) -> Option<i64>) -> Option<i64> {
+    u32::try_from(y).ok().and_then(|y| f(x, y))
+}
+
 macro_rules! impl_bit {
-    ($($t:ident $op:tt $opname:literal $fn:ident);+ $(;)? ) => {
+    ($($t:ident $opname:literal $fn:ident $int:expr);+ $(;)? ) => {
         $(
             impl $t for Value {
                 type Output = ConstantErr;
 
                 fn $fn(self, rhs: Self) -> Self::Output {
+                    let f: fn(i64, i64) -> Option<i64> = $int;
                     match (self, rhs) {
-                        (Self::Num(x), Self::Num(y)) if x.fract() == 0.0 && y.fract() == 0.0 => Ok(Self::Num(((x as u64) $op (y as u64)) as f64)),
-                        (Self::Str(x), Self::Str(y)) => Ok(Self::Str(GcRef::new(x.to_string() + &y))),
+                        (Self::Int(x), Self::Int(y)) => match f(x, y) {
+                            Some(n) => Ok(Self::Int(n)),
+                            None => raise!(ValueError, "Cannot shift '{}' by '{}' bits", x, y),
+                        },
                         (l, r) => raise!(TypeError, "Cannot apply '{}' operator between '{}' and '{}'", $opname, l, r),
                     }
                 }
@@ -327,11 +607,11 @@ macro_rules! impl_bit {
 }
 
 impl_bit!(
-    BitAnd & "&&&" bitand;
-    BitOr | "|||" bitor;
-    BitXor ^ "^^^" bitxor;
-    Shl << "<<<" shl;
-    Shr >> ">>>" shr;
+    BitAnd "&&&" bitand |x, y| Some(x & y);
+    BitOr "|||" bitor |x, y| Some(x | y);
+    BitXor "^^^" bitxor |x, y| Some(x ^ y);
+    Shl "<<<" shl |x, y| shift(x, y, i64::checked_shl);
+    Shr ">>>" shr |x, y| shift(x, y, i64::checked_shr);
 );
 
 impl Neg for Value {
@@ -340,6 +620,10 @@ impl Neg for Value {
     fn neg(self) -> Self::Output {
         match self {
             Self::Num(n) => Ok(Self::Num(-n)),
+            Self::Int(n) => match n.checked_neg() {
+                Some(n) => Ok(Self::Int(n)),
+                None => raise!(OverflowError, "'-{}' overflows a 64 bit integer", n),
+            },
             _ => raise!(TypeError, "Cannot apply '-' operator on '{}'", self),
         }
     }
@@ -384,27 +668,76 @@ macro_rules! impl_get {
 }
 
 impl_get!(String: Str (s) => s.to_string());
-impl_get!(f64: Num);
 impl_get!(bool: Bool);
 impl_get!(GcRef<YexModule>: Module);
 impl_get!(GcRef<Fn>: Fn);
 impl_get!(Symbol: Sym(s) => s.0);
 impl_get!(List: List);
-impl_get!(Ffi: FFI);
+impl_get!(Array: Array);
+impl_get!(Seq: Seq);
+impl_get!(Bytes: Bytes);
+#[cfg(feature = "std")]
+impl_get!(Ffi: FFI(f) => (**f).clone());
 impl_get!(Tuple: Tuple);
-impl_get!((GcRef<YexModule>, Symbol, Tuple): Tagged(m, s, t) => (m.clone(), *s, t.clone()));
-impl_get!(usize: Num(n) => {
-    if n.fract() != 0.0 || n.is_nan() || n.is_infinite() || *n < 0.0 {
-        return crate::raise!(ValueError, "Expected a positive integer, got '{}'", n);
+impl_get!(Dict: Dict);
+impl_get!(Range: Range(r) => **r);
+impl_get!(TaggedTuple: Tagged(t) => (**t).clone());
+/// Integers are promoted to floats
+impl TryGet<f64> for Value {
+    fn get(&self) -> InterpretResult<f64> {
+        match self {
+            Value::Num(n) => Ok(*n),
+            Value::Int(n) => Ok(*n as f64),
+            _ => crate::raise!(
+                TypeError,
+                "Unexpected type '{}', expected type was 'Num'",
+                self.type_of().name
+            ),
+        }
     }
+}
 
-    n.round() as usize
-});
+/// Floats are promoted to integers only if they have no fractional part
+fn get_int(value: &Value) -> InterpretResult<i64> {
+    match value {
+        Value::Int(n) => Ok(*n),
+        Value::Num(n) => match exact_int(*n) {
+            Some(n) => Ok(n),
+            None => crate::raise!(ValueError, "Expected an integer, got '{}'", n),
+        },
+        _ => crate::raise!(
+            TypeError,
+            "Unexpected type '{}', expected type was 'Int'",
+            value.type_of().name
+        ),
+    }
+}
 
-impl_get!(isize: Num(n) => {
-    if n.fract() != 0.0 || n.is_nan() || n.is_infinite() {
-        return crate::raise!(ValueError, "Expected an integer, got '{}'", n);
+impl TryGet<i64> for Value {
+    fn get(&self) -> InterpretResult<i64> {
+        get_int(self)
     }
+}
+
+impl TryGet<usize> for Value {
+    fn get(&self) -> InterpretResult<usize> {
+        match usize::try_from(get_int(self)?) {
+            Ok(n) => Ok(n),
+            Err(_) => crate::raise!(ValueError, "Expected a positive integer, got '{}'", self),
+        }
+    }
+}
 
-    n.round() as isize
-});
+impl TryGet<isize> for Value {
+    fn get(&self) -> InterpretResult<isize> {
+        match isize::try_from(get_int(self)?) {
+            Ok(n) => Ok(n),
+            Err(_) => crate::raise!(ValueError, "Integer '{}' is out of range", self),
+        }
+    }
+}
+
+#[test]
+fn two_words() {
+    assert_eq!(mem::size_of::<Value>(), 2 * mem::size_of::<usize>());
+}
@@ -0,0 +1,64 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{error::InterpretResult, raise, Value, VirtualMachine};
+
+use super::TryGet;
+
+/// Formats a float so it's read back as the same float, whole floats keep their `.0` so they
+/// aren't read back as integers
+pub fn format(n: f64) -> String {
+    let s = n.to_string();
+    if n.is_finite() && !s.contains('.') {
+        s + ".0"
+    } else {
+        s
+    }
+}
+
+/// Parses a number the way it's written in yex code, integers can also be written in
+/// hexadecimal (`0xff`) or binary (`0b1010`)
+pub fn parse(s: &str) -> Option<Value> {
+    let s = s.trim();
+    let (sign, digits) = match s.strip_prefix('-') {
+        Some(digits) => (-1, digits),
+        None => (1, s.strip_prefix('+').unwrap_or(s)),
+    };
+
+    let radix = match digits.get(..2) {
+        Some("0x" | "0X") => Some(16),
+        Some("0b" | "0B") => Some(2),
+        _ => None,
+    };
+
+    match radix {
+        Some(radix) => i64::from_str_radix(&digits[2..], radix)
+            .ok()
+            .map(|n| Value::Int(sign * n)),
+        None => s
+            .parse::<i64>()
+            .map(Value::Int)
+            .or_else(|_| s.parse::<f64>().map(Value::Num))
+            .ok(),
+    }
+}
+
+pub fn parse_str(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let s: String = args[0].get()?;
+
+    match parse(&s) {
+        Some(n) => Ok(n),
+        None => raise!(ValueError, "Cannot parse '{}' as a number", s),
+    }
+}
+
+/// Formats `n` with exactly `digits` digits after the decimal point
+pub fn to_str(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let digits: usize = args[0].get()?;
+    let n: f64 = args[1].get()?;
+
+    Ok(format!("{:.*}", digits, n).into())
+}
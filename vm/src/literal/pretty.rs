@@ -0,0 +1,164 @@
+use alloc::{
+    format,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use super::Value;
+
+/// The column where `pretty` starts breaking values into lines
+const WIDTH: usize = 80;
+
+/// Formats `value` like `Display`, but the lists, arrays, tuples, dictionaries and tagged values
+/// that don't fit in a line are broken into one element per line, indented by their depth
+///
+/// Values are immutable, so they can only reference themselves through the few places that are
+/// mutated in place, those references are shown as `<cycle>` instead of being followed forever
+pub fn pretty(value: &Value) -> String {
+    write(value, 0, &mut Vec::new())
+}
+
+/// The delimiters and the elements of a value that holds other values
+struct Parts {
+    open: String,
+    sep: &'static str,
+    close: &'static str,
+    /// The elements, with the text written before each one, like the keys of dictionaries
+    items: Vec<(String, Value)>,
+}
+
+/// Returns the address and the parts of `value`, if it holds other values
+fn parts(value: &Value) -> Option<(usize, Parts)> {
+    let parts = |open: String, sep, close, items| Parts {
+        open,
+        sep,
+        close,
+        items,
+    };
+    let unkeyed = |xs: &mut dyn Iterator<Item = Value>| xs.map(|x| (String::new(), x)).collect();
+
+    match value {
+        Value::List(xs) if !xs.is_empty() => Some((
+            xs.addr(),
+            parts("[".into(), ", ", "]", unkeyed(&mut xs.iter())),
+        )),
+        Value::Array(xs) if !xs.is_empty() => Some((
+            xs.addr(),
+            parts("#[".into(), ", ", "]", unkeyed(&mut xs.iter())),
+        )),
+        Value::Tuple(t) if !t.is_empty() => Some((
+            t.0.addr(),
+            parts("(".into(), ", ", ")", unkeyed(&mut t.0.iter().cloned())),
+        )),
+        Value::Dict(d) if !d.is_empty() => {
+            let items = d.iter().map(|(key, value)| {
+                let key = match key {
+                    Value::Sym(sym) => format!("{}: ", sym.as_str()),
+                    other => format!("{} => ", other),
+                };
+                (key, value.clone())
+            });
+            Some((d.addr(), parts("%{".into(), ", ", "}", items.collect())))
+        }
+        Value::Tagged(tagged) if !tagged.2.is_empty() => {
+            let (ty, tag, tup) = &**tagged;
+            let parts = match ty.struct_fields() {
+                Some(fields) => {
+                    let items = fields
+                        .0
+                        .iter()
+                        .zip(tup.0.iter())
+                        .map(|(name, item)| (format!("{}: ", show_field(name)), item.clone()));
+                    parts(format!("{}{{", tag.as_str()), ", ", "}", items.collect())
+                }
+                None => parts(
+                    format!("({} ", tag.as_str()),
+                    " ",
+                    ")",
+                    unkeyed(&mut tup.0.iter().cloned()),
+                ),
+            };
+            Some((tagged.addr(), parts))
+        }
+        _ => None,
+    }
+}
+
+fn show_field(name: &Value) -> String {
+    match name {
+        Value::Sym(name) => name.as_str().to_string(),
+        other => other.to_string(),
+    }
+}
+
+/// Formats `value` in a single line
+fn flat(value: &Value, seen: &mut Vec<usize>) -> String {
+    let (addr, parts) = match parts(value) {
+        Some((addr, _)) if seen.contains(&addr) => return "<cycle>".into(),
+        Some(parts) => parts,
+        None => return value.to_string(),
+    };
+
+    seen.push(addr);
+    let items: Vec<_> = parts
+        .items
+        .iter()
+        .map(|(key, item)| format!("{}{}", key, flat(item, seen)))
+        .collect();
+    seen.pop();
+
+    format!("{}{}{}", parts.open, items.join(parts.sep), parts.close)
+}
+
+/// Formats `value`, starting at the column `indent`
+fn write(value: &Value, indent: usize, seen: &mut Vec<usize>) -> String {
+    let line = flat(value, seen);
+    if indent + line.len() <= WIDTH {
+        return line;
+    }
+
+    let (addr, parts) = match parts(value) {
+        Some((addr, _)) if seen.contains(&addr) => return "<cycle>".into(),
+        Some(parts) => parts,
+        None => return line,
+    };
+
+    seen.push(addr);
+    let mut out = String::from(parts.open.trim_end());
+    let last = parts.items.len() - 1;
+    for (i, (key, item)) in parts.items.iter().enumerate() {
+        out.push('\n');
+        out.push_str(&" ".repeat(indent + 2));
+        out.push_str(key);
+        out.push_str(&write(item, indent + 2, seen));
+        if i != last {
+            out.push_str(parts.sep.trim_end());
+        }
+    }
+    seen.pop();
+
+    out.push('\n');
+    out.push_str(&" ".repeat(indent));
+    out.push_str(parts.close);
+    out
+}
+
+#[test]
+fn breaks_long_values() {
+    let short = Value::from(vec![Value::Int(1), Value::Nil]);
+    assert_eq!(pretty(&short), "(1, nil)");
+
+    let long: Value = crate::List::from_iter((0..30).map(Value::Int)).into();
+    assert_eq!(pretty(&long).lines().count(), 32);
+    assert!(pretty(&long).starts_with("[\n  29,\n  28,"));
+}
+
+#[test]
+fn cycles() {
+    let mut tup = crate::Tuple::from(vec![Value::Nil]);
+    let this = Value::Tuple(tup.clone());
+    // SAFETY: nothing else reads the tuple while it's changed
+    unsafe { tup.0.mut_ref()[0] = this };
+
+    assert_eq!(pretty(&Value::Tuple(tup)), "(<cycle>)");
+}
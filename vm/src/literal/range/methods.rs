@@ -0,0 +1,32 @@
+use alloc::vec::Vec;
+
+use crate::{
+    error::InterpretResult,
+    literal::{exact_int, TryGet, Value},
+    raise, List, VirtualMachine,
+};
+
+use super::Range;
+
+pub fn to_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let range: Range = args[0].get()?;
+    Ok(range.iter().rev().map(Value::Int).collect::<List>().into())
+}
+
+pub fn len(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let range: Range = args[0].get()?;
+    match i64::try_from(range.len()) {
+        Ok(n) => Ok(n.into()),
+        Err(_) => raise!(OverflowError, "The length of '{}' overflows", range),
+    }
+}
+
+pub fn has(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let range: Range = args[1].get()?;
+    let n = match args[0] {
+        Value::Int(n) => Some(n),
+        Value::Num(n) => exact_int(n),
+        _ => None,
+    };
+    Ok(n.is_some_and(|n| range.iter().contains(&n)).into())
+}
@@ -0,0 +1,112 @@
+pub mod methods;
+
+use core::{fmt, ops::RangeInclusive};
+
+use crate::gc::Trace;
+
+/// A lazy range of integers, from `start` up to `end`, which is only included in inclusive
+/// ranges, the elements are only computed while iterating it
+#[derive(Debug, Clone, Copy)]
+pub struct Range {
+    /// The first element
+    pub start: i64,
+    /// The end of the range
+    pub end: i64,
+    /// If `end` is included in the range
+    pub inclusive: bool,
+}
+
+impl Trace for Range {
+    const LEAF: bool = true;
+}
+
+impl Range {
+    /// Creates the range `start..end`
+    pub fn new(start: i64, end: i64) -> Self {
+        Self {
+            start,
+            end,
+            inclusive: false,
+        }
+    }
+
+    /// Creates the range `start..=end`
+    pub fn inclusive(start: i64, end: i64) -> Self {
+        Self {
+            start,
+            end,
+            inclusive: true,
+        }
+    }
+
+    /// Returns the first and the last elements, or `None` if the range is empty
+    pub fn bounds(&self) -> Option<(i64, i64)> {
+        let last = if self.inclusive {
+            self.end
+        } else {
+            self.end.checked_sub(1)?
+        };
+
+        (self.start <= last).then_some((self.start, last))
+    }
+
+    /// Returns the number of elements, saturating at `usize::MAX`
+    pub fn len(&self) -> usize {
+        match self.bounds() {
+            Some((first, last)) => (last.abs_diff(first) as usize).saturating_add(1),
+            None => 0,
+        }
+    }
+
+    /// Checks if the range has no elements
+    pub fn is_empty(&self) -> bool {
+        self.bounds().is_none()
+    }
+
+    /// Iterates over the elements
+    #[allow(clippy::reversed_empty_ranges)]
+    pub fn iter(&self) -> RangeInclusive<i64> {
+        match self.bounds() {
+            Some((first, last)) => first..=last,
+            None => 1..=0,
+        }
+    }
+}
+
+/// Ranges are equal when they have the same elements, so `0..3` is `0..=2`
+impl PartialEq for Range {
+    fn eq(&self, other: &Self) -> bool {
+        self.bounds() == other.bounds()
+    }
+}
+
+impl Eq for Range {}
+
+impl fmt::Display for Range {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = if self.inclusive { "..=" } else { ".." };
+        write!(f, "{}{}{}", self.start, op, self.end)
+    }
+}
+
+#[test]
+fn range_len() {
+    assert_eq!(Range::new(1, 10).len(), 9);
+    assert_eq!(Range::inclusive(1, 10).len(), 10);
+    assert!(Range::new(5, 1).is_empty());
+    assert!(Range::new(i64::MIN, i64::MIN).is_empty());
+    assert_eq!(Range::inclusive(0, i64::MAX).len(), i64::MAX as usize + 1);
+    assert_eq!(Range::inclusive(i64::MAX, i64::MAX).iter().count(), 1);
+    assert_eq!(Range::new(i64::MIN, i64::MAX).len(), usize::MAX);
+}
+
+#[test]
+fn range_eq() {
+    assert_eq!(Range::new(0, 3), Range::inclusive(0, 2));
+    assert_eq!(Range::new(5, 1), Range::inclusive(1, 0));
+    assert_ne!(Range::new(0, 3), Range::inclusive(0, 3));
+    assert_eq!(
+        Range::inclusive(0, i64::MAX).to_string(),
+        "0..=9223372036854775807"
+    );
+}
@@ -1,14 +1,15 @@
 use crate::{error::InterpretResult, gc::GcRef, Symbol, Tuple, Value, VirtualMachine, YexModule};
+use alloc::vec::Vec;
 
 pub fn ok(args: Vec<Value>) -> Value {
     let this: GcRef<YexModule> = GcRef::new(YexModule::default());
     let tup = Tuple(GcRef::new(args.into_boxed_slice()));
-    Value::Tagged(this, Symbol::from("Result.ok"), tup)
+    Value::tagged(this, Symbol::from("Result.ok"), tup)
 }
 pub fn fail(args: Vec<Value>) -> Value {
     let this: GcRef<YexModule> = GcRef::new(YexModule::default());
     let tup = Tuple(GcRef::new(args.into_boxed_slice()));
-    Value::Tagged(this, Symbol::from("Result.fail"), tup)
+    Value::tagged(this, Symbol::from("Result.fail"), tup)
 }
 pub fn vm_ok(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     Ok(ok(args))
@@ -0,0 +1,146 @@
+#[cfg(feature = "std")]
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use crate::{
+    error::InterpretResult,
+    generator::generator_of,
+    literal::{list::Items, nil, TryGet},
+    List, Value, VirtualMachine,
+};
+
+use super::{Seq, Stage};
+
+/// Turns a list, an array, a range or a generator into a sequence, sequences are returned as they
+/// are
+fn seq(value: &Value) -> InterpretResult<Seq> {
+    match value {
+        Value::Seq(xs) => Ok(xs.clone()),
+        Value::UserData(_) if generator_of(value).is_ok() => {
+            Ok(Seq::new(Stage::Generator(value.clone())))
+        }
+        other => {
+            // checks the type now, instead of when the sequence is consumed
+            Items::new(other)?;
+            Ok(Seq::new(Stage::Source(other.clone())))
+        }
+    }
+}
+
+pub fn from(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(seq(&args[0])?.into())
+}
+
+pub fn iterate(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Seq::new(Stage::Iterate(args[0].clone(), args[1].clone())).into())
+}
+
+#[cfg(feature = "std")]
+pub fn lines(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let path: String = args[0].get()?;
+    Ok(Seq::new(Stage::Lines(path)).into())
+}
+
+pub fn map(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Seq::new(Stage::Map(args[0].clone(), seq(&args[1])?)).into())
+}
+
+pub fn filter(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Seq::new(Stage::Filter(args[0].clone(), seq(&args[1])?)).into())
+}
+
+pub fn take(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Seq::new(Stage::Take(args[0].get()?, seq(&args[1])?)).into())
+}
+
+pub fn drop(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Seq::new(Stage::Drop(args[0].get()?, seq(&args[1])?)).into())
+}
+
+pub fn take_while(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Seq::new(Stage::TakeWhile(args[0].clone(), seq(&args[1])?)).into())
+}
+
+pub fn to_list(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let xs = seq(&args[0])?;
+
+    let mut cursor = xs.cursor()?;
+    let mut ys = List::new();
+    while let Some(x) = cursor.next(vm)? {
+        ys = ys.prepend(x);
+    }
+
+    Ok(ys.rev().into())
+}
+
+pub fn fold(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = args[0].clone();
+    let mut acc = args[1].clone();
+    let xs = seq(&args[2])?;
+
+    let mut cursor = xs.cursor()?;
+    while let Some(x) = cursor.next(vm)? {
+        vm.push(x);
+        vm.push(acc);
+        vm.push(fun.clone());
+        vm.call(2)?;
+        acc = vm.pop();
+    }
+
+    Ok(acc)
+}
+
+pub fn find(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = &args[0];
+    let xs = seq(&args[1])?;
+
+    let mut cursor = xs.cursor()?;
+    while let Some(x) = cursor.next(vm)? {
+        vm.push(x.clone());
+        vm.push(fun.clone());
+        vm.call(1)?;
+
+        if vm.pop().to_bool() {
+            return Ok(x);
+        }
+    }
+
+    Ok(nil())
+}
+
+pub fn each(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = &args[0];
+    let xs = seq(&args[1])?;
+
+    let mut cursor = xs.cursor()?;
+    while let Some(x) = cursor.next(vm)? {
+        vm.push(x);
+        vm.push(fun.clone());
+        vm.call(1)?;
+        vm.pop();
+    }
+
+    Ok(nil())
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn replay_lines() {
+    let mut vm = VirtualMachine::default();
+    let trace = "fileLine \"first\"\nfileLine \"second\"\nfileLine nil\n";
+    vm.set_trace(crate::Trace::replay(trace).unwrap());
+
+    // the file doesn't exist, the lines come from the trace
+    let xs = lines(&mut vm, vec!["missing.txt".to_string().into()]).unwrap();
+    let xs = to_list(&mut vm, vec![xs]).unwrap();
+    let expected: List = ["first", "second"]
+        .into_iter()
+        .rev()
+        .map(|line| Value::from(line.to_string()))
+        .collect();
+    assert_eq!(xs, expected.into());
+}
@@ -0,0 +1,217 @@
+pub(crate) mod methods;
+
+use alloc::boxed::Box;
+#[cfg(feature = "std")]
+use alloc::string::String;
+use core::fmt;
+#[cfg(feature = "std")]
+use std::io::BufRead;
+
+use crate::{
+    error::InterpretResult,
+    gc::{GcRef, Trace, Tracer},
+    generator::generator_of,
+    literal::{list::Items, Value},
+    VirtualMachine,
+};
+
+/// A step of a lazy sequence, each one wraps the sequence it reads from
+pub enum Stage {
+    /// The elements of a list, an array or a range
+    Source(Value),
+    /// The values a generator yields, it's resumed while the sequence is consumed, so the
+    /// sequence only has the values the generator didn't yield yet
+    Generator(Value),
+    /// `x`, `f x`, `f (f x)`, and so on, forever
+    Iterate(Value, Value),
+    /// The lines of a file, read as they're needed
+    #[cfg(feature = "std")]
+    Lines(String),
+    Map(Value, Seq),
+    Filter(Value, Seq),
+    Take(usize, Seq),
+    Drop(usize, Seq),
+    TakeWhile(Value, Seq),
+}
+
+/// A yex lazy sequence, which only computes its elements while it's consumed, so chaining
+/// `Seq.map` and `Seq.filter` doesn't build the lists in between
+///
+/// Sequences are only a description of the steps, consuming one twice runs the steps twice
+#[derive(Clone)]
+pub struct Seq(pub GcRef<Stage>);
+
+impl Trace for Stage {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        match self {
+            Stage::Source(value) | Stage::Generator(value) => value.trace(tracer),
+            Stage::Iterate(x, f) => {
+                x.trace(tracer);
+                f.trace(tracer);
+            }
+            #[cfg(feature = "std")]
+            Stage::Lines(_) => {}
+            Stage::Map(f, seq) | Stage::Filter(f, seq) | Stage::TakeWhile(f, seq) => {
+                f.trace(tracer);
+                seq.trace(tracer);
+            }
+            Stage::Take(_, seq) | Stage::Drop(_, seq) => seq.trace(tracer),
+        }
+    }
+}
+
+impl Trace for Seq {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        tracer.visit(&self.0);
+    }
+}
+
+impl Seq {
+    /// Creates a sequence from one of its steps
+    pub fn new(stage: Stage) -> Self {
+        Self(GcRef::new(stage))
+    }
+
+    /// Starts consuming the sequence
+    pub fn cursor(&self) -> InterpretResult<Cursor<'_>> {
+        let cursor = match &*self.0 {
+            Stage::Source(xs) => Cursor::Items(Items::new(xs)?),
+            Stage::Generator(generator) => Cursor::Generator(generator),
+            Stage::Iterate(f, x) => Cursor::Iterate(f, x.clone(), false),
+            #[cfg(feature = "std")]
+            Stage::Lines(path) => Cursor::Lines(path, None),
+            Stage::Map(f, xs) => Cursor::Map(f, Box::new(xs.cursor()?)),
+            Stage::Filter(f, xs) => Cursor::Filter(f, Box::new(xs.cursor()?)),
+            Stage::Take(n, xs) => Cursor::Take(*n, Box::new(xs.cursor()?)),
+            Stage::Drop(n, xs) => Cursor::Drop(*n, Box::new(xs.cursor()?)),
+            Stage::TakeWhile(f, xs) => Cursor::TakeWhile(f, Box::new(xs.cursor()?)),
+        };
+
+        Ok(cursor)
+    }
+}
+
+impl PartialEq for Seq {
+    fn eq(&self, other: &Self) -> bool {
+        core::ptr::eq(&*self.0, &*other.0)
+    }
+}
+
+impl fmt::Debug for Seq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<seq>")
+    }
+}
+
+impl fmt::Display for Seq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "<seq>")
+    }
+}
+
+/// The state of a sequence being consumed, the functions of the steps are called by `next`
+pub enum Cursor<'a> {
+    Items(Items<'a>),
+    Generator(&'a Value),
+    /// The function, the last element and if it was already returned
+    Iterate(&'a Value, Value, bool),
+    #[cfg(feature = "std")]
+    /// The file is only opened by the first line, so replaying a trace doesn't read it
+    Lines(
+        &'a str,
+        Option<std::io::Lines<std::io::BufReader<std::fs::File>>>,
+    ),
+    Map(&'a Value, Box<Cursor<'a>>),
+    Filter(&'a Value, Box<Cursor<'a>>),
+    Take(usize, Box<Cursor<'a>>),
+    Drop(usize, Box<Cursor<'a>>),
+    TakeWhile(&'a Value, Box<Cursor<'a>>),
+    /// A sequence that already ended
+    Done,
+}
+
+/// Calls `f` with `x`
+fn apply(vm: &mut VirtualMachine, f: &Value, x: Value) -> InterpretResult<Value> {
+    vm.push(x);
+    vm.push(f.clone());
+    vm.call(1)?;
+    Ok(vm.pop())
+}
+
+impl Cursor<'_> {
+    /// Returns the next element, or `None` when the sequence ends
+    pub fn next(&mut self, vm: &mut VirtualMachine) -> InterpretResult<Option<Value>> {
+        match self {
+            Cursor::Items(xs) => Ok(xs.next()),
+            Cursor::Done => Ok(None),
+            Cursor::Generator(generator) => vm.resume_generator(generator_of(generator)?),
+            Cursor::Iterate(f, x, started) => {
+                // the next element is only computed when it's asked for
+                if *started {
+                    *x = apply(vm, f, x.clone())?;
+                }
+                *started = true;
+                Ok(Some(x.clone()))
+            }
+            #[cfg(feature = "std")]
+            Cursor::Lines(path, lines) => {
+                let line = vm.nondeterministic("fileLine", || {
+                    if lines.is_none() {
+                        match std::fs::File::open(*path) {
+                            Ok(file) => *lines = Some(std::io::BufReader::new(file).lines()),
+                            Err(e) => crate::raise!(IOError, "Can't open '{}': {}", path, e)?,
+                        }
+                    }
+
+                    match lines.as_mut().unwrap().next() {
+                        Some(Ok(line)) => Ok(line.into()),
+                        Some(Err(e)) => crate::raise!(IOError, "Can't read a line: {}", e),
+                        None => Ok(Value::Nil),
+                    }
+                })?;
+
+                Ok((!line.is_nil()).then_some(line))
+            }
+            Cursor::Map(f, xs) => match xs.next(vm)? {
+                Some(x) => apply(vm, f, x).map(Some),
+                None => Ok(None),
+            },
+            Cursor::Filter(f, xs) => {
+                while let Some(x) = xs.next(vm)? {
+                    if apply(vm, f, x.clone())?.to_bool() {
+                        return Ok(Some(x));
+                    }
+                }
+                Ok(None)
+            }
+            Cursor::Take(n, xs) => {
+                if *n == 0 {
+                    return Ok(None);
+                }
+                *n -= 1;
+                xs.next(vm)
+            }
+            Cursor::Drop(n, xs) => {
+                while *n > 0 {
+                    *n -= 1;
+                    if xs.next(vm)?.is_none() {
+                        return Ok(None);
+                    }
+                }
+                xs.next(vm)
+            }
+            Cursor::TakeWhile(f, xs) => {
+                let x = match xs.next(vm)? {
+                    Some(x) if apply(vm, f, x.clone())?.to_bool() => Some(x),
+                    _ => None,
+                };
+
+                // the elements after the first one that fails aren't taken, even if they pass
+                if x.is_none() {
+                    *self = Cursor::Done;
+                }
+                Ok(x)
+            }
+        }
+    }
+}
@@ -4,6 +4,11 @@ use crate::{
     literal::{nil, TryGet},
     raise, List, Value, VirtualMachine,
 };
+use alloc::{
+    borrow::ToOwned,
+    string::{String, ToString},
+    vec::Vec,
+};
 
 pub fn get(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let string: String = args[1].get()?;
@@ -32,7 +37,7 @@ pub fn split(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value>
 pub fn len(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let str: String = args[0].get()?;
 
-    Ok((str.len() as f64).into())
+    Ok((str.len() as i64).into())
 }
 
 pub fn chars(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
@@ -49,7 +54,7 @@ pub fn ord(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
         raise!(ValueError, "Expected a character for 'ord'")?;
     }
 
-    Ok(Value::Num(str.as_bytes()[0].into()))
+    Ok(Value::Int(str.as_bytes()[0].into()))
 }
 
 pub fn chr(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
@@ -63,6 +68,69 @@ pub fn chr(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
 
     Ok(code.into())
 }
+
+pub fn strip_prefix(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let prefix: String = args[0].get()?;
+    let string: String = args[1].get()?;
+
+    Ok(string
+        .strip_prefix(&prefix)
+        .map(|rest| rest.to_string().into())
+        .unwrap_or_else(nil))
+}
+
+pub fn trim(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let string: String = args[0].get()?;
+    Ok(string.trim().to_string().into())
+}
+
+pub fn replace(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let from: String = args[0].get()?;
+    let to: String = args[1].get()?;
+    let string: String = args[2].get()?;
+
+    if from.is_empty() {
+        raise!(ValueError, "Can't replace an empty string")?;
+    }
+
+    Ok(string.replace(&from, &to).into())
+}
+
+pub fn contains(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let pattern: String = args[0].get()?;
+    let string: String = args[1].get()?;
+
+    Ok(string.contains(&pattern).into())
+}
+
+pub fn find(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let pattern: String = args[0].get()?;
+    let string: String = args[1].get()?;
+
+    // the index is in characters, like the one used by `Str.get`
+    Ok(string
+        .find(&pattern)
+        .map(|index| Value::Int(string[..index].chars().count() as i64))
+        .unwrap_or_else(nil))
+}
+
+pub fn to_upper(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let string: String = args[0].get()?;
+    Ok(string.to_uppercase().into())
+}
+
+pub fn to_lower(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let string: String = args[0].get()?;
+    Ok(string.to_lowercase().into())
+}
+
+pub fn bytes(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let string: String = args[0].get()?;
+    let iter = string.bytes().map(|b| Value::Int(b.into()));
+
+    Ok(List::from_iter(iter.rev()).into())
+}
+
 pub fn new(_: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
     Ok(Value::Str(GcRef::new(String::from(""))))
 }
@@ -1,40 +1,94 @@
-use std::{
+#[cfg(feature = "std")]
+use alloc::collections::BTreeMap;
+use alloc::{boxed::Box, string::String};
+use core::{
     fmt::{self, Formatter},
     hash::{Hash, Hasher},
     ops::Deref,
+    ptr,
 };
+#[cfg(feature = "std")]
+use std::sync::Mutex;
 
-/// Symbol struct, contains the symbol string and a pre-hashed value for faster comparison
-#[derive(Clone, Copy, Default)]
-pub struct Symbol {
+/// The string of a symbol and its hash, leaked once per distinct symbol
+struct Interned {
     string: &'static str,
-    pub(crate) hash: usize,
+    hash: usize,
+}
+
+static EMPTY: Interned = Interned {
+    string: "",
+    hash: 0,
+};
+
+/// Every symbol created, so creating the same symbol again reuses it instead of leaking a new
+/// one, and equal symbols point to the same place
+#[cfg(feature = "std")]
+static INTERNED: Mutex<BTreeMap<&'static str, &'static Interned>> = Mutex::new(BTreeMap::new());
+
+fn leak(string: String, hash: usize) -> &'static Interned {
+    Box::leak(Box::new(Interned {
+        string: Box::leak(string.into_boxed_str()),
+        hash,
+    }))
+}
+
+#[cfg(feature = "std")]
+fn intern(string: String, hash: usize) -> &'static Interned {
+    let mut interned = INTERNED.lock().unwrap_or_else(|e| e.into_inner());
+
+    match interned.get(string.as_str()) {
+        Some(interned) => interned,
+        None => {
+            let new = leak(string, hash);
+            interned.insert(new.string, new);
+            new
+        }
+    }
+}
+
+#[cfg(not(feature = "std"))]
+fn intern(string: String, hash: usize) -> &'static Interned {
+    leak(string, hash)
+}
+
+/// Symbol struct, points to the interned symbol string and a pre-hashed value for faster
+/// comparison, it's a single pointer, so values holding symbols stay small
+#[derive(Clone, Copy)]
+pub struct Symbol(&'static Interned);
+
+impl Default for Symbol {
+    fn default() -> Self {
+        Self(&EMPTY)
+    }
 }
 
 impl Hash for Symbol {
     fn hash<H: Hasher>(&self, state: &mut H) {
-        state.write_usize(self.hash)
+        state.write_usize(self.0.hash)
     }
 }
 
-impl std::cmp::PartialEq for Symbol {
+impl core::cmp::PartialEq for Symbol {
     fn eq(&self, rhs: &Self) -> bool {
-        self.hash == rhs.hash
+        // interned symbols are the same pointer, the strings are only compared when the hashes
+        // collide
+        ptr::eq(self.0, rhs.0) || (self.0.hash == rhs.0.hash && self.0.string == rhs.0.string)
     }
 }
 
 impl Eq for Symbol {}
 
-impl std::fmt::Display for Symbol {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{}", self.string)?;
+impl core::fmt::Display for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "{}", self.0.string)?;
         Ok(())
     }
 }
 
-impl std::fmt::Debug for Symbol {
-    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-        write!(f, "Symbol({})", self.string)
+impl core::fmt::Debug for Symbol {
+    fn fmt(&self, f: &mut Formatter<'_>) -> core::fmt::Result {
+        write!(f, "Symbol({})", self.0.string)
     }
 }
 
@@ -49,15 +103,17 @@ impl Symbol {
             hash = hash.wrapping_mul(16777619);
         }
 
-        Self {
-            string: Box::leak(str.into_boxed_str()),
-            hash,
-        }
+        Self(intern(str, hash))
     }
 
     /// Returns the intern symbol str
-    pub fn as_str(&self) -> &str {
-        self.string
+    pub fn as_str(&self) -> &'static str {
+        self.0.string
+    }
+
+    /// The pre-computed hash of the symbol
+    pub(crate) fn hash(&self) -> usize {
+        self.0.hash
     }
 }
 
@@ -89,3 +145,14 @@ impl Deref for YexSymbol {
         &self.0
     }
 }
+
+#[test]
+#[cfg(feature = "std")]
+fn interning() {
+    let a = Symbol::new("interned");
+    let b = Symbol::new(String::from("interned"));
+
+    assert!(ptr::eq(a.as_str(), b.as_str()));
+    assert_eq!(a, b);
+    assert_ne!(a, Symbol::new("other"));
+}
@@ -1,9 +1,10 @@
-use std::fmt::Write;
+use alloc::{string::String, vec::Vec};
+use core::fmt::Write;
 
 use crate::{
     error::InterpretResult,
     literal::{nil, TryGet},
-    Value, VirtualMachine,
+    List, Value, VirtualMachine,
 };
 
 use super::Tuple;
@@ -15,6 +16,16 @@ pub fn get(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     Ok(tup.0.get(idx).cloned().unwrap_or_else(nil))
 }
 
+pub fn len(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let tup: Tuple = args[0].get()?;
+    Ok((tup.len() as i64).into())
+}
+
+pub fn to_list(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let tup: Tuple = args[0].get()?;
+    Ok(tup.0.iter().rev().cloned().collect::<List>().into())
+}
+
 pub fn show(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
     let xs: Tuple = args[0].get()?;
 
@@ -24,7 +35,7 @@ pub fn show(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value>
         write!(s, "{}, ", super::super::show(vm, vec![x.clone()])?).unwrap();
     }
 
-    if xs.0.len() > 0 {
+    if !xs.0.is_empty() {
         s.pop();
         s.pop();
     }
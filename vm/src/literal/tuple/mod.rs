@@ -1,11 +1,27 @@
 pub mod methods;
 
-use crate::{gc::GcRef, Value};
+use crate::{
+    gc::{GcRef, Trace, Tracer},
+    Value,
+};
+use alloc::{boxed::Box, string::ToString, vec::Vec};
 
 #[derive(Debug, PartialEq, Clone)]
 /// A yex tuple
 pub struct Tuple(pub GcRef<Box<[Value]>>);
 
+impl Trace for Box<[Value]> {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        self.iter().for_each(|value| value.trace(tracer));
+    }
+}
+
+impl Trace for Tuple {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        tracer.visit(&self.0);
+    }
+}
+
 impl From<Vec<Value>> for Tuple {
     fn from(vec: Vec<Value>) -> Self {
         Tuple(GcRef::new(vec.into_boxed_slice()))
@@ -24,8 +40,8 @@ impl Tuple {
     }
 }
 
-impl std::fmt::Display for Tuple {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Display for Tuple {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(
             f,
             "({})",
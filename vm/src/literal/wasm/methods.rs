@@ -0,0 +1,42 @@
+use std::{cell::RefCell, path::Path};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{result, TryGet},
+    raise, Symbol, Value, VirtualMachine,
+};
+
+use super::Plugin;
+
+pub fn open(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let path: String = args[0].get()?;
+    let name = Path::new(&path)
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| path.clone());
+
+    match Plugin::open(&path, vm)? {
+        Ok(plugin) => Ok(result::ok(vec![Value::Module(GcRef::new(super::module(
+            Symbol::new(name),
+            plugin,
+        )))])),
+        Err(e) => Ok(result::fail(vec![e.to_string().into()])),
+    }
+}
+
+/// Calls a plugin function, the plugin and the function name are bound as the first arguments
+pub fn call(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let name: String = args[1].get()?;
+    let plugin = match &args[0] {
+        Value::UserData(data) => data.downcast_ref::<RefCell<Plugin>>(),
+        _ => None,
+    };
+
+    match plugin {
+        Some(plugin) => plugin.borrow_mut().call(&name, &args[2..], vm),
+        None => raise!(TypeError, "Expected a WASM plugin"),
+    }
+}
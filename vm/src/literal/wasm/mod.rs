@@ -0,0 +1,436 @@
+//! WASM plugins, values are exchanged with the guest using a small binary encoding:
+//!
+//! | tag | value                                 |
+//! |-----|---------------------------------------|
+//! | 0   | `nil`                                 |
+//! | 1   | `false`                               |
+//! | 2   | `true`                                |
+//! | 3   | `Num`, followed by a little-endian f64 |
+//! | 4   | `Str`, followed by a u32 length and the utf-8 bytes |
+//! | 5   | `Sym`, encoded like `Str`             |
+//! | 6   | `List`, followed by a u32 length and the elements |
+//! | 7   | `Tuple`, encoded like `List`          |
+//! | 8   | `Int`, followed by a little-endian i64 |
+//!
+//! A plugin must export it's `memory`, an allocator `yex_alloc(len: i32) -> i32` and the functions
+//! exposed to yex as `yex_fn_<name>(ptr: i32, len: i32) -> i64`. The functions receive a tuple with
+//! all the arguments and return the result pointer and length packed as `(ptr << 32) | len`. The
+//! arity of `<name>` can be set exporting an i32 global called `yex_arity_<name>`, it defaults to 1.
+//!
+//! The plugins run with the limits of the vm that calls them: the WASM instructions take the fuel
+//! and count for `max_instructions`, the deadline of the run interrupts them, and the values they
+//! return can't be larger than their memory, the heap left to the script or [`MAX_DEPTH`] levels.
+pub(crate) mod methods;
+
+use std::{
+    cell::RefCell,
+    path::Path,
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::Instant,
+};
+
+use wasmtime::{Config, Engine, Instance, Memory, Module, Store, Trap, TypedFunc, Val};
+
+use crate::{
+    error::InterpretResult, gc::GcRef, literal::ffi::userdata::UserData, raise, raise_err,
+    stackvec, Fn, FnKind, List, Symbol, Tuple, Value, VirtualMachine, YexModule,
+};
+
+const FN_PREFIX: &str = "yex_fn_";
+const ARITY_PREFIX: &str = "yex_arity_";
+
+/// How deep the lists and tuples returned by a plugin can be nested
+pub const MAX_DEPTH: usize = 256;
+
+/// A loaded WASM plugin
+pub struct Plugin {
+    engine: Engine,
+    store: Store<()>,
+    instance: Instance,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+}
+
+impl Plugin {
+    /// Loads a plugin from a `.wasm` (or `.wat`) file, its start function runs with the limits
+    /// of `vm`, which raise an error if it exceeds them
+    pub fn open(
+        path: impl AsRef<Path>,
+        vm: &mut VirtualMachine,
+    ) -> InterpretResult<wasmtime::Result<Self>> {
+        let mut config = Config::new();
+        config.consume_fuel(true).epoch_interruption(true);
+        let engine = match Engine::new(&config) {
+            Ok(engine) => engine,
+            Err(e) => return Ok(Err(e)),
+        };
+        let module = match Module::from_file(&engine, path) {
+            Ok(module) => module,
+            Err(e) => return Ok(Err(e)),
+        };
+
+        let mut store = Store::new(&engine, ());
+        let instance = limited(&engine, &mut store, vm, |store| {
+            Instance::new(store, &module, &[])
+        })?;
+
+        Ok(Self::new(engine, store, instance))
+    }
+
+    fn new(
+        engine: Engine,
+        mut store: Store<()>,
+        instance: wasmtime::Result<Instance>,
+    ) -> wasmtime::Result<Self> {
+        let instance = instance?;
+
+        let memory = instance
+            .get_memory(&mut store, "memory")
+            .ok_or_else(|| wasmtime::Error::msg("plugin doesn't export it's memory"))?;
+        let alloc = instance.get_typed_func(&mut store, "yex_alloc")?;
+
+        Ok(Self {
+            engine,
+            store,
+            instance,
+            memory,
+            alloc,
+        })
+    }
+
+    /// Returns the name and the arity of every function exported by the plugin
+    pub fn exports(&mut self) -> Vec<(String, usize)> {
+        let names: Vec<String> = self
+            .instance
+            .exports(&mut self.store)
+            .filter(|export| export.name().starts_with(FN_PREFIX))
+            .filter(|export| export.clone().into_func().is_some())
+            .map(|export| export.name()[FN_PREFIX.len()..].to_string())
+            .collect();
+
+        names
+            .into_iter()
+            .map(|name| {
+                let arity = self
+                    .instance
+                    .get_global(&mut self.store, &format!("{ARITY_PREFIX}{name}"))
+                    .map(|global| global.get(&mut self.store));
+
+                match arity {
+                    Some(Val::I32(n)) if n > 0 => (name, n as usize),
+                    _ => (name, 1),
+                }
+            })
+            .collect()
+    }
+
+    /// Calls the exported function `name` with the limits of `vm`
+    pub fn call(
+        &mut self,
+        name: &str,
+        args: &[Value],
+        vm: &mut VirtualMachine,
+    ) -> InterpretResult<Value> {
+        let fun: TypedFunc<(i32, i32), i64> = self
+            .instance
+            .get_typed_func(&mut self.store, &format!("{FN_PREFIX}{name}"))
+            .map_err(|e| raise_err!(WasmError, "{}", e))?;
+
+        let mut bytes = vec![];
+        encode(&Value::Tuple(args.to_vec().into()), &mut bytes)?;
+
+        let alloc = self.alloc.clone();
+        let ptr = limited(&self.engine, &mut self.store, vm, |store| {
+            alloc.call(store, bytes.len() as i32)
+        })?
+        .map_err(|e| raise_err!(WasmError, "{}", e))?;
+
+        self.memory
+            .write(&mut self.store, ptr as usize, &bytes)
+            .map_err(|e| raise_err!(WasmError, "{}", e))?;
+
+        let ret = limited(&self.engine, &mut self.store, vm, |store| {
+            fun.call(store, (ptr, bytes.len() as i32))
+        })?
+        .map_err(|e| raise_err!(WasmError, "{}", e))?;
+
+        let (ptr, len) = ((ret >> 32) as u32 as usize, ret as u32 as usize);
+        if let Some(left) = vm.heap_left().filter(|left| len > *left) {
+            return raise!(
+                MemoryError,
+                "The plugin returned {} bytes, over the {} bytes the script can still allocate",
+                len,
+                left
+            );
+        }
+
+        let bytes = self
+            .memory
+            .data(&self.store)
+            .get(ptr..ptr.saturating_add(len))
+            .ok_or_else(|| {
+                raise_err!(WasmError, "The plugin returned a value out of its memory")
+            })?;
+
+        decode(&mut &bytes[..], 0)
+    }
+}
+
+/// Runs `run` with the fuel and the deadline left to `vm`, the fuel it takes is counted as
+/// instructions of the vm. The limits are raised, the other errors of the plugin are returned
+fn limited<T>(
+    engine: &Engine,
+    store: &mut Store<()>,
+    vm: &mut VirtualMachine,
+    run: impl FnOnce(&mut Store<()>) -> wasmtime::Result<T>,
+) -> InterpretResult<wasmtime::Result<T>> {
+    let fuel = vm.instructions_left().unwrap_or(u64::MAX);
+    store
+        .set_fuel(fuel)
+        .map_err(|e| raise_err!(WasmError, "{}", e))?;
+    store.set_epoch_deadline(1);
+
+    // the timer is stopped once the sender is dropped, when the plugin returns
+    let timer = vm.deadline.map(|deadline| {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let engine = engine.clone();
+        thread::spawn(move || {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            if let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(timeout) {
+                engine.increment_epoch();
+            }
+        });
+        stop
+    });
+
+    let ret = run(store);
+    drop(timer);
+    vm.spend_instructions(fuel - store.get_fuel().unwrap_or(0));
+
+    match ret.as_ref().err().and_then(|e| e.downcast_ref::<Trap>()) {
+        Some(Trap::OutOfFuel) => {
+            vm.count_instruction()?;
+            raise!(TimeoutError, "The plugin ran out of fuel")
+        }
+        Some(Trap::Interrupt) => raise!(TimeoutError, "Execution timed out"),
+        _ => Ok(ret),
+    }
+}
+
+/// Creates a yex module exposing the functions exported by `plugin`
+pub fn module(name: Symbol, mut plugin: Plugin) -> YexModule {
+    let exports = plugin.exports();
+    let data = Value::UserData(UserData::new(RefCell::new(plugin)));
+
+    let mut module = YexModule::with_name(name);
+    for (name, arity) in exports {
+        let fun = Fn {
+            arity,
+            body: GcRef::new(FnKind::Native(methods::call)),
+            args: stackvec![name.clone().into(), data.clone()],
+            info: None,
+            upvalues: vec![],
+            more: None,
+            variadic: false,
+        };
+        module.fields.insert(name.into(), fun.into());
+    }
+
+    module
+}
+
+fn encode(value: &Value, out: &mut Vec<u8>) -> InterpretResult<()> {
+    fn encode_str(tag: u8, str: &str, out: &mut Vec<u8>) {
+        out.push(tag);
+        out.extend((str.len() as u32).to_le_bytes());
+        out.extend(str.as_bytes());
+    }
+
+    match value {
+        Value::Nil => out.push(0),
+        Value::Bool(false) => out.push(1),
+        Value::Bool(true) => out.push(2),
+        Value::Num(n) => {
+            out.push(3);
+            out.extend(n.to_le_bytes());
+        }
+        Value::Int(n) => {
+            out.push(8);
+            out.extend(n.to_le_bytes());
+        }
+        Value::Str(s) => encode_str(4, s, out),
+        Value::Sym(s) => encode_str(5, s.as_str(), out),
+        Value::List(xs) => {
+            out.push(6);
+            out.extend((xs.len() as u32).to_le_bytes());
+            for x in xs.iter() {
+                encode(&x, out)?;
+            }
+        }
+        Value::Tuple(tup) => {
+            out.push(7);
+            out.extend((tup.len() as u32).to_le_bytes());
+            for x in tup.0.iter() {
+                encode(x, out)?;
+            }
+        }
+        other => raise!(TypeError, "Can't pass '{}' to a WASM plugin", other)?,
+    }
+
+    Ok(())
+}
+
+fn decode(bytes: &mut &[u8], depth: usize) -> InterpretResult<Value> {
+    fn take<'a>(bytes: &mut &'a [u8], len: usize) -> InterpretResult<&'a [u8]> {
+        if bytes.len() < len {
+            return raise!(WasmError, "Malformed value returned by the plugin");
+        }
+        let (head, tail) = bytes.split_at(len);
+        *bytes = tail;
+        Ok(head)
+    }
+
+    fn take_u32(bytes: &mut &[u8]) -> InterpretResult<usize> {
+        Ok(u32::from_le_bytes(take(bytes, 4)?.try_into().unwrap()) as usize)
+    }
+
+    fn take_str(bytes: &mut &[u8]) -> InterpretResult<String> {
+        let len = take_u32(bytes)?;
+        String::from_utf8(take(bytes, len)?.to_vec())
+            .map_err(|_| raise_err!(WasmError, "Invalid utf-8 string returned by the plugin"))
+    }
+
+    let decode_all = |bytes: &mut &[u8]| {
+        if depth == MAX_DEPTH {
+            return raise!(
+                WasmError,
+                "The value returned by the plugin is nested more than {} levels",
+                MAX_DEPTH
+            );
+        }
+
+        let len = take_u32(bytes)?;
+        (0..len)
+            .map(|_| decode(bytes, depth + 1))
+            .collect::<InterpretResult<Vec<_>>>()
+    };
+
+    let value = match take(bytes, 1)?[0] {
+        0 => Value::Nil,
+        1 => Value::Bool(false),
+        2 => Value::Bool(true),
+        3 => Value::Num(f64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+        8 => Value::Int(i64::from_le_bytes(take(bytes, 8)?.try_into().unwrap())),
+        4 => take_str(bytes)?.into(),
+        5 => Symbol::new(take_str(bytes)?).into(),
+        6 => List::from_iter(decode_all(bytes)?.into_iter().rev()).into(),
+        7 => Tuple::from(decode_all(bytes)?).into(),
+        tag => raise!(
+            WasmError,
+            "Unknown value tag '{}' returned by the plugin",
+            tag
+        )?,
+    };
+
+    Ok(value)
+}
+
+#[cfg(test)]
+fn load(name: &str, plugin: &str, vm: &mut VirtualMachine) -> Value {
+    let path = std::env::temp_dir().join(format!("yex_test_{name}.wat"));
+    std::fs::write(&path, plugin).unwrap();
+
+    let plugin = Plugin::open(&path, vm).unwrap().unwrap();
+    Value::Module(GcRef::new(module(Symbol::new(name), plugin)))
+}
+
+#[cfg(test)]
+const LOOP_PLUGIN: &str = r#"
+    (module
+        (memory (export "memory") 1)
+        (func (export "yex_alloc") (param i32) (result i32) (i32.const 2048))
+        (func (export "yex_fn_spin") (param i32) (param i32) (result i64)
+            (loop $forever (br $forever))
+            (i64.const 0)))
+"#;
+
+#[test]
+fn call_plugin() {
+    const PLUGIN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (global (export "yex_arity_double") i32 (i32.const 1))
+            (func (export "yex_alloc") (param i32) (result i32) (i32.const 2048))
+            (func (export "yex_fn_double") (param $ptr i32) (param $len i32) (result i64)
+                (i32.store8 (i32.const 1024) (i32.const 3))
+                (f64.store
+                    (i32.const 1025)
+                    (f64.mul (f64.load (i32.add (local.get $ptr) (i32.const 6))) (f64.const 2)))
+                (i64.or (i64.shl (i64.const 1024) (i64.const 32)) (i64.const 9))))
+    "#;
+
+    let mut vm = VirtualMachine::default();
+    let module = load("plugin", PLUGIN, &mut vm);
+
+    let ret = vm.call_method(&module, "double", &[Value::Num(21.0)]);
+    assert_eq!(ret.unwrap(), Value::Num(42.0));
+}
+
+#[test]
+fn plugin_takes_the_fuel() {
+    let mut vm = VirtualMachine::default();
+    let module = load("fuel", LOOP_PLUGIN, &mut vm);
+
+    vm.set_fuel(Some(10_000));
+    let e = vm.call_method(&module, "spin", &[Value::Nil]).unwrap_err();
+    assert_eq!(e.err, Symbol::from("TimeoutError"));
+    assert_eq!(vm.fuel(), Some(0));
+}
+
+#[test]
+fn plugin_stops_at_the_deadline() {
+    let mut vm = VirtualMachine::default();
+    let module = load("deadline", LOOP_PLUGIN, &mut vm);
+
+    vm.deadline = Some(Instant::now() + std::time::Duration::from_millis(50));
+    let e = vm.call_method(&module, "spin", &[Value::Nil]).unwrap_err();
+    assert_eq!(e.err, Symbol::from("TimeoutError"));
+}
+
+#[test]
+fn plugin_returns_bounded_values() {
+    const PLUGIN: &str = r#"
+        (module
+            (memory (export "memory") 1)
+            (func (export "yex_alloc") (param i32) (result i32) (i32.const 2048))
+            (func (export "yex_fn_huge") (param i32) (param i32) (result i64)
+                (i64.const 0xffffffff))
+            (func (export "yex_fn_large") (param i32) (param i32) (result i64)
+                (i64.const 4096)))
+    "#;
+
+    let mut vm = VirtualMachine::default();
+    let module = load("bounded", PLUGIN, &mut vm);
+    let e = vm.call_method(&module, "huge", &[Value::Nil]).unwrap_err();
+    assert_eq!(e.err, Symbol::from("WasmError"));
+
+    let options = crate::VmOptions::new().max_heap(1024);
+    let mut vm = VirtualMachine::builder().options(options).build();
+    let module = load("bounded", PLUGIN, &mut vm);
+    let e = vm.call_method(&module, "large", &[Value::Nil]).unwrap_err();
+    assert_eq!(e.err, Symbol::from("MemoryError"));
+}
+
+#[test]
+fn decode_depth() {
+    let nested = |depth: usize| {
+        let mut bytes = [6, 1, 0, 0, 0].repeat(depth);
+        bytes.push(0);
+        bytes
+    };
+
+    assert!(decode(&mut &nested(MAX_DEPTH)[..], 0).is_ok());
+    let e = decode(&mut &nested(MAX_DEPTH + 1)[..], 0).unwrap_err();
+    assert_eq!(e.err, Symbol::from("WasmError"));
+}
@@ -1,6 +1,19 @@
-use crate::{env::EnvTable, gc::GcRef, literal, Symbol, Value};
-
-use super::{fun::Fn, list, str, tuple};
+use alloc::vec::Vec;
+
+use crate::{
+    env::EnvTable,
+    error::InterpretResult,
+    gc::{GcRef, Trace, Tracer},
+    literal, raise, raise_err, Symbol, Value,
+};
+
+use super::{
+    array, bytes,
+    dict::{self, Dict},
+    fun::{Fn, NativeFn},
+    int, list, num, range, seq, str,
+    tuple::{self, Tuple},
+};
 
 #[derive(Debug, PartialEq, Default)]
 /// A Yex user-defined type.
@@ -24,7 +37,7 @@ macro_rules! fields {
                 Value::Fn(GcRef::new(Fn::new_native($arg_count, |_, args| {
                     let this: GcRef<Self> = GcRef::new(Self::default());
                     let tup = Tuple(GcRef::new(args.into_boxed_slice()));
-                    let value = Value::Tagged(this, Symbol::from(concat!(stringify!($sname), ".", stringify!($name))), tup);
+                    let value = Value::tagged(this, Symbol::from(concat!(stringify!($sname), ".", stringify!($name))), tup);
                     Ok(value)
                 }))),
             );
@@ -43,12 +56,193 @@ macro_rules! fields {
          )*
     };}
 
+impl Trace for YexModule {
+    fn trace(&self, tracer: &mut Tracer<'_>) {
+        self.fields.values().for_each(|value| value.trace(tracer));
+    }
+}
+
 impl YexModule {
     /// Creates a new Yex type.
     pub fn new(name: Symbol, fields: EnvTable) -> Self {
         Self { name, fields }
     }
 
+    /// Returns the method of a trait that handles `receiver`, implemented by the type of the
+    /// receiver, raising a `TypeError` if the type doesn't implement the trait
+    pub fn dispatch(&self, receiver: &Value, method: Symbol) -> InterpretResult<Value> {
+        let ty = receiver.type_name();
+
+        // the implementations are stored in the trait, under the name of their type
+        match self.fields.get(&ty) {
+            Some(Value::Module(methods)) => methods.fields.get(&method).ok_or_else(|| {
+                raise_err!(TypeError, "'{}' isn't a method of '{}'", method, self.name)
+            }),
+            _ => raise!(TypeError, "'{}' doesn't implement '{}'", ty, self.name),
+        }
+    }
+
+    /// Implements the trait for `ty`, the trait methods missing from `methods` use the default
+    /// implementation of the trait, if there's one
+    pub fn implement(&mut self, ty: Symbol, methods: &YexModule) -> InterpretResult<()> {
+        for (name, _) in methods.fields.iter() {
+            if !self.is_method(name) {
+                return raise!(TypeError, "'{}' isn't a method of '{}'", name, self.name);
+            }
+        }
+
+        let mut fields = methods.fields.clone();
+        for (name, _) in self.fields.iter() {
+            if !self.is_method(name) || fields.get(&name).is_some() {
+                continue;
+            }
+
+            match self.fields.get(&Symbol::from(format!("#{}", name))) {
+                Some(default) => fields.insert(name, default),
+                None => {
+                    return raise!(
+                        TypeError,
+                        "'{}' doesn't implement '{}', which is required by '{}'",
+                        ty,
+                        name,
+                        self.name
+                    )
+                }
+            }
+        }
+
+        self.fields
+            .insert(ty, Value::Module(GcRef::new(YexModule::new(ty, fields))));
+        Ok(())
+    }
+
+    /// The key of a private member, which can't be written in the source, so it's only referenced
+    /// by the functions of the module, which the compiler points to it
+    pub fn private(name: Symbol) -> Symbol {
+        format!("#private.{}", name).into()
+    }
+
+    /// Adds the fields of `module` to the module at `path` inside of `parent`, which is named
+    /// `name`, returning a copy of `parent` with the module in it
+    ///
+    /// The modules in the path that don't exist are created, so `module Foo.Bar` works without a
+    /// `module Foo`, and declaring a module again adds to it instead of replacing it
+    pub fn nest(
+        parent: Option<Value>,
+        name: Symbol,
+        path: &[Symbol],
+        module: &YexModule,
+    ) -> InterpretResult<Value> {
+        let mut this = match parent {
+            None => YexModule::new(name, EnvTable::new()),
+            Some(Value::Module(parent)) => YexModule::new(parent.name, parent.fields.clone()),
+            Some(other) => {
+                return raise!(TypeError, "'{}' isn't a module, found '{}'", name, other)
+            }
+        };
+
+        match path.split_first() {
+            Some((&field, rest)) => {
+                let nested = format!("{}.{}", name, field).into();
+                let value = Self::nest(this.fields.get(&field), nested, rest, module)?;
+                this.fields.insert(field, value);
+            }
+            None => {
+                for (field, value) in module.fields.iter() {
+                    this.fields.insert(field, value);
+                }
+            }
+        }
+
+        Ok(Value::Module(GcRef::new(this)))
+    }
+
+    /// Checks if `name` is a method of the trait, and not one of the default implementations or
+    /// the implementations of a type
+    fn is_method(&self, name: Symbol) -> bool {
+        !name.as_str().starts_with('#') && matches!(self.fields.get(&name), Some(Value::Fn(_)))
+    }
+
+    /// Returns the names of the fields of a struct, or `None` if the type isn't a struct
+    pub fn struct_fields(&self) -> Option<Tuple> {
+        match self.fields.get(&Symbol::from("#fields")) {
+            Some(Value::Tuple(fields)) => Some(fields),
+            _ => None,
+        }
+    }
+
+    /// Creates a value of the struct from a dictionary with it's fields, raising a `FieldError`
+    /// if a field is missing, or isn't a field of the struct
+    pub fn instantiate(this: &GcRef<Self>, values: &Dict) -> InterpretResult<Value> {
+        let fields = match this.struct_fields() {
+            Some(fields) => fields,
+            None => return raise!(TypeError, "'{}' isn't a struct", this.name),
+        };
+
+        let mut tup = Vec::with_capacity(fields.len());
+        for field in fields.0.iter() {
+            match values.get(field)? {
+                Some(value) => tup.push(value),
+                None => {
+                    return raise!(
+                        FieldError,
+                        "Missing field '{}' of struct '{}'",
+                        field,
+                        this.name
+                    )
+                }
+            }
+        }
+
+        this.update(&tup.into(), values)
+            .map(|tup| Value::tagged(this.clone(), this.name, tup))
+    }
+
+    /// Returns the field `name` of a value of the struct, raising a `FieldError` if there's no
+    /// such field
+    pub fn field(&self, value: &Tuple, name: Symbol) -> InterpretResult<Value> {
+        let index = self.field_index(name)?;
+        Ok(value.0[index].clone())
+    }
+
+    /// Copies a value of the struct, replacing the fields in `values`
+    pub fn update(&self, value: &Tuple, values: &Dict) -> InterpretResult<Tuple> {
+        let mut tup = value.0.to_vec();
+        for (key, new) in values.iter() {
+            let index = match key {
+                Value::Sym(name) => self.field_index(name.0)?,
+                other => return raise!(FieldError, "Invalid field name '{}'", other),
+            };
+            tup[index] = new.clone();
+        }
+
+        Ok(tup.into())
+    }
+
+    fn field_index(&self, name: Symbol) -> InterpretResult<usize> {
+        self.struct_fields()
+            .and_then(|fields| fields.0.iter().position(|f| f == &Value::from(name)))
+            .ok_or_else(|| {
+                raise_err!(
+                    FieldError,
+                    "Undefined field '{}' for struct '{}'",
+                    name,
+                    self.name
+                )
+            })
+    }
+
+    /// Creates a new Yex type without any fields.
+    pub fn with_name<T: Into<Symbol>>(name: T) -> Self {
+        Self::new(name.into(), EnvTable::new())
+    }
+
+    /// Registers a native function as a method of the module.
+    pub fn register<T: Into<Symbol>>(&mut self, name: T, arity: usize, fun: NativeFn) {
+        self.fields
+            .insert(name.into(), Fn::new_native(arity, fun).into());
+    }
+
     /// Creates a new List type.
     pub fn list() -> Self {
         let mut methods = EnvTable::new();
@@ -78,6 +272,21 @@ impl YexModule {
             Value::Fn(GcRef::new(Fn::new_native(3, list::methods::fold))),
         );
 
+        methods.insert(
+            Symbol::from("reduceWhile"),
+            Value::Fn(GcRef::new(Fn::new_native(3, list::methods::reduce_while))),
+        );
+
+        methods.insert(
+            Symbol::from("continue"),
+            Value::Fn(GcRef::new(Fn::new_native(1, list::methods::cont))),
+        );
+
+        methods.insert(
+            Symbol::from("halt"),
+            Value::Fn(GcRef::new(Fn::new_native(1, list::methods::halt))),
+        );
+
         methods.insert(
             Symbol::from("rev"),
             Value::Fn(GcRef::new(Fn::new_native(1, list::methods::rev))),
@@ -123,9 +332,246 @@ impl YexModule {
             Value::Fn(GcRef::new(Fn::new_native(1, list::methods::to_list))),
         );
 
+        methods.insert(
+            Symbol::from("take"),
+            Value::Fn(GcRef::new(Fn::new_native(2, list::methods::take))),
+        );
+
+        methods.insert(
+            Symbol::from("takeWhile"),
+            Value::Fn(GcRef::new(Fn::new_native(2, list::methods::take_while))),
+        );
+
+        methods.insert(
+            Symbol::from("flatMap"),
+            Value::Fn(GcRef::new(Fn::new_native(2, list::methods::flat_map))),
+        );
+
+        methods.insert(
+            Symbol::from("zip"),
+            Value::Fn(GcRef::new(Fn::new_native(2, list::methods::zip))),
+        );
+
+        methods.insert(
+            Symbol::from("zipWith"),
+            Value::Fn(GcRef::new(Fn::new_native(3, list::methods::zip_with))),
+        );
+
+        methods.insert(
+            Symbol::from("any"),
+            Value::Fn(GcRef::new(Fn::new_native(2, list::methods::any))),
+        );
+
+        methods.insert(
+            Symbol::from("all"),
+            Value::Fn(GcRef::new(Fn::new_native(2, list::methods::all))),
+        );
+
+        methods.insert(
+            Symbol::from("indexOf"),
+            Value::Fn(GcRef::new(Fn::new_native(2, list::methods::index_of))),
+        );
+
+        methods.insert(
+            Symbol::from("sort"),
+            Value::Fn(GcRef::new(Fn::new_native(1, list::methods::sort))),
+        );
+
+        methods.insert(
+            Symbol::from("unique"),
+            Value::Fn(GcRef::new(Fn::new_native(1, list::methods::unique))),
+        );
+
         Self::new(Symbol::from("List"), methods)
     }
 
+    /// Creates a new Bytes type.
+    pub fn bytes() -> Self {
+        let mut methods = EnvTable::new();
+
+        methods.insert(
+            Symbol::from("get"),
+            Value::Fn(GcRef::new(Fn::new_native(2, bytes::methods::get))),
+        );
+
+        methods.insert(
+            Symbol::from("slice"),
+            Value::Fn(GcRef::new(Fn::new_native(3, bytes::methods::slice))),
+        );
+
+        methods.insert(
+            Symbol::from("len"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::len))),
+        );
+
+        methods.insert(
+            Symbol::from("concat"),
+            Value::Fn(GcRef::new(Fn::new_native(2, bytes::methods::concat))),
+        );
+
+        methods.insert(
+            Symbol::from("fromStr"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::from_str))),
+        );
+
+        methods.insert(
+            Symbol::from("toStr"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::to_str))),
+        );
+
+        methods.insert(
+            Symbol::from("fromList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::from_list))),
+        );
+
+        methods.insert(
+            Symbol::from("toList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::to_list))),
+        );
+
+        methods.insert(
+            Symbol::from("fromInt"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::from_int))),
+        );
+
+        methods.insert(
+            Symbol::from("toInt"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::to_int))),
+        );
+
+        methods.insert(
+            Symbol::from("fromNum"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::from_num))),
+        );
+
+        methods.insert(
+            Symbol::from("toNum"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::to_num))),
+        );
+
+        methods.insert(
+            Symbol::from("show"),
+            Value::Fn(GcRef::new(Fn::new_native(1, bytes::methods::show))),
+        );
+
+        Self::new(Symbol::from("Bytes"), methods)
+    }
+
+    /// Creates a new Seq type, the sources that need a capability, like `Seq.lines`, are added
+    /// by the prelude
+    pub fn seq() -> Self {
+        let mut methods = EnvTable::new();
+
+        methods.insert(
+            Symbol::from("from"),
+            Value::Fn(GcRef::new(Fn::new_native(1, seq::methods::from))),
+        );
+
+        methods.insert(
+            Symbol::from("iterate"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::iterate))),
+        );
+
+        methods.insert(
+            Symbol::from("map"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::map))),
+        );
+
+        methods.insert(
+            Symbol::from("filter"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::filter))),
+        );
+
+        methods.insert(
+            Symbol::from("take"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::take))),
+        );
+
+        methods.insert(
+            Symbol::from("drop"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::drop))),
+        );
+
+        methods.insert(
+            Symbol::from("takeWhile"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::take_while))),
+        );
+
+        methods.insert(
+            Symbol::from("toList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, seq::methods::to_list))),
+        );
+
+        methods.insert(
+            Symbol::from("fold"),
+            Value::Fn(GcRef::new(Fn::new_native(3, seq::methods::fold))),
+        );
+
+        methods.insert(
+            Symbol::from("find"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::find))),
+        );
+
+        methods.insert(
+            Symbol::from("each"),
+            Value::Fn(GcRef::new(Fn::new_native(2, seq::methods::each))),
+        );
+
+        Self::new(Symbol::from("Seq"), methods)
+    }
+
+    /// Creates a new Array type.
+    pub fn array() -> Self {
+        let mut methods = EnvTable::new();
+
+        methods.insert(
+            Symbol::from("new"),
+            Value::Fn(GcRef::new(Fn::new_native(0, array::methods::new))),
+        );
+
+        methods.insert(
+            Symbol::from("get"),
+            Value::Fn(GcRef::new(Fn::new_native(2, array::methods::get))),
+        );
+
+        methods.insert(
+            Symbol::from("set"),
+            Value::Fn(GcRef::new(Fn::new_native(3, array::methods::set))),
+        );
+
+        methods.insert(
+            Symbol::from("push"),
+            Value::Fn(GcRef::new(Fn::new_native(2, array::methods::push))),
+        );
+
+        methods.insert(
+            Symbol::from("concat"),
+            Value::Fn(GcRef::new(Fn::new_native(2, array::methods::concat))),
+        );
+
+        methods.insert(
+            Symbol::from("len"),
+            Value::Fn(GcRef::new(Fn::new_native(1, array::methods::len))),
+        );
+
+        methods.insert(
+            Symbol::from("fromList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, array::methods::from_list))),
+        );
+
+        methods.insert(
+            Symbol::from("toList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, array::methods::to_list))),
+        );
+
+        methods.insert(
+            Symbol::from("show"),
+            Value::Fn(GcRef::new(Fn::new_native(1, array::methods::show))),
+        );
+
+        Self::new(Symbol::from("Array"), methods)
+    }
+
     /// Creates a new Tuple type.
     pub fn tuple() -> Self {
         let mut methods = EnvTable::new();
@@ -135,6 +581,16 @@ impl YexModule {
             Value::Fn(GcRef::new(Fn::new_native(2, tuple::methods::get))),
         );
 
+        methods.insert(
+            Symbol::from("len"),
+            Value::Fn(GcRef::new(Fn::new_native(1, tuple::methods::len))),
+        );
+
+        methods.insert(
+            Symbol::from("toList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, tuple::methods::to_list))),
+        );
+
         methods.insert(
             Symbol::from("new"),
             Value::Fn(GcRef::new(Fn::new_native(0, tuple::methods::new))),
@@ -148,6 +604,94 @@ impl YexModule {
         Self::new(Symbol::from("Tuple"), methods)
     }
 
+    /// Creates a new Dict type.
+    pub fn dict() -> Self {
+        let mut methods = EnvTable::new();
+
+        methods.insert(
+            Symbol::from("new"),
+            Value::Fn(GcRef::new(Fn::new_native(0, dict::methods::new))),
+        );
+
+        methods.insert(
+            Symbol::from("get"),
+            Value::Fn(GcRef::new(Fn::new_native(2, dict::methods::get))),
+        );
+
+        methods.insert(
+            Symbol::from("insert"),
+            Value::Fn(GcRef::new(Fn::new_native(3, dict::methods::insert))),
+        );
+
+        methods.insert(
+            Symbol::from("remove"),
+            Value::Fn(GcRef::new(Fn::new_native(2, dict::methods::remove))),
+        );
+
+        methods.insert(
+            Symbol::from("has"),
+            Value::Fn(GcRef::new(Fn::new_native(2, dict::methods::has))),
+        );
+
+        methods.insert(
+            Symbol::from("keys"),
+            Value::Fn(GcRef::new(Fn::new_native(1, dict::methods::keys))),
+        );
+
+        methods.insert(
+            Symbol::from("values"),
+            Value::Fn(GcRef::new(Fn::new_native(1, dict::methods::values))),
+        );
+
+        methods.insert(
+            Symbol::from("toList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, dict::methods::to_list))),
+        );
+
+        methods.insert(
+            Symbol::from("len"),
+            Value::Fn(GcRef::new(Fn::new_native(1, dict::methods::len))),
+        );
+
+        methods.insert(
+            Symbol::from("show"),
+            Value::Fn(GcRef::new(Fn::new_native(1, |vm, x| {
+                super::show(vm, x).map(|x| x.into())
+            }))),
+        );
+
+        Self::new(Symbol::from("Dict"), methods)
+    }
+
+    /// Creates a new Range type.
+    pub fn range() -> Self {
+        let mut methods = EnvTable::new();
+
+        methods.insert(
+            Symbol::from("toList"),
+            Value::Fn(GcRef::new(Fn::new_native(1, range::methods::to_list))),
+        );
+
+        methods.insert(
+            Symbol::from("len"),
+            Value::Fn(GcRef::new(Fn::new_native(1, range::methods::len))),
+        );
+
+        methods.insert(
+            Symbol::from("has"),
+            Value::Fn(GcRef::new(Fn::new_native(2, range::methods::has))),
+        );
+
+        methods.insert(
+            Symbol::from("show"),
+            Value::Fn(GcRef::new(Fn::new_native(1, |vm, x| {
+                super::show(vm, x).map(|x| x.into())
+            }))),
+        );
+
+        Self::new(Symbol::from("Range"), methods)
+    }
+
     /// Creates a new Num type.
     pub fn num() -> Self {
         let mut methods = EnvTable::new();
@@ -159,9 +703,43 @@ impl YexModule {
             }))),
         );
 
+        methods.insert(
+            Symbol::from("parse"),
+            Value::Fn(GcRef::new(Fn::new_native(1, num::parse_str))),
+        );
+
+        methods.insert(
+            Symbol::from("toStr"),
+            Value::Fn(GcRef::new(Fn::new_native(2, num::to_str))),
+        );
+
         Self::new(Symbol::from("Num"), methods)
     }
 
+    /// Creates a new Int type.
+    pub fn int() -> Self {
+        let mut methods = EnvTable::new();
+
+        methods.insert(
+            Symbol::from("div"),
+            Value::Fn(GcRef::new(Fn::new_native(2, int::div))),
+        );
+
+        methods.insert(
+            Symbol::from("from"),
+            Value::Fn(GcRef::new(Fn::new_native(1, int::from))),
+        );
+
+        methods.insert(
+            Symbol::from("show"),
+            Value::Fn(GcRef::new(Fn::new_native(1, |vm, x| {
+                super::show(vm, x).map(|x| x.into())
+            }))),
+        );
+
+        Self::new(Symbol::from("Int"), methods)
+    }
+
     /// Creates a new Sym type.
     pub fn sym() -> Self {
         let mut methods = EnvTable::new();
@@ -215,6 +793,51 @@ impl YexModule {
             Value::Fn(GcRef::new(Fn::new_native(1, str::methods::chr))),
         );
 
+        methods.insert(
+            Symbol::new("stripPrefix"),
+            Value::Fn(GcRef::new(Fn::new_native(2, str::methods::strip_prefix))),
+        );
+
+        methods.insert(
+            Symbol::new("chars"),
+            Value::Fn(GcRef::new(Fn::new_native(1, str::methods::chars))),
+        );
+
+        methods.insert(
+            Symbol::new("trim"),
+            Value::Fn(GcRef::new(Fn::new_native(1, str::methods::trim))),
+        );
+
+        methods.insert(
+            Symbol::new("replace"),
+            Value::Fn(GcRef::new(Fn::new_native(3, str::methods::replace))),
+        );
+
+        methods.insert(
+            Symbol::new("contains"),
+            Value::Fn(GcRef::new(Fn::new_native(2, str::methods::contains))),
+        );
+
+        methods.insert(
+            Symbol::new("find"),
+            Value::Fn(GcRef::new(Fn::new_native(2, str::methods::find))),
+        );
+
+        methods.insert(
+            Symbol::new("toUpper"),
+            Value::Fn(GcRef::new(Fn::new_native(1, str::methods::to_upper))),
+        );
+
+        methods.insert(
+            Symbol::new("toLower"),
+            Value::Fn(GcRef::new(Fn::new_native(1, str::methods::to_lower))),
+        );
+
+        methods.insert(
+            Symbol::new("bytes"),
+            Value::Fn(GcRef::new(Fn::new_native(1, str::methods::bytes))),
+        );
+
         methods.insert(
             Symbol::from("show"),
             Value::Fn(GcRef::new(Fn::new_native(1, |vm, x| {
@@ -268,6 +891,7 @@ impl YexModule {
         Self::new(Symbol::from("Result"), methods)
     }
     /// Generates a new FFI type
+    #[cfg(feature = "std")]
     pub fn ffi() -> Self {
         let mut methods = EnvTable::new();
         fields!(FFI => {
@@ -276,6 +900,15 @@ impl YexModule {
         }, methods);
         Self::new(Symbol::from("FFI"), methods)
     }
+    /// Generates a new Wasm type
+    #[cfg(feature = "wasm")]
+    pub fn wasm() -> Self {
+        let mut methods = EnvTable::new();
+        fields!(Wasm => {
+            open @ literal::wasm::methods::open => 1,
+        }, methods);
+        Self::new(Symbol::from("Wasm"), methods)
+    }
     /// Creates a new Nil type.
     pub fn nil() -> Self {
         let mut methods = EnvTable::new();
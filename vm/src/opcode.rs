@@ -37,11 +37,34 @@ pub enum OpCode {
     /// The stack layout after running it: []
     Save(usize),
 
+    /// Reserves N locals in the current frame without saving anything to them, used by the
+    /// compiler when it removes the saves that would count them
+    /// The stack layout before running this opcode: []
+    /// The stack layout after running it: []
+    Reserve(usize),
+
     /// Read a value from a variable
     /// The stack layout before running this opcode: []
     /// The stack layout after running it: [variable-value]
     Loag(Symbol),
 
+    /// Read a value captured from an enclosing function, receives the index of the upvalue as
+    /// argument
+    /// The stack layout before running this opcode: []
+    /// The stack layout after running it: [upvalue]
+    LoadUpval(usize),
+
+    /// Creates a closure from the function on the stack top, capturing the N values below it
+    /// The stack layout before running this opcode: [upvalue¹, ..., upvalueⁿ, fun]
+    /// The stack layout after running it: [closure]
+    Closure(usize),
+
+    /// Makes `more` the function called when the stub on the stack top receives more arguments
+    /// than it takes, used for default arguments
+    /// The stack layout before running this opcode: [more, stub]
+    /// The stack layout after running it: [stub]
+    Overload,
+
     /// Save a value to a global variable
     /// The stack layout before running this opcode: [value-to-save]
     /// The stack layout after running it: []
@@ -197,9 +220,30 @@ pub enum OpCode {
     /// The stack layout after running it: [tuple]
     Tup(usize),
 
+    /// Creates a new `Array`, receives the number of elements as argument
+    /// The stack layout before running this opcode: [x¹, ..., xⁿ]
+    /// The stack layout after running it: [array]
+    Array(usize),
+
+    /// Creates a new `Dict`, receives the number of entries as argument
+    /// The stack layout before running this opcode: [key¹, value¹, ..., keyⁿ, valueⁿ]
+    /// The stack layout after running it: [dict]
+    Dict(usize),
+
+    /// Creates a new `Range`, the argument tells if the end is included in it
+    /// The stack layout before running this opcode: [start, end]
+    /// The stack layout after running it: [range]
+    Range(bool),
+
+    /// Imports a module, receives the module name as argument, the module is loaded, executed and
+    /// cached by the vm the first time it's imported
+    /// The stack layout before running this opcode: []
+    /// The stack layout after running it: [module]
+    Import(Symbol),
+
     /// Indexes a tuple, receives the tuple index as argument
-    /// NOTE: this isn't used by the `.get()` method on tuples because this requires compile-time
-    /// access to the index, which isn't possible.
+    /// Used by tuple patterns and by `t.0`, raises a `ValueError` if the index is out of bounds.
+    /// NOTE: this isn't used by `Tuple.get` because the index isn't known at compile-time.
     /// The stack layout before running this opcode: [tuple]
     /// The stack layout after running it: [tuple-element]
     TupGet(usize),
@@ -218,6 +262,83 @@ pub enum OpCode {
     /// The stack layout before running this opcode: [tagged]
     /// The stack layout after running it: [tuple]
     TagTup,
+
+    /// Resolves a trait method for the value it's called on, receives the method name as
+    /// argument, the receiver is the first argument of the call, so it stays on the stack
+    /// The stack layout before running this opcode: [...args, receiver, trait]
+    /// The stack layout after running it: [...args, receiver, method]
+    Dispatch(Symbol),
+
+    /// Implements a trait for a type, the methods that aren't implemented use the trait defaults
+    /// The stack layout before running this opcode: [trait, type, methods]
+    /// The stack layout after running it: []
+    Impl,
+
+    /// Creates a value of a struct, raising an error if a field is missing or isn't in the struct
+    /// The stack layout before running this opcode: [fields, struct]
+    /// The stack layout after running it: [value]
+    Struct,
+
+    /// Copies a struct value or a dictionary, replacing the fields in the dictionary
+    /// The stack layout before running this opcode: [value, fields]
+    /// The stack layout after running it: [value]
+    Update,
+
+    /// Returns a generator instead of running the rest of the function, the frame and its locals
+    /// move to the generator, which runs the code after this opcode when it's resumed
+    /// The stack layout before running this opcode: []
+    /// The stack layout after running it: [generator]
+    Generator,
+
+    /// Suspends the running generator, which resumes from here with `nil` as the result
+    /// The stack layout before running this opcode: [value]
+    /// The stack layout after running it: [nil]
+    Yield,
+
+    /// Raises a `MatchError` with the value that the patterns of a `let`, a function or a `match`
+    /// couldn't match
+    /// The stack layout before running this opcode: [value]
+    /// The stack layout after running it: []
+    MatchFail,
+
+    /// Adds the members of a module to the module at a dotted path, like `Foo.Bar`, the modules of
+    /// the path that don't exist are created and the first one is saved to a global
+    /// The stack layout before running this opcode: [module]
+    /// The stack layout after running it: []
+    Savm(Symbol),
+
+    /// Adds two numbers, emitted when the compiler knows their types, the other values are added
+    /// like `Add` does, since the types of annotated arguments aren't checked
+    /// The stack layout before running this opcode: [num1, num2]
+    /// The stack layout after running it: [result]
+    AddNum,
+
+    /// Concatenates two strings, emitted when the compiler knows their types, the other values are
+    /// added like `Add` does
+    /// The stack layout before running this opcode: [str1, str2]
+    /// The stack layout after running it: [result]
+    ConcatStr,
+}
+
+impl OpCode {
+    /// The instruction a jump goes to, `None` if the opcode isn't a jump
+    pub fn target(self) -> Option<usize> {
+        match self {
+            OpCode::Jmp(ip) | OpCode::Jmf(ip) | OpCode::Try(ip) => Some(ip),
+            _ => None,
+        }
+    }
+
+    /// Makes a jump go to another instruction, the other opcodes are returned as they are
+    #[must_use]
+    pub fn retarget(self, ip: usize) -> Self {
+        match self {
+            OpCode::Jmp(_) => OpCode::Jmp(ip),
+            OpCode::Jmf(_) => OpCode::Jmf(ip),
+            OpCode::Try(_) => OpCode::Try(ip),
+            other => other,
+        }
+    }
 }
 
 /// Stocks the [`crate::OpCode`] with the line and the column of it on the original source code,
@@ -243,13 +364,13 @@ impl OpCodeMetadata {
     }
 }
 
-impl std::fmt::Debug for OpCodeMetadata {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl core::fmt::Debug for OpCodeMetadata {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{:?}", self.opcode)
     }
 }
 
-impl std::cmp::PartialEq for OpCodeMetadata {
+impl core::cmp::PartialEq for OpCodeMetadata {
     fn eq(&self, other: &Self) -> bool {
         self.opcode == other.opcode
     }
@@ -0,0 +1,377 @@
+//! The options that restrict what the scripts can do, so hosts can run untrusted code
+
+use alloc::{boxed::Box, sync::Arc};
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use crate::{error::InterpretResult, gc, raise, Capabilities, VirtualMachine};
+
+/// The options of a [`VirtualMachine`], which decide the prelude modules the scripts can use and
+/// the resources a run of them can take
+///
+/// The limits are checked while the code runs, the instructions at every instruction, and the
+/// heap and the call depth at every call. The errors they raise can't be rescued, like timeouts,
+/// so a script can't ignore them
+///
+/// ```
+/// use vm::{VirtualMachine, VmOptions};
+///
+/// let options = VmOptions::sandbox()
+///     .max_instructions(10_000)
+///     .max_call_depth(64);
+/// let vm = VirtualMachine::builder().options(options).build();
+///
+/// // the processes and the network are denied, the pure modules are still there
+/// assert!(vm.get_global("Os").is_none());
+/// assert!(vm.get_global("Net").is_none());
+/// assert!(vm.get_global("List").is_some());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VmOptions {
+    pub(crate) capabilities: Capabilities,
+    max_instructions: Option<u64>,
+    max_heap: Option<usize>,
+    max_call_depth: Option<usize>,
+    #[cfg(feature = "std")]
+    timeout: Option<Duration>,
+}
+
+impl VmOptions {
+    /// Creates the options of a vm with every capability and no limits
+    pub const fn new() -> Self {
+        Self {
+            capabilities: Capabilities::ALL,
+            max_instructions: None,
+            max_heap: None,
+            max_call_depth: None,
+            #[cfg(feature = "std")]
+            timeout: None,
+        }
+    }
+
+    /// Creates the options of a vm without any capability, the scripts can only compute values,
+    /// without touching files, the network, processes or even stdout
+    pub const fn sandbox() -> Self {
+        Self::new().capabilities(Capabilities::NONE)
+    }
+
+    /// Sets the capabilities of the scripts, only the prelude modules allowed by them are
+    /// installed
+    #[must_use]
+    pub const fn capabilities(mut self, capabilities: Capabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Limits the instructions each run can execute, the ones executed by the functions it calls
+    /// included, raising a `LimitError` once they're exceeded
+    #[must_use]
+    pub const fn max_instructions(mut self, n: u64) -> Self {
+        self.max_instructions = Some(n);
+        self
+    }
+
+    /// Limits the bytes that each run can allocate and keep alive, raising a `MemoryError` once
+    /// they're exceeded, the bytes kept by the threads it started count too
+    ///
+    /// The memory is only tracked with the `std` feature, without it the limit is never reached
+    #[must_use]
+    pub const fn max_heap(mut self, bytes: usize) -> Self {
+        self.max_heap = Some(bytes);
+        self
+    }
+
+    /// Limits how many functions can be running inside each other, raising a `RecursionError`
+    /// once they're exceeded, the tail calls don't count since they replace their caller
+    #[must_use]
+    pub const fn max_call_depth(mut self, n: usize) -> Self {
+        self.max_call_depth = Some(n);
+        self
+    }
+
+    /// Limits how long each run can take, raising a `TimeoutError` once it's over, like
+    /// [`VirtualMachine::run_with_timeout`]
+    #[cfg(feature = "std")]
+    #[must_use]
+    pub const fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    fn limited(&self) -> bool {
+        #[cfg(feature = "std")]
+        if self.timeout.is_some() {
+            return true;
+        }
+
+        self.max_instructions.is_some() || self.max_heap.is_some() || self.max_call_depth.is_some()
+    }
+}
+
+impl Default for VmOptions {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The resources used by the current run, kept only when the options have limits or the vm has
+/// fuel
+#[derive(Debug, Default)]
+pub(crate) struct Usage {
+    instructions: u64,
+    /// The bytes that were alive in this thread when the run started
+    heap: usize,
+    /// The bytes of this thread added to `shared`
+    counted: usize,
+    /// The bytes used by the run, in this thread and in the threads it started, which share it
+    shared: Arc<AtomicUsize>,
+    /// Set once a limit is exceeded, so the error isn't rescued
+    exceeded: bool,
+    /// The instructions left, which are kept between the runs
+    fuel: Option<u64>,
+    /// Set when the run set the deadline of the timeout, so it's removed once the run is over
+    #[cfg(feature = "std")]
+    timed: bool,
+}
+
+impl Usage {
+    fn new(heap: usize, fuel: Option<u64>, shared: Arc<AtomicUsize>) -> Self {
+        Self {
+            instructions: 0,
+            heap,
+            counted: 0,
+            shared,
+            exceeded: false,
+            fuel,
+            #[cfg(feature = "std")]
+            timed: false,
+        }
+    }
+
+    /// Counts the bytes allocated by this thread since the run started, returning the bytes used
+    /// by the run in every thread
+    fn heap(&mut self) -> usize {
+        let used = gc::stats().live().saturating_sub(self.heap);
+        if used >= self.counted {
+            self.shared
+                .fetch_add(used - self.counted, Ordering::Relaxed);
+        } else {
+            self.shared
+                .fetch_sub(self.counted - used, Ordering::Relaxed);
+        }
+        self.counted = used;
+
+        self.shared.load(Ordering::Relaxed)
+    }
+}
+
+/// The bytes of a thread that stopped aren't used by the run anymore
+impl Drop for Usage {
+    fn drop(&mut self) {
+        self.shared.fetch_sub(self.counted, Ordering::Relaxed);
+    }
+}
+
+impl VirtualMachine {
+    /// Returns the options the vm was built with
+    pub fn options(&self) -> VmOptions {
+        self.options
+    }
+
+    pub(crate) fn set_options(&mut self, options: VmOptions) {
+        self.options = options;
+        self.usage = options.limited().then(Box::default);
+    }
+
+    /// Gives the vm a budget of `fuel` instructions, each instruction that runs takes one and a
+    /// `TimeoutError` is raised once they're over, until more fuel is added. `None` removes the
+    /// budget
+    ///
+    /// Unlike [`VmOptions::max_instructions`] the fuel is kept between the runs, so the host can
+    /// give a script a budget for a whole session and refill it with [`Self::add_fuel`]. The error
+    /// can be rescued by the script, but the code that rescues it doesn't have fuel to run either
+    ///
+    /// ```
+    /// # use vm::{OpCode, OpCodeMetadata, VirtualMachine};
+    /// let mut vm = VirtualMachine::default();
+    /// let bytecode = vec![OpCodeMetadata::new(1, 1, OpCode::Nop); 10];
+    ///
+    /// vm.set_fuel(Some(15));
+    /// vm.run(&bytecode).unwrap();
+    /// assert_eq!(vm.fuel(), Some(5));
+    /// assert!(vm.run(&bytecode).is_err());
+    ///
+    /// vm.add_fuel(10);
+    /// vm.run(&bytecode).unwrap();
+    /// ```
+    pub fn set_fuel(&mut self, fuel: Option<u64>) {
+        match (&mut self.usage, fuel) {
+            (Some(usage), _) => usage.fuel = fuel,
+            (None, Some(_)) => {
+                self.usage = Some(Box::new(Usage::new(0, fuel, Arc::default())));
+            }
+            (None, None) => (),
+        }
+    }
+
+    /// Adds `n` instructions to the fuel of the vm, giving it a budget if it had none
+    pub fn add_fuel(&mut self, n: u64) {
+        let fuel = self.fuel().unwrap_or(0).saturating_add(n);
+        self.set_fuel(Some(fuel));
+    }
+
+    /// Returns the instructions left, or `None` if the vm has no budget
+    pub fn fuel(&self) -> Option<u64> {
+        self.usage.as_ref()?.fuel
+    }
+
+    /// Starts counting the resources used by a new run
+    pub(crate) fn start_run(&mut self) {
+        let Some(usage) = &mut self.usage else {
+            return;
+        };
+
+        // the bytes counted by the last run are taken out of the shared count
+        **usage = Usage::new(gc::stats().live(), usage.fuel, usage.shared.clone());
+
+        #[cfg(feature = "std")]
+        if let (Some(timeout), None) = (self.options.timeout, self.deadline) {
+            self.deadline = Some(Instant::now() + timeout);
+            usage.timed = true;
+        }
+    }
+
+    /// Stops counting the resources of the run, once it's over
+    pub(crate) fn end_run(&mut self) {
+        #[cfg(feature = "std")]
+        if let Some(usage) = &mut self.usage {
+            if usage.timed {
+                usage.timed = false;
+                self.deadline = None;
+            }
+        }
+    }
+
+    /// Checks if a limit was exceeded by the current run
+    pub(crate) fn limit_exceeded(&self) -> bool {
+        self.usage.as_ref().is_some_and(|usage| usage.exceeded)
+    }
+
+    /// Counts an instruction, called before every instruction while the options have limits or
+    /// the vm has fuel
+    pub(crate) fn count_instruction(&mut self) -> InterpretResult<()> {
+        let max = self.options.max_instructions;
+        let usage = self.usage.as_mut().unwrap();
+        usage.instructions += 1;
+
+        match &mut usage.fuel {
+            Some(0) => return raise!(TimeoutError, "The script ran out of fuel"),
+            Some(fuel) => *fuel -= 1,
+            None => (),
+        }
+
+        match max {
+            Some(max) if usage.instructions > max => {
+                usage.exceeded = true;
+                raise!(LimitError, "The script ran more than {} instructions", max)
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Returns the instructions the current run can still execute, the least of the fuel and what
+    /// is left of `max_instructions`, or `None` if they aren't limited
+    #[cfg(feature = "wasm")]
+    pub(crate) fn instructions_left(&self) -> Option<u64> {
+        let usage = self.usage.as_ref()?;
+        let left = self
+            .options
+            .max_instructions
+            .map(|max| max.saturating_sub(usage.instructions));
+
+        match (usage.fuel, left) {
+            (Some(fuel), Some(left)) => Some(fuel.min(left)),
+            (fuel, left) => fuel.or(left),
+        }
+    }
+
+    /// Counts `n` instructions executed outside of the vm, like the ones of a WASM plugin, the
+    /// limits are checked by the next instruction
+    #[cfg(feature = "wasm")]
+    pub(crate) fn spend_instructions(&mut self, n: u64) {
+        if let Some(usage) = &mut self.usage {
+            usage.instructions = usage.instructions.saturating_add(n);
+            usage.fuel = usage.fuel.map(|fuel| fuel.saturating_sub(n));
+        }
+    }
+
+    /// Returns the bytes the current run can still allocate, or `None` if the heap isn't limited
+    #[cfg(feature = "wasm")]
+    pub(crate) fn heap_left(&mut self) -> Option<usize> {
+        let max = self.options.max_heap?;
+        let used = self.usage.as_mut()?.heap();
+        Some(max.saturating_sub(used))
+    }
+
+    /// The counter of the bytes used by the current run, shared with the threads it starts, the
+    /// bytes of this thread are counted first so the new thread sees them
+    #[cfg(feature = "std")]
+    pub(crate) fn shared_heap(&mut self) -> Option<Arc<AtomicUsize>> {
+        let usage = self.usage.as_mut()?;
+        usage.heap();
+        Some(usage.shared.clone())
+    }
+
+    /// Counts the bytes used by this vm in the run of the vm that started its thread
+    #[cfg(feature = "std")]
+    pub(crate) fn share_heap(&mut self, shared: Arc<AtomicUsize>) {
+        if let Some(usage) = &mut self.usage {
+            usage.shared = shared;
+        }
+    }
+
+    /// Checks the heap and the call depth when a function is entered, `depth` is the number of
+    /// functions that are running once it's entered
+    #[inline(always)]
+    pub(crate) fn check_limits(&mut self, depth: usize) -> InterpretResult<()> {
+        if self.usage.is_some() {
+            self.check_usage(depth)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn check_usage(&mut self, depth: usize) -> InterpretResult<()> {
+        let VmOptions {
+            max_heap,
+            max_call_depth,
+            ..
+        } = self.options;
+        let usage = self.usage.as_mut().unwrap();
+
+        if let Some(max) = max_call_depth.filter(|max| depth > *max) {
+            usage.exceeded = true;
+            return raise!(
+                RecursionError,
+                "The call depth exceeded the limit of {}",
+                max
+            );
+        }
+
+        if let Some(max) = max_heap {
+            let used = usage.heap();
+            if used > max {
+                usage.exceeded = true;
+                return raise!(
+                    MemoryError,
+                    "The script allocated {} bytes, over the limit of {}",
+                    used,
+                    max
+                );
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,68 @@
+use core::ops::{BitOr, BitOrAssign};
+
+/// The set of capabilities granted to the scripts running on a [`crate::VirtualMachine`], it
+/// controls which parts of the prelude are installed
+///
+/// ```
+/// use vm::{Capabilities, VirtualMachine};
+///
+/// // pure computation, scripts can't even print
+/// let vm = VirtualMachine::builder()
+///     .capabilities(Capabilities::NONE)
+///     .build();
+///
+/// assert!(vm.get_global("println").is_none());
+/// assert!(vm.get_global("List").is_some());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities(u8);
+
+impl Capabilities {
+    /// No capabilities, only pure computations are allowed
+    pub const NONE: Self = Self(0);
+    /// Reading from stdin and writing to stdout
+    pub const IO: Self = Self(1);
+    /// Reading and writing files
+    pub const FS: Self = Self(1 << 1);
+    /// Network access
+    pub const NET: Self = Self(1 << 2);
+    /// Spawning and exiting processes
+    pub const PROCESS: Self = Self(1 << 3);
+    /// Loading native or WASM extensions
+    pub const FFI: Self = Self(1 << 4);
+    /// Reading the command line arguments, and reading and changing the environment variables
+    pub const ENV: Self = Self(1 << 5);
+    /// Every capability
+    pub const ALL: Self = Self(u8::MAX);
+
+    /// Checks if all the capabilities in `other` are present in `self`
+    pub const fn contains(self, other: Self) -> bool {
+        self.0 & other.0 == other.0
+    }
+
+    /// Returns `self` without the capabilities in `other`
+    #[must_use]
+    pub const fn without(self, other: Self) -> Self {
+        Self(self.0 & !other.0)
+    }
+}
+
+impl Default for Capabilities {
+    fn default() -> Self {
+        Self::ALL
+    }
+}
+
+impl BitOr for Capabilities {
+    type Output = Self;
+
+    fn bitor(self, rhs: Self) -> Self {
+        Self(self.0 | rhs.0)
+    }
+}
+
+impl BitOrAssign for Capabilities {
+    fn bitor_assign(&mut self, rhs: Self) {
+        self.0 |= rhs.0;
+    }
+}
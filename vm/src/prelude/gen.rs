@@ -0,0 +1,56 @@
+use alloc::vec::Vec;
+
+use crate::{
+    error::InterpretResult, gc::GcRef, generator::generator_of, literal::fun::Fn, EnvTable, List,
+    Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `Gen` module, with the functions that consume generators
+pub fn gen() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("next"),
+        Value::Fn(GcRef::new(Fn::new_native(1, next))),
+    );
+    methods.insert(
+        Symbol::new("done"),
+        Value::Fn(GcRef::new(Fn::new_native(1, done))),
+    );
+    methods.insert(
+        Symbol::new("toList"),
+        Value::Fn(GcRef::new(Fn::new_native(1, to_list))),
+    );
+
+    YexModule::new(Symbol::new("Gen"), methods)
+}
+
+/// Runs the generator until its next `yield`, returning the value in the `Gen.yielded` tag, or
+/// `Gen.finished` once it's done, so the values yielded can be anything, nil included
+fn next(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let generator = generator_of(&args[0])?;
+
+    let (tag, args) = match vm.resume_generator(generator)? {
+        Some(value) => ("Gen.yielded", vec![value]),
+        None => ("Gen.finished", vec![]),
+    };
+    let this = GcRef::new(YexModule::default());
+    Ok(Value::tagged(this, Symbol::from(tag), args.into()))
+}
+
+fn done(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    Ok(Value::Bool(generator_of(&args[0])?.is_done()))
+}
+
+/// Collects the values the generator yields until it's done
+fn to_list(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let generator = generator_of(&args[0])?;
+
+    let mut values = Vec::new();
+    while let Some(value) = vm.resume_generator(generator)? {
+        values.push(value);
+    }
+    Ok(values.into_iter().rev().collect::<List>().into())
+}
@@ -0,0 +1,836 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::fmt::Write;
+
+use crate::{
+    env::EnvTable,
+    gc::GcRef,
+    literal::{fun::FnInfo, Value},
+    raise, raise_err, Fn, InterpretResult, Symbol, VirtualMachine,
+};
+
+/// The documentation of the prelude, as `(path, params, doc)`
+const DOCS: &[(&str, &str, &str)] = &[
+    ("println", "x", "Prints a value followed by a newline"),
+    ("print", "x", "Prints a value without a trailing newline"),
+    (
+        "print_stack!",
+        "_",
+        "Prints the vm stack, used to debug the interpreter",
+    ),
+    (
+        "input",
+        "prompt",
+        "Prints `prompt` and reads a line from the standard input",
+    ),
+    ("exit", "code", "Exits the process with the status `code`"),
+    ("type", "x", "Returns the type of `x`"),
+    (
+        "inspect",
+        "x",
+        "Formats `x` as it's written, breaking long values into indented lines",
+    ),
+    (
+        "num",
+        "str",
+        "Parses `str` into an integer or a float, raising a TypeError if it isn't a number",
+    ),
+    (
+        "raise",
+        "err msg",
+        "Raises an error with the name `err` and the message `msg`",
+    ),
+    (
+        "throw",
+        "x",
+        "Raises the tagged value `x`, which can be matched by the `rescue` arms",
+    ),
+    (
+        "memo",
+        "f",
+        "Returns `f` caching its results by argument, which must be hashable like dictionary keys",
+    ),
+    (
+        "assert",
+        "message condition",
+        "Raises an `AssertionError` with `message` if `condition` is false",
+    ),
+    (
+        "assert_eq",
+        "expected actual",
+        "Raises an `AssertionError` showing both values if `actual` isn't equal to `expected`",
+    ),
+    (
+        "spawn",
+        "f",
+        "Creates a task that calls `f` with `()`, returning its handle, it runs once the running task waits",
+    ),
+    ("send", "msg task", "Sends `msg` to `task`, waking it up if it's waiting for a message"),
+    (
+        "receive",
+        "()",
+        "Waits for a message sent to the running task, running the other tasks meanwhile",
+    ),
+    (
+        "yield",
+        "x",
+        "Suspends the program, returning `x` to the host that runs it, the result is the value the host resumes it with",
+    ),
+    (
+        "help",
+        "x",
+        "Prints the documentation of a function, a type or a global name",
+    ),
+    ("(+)", "a b", "Adds `a` and `b`"),
+    ("(-)", "a b", "Subtracts `b` from `a`"),
+    ("(*)", "a b", "Multiplies `a` by `b`"),
+    ("(/)", "a b", "Divides `a` by `b`"),
+    ("(%)", "a b", "Returns the remainder of dividing `a` by `b`"),
+    ("(&&&)", "a b", "Returns the bitwise and of `a` and `b`"),
+    ("(|||)", "a b", "Returns the bitwise or of `a` and `b`"),
+    ("(^^^)", "a b", "Returns the bitwise xor of `a` and `b`"),
+    ("(<<<)", "a b", "Shifts `a` left by `b` bits"),
+    ("(>>>)", "a b", "Shifts `a` right by `b` bits"),
+    ("(==)", "a b", "Checks if `a` is equal to `b`"),
+    ("(!=)", "a b", "Checks if `a` is not equal to `b`"),
+    ("(<)", "a b", "Checks if `a` is less than `b`"),
+    ("(<=)", "a b", "Checks if `a` is less than or equal to `b`"),
+    ("(>)", "a b", "Checks if `a` is greater than `b`"),
+    ("(>=)", "a b", "Checks if `a` is greater than or equal to `b`"),
+    ("(::)", "a b", "Prepends `a` to the list `b`"),
+    ("(..)", "a b", "Returns the range from `a` up to, but not including, `b`"),
+    ("(..=)", "a b", "Returns the range from `a` up to and including `b`"),
+    ("(!)", "x", "Returns the logical negation of `x`"),
+    (
+        "List.head",
+        "xs",
+        "Returns the first element of `xs`, or nil if it's empty",
+    ),
+    ("List.tail", "xs", "Returns `xs` without the first element"),
+    ("List.map", "f xs", "Applies `f` to every element of `xs`"),
+    (
+        "List.filter",
+        "f xs",
+        "Returns the elements of `xs` for which `f` returns true",
+    ),
+    (
+        "List.fold",
+        "f acc xs",
+        "Reduces `xs` from the left, calling `f acc x` for every element",
+    ),
+    (
+        "List.reduceWhile",
+        "f acc xs",
+        "Like `List.fold`, but stops early when `f` returns `List.halt acc` instead of `List.continue acc`",
+    ),
+    (
+        "List.continue",
+        "acc",
+        "Tells `List.reduceWhile` to keep going with `acc`",
+    ),
+    (
+        "List.halt",
+        "acc",
+        "Tells `List.reduceWhile` to stop and return `acc`",
+    ),
+    ("List.rev", "xs", "Reverses `xs`"),
+    (
+        "List.get",
+        "n xs",
+        "Returns the `n`th element of `xs`, or nil if it's out of bounds",
+    ),
+    (
+        "List.drop",
+        "n xs",
+        "Returns `xs` without the first `n` elements",
+    ),
+    (
+        "List.join",
+        "sep xs",
+        "Joins the elements of `xs` into a string, separated by `sep`",
+    ),
+    (
+        "List.find",
+        "f xs",
+        "Returns the first element of `xs` for which `f` returns true, or nil",
+    ),
+    ("List.len", "xs", "Returns the number of elements of `xs`"),
+    ("List.new", "", "Creates an empty list"),
+    ("List.show", "xs", "Converts `xs` to a string"),
+    (
+        "List.toList",
+        "xs",
+        "Returns `xs` itself, or the elements of a range as a list",
+    ),
+    ("List.take", "n xs", "Returns the first `n` elements of `xs`"),
+    (
+        "List.takeWhile",
+        "f xs",
+        "Returns the elements of `xs` until `f` returns false",
+    ),
+    (
+        "List.flatMap",
+        "f xs",
+        "Applies `f` to every element of `xs` and concatenates the lists it returns",
+    ),
+    (
+        "List.zip",
+        "xs ys",
+        "Pairs the elements of `xs` and `ys` into tuples, stopping at the shortest one",
+    ),
+    (
+        "List.zipWith",
+        "f xs ys",
+        "Calls `f x y` for each pair of elements of `xs` and `ys`",
+    ),
+    (
+        "List.any",
+        "f xs",
+        "Checks if `f` returns true for any element of `xs`",
+    ),
+    (
+        "List.all",
+        "f xs",
+        "Checks if `f` returns true for every element of `xs`",
+    ),
+    (
+        "List.indexOf",
+        "x xs",
+        "Returns the index of the first element of `xs` equal to `x`, or nil",
+    ),
+    ("List.sort", "xs", "Sorts the numbers of `xs` in ascending order"),
+    (
+        "List.unique",
+        "xs",
+        "Removes the repeated elements of `xs`, keeping their first occurrence",
+    ),
+    (
+        "Bytes.get",
+        "n bytes",
+        "Returns the `n`th byte of `bytes` as an integer, or nil if it's out of bounds",
+    ),
+    (
+        "Bytes.slice",
+        "start end bytes",
+        "Returns the bytes from `start` up to (but not including) `end`",
+    ),
+    ("Bytes.len", "bytes", "Returns the number of bytes"),
+    (
+        "Bytes.concat",
+        "left right",
+        "Returns the bytes of `left` followed by the bytes of `right`",
+    ),
+    ("Bytes.fromStr", "str", "Returns the utf-8 bytes of `str`"),
+    (
+        "Bytes.toStr",
+        "bytes",
+        "Decodes `bytes` as utf-8, raising a `ValueError` if they're invalid",
+    ),
+    (
+        "Bytes.fromList",
+        "xs",
+        "Creates a byte string from a list of integers between 0 and 255",
+    ),
+    ("Bytes.toList", "bytes", "Returns the bytes as a list of integers"),
+    (
+        "Bytes.fromInt",
+        "n",
+        "Encodes `n` in 8 big endian bytes",
+    ),
+    (
+        "Bytes.toInt",
+        "bytes",
+        "Decodes up to 8 big endian bytes as an integer",
+    ),
+    (
+        "Bytes.fromNum",
+        "n",
+        "Encodes the float `n` in 8 big endian bytes",
+    ),
+    (
+        "Bytes.toNum",
+        "bytes",
+        "Decodes 8 big endian bytes as a float",
+    ),
+    ("Bytes.show", "bytes", "Converts `bytes` to a string"),
+    (
+        "Seq.from",
+        "xs",
+        "Creates a lazy sequence with the elements of a list, an array, a range or a generator",
+    ),
+    (
+        "Seq.iterate",
+        "f x",
+        "Creates the endless sequence `x`, `f x`, `f (f x)`, ...",
+    ),
+    (
+        "Seq.lines",
+        "path",
+        "Creates a sequence with the lines of the file at `path`, read as they're needed",
+    ),
+    (
+        "Seq.map",
+        "f xs",
+        "Returns a sequence that calls `f` on each element of `xs`",
+    ),
+    (
+        "Seq.filter",
+        "f xs",
+        "Returns a sequence with the elements of `xs` for which `f` returns true",
+    ),
+    ("Seq.take", "n xs", "Returns a sequence with the first `n` elements of `xs`"),
+    ("Seq.drop", "n xs", "Returns a sequence without the first `n` elements of `xs`"),
+    (
+        "Seq.takeWhile",
+        "f xs",
+        "Returns a sequence with the elements of `xs` until `f` returns false",
+    ),
+    ("Seq.toList", "xs", "Computes the elements of `xs` into a list"),
+    (
+        "Seq.fold",
+        "f acc xs",
+        "Combines the elements of `xs` with `f`, starting from `acc`",
+    ),
+    (
+        "Seq.find",
+        "f xs",
+        "Returns the first element of `xs` for which `f` returns true, or nil",
+    ),
+    ("Seq.each", "f xs", "Calls `f` on each element of `xs`"),
+    ("Array.new", "", "Creates an empty array"),
+    (
+        "Array.get",
+        "n xs",
+        "Returns the `n`th element of `xs`, or nil if it's out of bounds",
+    ),
+    (
+        "Array.set",
+        "n x xs",
+        "Returns a copy of `xs` with the `n`th element replaced by `x`",
+    ),
+    ("Array.push", "x xs", "Returns a copy of `xs` with `x` at the end"),
+    (
+        "Array.concat",
+        "xs ys",
+        "Returns the elements of `xs` followed by the elements of `ys`",
+    ),
+    ("Array.len", "xs", "Returns the number of elements of `xs`"),
+    (
+        "Array.fromList",
+        "xs",
+        "Creates an array with the elements of a list or a range",
+    ),
+    ("Array.toList", "xs", "Returns the elements of `xs` as a list"),
+    ("Array.show", "xs", "Converts `xs` to a string"),
+    (
+        "IO.readLine",
+        "()",
+        "Reads a line from the standard input, without its line break, or nil if the input ended",
+    ),
+    (
+        "IO.readAll",
+        "()",
+        "Reads the rest of the standard input into a string",
+    ),
+    (
+        "IO.prompt",
+        "msg",
+        "Prints `msg` and reads a line from the standard input, or nil if the input ended",
+    ),
+    (
+        "Os.exec",
+        "cmd args",
+        "Runs `cmd` with the list `args` until it exits, returning `Result.ok %{code, stdout, stderr}` or `Result.fail msg` if it couldn't be run",
+    ),
+    (
+        "Os.execStream",
+        "cmd args f",
+        "Runs `cmd` with the list `args`, calling `f` with each line it prints, returning `Result.ok code` or `Result.fail msg` if it couldn't be run",
+    ),
+    (
+        "Net.connect",
+        "host port",
+        "Opens a TCP connection to `host` on `port`, waiting until it's connected",
+    ),
+    (
+        "Net.listen",
+        "port",
+        "Listens for TCP connections on `port`, port 0 lets the system choose a free one",
+    ),
+    (
+        "Net.accept",
+        "listener",
+        "Waits for the next connection to `listener` and returns its socket",
+    ),
+    ("Net.port", "listener", "Returns the port `listener` is bound to"),
+    (
+        "Socket.read",
+        "n sock",
+        "Waits for up to `n` bytes from `sock`, returning empty bytes once the connection is closed",
+    ),
+    (
+        "Socket.readLine",
+        "sock",
+        "Waits for a line from `sock`, without its line break, or nil once the connection is closed",
+    ),
+    (
+        "Socket.write",
+        "data sock",
+        "Sends a string or bytes through `sock`, waiting until all of it is sent",
+    ),
+    ("Socket.close", "sock", "Closes the connection of `sock`"),
+    (
+        "Http.get",
+        "url",
+        "Sends a GET request to `url`, returning `Result.ok %{status, headers, body}` or `Result.fail msg` if it couldn't be sent",
+    ),
+    (
+        "Http.post",
+        "url body headers",
+        "Sends a POST request with a string or bytes `body` and a dictionary of `headers`, returning `Result.ok %{status, headers, body}` or `Result.fail msg` if it couldn't be sent",
+    ),
+    (
+        "Regex.compile",
+        "pattern",
+        "Compiles `pattern`, so it isn't compiled again by every call, the other functions also take pattern strings",
+    ),
+    ("Regex.matches", "re str", "Checks if `re` matches somewhere in `str`"),
+    (
+        "Regex.find",
+        "re str",
+        "Returns the first match of `re` in `str`, or nil",
+    ),
+    ("Regex.findAll", "re str", "Returns every match of `re` in `str`"),
+    (
+        "Regex.captures",
+        "re str",
+        "Returns the first match of `re` in `str` followed by its groups, or nil",
+    ),
+    (
+        "Regex.namedCaptures",
+        "re str",
+        "Returns a dictionary with the named groups of the first match of `re` in `str`, or nil",
+    ),
+    (
+        "Regex.replace",
+        "re rep str",
+        "Replaces every match of `re` in `str` with `rep`, where `$1` or `$name` are replaced by the groups",
+    ),
+    ("Regex.split", "re str", "Splits `str` on every match of `re`"),
+    ("Task.current", "()", "Returns the handle of the running task"),
+    (
+        "Task.yield",
+        "()",
+        "Lets the other ready tasks run before the running task continues",
+    ),
+    (
+        "Task.async",
+        "f",
+        "Creates a task that calls `f` with `()`, returning a promise of its result",
+    ),
+    (
+        "Task.await",
+        "p",
+        "Returns the result of the promise `p`, waiting for its task to finish",
+    ),
+    (
+        "Task.sleep",
+        "ms",
+        "Suspends the running task for `ms` milliseconds, while the other tasks run",
+    ),
+    (
+        "Gen.next",
+        "g",
+        "Runs the generator `g` until its next `yield`, returning `Gen.yielded value`, or `Gen.finished` once it's done",
+    ),
+    (
+        "Gen.done",
+        "g",
+        "Checks if the generator `g` finished, which is known once `Gen.next` returned `Gen.finished`",
+    ),
+    (
+        "Gen.toList",
+        "g",
+        "Collects the values the generator `g` yields until it finishes",
+    ),
+    (
+        "Thread.spawn",
+        "f",
+        "Calls `f` with `()` in a new vm on an OS thread, returning the thread",
+    ),
+    (
+        "Thread.join",
+        "t",
+        "Waits for the thread `t`, returning a copy of the result of its function, or raising its error",
+    ),
+    ("Channel.new", "()", "Creates a channel shared by every thread it's sent to"),
+    (
+        "Channel.send",
+        "msg ch",
+        "Sends a copy of `msg` through the channel `ch`, without waiting for it to be received",
+    ),
+    (
+        "Channel.recv",
+        "ch",
+        "Waits for the oldest message sent through the channel `ch`",
+    ),
+    ("Math.sqrt", "x", "Returns the square root of `x`"),
+    ("Math.sin", "x", "Returns the sine of `x`, in radians"),
+    ("Math.cos", "x", "Returns the cosine of `x`, in radians"),
+    (
+        "Math.pow",
+        "x y",
+        "Raises `x` to the power of `y`, integers raised to integers stay integers",
+    ),
+    ("Math.log", "x", "Returns the natural logarithm of `x`"),
+    ("Math.floor", "x", "Rounds `x` down to an integer"),
+    ("Math.ceil", "x", "Rounds `x` up to an integer"),
+    ("Math.round", "x", "Rounds `x` to the nearest integer"),
+    ("Math.abs", "x", "Returns the absolute value of `x`"),
+    ("Math.random", "()", "Returns a random float from 0 up to, but not including, 1"),
+    (
+        "Math.randomRange",
+        "start end",
+        "Returns a random integer from `start` up to, but not including, `end`",
+    ),
+    ("Tuple.get", "n tup", "Returns the `n`th element of `tup`"),
+    ("Tuple.len", "tup", "Returns the number of elements of `tup`"),
+    ("Tuple.toList", "tup", "Converts the elements of `tup` to a list"),
+    ("Tuple.new", "", "Creates an empty tuple"),
+    ("Tuple.show", "tup", "Converts `tup` to a string"),
+    ("Dict.new", "", "Creates an empty dictionary"),
+    (
+        "Dict.get",
+        "key dict",
+        "Returns the value of `key` in `dict`, or nil if it's missing",
+    ),
+    (
+        "Dict.insert",
+        "key value dict",
+        "Returns a copy of `dict` with `key` set to `value`",
+    ),
+    (
+        "Dict.remove",
+        "key dict",
+        "Returns a copy of `dict` without `key`",
+    ),
+    ("Dict.has", "key dict", "Checks if `dict` has the key `key`"),
+    (
+        "Dict.keys",
+        "dict",
+        "Returns the keys of `dict`, in insertion order",
+    ),
+    (
+        "Dict.values",
+        "dict",
+        "Returns the values of `dict`, in insertion order",
+    ),
+    (
+        "Dict.toList",
+        "dict",
+        "Returns the entries of `dict` as a list of `(key, value)` tuples",
+    ),
+    (
+        "Dict.len",
+        "dict",
+        "Returns the number of entries of `dict`",
+    ),
+    ("Dict.show", "dict", "Converts `dict` to a string"),
+    ("Str.get", "n str", "Returns the `n`th character of `str`"),
+    (
+        "Str.split",
+        "sep str",
+        "Splits `str` on every occurrence of `sep`",
+    ),
+    (
+        "Str.toList",
+        "str",
+        "Returns the list of characters of `str`",
+    ),
+    (
+        "Str.len",
+        "str",
+        "Returns the number of characters of `str`",
+    ),
+    ("Str.new", "", "Creates an empty string"),
+    (
+        "Str.ord",
+        "str",
+        "Returns the code of the first character of `str`",
+    ),
+    (
+        "Str.chr",
+        "code",
+        "Returns the character with the code `code`",
+    ),
+    (
+        "Str.stripPrefix",
+        "prefix str",
+        "Returns `str` without `prefix`, or nil if it doesn't start with `prefix`",
+    ),
+    (
+        "Str.chars",
+        "str",
+        "Returns the list of characters of `str`",
+    ),
+    (
+        "Str.trim",
+        "str",
+        "Removes the whitespace from the start and the end of `str`",
+    ),
+    (
+        "Str.replace",
+        "from to str",
+        "Replaces every occurrence of `from` in `str` with `to`",
+    ),
+    (
+        "Str.contains",
+        "sub str",
+        "Checks if `sub` is part of `str`",
+    ),
+    (
+        "Str.find",
+        "sub str",
+        "Returns the index of the first occurrence of `sub` in `str`, or nil if there's none",
+    ),
+    ("Str.toUpper", "str", "Converts `str` to uppercase"),
+    ("Str.toLower", "str", "Converts `str` to lowercase"),
+    (
+        "Str.bytes",
+        "str",
+        "Returns the list of the UTF-8 bytes of `str`",
+    ),
+    ("Str.show", "str", "Returns `str` itself"),
+    (
+        "Json.parse",
+        "str",
+        "Parses the JSON document `str`, objects become dictionaries, raising a JsonError if it's invalid",
+    ),
+    (
+        "Json.encode",
+        "x",
+        "Encodes `x` as JSON, lists, tuples and ranges become arrays and dictionaries become objects",
+    ),
+    (
+        "Gc.stats",
+        "()",
+        "Returns a dictionary with the bytes allocated, freed and live, the values pending to be freed, and the collections run with their pauses in seconds",
+    ),
+    (
+        "Gc.collect",
+        "()",
+        "Frees every value that's waiting to be freed",
+    ),
+    (
+        "Gc.setStep",
+        "n",
+        "Sets how many pending values each allocation frees, higher values keep less memory around",
+    ),
+    (
+        "Sys.args",
+        "()",
+        "Returns the command line arguments of the script, without the script name",
+    ),
+    (
+        "Sys.env",
+        "name",
+        "Returns the value of the environment variable `name`, or nil if it isn't set",
+    ),
+    (
+        "Sys.setEnv",
+        "name value",
+        "Sets the environment variable `name` to `value`",
+    ),
+    ("Num.show", "n", "Converts `n` to a string"),
+    (
+        "Num.parse",
+        "str",
+        "Parses `str` as an integer or a float, integers can also be written as `0xff` or `0b1010`",
+    ),
+    (
+        "Num.toStr",
+        "digits n",
+        "Converts `n` to a string with exactly `digits` digits after the decimal point",
+    ),
+    (
+        "Int.div",
+        "x y",
+        "Divides the integer `x` by `y`, rounding towards zero",
+    ),
+    (
+        "Int.from",
+        "x",
+        "Converts a float (dropping its fractional part) or a string to an integer",
+    ),
+    ("Int.show", "n", "Converts `n` to a string"),
+    (
+        "Range.toList",
+        "r",
+        "Returns the elements of the range `r` as a list",
+    ),
+    ("Range.len", "r", "Returns the number of elements of `r`"),
+    (
+        "Range.has",
+        "n r",
+        "Checks if the integer `n` is in the range `r`",
+    ),
+    ("Range.show", "r", "Converts `r` to a string"),
+    ("Sym.show", "sym", "Converts `sym` to a string"),
+    ("Bool.show", "b", "Converts `b` to a string"),
+    ("Nil.show", "x", "Converts nil to a string"),
+    ("Fn.show", "f", "Converts `f` to a string"),
+    ("Result.ok", "x", "Wraps `x` in a successful result"),
+    ("Result.fail", "err", "Wraps `err` in a failed result"),
+    ("Result.show", "res", "Converts `res` to a string"),
+    (
+        "FFI.open",
+        "path",
+        "Loads the shared library at `path`, returning a Result",
+    ),
+    (
+        "FFI.get",
+        "name lib",
+        "Returns the function `name` exported by `lib`, or nil",
+    ),
+    (
+        "Wasm.open",
+        "path",
+        "Loads the WebAssembly plugin at `path`, returning a Result",
+    ),
+];
+
+fn documented(value: &Value, name: &str, params: &str, doc: &str) -> Option<Value> {
+    let fun: GcRef<Fn> = match value {
+        Value::Fn(f) => f.clone(),
+        _ => return None,
+    };
+
+    let params = params.split_whitespace().map(str::to_string).collect();
+    let info = FnInfo::new(name, params, Some(doc.to_string()));
+    Some(Value::Fn(GcRef::new((*fun).clone().with_info(info))))
+}
+
+/// Attaches the prelude documentation to the functions in `table`
+pub fn attach(table: &mut EnvTable) {
+    for (path, params, doc) in DOCS {
+        // the operators are global names, even the ones with dots like `(..)`
+        match path.split_once('.').filter(|_| !path.starts_with('(')) {
+            None => {
+                let name = Symbol::new(*path);
+                if let Some(fun) = table
+                    .get(&name)
+                    .and_then(|f| documented(&f, path, params, doc))
+                {
+                    table.insert(name, fun);
+                }
+            }
+            Some((module, method)) => {
+                let mut module = match table.get(&Symbol::new(module)) {
+                    Some(Value::Module(module)) => module,
+                    _ => continue,
+                };
+
+                let method = Symbol::new(method);
+                if let Some(fun) = module
+                    .fields
+                    .get(&method)
+                    .and_then(|f| documented(&f, path, params, doc))
+                {
+                    // SAFETY: the prelude modules are only referenced by the prelude table while
+                    // it's being built
+                    unsafe { module.mut_ref().fields.insert(method, fun) };
+                }
+            }
+        }
+    }
+}
+
+fn signature(fun: &Fn) -> String {
+    match &fun.info {
+        Some(info) if info.params.is_empty() => info.name.to_string(),
+        Some(info) => format!("{} {}", info.name, info.params.join(" ")),
+        None => fun.to_string(),
+    }
+}
+
+fn describe(value: &Value) -> String {
+    let mut out = String::new();
+
+    match value {
+        Value::Fn(fun) => {
+            let doc = fun.info.as_ref().and_then(|info| info.doc.as_deref());
+            writeln!(out, "{}", signature(fun)).ok();
+            writeln!(out, "  arity: {}", fun.arity).ok();
+            write!(out, "  {}", doc.unwrap_or("No documentation available")).ok();
+        }
+        Value::Module(module) => {
+            write!(out, "type {}", module.name).ok();
+
+            // the names starting with `#` are generated by the compiler, like the default
+            // implementations of the trait methods
+            let mut members: Vec<_> = module
+                .fields
+                .iter()
+                .filter(|(name, _)| !name.as_str().starts_with('#'))
+                .collect();
+            members.sort_by(|(a, _), (b, _)| a.as_str().cmp(b.as_str()));
+
+            for (name, member) in members {
+                let line = match &member {
+                    Value::Fn(fun) => {
+                        let doc = fun.info.as_ref().and_then(|info| info.doc.as_deref());
+                        match doc {
+                            Some(doc) => format!("{} - {}", signature(fun), doc),
+                            None => signature(fun),
+                        }
+                    }
+                    other => format!("{}.{} = {}", module.name, name, other),
+                };
+                write!(out, "\n  {}", line).ok();
+            }
+        }
+        other => {
+            write!(
+                out,
+                "'{}' is a value of type {}",
+                other,
+                other.type_of().name
+            )
+            .ok();
+        }
+    }
+
+    out
+}
+
+/// Resolves a global name, which may be a path to a member of a type, like `List.map`
+fn resolve(vm: &VirtualMachine, path: &str) -> InterpretResult<Value> {
+    let mut segments = path.split('.');
+    let global = segments.next().unwrap_or_default();
+    let mut value = vm
+        .get_global(global)
+        .ok_or_else(|| raise_err!(NameError, "Undefined variable '{}'", global))?;
+
+    for field in segments {
+        value = match &value {
+            Value::Module(module) => match module.fields.get(&Symbol::new(field)) {
+                Some(value) => value,
+                None => return raise!(FieldError, "Field '{}' not found in '{}'", field, value),
+            },
+            other => return raise!(TypeError, "Expected a type, found '{}'", other),
+        };
+    }
+
+    Ok(value)
+}
+
+/// Returns the text shown by `help` for a value, strings and symbols are looked up as names
+pub(crate) fn help_text(vm: &VirtualMachine, value: &Value) -> InterpretResult<String> {
+    match value {
+        Value::Str(name) => Ok(describe(&resolve(vm, name)?)),
+        Value::Sym(name) => Ok(describe(&resolve(vm, name.0.as_str())?)),
+        other => Ok(describe(other)),
+    }
+}
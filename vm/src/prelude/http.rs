@@ -0,0 +1,160 @@
+use std::io::Read;
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{fun::Fn, result, TryGet},
+    raise, Dict, EnvTable, List, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `Http` module, which sends HTTP requests
+pub fn http() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("get"),
+        Value::Fn(GcRef::new(Fn::new_native(1, get))),
+    );
+    methods.insert(
+        Symbol::new("post"),
+        Value::Fn(GcRef::new(Fn::new_native(3, post))),
+    );
+
+    YexModule::new(Symbol::new("Http"), methods)
+}
+
+/// Returns the name of a header, which can be a string or a symbol
+fn header_name(key: &Value) -> InterpretResult<String> {
+    match key {
+        Value::Str(name) => Ok(name.to_string()),
+        Value::Sym(name) => Ok(name.as_str().to_string()),
+        other => raise!(TypeError, "Invalid header name '{}'", other),
+    }
+}
+
+/// Sends a request, with a body if it's given, recording the response as a tuple `(status, headers, body)`, where the
+/// headers are a list of `(name, value)` tuples, since traces can't hold dictionaries, or as the
+/// error message if the request couldn't be sent
+fn send(
+    vm: &mut VirtualMachine,
+    request: ureq::Request,
+    body: Option<Vec<u8>>,
+) -> InterpretResult<Value> {
+    let value = vm.nondeterministic("http", || {
+        let response = match body {
+            Some(body) => request.send_bytes(&body),
+            None => request.call(),
+        };
+        let response = match response {
+            Ok(response) | Err(ureq::Error::Status(_, response)) => response,
+            Err(e) => return Ok(e.to_string().into()),
+        };
+
+        let status = Value::Int(response.status() as i64);
+
+        // header names are case insensitive, and repeated headers are joined by commas
+        let mut names: Vec<String> = vec![];
+        for name in response.headers_names() {
+            let name = name.to_lowercase();
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+        let headers: Vec<Value> = names
+            .into_iter()
+            .map(|name| {
+                let value = response.all(&name).join(", ");
+                vec![name.into(), value.into()].into()
+            })
+            .collect();
+        let headers = headers.into_iter().rev().collect::<List>();
+
+        let mut body = vec![];
+        if let Err(e) = response.into_reader().read_to_end(&mut body) {
+            return Ok(e.to_string().into());
+        }
+        let body = String::from_utf8_lossy(&body).into_owned();
+
+        Ok(vec![status, headers.into(), body.into()].into())
+    })?;
+
+    let response = match value {
+        Value::Tuple(response) => response,
+        e => return Ok(result::fail(vec![e])),
+    };
+
+    let headers = match &response.0[1] {
+        Value::List(headers) => headers.iter().map(|header| match header {
+            Value::Tuple(header) => (header.0[0].clone(), header.0[1].clone()),
+            _ => unreachable!(),
+        }),
+        _ => unreachable!(),
+    };
+    let headers = Dict::from_entries(headers.collect::<Vec<_>>())?;
+
+    let entries = [
+        ("status", response.0[0].clone()),
+        ("headers", headers.into()),
+        ("body", response.0[2].clone()),
+    ]
+    .into_iter()
+    .map(|(key, value)| (Value::from(Symbol::new(key)), value));
+    Ok(result::ok(vec![Dict::from_entries(entries)?.into()]))
+}
+
+/// Sends a GET request, returning `Result.ok %{status, headers, body}`, or `Result.fail msg` if
+/// it couldn't be sent
+fn get(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let url: String = args[0].get()?;
+
+    send(vm, ureq::get(&url), None)
+}
+
+/// Sends a POST request with a string or bytes body and a dictionary of headers
+fn post(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let url: String = args[0].get()?;
+
+    let body = match &args[1] {
+        Value::Str(s) => s.as_bytes().to_vec(),
+        Value::Bytes(b) => b.0.to_vec(),
+        other => return raise!(TypeError, "Can't send '{}' as a request body", other),
+    };
+
+    let mut request = ureq::post(&url);
+    let headers: Dict = args[2].get()?;
+    for (key, value) in headers.iter() {
+        let value: String = value.get()?;
+        request = request.set(&header_name(key)?, &value);
+    }
+
+    send(vm, request, Some(body))
+}
+
+#[test]
+fn get_request() {
+    use std::{io::Write, net::TcpListener, thread};
+
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}/", listener.local_addr().unwrap());
+    let server = thread::spawn(move || {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        stream
+            .write_all(
+                b"HTTP/1.1 404 Not Found\r\nX-Yex: a\r\nx-yex: b\r\nContent-Length: 2\r\n\r\nno",
+            )
+            .unwrap();
+    });
+
+    let mut vm = VirtualMachine::default();
+    let response = get(&mut vm, vec![url.into()]).unwrap();
+    server.join().unwrap();
+
+    assert_eq!(
+        response.to_string(),
+        r#"(Result.ok %{status: 404, headers: %{"x-yex" => "a, b", "content-length" => "2"}, body: "no"})"#
+    );
+}
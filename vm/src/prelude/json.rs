@@ -0,0 +1,430 @@
+use alloc::{
+    string::{String, ToString},
+    vec::Vec,
+};
+use core::{fmt::Write, iter::Peekable, str::Chars};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{fun::Fn, list::Items, TryGet},
+    raise, raise_err, Dict, EnvTable, List, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// How deep arrays and objects can be nested, so malicious documents can't overflow the stack
+const MAX_DEPTH: usize = 512;
+
+/// Creates the `Json` module
+pub fn json() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("parse"),
+        Value::Fn(GcRef::new(Fn::new_native(1, parse))),
+    );
+    methods.insert(
+        Symbol::new("encode"),
+        Value::Fn(GcRef::new(Fn::new_native(1, encode))),
+    );
+
+    YexModule::new(Symbol::new("Json"), methods)
+}
+
+fn parse(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let source: String = args[0].get()?;
+    let mut decoder = Decoder {
+        chars: source.chars().peekable(),
+        line: 1,
+        column: 1,
+    };
+
+    let value = decoder.value(0)?;
+    decoder.skip_whitespace();
+
+    match decoder.chars.peek().copied() {
+        None => Ok(value),
+        Some(c) => decoder.fail(format!("Unexpected '{}' after the document", c)),
+    }
+}
+
+fn encode(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let mut out = String::new();
+    encode_value(&args[0], &mut out, 0)?;
+    Ok(out.into())
+}
+
+struct Decoder<'a> {
+    chars: Peekable<Chars<'a>>,
+    line: usize,
+    column: usize,
+}
+
+impl Decoder<'_> {
+    fn fail<T>(&self, msg: String) -> InterpretResult<T> {
+        raise!(JsonError, "[{}:{}] {}", self.line, self.column, msg)
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars.next()?;
+        if c == '\n' {
+            self.line += 1;
+            self.column = 1;
+        } else {
+            self.column += 1;
+        }
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(' ' | '\t' | '\n' | '\r') = self.chars.peek() {
+            self.next();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> InterpretResult<()> {
+        match self.next() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => self.fail(format!("Expected '{}', found '{}'", expected, c)),
+            None => self.fail(format!(
+                "Expected '{}', found the end of the input",
+                expected
+            )),
+        }
+    }
+
+    fn keyword(&mut self, word: &str, value: Value) -> InterpretResult<Value> {
+        for c in word.chars() {
+            self.expect(c)?;
+        }
+        Ok(value)
+    }
+
+    fn value(&mut self, depth: usize) -> InterpretResult<Value> {
+        if depth > MAX_DEPTH {
+            return self.fail("The document is nested too deeply".to_string());
+        }
+
+        self.skip_whitespace();
+
+        match self.chars.peek() {
+            Some('n') => self.keyword("null", Value::Nil),
+            Some('t') => self.keyword("true", Value::Bool(true)),
+            Some('f') => self.keyword("false", Value::Bool(false)),
+            Some('"') => Ok(self.string()?.into()),
+            Some('[') => self.array(depth),
+            Some('{') => self.object(depth),
+            Some('-' | '0'..='9') => self.number(),
+            Some(&c) => self.fail(format!("Unexpected '{}'", c)),
+            None => self.fail("Unexpected end of the input".to_string()),
+        }
+    }
+
+    fn array(&mut self, depth: usize) -> InterpretResult<Value> {
+        self.expect('[')?;
+        let mut items = vec![];
+
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&']') {
+            self.next();
+            return Ok(List::new().into());
+        }
+
+        loop {
+            items.push(self.value(depth + 1)?);
+            self.skip_whitespace();
+
+            match self.next() {
+                Some(',') => continue,
+                Some(']') => break,
+                _ => return self.fail("Expected ',' or ']' in the array".to_string()),
+            }
+        }
+
+        Ok(items.into_iter().rev().collect::<List>().into())
+    }
+
+    fn object(&mut self, depth: usize) -> InterpretResult<Value> {
+        self.expect('{')?;
+        let mut entries = vec![];
+
+        self.skip_whitespace();
+        if self.chars.peek() == Some(&'}') {
+            self.next();
+            return Ok(Dict::new().into());
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.chars.peek() != Some(&'"') {
+                return self.fail("Expected a string as the object key".to_string());
+            }
+
+            let key = self.string()?;
+            self.skip_whitespace();
+            self.expect(':')?;
+
+            let value = self.value(depth + 1)?;
+            entries.push((key.into(), value));
+            self.skip_whitespace();
+
+            match self.next() {
+                Some(',') => continue,
+                Some('}') => break,
+                _ => return self.fail("Expected ',' or '}' in the object".to_string()),
+            }
+        }
+
+        Ok(Dict::from_entries(entries)?.into())
+    }
+
+    fn string(&mut self) -> InterpretResult<String> {
+        self.expect('"')?;
+        let mut str = String::new();
+
+        loop {
+            let c = match self.next() {
+                Some(c) => c,
+                None => return self.fail("Unterminated string".to_string()),
+            };
+
+            match c {
+                '"' => return Ok(str),
+                '\\' => {
+                    let c = match self.next() {
+                        Some('"') => '"',
+                        Some('\\') => '\\',
+                        Some('/') => '/',
+                        Some('b') => '\u{8}',
+                        Some('f') => '\u{c}',
+                        Some('n') => '\n',
+                        Some('r') => '\r',
+                        Some('t') => '\t',
+                        Some('u') => self.unicode()?,
+                        _ => return self.fail("Invalid escape sequence".to_string()),
+                    };
+                    str.push(c);
+                }
+                c if (c as u32) < 0x20 => {
+                    return self.fail("Control characters must be escaped in strings".to_string())
+                }
+                c => str.push(c),
+            }
+        }
+    }
+
+    fn hex4(&mut self) -> InterpretResult<u32> {
+        let mut code = 0;
+        for _ in 0..4 {
+            let digit = self.next().and_then(|c| c.to_digit(16));
+            match digit {
+                Some(digit) => code = code * 16 + digit,
+                None => return self.fail("Invalid unicode escape".to_string()),
+            }
+        }
+        Ok(code)
+    }
+
+    /// Decodes a `\u` escape, characters outside the basic plane are written as surrogate pairs
+    fn unicode(&mut self) -> InterpretResult<char> {
+        let high = self.hex4()?;
+
+        let code = if (0xD800..0xDC00).contains(&high) {
+            if self.next() != Some('\\') || self.next() != Some('u') {
+                return self.fail("Expected the low surrogate of the unicode escape".to_string());
+            }
+
+            let low = self.hex4()?;
+            if !(0xDC00..0xE000).contains(&low) {
+                return self.fail("Invalid low surrogate in the unicode escape".to_string());
+            }
+            0x10000 + ((high - 0xD800) << 10) + (low - 0xDC00)
+        } else {
+            high
+        };
+
+        match char::from_u32(code) {
+            Some(c) => Ok(c),
+            None => self.fail("Invalid unicode escape".to_string()),
+        }
+    }
+
+    fn digits(&mut self, number: &mut String) -> bool {
+        let mut any = false;
+        while let Some(&c) = self.chars.peek() {
+            if !c.is_ascii_digit() {
+                break;
+            }
+            number.push(c);
+            self.next();
+            any = true;
+        }
+        any
+    }
+
+    /// Numbers without a fraction or an exponent are decoded as integers, if they fit in one
+    fn number(&mut self) -> InterpretResult<Value> {
+        let mut number = String::new();
+        let mut float = false;
+
+        if self.chars.peek() == Some(&'-') {
+            number.push('-');
+            self.next();
+        }
+
+        let start = number.len();
+        if !self.digits(&mut number) {
+            return self.fail("Expected a digit".to_string());
+        }
+        if number[start..].starts_with('0') && number.len() - start > 1 {
+            return self.fail("Numbers can't have leading zeros".to_string());
+        }
+
+        if self.chars.peek() == Some(&'.') {
+            float = true;
+            number.push('.');
+            self.next();
+            if !self.digits(&mut number) {
+                return self.fail("Expected a digit after the decimal point".to_string());
+            }
+        }
+
+        if let Some('e' | 'E') = self.chars.peek() {
+            float = true;
+            number.push('e');
+            self.next();
+            if let Some(&c @ ('+' | '-')) = self.chars.peek() {
+                number.push(c);
+                self.next();
+            }
+            if !self.digits(&mut number) {
+                return self.fail("Expected a digit in the exponent".to_string());
+            }
+        }
+
+        if !float {
+            if let Ok(n) = number.parse::<i64>() {
+                return Ok(Value::Int(n));
+            }
+        }
+
+        // the numbers too large for a float would be decoded as infinity, which JSON can't encode
+        match number.parse::<f64>() {
+            Ok(n) if n.is_finite() => Ok(Value::Num(n)),
+            Ok(_) => self.fail(format!("The number '{}' is too large", number)),
+            Err(_) => self.fail(format!("Invalid number '{}'", number)),
+        }
+    }
+}
+
+fn encode_value(value: &Value, out: &mut String, depth: usize) -> InterpretResult<()> {
+    if depth > MAX_DEPTH {
+        return raise!(JsonError, "The value is nested too deeply to be encoded");
+    }
+
+    match value {
+        Value::Nil => out.push_str("null"),
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        Value::Int(n) => write!(out, "{}", n).unwrap(),
+        Value::Num(n) if n.is_finite() => write!(out, "{:?}", n).unwrap(),
+        Value::Num(n) => return raise!(JsonError, "Can't encode '{}' as a JSON number", n),
+        Value::Str(s) => encode_str(s, out),
+        Value::Sym(s) => encode_str(s.0.as_str(), out),
+        Value::Tuple(tup) => encode_array(tup.0.iter().cloned(), out, depth)?,
+        Value::List(_) | Value::Array(_) | Value::Range(_) => {
+            encode_array(Items::new(value)?, out, depth)?
+        }
+        Value::Dict(dict) => {
+            out.push('{');
+            for (i, (key, value)) in dict.iter().enumerate() {
+                if i != 0 {
+                    out.push(',');
+                }
+
+                match key {
+                    Value::Str(s) => encode_str(s, out),
+                    Value::Sym(s) => encode_str(s.0.as_str(), out),
+                    other => {
+                        return Err(raise_err!(
+                            JsonError,
+                            "Only strings and symbols can be JSON object keys, found '{}'",
+                            other
+                        ))
+                    }
+                }
+
+                out.push(':');
+                encode_value(value, out, depth + 1)?;
+            }
+            out.push('}');
+        }
+        other => return raise!(JsonError, "Can't encode '{}' as JSON", other),
+    }
+
+    Ok(())
+}
+
+fn encode_array(
+    items: impl Iterator<Item = Value>,
+    out: &mut String,
+    depth: usize,
+) -> InterpretResult<()> {
+    out.push('[');
+    for (i, item) in items.enumerate() {
+        if i != 0 {
+            out.push(',');
+        }
+        encode_value(&item, out, depth + 1)?;
+    }
+    out.push(']');
+    Ok(())
+}
+
+fn encode_str(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+#[test]
+fn roundtrip() {
+    let source = r#"{"name": "yex", "tags": ["a", "\u00e9\ud83d\ude00"], "n": -12, "x": 1.5e2, "ok": true, "none": null}"#;
+    let value = parse(core::ptr::null_mut(), vec![source.to_string().into()]).unwrap();
+
+    let dict: Dict = value.get().unwrap();
+    assert_eq!(
+        dict.get(&"n".to_string().into()).unwrap(),
+        Some(Value::Int(-12))
+    );
+    assert_eq!(
+        dict.get(&"x".to_string().into()).unwrap(),
+        Some(Value::Num(150.0))
+    );
+
+    let encoded = encode(core::ptr::null_mut(), vec![value.clone()]).unwrap();
+    let decoded = parse(core::ptr::null_mut(), vec![encoded]).unwrap();
+    assert_eq!(decoded, value);
+
+    for invalid in [
+        "[1, 2",
+        "{\"a\" 1}",
+        "01",
+        "\"\\x\"",
+        "[1] 2",
+        "nul",
+        "1e400",
+        "-1e400",
+    ] {
+        let err = parse(core::ptr::null_mut(), vec![invalid.to_string().into()]).unwrap_err();
+        assert_eq!(err.err, Symbol::new("JsonError"));
+    }
+}
@@ -0,0 +1,185 @@
+use std::{
+    collections::hash_map::RandomState,
+    f64::consts,
+    hash::{BuildHasher, Hasher},
+    sync::atomic::{AtomicU64, Ordering},
+};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{fun::Fn, TryGet},
+    raise, EnvTable, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `Math` module, with the usual numeric functions and a random number generator
+pub fn math() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("sqrt"),
+        Value::Fn(GcRef::new(Fn::new_native(1, sqrt))),
+    );
+    methods.insert(
+        Symbol::new("sin"),
+        Value::Fn(GcRef::new(Fn::new_native(1, sin))),
+    );
+    methods.insert(
+        Symbol::new("cos"),
+        Value::Fn(GcRef::new(Fn::new_native(1, cos))),
+    );
+    methods.insert(
+        Symbol::new("pow"),
+        Value::Fn(GcRef::new(Fn::new_native(2, pow))),
+    );
+    methods.insert(
+        Symbol::new("log"),
+        Value::Fn(GcRef::new(Fn::new_native(1, log))),
+    );
+    methods.insert(
+        Symbol::new("floor"),
+        Value::Fn(GcRef::new(Fn::new_native(1, floor))),
+    );
+    methods.insert(
+        Symbol::new("ceil"),
+        Value::Fn(GcRef::new(Fn::new_native(1, ceil))),
+    );
+    methods.insert(
+        Symbol::new("round"),
+        Value::Fn(GcRef::new(Fn::new_native(1, round))),
+    );
+    methods.insert(
+        Symbol::new("abs"),
+        Value::Fn(GcRef::new(Fn::new_native(1, abs))),
+    );
+    methods.insert(
+        Symbol::new("random"),
+        Value::Fn(GcRef::new(Fn::new_native(1, random))),
+    );
+    methods.insert(
+        Symbol::new("randomRange"),
+        Value::Fn(GcRef::new(Fn::new_native(2, random_range))),
+    );
+    methods.insert(Symbol::new("pi"), Value::Num(consts::PI));
+
+    YexModule::new(Symbol::new("Math"), methods)
+}
+
+fn sqrt(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let x: f64 = args[0].get()?;
+    Ok(Value::Num(x.sqrt()))
+}
+
+fn sin(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let x: f64 = args[0].get()?;
+    Ok(Value::Num(x.sin()))
+}
+
+fn cos(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let x: f64 = args[0].get()?;
+    Ok(Value::Num(x.cos()))
+}
+
+/// Integers raised to a non-negative integer stay integers, anything else is a float
+fn pow(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    match (&args[0], &args[1]) {
+        (Value::Int(x), Value::Int(y)) if *y >= 0 => {
+            match u32::try_from(*y).ok().and_then(|y| x.checked_pow(y)) {
+                Some(n) => Ok(Value::Int(n)),
+                None => raise!(OverflowError, "{} to the power of {} overflows", x, y),
+            }
+        }
+        (x, y) => {
+            let (x, y): (f64, f64) = (x.get()?, y.get()?);
+            Ok(Value::Num(x.powf(y)))
+        }
+    }
+}
+
+/// The natural logarithm
+fn log(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let x: f64 = args[0].get()?;
+    Ok(Value::Num(x.ln()))
+}
+
+/// Rounds `args[0]` with `f`, returning an integer
+fn to_int(args: &[Value], f: fn(f64) -> f64) -> InterpretResult<Value> {
+    let x = match &args[0] {
+        Value::Int(n) => return Ok(Value::Int(*n)),
+        other => f(other.get()?),
+    };
+
+    // `as` saturates, so the bounds are checked before
+    if x.is_finite() && x >= i64::MIN as f64 && x < i64::MAX as f64 {
+        Ok(Value::Int(x as i64))
+    } else {
+        raise!(ValueError, "Can't convert {} to an integer", x)
+    }
+}
+
+fn floor(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    to_int(&args, f64::floor)
+}
+
+fn ceil(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    to_int(&args, f64::ceil)
+}
+
+fn round(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    to_int(&args, f64::round)
+}
+
+fn abs(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    match &args[0] {
+        Value::Int(n) => match n.checked_abs() {
+            Some(n) => Ok(Value::Int(n)),
+            None => raise!(OverflowError, "The absolute value of {} overflows", n),
+        },
+        other => {
+            let x: f64 = other.get()?;
+            Ok(Value::Num(x.abs()))
+        }
+    }
+}
+
+/// The state of the xorshift generator behind `Math.random`, zero until it's seeded
+static STATE: AtomicU64 = AtomicU64::new(0);
+
+/// Returns 64 random bits, seeding the generator from the hasher keys of the process first
+fn next_u64() -> u64 {
+    let mut x = STATE.load(Ordering::Relaxed);
+    while x == 0 {
+        x = RandomState::new().build_hasher().finish();
+    }
+
+    x ^= x << 13;
+    x ^= x >> 7;
+    x ^= x << 17;
+    STATE.store(x, Ordering::Relaxed);
+    x
+}
+
+fn random(vm: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+
+    // the 53 high bits fill the mantissa, giving a float in [0, 1)
+    vm.nondeterministic("random", || {
+        Ok(Value::Num((next_u64() >> 11) as f64 / (1u64 << 53) as f64))
+    })
+}
+
+/// Returns an integer from `start` up to, but not including, `end`
+fn random_range(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let start: i64 = args[0].get()?;
+    let end: i64 = args[1].get()?;
+
+    if start >= end {
+        return raise!(ValueError, "The range {}..{} is empty", start, end);
+    }
+
+    vm.nondeterministic("random", || {
+        let len = end.abs_diff(start);
+        Ok(Value::Int(start.wrapping_add((next_u64() % len) as i64)))
+    })
+}
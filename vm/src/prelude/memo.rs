@@ -0,0 +1,106 @@
+use alloc::{vec, vec::Vec};
+use core::cell::RefCell;
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{
+        dict::Dict,
+        fun::{Fn, FnKind},
+    },
+    raise, stackvec, Bytecode, OpCode, OpCodeMetadata, Value, VirtualMachine,
+};
+
+/// The results returned by a memoized function so far, by its arguments
+type Cache = GcRef<RefCell<Dict>>;
+
+/// Returns a function that calls `f` once for each argument, remembering what it returned
+///
+/// The arguments are the cache key, so they must be hashable like the keys of a dictionary
+pub fn memo(_: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    let fun = match &args[0] {
+        Value::Fn(fun) => fun,
+        other => return raise!(TypeError, "Can't memoize '{}', expected a function", other),
+    };
+
+    let arity = fun.arity;
+    // the errors raised by the cache, like for unhashable arguments, point to the function
+    let (line, column) = match &*fun.body {
+        FnKind::Bytecode(code) => code.first().map_or((0, 0), |op| (op.line, op.column)),
+        FnKind::Native(_) => (0, 0),
+    };
+    let cache: Cache = GcRef::new(RefCell::new(Dict::new()));
+
+    // `f` is called by the bytecode of the returned function, in a frame of the dispatch loop, so
+    // the recursive calls don't grow the native stack
+    let lookup = {
+        let cache = cache.clone();
+        Fn::from_closure(arity, move |_, args| {
+            let cached = cache.borrow().get(&key(args))?;
+            Ok(match cached {
+                Some(value) => vec![Value::Bool(true), value].into(),
+                None => vec![Value::Bool(false), Value::Nil].into(),
+            })
+        })
+    };
+    let store = Fn::from_closure(arity + 1, move |_, args| {
+        let (value, args) = args.split_last().unwrap();
+        cache.borrow_mut().set(key(args), value.clone())?;
+        Ok(value.clone())
+    });
+
+    Ok(Value::Fn(GcRef::new(Fn {
+        arity,
+        body: GcRef::new(FnKind::Bytecode(memoized(arity, line, column))),
+        args: stackvec![],
+        info: fun.info.clone(),
+        upvalues: vec![lookup.into(), args[0].clone(), store.into()],
+        more: None,
+        variadic: false,
+    })))
+}
+
+/// The arguments of a call, which are the key of its result in the cache
+fn key(args: &[Value]) -> Value {
+    match args {
+        [arg] => arg.clone(),
+        args => args.to_vec().into(),
+    }
+}
+
+/// The code of a memoized function, which looks up its arguments in the cache with the first
+/// upvalue, calling the wrapped function, the second one, on a miss and storing its result with
+/// the third one
+fn memoized(arity: usize, line: usize, column: usize) -> Bytecode {
+    let found = arity;
+    let args = || (0..arity).rev().map(OpCode::Load);
+
+    let mut code: Vec<_> = (0..arity).map(OpCode::Save).collect();
+    code.extend(args());
+    code.extend([
+        OpCode::LoadUpval(0),
+        OpCode::Call(arity),
+        OpCode::Save(found),
+        OpCode::Load(found),
+        OpCode::TupGet(0),
+    ]);
+
+    // the jumps over the code that returns the cached value, and over the code of a miss
+    let miss = code.len() + 4;
+    let end = miss + 2 * (arity + 2);
+    code.extend([
+        OpCode::Jmf(miss),
+        OpCode::Load(found),
+        OpCode::TupGet(1),
+        OpCode::Jmp(end),
+    ]);
+
+    code.extend(args());
+    code.extend([OpCode::LoadUpval(1), OpCode::Call(arity)]);
+    code.extend(args());
+    code.extend([OpCode::LoadUpval(2), OpCode::Call(arity + 1)]);
+
+    code.into_iter()
+        .map(|op| OpCodeMetadata::new(line, column, op))
+        .collect()
+}
@@ -0,0 +1,65 @@
+use alloc::vec::Vec;
+
+use crate::{
+    error::InterpretResult,
+    gc::{self, GcRef},
+    literal::{fun::Fn, nil, TryGet},
+    raise, Dict, EnvTable, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `Gc` module, which reports and tunes how the memory is freed
+pub fn gc() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("stats"),
+        Value::Fn(GcRef::new(Fn::new_native(1, stats))),
+    );
+    methods.insert(
+        Symbol::new("collect"),
+        Value::Fn(GcRef::new(Fn::new_native(1, collect))),
+    );
+    methods.insert(
+        Symbol::new("setStep"),
+        Value::Fn(GcRef::new(Fn::new_native(1, set_step))),
+    );
+
+    YexModule::new(Symbol::new("Gc"), methods)
+}
+
+fn stats(_: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let stats = gc::stats();
+    let int = |n: usize| Value::Int(n as i64);
+
+    let entries = [
+        ("allocated", int(stats.allocated)),
+        ("freed", int(stats.freed)),
+        ("live", int(stats.live())),
+        ("pending", int(stats.pending)),
+        ("collections", int(stats.collections)),
+        ("cycles", int(stats.cycles)),
+        ("maxPause", Value::Num(stats.max_pause.as_secs_f64())),
+        ("totalPause", Value::Num(stats.total_pause.as_secs_f64())),
+    ];
+
+    let entries = entries
+        .into_iter()
+        .map(|(key, value)| (Value::from(Symbol::new(key)), value));
+    Ok(Dict::from_entries(entries)?.into())
+}
+
+fn collect(_: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    gc::collect();
+    Ok(nil())
+}
+
+fn set_step(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let n: i64 = args[0].get()?;
+
+    if n < 1 {
+        return raise!(ValueError, "The step must be at least 1, got {}", n);
+    }
+
+    gc::set_step_size(n as usize);
+    Ok(nil())
+}
@@ -1,11 +1,47 @@
+#[cfg(feature = "std")]
+use crate::literal::{nil, show};
 use crate::{
     env::EnvTable,
     error::InterpretError,
     gc::GcRef,
-    literal::{fun::FnKind, nil, show, TryGet, Value},
-    raise_err, InterpretResult, Symbol, VirtualMachine, YexModule,
+    literal::{fun::FnKind, TryGet, Value},
+    raise, raise_err, InterpretResult, Symbol, VirtualMachine, YexModule,
 };
+use alloc::{
+    boxed::Box,
+    string::{String, ToString},
+};
+#[cfg(feature = "std")]
 use std::io::{self, Write};
+
+mod capabilities;
+mod gen;
+mod help;
+#[cfg(feature = "http")]
+mod http;
+mod json;
+#[cfg(feature = "std")]
+mod math;
+mod memo;
+mod memory;
+#[cfg(feature = "std")]
+mod net;
+mod ops;
+#[cfg(feature = "std")]
+mod os;
+#[cfg(feature = "std")]
+mod regex;
+#[cfg(feature = "std")]
+mod stdio;
+#[cfg(feature = "std")]
+mod sys;
+mod task;
+mod testing;
+#[cfg(feature = "std")]
+mod thread;
+pub use capabilities::Capabilities;
+pub(crate) use help::help_text;
+
 #[macro_export]
 /// Insert a function into a `EnvTable`
 macro_rules! insert_fn {
@@ -19,6 +55,10 @@ macro_rules! insert_fn {
                 arity: $arity,
                 body: GcRef::new(FnKind::Native(|_, it| $fn(&*it))),
                 args: $crate::StackVec::new(),
+                info: None,
+                upvalues: vec![],
+                more: None,
+                variadic: false,
             })),
         )
     };
@@ -32,6 +72,10 @@ macro_rules! insert_fn {
                     $fn(unsafe { vm.as_mut().unwrap() }, &*it)
                 })),
                 args: $crate::StackVec::new(),
+                info: None,
+                upvalues: vec![],
+                more: None,
+                variadic: false,
             })),
         )
     };
@@ -44,34 +88,46 @@ macro_rules! insert {
     };
 }
 
+#[cfg(feature = "std")]
 fn println(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
     println!("{}", show(vm, args.into())?);
     Ok(nil())
 }
 
+#[cfg(feature = "std")]
 fn print(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
     print!("{}", show(vm, args.into())?);
     Ok(nil())
 }
 
+#[cfg(feature = "std")]
 fn debug_stack(vm: &mut VirtualMachine, _args: &[Value]) -> InterpretResult<Value> {
     println!("{:#?}", vm.stack);
     Ok(nil())
 }
 
-fn input(args: &[Value]) -> InterpretResult<Value> {
+#[cfg(feature = "std")]
+fn help(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    println!("{}", vm.help(&args[0])?);
+    Ok(nil())
+}
+
+#[cfg(feature = "std")]
+fn input(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
     let prompt: String = args[0].get()?;
     print!("{}", prompt);
 
     io::stdout().flush()?;
 
-    let mut input = String::new();
+    vm.nondeterministic("input", || {
+        let mut input = String::new();
 
-    io::stdin().read_line(&mut input)?;
+        io::stdin().read_line(&mut input)?;
 
-    input.pop();
+        input.pop();
 
-    Ok(Value::Str(GcRef::new(input)))
+        Ok(Value::Str(GcRef::new(input)))
+    })
 }
 
 fn r#type(args: &[Value]) -> InterpretResult<Value> {
@@ -79,17 +135,17 @@ fn r#type(args: &[Value]) -> InterpretResult<Value> {
 }
 
 fn inspect(args: &[Value]) -> InterpretResult<Value> {
-    Ok(Value::Str(GcRef::new(format!("{:#?}", &args[0]))))
+    Ok(Value::Str(GcRef::new(crate::pretty(&args[0]))))
 }
 
 fn num(args: &[Value]) -> InterpretResult<Value> {
     let str: String = args[0].get()?;
 
-    str.parse::<f64>()
-        .map(Value::Num)
-        .map_err(|_| raise_err!(TypeError, "Cannot convert '{}' to number", str))
+    crate::literal::num::parse(&str)
+        .ok_or_else(|| raise_err!(TypeError, "Cannot convert '{}' to number", str))
 }
 
+#[cfg(feature = "std")]
 fn exit(args: &[Value]) -> InterpretResult<Value> {
     let code: isize = args[0].get()?;
 
@@ -103,22 +159,105 @@ fn raise(args: &[Value]) -> InterpretResult<Value> {
     Err(InterpretError {
         err,
         msg,
-        line: unsafe { crate::LINE },
-        column: unsafe { crate::COLUMN },
+        value: None,
+        line: 0,
+        column: 0,
+        builtin: None,
+    })
+}
+
+fn throw(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    let (err, msg, value) = match &args[0] {
+        Value::Tagged(tagged) => (
+            tagged.1,
+            args[0].to_string(),
+            Some(Box::new(args[0].clone())),
+        ),
+        // the error names are rescued as symbols, so throwing the name raises the error again
+        Value::Sym(sym) => match vm.rescued.take() {
+            Some(e) if e.err == sym.0 && e.value.is_none() => return Err(e),
+            _ => (sym.0, String::from("Uncaught error"), None),
+        },
+        other => {
+            return raise!(
+                TypeError,
+                "Can't throw '{}', expected a tagged value",
+                other
+            )
+        }
+    };
+
+    Err(InterpretError {
+        err,
+        msg,
+        value,
+        line: 0,
+        column: 0,
+        builtin: None,
     })
 }
 
+/// Creates the prelude with every builtin, the globals of [`crate::VirtualMachine::default`]
 pub fn prelude() -> EnvTable {
+    prelude_with(Capabilities::ALL)
+}
+
+/// Creates the prelude, only installing the functions allowed by `caps`
+#[cfg_attr(not(feature = "std"), allow(unused_variables))]
+pub fn prelude_with(caps: Capabilities) -> EnvTable {
     let mut prelude = EnvTable::with_capacity(64);
-    insert_fn!(:vm prelude, "println", println, 1);
-    insert_fn!(:vm prelude, "print", print, 1);
-    insert_fn!(:vm prelude, "print_stack!", debug_stack, 1);
-    insert_fn!(prelude, "input", input);
+    #[cfg(feature = "std")]
+    if caps.contains(Capabilities::IO) {
+        insert_fn!(:vm prelude, "println", println, 1);
+        insert_fn!(:vm prelude, "print", print, 1);
+        insert_fn!(:vm prelude, "print_stack!", debug_stack, 1);
+        insert_fn!(:vm prelude, "input", input, 1);
+        insert_fn!(:vm prelude, "help", help, 1);
+        insert!(prelude, "IO", Value::Module(GcRef::new(stdio::io())));
+    }
+    #[cfg(feature = "std")]
+    if caps.contains(Capabilities::PROCESS) {
+        insert_fn!(prelude, "exit", exit);
+        insert!(prelude, "Os", Value::Module(GcRef::new(os::os())));
+    }
+    #[cfg(feature = "std")]
+    if caps.contains(Capabilities::ENV) {
+        insert!(prelude, "Sys", Value::Module(GcRef::new(sys::sys())));
+    }
+    #[cfg(feature = "std")]
+    if caps.contains(Capabilities::NET) {
+        insert!(prelude, "Net", Value::Module(GcRef::new(net::net())));
+        insert!(prelude, "Socket", Value::Module(GcRef::new(net::socket())));
+        #[cfg(feature = "http")]
+        insert!(prelude, "Http", Value::Module(GcRef::new(http::http())));
+    }
     insert_fn!(prelude, "type", r#type);
     insert_fn!(prelude, "inspect", inspect);
     insert_fn!(prelude, "num", num);
-    insert_fn!(prelude, "exit", exit);
     insert_fn!(prelude, "raise", raise, 2);
+    insert_fn!(:vm prelude, "throw", throw, 1);
+    insert_fn!(:vm prelude, "memo", memo::memo, 1);
+    insert_fn!(prelude, "assert", testing::assert, 2);
+    insert_fn!(:vm prelude, "assert_eq", testing::assert_eq, 2);
+    insert_fn!(:vm prelude, "spawn", task::spawn, 1);
+    insert_fn!(:vm prelude, "send", task::send, 2);
+    insert_fn!(:vm prelude, "receive", task::receive, 1);
+    insert_fn!(:vm prelude, "yield", crate::resumable::yield_, 1);
+    insert!(prelude, "Task", Value::Module(GcRef::new(task::task())));
+    insert!(prelude, "Gen", Value::Module(GcRef::new(gen::gen())));
+    #[cfg(feature = "std")]
+    insert!(
+        prelude,
+        "Thread",
+        Value::Module(GcRef::new(thread::thread()))
+    );
+    #[cfg(feature = "std")]
+    insert!(
+        prelude,
+        "Channel",
+        Value::Module(GcRef::new(thread::channel()))
+    );
+    ops::insert(&mut prelude);
 
     insert!(prelude, "Nil", Value::Module(GcRef::new(YexModule::nil())));
     insert!(
@@ -127,12 +266,35 @@ pub fn prelude() -> EnvTable {
         Value::Module(GcRef::new(YexModule::bool()))
     );
     insert!(prelude, "Num", Value::Module(GcRef::new(YexModule::num())));
+    insert!(prelude, "Int", Value::Module(GcRef::new(YexModule::int())));
+    insert!(
+        prelude,
+        "Range",
+        Value::Module(GcRef::new(YexModule::range()))
+    );
     insert!(prelude, "Str", Value::Module(GcRef::new(YexModule::str())));
     insert!(
         prelude,
         "List",
         Value::Module(GcRef::new(YexModule::list()))
     );
+    insert!(
+        prelude,
+        "Bytes",
+        Value::Module(GcRef::new(YexModule::bytes()))
+    );
+    #[cfg_attr(not(feature = "std"), allow(unused_mut))]
+    let mut seq = YexModule::seq();
+    #[cfg(feature = "std")]
+    if caps.contains(Capabilities::FS) {
+        seq.register("lines", 1, crate::literal::seq::methods::lines);
+    }
+    insert!(prelude, "Seq", Value::Module(GcRef::new(seq)));
+    insert!(
+        prelude,
+        "Array",
+        Value::Module(GcRef::new(YexModule::array()))
+    );
     insert!(prelude, "Sym", Value::Module(GcRef::new(YexModule::sym())));
     insert!(prelude, "Fn", Value::Module(GcRef::new(YexModule::fun())));
     insert!(
@@ -140,12 +302,34 @@ pub fn prelude() -> EnvTable {
         "Tuple",
         Value::Module(GcRef::new(YexModule::tuple()))
     );
+    insert!(
+        prelude,
+        "Dict",
+        Value::Module(GcRef::new(YexModule::dict()))
+    );
     insert!(
         prelude,
         "Result",
         Value::Module(GcRef::new(YexModule::result()))
     );
-    insert!(prelude, "FFI", Value::Module(GcRef::new(YexModule::ffi())));
+    insert!(prelude, "Json", Value::Module(GcRef::new(json::json())));
+    insert!(prelude, "Gc", Value::Module(GcRef::new(memory::gc())));
+    #[cfg(feature = "std")]
+    insert!(prelude, "Math", Value::Module(GcRef::new(math::math())));
+    #[cfg(feature = "std")]
+    insert!(prelude, "Regex", Value::Module(GcRef::new(regex::regex())));
+    #[cfg(feature = "std")]
+    if caps.contains(Capabilities::FFI | Capabilities::FS) {
+        insert!(prelude, "FFI", Value::Module(GcRef::new(YexModule::ffi())));
+        #[cfg(feature = "wasm")]
+        insert!(
+            prelude,
+            "Wasm",
+            Value::Module(GcRef::new(YexModule::wasm()))
+        );
+    }
+
+    help::attach(&mut prelude);
 
     prelude
 }
@@ -0,0 +1,417 @@
+#[cfg(unix)]
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::{
+    cell::RefCell,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{Shutdown, TcpListener, TcpStream},
+};
+
+use crate::{
+    error::{InterpretError, InterpretResult},
+    gc::GcRef,
+    literal::{bytes::Bytes, ffi::userdata::UserData, fun::Fn, nil, TryGet},
+    raise, raise_err,
+    task::{Event, Input},
+    EnvTable, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// A connected TCP socket, reads are buffered so lines can be read from it
+///
+/// On unix the sockets don't block, the operations that would block suspend the running task
+/// until the socket is ready instead, so the other tasks keep running
+///
+/// The connections and the data read from them are recorded in the trace, so when it's replayed
+/// the sockets aren't connected to anything, their reads come from the trace and their writes are
+/// ignored
+struct Socket(Option<RefCell<BufReader<TcpStream>>>);
+
+/// Creates the `Net` module, which opens TCP connections and listens for them
+pub fn net() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("connect"),
+        Value::Fn(GcRef::new(Fn::new_native(2, connect))),
+    );
+    methods.insert(
+        Symbol::new("listen"),
+        Value::Fn(GcRef::new(Fn::new_native(1, listen))),
+    );
+    methods.insert(
+        Symbol::new("accept"),
+        Value::Fn(GcRef::new(Fn::new_native(1, accept))),
+    );
+    methods.insert(
+        Symbol::new("port"),
+        Value::Fn(GcRef::new(Fn::new_native(1, local_port))),
+    );
+
+    YexModule::new(Symbol::new("Net"), methods)
+}
+
+/// Creates the `Socket` module, which reads and writes the sockets opened by `Net`
+pub fn socket() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("read"),
+        Value::Fn(GcRef::new(Fn::new_native(2, read))),
+    );
+    methods.insert(
+        Symbol::new("readLine"),
+        Value::Fn(GcRef::new(Fn::new_native(1, read_line))),
+    );
+    methods.insert(
+        Symbol::new("write"),
+        Value::Fn(GcRef::new(Fn::new_native(2, write))),
+    );
+    methods.insert(
+        Symbol::new("close"),
+        Value::Fn(GcRef::new(Fn::new_native(1, close))),
+    );
+
+    YexModule::new(Symbol::new("Socket"), methods)
+}
+
+fn io_error(action: &str, e: io::Error) -> InterpretError {
+    raise_err!(IOError, "Can't {}: {}", action, e)
+}
+
+fn socket_of(value: &Value) -> InterpretResult<&Socket> {
+    match value {
+        Value::UserData(data) if data.is::<Socket>() => Ok(data.downcast_ref().unwrap()),
+        other => raise!(TypeError, "Expected a socket, found '{}'", other),
+    }
+}
+
+fn new_socket(stream: TcpStream) -> InterpretResult<Value> {
+    #[cfg(unix)]
+    stream
+        .set_nonblocking(true)
+        .map_err(|e| io_error("set up the socket", e))?;
+
+    Ok(Value::UserData(UserData::new(Socket(Some(RefCell::new(
+        BufReader::new(stream),
+    ))))))
+}
+
+/// The connections are recorded as the address of the other side
+fn peer_of(value: &Value) -> Value {
+    let addr = socket_of(value)
+        .ok()
+        .and_then(|socket| socket.0.as_ref())
+        .and_then(|stream| stream.borrow().get_ref().peer_addr().ok());
+
+    match addr {
+        Some(addr) => addr.to_string().into(),
+        None => nil(),
+    }
+}
+
+fn replayed_socket(_: Value) -> InterpretResult<Value> {
+    Ok(Value::UserData(UserData::new(Socket(None))))
+}
+
+/// The sockets of a replayed trace only exist while it's replayed, so their reads are never
+/// computed
+fn replayed_only() -> InterpretResult<Value> {
+    raise!(
+        TraceError,
+        "The socket is only readable while the trace is replayed"
+    )
+}
+
+#[cfg(unix)]
+fn fd(socket: &impl AsRawFd) -> RawFd {
+    socket.as_raw_fd()
+}
+
+#[cfg(not(unix))]
+fn fd<T>(_: &T) {}
+
+/// Runs `op`, which returns `None` while it would block, the running task waits for `fd` to be
+/// ready for writing, or for reading if `write` is false
+#[cfg(unix)]
+fn when_ready(
+    vm: &mut VirtualMachine,
+    fd: RawFd,
+    write: bool,
+    op: impl FnMut() -> Option<InterpretResult<Value>> + 'static,
+) -> InterpretResult<Value> {
+    let event = if write {
+        Event::Writable(fd)
+    } else {
+        Event::Readable(fd)
+    };
+
+    vm.wait(event, op)
+}
+
+/// The sockets block on other platforms, so `op` is always done
+#[cfg(not(unix))]
+fn when_ready(
+    _: &mut VirtualMachine,
+    _: (),
+    _: bool,
+    mut op: impl FnMut() -> Option<InterpretResult<Value>>,
+) -> InterpretResult<Value> {
+    loop {
+        if let Some(result) = op() {
+            return result;
+        }
+    }
+}
+
+/// Like [`when_ready`] for reading, but the result is recorded in the trace as `input`
+#[cfg(unix)]
+fn input_when_ready(
+    vm: &mut VirtualMachine,
+    input: Input,
+    fd: RawFd,
+    op: impl FnMut() -> Option<InterpretResult<Value>> + 'static,
+) -> InterpretResult<Value> {
+    vm.wait_input(input, Event::Readable(fd), op)
+}
+
+/// The sockets block on other platforms, so the event is never waited for
+#[cfg(not(unix))]
+fn input_when_ready(
+    vm: &mut VirtualMachine,
+    input: Input,
+    _: (),
+    op: impl FnMut() -> Option<InterpretResult<Value>> + 'static,
+) -> InterpretResult<Value> {
+    vm.wait_input(input, Event::Timer(std::time::Instant::now()), op)
+}
+
+/// Turns the result of an operation that doesn't block into the result of `when_ready`
+fn attempt<T>(
+    result: io::Result<T>,
+    action: &str,
+    done: impl FnOnce(T) -> InterpretResult<Value>,
+) -> Option<InterpretResult<Value>> {
+    match result {
+        Ok(value) => Some(done(value)),
+        Err(e) if e.kind() == io::ErrorKind::WouldBlock => None,
+        Err(e) => Some(Err(io_error(action, e))),
+    }
+}
+
+fn port(value: &Value) -> InterpretResult<u16> {
+    let port: i64 = value.get()?;
+    match u16::try_from(port) {
+        Ok(port) => Ok(port),
+        Err(_) => raise!(ValueError, "Invalid port {}", port),
+    }
+}
+
+fn connect(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let host: String = args[0].get()?;
+    let port = port(&args[1])?;
+
+    // the socket is kept aside, since only the address is recorded
+    let mut socket = None;
+    vm.nondeterministic("connect", || {
+        match TcpStream::connect((host.as_str(), port)) {
+            Ok(stream) => {
+                let connected = new_socket(stream)?;
+                let peer = peer_of(&connected);
+                socket = Some(connected);
+                Ok(peer)
+            }
+            Err(e) => Err(io_error(&format!("connect to {}:{}", host, port), e)),
+        }
+    })?;
+
+    match socket {
+        Some(socket) => Ok(socket),
+        None => replayed_socket(nil()),
+    }
+}
+
+fn listen(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let port = port(&args[0])?;
+
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(e) => return Err(io_error(&format!("listen on port {}", port), e)),
+    };
+
+    #[cfg(unix)]
+    listener
+        .set_nonblocking(true)
+        .map_err(|e| io_error("set up the listener", e))?;
+
+    Ok(Value::UserData(UserData::new(listener)))
+}
+
+fn listener_of(value: &Value) -> InterpretResult<&TcpListener> {
+    match value {
+        Value::UserData(data) if data.is::<TcpListener>() => Ok(data.downcast_ref().unwrap()),
+        other => raise!(TypeError, "Expected a listener, found '{}'", other),
+    }
+}
+
+/// Waits for the next connection to a listener
+fn accept(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let value = args[0].clone();
+    let fd = fd(listener_of(&value)?);
+
+    let input = Input::mapped("accept", peer_of, replayed_socket);
+    input_when_ready(vm, input, fd, move || {
+        let listener = listener_of(&value).unwrap();
+        attempt(listener.accept(), "accept a connection", |(stream, _)| {
+            new_socket(stream)
+        })
+    })
+}
+
+/// Returns the port a listener is bound to, which is chosen by the system when listening on port 0
+fn local_port(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    match listener_of(&args[0])?.local_addr() {
+        Ok(addr) => Ok(Value::Int(addr.port() as i64)),
+        Err(e) => Err(io_error("get the port of the listener", e)),
+    }
+}
+
+/// Reads up to `n` bytes, returning empty bytes once the connection is closed
+fn read(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let n: usize = args[0].get()?;
+    let value = args[1].clone();
+    let fd = match &socket_of(&value)?.0 {
+        Some(stream) => fd(stream.borrow().get_ref()),
+        None => return vm.nondeterministic("socketRead", replayed_only),
+    };
+
+    let mut buf = vec![0; n];
+    input_when_ready(vm, Input::new("socketRead"), fd, move || {
+        let socket = socket_of(&value).unwrap().0.as_ref().unwrap();
+        let result = socket.borrow_mut().read(&mut buf);
+        attempt(result, "read from the socket", |len| {
+            Ok(Bytes::from(buf[..len].to_vec()).into())
+        })
+    })
+}
+
+/// Reads a line without its line break, or nil once the connection is closed
+fn read_line(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let value = args[0].clone();
+    let fd = match &socket_of(&value)?.0 {
+        Some(stream) => fd(stream.borrow().get_ref()),
+        None => return vm.nondeterministic("socketLine", replayed_only),
+    };
+
+    // the part of the line read before the socket would block is kept
+    let mut line = vec![];
+    input_when_ready(vm, Input::new("socketLine"), fd, move || {
+        let socket = socket_of(&value).unwrap().0.as_ref().unwrap();
+        let result = socket.borrow_mut().read_until(b'\n', &mut line);
+        attempt(result, "read from the socket", |_| {
+            if line.is_empty() {
+                return Ok(nil());
+            }
+
+            if line.ends_with(b"\n") {
+                line.pop();
+                if line.ends_with(b"\r") {
+                    line.pop();
+                }
+            }
+            Ok(String::from_utf8_lossy(&line).into_owned().into())
+        })
+    })
+}
+
+/// Writes a string or bytes, waiting until all of it is sent
+fn write(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let value = args[1].clone();
+
+    let data = match &args[0] {
+        Value::Str(s) => s.as_bytes().to_vec(),
+        Value::Bytes(b) => b.0.to_vec(),
+        other => return raise!(TypeError, "Can't write '{}' to a socket", other),
+    };
+
+    let fd = match &socket_of(&value)?.0 {
+        Some(stream) => fd(stream.borrow().get_ref()),
+        None => return Ok(nil()),
+    };
+
+    // the part of the data sent before the socket would block is skipped
+    let mut written = 0;
+    when_ready(vm, fd, true, move || {
+        let socket = socket_of(&value).unwrap().0.as_ref().unwrap();
+
+        while written < data.len() {
+            match socket.borrow().get_ref().write(&data[written..]) {
+                Ok(0) => {
+                    let e = io::Error::from(io::ErrorKind::WriteZero);
+                    return Some(Err(io_error("write to the socket", e)));
+                }
+                Ok(n) => written += n,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => return None,
+                Err(e) => return Some(Err(io_error("write to the socket", e))),
+            }
+        }
+
+        Some(Ok(nil()))
+    })
+}
+
+fn close(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let Some(socket) = &socket_of(&args[0])?.0 else {
+        return Ok(nil());
+    };
+
+    match socket.borrow().get_ref().shutdown(Shutdown::Both) {
+        Ok(()) => Ok(nil()),
+        // closing a socket twice isn't an error
+        Err(e) if e.kind() == io::ErrorKind::NotConnected => Ok(nil()),
+        Err(e) => Err(io_error("close the socket", e)),
+    }
+}
+
+#[test]
+fn record_and_replay() {
+    use std::thread;
+
+    let path = std::env::temp_dir().join("yex_test_net.trace");
+    let file = std::fs::File::create(&path).unwrap();
+
+    let mut vm = VirtualMachine::default();
+    vm.set_trace(crate::Trace::record(file));
+
+    let listener = listen(&mut vm, vec![Value::Int(0)]).unwrap();
+    let port = local_port(&mut vm, vec![listener.clone()]).unwrap();
+    let port: i64 = port.get().unwrap();
+    let client = thread::spawn(move || {
+        let mut stream = TcpStream::connect(("127.0.0.1", port as u16)).unwrap();
+        stream.write_all(b"hello\n\xff").unwrap();
+    });
+
+    let socket = accept(&mut vm, vec![listener]).unwrap();
+    client.join().unwrap();
+    let line = read_line(&mut vm, vec![socket.clone()]).unwrap();
+    assert_eq!(line, Value::from("hello".to_string()));
+    let rest = read(&mut vm, vec![Value::Int(8), socket]).unwrap();
+    assert_eq!(rest, Bytes::from(vec![0xff]).into());
+
+    // the replayed program gets the same values without anyone connecting
+    let trace = std::fs::read_to_string(&path).unwrap();
+    let mut vm = VirtualMachine::default();
+    vm.set_trace(crate::Trace::replay(&trace).unwrap());
+
+    let listener = listen(&mut vm, vec![Value::Int(0)]).unwrap();
+    let socket = accept(&mut vm, vec![listener]).unwrap();
+    assert_eq!(read_line(&mut vm, vec![socket.clone()]).unwrap(), line);
+    assert_eq!(
+        read(&mut vm, vec![Value::Int(8), socket.clone()]).unwrap(),
+        rest
+    );
+    assert!(write(&mut vm, vec!["bye".to_string().into(), socket]).is_ok());
+}
@@ -0,0 +1,128 @@
+use crate::{
+    env::EnvTable,
+    error::InterpretResult,
+    gc::GcRef,
+    insert_fn,
+    literal::{fun::FnKind, TryGet},
+    operator_method, List, Range, Value, VirtualMachine,
+};
+
+/// Inserts the operators as functions, so `(+)` can be passed around like any other function
+pub fn insert(prelude: &mut EnvTable) {
+    insert_fn!(:vm prelude, "(+)", add, 2);
+    insert_fn!(:vm prelude, "(-)", sub, 2);
+    insert_fn!(:vm prelude, "(*)", mul, 2);
+    insert_fn!(:vm prelude, "(/)", div, 2);
+    insert_fn!(:vm prelude, "(%)", rem, 2);
+    insert_fn!(:vm prelude, "(&&&)", bit_and, 2);
+    insert_fn!(:vm prelude, "(|||)", bit_or, 2);
+    insert_fn!(:vm prelude, "(^^^)", xor, 2);
+    insert_fn!(:vm prelude, "(<<<)", shl, 2);
+    insert_fn!(:vm prelude, "(>>>)", shr, 2);
+    insert_fn!(:vm prelude, "(==)", eq, 2);
+    insert_fn!(:vm prelude, "(<)", less, 2);
+    insert_fn!(:vm prelude, "(<=)", less_eq, 2);
+    insert_fn!(:vm prelude, "(!=)", ne, 2);
+    insert_fn!(:vm prelude, "(>)", greater, 2);
+    insert_fn!(:vm prelude, "(>=)", greater_eq, 2);
+    insert_fn!(prelude, "(::)", cons, 2);
+    insert_fn!(prelude, "(..)", range, 2);
+    insert_fn!(prelude, "(..=)", range_inclusive, 2);
+    insert_fn!(prelude, "(!)", not, 1);
+}
+
+/// Applies a binary operator, calling the method that overloads it like the vm does
+fn binop(
+    vm: &mut VirtualMachine,
+    op: &str,
+    args: &[Value],
+    f: fn(Value, Value) -> InterpretResult<Value>,
+) -> InterpretResult<Value> {
+    match operator_method(op, &args[0], &args[1]) {
+        Some(method) => vm.call_value(method, args),
+        None => f(args[0].clone(), args[1].clone()),
+    }
+}
+
+fn add(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(+)", args, |a, b| a + b)
+}
+
+fn sub(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(-)", args, |a, b| a - b)
+}
+
+fn mul(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(*)", args, |a, b| a * b)
+}
+
+fn div(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(/)", args, |a, b| a / b)
+}
+
+fn rem(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(%)", args, |a, b| a % b)
+}
+
+fn bit_and(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(&&&)", args, |a, b| a & b)
+}
+
+fn bit_or(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(|||)", args, |a, b| a | b)
+}
+
+fn xor(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(^^^)", args, |a, b| a ^ b)
+}
+
+fn shl(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(<<<)", args, |a, b| a << b)
+}
+
+fn shr(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(>>>)", args, |a, b| a >> b)
+}
+
+pub(super) fn eq(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(==)", args, |a, b| Ok((a == b).into()))
+}
+
+fn less(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(<)", args, |a, b| Ok(a.ord_cmp(&b)?.is_lt().into()))
+}
+
+fn less_eq(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    binop(vm, "(<=)", args, |a, b| Ok(a.ord_cmp(&b)?.is_le().into()))
+}
+
+fn ne(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    Ok(!eq(vm, args)?)
+}
+
+fn greater(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    Ok(!less_eq(vm, args)?)
+}
+
+fn greater_eq(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    Ok(!less(vm, args)?)
+}
+
+fn cons(args: &[Value]) -> InterpretResult<Value> {
+    let list: List = args[1].get()?;
+    Ok(list.prepend(args[0].clone()).into())
+}
+
+fn range(args: &[Value]) -> InterpretResult<Value> {
+    let (start, end): (i64, i64) = (args[0].get()?, args[1].get()?);
+    Ok(Range::new(start, end).into())
+}
+
+fn range_inclusive(args: &[Value]) -> InterpretResult<Value> {
+    let (start, end): (i64, i64) = (args[0].get()?, args[1].get()?);
+    Ok(Range::inclusive(start, end).into())
+}
+
+fn not(args: &[Value]) -> InterpretResult<Value> {
+    Ok(!args[0].clone())
+}
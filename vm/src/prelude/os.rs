@@ -0,0 +1,157 @@
+use std::{
+    io::{BufRead, BufReader},
+    process::{Child, ChildStdout, Command, Stdio},
+};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{fun::Fn, list::Items, nil, result, TryGet},
+    Dict, EnvTable, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `Os` module, which runs other programs
+pub fn os() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("exec"),
+        Value::Fn(GcRef::new(Fn::new_native(2, exec))),
+    );
+    methods.insert(
+        Symbol::new("execStream"),
+        Value::Fn(GcRef::new(Fn::new_native(3, exec_stream))),
+    );
+
+    YexModule::new(Symbol::new("Os"), methods)
+}
+
+/// Builds the command from the program name and the list of its arguments
+fn command(cmd: &Value, args: &Value) -> InterpretResult<Command> {
+    let cmd: String = cmd.get()?;
+    let mut command = Command::new(cmd);
+
+    for arg in Items::new(args)? {
+        let arg: String = arg.get()?;
+        command.arg(arg);
+    }
+
+    Ok(command)
+}
+
+/// Waits for the exit code, which is nil if the program was killed by a signal, or the error
+/// message if it can't be waited for
+fn code(child: &mut Child) -> Value {
+    match child.wait() {
+        Ok(status) => status
+            .code()
+            .map_or_else(nil, |code| Value::Int(code as i64)),
+        Err(e) => e.to_string().into(),
+    }
+}
+
+/// Turns the recorded outcome of a program into a result, programs that couldn't be run are
+/// recorded as the error message
+fn outcome(value: Value) -> InterpretResult<Value> {
+    match value {
+        Value::Str(e) => Ok(result::fail(vec![Value::Str(e)])),
+        value => Ok(result::ok(vec![value])),
+    }
+}
+
+/// Runs a program until it exits, returning `Result.ok %{code, stdout, stderr}`, or
+/// `Result.fail msg` if it couldn't be run
+fn exec(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let mut command = command(&args[0], &args[1])?;
+
+    let value = vm.nondeterministic("exec", || {
+        let output = match command.output() {
+            Ok(output) => output,
+            Err(e) => return Ok(e.to_string().into()),
+        };
+
+        let code = output
+            .status
+            .code()
+            .map_or_else(nil, |code| Value::Int(code as i64));
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+
+        // recorded as a tuple, since traces can't hold dictionaries
+        Ok(vec![code, stdout.into(), stderr.into()].into())
+    })?;
+
+    let output = match value {
+        Value::Tuple(output) => output,
+        other => return outcome(other),
+    };
+
+    let entries = ["code", "stdout", "stderr"]
+        .into_iter()
+        .zip(output.0.iter())
+        .map(|(key, value)| (Value::from(Symbol::new(key)), value.clone()));
+    outcome(Dict::from_entries(entries)?.into())
+}
+
+/// Runs a program, calling `f` with each line it writes to the standard output while it runs,
+/// returning `Result.ok code`, or `Result.fail msg` if it couldn't be run
+fn exec_stream(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = &args[2];
+    let mut command = command(&args[0], &args[1])?;
+    command.stdout(Stdio::piped());
+
+    // the program is only started by the first line, so replaying a trace doesn't run it
+    let mut running: Option<(Child, std::io::Lines<BufReader<ChildStdout>>)> = None;
+    let mut error = None;
+
+    loop {
+        let line = vm.nondeterministic("execLine", || {
+            if error.is_some() {
+                return Ok(nil());
+            }
+
+            if running.is_none() {
+                match command.spawn() {
+                    Ok(mut child) => {
+                        let stdout = BufReader::new(child.stdout.take().unwrap());
+                        running = Some((child, stdout.lines()));
+                    }
+                    Err(e) => {
+                        error = Some(e.to_string());
+                        return Ok(nil());
+                    }
+                }
+            }
+
+            let (_, lines) = running.as_mut().unwrap();
+            match lines.next() {
+                Some(line) => Ok(line?.into()),
+                None => Ok(nil()),
+            }
+        })?;
+
+        if line.is_nil() {
+            break;
+        }
+
+        vm.push(line);
+        vm.push(fun.clone());
+        if let Err(e) = vm.call(1) {
+            if let Some((child, _)) = &mut running {
+                child.kill().ok();
+            }
+            return Err(e);
+        }
+        vm.pop();
+    }
+
+    let value = vm.nondeterministic("exec", || match (&mut running, error.take()) {
+        (_, Some(e)) => Ok(e.into()),
+        (Some((child, _)), None) => Ok(code(child)),
+        (None, None) => Ok(nil()),
+    })?;
+
+    outcome(value)
+}
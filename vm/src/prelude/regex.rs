@@ -0,0 +1,163 @@
+use ::regex::Regex;
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{ffi::userdata::UserData, fun::Fn, nil, TryGet},
+    raise, Dict, EnvTable, List, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `Regex` module, which matches strings against regular expressions
+pub fn regex() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("compile"),
+        Value::Fn(GcRef::new(Fn::new_native(1, compile))),
+    );
+    methods.insert(
+        Symbol::new("matches"),
+        Value::Fn(GcRef::new(Fn::new_native(2, matches))),
+    );
+    methods.insert(
+        Symbol::new("find"),
+        Value::Fn(GcRef::new(Fn::new_native(2, find))),
+    );
+    methods.insert(
+        Symbol::new("findAll"),
+        Value::Fn(GcRef::new(Fn::new_native(2, find_all))),
+    );
+    methods.insert(
+        Symbol::new("captures"),
+        Value::Fn(GcRef::new(Fn::new_native(2, captures))),
+    );
+    methods.insert(
+        Symbol::new("namedCaptures"),
+        Value::Fn(GcRef::new(Fn::new_native(2, named_captures))),
+    );
+    methods.insert(
+        Symbol::new("replace"),
+        Value::Fn(GcRef::new(Fn::new_native(3, replace))),
+    );
+    methods.insert(
+        Symbol::new("split"),
+        Value::Fn(GcRef::new(Fn::new_native(2, split))),
+    );
+
+    YexModule::new(Symbol::new("Regex"), methods)
+}
+
+fn parse(pattern: &str) -> InterpretResult<Regex> {
+    match Regex::new(pattern) {
+        Ok(re) => Ok(re),
+        Err(e) => raise!(ValueError, "Invalid regex '{}': {}", pattern, e),
+    }
+}
+
+/// Takes a regex compiled by `Regex.compile`, or compiles a pattern string
+fn regex_of(value: &Value) -> InterpretResult<Regex> {
+    match value {
+        Value::UserData(data) if data.is::<Regex>() => {
+            Ok(data.downcast_ref::<Regex>().unwrap().clone())
+        }
+        Value::Str(pattern) => parse(pattern),
+        other => raise!(
+            TypeError,
+            "Expected a regex or a pattern string, found '{}'",
+            other
+        ),
+    }
+}
+
+/// Returns the values of a list built from `items`, keeping their order
+fn list(items: impl DoubleEndedIterator<Item = Value>) -> Value {
+    items.rev().collect::<List>().into()
+}
+
+fn compile(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let pattern: String = args[0].get()?;
+    Ok(Value::UserData(UserData::new(parse(&pattern)?)))
+}
+
+fn matches(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let re = regex_of(&args[0])?;
+    let str: String = args[1].get()?;
+
+    Ok(re.is_match(&str).into())
+}
+
+fn find(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let re = regex_of(&args[0])?;
+    let str: String = args[1].get()?;
+
+    Ok(re
+        .find(&str)
+        .map_or_else(nil, |m| m.as_str().to_string().into()))
+}
+
+fn find_all(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let re = regex_of(&args[0])?;
+    let str: String = args[1].get()?;
+
+    let found: Vec<Value> = re
+        .find_iter(&str)
+        .map(|m| m.as_str().to_string().into())
+        .collect();
+    Ok(list(found.into_iter()))
+}
+
+/// Returns the whole match followed by every group, groups that didn't match are nil
+fn captures(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let re = regex_of(&args[0])?;
+    let str: String = args[1].get()?;
+
+    let caps = match re.captures(&str) {
+        Some(caps) => caps,
+        None => return Ok(nil()),
+    };
+
+    let groups: Vec<Value> = caps
+        .iter()
+        .map(|group| group.map_or_else(nil, |m| m.as_str().to_string().into()))
+        .collect();
+    Ok(list(groups.into_iter()))
+}
+
+/// Returns a dictionary from the names of the groups to what they matched
+fn named_captures(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let re = regex_of(&args[0])?;
+    let str: String = args[1].get()?;
+
+    let caps = match re.captures(&str) {
+        Some(caps) => caps,
+        None => return Ok(nil()),
+    };
+
+    let entries = re.capture_names().flatten().map(|name| {
+        let value = caps
+            .name(name)
+            .map_or_else(nil, |m| m.as_str().to_string().into());
+        (Value::from(Symbol::new(name)), value)
+    });
+    Ok(Dict::from_entries(entries)?.into())
+}
+
+/// Replaces every match, `$1` or `$name` in the replacement are replaced by the groups
+fn replace(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let re = regex_of(&args[0])?;
+    let replacement: String = args[1].get()?;
+    let str: String = args[2].get()?;
+
+    Ok(re
+        .replace_all(&str, replacement.as_str())
+        .into_owned()
+        .into())
+}
+
+fn split(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let re = regex_of(&args[0])?;
+    let str: String = args[1].get()?;
+
+    let parts: Vec<Value> = re.split(&str).map(|s| s.to_string().into()).collect();
+    Ok(list(parts.into_iter()))
+}
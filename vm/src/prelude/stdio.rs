@@ -0,0 +1,70 @@
+use std::io::{self, Read, Write};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{fun::Fn, nil, TryGet},
+    EnvTable, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `IO` module, which reads the standard input
+pub fn io() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("readLine"),
+        Value::Fn(GcRef::new(Fn::new_native(1, read_line))),
+    );
+    methods.insert(
+        Symbol::new("readAll"),
+        Value::Fn(GcRef::new(Fn::new_native(1, read_all))),
+    );
+    methods.insert(
+        Symbol::new("prompt"),
+        Value::Fn(GcRef::new(Fn::new_native(1, prompt))),
+    );
+
+    YexModule::new(Symbol::new("IO"), methods)
+}
+
+/// Reads a line without its line break, or nil if the input ended
+fn line() -> InterpretResult<Value> {
+    let mut line = String::new();
+    if io::stdin().read_line(&mut line)? == 0 {
+        return Ok(nil());
+    }
+
+    if line.ends_with('\n') {
+        line.pop();
+        if line.ends_with('\r') {
+            line.pop();
+        }
+    }
+
+    Ok(line.into())
+}
+
+fn read_line(vm: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    vm.nondeterministic("readLine", line)
+}
+
+fn read_all(vm: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+
+    vm.nondeterministic("readAll", || {
+        let mut input = String::new();
+        io::stdin().read_to_string(&mut input)?;
+        Ok(input.into())
+    })
+}
+
+fn prompt(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let msg: String = args[0].get()?;
+
+    print!("{}", msg);
+    io::stdout().flush()?;
+
+    vm.nondeterministic("readLine", line)
+}
@@ -0,0 +1,63 @@
+use std::env;
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{fun::Fn, nil, TryGet},
+    EnvTable, List, Symbol, Value, VirtualMachine, YexModule,
+};
+
+/// Creates the `Sys` module, which gives access to the command line arguments and the environment
+pub fn sys() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("args"),
+        Value::Fn(GcRef::new(Fn::new_native(1, args))),
+    );
+    methods.insert(
+        Symbol::new("env"),
+        Value::Fn(GcRef::new(Fn::new_native(1, get_env))),
+    );
+    methods.insert(
+        Symbol::new("setEnv"),
+        Value::Fn(GcRef::new(Fn::new_native(2, set_env))),
+    );
+
+    YexModule::new(Symbol::new("Sys"), methods)
+}
+
+fn args(vm: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let args = vm.args.clone();
+
+    vm.nondeterministic("args", || {
+        Ok(args
+            .into_iter()
+            .rev()
+            .map(Value::from)
+            .collect::<List>()
+            .into())
+    })
+}
+
+fn get_env(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let name: String = args[0].get()?;
+
+    vm.nondeterministic("env", || {
+        Ok(env::var(&name).map(Value::from).unwrap_or_else(|_| nil()))
+    })
+}
+
+fn set_env(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let name: String = args[0].get()?;
+    let value: String = args[1].get()?;
+
+    if name.is_empty() || name.contains(['=', '\0']) || value.contains('\0') {
+        crate::raise!(ValueError, "Invalid environment variable '{}'", name)?;
+    }
+
+    env::set_var(name, value);
+    Ok(nil())
+}
@@ -0,0 +1,142 @@
+use alloc::{rc::Rc, vec::Vec};
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{ffi::userdata::UserData, fun::Fn, nil},
+    raise,
+    task::{self, Promise, Resume},
+    EnvTable, Symbol, Value, VirtualMachine, YexModule,
+};
+#[cfg(feature = "std")]
+use crate::{literal::TryGet, task::Event};
+
+/// Creates the `Task` module, with the functions about the running task
+pub fn task() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("current"),
+        Value::Fn(GcRef::new(Fn::new_native(1, current))),
+    );
+    methods.insert(
+        Symbol::new("yield"),
+        Value::Fn(GcRef::new(Fn::new_native(1, yield_))),
+    );
+    methods.insert(
+        Symbol::new("async"),
+        Value::Fn(GcRef::new(Fn::new_native(1, async_))),
+    );
+    methods.insert(
+        Symbol::new("await"),
+        Value::Fn(GcRef::new(Fn::new_native(1, await_))),
+    );
+    #[cfg(feature = "std")]
+    methods.insert(
+        Symbol::new("sleep"),
+        Value::Fn(GcRef::new(Fn::new_native(1, sleep))),
+    );
+
+    YexModule::new(Symbol::new("Task"), methods)
+}
+
+/// Creates a task that calls `fun` with `()`, returning its handle
+pub fn spawn(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    match &args[0] {
+        Value::Fn(_) => Ok(task::handle(vm.scheduler.spawn(args[0].clone()))),
+        other => raise!(TypeError, "Can't spawn '{}', expected a function", other),
+    }
+}
+
+/// Sends a message to a task, which doesn't wait for the task to receive it
+pub fn send(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    let id = task::id_of(&args[1])?;
+
+    vm.scheduler.send(id, args[0].clone());
+    Ok(nil())
+}
+
+/// Returns the oldest message sent to the running task, suspending it until one arrives
+pub fn receive(vm: &mut VirtualMachine, _: &[Value]) -> InterpretResult<Value> {
+    if let Some(msg) = vm.scheduler.receive() {
+        return Ok(msg);
+    }
+
+    if !vm.can_suspend() {
+        return raise!(
+            TaskError,
+            "Can't wait for a message inside a function called by a native function"
+        );
+    }
+
+    // the message is pushed in place of the result once the task resumes
+    vm.scheduler.suspend = Some(Resume::Receive);
+    Ok(nil())
+}
+
+fn current(vm: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    Ok(task::handle(vm.scheduler.current))
+}
+
+/// Lets the other ready tasks run before the running one continues
+fn yield_(vm: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+
+    if vm.can_suspend() {
+        vm.scheduler.suspend = Some(Resume::Yield);
+    }
+    Ok(nil())
+}
+
+/// Creates a task that calls `fun` with `()`, returning a promise of its result
+fn async_(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+
+    match &args[0] {
+        Value::Fn(_) => {
+            let promise = vm.scheduler.spawn_async(args[0].clone());
+            Ok(Value::UserData(UserData::new(promise)))
+        }
+        other => raise!(TypeError, "Can't spawn '{}', expected a function", other),
+    }
+}
+
+/// Returns the result of the task of a promise, suspending the running task until it finishes
+fn await_(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let promise = match &args[0] {
+        Value::UserData(data) if data.is::<Rc<Promise>>() => {
+            data.downcast_ref::<Rc<Promise>>().unwrap()
+        }
+        other => return raise!(TypeError, "Expected a promise, found '{}'", other),
+    };
+
+    if let Some(value) = &*promise.value.borrow() {
+        return Ok(value.clone());
+    }
+
+    if !vm.can_suspend() {
+        return raise!(
+            TaskError,
+            "Can't wait for a promise inside a function called by a native function"
+        );
+    }
+
+    vm.scheduler.wait_for(promise);
+    Ok(nil())
+}
+
+/// Suspends the running task for `ms` milliseconds, while the other tasks run
+#[cfg(feature = "std")]
+fn sleep(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let ms: usize = args[0].get()?;
+
+    let at = Instant::now() + Duration::from_millis(ms as u64);
+    vm.wait(Event::Timer(at), move || {
+        (Instant::now() >= at).then(|| Ok(nil()))
+    })
+}
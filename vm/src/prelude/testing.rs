@@ -0,0 +1,35 @@
+use alloc::string::String;
+
+use crate::{
+    error::InterpretResult,
+    literal::{nil, TryGet},
+    pretty, raise, Value, VirtualMachine,
+};
+
+use super::ops;
+
+/// Raises an `AssertionError` with `message` if `condition` is false
+pub fn assert(args: &[Value]) -> InterpretResult<Value> {
+    let message: String = args[0].get()?;
+
+    if args[1].to_bool() {
+        Ok(nil())
+    } else {
+        raise!(AssertionError, "Assertion failed: {}", message)
+    }
+}
+
+/// Raises an `AssertionError` showing both values if `actual` isn't equal to `expected`, `==` is
+/// used to compare them, so the types that overload it are compared by their method
+pub fn assert_eq(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    if ops::eq(vm, args)?.to_bool() {
+        Ok(nil())
+    } else {
+        raise!(
+            AssertionError,
+            "Expected {}, found {}",
+            pretty(&args[0]),
+            pretty(&args[1])
+        )
+    }
+}
@@ -0,0 +1,273 @@
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    sync::{Arc, Condvar, Mutex},
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+use crate::{
+    error::{InterpretError, InterpretResult},
+    gc::{self, GcRef},
+    literal::{
+        ffi::userdata::{Shared, UserData},
+        fun::Fn,
+        nil,
+    },
+    raise, raise_err, EnvTable, Packed, Symbol, Tuple, Value, VirtualMachine, YexModule,
+};
+
+/// How often `Channel.recv` checks if anybody else can still send to the channel
+const POLL: Duration = Duration::from_millis(100);
+
+/// An error raised by a thread, the value it threw is copied to the vm that joins it
+struct Failure {
+    msg: String,
+    err: Symbol,
+    value: Option<Packed>,
+    line: usize,
+    column: usize,
+    builtin: Option<Symbol>,
+}
+
+impl From<InterpretError> for Failure {
+    fn from(e: InterpretError) -> Self {
+        Self {
+            // values that can't be copied are raised as the error name
+            value: e.value.and_then(|value| Packed::new(&value).ok()),
+            msg: e.msg,
+            err: e.err,
+            line: e.line,
+            column: e.column,
+            builtin: e.builtin,
+        }
+    }
+}
+
+impl Failure {
+    fn raise(self) -> InterpretError {
+        let value = match self.value.map(|value| value.unpack()).transpose() {
+            Ok(value) => value.map(Box::new),
+            Err(e) => return e,
+        };
+
+        InterpretError {
+            msg: self.msg,
+            err: self.err,
+            value,
+            line: self.line,
+            column: self.column,
+            builtin: self.builtin,
+        }
+    }
+}
+
+/// A thread started by `Thread.spawn`, which is taken once it's joined
+struct Thread(RefCell<Option<JoinHandle<Result<Packed, Failure>>>>);
+
+/// A queue of messages shared by every vm that holds it
+#[derive(Default)]
+struct Channel {
+    queue: Mutex<VecDeque<Packed>>,
+    sent: Condvar,
+}
+
+/// Creates the `Thread` module, which runs functions in other vms on OS threads
+pub fn thread() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("spawn"),
+        Value::Fn(GcRef::new(Fn::new_native(1, spawn))),
+    );
+    methods.insert(
+        Symbol::new("join"),
+        Value::Fn(GcRef::new(Fn::new_native(1, join))),
+    );
+
+    YexModule::new(Symbol::new("Thread"), methods)
+}
+
+/// Creates the `Channel` module, which sends values between threads
+pub fn channel() -> YexModule {
+    let mut methods = EnvTable::new();
+
+    methods.insert(
+        Symbol::new("new"),
+        Value::Fn(GcRef::new(Fn::new_native(1, new))),
+    );
+    methods.insert(
+        Symbol::new("send"),
+        Value::Fn(GcRef::new(Fn::new_native(2, send))),
+    );
+    methods.insert(
+        Symbol::new("recv"),
+        Value::Fn(GcRef::new(Fn::new_native(1, recv))),
+    );
+
+    YexModule::new(Symbol::new("Channel"), methods)
+}
+
+/// Returns the values that can be copied to another vm, the others are replaced by nil
+fn copyable(values: impl Iterator<Item = Value>) -> Vec<Value> {
+    values
+        .map(|value| match Packed::new(&value) {
+            Ok(_) => value,
+            Err(_) => Value::Nil,
+        })
+        .collect()
+}
+
+/// Runs `fun` with `()` in a new vm, on a new thread
+///
+/// The new vm gets a copy of the constants and of the globals that can be copied, so it can run
+/// the functions defined by the program, but not the native functions registered by the host
+fn spawn(vm: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let vm = unsafe { &mut *vm };
+    let fun = args[0].clone();
+    if !matches!(fun, Value::Fn(_)) {
+        return raise!(TypeError, "Can't spawn '{}', expected a function", fun);
+    }
+
+    let mut globals = EnvTable::new();
+    if let Some(registry) = &vm.registry {
+        for (name, value) in registry.globals.iter() {
+            globals.insert(name, value);
+        }
+    }
+    for (name, value) in vm.builtins.iter().chain(vm.globals.iter()) {
+        globals.insert(name, value);
+    }
+    let globals: Vec<Value> = globals
+        .iter()
+        .filter(|(_, value)| Packed::new(value).is_ok())
+        .map(|(name, value)| vec![name.into(), value].into())
+        .collect();
+
+    // packed together, so the types shared by the constants and the globals stay the same
+    let constants = copyable(vm.shared.iter().chain(vm.constants.iter()).cloned());
+    let start = Packed::new(&vec![fun, constants.into(), globals.into()].into())?;
+
+    let options = vm.options();
+    let script_args = vm.args.clone();
+    // the memory of the thread counts in the limit of this run
+    let heap = vm.shared_heap();
+
+    let handle = thread::spawn(move || {
+        let result = {
+            let mut vm = VirtualMachine::builder().options(options).build();
+            vm.set_args(script_args);
+            if let Some(heap) = heap {
+                vm.share_heap(heap);
+            }
+
+            run(&mut vm, &start)
+        };
+
+        // the values queued by this thread would be leaked once it exits
+        gc::collect();
+        result
+    });
+
+    Ok(Value::UserData(UserData::new(Thread(RefCell::new(Some(
+        handle,
+    ))))))
+}
+
+/// Sets up the vm of a new thread and runs the function it was started with
+fn run(vm: &mut VirtualMachine, start: &Packed) -> Result<Packed, Failure> {
+    let start = start.unpack()?;
+    let start = tuple(&start);
+
+    vm.set_consts(tuple(&start[1]).to_vec());
+    for global in tuple(&start[2]).iter() {
+        let global = tuple(global);
+        if let Value::Sym(name) = &global[0] {
+            vm.set_global(name.0, global[1].clone());
+        }
+    }
+
+    let result = vm.call_value(start[0].clone(), &[Vec::new().into()])?;
+    Ok(Packed::new(&result)?)
+}
+
+fn tuple(value: &Value) -> &[Value] {
+    match value {
+        Value::Tuple(Tuple(xs)) => xs,
+        _ => unreachable!(),
+    }
+}
+
+fn thread_of(value: &Value) -> InterpretResult<&Thread> {
+    match value {
+        Value::UserData(data) if data.is::<Thread>() => Ok(data.downcast_ref().unwrap()),
+        other => raise!(TypeError, "Expected a thread, found '{}'", other),
+    }
+}
+
+/// Waits for a thread to finish, returning a copy of what its function returned, or raising the
+/// error that stopped it
+fn join(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let handle = thread_of(&args[0])?
+        .0
+        .borrow_mut()
+        .take()
+        .ok_or_else(|| raise_err!(ThreadError, "The thread was joined already"))?;
+
+    match handle.join() {
+        Ok(Ok(result)) => result.unpack(),
+        Ok(Err(failure)) => Err(failure.raise()),
+        Err(_) => raise!(ThreadError, "The thread panicked"),
+    }
+}
+
+fn channel_of(value: &Value) -> InterpretResult<(&Shared, &Channel)> {
+    match value {
+        Value::UserData(data) => match data.downcast_ref::<Shared>() {
+            Some(shared) if shared.is::<Channel>() => Ok((shared, shared.downcast_ref().unwrap())),
+            _ => raise!(TypeError, "Expected a channel, found '{}'", value),
+        },
+        other => raise!(TypeError, "Expected a channel, found '{}'", other),
+    }
+}
+
+fn new(_: *mut VirtualMachine, _: Vec<Value>) -> InterpretResult<Value> {
+    let channel: Shared = Arc::new(Channel::default());
+    Ok(Value::UserData(UserData::new(channel)))
+}
+
+/// Sends a copy of `msg`, without waiting for it to be received
+fn send(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let msg = Packed::new(&args[0])?;
+    let (_, channel) = channel_of(&args[1])?;
+
+    let mut queue = channel.queue.lock().unwrap_or_else(|e| e.into_inner());
+    queue.push_back(msg);
+    channel.sent.notify_one();
+
+    Ok(nil())
+}
+
+/// Waits for the oldest message sent to the channel, raising a `DeadlockError` if no other vm
+/// holds the channel, since nobody could send to it
+fn recv(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+    let (shared, channel) = channel_of(&args[0])?;
+
+    let mut queue = channel.queue.lock().unwrap_or_else(|e| e.into_inner());
+    loop {
+        if let Some(msg) = queue.pop_front() {
+            drop(queue);
+            return msg.unpack();
+        }
+
+        if Arc::strong_count(shared) == 1 {
+            return raise!(DeadlockError, "Nobody else can send to the channel");
+        }
+
+        queue = channel
+            .sent
+            .wait_timeout(queue, POLL)
+            .unwrap_or_else(|e| e.into_inner())
+            .0;
+    }
+}
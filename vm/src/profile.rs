@@ -0,0 +1,249 @@
+//! Profiling, a vm with profiling enabled samples the functions that are running every few
+//! instructions, so a report can show where the program spends its time
+//!
+//! The samples are taken by counting instructions instead of on a timer, so the same program
+//! always has the same profile, and the time spent inside native functions isn't counted
+use alloc::{boxed::Box, vec::Vec};
+
+use crate::{debug::Location, gc::GcRef, task::Frame, Fn, Symbol, VirtualMachine};
+
+/// A function that was running when a sample was taken
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Function {
+    /// The function name, `None` for anonymous functions and the top level code
+    pub name: Option<Symbol>,
+    /// Where the code of the function starts
+    pub at: Location,
+    /// Whether it's the code passed to [`VirtualMachine::run`] or the code of a module, instead of
+    /// a function
+    pub top_level: bool,
+}
+
+/// The samples of a function
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Samples {
+    /// The function sampled
+    pub function: Function,
+    /// The samples taken while the function itself was running
+    pub own: usize,
+    /// The samples taken while the function or the functions it called were running
+    pub total: usize,
+}
+
+/// The samples of the functions that ran, and of the calls between them
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    interval: usize,
+    /// The instructions left before the next sample
+    countdown: usize,
+    samples: usize,
+    functions: Vec<Samples>,
+    /// The indexes of the caller and the callee in `functions`, with the samples taken while the
+    /// caller was calling the callee
+    calls: Vec<(usize, usize, usize)>,
+}
+
+impl Profile {
+    fn new(interval: usize) -> Self {
+        let interval = interval.max(1);
+        Self {
+            interval,
+            countdown: interval,
+            samples: 0,
+            functions: Vec::new(),
+            calls: Vec::new(),
+        }
+    }
+
+    fn index(&mut self, function: Function) -> usize {
+        match self.functions.iter().position(|f| f.function == function) {
+            Some(idx) => idx,
+            None => {
+                self.functions.push(Samples {
+                    function,
+                    own: 0,
+                    total: 0,
+                });
+                self.functions.len() - 1
+            }
+        }
+    }
+
+    /// Records a sample of the call stack, the running function is the last one
+    fn record(&mut self, stack: &[Function]) {
+        self.samples += 1;
+
+        let indexes: Vec<usize> = stack.iter().map(|&f| self.index(f)).collect();
+        if let Some(&running) = indexes.last() {
+            self.functions[running].own += 1;
+        }
+
+        // the recursive functions are only counted once for each sample
+        let mut counted = Vec::with_capacity(indexes.len());
+        for &idx in &indexes {
+            if !counted.contains(&idx) {
+                self.functions[idx].total += 1;
+                counted.push(idx);
+            }
+        }
+
+        let mut counted = Vec::with_capacity(indexes.len());
+        for pair in indexes.windows(2) {
+            let call = (pair[0], pair[1]);
+            if counted.contains(&call) {
+                continue;
+            }
+            counted.push(call);
+
+            match self.calls.iter_mut().find(|(a, b, _)| (*a, *b) == call) {
+                Some((_, _, samples)) => *samples += 1,
+                None => self.calls.push((call.0, call.1, 1)),
+            }
+        }
+    }
+
+    /// How many instructions run between samples
+    pub fn interval(&self) -> usize {
+        self.interval
+    }
+
+    /// How many samples were taken
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    /// The samples of each function, in the order they first ran
+    pub fn functions(&self) -> &[Samples] {
+        &self.functions
+    }
+
+    /// The calls between the functions, as the caller, the callee and the samples taken while
+    /// the caller was calling the callee
+    pub fn calls(&self) -> impl Iterator<Item = (Function, Function, usize)> + '_ {
+        self.calls.iter().map(|&(caller, callee, samples)| {
+            (
+                self.functions[caller].function,
+                self.functions[callee].function,
+                samples,
+            )
+        })
+    }
+}
+
+impl VirtualMachine {
+    /// Starts sampling the running functions, once every `interval` instructions, which makes the
+    /// program run slower
+    pub fn enable_profiling(&mut self, interval: usize) {
+        if self.profile.is_none() {
+            self.profile = Some(Box::new(Profile::new(interval)));
+        }
+    }
+
+    /// The samples taken since [`VirtualMachine::enable_profiling`] was called
+    pub fn profile(&self) -> Option<&Profile> {
+        self.profile.as_deref()
+    }
+
+    /// Called before each instruction while profiling, samples the functions every `interval`
+    /// instructions
+    #[inline(never)]
+    pub(crate) fn sample(&mut self, frame: &Frame) {
+        let Some(mut profile) = self.profile.take() else {
+            return;
+        };
+
+        profile.countdown -= 1;
+        if profile.countdown == 0 {
+            profile.countdown = profile.interval;
+            let stack = self.stack_functions(frame);
+            profile.record(&stack);
+        }
+
+        self.profile = Some(profile);
+    }
+
+    /// The functions running, from the first one called to `frame`
+    ///
+    /// The frames of the functions that called a native function which calls back into the vm
+    /// (like `List.map`) aren't in `frames`, they're kept by the dispatch loop that called it
+    fn stack_functions(&self, frame: &Frame) -> Vec<Function> {
+        let mut stack = Vec::with_capacity(self.frames.len() + self.native_callers.len() + 1);
+        let mut callers = self.native_callers.iter().peekable();
+
+        for (idx, called) in self.frames.iter().chain([frame]).enumerate() {
+            while let Some((_, caller)) = callers.next_if(|(under, _)| *under <= idx) {
+                // SAFETY: the caller waits for the native function, which is still running
+                stack.push(self.function(unsafe { &**caller }));
+            }
+            stack.push(self.function(called));
+        }
+
+        stack
+    }
+
+    fn function(&self, frame: &Frame) -> Function {
+        // SAFETY: the code of the frames is alive while they are
+        let bytecode = unsafe { &*frame.bytecode };
+        let line = bytecode.iter().map(|op| op.line).find(|&line| line != 0);
+
+        Function {
+            name: name(frame.fun.as_ref()),
+            at: self.location(frame.bytecode as usize, line.unwrap_or(0)),
+            top_level: frame.fun.is_none(),
+        }
+    }
+}
+
+fn name(fun: Option<&GcRef<Fn>>) -> Option<Symbol> {
+    Some(fun?.info.as_ref()?.name)
+}
+
+#[test]
+fn samples() {
+    use crate::{FnInfo, OpCode, OpCodeMetadata, Value};
+
+    let code = |ops: &[(usize, OpCode)]| -> crate::Bytecode {
+        ops.iter()
+            .map(|&(line, op)| OpCodeMetadata::new(line, 1, op))
+            .collect()
+    };
+
+    // `def double x = x + x`, called once by the program
+    let double = Fn::new_bt(
+        1,
+        code(&[
+            (1, OpCode::Save(0)),
+            (1, OpCode::Load(0)),
+            (1, OpCode::Load(0)),
+            (1, OpCode::Add),
+        ]),
+    )
+    .with_info(FnInfo::new("double", vec!["x".into()], None));
+    let program = code(&[
+        (2, OpCode::Push(0)),
+        (2, OpCode::Push(1)),
+        (2, OpCode::Call(1)),
+    ]);
+
+    let mut vm = VirtualMachine::default();
+    vm.set_consts(vec![Value::Int(2), Value::Fn(double.to_gcref())]);
+    vm.enable_profiling(1);
+    vm.run(&program).unwrap();
+
+    let profile = vm.profile().unwrap();
+    assert_eq!(profile.samples(), 7);
+
+    let counts: Vec<_> = profile
+        .functions()
+        .iter()
+        .map(|f| (f.function.name.map(|name| name.as_str()), f.own, f.total))
+        .collect();
+    assert_eq!(counts, vec![(None, 3, 7), (Some("double"), 4, 4)]);
+
+    let calls: Vec<_> = profile
+        .calls()
+        .map(|(_, callee, n)| (callee.name, n))
+        .collect();
+    assert_eq!(calls, vec![(Some(Symbol::from("double")), 4)]);
+    assert!(profile.functions()[0].function.top_level);
+}
@@ -0,0 +1,105 @@
+use alloc::sync::Arc;
+
+use crate::{env::EnvTable, prelude, Capabilities, Symbol, Value};
+
+/// A frozen set of globals and constants that can be shared by many [`crate::VirtualMachine`]s,
+/// so the prelude and the host libraries are built only once instead of once per vm
+///
+/// The globals and constants are behind an [`Arc`], so the vms share them instead of copying
+/// them. Values use non-atomic reference counting though, so a registry can be shared by all the
+/// vms of a thread, but each thread needs its own registry.
+///
+/// Code compiled after the vm is created must be added with [`crate::VirtualMachine::load`], which
+/// keeps the registry constants in place.
+///
+/// ```
+/// use vm::{error::InterpretResult, literal::TryGet, Value, VirtualMachine};
+///
+/// fn double(_: *mut VirtualMachine, args: Vec<Value>) -> InterpretResult<Value> {
+///     let n: f64 = args[0].get()?;
+///     Ok(Value::Num(n * 2.0))
+/// }
+///
+/// let registry = VirtualMachine::builder().register("double", 1, double).freeze();
+///
+/// for _ in 0..2 {
+///     let mut vm = VirtualMachine::with_registry(&registry);
+///     let res = vm.call_global("double", &[Value::Num(2.0)]).unwrap();
+///     assert_eq!(res, Value::Num(4.0));
+/// }
+/// ```
+#[derive(Clone)]
+pub struct Registry {
+    pub(crate) globals: Arc<EnvTable>,
+    pub(crate) constants: Arc<[Value]>,
+}
+
+impl Registry {
+    /// Creates a registry from a table of globals
+    pub fn new(globals: EnvTable) -> Self {
+        Self::with_constants(globals, Arc::from([]))
+    }
+
+    // not `Send`, see the documentation of the type
+    #[allow(clippy::arc_with_non_send_sync)]
+    pub(crate) fn with_constants(globals: EnvTable, constants: Arc<[Value]>) -> Self {
+        Self {
+            globals: Arc::new(globals),
+            constants,
+        }
+    }
+
+    /// Creates a registry containing the prelude functions allowed by `capabilities`
+    pub fn prelude(capabilities: Capabilities) -> Self {
+        Self::new(prelude::prelude_with(capabilities))
+    }
+
+    /// Get the value of a global variable
+    pub fn get<T: Into<Symbol>>(&self, name: T) -> Option<Value> {
+        self.globals.get(&name.into())
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::prelude(Capabilities::ALL)
+    }
+}
+
+#[test]
+fn shared_constants() {
+    use crate::{gc::GcRef, Fn, OpCode, OpCodeMetadata, VirtualMachine};
+
+    let ops = |ops: &[OpCode]| -> crate::Bytecode {
+        ops.iter()
+            .map(|op| OpCodeMetadata::new(1, 1, *op))
+            .collect()
+    };
+
+    // `def answer = 42`, called by the code loaded in each vm
+    let answer = Fn::new_bt(0, ops(&[OpCode::Push(1)]));
+    let mut vm = VirtualMachine::default();
+    let code = vm.load(
+        ops(&[OpCode::Push(0), OpCode::Savg("answer".into())]),
+        vec![Value::Fn(GcRef::new(answer)), Value::Int(42)],
+    );
+    vm.run(&code).unwrap();
+    let registry = vm.freeze();
+
+    for n in 0..2 {
+        let mut vm = VirtualMachine::with_registry(&registry);
+        assert!(Arc::ptr_eq(&vm.shared, &registry.constants));
+
+        let code = vm.load(
+            ops(&[
+                OpCode::Push(0),
+                OpCode::Loag("answer".into()),
+                OpCode::Call(0),
+                OpCode::Add,
+            ]),
+            vec![Value::Int(n)],
+        );
+        vm.run(&code).unwrap();
+        assert_eq!(vm.pop_last(), &Value::Int(42 + n));
+    }
+}
@@ -0,0 +1,179 @@
+//! Programs that yield to the host, so it can interleave them with its own work without threads
+//!
+//! A program started by [`VirtualMachine::run_until_yield`] stops at every call to the `yield`
+//! builtin, keeping its frames, stack and locals in the vm, and continues from there once the host
+//! calls [`VirtualMachine::resume`]
+
+use alloc::boxed::Box;
+
+use crate::{
+    error::InterpretResult,
+    raise,
+    task::{Frame, Resume},
+    Bytecode, Value, VirtualMachine,
+};
+
+/// How a program that can yield to the host stopped
+#[derive(Debug, Clone, PartialEq)]
+pub enum Status {
+    /// The program called `yield` with the value, and waits for [`VirtualMachine::resume`]
+    Yielded(Value),
+    /// The program finished
+    Done,
+}
+
+/// The program started by [`VirtualMachine::run_until_yield`]
+pub(crate) struct Resumable {
+    /// The code of the program, boxed so the frames that point to it stay valid
+    bytecode: Box<Bytecode>,
+    /// The state of the program once it yielded
+    suspended: Option<Suspended>,
+    /// The value passed to `yield`
+    yielded: Option<Value>,
+}
+
+/// A program that waits for the host, the rest of its state stays in the vm
+struct Suspended {
+    /// The frame that called `yield`
+    frame: Frame,
+    /// The frames under the program
+    base: usize,
+    /// The task that started the program
+    entering: usize,
+    /// The values the program left on the stack, which [`VirtualMachine::reset`] keeps
+    stack: usize,
+}
+
+impl VirtualMachine {
+    /// Executes the bytecode until it calls `yield`, returning the value passed to it, or until
+    /// it finishes
+    ///
+    /// The program only yields from its own functions, a `yield` inside a function called by a
+    /// native function (like the function passed to `List.map`) raises a `YieldError`
+    ///
+    /// ```
+    /// use vm::{OpCode, OpCodeMetadata, Status, Value, VirtualMachine};
+    ///
+    /// let mut vm = VirtualMachine::default();
+    /// vm.set_consts(vec![Value::Int(1)]);
+    /// // `yield 1 + 1`
+    /// let bytecode = [
+    ///     OpCode::Push(0),
+    ///     OpCode::Push(0),
+    ///     OpCode::Loag("yield".into()),
+    ///     OpCode::Call(1),
+    ///     OpCode::Add,
+    /// ]
+    /// .into_iter()
+    /// .map(|op| OpCodeMetadata::new(1, 1, op))
+    /// .collect();
+    ///
+    /// assert_eq!(vm.run_until_yield(bytecode).unwrap(), Status::Yielded(Value::Int(1)));
+    /// assert_eq!(vm.resume(Value::Int(41)).unwrap(), Status::Done);
+    /// assert_eq!(vm.pop_last(), &Value::Int(42));
+    /// ```
+    pub fn run_until_yield(&mut self, bytecode: Bytecode) -> InterpretResult<Status> {
+        if self.resumable.is_some() {
+            return raise!(
+                YieldError,
+                "A program is already suspended, it must finish before another one starts"
+            );
+        }
+
+        let resumable = self.resumable.insert(Box::new(Resumable {
+            bytecode: Box::new(bytecode),
+            suspended: None,
+            yielded: None,
+        }));
+        let frame = Frame::borrowed(&resumable.bytecode, &[]);
+
+        let res = self.run_frame(frame);
+        self.stopped(res)
+    }
+
+    /// Continues the program that yielded, `value` is the result of its call to `yield`
+    pub fn resume(&mut self, value: Value) -> InterpretResult<Status> {
+        let suspended = self.resumable.as_mut().and_then(|r| r.suspended.take());
+        let Some(Suspended {
+            mut frame,
+            base,
+            entering,
+            ..
+        }) = suspended
+        else {
+            return raise!(YieldError, "There's no suspended program to resume");
+        };
+
+        self.push(value);
+        frame.ip += 1;
+
+        let res = self.enter_loop(frame, base, entering);
+        self.stopped(res)
+    }
+
+    /// Checks if a program yielded and waits for [`Self::resume`]
+    pub fn is_suspended(&self) -> bool {
+        self.resumable
+            .as_ref()
+            .is_some_and(|r| r.suspended.is_some())
+    }
+
+    /// Returns the number of values on the stack that belong to a suspended program
+    pub(crate) fn suspended_stack(&self) -> usize {
+        match &self.resumable {
+            Some(resumable) => resumable.suspended.as_ref().map_or(0, |s| s.stack),
+            None => 0,
+        }
+    }
+
+    /// Returns how the program stopped, it's dropped once it finishes or raises an error
+    fn stopped(&mut self, res: InterpretResult<()>) -> InterpretResult<Status> {
+        let resumable = self.resumable.as_mut().unwrap();
+        if res.is_ok() && resumable.suspended.is_some() {
+            let value = resumable.yielded.take().unwrap_or(Value::Nil);
+            return Ok(Status::Yielded(value));
+        }
+
+        self.resumable = None;
+        res.map(|()| Status::Done)
+    }
+
+    /// Saves the frame that called `yield`, the dispatch loop returns to the host afterwards
+    pub(crate) fn suspend_to_host(
+        &mut self,
+        frame: Frame,
+        base: usize,
+        entering: usize,
+        value: Value,
+    ) {
+        let stack = self.stack.len();
+        let resumable = self.resumable.as_mut().unwrap();
+        resumable.suspended = Some(Suspended {
+            frame,
+            base,
+            entering,
+            stack,
+        });
+        resumable.yielded = Some(value);
+    }
+}
+
+/// Suspends the program, returning `value` to the host
+pub(crate) fn yield_(vm: &mut VirtualMachine, args: &[Value]) -> InterpretResult<Value> {
+    if vm.resumable.is_none() || vm.is_suspended() {
+        return raise!(
+            YieldError,
+            "Only the programs started by `run_until_yield` can yield to the host"
+        );
+    }
+    if !vm.can_suspend() {
+        return raise!(
+            YieldError,
+            "Can't yield inside a function called by a native function"
+        );
+    }
+
+    // the value the host resumes with is pushed in place of the result
+    vm.scheduler.suspend = Some(Resume::Host(args[0].clone()));
+    Ok(Value::Nil)
+}
@@ -0,0 +1,1083 @@
+use alloc::{
+    collections::BTreeSet,
+    string::{String, ToString},
+    vec::Vec,
+};
+
+use crate::{
+    error::InterpretResult,
+    gc::GcRef,
+    literal::{
+        ffi::userdata::{Shared, UserData},
+        fun::{walk_code, FnArgs, FnInfo, MAX_APPLIED},
+        TryGet,
+    },
+    raise, raise_err, Array, Bytecode, Dict, Fn, FnKind, List, OpCode, OpCodeMetadata, Range,
+    Symbol, Tuple, Value, YexModule,
+};
+
+/// The first bytes of every compiled program
+const MAGIC: &[u8; 4] = b"YEXC";
+
+/// Bumped every time the format changes, so stale `.yexc` files are rejected
+const VERSION: u32 = 7;
+
+/// Checks if `bytes` looks like a compiled program
+pub fn is_compiled(bytes: &[u8]) -> bool {
+    bytes.starts_with(MAGIC)
+}
+
+/// Serializes a compiled program, so it can be written to a `.yexc` file and executed later
+/// without being parsed again
+///
+/// Only values created by the compiler can be serialized, native functions and foreign values
+/// raise a `SerializeError`
+pub fn serialize(bytecode: &Bytecode, constants: &[Value]) -> InterpretResult<Vec<u8>> {
+    let mut enc = Encoder {
+        out: MAGIC.to_vec(),
+        modules: vec![],
+        shared: None,
+    };
+
+    enc.u32(VERSION);
+    enc.usize(constants.len());
+    for value in constants {
+        enc.value(value)?;
+    }
+    enc.bytecode(bytecode);
+
+    Ok(enc.out)
+}
+
+/// Deserializes a program created by [`serialize`], returning its bytecode and constants
+///
+/// The operands and the stack usage of the code are checked, so a corrupted program raises a
+/// `SerializeError` instead of crashing the vm, the upvalues and the dropped locals are checked
+/// while it runs
+pub fn deserialize(bytes: &[u8]) -> InterpretResult<(Bytecode, Vec<Value>)> {
+    if !is_compiled(bytes) {
+        return raise!(SerializeError, "Not a compiled yex program");
+    }
+
+    let mut dec = Decoder {
+        bytes,
+        pos: MAGIC.len(),
+        modules: vec![],
+        shared: &[],
+    };
+
+    let version = dec.u32()?;
+    if version != VERSION {
+        return raise!(
+            SerializeError,
+            "Unsupported format version {}, expected {}",
+            version,
+            VERSION
+        );
+    }
+
+    let len = dec.usize()?;
+    let mut constants = Vec::with_capacity(len.min(bytes.len()));
+    for _ in 0..len {
+        constants.push(dec.value()?);
+    }
+    let bytecode = dec.bytecode()?;
+
+    if dec.pos != bytes.len() {
+        return raise!(SerializeError, "Trailing bytes after the program");
+    }
+
+    validate(&bytecode, constants.len(), 0)?;
+    let mut seen = BTreeSet::new();
+    let mut result = Ok(());
+    for value in constants.iter() {
+        walk_code(value, &mut seen, &mut |fun, code| {
+            if result.is_ok() {
+                // the arguments of a function are on the stack when its code starts
+                result = validate(code, constants.len(), fun.arity + fun.args.len());
+            }
+        });
+    }
+    result?;
+
+    Ok((bytecode, constants))
+}
+
+/// Checks that the operands of a deserialized chunk are in its bounds and that it never pops more
+/// values than the stack has, the vm trusts the compiler and panics on a corrupted program
+///
+/// `args` is how many values are on the stack when the chunk starts, a function must leave only
+/// its result on it
+fn validate(bytecode: &Bytecode, constants: usize, args: usize) -> InterpretResult<()> {
+    // a chunk can't use more locals than it declares, and it declares at most one for each of
+    // its instructions
+    let mut locals = 0;
+    for op in bytecode.iter() {
+        match op.opcode {
+            OpCode::Save(_) => locals += 1,
+            OpCode::Reserve(n) if n <= bytecode.len() => locals += n,
+            OpCode::Reserve(n) => return raise!(SerializeError, "Invalid reserve of {} locals", n),
+            _ => {}
+        }
+    }
+
+    for (ip, op) in bytecode.iter().enumerate() {
+        let (kind, index, len) = match op.opcode {
+            OpCode::Push(idx) => ("constant", idx, constants),
+            OpCode::Load(idx) | OpCode::Save(idx) => ("local", idx, locals),
+            // jumping past the last instruction returns from the code
+            OpCode::Jmp(to) | OpCode::Jmf(to) | OpCode::Try(to) => {
+                ("jump target", to, bytecode.len() + 1)
+            }
+            _ => continue,
+        };
+
+        if index >= len {
+            return raise!(
+                SerializeError,
+                "Invalid {} {} in the instruction {}",
+                kind,
+                index,
+                ip
+            );
+        }
+    }
+
+    // the height of the stack before each instruction, which must be the same on every path that
+    // reaches it, so the loops can't grow the stack
+    let mut heights = vec![None; bytecode.len() + 1];
+    let mut pending = vec![0];
+    heights[0] = Some(args);
+
+    while let Some(ip) = pending.pop() {
+        let height = heights[ip].unwrap();
+        let op = match bytecode.get(ip) {
+            Some(op) => op.opcode,
+            None => continue,
+        };
+
+        let (pops, pushes) = match stack_effect(op) {
+            Some(effect) => effect,
+            None => return raise!(SerializeError, "Invalid opcode in the instruction {}", ip),
+        };
+        if height < pops {
+            return raise!(SerializeError, "Stack underflow in the instruction {}", ip);
+        }
+        let after = height - pops + pushes;
+
+        let next: &[(usize, usize)] = match op {
+            OpCode::Jmp(to) => &[(to, after)],
+            OpCode::Jmf(to) => &[(to, after), (ip + 1, after)],
+            // the handler runs after the instruction at the target, with the stack as it was at
+            // the `Try` plus the error
+            OpCode::Try(to) if to < bytecode.len() => &[(to + 1, height + 1), (ip + 1, after)],
+            OpCode::Try(to) => {
+                return raise!(
+                    SerializeError,
+                    "Invalid jump target {} in the instruction {}",
+                    to,
+                    ip
+                )
+            }
+            OpCode::MatchFail => &[],
+            _ => &[(ip + 1, after)],
+        };
+        for &(to, height) in next {
+            match heights[to] {
+                None => {
+                    heights[to] = Some(height);
+                    pending.push(to);
+                }
+                Some(known) if known == height => {}
+                Some(_) => {
+                    return raise!(SerializeError, "Mismatched stack in the instruction {}", to)
+                }
+            }
+        }
+    }
+
+    // the functions return the value on the stack top
+    match heights[bytecode.len()] {
+        Some(height) if args > 0 && height != 1 => raise!(
+            SerializeError,
+            "The function leaves {} values on the stack, instead of its result",
+            height
+        ),
+        _ => Ok(()),
+    }
+}
+
+/// How many values an instruction pops from the stack and how many it pushes, `None` for the
+/// instructions the compiler doesn't emit
+fn stack_effect(op: OpCode) -> Option<(usize, usize)> {
+    let effect = match op {
+        OpCode::Nop
+        | OpCode::Reserve(_)
+        | OpCode::Drop(_)
+        | OpCode::Jmp(_)
+        | OpCode::Try(_)
+        | OpCode::EndTry
+        | OpCode::Generator => (0, 0),
+        OpCode::Push(_)
+        | OpCode::Load(_)
+        | OpCode::Loag(_)
+        | OpCode::LoadUpval(_)
+        | OpCode::Import(_) => (0, 1),
+        OpCode::Pop
+        | OpCode::Save(_)
+        | OpCode::Savg(_)
+        | OpCode::Savm(_)
+        | OpCode::Jmf(_)
+        | OpCode::MatchFail => (1, 0),
+        OpCode::Dup => (1, 2),
+        OpCode::Neg
+        | OpCode::Len
+        | OpCode::Not
+        | OpCode::Type
+        | OpCode::Ref(_)
+        | OpCode::TupGet(_)
+        | OpCode::TagOf
+        | OpCode::TagTup
+        | OpCode::Yield => (1, 1),
+        OpCode::Add
+        | OpCode::Rem
+        | OpCode::Sub
+        | OpCode::Mul
+        | OpCode::Div
+        | OpCode::Xor
+        | OpCode::Shr
+        | OpCode::Shl
+        | OpCode::BitAnd
+        | OpCode::BitOr
+        | OpCode::Eq
+        | OpCode::Less
+        | OpCode::LessEq
+        | OpCode::AddNum
+        | OpCode::ConcatStr
+        | OpCode::Prep
+        | OpCode::Overload
+        | OpCode::Range(_)
+        | OpCode::Tag(_)
+        | OpCode::Struct
+        | OpCode::Update => (2, 1),
+        OpCode::Rev | OpCode::Dispatch(_) => (2, 2),
+        OpCode::Impl => (3, 0),
+        OpCode::RevN(n) => (n, n),
+        OpCode::Tup(n) | OpCode::Array(n) => (n, 1),
+        OpCode::Dict(n) => (n.checked_mul(2)?, 1),
+        OpCode::Call(n) | OpCode::TCall(n) | OpCode::Closure(n) => (n.checked_add(1)?, 1),
+        OpCode::Swap(..) | OpCode::New => return None,
+    };
+    Some(effect)
+}
+
+/// A value copied out of a vm, so it can be moved to another thread and copied into another vm
+///
+/// Everything is copied, except for [`Shared`] userdata, which is kept by reference, so both vms
+/// see the same data
+pub struct Packed {
+    bytes: Vec<u8>,
+    shared: Vec<Shared>,
+}
+
+impl Packed {
+    /// Copies `value`, raising a `SerializeError` if it holds native functions or foreign values
+    pub fn new(value: &Value) -> InterpretResult<Self> {
+        let mut enc = Encoder {
+            out: vec![],
+            modules: vec![],
+            shared: Some(vec![]),
+        };
+        enc.value(value)?;
+
+        Ok(Self {
+            bytes: enc.out,
+            shared: enc.shared.unwrap_or_default(),
+        })
+    }
+
+    /// Creates a new copy of the packed value, the types it refers to are copied as well, so they
+    /// aren't the same as the ones in the original vm
+    pub fn unpack(&self) -> InterpretResult<Value> {
+        let mut dec = Decoder {
+            bytes: &self.bytes,
+            pos: 0,
+            modules: vec![],
+            shared: &self.shared,
+        };
+        dec.value()
+    }
+}
+
+mod tag {
+    pub const NIL: u8 = 0;
+    pub const NUM: u8 = 1;
+    pub const STR: u8 = 2;
+    pub const SYM: u8 = 3;
+    pub const BOOL: u8 = 4;
+    pub const FN: u8 = 5;
+    pub const LIST: u8 = 6;
+    pub const MODULE: u8 = 7;
+    pub const MODULE_REF: u8 = 8;
+    pub const TUPLE: u8 = 9;
+    pub const TAGGED: u8 = 10;
+    pub const INT: u8 = 11;
+    pub const BYTES: u8 = 12;
+    pub const DICT: u8 = 13;
+    pub const ARRAY: u8 = 14;
+    pub const RANGE: u8 = 15;
+    pub const SHARED: u8 = 16;
+}
+
+struct Encoder {
+    out: Vec<u8>,
+    /// Modules already written, they're referenced by index afterwards, since types refer to
+    /// themselves through their variants
+    modules: Vec<*const YexModule>,
+    /// The shared userdata found while packing a value, `None` when serializing a program, since
+    /// it can't be written to a file
+    shared: Option<Vec<Shared>>,
+}
+
+impl Encoder {
+    fn u8(&mut self, n: u8) {
+        self.out.push(n);
+    }
+
+    fn u32(&mut self, n: u32) {
+        self.out.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn usize(&mut self, n: usize) {
+        self.out.extend_from_slice(&(n as u64).to_le_bytes());
+    }
+
+    fn str(&mut self, str: &str) {
+        self.usize(str.len());
+        self.out.extend_from_slice(str.as_bytes());
+    }
+
+    fn symbol(&mut self, sym: Symbol) {
+        self.str(sym.as_str());
+    }
+
+    fn module(&mut self, module: &GcRef<YexModule>) -> InterpretResult<()> {
+        let ptr = &**module as *const YexModule;
+        if let Some(index) = self.modules.iter().position(|it| *it == ptr) {
+            self.u8(tag::MODULE_REF);
+            self.usize(index);
+            return Ok(());
+        }
+
+        self.modules.push(ptr);
+        self.u8(tag::MODULE);
+        self.symbol(module.name);
+
+        let fields: Vec<_> = module.fields.iter().collect();
+        self.usize(fields.len());
+        for (name, value) in fields {
+            self.symbol(name);
+            self.value(&value)?;
+        }
+
+        Ok(())
+    }
+
+    fn value(&mut self, value: &Value) -> InterpretResult<()> {
+        match value {
+            Value::Nil => self.u8(tag::NIL),
+            Value::Num(n) => {
+                self.u8(tag::NUM);
+                self.out.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Int(n) => {
+                self.u8(tag::INT);
+                self.out.extend_from_slice(&n.to_le_bytes());
+            }
+            Value::Str(str) => {
+                self.u8(tag::STR);
+                self.str(str);
+            }
+            Value::Bytes(bytes) => {
+                self.u8(tag::BYTES);
+                self.usize(bytes.len());
+                self.out.extend_from_slice(&bytes.0);
+            }
+            Value::Sym(sym) => {
+                self.u8(tag::SYM);
+                self.symbol(sym.0);
+            }
+            Value::Bool(b) => {
+                self.u8(tag::BOOL);
+                self.u8(*b as u8);
+            }
+            Value::Fn(fun) => {
+                let bytecode = match &*fun.body {
+                    FnKind::Bytecode(bytecode) => bytecode,
+                    FnKind::Native(_) => {
+                        return raise!(
+                            SerializeError,
+                            "Can't serialize the native function '{}'",
+                            value
+                        )
+                    }
+                };
+
+                self.u8(tag::FN);
+                self.usize(fun.arity);
+                self.u8(fun.variadic as u8);
+                self.bytecode(bytecode);
+
+                self.usize(fun.args.len());
+                for arg in fun.args.iter() {
+                    self.value(arg)?;
+                }
+
+                self.usize(fun.upvalues.len());
+                for upvalue in fun.upvalues.iter() {
+                    self.value(upvalue)?;
+                }
+
+                match &fun.info {
+                    Some(info) => {
+                        self.u8(1);
+                        self.symbol(info.name);
+                        self.usize(info.params.len());
+                        for param in info.params.iter() {
+                            self.str(param);
+                        }
+                        match &info.doc {
+                            Some(doc) => {
+                                self.u8(1);
+                                self.str(doc);
+                            }
+                            None => self.u8(0),
+                        }
+                        self.usize(info.locals.len());
+                        for (slot, name) in info.locals.iter() {
+                            self.usize(*slot);
+                            self.symbol(*name);
+                        }
+                    }
+                    None => self.u8(0),
+                }
+
+                match &fun.more {
+                    Some(more) => {
+                        self.u8(1);
+                        self.value(&Value::Fn(more.clone()))?;
+                    }
+                    None => self.u8(0),
+                }
+            }
+            Value::List(xs) => {
+                self.u8(tag::LIST);
+                self.usize(xs.len());
+                for x in xs.iter() {
+                    self.value(&x)?;
+                }
+            }
+            Value::Array(xs) => {
+                self.u8(tag::ARRAY);
+                self.usize(xs.len());
+                for x in xs.iter() {
+                    self.value(&x)?;
+                }
+            }
+            Value::Dict(dict) => {
+                self.u8(tag::DICT);
+                self.usize(dict.len());
+                for (key, value) in dict.iter() {
+                    self.value(key)?;
+                    self.value(value)?;
+                }
+            }
+            Value::Range(range) => {
+                self.u8(tag::RANGE);
+                self.out.extend_from_slice(&range.start.to_le_bytes());
+                self.out.extend_from_slice(&range.end.to_le_bytes());
+                self.u8(range.inclusive as u8);
+            }
+            Value::UserData(data) if data.is::<Shared>() && self.shared.is_some() => {
+                let shared = self.shared.as_mut().unwrap();
+                shared.push(data.downcast_ref::<Shared>().unwrap().clone());
+                let index = shared.len() - 1;
+
+                self.u8(tag::SHARED);
+                self.usize(index);
+            }
+            Value::Module(module) => self.module(module)?,
+            Value::Tuple(tup) => {
+                self.u8(tag::TUPLE);
+                self.tuple(tup)?;
+            }
+            Value::Tagged(tagged) => {
+                let (module, name, tup) = &**tagged;
+                self.u8(tag::TAGGED);
+                self.module(module)?;
+                self.symbol(*name);
+                self.tuple(tup)?;
+            }
+            other => return raise!(SerializeError, "Can't serialize the value '{}'", other),
+        }
+
+        Ok(())
+    }
+
+    fn tuple(&mut self, tup: &Tuple) -> InterpretResult<()> {
+        self.usize(tup.len());
+        for x in tup.0.iter() {
+            self.value(x)?;
+        }
+        Ok(())
+    }
+
+    fn bytecode(&mut self, bytecode: &Bytecode) {
+        self.usize(bytecode.len());
+        for op in bytecode {
+            self.usize(op.line);
+            self.usize(op.column);
+            self.opcode(op.opcode);
+        }
+    }
+
+    fn opcode(&mut self, op: OpCode) {
+        use OpCode::*;
+
+        let (code, arg): (u8, Option<usize>) = match op {
+            Nop => (0, None),
+            Push(n) => (1, Some(n)),
+            Pop => (2, None),
+            Dup => (3, None),
+            Swap(a, b) => {
+                self.u8(4);
+                self.usize(a);
+                self.usize(b);
+                return;
+            }
+            Load(n) => (5, Some(n)),
+            Save(n) => (6, Some(n)),
+            Loag(name) => {
+                self.u8(7);
+                return self.symbol(name);
+            }
+            Savg(name) => {
+                self.u8(8);
+                return self.symbol(name);
+            }
+            Drop(n) => (9, Some(n)),
+            Jmf(n) => (10, Some(n)),
+            Jmp(n) => (11, Some(n)),
+            Call(n) => (12, Some(n)),
+            TCall(n) => (13, Some(n)),
+            Prep => (14, None),
+            Rev => (15, None),
+            RevN(n) => (16, Some(n)),
+            Add => (17, None),
+            Rem => (18, None),
+            Sub => (19, None),
+            Mul => (20, None),
+            Div => (21, None),
+            Neg => (22, None),
+            Len => (23, None),
+            Not => (24, None),
+            Xor => (25, None),
+            Shr => (26, None),
+            Shl => (27, None),
+            BitAnd => (28, None),
+            BitOr => (29, None),
+            Eq => (30, None),
+            Less => (31, None),
+            LessEq => (32, None),
+            New => (33, None),
+            Type => (34, None),
+            Ref(name) => {
+                self.u8(35);
+                return self.symbol(name);
+            }
+            Try(n) => (36, Some(n)),
+            EndTry => (37, None),
+            Tup(n) => (38, Some(n)),
+            TupGet(n) => (39, Some(n)),
+            Tag(name) => {
+                self.u8(40);
+                return self.symbol(name);
+            }
+            TagOf => (41, None),
+            TagTup => (42, None),
+            LoadUpval(n) => (43, Some(n)),
+            Closure(n) => (44, Some(n)),
+            Dict(n) => (45, Some(n)),
+            Array(n) => (54, Some(n)),
+            Range(inclusive) => (46, Some(inclusive as usize)),
+            Import(name) => {
+                self.u8(47);
+                return self.symbol(name);
+            }
+            Overload => (48, None),
+            Dispatch(name) => {
+                self.u8(49);
+                return self.symbol(name);
+            }
+            Impl => (50, None),
+            Struct => (51, None),
+            Update => (52, None),
+            Reserve(n) => (53, Some(n)),
+            Generator => (55, None),
+            Yield => (56, None),
+            MatchFail => (57, None),
+            Savm(path) => {
+                self.u8(58);
+                return self.symbol(path);
+            }
+            AddNum => (59, None),
+            ConcatStr => (60, None),
+        };
+
+        self.u8(code);
+        if let Some(arg) = arg {
+            self.usize(arg);
+        }
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    modules: Vec<GcRef<YexModule>>,
+    shared: &'a [Shared],
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, len: usize) -> InterpretResult<&[u8]> {
+        let end = self
+            .pos
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| raise_err!(SerializeError, "Unexpected end of the program"))?;
+
+        let bytes = &self.bytes[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> InterpretResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> InterpretResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn usize(&mut self) -> InterpretResult<usize> {
+        let n = u64::from_le_bytes(self.take(8)?.try_into().unwrap());
+        usize::try_from(n).map_err(|_| raise_err!(SerializeError, "Invalid length {}", n))
+    }
+
+    fn str(&mut self) -> InterpretResult<String> {
+        let len = self.usize()?;
+        let bytes = self.take(len)?;
+
+        core::str::from_utf8(bytes)
+            .map(ToString::to_string)
+            .map_err(|_| raise_err!(SerializeError, "Invalid utf-8 string"))
+    }
+
+    fn symbol(&mut self) -> InterpretResult<Symbol> {
+        self.str().map(Symbol::new)
+    }
+
+    fn flag(&mut self) -> InterpretResult<bool> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            n => raise!(SerializeError, "Invalid boolean {}", n),
+        }
+    }
+
+    fn module(&mut self) -> InterpretResult<GcRef<YexModule>> {
+        match self.u8()? {
+            tag::MODULE => {
+                let name = self.symbol()?;
+                let mut module = GcRef::new(YexModule::with_name(name));
+                self.modules.push(module.clone());
+
+                for _ in 0..self.usize()? {
+                    let field = self.symbol()?;
+                    let value = self.value()?;
+                    // SAFETY: the module is only referenced by the values being decoded
+                    unsafe { module.mut_ref().fields.insert(field, value) };
+                }
+
+                Ok(module)
+            }
+            tag::MODULE_REF => {
+                let index = self.usize()?;
+                self.modules
+                    .get(index)
+                    .cloned()
+                    .ok_or_else(|| raise_err!(SerializeError, "Invalid type reference {}", index))
+            }
+            n => raise!(SerializeError, "Expected a type, found the tag {}", n),
+        }
+    }
+
+    fn value(&mut self) -> InterpretResult<Value> {
+        let value = match self.u8()? {
+            tag::NIL => Value::Nil,
+            tag::NUM => Value::Num(f64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            tag::INT => Value::Int(i64::from_le_bytes(self.take(8)?.try_into().unwrap())),
+            tag::STR => Value::Str(GcRef::new(self.str()?)),
+            tag::SYM => Value::Sym(self.symbol()?.into()),
+            tag::BYTES => {
+                let len = self.usize()?;
+                Value::Bytes(self.take(len)?.to_vec().into())
+            }
+            tag::BOOL => Value::Bool(self.flag()?),
+            tag::FN => {
+                let arity = self.usize()?;
+                let variadic = self.flag()?;
+                let body = self.bytecode()?;
+
+                let mut args = FnArgs::new();
+                for _ in 0..self.usize()? {
+                    let arg = self.value()?;
+                    if args.len() == MAX_APPLIED {
+                        return raise!(SerializeError, "Too many applied arguments");
+                    }
+                    args.push(arg);
+                }
+
+                let upvalues = (0..self.usize()?)
+                    .map(|_| self.value())
+                    .collect::<InterpretResult<_>>()?;
+
+                let mut fun = Fn::new_bt(arity, body);
+                fun.args = args;
+                fun.upvalues = upvalues;
+                fun.variadic = variadic;
+
+                if self.flag()? {
+                    let name = self.symbol()?;
+                    let params = (0..self.usize()?)
+                        .map(|_| self.str())
+                        .collect::<InterpretResult<_>>()?;
+                    let doc = if self.flag()? {
+                        Some(self.str()?)
+                    } else {
+                        None
+                    };
+                    let locals = (0..self.usize()?)
+                        .map(|_| Ok((self.usize()?, self.symbol()?)))
+                        .collect::<InterpretResult<_>>()?;
+                    fun = fun.with_info(FnInfo::new(name, params, doc).with_locals(locals));
+                }
+
+                if self.flag()? {
+                    fun.more = Some(self.value()?.get()?);
+                }
+
+                Value::Fn(GcRef::new(fun))
+            }
+            tag::LIST => {
+                let xs = (0..self.usize()?)
+                    .map(|_| self.value())
+                    .collect::<InterpretResult<Vec<_>>>()?;
+                Value::List(xs.into_iter().rev().collect::<List>())
+            }
+            tag::ARRAY => {
+                let xs = (0..self.usize()?)
+                    .map(|_| self.value())
+                    .collect::<InterpretResult<Vec<_>>>()?;
+                Value::Array(xs.into_iter().collect::<Array>())
+            }
+            tag::DICT => {
+                let entries = (0..self.usize()?)
+                    .map(|_| Ok((self.value()?, self.value()?)))
+                    .collect::<InterpretResult<Vec<_>>>()?;
+                Value::Dict(Dict::from_entries(entries)?)
+            }
+            tag::RANGE => {
+                let start = i64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                let end = i64::from_le_bytes(self.take(8)?.try_into().unwrap());
+                let range = match self.u8()? {
+                    0 => Range::new(start, end),
+                    _ => Range::inclusive(start, end),
+                };
+                Value::Range(GcRef::new(range))
+            }
+            tag::SHARED => {
+                let index = self.usize()?;
+                let shared = self.shared.get(index).ok_or_else(|| {
+                    raise_err!(SerializeError, "Invalid shared reference {}", index)
+                })?;
+                Value::UserData(UserData::new(shared.clone()))
+            }
+            tag::MODULE | tag::MODULE_REF => {
+                self.pos -= 1;
+                Value::Module(self.module()?)
+            }
+            tag::TUPLE => Value::Tuple(self.tuple()?),
+            tag::TAGGED => {
+                let module = self.module()?;
+                let name = self.symbol()?;
+                Value::tagged(module, name, self.tuple()?)
+            }
+            n => return raise!(SerializeError, "Invalid value tag {}", n),
+        };
+
+        Ok(value)
+    }
+
+    fn tuple(&mut self) -> InterpretResult<Tuple> {
+        let xs = (0..self.usize()?)
+            .map(|_| self.value())
+            .collect::<InterpretResult<Vec<_>>>()?;
+        Ok(Tuple::from(xs))
+    }
+
+    fn bytecode(&mut self) -> InterpretResult<Bytecode> {
+        let len = self.usize()?;
+        let mut bytecode = Vec::with_capacity(len.min(self.bytes.len()));
+
+        for _ in 0..len {
+            let line = self.usize()?;
+            let column = self.usize()?;
+            let opcode = self.opcode()?;
+            bytecode.push(OpCodeMetadata::new(line, column, opcode));
+        }
+
+        Ok(bytecode)
+    }
+
+    fn opcode(&mut self) -> InterpretResult<OpCode> {
+        use OpCode::*;
+
+        let op = match self.u8()? {
+            0 => Nop,
+            1 => Push(self.usize()?),
+            2 => Pop,
+            3 => Dup,
+            4 => Swap(self.usize()?, self.usize()?),
+            5 => Load(self.usize()?),
+            6 => Save(self.usize()?),
+            7 => Loag(self.symbol()?),
+            8 => Savg(self.symbol()?),
+            9 => Drop(self.usize()?),
+            10 => Jmf(self.usize()?),
+            11 => Jmp(self.usize()?),
+            12 => Call(self.usize()?),
+            13 => TCall(self.usize()?),
+            14 => Prep,
+            15 => Rev,
+            16 => RevN(self.usize()?),
+            17 => Add,
+            18 => Rem,
+            19 => Sub,
+            20 => Mul,
+            21 => Div,
+            22 => Neg,
+            23 => Len,
+            24 => Not,
+            25 => Xor,
+            26 => Shr,
+            27 => Shl,
+            28 => BitAnd,
+            29 => BitOr,
+            30 => Eq,
+            31 => Less,
+            32 => LessEq,
+            33 => New,
+            34 => Type,
+            35 => Ref(self.symbol()?),
+            36 => Try(self.usize()?),
+            37 => EndTry,
+            38 => Tup(self.usize()?),
+            39 => TupGet(self.usize()?),
+            40 => Tag(self.symbol()?),
+            41 => TagOf,
+            42 => TagTup,
+            43 => LoadUpval(self.usize()?),
+            44 => Closure(self.usize()?),
+            45 => Dict(self.usize()?),
+            54 => Array(self.usize()?),
+            46 => Range(self.usize()? != 0),
+            47 => Import(self.symbol()?),
+            48 => Overload,
+            49 => Dispatch(self.symbol()?),
+            50 => Impl,
+            51 => Struct,
+            52 => Update,
+            53 => Reserve(self.usize()?),
+            55 => Generator,
+            56 => Yield,
+            57 => MatchFail,
+            58 => Savm(self.symbol()?),
+            59 => AddNum,
+            60 => ConcatStr,
+            n => return raise!(SerializeError, "Invalid opcode {}", n),
+        };
+
+        Ok(op)
+    }
+}
+
+#[test]
+fn roundtrip() {
+    let mut module = GcRef::new(YexModule::with_name("Option"));
+    let none = Value::tagged(module.clone(), "Option.none".into(), vec![].into());
+    unsafe { module.mut_ref().fields.insert("none".into(), none) };
+
+    let body = vec![
+        OpCodeMetadata::new(1, 1, OpCode::Save(0)),
+        OpCodeMetadata::new(1, 2, OpCode::Load(0)),
+        OpCodeMetadata::new(1, 4, OpCode::Ref("len".into())),
+    ];
+    let fun = Fn::new_bt(1, body.clone())
+        .with_info(FnInfo::new("len", vec!["xs".into()], None).with_locals(vec![(0, "xs".into())]));
+    let constants = vec![
+        Value::Num(1.5),
+        Value::Int(-42),
+        Value::Bytes(vec![0, 255].into()),
+        "hello".to_string().into(),
+        Value::List([Value::Bool(true), Value::Nil].into_iter().rev().collect()),
+        Value::Module(module),
+        Value::Fn(GcRef::new(fun)),
+    ];
+
+    let code: Bytecode = [OpCode::Push(6), OpCode::Save(0), OpCode::Load(0)]
+        .into_iter()
+        .map(|op| OpCodeMetadata::new(1, 1, op))
+        .collect();
+    let bytes = serialize(&code, &constants).unwrap();
+    let (bytecode, decoded) = deserialize(&bytes).unwrap();
+
+    assert_eq!(bytecode, code);
+    assert_eq!(decoded[..4], constants[..4]);
+    assert_eq!(decoded[5], constants[5]);
+
+    match &decoded[5] {
+        Value::Module(module) => match module.fields.get(&"none".into()) {
+            Some(Value::Tagged(inner)) => assert!(core::ptr::eq(&**module, &*inner.0)),
+            _ => panic!("expected a tagged value"),
+        },
+        _ => panic!("expected a module"),
+    }
+    match &decoded[6] {
+        Value::Fn(fun) => assert_eq!(fun.info.as_ref().unwrap().locals, [(0, "xs".into())]),
+        _ => panic!("expected a function"),
+    }
+
+    assert!(deserialize(&bytes[..bytes.len() - 1]).is_err());
+    // the errors raised while loading have no location
+    assert_eq!(
+        deserialize(b"YEXC").unwrap_err().to_string(),
+        ":SerializeError\n  Unexpected end of the program"
+    );
+
+    // the operands that would make the vm panic are rejected
+    let invalid = |code: &[OpCode], constants: &[Value]| {
+        let code: Bytecode = code
+            .iter()
+            .map(|op| OpCodeMetadata::new(1, 1, *op))
+            .collect();
+        let bytes = serialize(&code, constants).unwrap();
+        deserialize(&bytes).unwrap_err().msg
+    };
+    assert_eq!(
+        invalid(&[OpCode::Push(1)], &[Value::Nil]),
+        "Invalid constant 1 in the instruction 0"
+    );
+    assert_eq!(
+        invalid(&[OpCode::Save(0), OpCode::Load(1)], &[]),
+        "Invalid local 1 in the instruction 1"
+    );
+    assert_eq!(
+        invalid(&[OpCode::Jmp(3), OpCode::Nop], &[]),
+        "Invalid jump target 3 in the instruction 0"
+    );
+    let fun = Fn::new_bt(1, vec![OpCodeMetadata::new(1, 1, OpCode::Load(0))]);
+    assert_eq!(
+        invalid(&[OpCode::Push(0)], &[Value::Fn(GcRef::new(fun))]),
+        "Invalid local 0 in the instruction 0"
+    );
+
+    // and so is the code that pops more values than the stack has
+    assert_eq!(
+        invalid(&[OpCode::Push(0), OpCode::RevN(2)], &[Value::Nil]),
+        "Stack underflow in the instruction 1"
+    );
+    assert_eq!(
+        invalid(&[OpCode::Push(0), OpCode::Tup(3)], &[Value::Nil]),
+        "Stack underflow in the instruction 1"
+    );
+    assert_eq!(
+        invalid(&[OpCode::Push(0), OpCode::Jmp(0)], &[Value::Nil]),
+        "Mismatched stack in the instruction 0"
+    );
+    assert_eq!(
+        invalid(&[OpCode::Swap(0, 9)], &[]),
+        "Invalid opcode in the instruction 0"
+    );
+    let fun = Fn::new_bt(1, vec![OpCodeMetadata::new(1, 1, OpCode::Dup)]);
+    assert_eq!(
+        invalid(&[OpCode::Push(0)], &[Value::Fn(GcRef::new(fun))]),
+        "The function leaves 2 values on the stack, instead of its result"
+    );
+
+    // the locals and the upvalues are checked while running
+    let run = |code: &[OpCode]| {
+        let code: Bytecode = code
+            .iter()
+            .map(|op| OpCodeMetadata::new(1, 1, *op))
+            .collect();
+        let (code, constants) = deserialize(&serialize(&code, &[]).unwrap()).unwrap();
+        let mut vm = crate::VirtualMachine::default();
+        let code = vm.load(code, constants);
+        vm.run(&code).unwrap_err().msg
+    };
+    assert_eq!(run(&[OpCode::Drop(0)]), "Dropped a local that wasn't saved");
+    assert_eq!(run(&[OpCode::LoadUpval(3)]), "Invalid upvalue 3");
+}
+
+#[test]
+fn pack() {
+    use alloc::sync::Arc;
+
+    let shared: Shared = Arc::new(42_i32);
+    let dict = Dict::from_entries([
+        (
+            Value::Int(1),
+            Value::Range(GcRef::new(Range::inclusive(0, i64::MAX))),
+        ),
+        (
+            Value::Int(2),
+            Value::Array([Value::Nil].into_iter().collect()),
+        ),
+    ])
+    .unwrap();
+    let value: Value = vec![
+        dict.clone().into(),
+        Value::UserData(UserData::new(shared.clone())),
+    ]
+    .into();
+
+    let packed = Packed::new(&value).unwrap();
+    assert_eq!(Arc::strong_count(&shared), 3);
+
+    match packed.unpack().unwrap() {
+        Value::Tuple(tup) => {
+            assert_eq!(tup.0[0], Value::Dict(dict));
+            match &tup.0[1] {
+                Value::UserData(data) => {
+                    let data = data.downcast_ref::<Shared>().unwrap();
+                    assert!(Arc::ptr_eq(data, &shared));
+                }
+                _ => panic!("expected userdata"),
+            }
+        }
+        _ => panic!("expected a tuple"),
+    }
+
+    assert!(serialize(&vec![], &[value]).is_err());
+}
@@ -1,4 +1,5 @@
-use std::{
+use alloc::vec::Vec;
+use core::{
     mem::{self, MaybeUninit},
     ops::Deref,
 };
@@ -48,7 +49,7 @@ impl<T, const S: usize> StackVec<T, S> {
 
     #[track_caller]
     /// Returns an iterator of mutable references to the elements
-    pub fn iter_mut(&mut self) -> impl Iterator<Item = &mut T> + DoubleEndedIterator {
+    pub fn iter_mut(&mut self) -> impl DoubleEndedIterator<Item = &mut T> {
         self.array[0..self.len]
             .iter_mut()
             .map(|it| unsafe { it.assume_init_mut() })
@@ -56,7 +57,7 @@ impl<T, const S: usize> StackVec<T, S> {
 
     #[track_caller]
     /// Returns an iterator of references to the elements
-    pub fn iter(&self) -> impl Iterator<Item = &T> + DoubleEndedIterator {
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> {
         self.array[0..self.len]
             .iter()
             .map(|it| unsafe { it.assume_init_ref() })
@@ -70,6 +71,13 @@ impl<T, const S: usize> StackVec<T, S> {
         self.pop();
     }
 
+    #[track_caller]
+    #[inline]
+    /// Reverses the order of the last `n` elements
+    pub fn reverse_last(&mut self, n: usize) {
+        self.array[self.len - n..self.len].reverse();
+    }
+
     /// Reverses the StackVec in place
     #[must_use]
     #[track_caller]
@@ -87,6 +95,17 @@ impl<T, const S: usize> StackVec<T, S> {
         }
     }
 
+    #[track_caller]
+    #[inline]
+    /// Returns a reference to the `n`th element from the top, `peek(0)` is the last one
+    pub fn peek(&self, n: usize) -> Option<&T> {
+        if n >= self.len {
+            None
+        } else {
+            unsafe { Some(self.array[self.len - n - 1].assume_init_ref()) }
+        }
+    }
+
     #[track_caller]
     /// Returns a mutable reference to the last element
     pub fn last_mut(&mut self) -> Option<&mut T> {
@@ -126,8 +145,8 @@ impl<T, const S: usize> StackVec<T, S> {
     }
 }
 
-impl<T: std::fmt::Debug, const S: usize> std::fmt::Debug for StackVec<T, S> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+impl<T: core::fmt::Debug, const S: usize> core::fmt::Debug for StackVec<T, S> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         writeln!(f, "[")?;
         for value in self.iter() {
             let fmt = format!("{value:#?}");
@@ -164,7 +183,11 @@ impl<T, const S: usize> Iterator for IntoIter<T, S> {
     fn next(&mut self) -> Option<Self::Item> {
         if self.next < self.array.len() {
             let item = unsafe {
-                (self.array.array.get_unchecked_mut(self.next).as_ptr() as *const T).read()
+                self.array
+                    .array
+                    .get_unchecked_mut(self.next)
+                    .as_ptr()
+                    .read()
             };
             self.next += 1;
             Some(item)
@@ -0,0 +1,718 @@
+//! Lightweight tasks, scheduled cooperatively by the vm
+//!
+//! Every task has its own stack, locals and frames, but they share the globals. Only one task runs
+//! at a time, the running one keeps going until it waits for a message with `receive`, gives way
+//! with `Task.yield` or finishes, then the next ready task runs, in the order they became ready.
+//!
+//! Tasks can only be suspended by the dispatch loop that runs the program, so they don't switch
+//! inside of native functions that call back into the vm (like the function passed to `List.map`)
+//!
+//! With the `std` feature, tasks can also wait for events, like a socket becoming readable or a
+//! timer expiring. Once no task can run, the vm waits until one of the events happens, so builtins
+//! that would block only suspend the task that called them.
+
+use alloc::{
+    boxed::Box,
+    collections::{BTreeMap, VecDeque},
+    rc::Rc,
+    vec::Vec,
+};
+use core::{cell::RefCell, mem};
+#[cfg(all(feature = "std", unix))]
+use std::os::unix::io::RawFd;
+#[cfg(feature = "std")]
+use std::time::{Duration, Instant};
+
+#[cfg(feature = "std")]
+use crate::Trace;
+use crate::{
+    error::{InterpretError, InterpretResult},
+    gc::GcRef,
+    raise_err, Bytecode, Fn, FnKind, Stack, Symbol, Tuple, Value, VirtualMachine, YexModule, NIL,
+};
+
+/// How many locals a new task starts with, they grow when needed
+const TASK_LOCALS: usize = 256;
+
+/// A function running in the vm, saved while it calls another function or while its task is
+/// suspended
+pub(crate) struct Frame {
+    pub bytecode: *const Bytecode,
+    pub upvalues: *const [Value],
+    pub ip: usize,
+    /// The locals saved by the frame
+    pub locals: usize,
+    /// The instructions where the errors raised inside each `try` block are rescued, with the
+    /// length of the stack when the block started
+    pub try_stack: Vec<(usize, usize)>,
+    /// Keeps the function of the frame alive, the code run by [`VirtualMachine::run`] is
+    /// borrowed instead
+    pub fun: Option<GcRef<Fn>>,
+}
+
+impl Frame {
+    /// Creates the frame of code borrowed by the caller, which must outlive the frame
+    pub fn borrowed(bytecode: &Bytecode, upvalues: &[Value]) -> Self {
+        Self {
+            bytecode,
+            upvalues,
+            ip: 0,
+            locals: 0,
+            try_stack: Vec::new(),
+            fun: None,
+        }
+    }
+
+    /// Creates the frame of a bytecode function
+    pub fn call(fun: GcRef<Fn>) -> Self {
+        let FnKind::Bytecode(bytecode) = &*fun.body else {
+            unreachable!()
+        };
+
+        Self {
+            bytecode,
+            upvalues: fun.upvalues.as_slice(),
+            ip: 0,
+            locals: 0,
+            try_stack: Vec::new(),
+            fun: Some(fun),
+        }
+    }
+}
+
+/// How a suspended task continues
+pub(crate) enum Resume {
+    /// The task didn't start yet, it calls the function with `()`
+    Start(Value),
+    /// The task waits for a message, which is pushed as the result of `receive`
+    Receive,
+    /// The task gave way to the others, `nil` is pushed as the result of `Task.yield`
+    Yield,
+    /// The program yields the value to the host, which isn't a task switch, the dispatch loop
+    /// returns and the program continues once the host resumes it
+    Host(Value),
+    /// The task that started the dispatch loop finished, it returns once no other task can run
+    Finished,
+    /// The task waits for an event
+    #[cfg(feature = "std")]
+    Wait(Wait),
+    /// The task waits for a promise to be resolved
+    Await,
+    /// The event or the promise the task waited for happened, the result is pushed, or raised if
+    /// it's an error
+    Woken(InterpretResult<Value>),
+}
+
+/// Something that happens outside of the vm
+#[cfg(feature = "std")]
+pub(crate) enum Event {
+    /// The file descriptor can be read without blocking
+    #[cfg(unix)]
+    Readable(RawFd),
+    /// The file descriptor can be written without blocking
+    #[cfg(unix)]
+    Writable(RawFd),
+    /// The time was reached
+    Timer(Instant),
+}
+
+/// An operation that would block until `event` happens
+#[cfg(feature = "std")]
+pub(crate) struct Wait {
+    event: Event,
+    /// Tries the operation again, returning `None` if it would still block
+    op: Box<dyn FnMut() -> Option<InterpretResult<Value>>>,
+    /// The input the result is recorded as, once the operation is done
+    input: Option<Input>,
+}
+
+/// A nondeterministic input given by an operation that would block, like the data read from a
+/// socket
+#[cfg(feature = "std")]
+pub(crate) struct Input {
+    kind: Symbol,
+    /// Turns the result of the operation into the value recorded in the trace
+    record: fn(&Value) -> Value,
+    /// Turns the recorded value back into the result, when the trace is replayed
+    replay: fn(Value) -> InterpretResult<Value>,
+}
+
+#[cfg(feature = "std")]
+impl Input {
+    /// Creates an input whose result is recorded as it is
+    pub fn new(kind: &str) -> Self {
+        Self::mapped(kind, Value::clone, Ok)
+    }
+
+    /// Creates an input whose result can't be recorded as it is, like a socket
+    pub fn mapped(
+        kind: &str,
+        record: fn(&Value) -> Value,
+        replay: fn(Value) -> InterpretResult<Value>,
+    ) -> Self {
+        Self {
+            kind: Symbol::new(kind),
+            record,
+            replay,
+        }
+    }
+}
+
+/// The result of a task started by `Task.async`
+#[derive(Default)]
+pub(crate) struct Promise {
+    pub value: RefCell<Option<Value>>,
+    /// The tasks waiting for the value
+    waiting: RefCell<Vec<usize>>,
+}
+
+/// The state of a task that isn't running
+struct Saved {
+    stack: Stack,
+    locals: Vec<Value>,
+    used_locals: usize,
+    frames: Vec<Frame>,
+    /// The frame that was running, `None` if the task didn't start yet
+    frame: Option<Frame>,
+    rescued: Option<InterpretError>,
+    resume: Resume,
+}
+
+/// The tasks of a vm
+#[derive(Default)]
+pub(crate) struct Scheduler {
+    /// The id of the running task, the task that runs the program is 0
+    pub current: usize,
+    last_id: usize,
+    /// The tasks that aren't running, by their ids
+    saved: BTreeMap<usize, Box<Saved>>,
+    /// The saved tasks that can run, in the order they became ready
+    ready: VecDeque<usize>,
+    /// The messages sent to each task that didn't finish yet
+    mailboxes: BTreeMap<usize, VecDeque<Value>>,
+    /// The saved tasks waiting for events
+    #[cfg(feature = "std")]
+    waiting: Vec<usize>,
+    /// The promises resolved by each task once it finishes
+    promises: BTreeMap<usize, Rc<Promise>>,
+    /// Set by the native functions that suspend the running task
+    pub suspend: Option<Resume>,
+}
+
+impl Scheduler {
+    /// Creates a task that calls `fun`, returning its id
+    pub fn spawn(&mut self, fun: Value) -> usize {
+        self.last_id += 1;
+        let id = self.last_id;
+
+        let saved = Saved {
+            stack: Stack::new(),
+            locals: vec![NIL; TASK_LOCALS],
+            used_locals: 0,
+            frames: Vec::new(),
+            frame: None,
+            rescued: None,
+            resume: Resume::Start(fun),
+        };
+        self.saved.insert(id, Box::new(saved));
+        self.mailboxes.insert(id, VecDeque::new());
+        self.ready.push_back(id);
+
+        id
+    }
+
+    /// Sends `msg` to the task `id`, waking it up if it's waiting for a message, messages sent to
+    /// tasks that finished are dropped
+    pub fn send(&mut self, id: usize, msg: Value) {
+        let mailbox = match self.mailboxes.get_mut(&id) {
+            Some(mailbox) => mailbox,
+            // the task that runs the program gets a mailbox once it's needed
+            None if id == 0 => self.mailboxes.entry(id).or_default(),
+            None => return,
+        };
+        mailbox.push_back(msg);
+
+        if let Some(saved) = self.saved.get(&id) {
+            if matches!(saved.resume, Resume::Receive) && !self.ready.contains(&id) {
+                self.ready.push_back(id);
+            }
+        }
+    }
+
+    /// Takes the oldest message sent to the running task
+    pub fn receive(&mut self) -> Option<Value> {
+        self.mailboxes.get_mut(&self.current)?.pop_front()
+    }
+
+    /// Creates a task that calls `fun`, returning the promise it resolves with the result
+    pub fn spawn_async(&mut self, fun: Value) -> Rc<Promise> {
+        let promise = Rc::new(Promise::default());
+        let id = self.spawn(fun);
+        self.promises.insert(id, promise.clone());
+        promise
+    }
+
+    /// Makes the running task wait for `promise`, which isn't resolved yet
+    pub fn wait_for(&mut self, promise: &Promise) {
+        promise.waiting.borrow_mut().push(self.current);
+        self.suspend = Some(Resume::Await);
+    }
+
+    /// Resolves the promise of the task `id` with `value`, waking up the tasks waiting for it
+    fn resolve(&mut self, id: usize, value: Value) {
+        let Some(promise) = self.promises.remove(&id) else {
+            return;
+        };
+
+        for id in promise.waiting.take() {
+            self.wake(id, Ok(value.clone()));
+        }
+        *promise.value.borrow_mut() = Some(value);
+    }
+
+    /// Wakes up the saved task `id`, which continues with `result`
+    fn wake(&mut self, id: usize, result: InterpretResult<Value>) {
+        if let Some(saved) = self.saved.get_mut(&id) {
+            saved.resume = Resume::Woken(result);
+            self.ready.push_back(id);
+        }
+    }
+}
+
+/// Returns the handle of the task `id`, a tagged value so handles are equal to each other
+pub(crate) fn handle(id: usize) -> Value {
+    let ty = GcRef::new(YexModule::default());
+    let tup = Tuple::from(vec![Value::Int(id as i64)]);
+    Value::tagged(ty, Symbol::from("Task.id"), tup)
+}
+
+/// Returns the id of a task handle
+pub(crate) fn id_of(value: &Value) -> InterpretResult<usize> {
+    match value {
+        Value::Tagged(tagged) if tagged.1 == Symbol::from("Task.id") => match tagged.2 .0.first() {
+            Some(Value::Int(id)) => Ok(*id as usize),
+            _ => Err(raise_err!(TypeError, "Expected a task, found '{}'", value)),
+        },
+        other => Err(raise_err!(TypeError, "Expected a task, found '{}'", other)),
+    }
+}
+
+/// What the dispatch loop does after switching tasks
+pub(crate) enum Switched {
+    /// Continues running `frame`, past the instruction that suspended it if `advance` is true
+    Run { frame: Frame, advance: bool },
+    /// Raises `error` from the instruction that suspended `frame`
+    Raise { frame: Frame, error: InterpretError },
+    /// Returns from the dispatch loop, the task that started it finished
+    Return,
+}
+
+impl VirtualMachine {
+    /// Whether the running task can be suspended by the native function that's running, which
+    /// is only possible if it was called by the dispatch loop that runs the program
+    pub(crate) fn can_suspend(&self) -> bool {
+        self.depth == 1 && self.natives == 1
+    }
+
+    /// Saves the running task, which continues with `resume`, and runs the next ready task
+    ///
+    /// `entering` is the task that started the dispatch loop, if no task can run it returns if it
+    /// finished, otherwise every task is waiting for a message, so it raises a `DeadlockError`
+    pub(crate) fn switch_task(
+        &mut self,
+        frame: Frame,
+        resume: Resume,
+        entering: usize,
+    ) -> InterpretResult<Switched> {
+        if !self.scheduler.ready.is_empty() || self.waits() {
+            // the other tasks run first
+        } else if matches!(resume, Resume::Finished) {
+            return Ok(Switched::Return);
+        } else if matches!(resume, Resume::Yield) {
+            self.push(NIL);
+            return Ok(Switched::Run {
+                frame,
+                advance: true,
+            });
+        }
+
+        let current = self.scheduler.current;
+        match resume {
+            Resume::Yield => self.scheduler.ready.push_back(current),
+            #[cfg(feature = "std")]
+            Resume::Wait(_) => self.scheduler.waiting.push(current),
+            _ => (),
+        }
+        self.save_task(Some(frame), resume);
+
+        match self.next_task() {
+            Ok(Some(next)) => self.resume_task(next, entering),
+            Ok(None) => self.deadlock(entering),
+            Err(e) => self.stop(entering, e),
+        }
+    }
+
+    /// Ends the running task, which isn't the one that started the dispatch loop, and runs the
+    /// next ready task
+    pub(crate) fn finish_task(&mut self, entering: usize) -> InterpretResult<Switched> {
+        let current = self.scheduler.current;
+        self.scheduler.mailboxes.remove(&current);
+        if self.scheduler.promises.contains_key(&current) {
+            let value = self.pop();
+            self.scheduler.resolve(current, value);
+        }
+
+        match self.next_task() {
+            Ok(Some(next)) => self.resume_task(next, entering),
+            Ok(None) => self.deadlock(entering),
+            Err(e) => self.stop(entering, e),
+        }
+    }
+
+    /// Restores the task that started the dispatch loop after another task raised an error that
+    /// wasn't rescued, returning its frame, the task that raised it is dropped
+    pub(crate) fn fail_task(&mut self, entering: usize) -> Option<Frame> {
+        if self.scheduler.current == entering {
+            return None;
+        }
+
+        self.scheduler.mailboxes.remove(&self.scheduler.current);
+        self.scheduler.promises.remove(&self.scheduler.current);
+        self.scheduler.ready.retain(|id| *id != entering);
+        #[cfg(feature = "std")]
+        self.scheduler.waiting.retain(|id| *id != entering);
+        self.restore_task(entering).0
+    }
+
+    /// No task is ready, so the task that started the loop returns if it finished, otherwise it
+    /// raises a `DeadlockError` where it waits
+    fn deadlock(&mut self, entering: usize) -> InterpretResult<Switched> {
+        let (frame, resume) = self.restore_task(entering);
+        match resume {
+            Resume::Finished => Ok(Switched::Return),
+            _ => Ok(Switched::Raise {
+                frame: frame.unwrap(),
+                error: raise_err!(
+                    DeadlockError,
+                    "Every task is waiting for a message or a promise"
+                ),
+            }),
+        }
+    }
+
+    /// Stops the dispatch loop because of an error that can't be rescued, like a timeout, the
+    /// task that started it is restored, so the error is returned from it
+    fn stop(&mut self, entering: usize, e: InterpretError) -> InterpretResult<Switched> {
+        let (frame, resume) = self.restore_task(entering);
+        if let (Some(frame), false) = (frame, matches!(resume, Resume::Finished)) {
+            self.unwind(frame, 0);
+        }
+        Err(e)
+    }
+
+    /// Whether any task waits for an event
+    fn waits(&self) -> bool {
+        #[cfg(feature = "std")]
+        return !self.scheduler.waiting.is_empty();
+        #[cfg(not(feature = "std"))]
+        return false;
+    }
+
+    /// Takes the next ready task, if no task is ready but some of them wait for events, it waits
+    /// until one of them is woken up
+    fn next_task(&mut self) -> InterpretResult<Option<usize>> {
+        #[cfg(feature = "std")]
+        if self.waits() {
+            self.poll_events(false);
+            while self.scheduler.ready.is_empty() && self.waits() {
+                self.check_deadline()?;
+                self.poll_events(true);
+            }
+        }
+
+        Ok(self.scheduler.ready.pop_front())
+    }
+
+    /// Saves the state of the running task
+    fn save_task(&mut self, frame: Option<Frame>, resume: Resume) {
+        let saved = Saved {
+            stack: mem::take(&mut self.stack),
+            locals: mem::take(&mut self.locals),
+            used_locals: self.used_locals,
+            frames: mem::take(&mut self.frames),
+            frame,
+            rescued: self.rescued.take(),
+            resume,
+        };
+
+        self.used_locals = 0;
+        self.scheduler
+            .saved
+            .insert(self.scheduler.current, Box::new(saved));
+    }
+
+    /// Makes the saved task `id` the running one, returning its frame and how it continues
+    fn restore_task(&mut self, id: usize) -> (Option<Frame>, Resume) {
+        let saved = *self.scheduler.saved.remove(&id).unwrap();
+
+        self.stack = saved.stack;
+        self.locals = saved.locals;
+        self.used_locals = saved.used_locals;
+        self.frames = saved.frames;
+        self.rescued = saved.rescued;
+        self.scheduler.current = id;
+
+        (saved.frame, saved.resume)
+    }
+
+    /// Runs the saved task `id`
+    fn resume_task(&mut self, id: usize, entering: usize) -> InterpretResult<Switched> {
+        let (frame, resume) = self.restore_task(id);
+
+        match resume {
+            Resume::Start(fun) => {
+                self.push(Vec::new().into());
+                self.push(fun);
+
+                match self.prepare_call(1)? {
+                    crate::Callee::Enter(fun) => {
+                        self.used_locals += 1;
+                        Ok(Switched::Run {
+                            frame: Frame::call(fun),
+                            advance: false,
+                        })
+                    }
+                    // the task finished right away, like a partial application
+                    _ => self.finish_task(entering),
+                }
+            }
+            Resume::Receive => {
+                let msg = self.scheduler.receive().unwrap_or(NIL);
+                self.push(msg);
+                Ok(Switched::Run {
+                    frame: frame.unwrap(),
+                    advance: true,
+                })
+            }
+            Resume::Yield => {
+                self.push(NIL);
+                Ok(Switched::Run {
+                    frame: frame.unwrap(),
+                    advance: true,
+                })
+            }
+            Resume::Woken(Ok(value)) => {
+                self.push(value);
+                Ok(Switched::Run {
+                    frame: frame.unwrap(),
+                    advance: true,
+                })
+            }
+            Resume::Woken(Err(error)) => Ok(Switched::Raise {
+                frame: frame.unwrap(),
+                error,
+            }),
+            Resume::Finished => Ok(Switched::Return),
+            // the waiting tasks aren't ready until they're woken up, and yielding to the host
+            // doesn't save the task
+            Resume::Await | Resume::Host(_) => unreachable!(),
+            #[cfg(feature = "std")]
+            Resume::Wait(_) => unreachable!(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl VirtualMachine {
+    /// Runs `op`, which returns `None` if it would block, until it's done
+    ///
+    /// While it would block, the running task waits for `event` and the other tasks run, if the
+    /// task can't be suspended the whole vm waits for it instead
+    pub(crate) fn wait(
+        &mut self,
+        event: Event,
+        op: impl FnMut() -> Option<InterpretResult<Value>> + 'static,
+    ) -> InterpretResult<Value> {
+        self.wait_until_done(event, op, None)
+    }
+
+    /// Like [`Self::wait`], but the result is a nondeterministic `input`: it's recorded once `op`
+    /// is done, and taken from the trace without running `op` when the trace is replayed
+    pub(crate) fn wait_input(
+        &mut self,
+        input: Input,
+        event: Event,
+        op: impl FnMut() -> Option<InterpretResult<Value>> + 'static,
+    ) -> InterpretResult<Value> {
+        if matches!(self.trace, Some(Trace::Replay { .. })) {
+            let recorded = self.nondeterministic(input.kind, || {
+                unreachable!("replayed inputs aren't computed")
+            })?;
+            return (input.replay)(recorded);
+        }
+
+        self.wait_until_done(event, op, Some(input))
+    }
+
+    fn wait_until_done(
+        &mut self,
+        event: Event,
+        mut op: impl FnMut() -> Option<InterpretResult<Value>> + 'static,
+        input: Option<Input>,
+    ) -> InterpretResult<Value> {
+        if let Some(result) = op() {
+            return self.record_input(input.as_ref(), result);
+        }
+
+        if self.can_suspend() {
+            // the result is pushed in place of the placeholder once the task is woken up
+            self.scheduler.suspend = Some(Resume::Wait(Wait {
+                event,
+                op: Box::new(op),
+                input,
+            }));
+            return Ok(NIL);
+        }
+
+        loop {
+            poll(&[&event], None);
+            if let Some(result) = op() {
+                return self.record_input(input.as_ref(), result);
+            }
+        }
+    }
+
+    /// Records the result of an operation that's done, if it's an input
+    fn record_input(
+        &mut self,
+        input: Option<&Input>,
+        result: InterpretResult<Value>,
+    ) -> InterpretResult<Value> {
+        let (Some(input), Ok(value)) = (input, &result) else {
+            return result;
+        };
+
+        self.nondeterministic(input.kind, || Ok((input.record)(value)))?;
+        result
+    }
+
+    /// Wakes up the tasks whose operations are done, waiting for their events first if `block` is
+    /// true, but not past the vm deadline
+    fn poll_events(&mut self, block: bool) {
+        let timeout = match (block, self.deadline) {
+            (false, _) => Some(Duration::ZERO),
+            (true, deadline) => deadline.map(|at| at.saturating_duration_since(Instant::now())),
+        };
+
+        let waiting = mem::take(&mut self.scheduler.waiting);
+        let happened = {
+            let events: Vec<&Event> = waiting
+                .iter()
+                .map(|id| match &self.scheduler.saved[id].resume {
+                    Resume::Wait(wait) => &wait.event,
+                    _ => unreachable!(),
+                })
+                .collect();
+            poll(&events, timeout)
+        };
+
+        for (id, happened) in waiting.into_iter().zip(happened) {
+            let done = match &mut self.scheduler.saved.get_mut(&id).unwrap().resume {
+                Resume::Wait(wait) if happened => {
+                    (wait.op)().map(|result| (wait.input.take(), result))
+                }
+                _ => None,
+            };
+
+            match done {
+                Some((input, result)) => {
+                    let result = self.record_input(input.as_ref(), result);
+                    self.scheduler.wake(id, result);
+                }
+                None => self.scheduler.waiting.push(id),
+            }
+        }
+    }
+}
+
+/// Waits until one of `events` happens, or until `timeout` passes (forever if it's `None`),
+/// returning which of them happened
+#[cfg(feature = "std")]
+fn poll(events: &[&Event], timeout: Option<Duration>) -> Vec<bool> {
+    let now = Instant::now();
+    let timers = events.iter().filter_map(|event| match event {
+        Event::Timer(at) => Some(at.saturating_duration_since(now)),
+        #[cfg(unix)]
+        _ => None,
+    });
+    let timeout = timers.chain(timeout).min();
+
+    #[cfg(unix)]
+    let ready = {
+        let mut fds: Vec<libc::pollfd> = events
+            .iter()
+            .filter_map(|event| match event {
+                Event::Readable(fd) => Some((*fd, libc::POLLIN)),
+                Event::Writable(fd) => Some((*fd, libc::POLLOUT)),
+                Event::Timer(_) => None,
+            })
+            .map(|(fd, events)| libc::pollfd {
+                fd,
+                events,
+                revents: 0,
+            })
+            .collect();
+
+        // rounded up, so the timers aren't polled again before they expire
+        let ms = timeout.map_or(-1, |timeout| {
+            timeout.as_nanos().div_ceil(1_000_000).min(i32::MAX as u128) as i32
+        });
+        // an interrupted poll is the same as a poll where nothing happened
+        unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as libc::nfds_t, ms) };
+
+        fds.into_iter().map(|fd| fd.revents != 0)
+    };
+    #[cfg(not(unix))]
+    if let Some(timeout) = timeout {
+        std::thread::sleep(timeout);
+    }
+
+    let now = Instant::now();
+    #[cfg(unix)]
+    let mut ready = ready;
+    events
+        .iter()
+        .map(|event| match event {
+            Event::Timer(at) => now >= *at,
+            #[cfg(unix)]
+            _ => ready.next().unwrap_or(false),
+        })
+        .collect()
+}
+
+#[test]
+#[cfg(feature = "std")]
+fn sleep_times_out() {
+    use crate::{OpCode, OpCodeMetadata};
+
+    let mut vm = VirtualMachine::default();
+    vm.set_consts(vec![Value::Int(10_000)]);
+    let bytecode: Bytecode = [
+        OpCode::Push(0),
+        OpCode::Loag("Task".into()),
+        OpCode::Ref("sleep".into()),
+        OpCode::Call(1),
+        OpCode::Pop,
+    ]
+    .into_iter()
+    .map(|op| OpCodeMetadata::new(1, 1, op))
+    .collect();
+
+    let start = Instant::now();
+    let e = vm
+        .run_with_timeout(&bytecode, Duration::from_millis(50))
+        .unwrap_err();
+
+    assert_eq!(e.err, Symbol::from("TimeoutError"));
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
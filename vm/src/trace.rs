@@ -0,0 +1,289 @@
+use std::{
+    fmt::Write as _,
+    io::Write,
+    iter::Peekable,
+    str::{Chars, FromStr},
+};
+
+use crate::{
+    error::InterpretResult, gc::GcRef, literal::bytes::Bytes, raise, raise_err, List, Symbol,
+    Tuple, Value,
+};
+
+/// Records or replays the nondeterministic inputs of a program (like the lines read by `input`)
+///
+/// Each entry is written as a line with the entry kind followed by the value, using the yex
+/// literal syntax, so traces can be inspected and edited by hand:
+///
+/// ```text
+/// input "John"
+/// input "18"
+/// ```
+pub enum Trace {
+    /// Every input is appended to the writer
+    Record(Box<dyn Write>),
+    /// Inputs are read from the trace instead of being computed
+    Replay {
+        /// The recorded entries
+        entries: Vec<(Symbol, Value)>,
+        /// The next entry to be replayed
+        cursor: usize,
+    },
+}
+
+impl Trace {
+    /// Creates a trace that writes every input to `out`
+    pub fn record<W: Write + 'static>(out: W) -> Self {
+        Self::Record(Box::new(out))
+    }
+
+    /// Parses a recorded trace
+    pub fn replay(trace: &str) -> InterpretResult<Self> {
+        let mut entries = vec![];
+
+        for (number, line) in trace.lines().enumerate() {
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let (kind, value) = line.split_once(' ').unwrap_or((line, ""));
+            let mut decoder = Decoder {
+                chars: value.chars().peekable(),
+            };
+
+            let value = decoder
+                .value()
+                .map_err(|msg| raise_err!(TraceError, "line {}: {}", number + 1, msg))?;
+            entries.push((Symbol::new(kind), value));
+        }
+
+        Ok(Self::Replay { entries, cursor: 0 })
+    }
+
+    /// Records the value of an input, or returns the recorded one when replaying
+    pub(crate) fn entry(
+        &mut self,
+        kind: Symbol,
+        compute: impl FnOnce() -> InterpretResult<Value>,
+    ) -> InterpretResult<Value> {
+        match self {
+            Self::Record(out) => {
+                let value = compute()?;
+
+                let mut line = kind.to_string();
+                line.push(' ');
+                encode(&value, &mut line)?;
+
+                writeln!(out, "{}", line)
+                    .and_then(|_| out.flush())
+                    .map_err(|e| raise_err!(TraceError, "Failed to record the trace: {}", e))?;
+                Ok(value)
+            }
+            Self::Replay { entries, cursor } => match entries.get(*cursor) {
+                Some((recorded, value)) if *recorded == kind => {
+                    *cursor += 1;
+                    Ok(value.clone())
+                }
+                Some((recorded, _)) => raise!(
+                    TraceError,
+                    "Expected a '{}' entry, but the trace has '{}'",
+                    kind,
+                    recorded
+                ),
+                None => raise!(TraceError, "The trace has no more '{}' entries", kind),
+            },
+        }
+    }
+}
+
+fn encode(value: &Value, out: &mut String) -> InterpretResult<()> {
+    match value {
+        Value::Nil => out.push_str("nil"),
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        // `{:?}` keeps the `.0` of round floats, so they aren't replayed as integers
+        Value::Num(n) => write!(out, "{:?}", n).unwrap(),
+        Value::Int(n) => write!(out, "{}", n).unwrap(),
+        Value::Sym(s) => write!(out, ":{}", s.0).unwrap(),
+        Value::Str(s) => write!(out, "{:?}", s.as_str()).unwrap(),
+        Value::Bytes(bytes) => write!(out, "{}", bytes).unwrap(),
+        Value::List(xs) => {
+            out.push('[');
+            for (i, x) in xs.iter().enumerate() {
+                if i != 0 {
+                    out.push_str(", ");
+                }
+                encode(&x, out)?;
+            }
+            out.push(']');
+        }
+        Value::Tuple(tup) => {
+            out.push('(');
+            for (i, x) in tup.0.iter().enumerate() {
+                if i != 0 {
+                    out.push_str(", ");
+                }
+                encode(x, out)?;
+            }
+            out.push(')');
+        }
+        other => return raise!(TraceError, "Can't record the value '{}'", other),
+    }
+
+    Ok(())
+}
+
+struct Decoder<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Decoder<'_> {
+    fn skip_spaces(&mut self) {
+        while self.chars.next_if(|c| c.is_whitespace()).is_some() {}
+    }
+
+    fn word(&mut self) -> String {
+        let mut word = String::new();
+        while let Some(c) = self
+            .chars
+            .next_if(|c| !c.is_whitespace() && !matches!(c, ',' | ']' | ')'))
+        {
+            word.push(c);
+        }
+        word
+    }
+
+    fn value(&mut self) -> Result<Value, String> {
+        self.skip_spaces();
+
+        let mut ahead = self.chars.clone();
+        if ahead.next() == Some('b') && ahead.next() == Some('"') {
+            self.chars.next();
+            return self.bytes();
+        }
+
+        match self.chars.peek() {
+            Some('"') => self.string(),
+            Some('[') => {
+                self.chars.next();
+                let xs = self.sequence(']')?;
+                Ok(xs.into_iter().rev().collect::<List>().into())
+            }
+            Some('(') => {
+                self.chars.next();
+                Ok(Tuple::from(self.sequence(')')?).into())
+            }
+            Some(':') => {
+                self.chars.next();
+                Ok(Symbol::new(self.word()).into())
+            }
+            Some(_) => match self.word().as_str() {
+                "nil" => Ok(Value::Nil),
+                "true" => Ok(Value::Bool(true)),
+                "false" => Ok(Value::Bool(false)),
+                word => i64::from_str(word)
+                    .map(Value::Int)
+                    .or_else(|_| f64::from_str(word).map(Value::Num))
+                    .map_err(|_| format!("Invalid value '{}'", word)),
+            },
+            None => Err("Expected a value".to_string()),
+        }
+    }
+
+    fn sequence(&mut self, end: char) -> Result<Vec<Value>, String> {
+        let mut items = vec![];
+
+        loop {
+            self.skip_spaces();
+            if self.chars.next_if_eq(&end).is_some() {
+                return Ok(items);
+            }
+
+            items.push(self.value()?);
+
+            self.skip_spaces();
+            match self.chars.next() {
+                Some(',') => continue,
+                Some(c) if c == end => return Ok(items),
+                _ => return Err(format!("Expected ',' or '{}'", end)),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<Value, String> {
+        self.chars.next();
+
+        let mut str = String::new();
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(Value::Str(GcRef::new(str))),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => str.push('\n'),
+                    Some('r') => str.push('\r'),
+                    Some('t') => str.push('\t'),
+                    Some('0') => str.push('\0'),
+                    Some('u') => str.push(self.unicode_escape()?),
+                    Some(c) => str.push(c),
+                    None => return Err("Unterminated string".to_string()),
+                },
+                Some(c) => str.push(c),
+                None => return Err("Unterminated string".to_string()),
+            }
+        }
+    }
+
+    /// Parses the `"..."` part of a `b"..."` literal, written like the bytes are displayed
+    fn bytes(&mut self) -> Result<Value, String> {
+        self.chars.next();
+
+        let mut bytes = vec![];
+        loop {
+            match self.chars.next() {
+                Some('"') => return Ok(Bytes::from(bytes).into()),
+                Some('\\') => match self.chars.next() {
+                    Some('n') => bytes.push(b'\n'),
+                    Some('r') => bytes.push(b'\r'),
+                    Some('t') => bytes.push(b'\t'),
+                    Some('x') => {
+                        let code: String = self.chars.by_ref().take(2).collect();
+                        let byte = u8::from_str_radix(&code, 16)
+                            .map_err(|_| format!("Invalid escape '\\x{}'", code))?;
+                        bytes.push(byte);
+                    }
+                    Some(c) if c.is_ascii() => bytes.push(c as u8),
+                    _ => return Err("Invalid bytes".to_string()),
+                },
+                Some(c) if c.is_ascii() => bytes.push(c as u8),
+                Some(_) => return Err("Invalid bytes".to_string()),
+                None => return Err("Unterminated bytes".to_string()),
+            }
+        }
+    }
+
+    /// Parses the `{XXXX}` part of a `\u{XXXX}` escape
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let mut code = String::new();
+        self.chars.next_if_eq(&'{');
+        while let Some(c) = self.chars.next_if(|c| *c != '}') {
+            code.push(c);
+        }
+        self.chars.next();
+
+        u32::from_str_radix(&code, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| format!("Invalid escape '\\u{{{}}}'", code))
+    }
+}
+
+#[test]
+fn bytes_roundtrip() {
+    let bytes = Value::from(Bytes::from(b"a \"b\"\n\\\x00\xff".to_vec()));
+
+    let mut line = "socketRead ".to_string();
+    encode(&bytes, &mut line).unwrap();
+
+    let Trace::Replay { entries, .. } = Trace::replay(&line).unwrap() else {
+        unreachable!()
+    };
+    assert_eq!(entries[0].1, bytes);
+}
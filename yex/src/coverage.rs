@@ -0,0 +1,125 @@
+use crate::{file_dir, module_file};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fs,
+};
+use vm::coverage::Coverage;
+
+/// The lines of a source file that have code, and the ones of them that ran
+struct Lines {
+    code: BTreeSet<usize>,
+    ran: BTreeSet<usize>,
+}
+
+/// The line coverage of the files that ran, merged from every vm that ran them, which are
+/// printed by `--coverage`
+#[derive(Default)]
+pub struct CoverageReport {
+    files: BTreeMap<String, Lines>,
+}
+
+impl CoverageReport {
+    /// Adds the lines that ran in a vm that ran `file`, the modules it imported are looked for
+    /// next to it
+    pub fn add(&mut self, file: &str, coverage: &Coverage) {
+        for module in coverage.modules() {
+            let path = match module {
+                Some(name) => module_file(&file_dir(file), name).display().to_string(),
+                None => file.to_string(),
+            };
+
+            let lines = self.files.entry(path).or_insert_with_key(|path| Lines {
+                code: code_lines(path),
+                ran: BTreeSet::new(),
+            });
+            lines.ran.extend(coverage.lines(module));
+        }
+    }
+
+    /// Prints the percentage of the lines with code that ran in each file, and the lines that
+    /// didn't
+    pub fn print(&self) {
+        let (mut ran, mut code) = (0, 0);
+
+        eprintln!("\ncoverage:");
+        for (file, lines) in &self.files {
+            // the compiled files have no source to compare with
+            if lines.code.is_empty() {
+                continue;
+            }
+
+            let covered = lines.code.intersection(&lines.ran).count();
+            eprint!("  {} ... {}", file, percent(covered, lines.code.len()));
+
+            let missed = missed(&lines.code, &lines.ran);
+            if !missed.is_empty() {
+                eprint!(", missed {}", missed.join(", "));
+            }
+            eprintln!();
+
+            ran += covered;
+            code += lines.code.len();
+        }
+        eprintln!("  total ... {}", percent(ran, code));
+    }
+}
+
+fn percent(covered: usize, total: usize) -> String {
+    let percent = if total == 0 {
+        100.0
+    } else {
+        covered as f64 * 100.0 / total as f64
+    };
+    format!("{:.1}% ({} of {} lines)", percent, covered, total)
+}
+
+/// The lines with code that didn't run, the lines that follow each other (ignoring the lines
+/// without code between them) are joined in ranges like `8-12`
+fn missed(code: &BTreeSet<usize>, ran: &BTreeSet<usize>) -> Vec<String> {
+    let mut ranges: Vec<(usize, usize)> = vec![];
+    let mut previous_missed = false;
+
+    for &line in code {
+        let missed = !ran.contains(&line);
+        match ranges.last_mut() {
+            Some((_, end)) if missed && previous_missed => *end = line,
+            _ if missed => ranges.push((line, line)),
+            _ => (),
+        }
+        previous_missed = missed;
+    }
+
+    ranges
+        .into_iter()
+        .map(|(start, end)| {
+            if start == end {
+                start.to_string()
+            } else {
+                format!("{}-{}", start, end)
+            }
+        })
+        .collect()
+}
+
+/// The lines of a source file with code, not counting the `test` blocks, since the report is
+/// about the code they test
+fn code_lines(file: &str) -> BTreeSet<usize> {
+    let Ok(source) = fs::read_to_string(file) else {
+        return BTreeSet::new();
+    };
+
+    match front::compile(source.as_str()) {
+        Ok((bt, ct, _)) => vm::coverage::lines(&bt, &ct),
+        Err(..) => BTreeSet::new(),
+    }
+}
+
+#[test]
+fn missed_ranges() {
+    let code = BTreeSet::from([1, 2, 4, 5, 8, 10]);
+    let ran = BTreeSet::from([1, 5]);
+
+    // 8 and 10 are joined, the line 9 has no code
+    assert_eq!(missed(&code, &ran), vec!["2-4", "8-10"]);
+    assert!(missed(&code, &code).is_empty());
+}
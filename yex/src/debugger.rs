@@ -0,0 +1,224 @@
+use std::{
+    fs,
+    io::{self, BufRead, Write},
+    path::{Path, PathBuf},
+    process::exit,
+};
+use vm::{
+    debug::{self, Location, Pause, Step},
+    Symbol, Value, VirtualMachine,
+};
+
+const HELP: &str = "\
+break [file:]line   stops the program before it runs the line
+delete [file:]line  removes a breakpoint
+continue            runs until a breakpoint
+step                runs the next line, stopping inside the functions it calls
+next                runs the next line of this function
+finish              runs until this function returns
+locals              shows the locals of this function
+stack               shows the values being evaluated
+backtrace           shows the running functions
+print name          shows the value of a local, or of a global
+list                shows the lines around this one
+quit                stops the program
+an empty line runs the last command again";
+
+/// The `yex debug` command line, reads the commands from stdin every time the program stops
+pub struct Debugger {
+    /// The script being debugged, the other files are imported modules
+    script: PathBuf,
+    breakpoints: Vec<Location>,
+    last: String,
+}
+
+impl Debugger {
+    /// Creates the debugger of `script`, which doesn't have any breakpoints yet
+    pub fn new(script: &str) -> Self {
+        Self {
+            script: PathBuf::from(script),
+            breakpoints: vec![],
+            last: String::new(),
+        }
+    }
+
+    /// The file of the code at `at`, imported modules are read from the script directory, like
+    /// `module_loader` does
+    fn file(&self, at: Location) -> PathBuf {
+        match at.module {
+            Some(name) => {
+                let mut chars = name.as_str().chars();
+                let file = match chars.next() {
+                    Some(c) => format!("{}{}.yex", c.to_lowercase(), chars.as_str()),
+                    None => String::from(".yex"),
+                };
+                crate::file_dir(&self.script.to_string_lossy()).join(file)
+            }
+            None => self.script.clone(),
+        }
+    }
+
+    /// Parses a breakpoint written as `line` or `file:line`, the file is the script or one of the
+    /// modules it imports
+    fn location(&self, spec: &str) -> Option<Location> {
+        let (file, line) = match spec.rsplit_once(':') {
+            Some((file, line)) => (Some(file), line),
+            None => (None, spec),
+        };
+        let line = line.parse().ok()?;
+
+        let module = match file.map(Path::new) {
+            Some(file) if file.file_stem() != self.script.file_stem() => {
+                let stem = file.file_stem()?.to_str()?;
+                let mut chars = stem.chars();
+                let first = chars.next()?;
+                Some(Symbol::new(format!(
+                    "{}{}",
+                    first.to_uppercase(),
+                    chars.as_str()
+                )))
+            }
+            _ => None,
+        };
+
+        Some(Location { module, line })
+    }
+
+    fn show(&self, at: Location) -> String {
+        format!("{}:{}", self.file(at).display(), at.line)
+    }
+
+    /// Prints the lines of the source from `from` to `to`, marking the line `at`
+    fn list(&self, at: Location, from: usize, to: usize) {
+        let source = match fs::read_to_string(self.file(at)) {
+            Ok(source) => source,
+            Err(_) => return println!("can't read {}", self.file(at).display()),
+        };
+
+        for (n, text) in source.lines().enumerate().skip(from.max(1) - 1) {
+            let n = n + 1;
+            if n > to {
+                break;
+            }
+            let mark = if n == at.line { ">" } else { " " };
+            println!("{} {:4} | {}", mark, n, text);
+        }
+    }
+
+    /// Runs a command, returning how the program continues, or `None` if it stays stopped
+    fn run(&mut self, vm: &VirtualMachine, pause: &Pause<'_>, command: &str) -> Option<Step> {
+        let (command, arg) = match command.split_once(' ') {
+            Some((command, arg)) => (command, arg.trim()),
+            None => (command, ""),
+        };
+
+        match command {
+            "c" | "continue" => return Some(Step::Continue),
+            "s" | "step" => return Some(Step::Into),
+            "n" | "next" => return Some(Step::Over),
+            "f" | "finish" => return Some(Step::Out),
+            "q" | "quit" => exit(0),
+            "b" | "break" => match self.location(arg) {
+                Some(at) => {
+                    if !self.breakpoints.contains(&at) {
+                        self.breakpoints.push(at);
+                    }
+                    println!("breakpoint at {}", self.show(at));
+                }
+                None => println!("expected a line, like 'break 10' or 'break foo.yex:10'"),
+            },
+            "d" | "delete" => match self.location(arg) {
+                Some(at) if self.breakpoints.contains(&at) => {
+                    self.breakpoints.retain(|bp| *bp != at);
+                    println!("removed the breakpoint at {}", self.show(at));
+                }
+                Some(at) => println!("there's no breakpoint at {}", self.show(at)),
+                None => println!("expected a line, like 'delete 10' or 'delete foo.yex:10'"),
+            },
+            "locals" => {
+                for (i, value) in pause.locals.iter().enumerate() {
+                    // a slot holds a local after another one went out of scope
+                    let names: Vec<_> = pause
+                        .names
+                        .iter()
+                        .filter(|(slot, _)| *slot == i)
+                        .map(|(_, name)| name.as_str())
+                        .collect();
+                    match names.as_slice() {
+                        [] => println!("${} = {}", i, vm::pretty(value)),
+                        names => println!("{} = {}", names.join("/"), vm::pretty(value)),
+                    }
+                }
+            }
+            "stack" => {
+                for value in pause.stack.iter().rev() {
+                    println!("{}", vm::pretty(value));
+                }
+            }
+            "bt" | "backtrace" => {
+                for call in pause.backtrace.iter().rev() {
+                    let name = crate::function_name(call.name, call.top_level, call.at.module);
+                    println!("{} at {}", name, self.show(call.at));
+                }
+            }
+            "p" | "print" => match local(pause, arg).or_else(|| vm.get_global(arg)) {
+                Some(value) => println!("{}", vm::pretty(&value)),
+                None => println!("'{}' is not defined", arg),
+            },
+            "l" | "list" => self.list(pause.at, pause.at.line.saturating_sub(5), pause.at.line + 5),
+            "h" | "help" => println!("{}", HELP),
+            other => println!(
+                "unknown command '{}', type 'help' to see the commands",
+                other
+            ),
+        }
+
+        None
+    }
+}
+
+/// The value of the local `name` of the function that stopped
+fn local(pause: &Pause<'_>, name: &str) -> Option<Value> {
+    let (slot, _) = pause
+        .names
+        .iter()
+        .rev()
+        .find(|(_, it)| it.as_str() == name)?;
+    pause.locals.get(*slot).cloned()
+}
+
+impl debug::Debugger for Debugger {
+    fn breaks(&self, at: Location) -> bool {
+        self.breakpoints.contains(&at)
+    }
+
+    fn pause(&mut self, vm: &VirtualMachine, pause: &Pause<'_>) -> Step {
+        if pause.breakpoint {
+            println!("breakpoint at {}", self.show(pause.at));
+        }
+        self.list(pause.at, pause.at.line, pause.at.line);
+
+        let stdin = io::stdin();
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().ok();
+
+            let mut command = String::new();
+            // the program stops once the commands end
+            if stdin.lock().read_line(&mut command).unwrap_or(0) == 0 {
+                println!();
+                exit(0);
+            }
+
+            let command = match command.trim() {
+                "" => self.last.clone(),
+                command => command.to_string(),
+            };
+            self.last = command.clone();
+
+            if let Some(step) = self.run(vm, pause, &command) {
+                return step;
+            }
+        }
+    }
+}
@@ -1,101 +1,432 @@
-use rustyline::Editor;
-use std::{env::args, fs::{self, File}, process::exit};
-use vm::{OpCode, OpCodeMetadata, VirtualMachine};
+mod coverage;
+mod debugger;
+mod profile;
+mod repl;
+mod report;
+mod runner;
 
-fn eval_file(file: &str) {
-    let file = match fs::read_to_string(file) {
-        Ok(file) => file,
+use coverage::CoverageReport;
+use debugger::Debugger;
+use repl::Repl;
+use report::ErrorFormat;
+use std::{
+    env::args,
+    fs::{self, File},
+    path::{Path, PathBuf},
+    process::exit,
+};
+use vm::{Trace, VirtualMachine};
+
+/// Whether the nondeterministic inputs are recorded to, or replayed from, a trace file
+enum TraceMode {
+    Record(String),
+    Replay(String),
+}
+
+fn open_trace(mode: &TraceMode) -> Trace {
+    let res = match mode {
+        TraceMode::Record(path) => File::create(path)
+            .map(Trace::record)
+            .map_err(|e| e.to_string()),
+        TraceMode::Replay(path) => fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|trace| Trace::replay(&trace).map_err(|e| e.to_string())),
+    };
+
+    match res {
+        Ok(trace) => trace,
+        Err(e) => {
+            eprintln!("error opening the trace: {}", e);
+            exit(1);
+        }
+    }
+}
+
+/// Reads a program, which may be a source file or a file compiled with `--compile`, along with its
+/// source, which compiled files don't have
+fn load_program(file: &str) -> (vm::Bytecode, Vec<vm::Value>, Option<String>) {
+    let bytes = match fs::read(file) {
+        Ok(bytes) => bytes,
         Err(..) => {
             eprintln!("error reading {}", file);
             exit(1);
         }
     };
 
-    let (bt, ct) = match front::parse(file) {
-        Ok(res) => res,
-        Err(e) => {
-            eprintln!("{}", e);
+    if vm::is_compiled(&bytes) {
+        let (bt, ct) = vm::deserialize(&bytes).unwrap_or_else(|e| {
+            eprintln!("error loading {}: {}", file, e);
+            exit(1);
+        });
+        return (bt, ct, None);
+    }
+
+    let source = match String::from_utf8(bytes) {
+        Ok(source) => source,
+        Err(..) => {
+            eprintln!("error reading {}: invalid utf-8", file);
+            exit(1);
+        }
+    };
+
+    match front::compile(source.as_str()) {
+        Ok((bt, ct, diagnostics)) => {
+            for diagnostic in diagnostics {
+                report::diagnostic(file, &source, &diagnostic);
+            }
+            (bt, ct, Some(source))
+        }
+        Err(errors) => {
+            for e in errors {
+                report::parse_error(file, &source, &e);
+            }
             exit(1);
         }
+    }
+}
+
+/// The file of a module, `Foo` is the file `foo.yex` in `dir`
+fn module_file(dir: &Path, name: vm::Symbol) -> PathBuf {
+    let mut chars = name.as_str().chars();
+    let file = match chars.next() {
+        Some(c) => format!("{}{}.yex", c.to_lowercase(), chars.as_str()),
+        None => String::from(".yex"),
     };
+    dir.join(file)
+}
+
+/// The name of a function in the backtraces and the profiles, the anonymous functions are `<fn>`,
+/// the code of a module outside its functions is `<Module>` and the code of the program `<main>`
+fn function_name(name: Option<vm::Symbol>, top_level: bool, module: Option<vm::Symbol>) -> String {
+    match (name, module) {
+        (Some(name), _) => name.to_string(),
+        (None, _) if !top_level => String::from("<fn>"),
+        (None, Some(module)) => format!("<{}>", module),
+        (None, None) => String::from("<main>"),
+    }
+}
+
+/// Creates the loader used by `import`, `import Foo` reads the file `foo.yex` from `dir`
+fn module_loader(dir: PathBuf) -> vm::ModuleLoader {
+    Box::new(move |name| {
+        let file = module_file(&dir, name);
+
+        let source = fs::read_to_string(&file).map_err(|_| {
+            vm::raise_err!(
+                ImportError,
+                "Can't find module '{}', expected it at '{}'",
+                name,
+                file.display()
+            )
+        })?;
+
+        match front::parse_with_diagnostics(source.as_str()) {
+            Ok((bt, ct, diagnostics)) => {
+                let file = file.display().to_string();
+                for diagnostic in diagnostics {
+                    report::diagnostic(&file, &source, &diagnostic);
+                }
+                Ok((bt, ct))
+            }
+            Err(e) => Err(vm::raise_err!(
+                ImportError,
+                "Error compiling module '{}': {}",
+                name,
+                e
+            )),
+        }
+    })
+}
+
+/// The directory of a file, where the modules it imports are looked for
+fn file_dir(file: &str) -> PathBuf {
+    match Path::new(file).parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+fn eval_file(
+    file: &str,
+    trace: Option<&TraceMode>,
+    script_args: &[String],
+    coverage: &mut Option<CoverageReport>,
+    profile: bool,
+) {
+    let (bt, ct, source) = load_program(file);
 
     let mut vm = VirtualMachine::default();
+    vm.set_loader(module_loader(file_dir(file)));
+    vm.set_args(script_args.to_vec());
+    if let Some(mode) = trace {
+        vm.set_trace(open_trace(mode));
+    }
+    if coverage.is_some() {
+        vm.enable_coverage();
+    }
+    if profile {
+        vm.enable_profiling(profile::INTERVAL);
+    }
 
     vm.set_consts(ct);
-    if let Err(e) = vm.run(&bt) {
-        eprintln!("{}", e);
+    let res = vm.run(&bt);
+    if let (Some(coverage), Some(covered)) = (coverage.as_mut(), vm.coverage()) {
+        coverage.add(file, covered);
+    }
+    if let Some(samples) = vm.profile() {
+        profile::print(file, samples);
+    }
+
+    if let Err(e) = res {
+        report::runtime_error(file, source.as_deref(), &e);
+        // the report still shows what ran before the error
+        if let Some(coverage) = coverage {
+            coverage.print();
+        }
         exit(1);
     }
 }
 
-fn patch_bytecode(ops: &mut [OpCodeMetadata], old_len: usize) {
-    for op in ops.iter_mut() {
-        if let OpCode::Push(idx) = &mut op.opcode {
-            *idx += old_len;
+/// Runs a file with the debugger attached, which stops before the first line
+fn debug_file(file: &str, script_args: &[String]) -> i32 {
+    let (bt, ct, source) = load_program(file);
+
+    let mut vm = VirtualMachine::default();
+    vm.set_loader(module_loader(file_dir(file)));
+    vm.set_args(script_args.to_vec());
+    vm.set_debugger(Debugger::new(file));
+
+    vm.set_consts(ct);
+    match vm.run(&bt) {
+        Ok(()) => 0,
+        Err(e) => {
+            report::runtime_error(file, source.as_deref(), &e);
+            1
         }
     }
 }
 
-fn start(args: Vec<String>) -> i32 {
-    let mut repl = Editor::<()>::new();
+/// Prints the bytecode of a file, without running it
+fn emit_bytecode(file: &str) {
+    let (bt, ct, _) = load_program(file);
+    print!("{}", vm::Disassembler::new(&bt, &ct));
+}
 
-    let path = format!("{}/.yex_history", std::env::var("HOME").unwrap());
-    if repl.load_history(&path).is_err() {
-        File::create(&path).ok();
-        repl.load_history(&path).ok();
-    }
+/// Prints the syntax tree of a source file, without compiling it
+fn emit_ast(file: &str) -> i32 {
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(..) => {
+            eprintln!("error reading {}", file);
+            return 1;
+        }
+    };
 
-    if args.len() > 1 {
-        for args in args.iter().skip(1) {
-            eval_file(args);
+    match front::dump_ast(source.as_str()) {
+        Ok(ast) => {
+            print!("{}", ast);
+            0
+        }
+        Err(e) => {
+            report::parse_error(file, &source, &e);
+            1
         }
-        return 0;
     }
+}
 
-    let mut vm = VirtualMachine::default();
+/// Compiles a source file to a `.yexc` file next to it, which can be executed without parsing it
+fn compile_file(file: &str) -> i32 {
+    let (bt, ct, _) = load_program(file);
+    let out = Path::new(file).with_extension("yexc");
 
-    loop {
-        let line = match repl.readline("yex> ") {
-            Ok(str) => str.trim().to_string(),
-            Err(_) => {
-                repl.save_history(&path).ok();
-                return 0;
-            }
-        };
+    let res = vm::serialize(&bt, &ct)
+        .map_err(|e| e.to_string())
+        .and_then(|bytes| fs::write(&out, bytes).map_err(|e| e.to_string()));
 
-        if line.is_empty() || line.starts_with("//") {
-            continue;
+    match res {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error compiling {}: {}", file, e);
+            1
         }
+    }
+}
 
-        repl.add_history_entry(&line);
+/// Loads a file into an already running vm, used by the repl to load the init file and the
+/// `--preload` files
+fn preload_file(vm: &mut VirtualMachine, file: &str) {
+    let source = match fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(..) => {
+            eprintln!("error reading {}", file);
+            return;
+        }
+    };
+
+    match front::parse(source) {
+        Ok((bt, ct)) => {
+            let bt = vm.load(bt, ct);
+            vm.run(&bt)
+                .unwrap_or_else(|e| eprintln!("error loading {}: {}", file, e));
+        }
+        Err(e) => eprintln!("error loading {}: {}", file, e),
+    }
+
+    vm.reset();
+}
+
+fn start(args: Vec<String>) -> i32 {
+    let home = std::env::var("HOME").unwrap();
+
+    let mut preload = vec![];
+    let mut files = vec![];
+    let mut trace = None;
+    let mut compile = false;
+    let mut coverage = None;
+    let mut profile = false;
+    let mut emit = None;
+    let mut script_args = vec![];
 
-        if line.starts_with("def") || line.starts_with("let") || line.starts_with("type") {
-            match front::parse(line) {
-                Ok((mut bt, ct)) => {
-                    patch_bytecode(&mut bt, vm.constants.len());
-                    vm.constants.extend(ct);
-                    vm.run(&bt).unwrap_or_else(|e| println!("{}", e));
-                    println!("{}", vm.pop_last());
+    let mut args = args.into_iter().skip(1).peekable();
+    // `yex debug file.yex` runs the file in the debugger, and `yex test dir` and `yex bench dir`
+    // run the tests and the benchmarks in the files of `dir`
+    let command = args.next_if(|arg| arg == "debug" || arg == "test" || arg == "bench");
+
+    while let Some(arg) = args.next() {
+        if arg == "--preload" || arg == "--record" || arg == "--replay" {
+            let file = match args.next() {
+                Some(file) => file,
+                None => {
+                    eprintln!("expected a file after '{}'", arg);
+                    return 1;
                 }
-                Err(err) => {
-                    eprintln!("{}", err);
+            };
+
+            match arg.as_str() {
+                "--preload" => preload.push(file),
+                "--record" => trace = Some(TraceMode::Record(file)),
+                _ => trace = Some(TraceMode::Replay(file)),
+            }
+        } else if arg == "--compile" {
+            compile = true;
+        } else if arg == "--coverage" {
+            // `--coverage` prints the lines that ran in each file, after running them
+            coverage = Some(CoverageReport::default());
+        } else if arg == "--profile" {
+            // `--profile` prints the functions that ran the most, after running each file
+            profile = true;
+        } else if let Some(kind) = arg.strip_prefix("--emit=") {
+            // `--emit=bytecode` and `--emit=ast` print what the file compiles or parses to, instead
+            // of running it
+            match kind {
+                "bytecode" | "ast" => emit = Some(kind.to_string()),
+                _ => {
+                    eprintln!("can't emit '{}', expected 'bytecode' or 'ast'", kind);
+                    return 1;
                 }
             }
-        } else {
-            match front::parse_expr(line) {
-                Ok((mut bt, ct)) => {
-                    patch_bytecode(&mut bt, vm.constants.len());
-                    vm.constants.extend(ct);
-                    vm.run(&bt).unwrap_or_else(|e| println!("{}", e));
-                    println!("{}", vm.pop_last());
+        } else if let Some(format) = arg.strip_prefix("--error-format=") {
+            // `--error-format=json` prints the errors and warnings as JSON, one object per line
+            match format {
+                "human" => report::set_format(ErrorFormat::Human),
+                "json" => report::set_format(ErrorFormat::Json),
+                _ => {
+                    eprintln!(
+                        "unknown error format '{}', expected 'human' or 'json'",
+                        format
+                    );
+                    return 1;
                 }
-                Err(err) => {
-                    eprintln!("{}", err);
+            }
+        } else if arg == "--" {
+            // everything after `--` is passed to the script, see `Sys.args`
+            script_args.extend(args.by_ref());
+        } else {
+            files.push(arg);
+        }
+    }
+
+    if compile {
+        if files.is_empty() {
+            eprintln!("expected a file to compile");
+            return 1;
+        }
+        return files
+            .iter()
+            .map(|file| compile_file(file))
+            .max()
+            .unwrap_or(0);
+    }
+
+    if let Some(kind) = emit {
+        if files.is_empty() {
+            eprintln!("expected a file to emit");
+            return 1;
+        }
+        return files
+            .iter()
+            .map(|file| match kind.as_str() {
+                "ast" => emit_ast(file),
+                _ => {
+                    emit_bytecode(file);
+                    0
                 }
+            })
+            .max()
+            .unwrap_or(0);
+    }
+
+    if command.as_deref() == Some("test") {
+        return runner::run(&files, coverage);
+    }
+
+    if command.as_deref() == Some("bench") {
+        return runner::bench(&files);
+    }
+
+    if command.as_deref() == Some("debug") {
+        return match files.as_slice() {
+            [file] => debug_file(file, &script_args),
+            _ => {
+                eprintln!("expected a single file to debug");
+                1
             }
+        };
+    }
+
+    if trace.is_some() && files.len() > 1 {
+        eprintln!("'--record' and '--replay' only work with a single file");
+        return 1;
+    }
+
+    if !files.is_empty() {
+        for file in files.iter() {
+            eval_file(file, trace.as_ref(), &script_args, &mut coverage, profile);
         }
-        vm.reset();
+        if let Some(coverage) = coverage {
+            coverage.print();
+        }
+        return 0;
+    }
+
+    let mut vm = VirtualMachine::default();
+    vm.set_loader(module_loader(PathBuf::from(".")));
+    vm.set_args(script_args);
+    if let Some(mode) = &trace {
+        vm.set_trace(open_trace(mode));
+    }
+
+    let init_file = format!("{}/.yexrc", home);
+    if Path::new(&init_file).exists() {
+        preload_file(&mut vm, &init_file);
     }
+
+    for file in preload.iter() {
+        preload_file(&mut vm, file);
+    }
+
+    Repl::new(&home).run(&mut vm);
+    0
 }
 
 fn main() {
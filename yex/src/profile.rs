@@ -0,0 +1,65 @@
+use crate::{file_dir, function_name, module_file};
+use vm::profile::{Function, Profile};
+
+/// How many instructions run between the samples of `--profile`
+pub const INTERVAL: usize = 100;
+
+/// The name of a function and where it's defined, the modules are looked for next to `file`
+fn label(file: &str, function: &Function) -> String {
+    let name = function_name(function.name, function.top_level, function.at.module);
+
+    let source = match function.at.module {
+        Some(module) => module_file(&file_dir(file), module).display().to_string(),
+        None => file.to_string(),
+    };
+    format!("{} ({}:{})", name, source, function.at.line)
+}
+
+fn percent(samples: usize, total: usize) -> f64 {
+    samples as f64 * 100.0 / total.max(1) as f64
+}
+
+/// Prints the flat profile of the samples taken while running `file`, with the functions that
+/// ran the most first, followed by the calls between them
+pub fn print(file: &str, profile: &Profile) {
+    let samples = profile.samples();
+    eprintln!(
+        "\nprofile of {} ({} samples, one every {} instructions):",
+        file,
+        samples,
+        profile.interval()
+    );
+
+    let mut functions = profile.functions().to_vec();
+    functions.sort_by(|a, b| b.own.cmp(&a.own).then(b.total.cmp(&a.total)));
+
+    eprintln!(
+        "\n  {:>7} {:>8} {:>8} {:>8}  function",
+        "own %", "own", "total %", "total"
+    );
+    for f in &functions {
+        eprintln!(
+            "  {:>6.1}% {:>8} {:>7.1}% {:>8}  {}",
+            percent(f.own, samples),
+            f.own,
+            percent(f.total, samples),
+            f.total,
+            label(file, &f.function)
+        );
+    }
+
+    // the functions that took the longest, with the calls inside them, come first
+    functions.sort_by_key(|f| std::cmp::Reverse(f.total));
+    let calls: Vec<_> = profile.calls().collect();
+
+    eprintln!("\ncall graph:");
+    for f in &functions {
+        eprintln!("\n  {}", label(file, &f.function));
+        for (caller, _, n) in calls.iter().filter(|(_, callee, _)| *callee == f.function) {
+            eprintln!("    called by {} {}", label(file, caller), n);
+        }
+        for (_, callee, n) in calls.iter().filter(|(caller, _, _)| *caller == f.function) {
+            eprintln!("    calls {} {}", label(file, callee), n);
+        }
+    }
+}
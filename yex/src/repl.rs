@@ -0,0 +1,99 @@
+use crate::report::color;
+use rustyline::Editor;
+use std::fs::File;
+use vm::VirtualMachine;
+
+/// The interactive mode, every input runs on the same vm so the globals defined by one input can
+/// be used by the next ones
+pub struct Repl {
+    editor: Editor<()>,
+    history: String,
+}
+
+impl Repl {
+    /// Creates the repl, loading the history from `~/.yex_history`
+    pub fn new(home: &str) -> Self {
+        let mut editor = Editor::<()>::new();
+
+        let history = format!("{}/.yex_history", home);
+        if editor.load_history(&history).is_err() {
+            File::create(&history).ok();
+            editor.load_history(&history).ok();
+        }
+
+        Self { editor, history }
+    }
+
+    /// Reads an input, if the first line is incomplete (like `def f x =`) the next lines are read
+    /// until an empty one
+    fn read(&mut self, vm: &mut VirtualMachine) -> Option<(vm::Bytecode, bool)> {
+        loop {
+            let mut input = self.editor.readline("yex> ").ok()?;
+            if input.trim().is_empty() || input.trim().starts_with("//") {
+                continue;
+            }
+
+            // `:help name` shows the documentation of a global, like `help "name"`
+            if let Some(name) = input.trim().strip_prefix(":help") {
+                self.editor.add_history_entry(input.as_str());
+                match vm.help(&name.trim().to_string().into()) {
+                    Ok(text) => println!("{}", text),
+                    Err(e) => println!("{}", e),
+                }
+                continue;
+            }
+
+            let mut res = front::compile_into(input.as_str(), &mut vm.constants);
+            if matches!(&res, Err(e) if e.is_incomplete()) {
+                loop {
+                    let line = self.editor.readline("...> ").ok()?;
+                    if line.trim().is_empty() {
+                        break;
+                    }
+                    input.push('\n');
+                    input.push_str(&line);
+                }
+                res = front::compile_into(input.as_str(), &mut vm.constants);
+            }
+
+            self.editor.add_history_entry(input.as_str());
+            match res {
+                Ok((bytecode, diagnostics)) => {
+                    for diagnostic in diagnostics {
+                        eprintln!("{}", diagnostic.render("<repl>", &input, color()));
+                    }
+
+                    let stmt = matches!(
+                        input.split_whitespace().next(),
+                        Some(
+                            "def"
+                                | "let"
+                                | "type"
+                                | "struct"
+                                | "trait"
+                                | "impl"
+                                | "module"
+                                | "import"
+                        )
+                    );
+                    return Some((bytecode, !stmt));
+                }
+                Err(e) => eprintln!("{}", e.render("<repl>", &input, color())),
+            }
+        }
+    }
+
+    /// Runs the repl until the input ends, printing the value of every expression
+    pub fn run(mut self, vm: &mut VirtualMachine) {
+        while let Some((bytecode, expr)) = self.read(vm) {
+            match vm.run(&bytecode) {
+                Ok(()) if expr => println!("{}", vm::pretty(vm.pop_last())),
+                Ok(()) => (),
+                Err(e) => println!("{}", e),
+            }
+            vm.reset();
+        }
+
+        self.editor.save_history(&self.history).ok();
+    }
+}
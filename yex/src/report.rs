@@ -0,0 +1,98 @@
+use front::{Diagnostic, Location, ParseError};
+use serde_json::{json, Value};
+use std::{
+    env,
+    io::{self, IsTerminal},
+    sync::OnceLock,
+};
+use vm::error::InterpretError;
+
+/// How the errors and warnings are printed to stderr
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ErrorFormat {
+    /// With the source line they're about, for people
+    Human,
+    /// As a JSON object per line, for editors and CI
+    Json,
+}
+
+static FORMAT: OnceLock<ErrorFormat> = OnceLock::new();
+
+/// Sets the format of the errors, it can only be set once, before anything is reported
+pub fn set_format(format: ErrorFormat) {
+    FORMAT.set(format).ok();
+}
+
+fn format() -> ErrorFormat {
+    *FORMAT.get().unwrap_or(&ErrorFormat::Human)
+}
+
+/// Checks if the diagnostics should be colored, which they are when stderr is a terminal, unless
+/// `NO_COLOR` is set
+pub fn color() -> bool {
+    io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none()
+}
+
+/// Reports an error or warning found while compiling `source`, which was read from `file`
+pub fn diagnostic(file: &str, source: &str, diagnostic: &Diagnostic) {
+    match format() {
+        ErrorFormat::Human => eprintln!("{}\n", diagnostic.render(file, source, color())),
+        ErrorFormat::Json => {
+            let position = diagnostic.position(source);
+            let span = diagnostic
+                .span
+                .map(|span| json!({ "start": span.start, "end": span.end }));
+
+            emit(json!({
+                "kind": "compile",
+                "severity": diagnostic.severity.to_string(),
+                "code": diagnostic.code,
+                "message": diagnostic.message,
+                "help": diagnostic.help,
+                "file": file,
+                "line": position.line,
+                "column": position.column,
+                "span": span,
+            }));
+        }
+    }
+}
+
+/// Reports a syntax error, see [`diagnostic`]
+pub fn parse_error(file: &str, source: &str, e: &ParseError) {
+    diagnostic(file, source, &Diagnostic::from(*e));
+}
+
+/// Reports an error raised while running `file`, the code is the name of the error, like
+/// `TypeError`, the span is found in the `source` of the file, when it isn't compiled
+pub fn runtime_error(file: &str, source: Option<&str>, e: &InterpretError) {
+    let span = source.map(|source| {
+        let span = front::location_span(
+            source,
+            Location {
+                line: e.line,
+                column: e.column,
+            },
+        );
+        json!({ "start": span.start, "end": span.end })
+    });
+
+    match format() {
+        ErrorFormat::Human => eprintln!("{}", e),
+        ErrorFormat::Json => emit(json!({
+            "kind": "runtime",
+            "severity": "error",
+            "code": e.err.as_str(),
+            "message": e.msg,
+            "builtin": e.builtin.map(|builtin| builtin.as_str().to_string()),
+            "file": file,
+            "line": e.line,
+            "column": e.column,
+            "span": span,
+        })),
+    }
+}
+
+fn emit(report: Value) {
+    eprintln!("{}", report);
+}
@@ -0,0 +1,264 @@
+use crate::{coverage::CoverageReport, file_dir, module_loader, report};
+use front::{Block, BlockKind};
+use std::{
+    fs,
+    path::Path,
+    time::{Duration, Instant},
+};
+use vm::{error::InterpretError, Bytecode, Value, VirtualMachine};
+
+/// How long a benchmark runs before it's measured, so the caches are warm
+const WARMUP: Duration = Duration::from_millis(100);
+/// How long a benchmark is measured for, at least
+const MEASURE: Duration = Duration::from_secs(1);
+
+/// A test that didn't pass, with the error that made it fail
+struct Failure {
+    name: String,
+    error: InterpretError,
+}
+
+/// A compiled file, with its `test` or `bench` blocks
+struct Program {
+    file: String,
+    bytecode: Bytecode,
+    constants: Vec<Value>,
+    blocks: Vec<Block>,
+}
+
+impl Program {
+    /// Creates a vm with the globals of the file, ready to run its blocks
+    fn vm(&self, coverage: bool) -> Result<VirtualMachine, InterpretError> {
+        let mut vm = VirtualMachine::default();
+        vm.set_loader(module_loader(file_dir(&self.file)));
+        vm.set_consts(self.constants.clone());
+        if coverage {
+            vm.enable_coverage();
+        }
+
+        vm.run(&self.bytecode)?;
+        vm.reset();
+        Ok(vm)
+    }
+}
+
+/// Finds the source files in `path`, searching the directories recursively
+fn discover(path: &Path, files: &mut Vec<String>) {
+    if !path.is_dir() {
+        files.push(path.display().to_string());
+        return;
+    }
+
+    let mut entries: Vec<_> = match fs::read_dir(path) {
+        Ok(entries) => entries.flatten().map(|entry| entry.path()).collect(),
+        Err(..) => return eprintln!("error reading {}", path.display()),
+    };
+    entries.sort();
+
+    for entry in entries {
+        if entry.is_dir() || entry.extension().is_some_and(|ext| ext == "yex") {
+            discover(&entry, files);
+        }
+    }
+}
+
+/// Compiles the files in `paths` (the current directory if there's none), returning the ones with
+/// blocks of the given kind and the number of files that couldn't be compiled
+fn compile(paths: &[String], kind: BlockKind) -> (Vec<Program>, usize) {
+    let mut files = vec![];
+    if paths.is_empty() {
+        discover(Path::new("."), &mut files);
+    }
+    for path in paths {
+        discover(Path::new(path), &mut files);
+    }
+
+    let mut programs = vec![];
+    let mut broken = 0;
+
+    for file in files {
+        let source = match fs::read_to_string(&file) {
+            Ok(source) => source,
+            Err(..) => {
+                eprintln!("error reading {}", file);
+                broken += 1;
+                continue;
+            }
+        };
+
+        let (bytecode, constants, blocks, diagnostics) =
+            match front::compile_blocks(source.as_str(), kind) {
+                Ok(program) => program,
+                Err(errors) => {
+                    for e in errors {
+                        report::parse_error(&file, &source, &e);
+                    }
+                    broken += 1;
+                    continue;
+                }
+            };
+        // the files without blocks aren't reported, they may not even be meant to run
+        if blocks.is_empty() {
+            continue;
+        }
+        for diagnostic in diagnostics {
+            report::diagnostic(&file, &source, &diagnostic);
+        }
+
+        programs.push(Program {
+            file,
+            bytecode,
+            constants,
+            blocks,
+        });
+    }
+
+    (programs, broken)
+}
+
+/// Runs the `test` blocks of the files in `paths` (the current directory if there's none),
+/// returning the exit code, which is 1 if any test failed
+///
+/// Each test runs in its own vm, after the statements of its file, so the tests can't see what
+/// the others changed. With a coverage report, the lines that ran in all of them are printed
+/// before the result
+pub fn run(paths: &[String], mut coverage: Option<CoverageReport>) -> i32 {
+    let (programs, broken) = compile(paths, BlockKind::Test);
+
+    let mut passed = 0;
+    let mut failures = vec![];
+
+    for program in programs {
+        for test in &program.blocks {
+            let name = format!("{} > {}", program.file, test.name);
+
+            let res = program.vm(coverage.is_some()).and_then(|mut vm| {
+                let res = vm.run(&test.bytecode);
+                if let (Some(coverage), Some(covered)) = (coverage.as_mut(), vm.coverage()) {
+                    coverage.add(&program.file, covered);
+                }
+                res
+            });
+            match res {
+                Ok(()) => {
+                    println!("test {} ... ok", name);
+                    passed += 1;
+                }
+                Err(error) => {
+                    println!("test {} ... FAILED", name);
+                    failures.push(Failure { name, error });
+                }
+            }
+        }
+    }
+
+    if !failures.is_empty() {
+        println!("\nfailures:");
+        for failure in &failures {
+            println!("\n---- {} ----\n{}", failure.name, failure.error);
+        }
+    }
+
+    if let Some(coverage) = coverage {
+        coverage.print();
+    }
+
+    let status = if failures.is_empty() && broken == 0 {
+        "ok"
+    } else {
+        "FAILED"
+    };
+    print!(
+        "\ntest result: {}. {} passed; {} failed",
+        status,
+        passed,
+        failures.len()
+    );
+    if broken > 0 {
+        print!("; {} files couldn't be compiled", broken);
+    }
+    println!();
+
+    if status == "ok" {
+        0
+    } else {
+        1
+    }
+}
+
+/// Runs `bytecode` `iterations` times, returning how long it took
+fn measure(
+    vm: &mut VirtualMachine,
+    bytecode: &Bytecode,
+    iterations: u64,
+) -> Result<Duration, InterpretError> {
+    let start = Instant::now();
+    for _ in 0..iterations {
+        vm.run(bytecode)?;
+        vm.reset();
+    }
+    Ok(start.elapsed())
+}
+
+/// Runs a benchmark, returning the nanoseconds per run and the number of runs measured
+///
+/// The number of runs grows until they take at least [`MEASURE`], so the fast benchmarks run
+/// enough times to be measured and the slow ones don't take forever
+fn bench_block(vm: &mut VirtualMachine, bytecode: &Bytecode) -> Result<(f64, u64), InterpretError> {
+    let start = Instant::now();
+    while start.elapsed() < WARMUP {
+        measure(vm, bytecode, 1)?;
+    }
+
+    let mut iterations = 1;
+    loop {
+        let elapsed = measure(vm, bytecode, iterations)?;
+        if elapsed >= MEASURE {
+            return Ok((elapsed.as_nanos() as f64 / iterations as f64, iterations));
+        }
+
+        // predicts the runs needed from the last batch, growing at most 100 times at once
+        let per_run = elapsed.as_nanos().max(1) as f64 / iterations as f64;
+        let needed = (MEASURE.as_nanos() as f64 * 1.2 / per_run) as u64;
+        iterations = needed.clamp(iterations + 1, iterations * 100);
+    }
+}
+
+/// Runs the `bench` blocks of the files in `paths` (the current directory if there's none),
+/// printing how long each run of them takes and returning the exit code, which is 1 if any
+/// benchmark raised an error
+///
+/// Each benchmark runs in its own vm, after the statements of its file, like the tests
+pub fn bench(paths: &[String]) -> i32 {
+    let (programs, broken) = compile(paths, BlockKind::Bench);
+    let mut failed = 0;
+
+    for program in programs {
+        for bench in &program.blocks {
+            let name = format!("{} > {}", program.file, bench.name);
+
+            let res = program
+                .vm(false)
+                .and_then(|mut vm| bench_block(&mut vm, &bench.bytecode));
+            match res {
+                Ok((ns, iterations)) => {
+                    println!("bench {} ... {:.1} ns/op ({} runs)", name, ns, iterations)
+                }
+                Err(error) => {
+                    println!("bench {} ... FAILED\n{}", name, error);
+                    failed += 1;
+                }
+            }
+        }
+    }
+
+    if broken > 0 {
+        println!("\n{} files couldn't be compiled", broken);
+    }
+
+    if failed == 0 && broken == 0 {
+        0
+    } else {
+        1
+    }
+}